@@ -0,0 +1,395 @@
+//! Comparisons of the runtimes of this crate with plain Rust baselines.
+//!
+//! Each workload is implemented with the runtimes of this crate and with baselines using the
+//! standard library only (sequential code, or scoped threads and channels) and rayon's parallel
+//! iterators, on a thread pool with the same number of workers.  Each implementation is run
+//! several times, and the report gives the median time of each implementation along with
+//! its overhead relative to the fastest baseline of the workload, e.g.:
+//!
+//! ```text
+//! | workload      | implementation          |    median | relative |
+//! |---------------|-------------------------|-----------|----------|
+//! | fork-join sum | std::thread::scope      | 1200.0 us |    1.00x |
+//! | fork-join sum | parallel::single_use    | 1850.0 us |    1.54x |
+//! ```
+//!
+//! Run with `cargo bench --bench comparisons`.  The number of workers defaults to the available
+//! parallelism, and can be set with the `WORKERS` environment variable.
+
+extern crate rayon;
+extern crate rrs;
+
+use std::env;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use rayon::ThreadPool;
+
+use rrs::api::prelude::*;
+use rrs::common::prelude::*;
+
+/// The number of runs of each implementation.
+const RUNS: usize = 7;
+
+/// The number of chunks of the fork-join sum, and the size of each chunk.
+const CHUNKS: u64 = 64;
+const CHUNK_SIZE: u64 = 1 << 16;
+
+/// The number of stages of the pipeline, and the number of items flowing through it.
+const STAGES: usize = 8;
+const ITEMS: usize = 200;
+
+/// The width of the adder and the number of additions of the circuit simulation.
+const BITS: usize = 8;
+const ADDITIONS: usize = 64;
+
+/// A measured implementation of a workload.
+struct Measure {
+    workload: &'static str,
+    implementation: &'static str,
+    baseline: bool,
+    median: Duration,
+}
+
+/// Run `f` `RUNS` times and return the median time.  The result of each run is checked against
+/// `expected`.
+fn measure<T, F>(expected: &T, mut f: F) -> Duration
+where
+    T: PartialEq + std::fmt::Debug,
+    F: FnMut() -> T,
+{
+    let mut times: Vec<_> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let result = f();
+            let elapsed = start.elapsed();
+            assert_eq!(
+                &result, expected,
+                "The implementation computed a wrong result."
+            );
+            elapsed
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn fork_join_sum(workers: usize, pool: &ThreadPool, report: &mut Vec<Measure>) {
+    let data: Vec<u64> = (0..CHUNKS * CHUNK_SIZE).map(|x| x * x % 1000).collect();
+    let chunks: Vec<&[u64]> = data.chunks(CHUNK_SIZE as usize).collect();
+    let expected: u64 = data.iter().sum();
+    let mut add = |implementation, baseline, median| {
+        report.push(Measure {
+            workload: "fork-join sum",
+            implementation,
+            baseline,
+            median,
+        })
+    };
+
+    add(
+        "sequential",
+        true,
+        measure(&expected, || {
+            chunks
+                .iter()
+                .map(|chunk| chunk.iter().sum::<u64>())
+                .sum::<u64>()
+        }),
+    );
+
+    add(
+        "std::thread::scope",
+        true,
+        measure(&expected, || {
+            let per_worker = chunks.len().div_ceil(workers);
+            thread::scope(|s| {
+                let handles: Vec<_> = chunks
+                    .chunks(per_worker)
+                    .map(|group| {
+                        s.spawn(move || {
+                            group
+                                .iter()
+                                .map(|chunk| chunk.iter().sum::<u64>())
+                                .sum::<u64>()
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).sum()
+            })
+        }),
+    );
+
+    add(
+        "rayon",
+        true,
+        measure(&expected, || {
+            pool.install(|| {
+                chunks
+                    .par_iter()
+                    .map(|chunk| chunk.iter().sum::<u64>())
+                    .sum::<u64>()
+            })
+        }),
+    );
+
+    add(
+        "parallel::single_use",
+        false,
+        measure(&expected, || {
+            let mut runtime = rrs::parallel::single_use::Toexec::new();
+            rrs::patterns::par_map::par_map_graph(&mut runtime, workers, &chunks, |chunk| {
+                chunk.iter().sum::<u64>()
+            })
+            .unwrap()
+            .into_iter()
+            .sum()
+        }),
+    );
+}
+
+fn pipeline(workers: usize, pool: &ThreadPool, report: &mut Vec<Measure>) {
+    let expected: Vec<usize> = (0..ITEMS).map(|x| x + STAGES).collect();
+    let mut add = |implementation, baseline, median| {
+        report.push(Measure {
+            workload: "pipeline",
+            implementation,
+            baseline,
+            median,
+        })
+    };
+
+    add(
+        "std::thread + mpsc",
+        true,
+        measure(&expected, || {
+            let (input, mut receiver) = mpsc::channel();
+            thread::scope(|s| {
+                for _ in 0..STAGES {
+                    let (sender, next) = mpsc::channel();
+                    let stage_input = receiver;
+                    s.spawn(move || {
+                        for x in stage_input {
+                            sender.send(x + 1).unwrap();
+                        }
+                    });
+                    receiver = next;
+                }
+                for x in 0..ITEMS {
+                    input.send(x).unwrap();
+                }
+                drop(input);
+                receiver.iter().collect()
+            })
+        }),
+    );
+
+    // The stages are stateless, so that the items can go through all the stages independently.
+    add(
+        "rayon",
+        true,
+        measure(&expected, || {
+            pool.install(|| {
+                (0..ITEMS)
+                    .into_par_iter()
+                    .map(|x| (0..STAGES).fold(x, |x, _| x + 1))
+                    .collect()
+            })
+        }),
+    );
+
+    macro_rules! pipeline_graph {
+        ($runtime:ident, $name:expr) => {
+            add(
+                $name,
+                false,
+                measure(&expected, || {
+                    use rrs::$runtime::multiple_uses::*;
+
+                    let results = Arc::new(Mutex::new(Vec::with_capacity(ITEMS)));
+                    let mut runtime = Toexec::new();
+                    let collected = results.clone();
+                    let root = runtime.build_scope(|b| {
+                        let (sender, receiver) = b.port(None).split();
+                        let mut next = b
+                            .node(TaskNode {
+                                inputs: (receiver.as_data_input(),),
+                                outputs: (),
+                                task: StrictTask::new(move |x: Option<usize>| {
+                                    collected.lock().unwrap().push(x.unwrap())
+                                }),
+                            })
+                            .add_activator();
+                        let mut input = sender;
+                        for _ in 0..STAGES {
+                            let (sender, receiver) = b.port(None).split();
+                            next = b
+                                .node(TaskNode {
+                                    inputs: (receiver.as_data_input(),),
+                                    outputs: (input.with_activator(next),),
+                                    task: StrictTask::new(|x: Option<usize>| (x.map(|x| x + 1),)),
+                                })
+                                .add_activator();
+                            input = sender;
+                        }
+                        input.with_activator(next)
+                    });
+
+                    for x in 0..ITEMS {
+                        root.send_activate(&mut runtime, Some(x));
+                        // Same signature for both runtimes, panicking if the execution fails.
+                        ExecuteSpec::execute(&mut runtime, workers);
+                    }
+                    let results = results.lock().unwrap().clone();
+                    results
+                }),
+            )
+        };
+    }
+
+    pipeline_graph!(sequential, "sequential::multiple_uses");
+    pipeline_graph!(parallel, "parallel::multiple_uses");
+}
+
+/// Simulate the gates of a ripple-carry adder bit by bit.
+fn add_gates(x: u32, y: u32) -> u32 {
+    let (mut sum, mut carry) = (0, false);
+    for i in 0..BITS {
+        let (a, b) = (x >> i & 1 == 1, y >> i & 1 == 1);
+        sum |= ((a ^ b ^ carry) as u32) << i;
+        carry = (a && b) || (carry && (a ^ b));
+    }
+    sum | (carry as u32) << BITS
+}
+
+fn logic_circuit(workers: usize, pool: &ThreadPool, report: &mut Vec<Measure>) {
+    use rrs::components::logic::*;
+    use rrs::parallel::multiple_uses::*;
+
+    let operands: Vec<(u32, u32)> = (0..ADDITIONS as u32)
+        .map(|i| ((i * 37) % 256, (i * 91 + 13) % 256))
+        .collect();
+    let expected: Vec<u32> = operands.iter().map(|&(x, y)| x + y).collect();
+
+    let baseline = measure(&expected, || {
+        operands.iter().map(|&(x, y)| add_gates(x, y)).collect()
+    });
+    report.push(Measure {
+        workload: "logic circuit",
+        implementation: "sequential gates",
+        baseline: true,
+        median: baseline,
+    });
+
+    // The additions are independent, so that rayon simulates them in parallel.
+    let baseline = measure(&expected, || {
+        pool.install(|| operands.par_iter().map(|&(x, y)| add_gates(x, y)).collect())
+    });
+    report.push(Measure {
+        workload: "logic circuit",
+        implementation: "rayon",
+        baseline: true,
+        median: baseline,
+    });
+
+    let graph = measure(&expected, || {
+        let mut runtime = Toexec::new();
+        let (mut sources, sum) = runtime.build_scope(|b| {
+            let (sources, wires): (Vec<_>, Vec<_>) = (0..2 * BITS + 1).map(|_| b.source()).unzip();
+            let (mut sum_wires, sum): (Vec<_>, Vec<_>) = (0..BITS + 1)
+                .map(|_| {
+                    let (sender, receiver) = b.port(None).split();
+                    (
+                        InputHandle::from_edge(sender.as_data_output().map(Some)),
+                        receiver,
+                    )
+                })
+                .unzip();
+            let carry_out = sum_wires.pop().unwrap();
+            let (x, y, carry_in) = b.instantiate(
+                "adder",
+                &RippleCarryAdder::new(BITS),
+                (sum_wires, carry_out),
+            );
+            for (wire, input) in wires.iter().zip(x.into_iter().chain(y)) {
+                wire.connect(input);
+            }
+            wires[2 * BITS].connect(carry_in);
+            (sources, sum)
+        });
+
+        operands
+            .iter()
+            .map(|&(x, y)| {
+                for i in 0..BITS {
+                    sources[i].send_activate_mut(&mut runtime, x >> i & 1 == 1);
+                    sources[BITS + i].send_activate_mut(&mut runtime, y >> i & 1 == 1);
+                }
+                sources[2 * BITS].send_activate_mut(&mut runtime, false);
+                runtime.execute(workers).unwrap();
+                sum.iter()
+                    .enumerate()
+                    .map(|(i, bit)| (bit.recv().unwrap() as u32) << i)
+                    .sum()
+            })
+            .collect()
+    });
+    report.push(Measure {
+        workload: "logic circuit",
+        implementation: "parallel::multiple_uses",
+        baseline: false,
+        median: graph,
+    });
+}
+
+/// Print the report as a Markdown table.
+fn print_report(workers: usize, report: &[Measure]) {
+    println!("{} workers, median of {} runs\n", workers, RUNS);
+    println!(
+        "| {:<13} | {:<25} | {:>11} | {:>8} |",
+        "workload", "implementation", "median", "relative"
+    );
+    println!("|{:-<15}|{:-<27}|{:-<13}|{:-<10}|", "", "", "", "");
+    for measure in report {
+        let fastest = report
+            .iter()
+            .filter(|other| other.workload == measure.workload && other.baseline)
+            .map(|other| other.median)
+            .min()
+            .unwrap();
+        println!(
+            "| {:<13} | {:<25} | {:>8.1} us | {:>7.2}x |",
+            measure.workload,
+            measure.implementation,
+            measure.median.as_secs_f64() * 1e6,
+            measure.median.as_secs_f64() / fastest.as_secs_f64()
+        );
+    }
+}
+
+fn main() {
+    let workers = env::var("WORKERS")
+        .ok()
+        .and_then(|workers| workers.parse().ok())
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(4);
+
+    // `cargo test --benches` runs the benchmark binary with `--bench` omitted; skip the workloads.
+    if !env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(workers)
+        .build()
+        .expect("Failed to create the rayon thread pool.");
+
+    let mut report = Vec::new();
+    fork_join_sum(workers, &pool, &mut report);
+    pipeline(workers, &pool, &mut report);
+    logic_circuit(workers, &pool, &mut report);
+    print_report(workers, &report);
+}
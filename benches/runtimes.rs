@@ -0,0 +1,119 @@
+//! Criterion benchmarks of the parallel runtimes on the graphs of `rrs::bench_support`.
+//!
+//! Each shape of graph is run on the single-use runtime, which builds the graph for each run, and
+//! on the reusable runtime, which builds it once and executes it repeatedly.  The benchmarks are
+//! parameterized by the scheduling configuration (see `rrs::parallel::config`) and by the number
+//! of workers, from 1 to the available parallelism, so that the reports show how each runtime
+//! scales and which configurations suit each shape:
+//!
+//! ```text
+//! cargo bench --bench runtimes
+//! cargo bench --bench runtimes -- multiple_uses/pipeline
+//! cargo bench --bench runtimes -- fork-join/lifo
+//! ```
+
+#[macro_use]
+extern crate criterion;
+extern crate rrs;
+
+use std::thread;
+use std::time::Duration;
+
+use criterion::{BenchmarkId, Criterion};
+
+use rrs::bench_support::BenchDag;
+use rrs::parallel::config::{QueueOrder, RuntimeConfig};
+use rrs::parallel::{multiple_uses, single_use};
+
+/// The graphs to run, along with their names.
+fn workloads() -> Vec<(&'static str, BenchDag)> {
+    vec![
+        ("fork-join", BenchDag::fork_join(256, 2_000)),
+        ("pipeline", BenchDag::pipeline(8, 64, 2_000)),
+        (
+            "irregular-loop",
+            BenchDag::irregular_loop(32, 16, 4_000, 42),
+        ),
+    ]
+}
+
+/// The scheduling configurations to compare, along with their names: the default one, LIFO local
+/// queues, batch stealing, and idle workers either spinning longer or parking right away.
+fn configs() -> Vec<(&'static str, RuntimeConfig)> {
+    vec![
+        ("default", RuntimeConfig::new()),
+        ("lifo", RuntimeConfig::new().queue(QueueOrder::Lifo)),
+        ("steal-8", RuntimeConfig::new().steal_batch(8)),
+        (
+            "lifo-steal-8",
+            RuntimeConfig::new().queue(QueueOrder::Lifo).steal_batch(8),
+        ),
+        ("spin-100", RuntimeConfig::new().spins(100)),
+        (
+            "park-10ms",
+            RuntimeConfig::new()
+                .spins(0)
+                .max_park(Duration::from_millis(10)),
+        ),
+    ]
+}
+
+/// The numbers of workers to run with: the powers of two up to the available parallelism, and
+/// the available parallelism itself.
+fn worker_counts() -> Vec<usize> {
+    let available = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut counts: Vec<usize> = (0..)
+        .map(|i| 1 << i)
+        .take_while(|&count| count < available)
+        .collect();
+    counts.push(available);
+    counts
+}
+
+fn bench_single_use(c: &mut Criterion) {
+    let mut group = c.benchmark_group("single_use");
+    for (name, dag) in workloads() {
+        let expected = dag.expected();
+        for (config_name, config) in configs() {
+            for workers in worker_counts() {
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{}/{}", name, config_name), workers),
+                    &workers,
+                    |bench, &workers| {
+                        bench.iter(|| {
+                            let mut runtime = single_use::Toexec::new();
+                            runtime.set_config(config);
+                            assert_eq!(dag.run_single_use(&mut runtime, workers), expected);
+                        })
+                    },
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+fn bench_multiple_uses(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multiple_uses");
+    for (name, dag) in workloads() {
+        let expected = dag.expected();
+        for (config_name, config) in configs() {
+            for workers in worker_counts() {
+                let mut runtime = multiple_uses::Toexec::new();
+                runtime.set_config(config);
+                let graph = dag.build_multiple_uses(&mut runtime);
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{}/{}", name, config_name), workers),
+                    &workers,
+                    |bench, &workers| {
+                        bench.iter(|| assert_eq!(graph.run(&mut runtime, workers), expected))
+                    },
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_single_use, bench_multiple_uses);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+//! Check that no sequence of builder calls panics.  See `rrs::common::fuzz`.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate rrs;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = rrs::common::fuzz::run_build_script(data);
+});
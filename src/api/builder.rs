@@ -5,8 +5,13 @@
 //! `common::builder` for a simpler API.
 
 use super::port::Port;
+use parallel::quiescence::Quiescence;
+use std::borrow::Cow;
 use std::ops::DerefMut;
 
+/// A human-readable name attached to a node for debugging purposes.
+pub type Label = Cow<'static, str>;
+
 /// A trait for types which can create graphs of nodes.
 ///
 /// Runtime type should implement this trait by specifying the appropriate types.  The `GraphSpec`
@@ -56,6 +61,30 @@ pub trait PortSpec<T> {
     fn port(&self, init: T) -> Self::Port;
 }
 
+/// A type which can be used to create ports buffering multiple values.
+///
+/// This is separate from `PortSpec` since runtimes usually implement `PortSpec<T>` for all data
+/// types `T`, which leaves no room for a dedicated initialization value.
+pub trait BufferedPortSpec<T> {
+    /// The type of buffered ports containing values of type `T`.
+    type Port: Port;
+
+    /// Create a new, empty port holding at most `capacity` values at once.
+    fn port_buffered(&self, capacity: usize) -> Self::Port;
+}
+
+/// A type which can be used to create ports checking the port contract at runtime.
+///
+/// See `common::port::CheckedPort`.  This is meant to be used while debugging graph construction
+/// code, since checked ports are slower than their unchecked counterparts.
+pub trait CheckedPortSpec<T> {
+    /// The type of checked ports containing values of type `T`.
+    type Port: Port;
+
+    /// Create a new, empty port.  `name` is included in the panic message when the port is misused.
+    fn port_checked(&self, name: &str) -> Self::Port;
+}
+
 /// A trait for types which can build nodes.
 ///
 /// A builder represent a node which has been created, but was not fully initialized; typically,
@@ -67,6 +96,23 @@ pub trait NodeBuilder<Spec: GraphSpec>: Sized {
     /// Create a new activator for the underlying node.
     fn add_activator(&mut self) -> Spec::Activator;
 
+    /// Attach a label to the underlying node, for use in debug output and panic messages.
+    ///
+    /// The default implementation ignores the label.
+    fn set_label(&mut self, _label: Label) {}
+
+    /// Pin the underlying node to the worker with index `worker`, for runtimes supporting it.  See
+    /// `parallel::affinity`.
+    ///
+    /// The default implementation ignores the affinity.
+    fn set_affinity(&mut self, _worker: usize) {}
+
+    /// Add the underlying node to the quiescence group `group`, for runtimes supporting it.  See
+    /// `parallel::quiescence`.
+    ///
+    /// The default implementation ignores the group.
+    fn add_to_group(&mut self, _group: &Quiescence) {}
+
     /// Finalize node creation.  This consumes the builder.
     ///
     /// Upon finalization, the builder should make sure the underlying node is ready to be
@@ -20,12 +20,12 @@
 //! take a `scheduler` as argument.  They represent a type which can be used to get the inputs of
 //! an executing task.
 //!
-//! Note that the `InputEdge` interface could allow two-way control flow by notifying a producer
-//! node that a value was read and activating generation of the following value, but this is
-//! currently not implemented: in practice, the `InputEdge` traits are simply wrappers around the
-//! `Receiver` traits.  We use the `InputEdge` traits not only for consistency and symmetry with
-//! the `OutputEdge` traits, but also to allow writing debug properties which can access the
-//! scheduler's data structures.
+//! Note that the `InputEdge` interface allows two-way control flow by notifying a producer node
+//! that a value was read and activating generation of the following value: this is implemented by
+//! the `AckInput` edge from the `common::edge` module.  Otherwise, the `InputEdge` traits are
+//! simply wrappers around the `Receiver` traits.  We use the `InputEdge` traits not only for
+//! consistency and symmetry with the `OutputEdge` traits, but also to allow writing debug
+//! properties which can access the scheduler's data structures.
 
 /// An output edge for a node.  Common trait encompassing both data and control components.
 pub trait OutputEdgeOnce<S> {
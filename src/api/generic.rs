@@ -0,0 +1,101 @@
+//! A stable layer for writing libraries of nodes working with any runtime.
+//!
+//! Code which is generic over the runtime needs long lists of bounds: the runtime must create
+//! activators usable from its scheduler, ports whose senders and receivers implement the right
+//! traits, and edges must carry the right item types.  The traits in this module are aliases for
+//! the common combinations of these bounds, so that third-party libraries can target all the
+//! runtimes of this crate without naming a concrete `Toexec` or `RuntimeLoc`:
+//!
+//! ```rust,ignore
+//! /// Build a node scaling its input by `factor`, and return the edge sending values into it.
+//! pub fn scale<'a, S, Spec, O>(
+//!     b: &mut ScopedGraphBuilder<'a, Spec>,
+//!     factor: i32,
+//!     output: O,
+//! ) -> NodeInput<Spec::Activator, DataSender<Spec, i32>>
+//! where
+//!     O: EdgeFor<S, Option<i32>> + 'a,
+//!     Spec: RuntimeFor<S> + DataPortFor<i32> + NodeSpec<ScaleNode<Spec, O>> + 'a,
+//! {
+//!     let (sender, receiver) = b.port(None).split();
+//!     let activator = b.node(TaskNode { ... }).add_activator();
+//!     sender.with_activator(activator)
+//! }
+//! ```
+//!
+//! Here, `S` is the type of the scheduler passed to the library's tasks: each runtime's
+//! `RuntimeLoc`, which is the runtime itself for the sequential ones.
+//!
+//! # Stability
+//!
+//! The aliases are the stable surface for libraries: each one is implemented for all the types
+//! satisfying its bounds, through a blanket implementation, and its bounds will not change until
+//! the next major version.  In particular, the runtimes of this crate implement:
+//!
+//!  - `RuntimeFor<RuntimeLoc>` and `DataPortFor<T>` for all the runtimes;
+//!  - `ReusableRuntimeFor<RuntimeLoc>` for the runtimes supporting reusable graphs (the
+//!    `multiple_uses` modules).
+//!
+//! Libraries should only depend on these aliases and on the traits they are built from, which are
+//! re-exported by `api::prelude`.
+
+use super::activator::{ActivatorMut, ActivatorOnce};
+use super::builder::{GraphSpec, PortSpec};
+use super::edge::{InputEdgeMut, InputEdgeOnce, OutputEdgeMut, OutputEdgeOnce};
+use super::port::{Port, ReceiverMut, SenderMut};
+
+/// An output edge sending values of type `T` from the nodes executed with the scheduler `S`.
+pub trait EdgeFor<S, T>: OutputEdgeOnce<S, Item = T> {}
+
+impl<S, T, E: OutputEdgeOnce<S, Item = T>> EdgeFor<S, T> for E {}
+
+/// An output edge sending values of type `T` from reusable nodes executed with the scheduler `S`.
+pub trait ReusableEdgeFor<S, T>: EdgeFor<S, T> + OutputEdgeMut<S> {}
+
+impl<S, T, E: OutputEdgeMut<S, Item = T>> ReusableEdgeFor<S, T> for E {}
+
+/// An input edge receiving values of type `T` in the nodes executed with the scheduler `S`.
+pub trait InputFor<S, T>: InputEdgeOnce<S, Item = T> {}
+
+impl<S, T, I: InputEdgeOnce<S, Item = T>> InputFor<S, T> for I {}
+
+/// An input edge receiving values of type `T` in reusable nodes executed with the scheduler `S`.
+pub trait ReusableInputFor<S, T>: InputFor<S, T> + InputEdgeMut<S> {}
+
+impl<S, T, I: InputEdgeMut<S, Item = T>> ReusableInputFor<S, T> for I {}
+
+/// A runtime building graphs whose nodes are executed with the scheduler `S`, i.e. whose
+/// activators can be activated by the tasks of its nodes.
+pub trait RuntimeFor<S>: GraphSpec<Activator: ActivatorOnce<S>> {}
+
+impl<S, Spec: GraphSpec<Activator: ActivatorOnce<S>>> RuntimeFor<S> for Spec {}
+
+/// A runtime building reusable graphs whose nodes are executed with the scheduler `S`, i.e. whose
+/// activators can be activated several times.
+pub trait ReusableRuntimeFor<S>: RuntimeFor<S> + GraphSpec<Activator: ActivatorMut<S>> {}
+
+impl<S, Spec: GraphSpec<Activator: ActivatorMut<S>>> ReusableRuntimeFor<S> for Spec {}
+
+/// A runtime creating data ports for values of type `T`, i.e. ports holding an `Option<T>` whose
+/// sender and receiver can be used repeatedly.  Ports are created with `port(None)`.
+pub trait DataPortFor<T>:
+    PortSpec<
+    Option<T>,
+    Port: Port<Sender: SenderMut<Item = Option<T>>, Receiver: ReceiverMut<Item = Option<T>>>,
+>
+{
+}
+
+impl<T, Spec> DataPortFor<T> for Spec where
+    Spec: PortSpec<
+        Option<T>,
+        Port: Port<Sender: SenderMut<Item = Option<T>>, Receiver: ReceiverMut<Item = Option<T>>>,
+    >
+{
+}
+
+/// The sending part of the data ports of type `T` of the runtime `Spec`.
+pub type DataSender<Spec, T> = <<Spec as PortSpec<Option<T>>>::Port as Port>::Sender;
+
+/// The receiving part of the data ports of type `T` of the runtime `Spec`.
+pub type DataReceiver<Spec, T> = <<Spec as PortSpec<Option<T>>>::Port as Port>::Receiver;
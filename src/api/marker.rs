@@ -8,17 +8,24 @@
 /// is to forget to wrap values in a single element tuple: the `Tuple` trait serves as a marker
 /// trait to indicate that a value *must* be a tuple, and allows to give an error message
 /// indicating that a tuple argument was expected.
-pub trait Tuple {}
+pub trait Tuple {
+    /// The number of elements of the tuple.
+    const ARITY: usize;
+}
 
 // Recursively implement the `Tuple` trait for tuples up to 10 elements. If needed, you can
 // implement the trait for larger tuples by adding extra types in the `auto_impl_tuple!` call
 // below.
 macro_rules! auto_impl_tuple {
     (impl<>) => {
-        impl Tuple for () {}
+        impl Tuple for () {
+            const ARITY: usize = 0;
+        }
     };
     (impl<$T:ident $(, $Ts:ident)*>) => {
-        impl<$T, $($Ts),*> Tuple for ($T, $($Ts),*) {}
+        impl<$T, $($Ts),*> Tuple for ($T, $($Ts),*) {
+            const ARITY: usize = 1 + <($($Ts,)*) as Tuple>::ARITY;
+        }
 
         auto_impl_tuple!{
             impl<$($Ts),*>
@@ -27,3 +34,32 @@ macro_rules! auto_impl_tuple {
 }
 
 auto_impl_tuple!(impl<T0, T1, T2, T3, T4, T5, T6, T7, T8, T9>);
+
+/// A marker trait for tuples with as many elements as the tuple `T`, whatever their types.
+///
+/// This is used as a bound to check that a node has one edge for each input and output of its
+/// task (see `TaskNode::checked`), so that a mismatch is reported as such instead of as a missing
+/// implementation of the `Task` traits.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not have as many elements as `{T}`",
+    label = "expected one element for each element of `{T}`"
+)]
+pub trait SameArity<T: Tuple>: Tuple {}
+
+// Implement the `SameArity` trait for pairs of tuples of the same length, up to 10 elements.
+macro_rules! auto_impl_same_arity {
+    (impl<> <>) => {
+        impl SameArity<()> for () {}
+    };
+    (impl<$A:ident $(, $As:ident)*> <$B:ident $(, $Bs:ident)*>) => {
+        impl<$A, $($As,)* $B, $($Bs),*> SameArity<($B, $($Bs,)*)> for ($A, $($As,)*) {}
+
+        auto_impl_same_arity!{
+            impl<$($As),*> <$($Bs),*>
+        }
+    };
+}
+
+auto_impl_same_arity!(
+    impl<A0, A1, A2, A3, A4, A5, A6, A7, A8, A9> <B0, B1, B2, B3, B4, B5, B6, B7, B8, B9>
+);
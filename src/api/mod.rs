@@ -6,6 +6,7 @@
 pub mod activator;
 pub mod builder;
 pub mod edge;
+pub mod generic;
 pub mod marker;
 pub mod node;
 pub mod port;
@@ -16,6 +17,7 @@ pub mod prelude {
     pub use super::activator::*;
     pub use super::builder::*;
     pub use super::edge::*;
+    pub use super::generic::*;
     pub use super::marker::*;
     pub use super::node::*;
     pub use super::port::*;
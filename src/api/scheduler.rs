@@ -1,7 +1,73 @@
 //! The scheduling API
 
+use super::edge::OutputEdgeOnce;
+
 pub trait Scheduler {
     type Handle;
 
     fn schedule(&mut self, handle: Self::Handle);
 }
+
+/// A handle which can be used to send values into a graph from outside of its runtime, for
+/// instance from an I/O thread or a callback.
+///
+/// Injecting a value on an edge should have the same effect as sending it with
+/// `send_activate_once` from a task running on the scheduler `S`: the value is written and the
+/// target node is activated.  Injectors are usually cheap to clone.
+pub trait EventInjector<S>: Clone + Send {
+    /// Send `item` on `edge` and activate its target.
+    fn inject_send<E>(&self, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<S> + Send + Sync + 'static,
+        E::Item: Send + Sync + 'static;
+}
+
+/// A trait for schedulers supporting external events.
+pub trait InjectorSpec: Sized {
+    /// The type of injectors pushing events to this scheduler.
+    type Injector: EventInjector<Self>;
+
+    /// Create a new injector for this scheduler.
+    fn injector(&self) -> Self::Injector;
+}
+
+/// A scheduler whose execution can be cancelled before the graph quiesces, for instance by
+/// `execute_until` in the parallel runtimes.
+///
+/// Long-running tasks can poll `is_cancelled` to stop early, since their results would be
+/// discarded anyways.
+pub trait HasCancellation {
+    /// Whether the current execution was cancelled.
+    fn is_cancelled(&self) -> bool;
+}
+
+/// A scheduler executing nodes on a fixed set of workers.
+///
+/// The index of the worker can be used to access per-worker state without contention (see
+/// `common::capability::WorkerLocal`).
+pub trait HasWorkerIndex {
+    /// The index of the worker executing the current node, between 0 and `worker_count() - 1`.
+    fn worker_index(&self) -> usize;
+
+    /// The number of workers of the current execution.
+    fn worker_count(&self) -> usize;
+}
+
+/// Dynamic discovery of the optional features of a scheduler.
+///
+/// Requiring a feature with a `S: HasCancellation` bound makes tasks unusable with the schedulers
+/// which don't support it, and the bounds add up as more features are used.  Instead, tasks and
+/// combinators can require `S: Capabilities` and ask for a feature at runtime, falling back to a
+/// sensible default when the feature is missing.  All the methods return `None` by default, so
+/// that schedulers only need to override the methods for the features they support.
+pub trait Capabilities {
+    /// The scheduler's cancellation state, if it can be cancelled.
+    fn as_cancellation(&self) -> Option<&dyn HasCancellation> {
+        None
+    }
+
+    /// The scheduler's worker index, if it executes nodes on several workers.
+    fn as_worker_index(&self) -> Option<&dyn HasWorkerIndex> {
+        None
+    }
+}
@@ -31,3 +31,37 @@ pub trait TaskMut<I: Tuple, O: Tuple, S> {
 pub trait Task<I: Tuple, O: Tuple, S> {
     fn run(&self, scheduler: &mut S, inputs: I, outputs: O);
 }
+
+/// A trait for tasks which can be run only once and may fail.
+///
+/// Instead of panicking, which would unwind through the scheduler and take down the worker, a
+/// failing task returns its error: the `TryTaskNode` running it then sends the error on its error
+/// edge, which is usually routed to an error-handler node.  Tasks should not send on their outputs
+/// when they fail, since the nodes downstream would then be activated with partial results.
+pub trait TryTaskOnce<I: Tuple, O: Tuple, S> {
+    /// The error returned by the task.
+    type Error;
+
+    fn try_run_once(self, scheduler: &mut S, inputs: I, outputs: O) -> Result<(), Self::Error>;
+}
+
+/// A trait for tasks which can be run multiple times, but not concurrently, and may fail.  See
+/// `TryTaskOnce`.
+pub trait TryTaskMut<I: Tuple, O: Tuple, S> {
+    /// The error returned by the task.
+    type Error;
+
+    fn try_run_mut(&mut self, scheduler: &mut S, inputs: I, outputs: O) -> Result<(), Self::Error>;
+}
+
+/// The inputs and outputs a task expects, for checking them against the edges of a node (see
+/// `TaskNode::checked`).
+///
+/// `Args` is the tuple of the items received by the task, one per input.  It is a parameter
+/// rather than an associated type so that tasks wrapping functions with different numbers of
+/// arguments, e.g. `StrictTask`, can implement the trait for each of them; it is inferred from the
+/// function.
+pub trait TaskArity<Args: Tuple> {
+    /// The tuple of the items sent by the task, one per output.
+    type Outputs: Tuple;
+}
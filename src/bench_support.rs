@@ -0,0 +1,270 @@
+//! Graph generators for benchmarking the runtimes.
+//!
+//! A `BenchDag` is a DAG whose nodes do a given amount of busy work on the values of their inputs.
+//! The same DAG can be executed on the parallel runtimes, once per build on the single-use runtime
+//! and repeatedly on the reusable runtime, and always produces the same result:
+//!
+//! ```rust,ignore
+//! let dag = BenchDag::fork_join(64, 10_000);
+//!
+//! let mut runtime = parallel::single_use::Toexec::new();
+//! assert_eq!(dag.run_single_use(&mut runtime, 8), dag.expected());
+//!
+//! let mut runtime = parallel::multiple_uses::Toexec::new();
+//! let graph = dag.build_multiple_uses(&mut runtime);
+//! for _ in 0..100 {
+//!     assert_eq!(graph.run(&mut runtime, 8), dag.expected());
+//! }
+//! ```
+//!
+//! The generators cover the usual shapes of task graphs: fork-join fan-outs, pipelines, and loops
+//! whose iterations have an irregular amount of parallelism.  Other shapes are described by the
+//! inputs and the work of each node.  The runtime is created by the caller, so that different
+//! configurations of the same runtime can be compared.
+//!
+//! The `runtimes` benchmark runs each shape on both runtimes with Criterion, for several
+//! scheduling configurations and an increasing number of workers:
+//!
+//! ```text
+//! cargo bench --bench runtimes
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering::SeqCst};
+use std::sync::Arc;
+
+use api::prelude::*;
+use common::prelude::*;
+use parallel::{multiple_uses, single_use};
+
+/// Do `work` rounds of busy work, starting from `seed`, and return the result.  Each round is a
+/// few arithmetic operations.
+pub fn spin(seed: u64, work: u64) -> u64 {
+    // The state of a xorshift generator must not be zero.
+    let mut state = seed | 1;
+    for _ in 0..work {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+    }
+    state
+}
+
+/// The value computed by `node` doing `work` rounds of work on the values of its inputs.
+fn evaluate(node: usize, work: u64, inputs: &[u64]) -> u64 {
+    let seed = inputs.iter().fold(node as u64 + 1, |acc, &x| {
+        acc.wrapping_mul(31).wrapping_add(x)
+    });
+    spin(seed, work)
+}
+
+/// Build `dag` on `runtime`, whose type is `Toexec`, and return the activators of its roots along
+/// with the cell receiving its result.  Both parallel runtimes build the graph the same way: a
+/// `JoinNode` gathers the inputs of each node, and a `CloneOutput` sends its value to its
+/// consumers.
+macro_rules! build_dag {
+    ($dag:expr, $runtime:expr, $Toexec:ty) => {{
+        let dag: &BenchDag = $dag;
+        let result = Arc::new(AtomicU64::new(0));
+        let last = dag.len().saturating_sub(1);
+        let roots = $runtime.build_scope(|b| {
+            // Build from the sink, so that the inputs of the consumers of a node are known when
+            // it is created.
+            let mut outputs: Vec<_> = (0..dag.len())
+                .map(|_| CloneOutput::<JoinInput<$Toexec, u64>>::new())
+                .collect();
+            let mut roots = Vec::new();
+            for node in (0..dag.len()).rev() {
+                let output = outputs.pop().unwrap();
+                let work = dag.work[node];
+                let result = result.clone();
+                let record = move |value: u64| {
+                    if node == last {
+                        result.store(value, SeqCst);
+                    }
+                    (value,)
+                };
+                let inputs = &dag.inputs[node];
+                if inputs.is_empty() {
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (),
+                            outputs: (output,),
+                            task: StrictTask::new(move || record(evaluate(node, work, &[]))),
+                        })
+                        .add_activator();
+                    roots.push(activator);
+                    continue;
+                }
+                let (sender, receiver) = b.port(None).split();
+                let activator = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (output,),
+                        task: StrictTask::new(move |values: Option<Vec<u64>>| {
+                            record(evaluate(node, work, &values.unwrap_or_default()))
+                        }),
+                    })
+                    .add_activator();
+                let joined = sender
+                    .with_activator(activator)
+                    .map(Some as fn(Vec<u64>) -> Option<Vec<u64>>);
+                for (&producer, input) in inputs.iter().zip(b.join(inputs.len(), joined)) {
+                    outputs[producer].connect(input);
+                }
+            }
+            roots
+        });
+        (roots, result)
+    }};
+}
+
+/// A DAG of nodes doing busy work.  Node `i` only reads from nodes with a smaller index, so that
+/// the nodes are in topological order; the result of the DAG is the value of its last node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchDag {
+    /// The producers of the inputs of each node, in input order.  Nodes without inputs are the
+    /// roots of the DAG.
+    pub inputs: Vec<Vec<usize>>,
+    /// The number of rounds of busy work done by each node (see `spin`).
+    pub work: Vec<u64>,
+}
+
+impl BenchDag {
+    /// A root fanning out to `width` nodes doing `work` rounds of work each, whose values are
+    /// joined by the last node.
+    pub fn fork_join(width: usize, work: u64) -> Self {
+        let mut inputs = vec![Vec::new()];
+        inputs.extend((0..width).map(|_| vec![0]));
+        inputs.push((1..=width).collect());
+        let mut works = vec![work; width + 2];
+        works[0] = 0;
+        works[width + 1] = 0;
+        BenchDag {
+            inputs,
+            work: works,
+        }
+    }
+
+    /// A pipeline of `stages` stages doing `work` rounds of work each, through which `items` items
+    /// flow.  Each stage processes the items in order, so that consecutive items are processed by
+    /// different stages in parallel.
+    pub fn pipeline(stages: usize, items: usize, work: u64) -> Self {
+        let inputs = (0..items)
+            .flat_map(|item| {
+                (0..stages).map(move |stage| {
+                    let mut inputs = Vec::new();
+                    if stage > 0 {
+                        inputs.push(item * stages + stage - 1);
+                    }
+                    if item > 0 {
+                        inputs.push((item - 1) * stages + stage);
+                    }
+                    inputs
+                })
+            })
+            .collect();
+        BenchDag {
+            inputs,
+            work: vec![work; stages * items],
+        }
+    }
+
+    /// A loop of `iterations` iterations, each of which runs from 1 to `max_width` nodes in
+    /// parallel, doing from 1 to `max_work` rounds of work each.  Each iteration starts once the
+    /// previous one is done.  The same seed always generates the same loop.
+    pub fn irregular_loop(iterations: usize, max_width: usize, max_work: u64, seed: u64) -> Self {
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        let mut random = move |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound.max(1) + 1
+        };
+        // Each iteration is a fan-out from the node ending the previous iteration.
+        let mut dag = BenchDag {
+            inputs: vec![Vec::new()],
+            work: vec![0],
+        };
+        for _ in 0..iterations {
+            let start = dag.len() - 1;
+            let width = random(max_width as u64) as usize;
+            for _ in 0..width {
+                dag.inputs.push(vec![start]);
+                dag.work.push(random(max_work));
+            }
+            dag.inputs.push((start + 1..start + 1 + width).collect());
+            dag.work.push(0);
+        }
+        dag
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Whether the DAG has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// The total number of rounds of work done by the nodes.
+    pub fn total_work(&self) -> u64 {
+        self.work.iter().sum()
+    }
+
+    /// The value computed by `node` from the values of its inputs, in input order.
+    pub fn evaluate(&self, node: usize, inputs: &[u64]) -> u64 {
+        evaluate(node, self.work[node], inputs)
+    }
+
+    /// The result of the DAG, computed sequentially.
+    pub fn expected(&self) -> u64 {
+        let mut values: Vec<u64> = Vec::with_capacity(self.len());
+        for (node, inputs) in self.inputs.iter().enumerate() {
+            let inputs: Vec<_> = inputs.iter().map(|&input| values[input]).collect();
+            let value = self.evaluate(node, &inputs);
+            values.push(value);
+        }
+        values.last().cloned().unwrap_or(0)
+    }
+
+    /// Build the DAG on the parallel single-use runtime, execute it with `workers` workers, and
+    /// return its result.
+    pub fn run_single_use(&self, runtime: &mut single_use::Toexec<'static>, workers: usize) -> u64 {
+        let (roots, result) = build_dag!(self, runtime, single_use::Toexec<'static>);
+        for root in roots {
+            root.activate_once(runtime);
+        }
+        if let Err(error) = runtime.execute(workers) {
+            panic!("{}", error)
+        }
+        result.load(SeqCst)
+    }
+
+    /// Build the DAG on the parallel reusable runtime.  See `ReusableDag`.
+    pub fn build_multiple_uses(&self, runtime: &mut multiple_uses::Toexec<'static>) -> ReusableDag {
+        let (roots, result) = build_dag!(self, runtime, multiple_uses::Toexec<'static>);
+        ReusableDag { roots, result }
+    }
+}
+
+/// A `BenchDag` built on the parallel reusable runtime, which can be executed repeatedly.
+#[derive(Debug)]
+pub struct ReusableDag {
+    roots: Vec<multiple_uses::RuntimeActivator<'static>>,
+    result: Arc<AtomicU64>,
+}
+
+impl ReusableDag {
+    /// Execute the DAG with `workers` workers, and return its result.
+    pub fn run(&self, runtime: &mut multiple_uses::Toexec<'static>, workers: usize) -> u64 {
+        for root in &self.roots {
+            root.activate(runtime);
+        }
+        if let Err(error) = runtime.execute(workers) {
+            panic!("{}", error)
+        }
+        self.result.load(SeqCst)
+    }
+}
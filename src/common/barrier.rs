@@ -0,0 +1,79 @@
+//! Start barriers, to hold parts of a graph until an external "go" signal.
+//!
+//! A `StartBarrier` owns an extra activator for each node registered against it.  Since a node is
+//! only scheduled once all of its activators have been activated, registered nodes cannot start
+//! until the barrier is released, even if all their inputs have already been sent.  This allows
+//! fully building and activating a graph ahead of time (for instance, during a warm-up phase) and
+//! triggering it at a precise moment.
+
+use api::prelude::*;
+use common::builder::ScopedNodeBuilder;
+
+/// A barrier holding an extra activator for each registered node.
+#[derive(Debug)]
+pub struct StartBarrier<A> {
+    activators: Vec<A>,
+}
+
+impl<A> StartBarrier<A> {
+    /// Create a new barrier with no registered nodes.
+    pub fn new() -> Self {
+        StartBarrier {
+            activators: Vec::new(),
+        }
+    }
+
+    /// Hold an additional activator until the barrier is released.
+    pub fn add(&mut self, activator: A) {
+        self.activators.push(activator)
+    }
+
+    /// Register a node against the barrier, adding an implicit activation to it which will only be
+    /// released along with the barrier.
+    pub fn register<'a, Spec, B>(&mut self, builder: &mut ScopedNodeBuilder<'a, Spec, B>)
+    where
+        Spec: GraphSpec<Activator = A> + 'a,
+        B: NodeBuilder<Spec>,
+    {
+        self.add(builder.add_activator())
+    }
+
+    /// The number of nodes held by the barrier.
+    pub fn len(&self) -> usize {
+        self.activators.len()
+    }
+
+    /// Whether no nodes are held by the barrier.
+    pub fn is_empty(&self) -> bool {
+        self.activators.is_empty()
+    }
+
+    /// Release the barrier, activating all the registered nodes.  This consumes the barrier.
+    pub fn release<S>(self, scheduler: &mut S)
+    where
+        A: ActivatorOnce<S>,
+    {
+        for activator in self.activators {
+            activator.activate_once(scheduler)
+        }
+    }
+
+    /// Release the barrier for the current execution of reusable nodes.
+    ///
+    /// The activators are kept, and the barrier must be released again before the registered
+    /// nodes can execute another time.
+    pub fn release_mut<S>(&mut self, scheduler: &mut S)
+    where
+        A: ActivatorMut<S>,
+    {
+        for activator in self.activators.iter_mut() {
+            activator.activate_mut(scheduler)
+        }
+    }
+}
+
+impl<A> Default for StartBarrier<A> {
+    fn default() -> Self {
+        StartBarrier::new()
+    }
+}
@@ -0,0 +1,107 @@
+//! Passing values between graphs executed by different runtimes.
+//!
+//! A small high-priority graph and a large batch graph are best run on separate runtimes, so that
+//! the batch graph doesn't delay the high-priority one, but they still need to exchange values.
+//! A `BridgePort` is an output edge of a node of the first runtime which sends its values on an
+//! edge of the second runtime: instead of writing to the target port directly, which would race
+//! with the second runtime's workers, the value is injected into the second runtime (see the
+//! `EventInjector` trait), which writes it and activates the target node on one of its workers.
+//!
+//! ```rust,ignore
+//! let batch_input = batch.build_scope(|b| ...);
+//! let bridge = BridgePort::new(batch.injector_handle(), batch_input);
+//! let root = interactive.build_scope(|b| {
+//!     b.node(TaskNode { inputs: (...), outputs: (bridge,), task: ... });
+//!     ...
+//! });
+//! ```
+//!
+//! Bridges are one-way: values are sent the other way with a second bridge, created with an
+//! injector of the first runtime.  A bridge is `Send` and `Sync` as long as its target edge is,
+//! so that it can be used by the nodes of any runtime.
+//!
+//! Note that the injectors of the parallel runtimes keep their runtime executing while they are
+//! alive, since they may inject new values: an execution of the target runtime only returns once
+//! the bridges into it were dropped, e.g. once the single-use nodes owning them were executed.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use api::prelude::*;
+
+/// An output edge sending its values into another runtime.  See the module documentation.
+///
+/// `J` is an injector of the target runtime, whose scheduler is `T`, and `E` is the target edge.
+/// Reusable bridges need a target edge which can be cloned, since each injection consumes an edge.
+pub struct BridgePort<J, E, T> {
+    injector: J,
+    edge: E,
+    _marker: PhantomData<fn(&mut T)>,
+}
+
+impl<J, E, T> BridgePort<J, E, T>
+where
+    J: EventInjector<T>,
+{
+    /// Create a bridge sending values on `edge` through `injector`.
+    pub fn new(injector: J, edge: E) -> Self {
+        BridgePort {
+            injector,
+            edge,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<J: Clone, E: Clone, T> Clone for BridgePort<J, E, T> {
+    fn clone(&self) -> Self {
+        BridgePort {
+            injector: self.injector.clone(),
+            edge: self.edge.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<J, E: fmt::Debug, T> fmt::Debug for BridgePort<J, E, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BridgePort")
+            .field("edge", &self.edge)
+            .finish()
+    }
+}
+
+impl<S, J, E, T> OutputEdgeOnce<S> for BridgePort<J, E, T>
+where
+    J: EventInjector<T>,
+    E: OutputEdgeOnce<T> + Send + Sync + 'static,
+    E::Item: Send + Sync + 'static,
+{
+    type Item = E::Item;
+
+    fn send_activate_once(self, _scheduler: &mut S, item: Self::Item) {
+        self.injector.inject_send(self.edge, item)
+    }
+}
+
+impl<S, J, E, T> OutputEdgeMut<S> for BridgePort<J, E, T>
+where
+    J: EventInjector<T>,
+    E: OutputEdgeOnce<T> + Clone + Send + Sync + 'static,
+    E::Item: Send + Sync + 'static,
+{
+    fn send_activate_mut(&mut self, _scheduler: &mut S, item: Self::Item) {
+        self.injector.inject_send(self.edge.clone(), item)
+    }
+}
+
+impl<S, J, E, T> OutputEdge<S> for BridgePort<J, E, T>
+where
+    J: EventInjector<T>,
+    E: OutputEdgeOnce<T> + Clone + Send + Sync + 'static,
+    E::Item: Send + Sync + 'static,
+{
+    fn send_activate(&self, _scheduler: &mut S, item: Self::Item) {
+        self.injector.inject_send(self.edge.clone(), item)
+    }
+}
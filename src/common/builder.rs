@@ -1,10 +1,22 @@
 use std::{
     cell::RefCell,
+    error::Error,
+    fmt,
     ops::DerefMut,
     rc::{Rc, Weak},
+    thread,
 };
 
+use api::activator::ActivatorOnce;
 use api::builder::*;
+use api::port::{Port, Receiver, ReceiverOnce, SenderOnce};
+use common::edge::{MapOutput, OutputEdgeExt};
+use common::inspect::{CycleError, Inspector, NodeId};
+use common::interface::OutputSpec;
+use common::node::{JoinNode, LazyNode};
+use common::port::{DataInput, NodeInput, ReceiverExt};
+use parallel::activator::{AnyActivator, MergeActivator};
+use parallel::quiescence::Quiescence;
 
 pub trait GraphSpecExt: GraphSpec {
     /// Create a new scope for creating new nodes.
@@ -16,10 +28,96 @@ pub trait GraphSpecExt: GraphSpec {
     ) -> T {
         build_fn(&mut ScopedGraphBuilder::new(self))
     }
+
+    /// Like `build_scope`, but report the malformed sequences of builder calls as a `BuildError`
+    /// instead of panicking.
+    ///
+    /// The first error is returned once `build_fn` has returned; the nodes are finalized all the
+    /// same, so that the graph should be dropped without being executed.  Errors of node builders
+    /// which outlive the scope (e.g. returned by `build_fn`) are ignored.
+    fn try_build_scope<'a, T>(
+        &'a mut self,
+        build_fn: impl FnOnce(&mut ScopedGraphBuilder<'a, Self>) -> T,
+    ) -> Result<T, BuildError> {
+        let diagnostics = Diagnostics::default();
+        let mut builder = ScopedGraphBuilder::new(self);
+        builder.diagnostics = Some(diagnostics.clone());
+        let result = build_fn(&mut builder);
+        let error = diagnostics.borrow_mut().drain(..).next();
+        match error {
+            Some(error) => Err(error),
+            None => Ok(result),
+        }
+    }
 }
 
 impl<Spec: GraphSpec> GraphSpecExt for Spec {}
 
+/// A malformed sequence of builder calls.
+///
+/// Builders panic with this error, except in the scopes created with
+/// `GraphSpecExt::try_build_scope`, which return it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// An activation cycle without a delay was closed.  See the `inspect` module.
+    Cycle(CycleError),
+    /// `ScopedNodeBuilder::activates` was given a node which was not recorded by the scope's
+    /// inspector.
+    UnknownNode {
+        /// The node declaring the connection.
+        node: NodeId,
+        /// The unknown target.
+        target: NodeId,
+    },
+    /// A node was finalized without any activator, so that it would never be executed.  This is
+    /// only reported by scopes created with `try_build_scope`.  See
+    /// `ScopedNodeBuilder::auto_start`.
+    NoActivator {
+        /// The node in the scope's inspector, if any.
+        node: Option<NodeId>,
+        /// The label of the node, if any.
+        label: Option<Label>,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::Cycle(ref error) => write!(f, "{}", error),
+            BuildError::UnknownNode { node, target } => write!(
+                f,
+                "node {} activates {}, which was not recorded by the scope's inspector",
+                node, target
+            ),
+            BuildError::NoActivator { node, ref label } => {
+                match (label, node) {
+                    (Some(label), _) => write!(f, "node `{}`", label)?,
+                    (None, Some(node)) => write!(f, "node {}", node)?,
+                    (None, None) => write!(f, "a node")?,
+                }
+                write!(
+                    f,
+                    " has no activator and would never run; call `auto_start` or `never_start` \
+                     on its builder"
+                )
+            }
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// The errors reported by the builders of a scope created with `try_build_scope`.
+type Diagnostics = Rc<RefCell<Vec<BuildError>>>;
+
+/// Record `error` in `diagnostics`, or panic with it in scopes without diagnostics.
+fn report(diagnostics: &Option<Diagnostics>, error: BuildError) {
+    match *diagnostics {
+        Some(ref diagnostics) => diagnostics.borrow_mut().push(error),
+        None => panic!("{}", error),
+    }
+}
+
 /// Wraps a node builder with a lifetime marker and automatically finalize the builder when
 /// dropped.
 ///
@@ -35,8 +133,30 @@ impl<Spec: GraphSpec> GraphSpecExt for Spec {}
 pub struct ScopedNodeBuilder<'a, Spec: GraphSpec + 'a, B: NodeBuilder<Spec>> {
     spec: Weak<RefCell<&'a mut Spec>>,
     builder: B,
+    inspected: Option<(Inspector, NodeId)>,
+    /// The sends staged with `preload`, in order.
+    preloaded: Vec<Box<dyn FnOnce() + 'a>>,
+    /// The label of the node, if any, for error messages.
+    label: Option<Label>,
+    /// The number of activators created for the node.
+    activators: usize,
+    /// The activation of the node staged with `auto_start`, if any.
+    start: Option<Start<'a, Spec>>,
+    /// The activations of the scope, which the staged one is moved to once the node is finalized.
+    starts: Weak<Starts<'a, Spec>>,
+    /// Whether the node was declared as never started with `never_start`.
+    never_start: bool,
+    /// Where errors are reported, in scopes created with `try_build_scope`.
+    diagnostics: Option<Diagnostics>,
 }
 
+/// An activation of a node staged until the end of its scope.  See
+/// `ScopedNodeBuilder::auto_start`.
+type Start<'a, Spec> = Box<dyn FnOnce(&mut Spec) + 'a>;
+
+/// The activations staged by the finalized nodes of a scope, in order.
+type Starts<'a, Spec> = RefCell<Vec<Start<'a, Spec>>>;
+
 impl<'a, Spec: GraphSpec + 'a, NB: NodeBuilder<Spec>> ScopedNodeBuilder<'a, Spec, NB> {
     /// Create and return an activator for the underlying node.
     ///
@@ -44,9 +164,79 @@ impl<'a, Spec: GraphSpec + 'a, NB: NodeBuilder<Spec>> ScopedNodeBuilder<'a, Spec
     ///
     /// This may panic if the builder was already finalized.
     pub fn add_activator(&mut self) -> Spec::Activator {
+        if let Some((ref inspector, id)) = self.inspected {
+            inspector.add_activator(id);
+        }
+        self.activators += 1;
         self.builder.add_activator()
     }
 
+    /// Schedule the node once the scope's build function returns, e.g. for a source node without
+    /// inputs.
+    ///
+    /// This creates an activator of the node which is activated once, after all the nodes built in
+    /// the scope are finalized: a node with other activators still waits for them, and a reusable
+    /// node is only started once.
+    ///
+    /// Nodes without activators never run.  In scopes created with `try_build_scope`, finalizing
+    /// one reports a `BuildError::NoActivator` unless it is either started with this method or
+    /// declared with `never_start`; other scopes accept such nodes, as they always did.
+    pub fn auto_start(mut self) -> Self
+    where
+        Spec::Activator: ActivatorOnce<Spec> + 'a,
+    {
+        let activator = self.add_activator();
+        self.start = Some(Box::new(move |spec| activator.activate_once(spec)));
+        self
+    }
+
+    /// Declare that the node has no activators on purpose, so that it is never executed.  See
+    /// `auto_start`.
+    pub fn never_start(mut self) -> Self {
+        self.never_start = true;
+        self
+    }
+
+    /// Create an input of `merge` for the underlying node, which is scheduled as soon as any of
+    /// the group's inputs fires.  See `MergeActivator`.
+    ///
+    /// The first call arms the group with a regular activator of the node, so that the node should
+    /// have no other activators.
+    pub fn add_any_activator(
+        &mut self,
+        merge: &MergeActivator<Spec::Activator>,
+    ) -> AnyActivator<Spec::Activator> {
+        if !merge.is_armed() {
+            merge.arm(self.add_activator());
+        }
+        merge.add_input()
+    }
+
+    /// Stage sending `value` on `sender`, typically to pre-fill a port read by the node.
+    ///
+    /// The value is sent when the builder is dropped, just before the node is finalized: sending
+    /// it right away would race with the workers when building nodes dynamically, since a producer
+    /// could activate the node (and overwrite or consume the value) before it is wired.  Staged
+    /// values are sent in order.
+    pub fn preload<P>(mut self, sender: P, value: P::Item) -> Self
+    where
+        P: SenderOnce + 'a,
+        P::Item: 'a,
+    {
+        self.preloaded.push(Box::new(move || sender.send_once(value)));
+        if let Some((ref inspector, id)) = self.inspected {
+            inspector.add_delay(id);
+        }
+        self
+    }
+
+    /// Pin the node to the worker with index `worker`, so that it is always executed by this
+    /// worker.  See the `parallel::affinity` module; runtimes without workers ignore it.
+    pub fn with_affinity(mut self, worker: usize) -> Self {
+        self.builder.set_affinity(worker);
+        self
+    }
+
     /// Mutably borrows the wrapped node.
     ///
     /// The borrow lasts until the returned value is dropped.  The node cannot be borrowed again
@@ -69,19 +259,123 @@ impl<'a, Spec: GraphSpec + 'a, NB: NodeBuilder<Spec>> ScopedNodeBuilder<'a, Spec
     {
         self.builder.borrow_mut()
     }
+
+    /// Attach a human-readable description to the node, replacing any previous one.
+    ///
+    /// The description is recorded in the scope's inspector, if any, and ignored otherwise.
+    pub fn describe<D: Into<String>>(self, description: D) -> Self {
+        if let Some((ref inspector, id)) = self.inspected {
+            let description = description.into();
+            inspector.update(id, |metadata| metadata.description = Some(description));
+        }
+        self
+    }
+
+    /// Attach a tag to the node.
+    ///
+    /// The tag is recorded in the scope's inspector, if any, and ignored otherwise.
+    pub fn tag<T: Into<String>>(self, tag: T) -> Self {
+        if let Some((ref inspector, id)) = self.inspected {
+            let tag = tag.into();
+            inspector.update(id, |metadata| metadata.tags.push(tag));
+        }
+        self
+    }
+
+    /// Declare that the node holds an activator of `target`, for use in the inspector's graph
+    /// export.  This is ignored if the scope has no inspector.
+    ///
+    /// # Panics
+    ///
+    /// This panics with `BuildError::UnknownNode` if `target` was not recorded by the scope's
+    /// inspector.
+    pub fn activates(self, target: NodeId) -> Self {
+        if let Some((ref inspector, id)) = self.inspected {
+            if target.0 < inspector.len() {
+                inspector.add_edge(id, target);
+            } else {
+                let error = BuildError::UnknownNode { node: id, target };
+                report(&self.diagnostics, error);
+            }
+        }
+        self
+    }
+
+    /// Declare that the node delays its values, as a `DelayNode` does, so that the activation
+    /// cycles through it are accepted.  See the `inspect` module; this is ignored if the scope has
+    /// no inspector.
+    pub fn delayed(self) -> Self {
+        if let Some((ref inspector, id)) = self.inspected {
+            inspector.add_delay(id);
+        }
+        self
+    }
+
+    /// The identifier of the node in the scope's inspector, if any.
+    pub fn id(&self) -> Option<NodeId> {
+        self.inspected.as_ref().map(|&(_, id)| id)
+    }
 }
 
 /// Automatically finalize the node when the builder gets dropped.
 impl<'a, Spec: GraphSpec + 'a, B: NodeBuilder<Spec>> Drop for ScopedNodeBuilder<'a, Spec, B> {
     fn drop(&mut self) {
+        for send in self.preloaded.drain(..) {
+            send()
+        }
+        // Don't panic again while unwinding from a failed build.
+        if !thread::panicking() {
+            if let Some((ref inspector, id)) = self.inspected {
+                if let Err(error) = inspector.check_cycle(id) {
+                    report(&self.diagnostics, BuildError::Cycle(error))
+                }
+            }
+            if let Some(ref diagnostics) = self.diagnostics {
+                if self.activators == 0 && !self.never_start {
+                    diagnostics.borrow_mut().push(BuildError::NoActivator {
+                        node: self.id(),
+                        label: self.label.take(),
+                    })
+                }
+            }
+        }
         if let Some(spec) = self.spec.upgrade() {
-            self.builder.finalize(&mut *spec.borrow_mut())
+            self.builder.finalize(&mut spec.borrow_mut());
+            // The node can only be activated once the builder is dropped, since some runtimes
+            // require the activator to hold the last reference to the node.
+            if let (Some(start), Some(starts)) = (self.start.take(), self.starts.upgrade()) {
+                starts.borrow_mut().push(start)
+            }
         } else {
             eprintln!("Scoped builder was dropped after its scope ended.");
         }
     }
 }
 
+/// A reusable fragment of a graph, which can be instantiated several times with
+/// `ScopedGraphBuilder::instantiate`.
+///
+/// Since the outputs of a node are needed to create it, fragments are built from their outputs to
+/// their inputs, like whole graphs: an instance is given the edges its results should be sent to,
+/// and returns the edges sending values into it (typically a tuple of `NodeInput`s), which can in
+/// turn be used as the outputs of another instance.  For instance, a ripple-carry adder is built
+/// from its most significant bit, the carry input of each full adder being the carry output of the
+/// next less significant one.
+pub trait Subgraph<'a, Spec: GraphSpec + 'a> {
+    /// The edges sending values into an instance of the fragment.
+    type Inputs;
+
+    /// The edges an instance of the fragment sends its results to.
+    type Outputs;
+
+    /// Build an instance of the fragment sending its results to `outputs`, and return its inputs.
+    fn build(
+        &self,
+        builder: &mut ScopedGraphBuilder<'a, Spec>,
+        outputs: Self::Outputs,
+    ) -> Self::Inputs;
+}
+
 /// Wraps a graph builder with a lifetime marker.
 ///
 /// The lifetime marker is used to enforce that no mutable reference to the graph can exist while
@@ -90,12 +384,80 @@ impl<'a, Spec: GraphSpec + 'a, B: NodeBuilder<Spec>> Drop for ScopedNodeBuilder<
 /// being activated before it was finalized, causing a panic due to wrong pending counts.
 pub struct ScopedGraphBuilder<'a, Spec: GraphSpec + 'a> {
     spec: Rc<RefCell<&'a mut Spec>>,
+    /// The activations staged with `ScopedNodeBuilder::auto_start`.
+    starts: Rc<Starts<'a, Spec>>,
+    inspector: Option<Inspector>,
+    /// The path of the current namespace, see `namespace`.
+    namespace: Vec<String>,
+    /// The quiescence groups of the nodes created from now on, see `in_group`.
+    groups: Vec<Quiescence>,
+    /// Where errors are reported, in scopes created with `try_build_scope`.
+    diagnostics: Option<Diagnostics>,
 }
 
 impl<'a, Spec: GraphSpec + 'a> ScopedGraphBuilder<'a, Spec> {
     fn new(spec: &'a mut Spec) -> Self {
         ScopedGraphBuilder {
             spec: Rc::new(RefCell::new(spec)),
+            starts: Rc::new(RefCell::new(Vec::new())),
+            inspector: None,
+            namespace: Vec::new(),
+            groups: Vec::new(),
+            diagnostics: None,
+        }
+    }
+
+    /// Record the nodes created from now on in `inspector`, along with their metadata.
+    pub fn inspect(&mut self, inspector: &Inspector) {
+        self.inspector = Some(inspector.clone());
+    }
+
+    /// Build part of the graph in a nested namespace.
+    ///
+    /// The labels of the nodes and ports created by `build_fn` are prefixed with the path of the
+    /// namespace, e.g. a node labelled `pid` in the `controller` namespace nested in the `motor`
+    /// namespace is labelled `motor/controller/pid`.  Nodes also record their namespace in the
+    /// scope's inspector, if any, which allows querying whole subgraphs with `Inspector::subtree`.
+    pub fn namespace<T, F>(&mut self, name: &str, build_fn: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        self.namespace.push(name.to_string());
+        let result = build_fn(self);
+        self.namespace.pop();
+        result
+    }
+
+    /// Build part of the graph in the quiescence group `group`.
+    ///
+    /// The nodes created by `build_fn`, including the nodes of nested namespaces and fragments, are
+    /// added to `group`, whose callback is called once they have all executed.  Groups can be
+    /// nested: nodes belong to all the enclosing groups.  See the `parallel::quiescence` module.
+    pub fn in_group<T, F>(&mut self, group: &Quiescence, build_fn: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        self.groups.push(group.clone());
+        let result = build_fn(self);
+        self.groups.pop();
+        result
+    }
+
+    /// Build an instance of `fragment` in the namespace `name`, sending its results to `outputs`,
+    /// and return its inputs.  See `Subgraph`.
+    pub fn instantiate<G>(&mut self, name: &str, fragment: &G, outputs: G::Outputs) -> G::Inputs
+    where
+        G: Subgraph<'a, Spec>,
+    {
+        self.namespace(name, |b| fragment.build(b, outputs))
+    }
+
+    /// Prefix `label` with the path of the current namespace.
+    fn qualify(&self, label: Label) -> Label {
+        if self.namespace.is_empty() {
+            label
+        } else {
+            Label::Owned(format!("{}/{}", self.namespace.join("/"), label))
         }
     }
 
@@ -104,12 +466,76 @@ impl<'a, Spec: GraphSpec + 'a> ScopedGraphBuilder<'a, Spec> {
     where
         Spec: NodeSpec<N>,
     {
+        let inspected = self.inspector.as_ref().map(|inspector| {
+            let id = inspector.add_node();
+            if !self.namespace.is_empty() {
+                let namespace = self.namespace.join("/");
+                inspector.update(id, |metadata| metadata.namespace = Some(namespace));
+            }
+            (inspector.clone(), id)
+        });
+
+        let mut builder = self.spec.borrow_mut().node(node);
+        for group in &self.groups {
+            builder.add_to_group(group);
+        }
+
         ScopedNodeBuilder {
-            builder: self.spec.borrow_mut().node(node),
+            builder,
             spec: Rc::downgrade(&self.spec),
+            inspected,
+            preloaded: Vec::new(),
+            label: None,
+            activators: 0,
+            start: None,
+            starts: Rc::downgrade(&self.starts),
+            never_start: false,
+            diagnostics: self.diagnostics.clone(),
         }
     }
 
+    /// Create a new builder for a node built by `constructor` on its first execution.  See
+    /// `LazyNode`.
+    ///
+    /// Activators are created through the returned builder as usual: only the node itself is
+    /// built lazily, which is mostly useful when the node owns large data or subgraphs.
+    pub fn lazy_node<F, N>(
+        &mut self,
+        constructor: F,
+    ) -> ScopedNodeBuilder<'a, Spec, <Spec as NodeSpec<LazyNode<F, N>>>::Builder>
+    where
+        F: FnOnce() -> N + 'a,
+        N: 'a,
+        Spec: NodeSpec<LazyNode<F, N>>,
+    {
+        self.node(LazyNode::new(constructor))
+    }
+
+    /// Create a new builder from a node, attaching a label to the node.
+    ///
+    /// The label is prefixed with the current namespace.  It is shown in the `Debug` output of the
+    /// node's activators and in the panic messages of runtimes supporting labels, and recorded in
+    /// the scope's inspector, if any.
+    pub fn node_named<L, N: 'a>(
+        &mut self,
+        label: L,
+        node: N,
+    ) -> ScopedNodeBuilder<'a, Spec, Spec::Builder>
+    where
+        L: Into<Label>,
+        Spec: NodeSpec<N>,
+    {
+        let label = self.qualify(label.into());
+        let mut builder = self.node(node);
+        if let Some((ref inspector, id)) = builder.inspected {
+            let label = label.to_string();
+            inspector.update(id, |metadata| metadata.label = Some(label));
+        }
+        builder.label = Some(label.clone());
+        builder.builder.set_label(label);
+        builder
+    }
+
     /// Create a new port with an initial value.
     pub fn port<T>(&self, init: T) -> Spec::Port
     where
@@ -118,17 +544,104 @@ impl<'a, Spec: GraphSpec + 'a> ScopedGraphBuilder<'a, Spec> {
         self.spec.borrow().port(init)
     }
 
+    /// Create a new, empty port buffering at most `capacity` values in FIFO order.
+    pub fn port_buffered<T>(&self, capacity: usize) -> <Spec as BufferedPortSpec<T>>::Port
+    where
+        Spec: BufferedPortSpec<T>,
+    {
+        self.spec.borrow().port_buffered(capacity)
+    }
+
+    /// Create a new, empty port panicking with `name` when it is written to while full or read from
+    /// while empty.
+    pub fn port_checked<T>(&self, name: &str) -> <Spec as CheckedPortSpec<T>>::Port
+    where
+        Spec: CheckedPortSpec<T>,
+    {
+        self.spec.borrow().port_checked(name)
+    }
+
+    /// Create a new port with an initial value, attaching a label to the port.
+    ///
+    /// The label is prefixed with the current namespace.  Ports do not keep their label: it is only
+    /// recorded in the scope's inspector, if any.
+    pub fn port_named<L, T>(&self, label: L, init: T) -> Spec::Port
+    where
+        L: Into<Label>,
+        Spec: PortSpec<T>,
+    {
+        if let Some(ref inspector) = self.inspector {
+            inspector.add_port(self.qualify(label.into()).into_owned());
+        }
+        self.port(init)
+    }
+
+    /// Create a `JoinNode` with `count` inputs, sending the vector of their values to `output`,
+    /// and return the edges sending a value to each input, in order.
+    pub fn join<T, O>(&mut self, count: usize, output: O) -> Vec<JoinInput<Spec, T>>
+    where
+        Spec: PortSpec<Option<T>> + NodeSpec<JoinNode<DataInput<PortReceiver<Spec, T>>, O>>,
+        PortReceiver<Spec, T>: ReceiverOnce,
+        JoinNode<DataInput<PortReceiver<Spec, T>>, O>: 'a,
+    {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..count)
+            .map(|_| self.port(None).split())
+            .map(|(sender, receiver)| (sender, receiver.as_data_input()))
+            .unzip();
+        let mut node = self.node(JoinNode::new(receivers, output));
+        senders
+            .into_iter()
+            .map(|sender| {
+                let input = NodeInput {
+                    activator: node.add_activator(),
+                    sender,
+                };
+                input.map(Some as fn(T) -> Option<T>)
+            })
+            .collect()
+    }
+
+    /// Expose `receiver` as a named output of the graph, which can be read from the runtime with
+    /// `OutputSpec::output` once the graph has executed.
+    pub fn expose_output<T, R>(&mut self, name: &str, receiver: R)
+    where
+        Spec: OutputSpec,
+        T: 'static,
+        R: Receiver<Item = T> + Send + Sync + 'static,
+    {
+        self.spec.borrow_mut().outputs_mut().expose(name, receiver)
+    }
+
     pub fn borrow_mut<'b, T>(&'b mut self) -> impl DerefMut<Target = &'a mut Spec> + 'b {
         self.spec.borrow_mut()
     }
 }
 
-/// Display an error message if there are remaining scoped node builders when the graph builder is
-/// dropped.
+/// The sending side of the ports holding optional values of type `T`.
+type PortSender<Spec, T> = <<Spec as PortSpec<Option<T>>>::Port as Port>::Sender;
+
+/// The receiving side of the ports holding optional values of type `T`.
+type PortReceiver<Spec, T> = <<Spec as PortSpec<Option<T>>>::Port as Port>::Receiver;
+
+/// An edge sending a value to an input of a join node.  See `ScopedGraphBuilder::join`.
+pub type JoinInput<Spec, T> = MapOutput<
+    NodeInput<<Spec as GraphSpec>::Activator, PortSender<Spec, T>>,
+    fn(T) -> Option<T>,
+    T,
+>;
+
+/// Activate the nodes started with `ScopedNodeBuilder::auto_start`, and display an error message
+/// if there are remaining scoped node builders when the graph builder is dropped.
 impl<'a, Spec: GraphSpec + 'a> Drop for ScopedGraphBuilder<'a, Spec> {
     fn drop(&mut self) {
         if Rc::strong_count(&self.spec) != 1 {
             eprintln!("Some nodes were not finalized after scoped build.");
         }
+        if !thread::panicking() {
+            let mut spec = self.spec.borrow_mut();
+            for start in self.starts.borrow_mut().drain(..) {
+                start(&mut **spec)
+            }
+        }
     }
 }
@@ -0,0 +1,111 @@
+//! Combinators using the optional features of the schedulers.
+//!
+//! The combinators in this module query the scheduler's features through the `Capabilities`
+//! trait, and keep working, with a degraded behavior, on schedulers lacking them:
+//!
+//!  - `CancellableTask` skips its task when the execution was cancelled, and always runs it on
+//!    schedulers which can't be cancelled.
+//!  - `WorkerLocal` gives each worker its own copy of some state, and falls back to a single
+//!    shared copy on schedulers which don't have workers.
+
+use std::sync::Mutex;
+
+use api::prelude::*;
+
+/// A task wrapper skipping the underlying task when the scheduler's execution was cancelled.
+///
+/// The results of the tasks executed after a cancellation are usually discarded: skipping them
+/// makes cancellation faster for graphs with expensive tasks.  Note that skipped tasks don't send
+/// anything on their outputs.
+#[derive(Debug, Clone)]
+pub struct CancellableTask<T> {
+    inner: T,
+}
+
+impl<T> CancellableTask<T> {
+    /// Wrap a task.
+    pub fn new(inner: T) -> Self {
+        CancellableTask { inner }
+    }
+}
+
+/// Whether the scheduler's execution was cancelled.  This is always false on schedulers which
+/// can't be cancelled.
+fn is_cancelled<S: Capabilities>(scheduler: &S) -> bool {
+    scheduler
+        .as_cancellation()
+        .is_some_and(|cancellation| cancellation.is_cancelled())
+}
+
+impl<I: Tuple, O: Tuple, S: Capabilities, T: TaskOnce<I, O, S>> TaskOnce<I, O, S>
+    for CancellableTask<T>
+{
+    fn run_once(self, scheduler: &mut S, inputs: I, outputs: O) {
+        if !is_cancelled(scheduler) {
+            self.inner.run_once(scheduler, inputs, outputs)
+        }
+    }
+}
+
+impl<I: Tuple, O: Tuple, S: Capabilities, T: TaskMut<I, O, S>> TaskMut<I, O, S>
+    for CancellableTask<T>
+{
+    fn run_mut(&mut self, scheduler: &mut S, inputs: I, outputs: O) {
+        if !is_cancelled(scheduler) {
+            self.inner.run_mut(scheduler, inputs, outputs)
+        }
+    }
+}
+
+impl<I: Tuple, O: Tuple, S: Capabilities, T: Task<I, O, S>> Task<I, O, S> for CancellableTask<T> {
+    fn run(&self, scheduler: &mut S, inputs: I, outputs: O) {
+        if !is_cancelled(scheduler) {
+            self.inner.run(scheduler, inputs, outputs)
+        }
+    }
+}
+
+/// Per-worker state, e.g. scratch buffers or partial aggregates, shared by the nodes of a graph.
+///
+/// The state is split in a fixed number of slots, each protected by a lock.  Nodes access the slot
+/// of the worker executing them, so that the locks are not contended as long as there are at least
+/// as many slots as workers.  On schedulers without workers, all the nodes use the first slot.
+#[derive(Debug)]
+pub struct WorkerLocal<T> {
+    slots: Vec<Mutex<T>>,
+}
+
+impl<T> WorkerLocal<T> {
+    /// Create `slots` slots, initialized with `init`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `slots` is zero.
+    pub fn new<F: FnMut() -> T>(slots: usize, mut init: F) -> Self {
+        assert!(slots > 0, "A worker-local state needs at least one slot.");
+
+        WorkerLocal {
+            slots: (0..slots).map(|_| Mutex::new(init())).collect(),
+        }
+    }
+
+    /// Call `f` with the slot of the worker running on `scheduler`.
+    pub fn with<S, F, R>(&self, scheduler: &S, f: F) -> R
+    where
+        S: Capabilities,
+        F: FnOnce(&mut T) -> R,
+    {
+        let index = scheduler
+            .as_worker_index()
+            .map_or(0, |workers| workers.worker_index());
+        f(&mut self.slots[index % self.slots.len()].lock().unwrap())
+    }
+
+    /// The state of all the slots, e.g. to combine the partial aggregates.
+    pub fn into_inner(self) -> Vec<T> {
+        self.slots
+            .into_iter()
+            .map(|slot| slot.into_inner().unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,160 @@
+//! Coalescing bursts of external events.
+//!
+//! Some external sources fire in bursts, e.g. a file watcher reporting each file of a checkout or
+//! a window manager reporting each step of a resize.  Injecting each event separately schedules
+//! one node, and possibly one instant, per event, although the graph only cares about the burst as
+//! a whole.  A `CoalescingSource` merges the events of a burst instead: the first event opens a
+//! *window* of a fixed duration, the events received before the window closes are merged into the
+//! first one with a user-supplied fold function, and the result is injected once the window
+//! closes.
+//!
+//! ```rust,ignore
+//! let source = CoalescingSource::new(
+//!     Duration::from_millis(20),
+//!     |mut paths: Vec<PathBuf>, more: Vec<PathBuf>| { paths.extend(more); paths },
+//!     move || edges.next(),
+//! )
+//! .start(&runtime);
+//! watcher.on_change(move |path| source.push(vec![path]));
+//! ```
+//!
+//! Just like timers (see the `timer` module), coalescing sources are driven by a dedicated thread
+//! and push their results through the runtime's external event mechanism (see the `InjectorSpec`
+//! trait), requesting a new edge for each injection.  Windows are not extended by late events: a
+//! burst longer than the window is split into several injections, so that the latency stays
+//! bounded.
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use api::prelude::*;
+
+/// The description of a coalescing source, before it is started.
+///
+/// `fold` merges an event into the value accumulated since the window opened, and `edges` is
+/// called on the source's thread before each injection to get the edge on which the merged value
+/// is sent; the source stops once it returns `None`.
+pub struct CoalescingSource<F, G> {
+    window: Duration,
+    fold: F,
+    edges: G,
+}
+
+impl<F, G> CoalescingSource<F, G> {
+    /// Create a source merging the events received within `window` of the first event of a
+    /// burst.  With a zero window, events are not merged.
+    pub fn new(window: Duration, fold: F, edges: G) -> Self {
+        CoalescingSource {
+            window,
+            fold,
+            edges,
+        }
+    }
+
+    /// The coalescing window of the source.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Start the source's thread, injecting the merged events into `runtime`.
+    pub fn start<S, T, E>(self, runtime: &S) -> EventSource<T>
+    where
+        S: InjectorSpec,
+        S::Injector: 'static,
+        T: Send + 'static,
+        F: FnMut(T, T) -> T + Send + 'static,
+        G: FnMut() -> Option<E> + Send + 'static,
+        E: OutputEdgeOnce<S> + Send + Sync + 'static,
+        E::Item: From<T> + Send + Sync + 'static,
+    {
+        let injector = runtime.injector();
+        let (sender, receiver) = mpsc::channel();
+
+        let CoalescingSource {
+            window,
+            mut fold,
+            mut edges,
+        } = self;
+        let thread = thread::spawn(move || {
+            while let Ok(first) = receiver.recv() {
+                let deadline = Instant::now() + window;
+                let mut value = first;
+                let mut open = true;
+                loop {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    match receiver.recv_timeout(deadline - now) {
+                        Ok(event) => value = fold(value, event),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => {
+                            open = false;
+                            break;
+                        }
+                    }
+                }
+
+                match edges() {
+                    Some(edge) => injector.inject_send(edge, E::Item::from(value)),
+                    None => return,
+                }
+                if !open {
+                    return;
+                }
+            }
+        });
+
+        EventSource {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// A handle for pushing events into a running `CoalescingSource`.  Dropping the handle closes the
+/// current window early, injects its events and waits for the source's thread to exit.
+///
+/// The handle can be shared between threads, e.g. in an `Arc`.
+pub struct EventSource<T> {
+    sender: Option<Sender<T>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T> EventSource<T> {
+    /// Push an event, opening a new window if there is none.  Events pushed after the source
+    /// stopped are dropped.
+    pub fn push(&self, event: T) {
+        if let Some(ref sender) = self.sender {
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Whether the source stopped by itself because it ran out of edges.
+    pub fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+
+    /// Close the source, injecting the pending events without waiting for the end of the current
+    /// window.  No events are injected once this returns.
+    pub fn close(mut self) {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            // Don't panic while dropping if the `fold` or `edges` closures panicked.
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<T> Drop for EventSource<T> {
+    fn drop(&mut self) {
+        self.shutdown()
+    }
+}
@@ -0,0 +1,352 @@
+//! A higher-level API for building reusable graphs of tasks.
+//!
+//! Building a graph with `ScopedGraphBuilder` requires creating a port for each edge, splitting it,
+//! creating an activator for the consumer and bundling it with the sender.  The methods in this
+//! module do all of that for tasks which are pure functions of their inputs:
+//!
+//! ```rust,ignore
+//! runtime.build_scope(|b| {
+//!     let mut double = b.task1(|x: i32| 2 * x);
+//!     let mut add = b.task2(|x: i32, y: i32| x + y);
+//!     double.output().connect(add.input0());
+//!     ...
+//! })
+//! ```
+//!
+//! `task1`, `task2`, `task3` and `task4` create a node executing a function with as many
+//! arguments, and return a typed `NodeHandle`.  The function is called with the values received on
+//! the node's inputs, and its result is sent to all the inputs connected to the node's output.
+//! Inputs are accessed by position, with `input0` to `input3`, and each input must be connected
+//! exactly once: a node runs when all of its inputs have been sent a value.  Values are sent into a
+//! graph through the nodes created with `source`, and read from a graph with
+//! `OutputHandle::connect_edge`.
+//!
+//! Since outputs are connected after the node is created, a node's output is shared with its
+//! handle and protected by a lock.  The lock is not contended once the graph is built.
+
+use std::sync::{Arc, Mutex};
+
+use api::prelude::*;
+use common::builder::ScopedGraphBuilder;
+use common::edge::{CloneOutput, OutputEdgeExt};
+use common::erased::{ErasedInput, ErasedOutput};
+use common::node::TaskNode;
+use common::port::{ReceiverExt, SenderExt};
+
+/// The sending side of an input, waiting for the node's activator.
+type Wire<'r, Spec, X, T> =
+    Box<dyn FnOnce(<Spec as GraphSpec>::Activator) -> ErasedOutput<'r, X, T> + 'r>;
+
+/// A runtime which can create the ports used by the DSL, i.e. ports holding an optional value of
+/// type `T` whose sender and receiver can be erased.  The receiver is read by nodes executed with
+/// a scheduler of type `S`, and the sender is activated with a scheduler of type `X`: this is `S`
+/// for the edges between nodes, and the runtime itself for the sources of the graph.
+///
+/// This is implemented for all runtimes supporting reusable graphs.
+pub trait DslPortSpec<'r, S, X, T>: GraphSpec {
+    /// Create a port, and return its receiving side and a function bundling its sending side with
+    /// an activator.
+    fn dsl_port<'a>(
+        builder: &ScopedGraphBuilder<'a, Self>,
+    ) -> (ErasedInput<'r, S, Option<T>>, Wire<'r, Self, X, T>)
+    where
+        Self: 'a;
+}
+
+impl<'r, S, X, T, Spec> DslPortSpec<'r, S, X, T> for Spec
+where
+    S: 'r,
+    X: 'r,
+    T: 'r,
+    Spec: GraphSpec + PortSpec<Option<T>>,
+    Spec::Activator: ActivatorMut<X> + Send + Sync + 'r,
+    <<Spec as PortSpec<Option<T>>>::Port as Port>::Sender:
+        SenderMut<Item = Option<T>> + Send + Sync + 'r,
+    <<Spec as PortSpec<Option<T>>>::Port as Port>::Receiver:
+        ReceiverMut<Item = Option<T>> + Send + Sync + 'r,
+{
+    fn dsl_port<'a>(
+        builder: &ScopedGraphBuilder<'a, Self>,
+    ) -> (ErasedInput<'r, S, Option<T>>, Wire<'r, Self, X, T>)
+    where
+        Self: 'a,
+    {
+        let (sender, receiver) = builder.port(None).split();
+        (
+            ErasedInput::input(receiver.as_data_input()),
+            Box::new(move |activator| {
+                ErasedOutput::output(sender.with_activator(activator).map(Some))
+            }),
+        )
+    }
+}
+
+/// The output of a node created by the DSL.  It sends a clone of the result to each connected
+/// edge.
+pub struct SharedOutput<'r, S, T> {
+    outputs: Arc<Mutex<CloneOutput<ErasedOutput<'r, S, T>>>>,
+}
+
+impl<'r, S, T> Clone for SharedOutput<'r, S, T> {
+    fn clone(&self) -> Self {
+        SharedOutput {
+            outputs: self.outputs.clone(),
+        }
+    }
+}
+
+impl<'r, S, T: Clone> OutputEdgeOnce<S> for SharedOutput<'r, S, T> {
+    type Item = T;
+
+    fn send_activate_once(mut self, scheduler: &mut S, item: T) {
+        self.send_activate_mut(scheduler, item)
+    }
+}
+
+impl<'r, S, T: Clone> OutputEdgeMut<S> for SharedOutput<'r, S, T> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: T) {
+        self.outputs
+            .lock()
+            .unwrap()
+            .send_activate_mut(scheduler, item)
+    }
+}
+
+/// The task of a node created by the DSL.  It unwraps the values received on the inputs, and
+/// calls the underlying function with them.
+pub struct DslTask<F> {
+    inner: F,
+}
+
+// Macro implementation of `TaskMut` for `DslTask` with functions of multiple arguments.
+macro_rules! auto_impl_dsl_task {
+    ($($Is:ident $As:ident,)*) => {
+        impl<S, $($Is, $As,)* O, R, F> TaskMut<($($Is,)*), (O,), S> for DslTask<F>
+        where
+            $($Is: InputEdgeOnce<S, Item = Option<$As>>,)*
+            O: OutputEdgeOnce<S, Item = R>,
+            F: FnMut($($As,)*) -> R,
+        {
+            fn run_mut(&mut self, scheduler: &mut S, inputs: ($($Is,)*), outputs: (O,)) {
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = inputs;
+                #[allow(non_snake_case)]
+                let ($($As,)*) = ($(
+                    $Is.recv_activate_once(scheduler)
+                        .expect("A node was executed with an empty input."),
+                )*);
+                outputs.0.send_activate_once(scheduler, (self.inner)($($As,)*));
+            }
+        }
+    };
+}
+
+auto_impl_dsl_task! { I0 A0, }
+auto_impl_dsl_task! { I0 A0, I1 A1, }
+auto_impl_dsl_task! { I0 A0, I1 A1, I2 A2, }
+auto_impl_dsl_task! { I0 A0, I1 A1, I2 A2, I3 A3, }
+
+/// An input of a node created by the DSL, which was not connected yet.
+pub struct InputHandle<'r, S, T> {
+    edge: ErasedOutput<'r, S, T>,
+}
+
+impl<'r, S, T> InputHandle<'r, S, T> {
+    /// An input sending the values it receives to an arbitrary output edge, e.g. to read the
+    /// results of a `Subgraph` built with the DSL from the outside of the graph.
+    pub fn from_edge<E>(edge: E) -> Self
+    where
+        E: OutputEdgeMut<S, Item = T> + Send + Sync + 'r,
+    {
+        InputHandle {
+            edge: ErasedOutput::output(edge),
+        }
+    }
+}
+
+/// The output of a node created by the DSL.
+pub struct OutputHandle<'r, S, T> {
+    output: SharedOutput<'r, S, T>,
+}
+
+impl<'r, S: 'r, T: 'r> OutputHandle<'r, S, T> {
+    /// Send the results of the node to `input`.
+    pub fn connect(&self, input: InputHandle<'r, S, T>) {
+        self.output.outputs.lock().unwrap().connect(input.edge)
+    }
+
+    /// Send the results of the node to `input`, converted with `f`.  This is typically used to
+    /// connect a single field of a node returning a tuple.
+    pub fn connect_map<U, F>(&self, input: InputHandle<'r, S, U>, f: F)
+    where
+        U: 'r,
+        F: FnMut(T) -> U + Send + Sync + 'r,
+    {
+        self.connect_edge(input.edge.map(f))
+    }
+
+    /// Send the results of the node to an arbitrary output edge, e.g. to read them from the
+    /// outside of the graph.
+    pub fn connect_edge<E>(&self, edge: E)
+    where
+        E: OutputEdgeMut<S, Item = T> + Send + Sync + 'r,
+    {
+        self.output
+            .outputs
+            .lock()
+            .unwrap()
+            .connect(ErasedOutput::output(edge))
+    }
+
+    /// Send the results of the node to an arbitrary output edge, converted with `f`.
+    pub fn connect_edge_map<E, F>(&self, edge: E, f: F)
+    where
+        E: OutputEdgeMut<S> + OutputEdgeExt + Send + Sync + 'r,
+        F: FnMut(T) -> E::Item + Send + Sync + 'r,
+    {
+        self.connect_edge(edge.map(f))
+    }
+}
+
+/// A node created by the DSL.  `I` is a tuple with one optional `InputHandle` per input, taken
+/// when the input is connected.
+pub struct NodeHandle<'r, S, I, O> {
+    inputs: I,
+    output: SharedOutput<'r, S, O>,
+}
+
+impl<'r, S, I, O> NodeHandle<'r, S, I, O> {
+    /// The output of the node.  It can be connected to any number of inputs.
+    pub fn output(&self) -> OutputHandle<'r, S, O> {
+        OutputHandle {
+            output: self.output.clone(),
+        }
+    }
+}
+
+// Accessors for the inputs of the nodes created by the DSL.
+macro_rules! impl_dsl_inputs {
+    ($($As:ident),* : $($input:ident . $index:tt -> $A:ident),*) => {
+        impl<'r, S, $($As,)* O> NodeHandle<'r, S, ($(Option<InputHandle<'r, S, $As>>,)*), O> {
+            $(
+                /// An input of the node, to be connected to an output.
+                ///
+                /// # Panics
+                ///
+                /// This panics if the input was already taken.
+                pub fn $input(&mut self) -> InputHandle<'r, S, $A> {
+                    self.inputs
+                        .$index
+                        .take()
+                        .expect("The input was already connected.")
+                }
+            )*
+        }
+    };
+}
+
+impl_dsl_inputs! { A0 : input0.0 -> A0 }
+impl_dsl_inputs! { A0, A1 : input0.0 -> A0, input1.1 -> A1 }
+impl_dsl_inputs! { A0, A1, A2 : input0.0 -> A0, input1.1 -> A1, input2.2 -> A2 }
+impl_dsl_inputs! {
+    A0, A1, A2, A3 : input0.0 -> A0, input1.1 -> A1, input2.2 -> A2, input3.3 -> A3
+}
+
+// The `taskN` methods of the builder.
+macro_rules! impl_dsl_tasks {
+    ($($(#[$attr:meta])* fn $task:ident($($As:ident $as:ident),*);)*) => {
+        impl<'a, Spec: GraphSpec + 'a> ScopedGraphBuilder<'a, Spec> {
+            $(
+                $(#[$attr])*
+                #[allow(clippy::type_complexity)]
+                pub fn $task<'r, S, $($As,)* R, F>(
+                    &mut self,
+                    task: F,
+                ) -> NodeHandle<'r, S, ($(Option<InputHandle<'r, S, $As>>,)*), R>
+                where
+                    $(Spec: DslPortSpec<'r, S, S, $As>,)*
+                    Spec: NodeSpec<
+                        TaskNode<
+                            ($(ErasedInput<'r, S, Option<$As>>,)*),
+                            (SharedOutput<'r, S, R>,),
+                            DslTask<F>,
+                        >,
+                    >,
+                    'r: 'a,
+                    S: 'r,
+                    $($As: 'r,)*
+                    R: 'r,
+                    F: FnMut($($As),*) -> R + 'r,
+                {
+                    $(let $as = <Spec as DslPortSpec<'r, S, S, $As>>::dsl_port(self);)*
+                    let output = SharedOutput {
+                        outputs: Arc::new(Mutex::new(CloneOutput::new())),
+                    };
+                    let mut node = self.node(TaskNode {
+                        inputs: ($($as.0,)*),
+                        outputs: (output.clone(),),
+                        task: DslTask { inner: task },
+                    });
+
+                    NodeHandle {
+                        inputs: ($(Some(InputHandle {
+                            edge: ($as.1)(node.add_activator()),
+                        }),)*),
+                        output,
+                    }
+                }
+            )*
+        }
+    };
+}
+
+impl_dsl_tasks! {
+    /// Create a node executing `task` with the value received on its single input.
+    fn task1(A0 a0);
+    /// Create a node executing `task` with the values received on its two inputs.
+    fn task2(A0 a0, A1 a1);
+    /// Create a node executing `task` with the values received on its three inputs.
+    fn task3(A0 a0, A1 a1, A2 a2);
+    /// Create a node executing `task` with the values received on its four inputs.
+    fn task4(A0 a0, A1 a1, A2 a2, A3 a3);
+}
+
+/// The task of the nodes created by `ScopedGraphBuilder::source`.
+fn identity<T>(item: T) -> T {
+    item
+}
+
+impl<'a, Spec: GraphSpec + 'a> ScopedGraphBuilder<'a, Spec> {
+    /// Create an entry point of the graph.  The returned edge is activated with the runtime to
+    /// send a value into the graph, typically between executions, and the value is forwarded to
+    /// the inputs connected to the returned output.
+    #[allow(clippy::type_complexity)]
+    pub fn source<'r, X, S, T>(&mut self) -> (ErasedOutput<'r, X, T>, OutputHandle<'r, S, T>)
+    where
+        Spec: DslPortSpec<'r, S, X, T>,
+        Spec: NodeSpec<
+            TaskNode<
+                (ErasedInput<'r, S, Option<T>>,),
+                (SharedOutput<'r, S, T>,),
+                DslTask<fn(T) -> T>,
+            >,
+        >,
+        'r: 'a,
+        S: 'r,
+        T: 'r,
+    {
+        let (input, wire) = <Spec as DslPortSpec<'r, S, X, T>>::dsl_port(self);
+        let output = SharedOutput {
+            outputs: Arc::new(Mutex::new(CloneOutput::new())),
+        };
+        let mut node = self.node(TaskNode {
+            inputs: (input,),
+            outputs: (output.clone(),),
+            task: DslTask {
+                inner: identity as fn(T) -> T,
+            },
+        });
+
+        (wire(node.add_activator()), OutputHandle { output })
+    }
+}
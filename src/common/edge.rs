@@ -5,11 +5,33 @@
 //! This includes a `CloneOutput` type which allows combining multiple output edges as one, cloning
 //! the underlying data into each of the edges.
 //!
+//! The `OutputEdgeExt` trait provides `map`, `filter` and `scan` adapters which transform the items
+//! sent on an output edge, so that simple per-edge transformations don't need a node and a port.
+//!
+//! The `FilterOutput` type forwards its output to one of two edges depending on a predicate, which
+//! allows if/else routing without writing a dedicated task.  Similarly, `RouteOutput` dispatches
+//! keyed items to the edge registered for their key, which allows demultiplexing on an enum.
+//!
+//! The `ErrorOutput` type is the edge on which a `TryTaskNode` sends the errors of its task, so
+//! that they can be routed to an error-handler node.
+//!
+//! The `AckInput` and `AckOutput` types implement two-way edges, where the consumer notifies the
+//! producer each time it reads a value.  This allows demand-driven producers which only compute a
+//! new value once the previous one was consumed.
+//!
+//! The `ZipInput` type combines several receivers into a single input edge which yields the most
+//! recent value received on each of them, like the "combineLatest" reactive operator.
+//!
 //! It also includes macro implementations to allow considering tuples of input edges as a single
 //! input edge receiving a tuple of values, and tuples of output edges as a single output edge
 //! accepting a tuple of values.  This can be convenient when writing generic tasks.
 
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
 use api::prelude::*;
+use common::port::{DataOutput, NodeInput};
 
 /// An output edge which clones its output and propagates it to additional edges.
 ///
@@ -93,6 +115,302 @@ where
     }
 }
 
+/// An output edge which only forwards items satisfying a predicate, and optionally sends the
+/// other items to an alternate edge.
+///
+/// Note that when an item is dropped, the target node is not activated: in single-use graphs, it
+/// will never be executed.
+///
+/// The type of the alternate edge is `E` until one is set with `otherwise`.
+#[derive(Debug)]
+pub struct FilterOutput<E, F, O = E> {
+    output: E,
+    predicate: F,
+    otherwise: Option<O>,
+}
+
+impl<E, F> FilterOutput<E, F> {
+    /// Create a new edge forwarding items to `output` when `predicate` returns `true`, and
+    /// dropping them otherwise.
+    pub fn new(output: E, predicate: F) -> Self {
+        FilterOutput {
+            output,
+            predicate,
+            otherwise: None,
+        }
+    }
+}
+
+impl<E, F, O> FilterOutput<E, F, O> {
+    /// Send the items for which the predicate returns `false` to `otherwise` instead of dropping
+    /// them, replacing any previous alternate edge.
+    pub fn otherwise<P>(self, otherwise: P) -> FilterOutput<E, F, P> {
+        FilterOutput {
+            output: self.output,
+            predicate: self.predicate,
+            otherwise: Some(otherwise),
+        }
+    }
+}
+
+impl<S, E, F, O> OutputEdgeOnce<S> for FilterOutput<E, F, O>
+where
+    E: OutputEdgeOnce<S>,
+    F: Fn(&E::Item) -> bool,
+    O: OutputEdgeOnce<S, Item = E::Item>,
+{
+    type Item = E::Item;
+
+    fn send_activate_once(self, scheduler: &mut S, item: Self::Item) {
+        if (self.predicate)(&item) {
+            self.output.send_activate_once(scheduler, item)
+        } else if let Some(otherwise) = self.otherwise {
+            otherwise.send_activate_once(scheduler, item)
+        }
+    }
+}
+
+impl<S, E, F, O> OutputEdgeMut<S> for FilterOutput<E, F, O>
+where
+    E: OutputEdgeMut<S>,
+    F: Fn(&E::Item) -> bool,
+    O: OutputEdgeMut<S, Item = E::Item>,
+{
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: Self::Item) {
+        if (self.predicate)(&item) {
+            self.output.send_activate_mut(scheduler, item)
+        } else if let Some(ref mut otherwise) = self.otherwise {
+            otherwise.send_activate_mut(scheduler, item)
+        }
+    }
+}
+
+impl<S, E, F, O> OutputEdge<S> for FilterOutput<E, F, O>
+where
+    E: OutputEdge<S>,
+    F: Fn(&E::Item) -> bool,
+    O: OutputEdge<S, Item = E::Item>,
+{
+    fn send_activate(&self, scheduler: &mut S, item: Self::Item) {
+        if (self.predicate)(&item) {
+            self.output.send_activate(scheduler, item)
+        } else if let Some(ref otherwise) = self.otherwise {
+            otherwise.send_activate(scheduler, item)
+        }
+    }
+}
+
+/// An output edge dispatching `(key, item)` pairs to the edge registered under `key`.
+///
+/// Keys are compared with `PartialEq`, so that fieldless enums only need to derive it.  As with
+/// `FilterOutput`, items whose key has no registered edge are dropped without activating anything.
+/// Heterogeneous targets can be connected by using boxed edges as `E`.
+#[derive(Debug)]
+pub struct RouteOutput<K, E> {
+    routes: Vec<(K, E)>,
+}
+
+impl<K, E> RouteOutput<K, E> {
+    /// Create a new `RouteOutput` without any routes.
+    pub fn new() -> Self {
+        RouteOutput { routes: Vec::new() }
+    }
+}
+
+impl<K: PartialEq, E> RouteOutput<K, E> {
+    /// Register `output` as the target of items sent with `key`, replacing any edge previously
+    /// registered under the same key.
+    pub fn connect(&mut self, key: K, output: E) {
+        match self.routes.iter_mut().find(|route| route.0 == key) {
+            Some(route) => route.1 = output,
+            None => self.routes.push((key, output)),
+        }
+    }
+
+    /// Whether an edge is registered under `key`.
+    pub fn is_connected(&self, key: &K) -> bool {
+        self.routes.iter().any(|route| route.0 == *key)
+    }
+}
+
+impl<K, E> Default for RouteOutput<K, E> {
+    fn default() -> Self {
+        RouteOutput::new()
+    }
+}
+
+impl<S, K: PartialEq, E: OutputEdgeOnce<S>> OutputEdgeOnce<S> for RouteOutput<K, E> {
+    type Item = (K, E::Item);
+
+    fn send_activate_once(self, scheduler: &mut S, (key, item): Self::Item) {
+        if let Some((_, output)) = self.routes.into_iter().find(|route| route.0 == key) {
+            output.send_activate_once(scheduler, item)
+        }
+    }
+}
+
+impl<S, K: PartialEq, E: OutputEdgeMut<S>> OutputEdgeMut<S> for RouteOutput<K, E> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, (key, item): Self::Item) {
+        if let Some(route) = self.routes.iter_mut().find(|route| route.0 == key) {
+            route.1.send_activate_mut(scheduler, item)
+        }
+    }
+}
+
+impl<S, K: PartialEq, E: OutputEdge<S>> OutputEdge<S> for RouteOutput<K, E> {
+    fn send_activate(&self, scheduler: &mut S, (key, item): Self::Item) {
+        if let Some(route) = self.routes.iter().find(|route| route.0 == key) {
+            route.1.send_activate(scheduler, item)
+        }
+    }
+}
+
+/// The output edge on which a `TryTaskNode` sends the errors of its task.
+///
+/// The wrapped edge is typically connected to an error-handler node.  It is only used when the task
+/// fails, so that the handler is only activated on failure: in single-use graphs, it is not
+/// executed at all when the task succeeds.
+#[derive(Debug)]
+pub struct ErrorOutput<E> {
+    output: E,
+}
+
+impl<E> ErrorOutput<E> {
+    /// Route the errors to `output`.
+    pub fn new(output: E) -> Self {
+        ErrorOutput { output }
+    }
+}
+
+impl<S, E: OutputEdgeOnce<S>> OutputEdgeOnce<S> for ErrorOutput<E> {
+    type Item = E::Item;
+
+    fn send_activate_once(self, scheduler: &mut S, error: Self::Item) {
+        self.output.send_activate_once(scheduler, error)
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeMut<S> for ErrorOutput<E> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, error: Self::Item) {
+        self.output.send_activate_mut(scheduler, error)
+    }
+}
+
+impl<S, E: OutputEdge<S>> OutputEdge<S> for ErrorOutput<E> {
+    fn send_activate(&self, scheduler: &mut S, error: Self::Item) {
+        self.output.send_activate(scheduler, error)
+    }
+}
+
+/// Adapters transforming the items sent on an output edge.
+///
+/// Each adapter returns a new edge which transforms the items it receives before sending them on
+/// the wrapped edge.  Note that adapters are thus applied in reverse order: the items sent on
+/// `edge.map(f).filter(p)` are first filtered with `p`, then mapped with `f`.
+///
+/// This is implemented for the output edges of this crate; other edges can implement it with an
+/// empty `impl` block.
+pub trait OutputEdgeExt: Sized {
+    /// Apply `f` to the items before sending them on this edge.
+    fn map<T, F>(self, f: F) -> MapOutput<Self, F, T> {
+        MapOutput {
+            output: self,
+            f,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Only send the items for which `predicate` returns `true` on this edge.  See `FilterOutput`.
+    fn filter<F>(self, predicate: F) -> FilterOutput<Self, F> {
+        FilterOutput::new(self, predicate)
+    }
+
+    /// Fold the items into a state initialized with `init`, and send the values returned by `f`
+    /// on this edge.  This allows computing running sums or averages over the items.
+    fn scan<St, T, F>(self, init: St, f: F) -> ScanOutput<Self, St, F, T> {
+        ScanOutput {
+            output: self,
+            state: init,
+            f,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, I> OutputEdgeExt for NodeInput<A, I> {}
+impl<T> OutputEdgeExt for DataOutput<T> {}
+impl<E> OutputEdgeExt for CloneOutput<E> {}
+impl<E, F, O> OutputEdgeExt for FilterOutput<E, F, O> {}
+impl<K, E> OutputEdgeExt for RouteOutput<K, E> {}
+impl<E> OutputEdgeExt for AckOutput<E> {}
+impl<E> OutputEdgeExt for ErrorOutput<E> {}
+impl<E, F, T> OutputEdgeExt for MapOutput<E, F, T> {}
+impl<E, St, F, T> OutputEdgeExt for ScanOutput<E, St, F, T> {}
+impl<E: ?Sized> OutputEdgeExt for Box<E> {}
+
+/// An output edge applying a function to the items before sending them.  See
+/// `OutputEdgeExt::map`.
+pub struct MapOutput<E, F, T> {
+    output: E,
+    f: F,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<S, T, E: OutputEdgeOnce<S>, F: FnOnce(T) -> E::Item> OutputEdgeOnce<S> for MapOutput<E, F, T> {
+    type Item = T;
+
+    fn send_activate_once(self, scheduler: &mut S, item: T) {
+        self.output.send_activate_once(scheduler, (self.f)(item))
+    }
+}
+
+impl<S, T, E: OutputEdgeMut<S>, F: FnMut(T) -> E::Item> OutputEdgeMut<S> for MapOutput<E, F, T> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: T) {
+        self.output.send_activate_mut(scheduler, (self.f)(item))
+    }
+}
+
+impl<S, T, E: OutputEdge<S>, F: Fn(T) -> E::Item> OutputEdge<S> for MapOutput<E, F, T> {
+    fn send_activate(&self, scheduler: &mut S, item: T) {
+        self.output.send_activate(scheduler, (self.f)(item))
+    }
+}
+
+/// An output edge folding the items into a state before sending them.  See
+/// `OutputEdgeExt::scan`.
+///
+/// Since the state is local to the edge, this is not an `OutputEdge`.
+pub struct ScanOutput<E, St, F, T> {
+    output: E,
+    state: St,
+    f: F,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<S, St, T, E, F> OutputEdgeOnce<S> for ScanOutput<E, St, F, T>
+where
+    E: OutputEdgeOnce<S>,
+    F: FnOnce(&mut St, T) -> E::Item,
+{
+    type Item = T;
+
+    fn send_activate_once(mut self, scheduler: &mut S, item: T) {
+        let item = (self.f)(&mut self.state, item);
+        self.output.send_activate_once(scheduler, item)
+    }
+}
+
+impl<S, St, T, E, F> OutputEdgeMut<S> for ScanOutput<E, St, F, T>
+where
+    E: OutputEdgeMut<S>,
+    F: FnMut(&mut St, T) -> E::Item,
+{
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: T) {
+        let item = (self.f)(&mut self.state, item);
+        self.output.send_activate_mut(scheduler, item)
+    }
+}
+
 impl<S, E: OutputEdgeBox<S> + ?Sized> OutputEdgeOnce<S> for Box<E> {
     type Item = E::Item;
 
@@ -113,6 +431,228 @@ impl<S, E: OutputEdge<S> + ?Sized> OutputEdge<S> for Box<E> {
     }
 }
 
+/// A shared count of the values sent on a two-way edge which were not consumed yet.
+///
+/// The count is created along with the consumer side of the edge (see `AckInput::counter`), and
+/// used to create the producer side with `output`.  This allows creating the producer side once
+/// the consumer node was built, since it usually needs the consumer's activator.
+#[derive(Debug, Clone)]
+pub struct AckCounter(Arc<AtomicUsize>);
+
+impl AckCounter {
+    /// The number of values which were sent but not consumed yet.
+    pub fn outstanding(&self) -> usize {
+        self.0.load(SeqCst)
+    }
+
+    /// Wrap the edge used by the producer to send values to the consumer (usually a sender
+    /// bundled with the consumer's activator), so that it counts the values it sends.
+    pub fn output<E>(&self, output: E) -> AckOutput<E> {
+        AckOutput {
+            output,
+            outstanding: self.0.clone(),
+        }
+    }
+}
+
+/// The producer side of a two-way edge, created with `AckCounter::output`.
+#[derive(Debug)]
+pub struct AckOutput<E> {
+    output: E,
+    outstanding: Arc<AtomicUsize>,
+}
+
+impl<E> AckOutput<E> {
+    /// The number of values which were sent but not consumed yet.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.load(SeqCst)
+    }
+
+    /// Whether all the values sent were consumed.
+    pub fn is_acked(&self) -> bool {
+        self.outstanding() == 0
+    }
+}
+
+impl<S, E: OutputEdgeOnce<S>> OutputEdgeOnce<S> for AckOutput<E> {
+    type Item = E::Item;
+
+    fn send_activate_once(self, scheduler: &mut S, item: Self::Item) {
+        self.outstanding.fetch_add(1, SeqCst);
+        self.output.send_activate_once(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeMut<S> for AckOutput<E> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: Self::Item) {
+        self.outstanding.fetch_add(1, SeqCst);
+        self.output.send_activate_mut(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdge<S>> OutputEdge<S> for AckOutput<E> {
+    fn send_activate(&self, scheduler: &mut S, item: Self::Item) {
+        self.outstanding.fetch_add(1, SeqCst);
+        self.output.send_activate(scheduler, item)
+    }
+}
+
+/// The consumer side of a two-way edge.  Receiving a value activates the producer's activator.
+///
+/// The consumer reads values from `receiver`, then activates `ack`, which is expected to activate
+/// the producer.  The producer side is created from the edge's `counter`: both sides share a count
+/// of the values which were sent but not consumed yet, which producers can use for simple flow
+/// control.
+///
+/// See also the `with_ack` method from the `ReceiverExt` trait.
+#[derive(Debug)]
+pub struct AckInput<R, A> {
+    receiver: R,
+    ack: A,
+    outstanding: Arc<AtomicUsize>,
+}
+
+impl<R, A> AckInput<R, A> {
+    /// Create a new input edge reading from `receiver` and activating `ack` on consumption.
+    pub fn new(receiver: R, ack: A) -> Self {
+        AckInput {
+            receiver,
+            ack,
+            outstanding: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The count shared with the producer side of the edge.
+    pub fn counter(&self) -> AckCounter {
+        AckCounter(self.outstanding.clone())
+    }
+
+    /// The number of values which were sent but not consumed yet.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.load(SeqCst)
+    }
+
+    /// Record that a value was consumed.  Values received while nothing was sent, e.g. the initial
+    /// value of the port, are not counted.
+    fn consumed(&self) {
+        let _ = self
+            .outstanding
+            .fetch_update(SeqCst, SeqCst, |count| count.checked_sub(1));
+    }
+}
+
+impl<S, R: ReceiverOnce, A: ActivatorOnce<S>> InputEdgeOnce<S> for AckInput<R, A> {
+    type Item = R::Item;
+
+    fn recv_activate_once(self, scheduler: &mut S) -> Self::Item {
+        self.consumed();
+        let item = self.receiver.recv_once();
+        self.ack.activate_once(scheduler);
+        item
+    }
+}
+
+impl<S, R: ReceiverMut, A: ActivatorMut<S>> InputEdgeMut<S> for AckInput<R, A> {
+    fn recv_activate_mut(&mut self, scheduler: &mut S) -> Self::Item {
+        self.consumed();
+        let item = self.receiver.recv_mut();
+        self.ack.activate_mut(scheduler);
+        item
+    }
+}
+
+impl<S, R: Receiver, A: Activator<S>> InputEdge<S> for AckInput<R, A> {
+    fn recv_activate(&self, scheduler: &mut S) -> Self::Item {
+        self.consumed();
+        let item = self.receiver.recv();
+        self.ack.activate(scheduler);
+        item
+    }
+}
+
+/// Receivers which can be combined by a `ZipInput`.  This is implemented for tuples of up to six
+/// receivers of `Option` values, where `None` indicates that no new value was sent.
+pub trait ZipReceivers {
+    /// The latched values: a tuple holding the latest value received on each receiver, if any.
+    type Latch: Clone + Default;
+
+    /// Receive from each receiver, replacing the latched values for which a new one was sent.
+    fn recv_latch(&mut self, latch: &mut Self::Latch);
+}
+
+macro_rules! auto_impl_zip_receivers {
+    ($(($([$R:ident, $T:ident, $i:tt]),*))*) => {$(
+        impl<$($T: Clone, $R: ReceiverMut<Item = Option<$T>>,)*> ZipReceivers for ($($R,)*) {
+            type Latch = ($(Option<$T>,)*);
+
+            fn recv_latch(&mut self, latch: &mut Self::Latch) {
+                $(
+                    if let Some(item) = self.$i.recv_mut() {
+                        latch.$i = Some(item);
+                    }
+                )*
+            }
+        }
+    )*};
+}
+
+auto_impl_zip_receivers! {
+    ([R0, T0, 0])
+    ([R0, T0, 0], [R1, T1, 1])
+    ([R0, T0, 0], [R1, T1, 1], [R2, T2, 2])
+    ([R0, T0, 0], [R1, T1, 1], [R2, T2, 2], [R3, T3, 3])
+    ([R0, T0, 0], [R1, T1, 1], [R2, T2, 2], [R3, T3, 3], [R4, T4, 4])
+    ([R0, T0, 0], [R1, T1, 1], [R2, T2, 2], [R3, T3, 3], [R4, T4, 4], [R5, T5, 5])
+}
+
+/// An input edge combining several receivers, and yielding the latest value received on each of
+/// them.
+///
+/// Each time the edge is read, it receives from all its receivers and latches the values which
+/// were sent since the previous read.  It then yields a tuple of the latched values, so that
+/// values from inputs which were not updated are re-emitted.  Inputs which never received a value
+/// yield `None`.
+///
+/// Since the latch is local state, the edge can be used in single-use or reusable nodes, but is
+/// not an `InputEdge`.  It usually goes with an activator firing on any input, such as the
+/// `AnyActivator`s of a `MergeActivator` or a single activator shared by the producers.
+#[derive(Debug)]
+pub struct ZipInput<R: ZipReceivers> {
+    receivers: R,
+    latch: R::Latch,
+}
+
+impl<R: ZipReceivers> ZipInput<R> {
+    /// Create a new edge combining a tuple of receivers.
+    pub fn new(receivers: R) -> Self {
+        ZipInput {
+            receivers,
+            latch: R::Latch::default(),
+        }
+    }
+
+    /// The latched values, as of the last read.
+    pub fn latest(&self) -> &R::Latch {
+        &self.latch
+    }
+}
+
+impl<S, R: ZipReceivers> InputEdgeOnce<S> for ZipInput<R> {
+    type Item = R::Latch;
+
+    fn recv_activate_once(mut self, _: &mut S) -> Self::Item {
+        self.receivers.recv_latch(&mut self.latch);
+        self.latch
+    }
+}
+
+impl<S, R: ZipReceivers> InputEdgeMut<S> for ZipInput<R> {
+    fn recv_activate_mut(&mut self, _: &mut S) -> Self::Item {
+        self.receivers.recv_latch(&mut self.latch);
+        self.latch.clone()
+    }
+}
+
 macro_rules! auto_type_item {
     (! $T:ty) => {
         type Item = $T;
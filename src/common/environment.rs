@@ -0,0 +1,467 @@
+//! Per-node execution environments.
+//!
+//! Some nodes need the worker thread executing them to be set up in a particular way: an audio
+//! callback wants a higher thread priority, and DSP code often runs with denormal numbers flushed
+//! to zero, which would silently change the results of the other nodes if left enabled.  Wrapping
+//! such a node in an `InEnvironment` applies its `Environment` on the worker just before the node
+//! runs, and restores the previous settings right after, so that nodes with different requirements
+//! can share the same workers:
+//!
+//! ```rust,ignore
+//! let environment = (
+//!     ThreadPriority::new(-10),
+//!     // SAFETY: `mix` only does floating-point arithmetic meant to run without denormals.
+//!     unsafe { FloatMode::new().flush_denormals(true) },
+//! );
+//! b.node(InEnvironment::new(
+//!     TaskNode { inputs: (...), outputs: (...), task: mix },
+//!     environment,
+//! ));
+//! ```
+//!
+//! Environments are composed with tuples, which enter their components in order and exit them in
+//! the reverse order.  Arbitrary setup is done with a pair of closures (see `Hooks`).  The previous
+//! settings are restored even if the node panics.
+//!
+//! Changing the floating-point mode is unsafe: the compiler assumes the default mode for all the
+//! code it generates, so that a node running under another mode must be written with that in mind
+//! (see `FloatMode`).
+//!
+//! Note that the environment only applies to the node itself: nodes which it schedules are
+//! executed with the worker's own settings, possibly on another worker.
+
+use std::fmt;
+
+use api::prelude::*;
+
+/// Settings of the current thread, applied before executing a node and restored afterwards.
+pub trait Environment {
+    /// The previous settings, as saved by `enter`.
+    type Saved;
+
+    /// Apply the settings to the current thread, and return the previous ones.
+    fn enter(&self) -> Self::Saved;
+
+    /// Restore the settings saved by `enter`.
+    fn exit(&self, saved: Self::Saved);
+}
+
+impl Environment for () {
+    type Saved = ();
+
+    fn enter(&self) {}
+
+    fn exit(&self, _saved: ()) {}
+}
+
+impl<A: Environment, B: Environment> Environment for (A, B) {
+    type Saved = (A::Saved, B::Saved);
+
+    fn enter(&self) -> Self::Saved {
+        let a = self.0.enter();
+        (a, self.1.enter())
+    }
+
+    fn exit(&self, (a, b): Self::Saved) {
+        self.1.exit(b);
+        self.0.exit(a)
+    }
+}
+
+impl<A: Environment, B: Environment, C: Environment> Environment for (A, B, C) {
+    type Saved = (A::Saved, B::Saved, C::Saved);
+
+    fn enter(&self) -> Self::Saved {
+        let a = self.0.enter();
+        let b = self.1.enter();
+        (a, b, self.2.enter())
+    }
+
+    fn exit(&self, (a, b, c): Self::Saved) {
+        self.2.exit(c);
+        self.1.exit(b);
+        self.0.exit(a)
+    }
+}
+
+/// An environment defined by a pair of closures: `enter` is called before the node runs, and its
+/// result is passed to `exit` afterwards.
+pub struct Hooks<P, E> {
+    enter: P,
+    exit: E,
+}
+
+impl<P, E> fmt::Debug for Hooks<P, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hooks").finish()
+    }
+}
+
+impl<T, P: Fn() -> T, E: Fn(T)> Hooks<P, E> {
+    /// Create an environment calling `enter` before the node and `exit` after it.
+    pub fn new(enter: P, exit: E) -> Self {
+        Hooks { enter, exit }
+    }
+}
+
+impl<T, P: Fn() -> T, E: Fn(T)> Environment for Hooks<P, E> {
+    type Saved = T;
+
+    fn enter(&self) -> T {
+        (self.enter)()
+    }
+
+    fn exit(&self, saved: T) {
+        (self.exit)(saved)
+    }
+}
+
+/// The rounding modes of floating-point operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round to the nearest value, ties to even.  This is the default.
+    Nearest,
+    /// Round toward negative infinity.
+    Down,
+    /// Round toward positive infinity.
+    Up,
+    /// Round toward zero.
+    TowardZero,
+}
+
+/// The floating-point mode of the current thread.
+///
+/// This is only supported on x86, x86-64 and AArch64, where it sets the SSE control register
+/// (respectively the FPCR register); on other architectures the mode is left unchanged.
+///
+/// Rust and LLVM assume that all code runs in the default floating-point mode: they may fold
+/// floating-point operations at compile time, reorder them across the mode switch, or rely on the
+/// default mode in the standard library (e.g. when formatting or parsing floats).  The node runs
+/// entirely under the mode, so that the methods setting a non-default mode are `unsafe`.  A mode
+/// created with `new` alone leaves the settings unchanged, and is safe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FloatMode {
+    rounding: Option<Rounding>,
+    flush_denormals: Option<bool>,
+}
+
+impl FloatMode {
+    /// Create a mode leaving all the settings unchanged.
+    pub fn new() -> Self {
+        FloatMode::default()
+    }
+
+    /// Set the rounding mode.
+    ///
+    /// # Safety
+    ///
+    /// Running code compiled with the default rounding mode under another one is undefined
+    /// behavior.  The caller must ensure that the node's results do not depend on floating-point
+    /// operations which the compiler may have evaluated or transformed assuming the default
+    /// rounding mode, including in the functions it calls.
+    pub unsafe fn rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = Some(rounding);
+        self
+    }
+
+    /// Flush denormal results and inputs to zero, or handle them as specified by IEEE 754.
+    ///
+    /// # Safety
+    ///
+    /// Flushing denormals is a non-default mode: the same requirements as for `rounding` apply.
+    pub unsafe fn flush_denormals(mut self, flush: bool) -> Self {
+        self.flush_denormals = Some(flush);
+        self
+    }
+
+    /// Compute the value of the control register implementing this mode.
+    #[cfg_attr(
+        not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")),
+        allow(dead_code)
+    )]
+    fn apply(
+        &self,
+        mut control: u64,
+        rounding_shift: u32,
+        rounding_bits: [u64; 4],
+        flush: u64,
+    ) -> u64 {
+        if let Some(rounding) = self.rounding {
+            let bits = rounding_bits[rounding as usize];
+            control = control & !(0b11 << rounding_shift) | bits << rounding_shift;
+        }
+        match self.flush_denormals {
+            Some(true) => control |= flush,
+            Some(false) => control &= !flush,
+            None => {}
+        }
+        control
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod control {
+    /// The rounding control field of MXCSR, in the order of `Rounding`.
+    pub const ROUNDING_SHIFT: u32 = 13;
+    pub const ROUNDING_BITS: [u64; 4] = [0b00, 0b01, 0b10, 0b11];
+    /// The flush-to-zero and denormals-are-zero flags.
+    pub const FLUSH: u64 = 1 << 15 | 1 << 6;
+
+    pub fn get() -> u64 {
+        let mut mxcsr: u32 = 0;
+        unsafe {
+            ::std::arch::asm!("stmxcsr [{}]", in(reg) &mut mxcsr, options(nostack));
+        }
+        mxcsr as u64
+    }
+
+    pub fn set(control: u64) {
+        let mxcsr = control as u32;
+        unsafe {
+            ::std::arch::asm!("ldmxcsr [{}]", in(reg) &mxcsr, options(nostack, readonly));
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod control {
+    /// The RMode field of FPCR, in the order of `Rounding`.
+    pub const ROUNDING_SHIFT: u32 = 22;
+    pub const ROUNDING_BITS: [u64; 4] = [0b00, 0b10, 0b01, 0b11];
+    /// The flush-to-zero flag.
+    pub const FLUSH: u64 = 1 << 24;
+
+    pub fn get() -> u64 {
+        let fpcr: u64;
+        unsafe {
+            ::std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack));
+        }
+        fpcr
+    }
+
+    pub fn set(control: u64) {
+        unsafe {
+            ::std::arch::asm!("msr fpcr, {}", in(reg) control, options(nostack));
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+impl Environment for FloatMode {
+    type Saved = u64;
+
+    fn enter(&self) -> u64 {
+        let saved = control::get();
+        let control = self.apply(
+            saved,
+            control::ROUNDING_SHIFT,
+            control::ROUNDING_BITS,
+            control::FLUSH,
+        );
+        if control != saved {
+            control::set(control);
+        }
+        saved
+    }
+
+    fn exit(&self, saved: u64) {
+        control::set(saved)
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+impl Environment for FloatMode {
+    type Saved = ();
+
+    fn enter(&self) {}
+
+    fn exit(&self, _saved: ()) {}
+}
+
+/// The scheduling priority of the current thread, as a nice value: from -20 (highest priority) to
+/// 19 (lowest priority).
+///
+/// This is only supported on Linux, where each thread has its own nice value; on other systems the
+/// priority is left unchanged.  Raising the priority of a thread (lowering its nice value) requires
+/// the `CAP_SYS_NICE` capability, or a high enough `RLIMIT_NICE`: without them, the priority is
+/// left unchanged.  For the same reason, the priority is only lowered if the worker will be allowed
+/// to restore its own priority after the node has executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadPriority {
+    nice: i32,
+}
+
+impl ThreadPriority {
+    /// Create a priority with the nice value `nice`, clamped to the range from -20 to 19.
+    pub fn new(nice: i32) -> Self {
+        ThreadPriority {
+            nice: nice.clamp(-20, 19),
+        }
+    }
+
+    /// The nice value.
+    pub fn nice(&self) -> i32 {
+        self.nice
+    }
+
+    /// The nice value of the current thread, if supported.
+    pub fn current() -> Option<i32> {
+        priority::get()
+    }
+}
+
+impl Environment for ThreadPriority {
+    type Saved = Option<i32>;
+
+    fn enter(&self) -> Option<i32> {
+        let saved = priority::get()?;
+        if saved == self.nice {
+            return None;
+        }
+        // Don't lower the priority of the worker for good.
+        if self.nice > saved && !priority::can_raise(saved) {
+            return None;
+        }
+        if priority::set(self.nice) {
+            Some(saved)
+        } else {
+            None
+        }
+    }
+
+    fn exit(&self, saved: Option<i32>) {
+        if let Some(nice) = saved {
+            priority::set(nice);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod priority {
+    use std::fs;
+    use std::os::raw::{c_int, c_ulong};
+
+    /// The `which` argument selecting a process, or a thread on Linux.
+    const PRIO_PROCESS: c_int = 0;
+
+    /// The resource limit on the nice value an unprivileged thread can raise its priority to.
+    const RLIMIT_NICE: c_int = 13;
+
+    /// The capability allowing to raise the priority of a thread without limits.
+    const CAP_SYS_NICE: u32 = 23;
+
+    #[repr(C)]
+    struct Rlimit {
+        cur: c_ulong,
+        _max: c_ulong,
+    }
+
+    extern "C" {
+        fn getpriority(which: c_int, who: u32) -> c_int;
+        fn setpriority(which: c_int, who: u32, prio: c_int) -> c_int;
+        fn getrlimit(resource: c_int, rlim: *mut Rlimit) -> c_int;
+        fn __errno_location() -> *mut c_int;
+    }
+
+    /// The nice value of the calling thread.
+    pub fn get() -> Option<i32> {
+        // -1 is a valid nice value: errors are told apart by errno.
+        unsafe {
+            *__errno_location() = 0;
+            let nice = getpriority(PRIO_PROCESS, 0);
+            if nice == -1 && *__errno_location() != 0 {
+                None
+            } else {
+                Some(nice)
+            }
+        }
+    }
+
+    /// Set the nice value of the calling thread, and return whether it succeeded.
+    pub fn set(nice: i32) -> bool {
+        unsafe { setpriority(PRIO_PROCESS, 0, nice) == 0 }
+    }
+
+    /// Whether the calling thread may raise its priority up to the nice value `nice`.
+    pub fn can_raise(nice: i32) -> bool {
+        // `RLIMIT_NICE` is a ceiling on `20 - nice`.
+        let mut limit = Rlimit { cur: 0, _max: 0 };
+        let queried = unsafe { getrlimit(RLIMIT_NICE, &mut limit) } == 0;
+        if queried && (20 - nice) as c_ulong <= limit.cur {
+            return true;
+        }
+        fs::read_to_string("/proc/thread-self/status")
+            .ok()
+            .and_then(|status| {
+                let capabilities = status.lines().find(|line| line.starts_with("CapEff:"))?;
+                u64::from_str_radix(capabilities["CapEff:".len()..].trim(), 16).ok()
+            })
+            .is_some_and(|capabilities| capabilities & (1 << CAP_SYS_NICE) != 0)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod priority {
+    pub fn get() -> Option<i32> {
+        None
+    }
+
+    pub fn set(_nice: i32) -> bool {
+        false
+    }
+
+    pub fn can_raise(_nice: i32) -> bool {
+        false
+    }
+}
+
+/// Restores the settings of an environment when dropped, including when the node panics.
+struct Restore<'a, E: Environment + 'a> {
+    environment: &'a E,
+    saved: Option<E::Saved>,
+}
+
+impl<'a, E: Environment> Restore<'a, E> {
+    fn enter(environment: &'a E) -> Self {
+        Restore {
+            environment,
+            saved: Some(environment.enter()),
+        }
+    }
+}
+
+impl<'a, E: Environment> Drop for Restore<'a, E> {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            self.environment.exit(saved)
+        }
+    }
+}
+
+/// A node executed in an environment.  See the module documentation.
+#[derive(Debug)]
+pub struct InEnvironment<N, E> {
+    node: N,
+    environment: E,
+}
+
+impl<N, E: Environment> InEnvironment<N, E> {
+    /// Execute `node` in `environment`.
+    pub fn new(node: N, environment: E) -> Self {
+        InEnvironment { node, environment }
+    }
+}
+
+impl<S, N: NodeOnce<S>, E: Environment> NodeOnce<S> for InEnvironment<N, E> {
+    fn execute_once(self, scheduler: &mut S) {
+        let _restore = Restore::enter(&self.environment);
+        self.node.execute_once(scheduler)
+    }
+}
+
+impl<S, N: NodeMut<S>, E: Environment> NodeMut<S> for InEnvironment<N, E> {
+    fn execute_mut(&mut self, scheduler: &mut S) {
+        let _restore = Restore::enter(&self.environment);
+        self.node.execute_mut(scheduler)
+    }
+}
@@ -0,0 +1,329 @@
+//! A type-erased layer for edges and task nodes.
+//!
+//! Every `TaskNode` is generic over the types of its edges and of its task, so that large graphs
+//! instantiate the node implementations (and the runtime code executing them) once per node.
+//! This is the fastest option at runtime, but it can blow up compile times and binary sizes.
+//!
+//! The types in this module trade a virtual call per edge operation for fewer instantiations:
+//!
+//!  - `ErasedEdge` hides the type of an input or output edge behind a trait object, so that all
+//!    the edges transferring the same type of items share the same type.  The aliases
+//!    `ErasedInputOnce`, `ErasedInput`, `ErasedOutputOnce` and `ErasedOutput` select whether the
+//!    edge is single-use or reusable.
+//!  - `ErasedTaskNode` bundles erased edges with a boxed function, and is only instantiated once
+//!    per scheduler, input and output types.  The aliases `ErasedTaskNodeOnce` and
+//!    `ErasedTaskNodeMut` are used for single-use and reusable graphs respectively.
+//!
+//! Erasure is opt-in and selected per node: erased nodes can be mixed freely with `TaskNode`s in
+//! the same graph.  Tuples of edges are themselves edges, so that a node with several inputs
+//! or outputs still only needs one erased edge for each direction.
+//!
+//! Small edges, such as a port sender with its activator, are stored inline in the `ErasedEdge`,
+//! so that erasing them does not allocate.  Edges larger than `INLINE_SIZE` bytes, or aligned on
+//! more than a pointer, are boxed.  Since trait objects can't be consumed by value without a box,
+//! single-use edges are erased through the `InputEdgeTake` and `OutputEdgeTake` traits, which
+//! consume the edge through a mutable reference.
+
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ptr;
+
+use api::prelude::*;
+use common::edge::OutputEdgeExt;
+
+/// The inline storage of an erased edge: four pointers.
+type Inline = MaybeUninit<[usize; 4]>;
+
+/// The maximum size of the edges stored inline in an `ErasedEdge`, in bytes.
+pub const INLINE_SIZE: usize = mem::size_of::<Inline>();
+
+/// Where an erased edge is stored.
+enum Storage<E: ?Sized> {
+    /// An edge which fits in the buffer.  `cast` converts a pointer to the buffer into a pointer to
+    /// the trait object, which is recomputed on each access since the buffer moves along with the
+    /// edge.
+    Inline {
+        buffer: Inline,
+        cast: fn(*mut u8) -> *mut E,
+        /// The edge is owned, and only `Send` or `Sync` if `E` is.
+        _marker: PhantomData<Box<E>>,
+    },
+    Boxed(Box<E>),
+}
+
+/// A type-erased edge.  `E` is the trait object type; see the aliases below.
+///
+/// Edges of up to `INLINE_SIZE` bytes are stored inline, and larger ones in a `Box`.
+pub struct ErasedEdge<E: ?Sized> {
+    storage: Storage<E>,
+}
+
+impl<E: ?Sized> ErasedEdge<E> {
+    /// Erase `edge`, inline if it fits.  `cast` must convert a pointer to an `X` into a pointer to
+    /// `E`, and `boxed` a `Box<X>` into a `Box<E>`: both are unsizing coercions, which can't be
+    /// expressed generically.
+    fn new<X>(edge: X, cast: fn(*mut u8) -> *mut E, boxed: fn(Box<X>) -> Box<E>) -> Self {
+        let storage = if mem::size_of::<X>() <= mem::size_of::<Inline>()
+            && mem::align_of::<X>() <= mem::align_of::<Inline>()
+        {
+            let mut buffer = Inline::uninit();
+            // SAFETY: the buffer is large and aligned enough for an `X`.
+            unsafe { ptr::write(buffer.as_mut_ptr() as *mut X, edge) };
+            Storage::Inline {
+                buffer,
+                cast,
+                _marker: PhantomData,
+            }
+        } else {
+            Storage::Boxed(boxed(Box::new(edge)))
+        };
+        ErasedEdge { storage }
+    }
+
+    /// Whether the edge is stored inline.
+    pub fn is_inline(&self) -> bool {
+        match self.storage {
+            Storage::Inline { .. } => true,
+            Storage::Boxed(_) => false,
+        }
+    }
+
+    fn get_mut(&mut self) -> &mut E {
+        match self.storage {
+            // SAFETY: the buffer holds the edge written by `new`, which `cast` points to.
+            Storage::Inline {
+                ref mut buffer,
+                cast,
+                ..
+            } => unsafe { &mut *cast(buffer.as_mut_ptr() as *mut u8) },
+            Storage::Boxed(ref mut edge) => edge,
+        }
+    }
+}
+
+impl<E: ?Sized> Drop for ErasedEdge<E> {
+    fn drop(&mut self) {
+        if let Storage::Inline {
+            ref mut buffer,
+            cast,
+            ..
+        } = self.storage
+        {
+            // SAFETY: the edge is dropped exactly once, and the buffer is not used afterwards.
+            unsafe { ptr::drop_in_place(cast(buffer.as_mut_ptr() as *mut u8)) }
+        }
+    }
+}
+
+/// A single-use input edge consumed through a mutable reference.  This is implemented by
+/// `Option`s of single-use input edges, which are left empty.
+pub trait InputEdgeTake<S> {
+    type Item;
+
+    /// Receive from the edge and activate its node.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the edge was already used.
+    fn recv_activate_take(&mut self, scheduler: &mut S) -> Self::Item;
+}
+
+impl<S, I: InputEdgeOnce<S>> InputEdgeTake<S> for Option<I> {
+    type Item = I::Item;
+
+    fn recv_activate_take(&mut self, scheduler: &mut S) -> I::Item {
+        self.take()
+            .expect("The single-use edge was already used.")
+            .recv_activate_once(scheduler)
+    }
+}
+
+/// A single-use output edge consumed through a mutable reference.  This is implemented by
+/// `Option`s of single-use output edges, which are left empty.
+pub trait OutputEdgeTake<S> {
+    type Item;
+
+    /// Send `item` on the edge and activate its node.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the edge was already used.
+    fn send_activate_take(&mut self, scheduler: &mut S, item: Self::Item);
+}
+
+impl<S, O: OutputEdgeOnce<S>> OutputEdgeTake<S> for Option<O> {
+    type Item = O::Item;
+
+    fn send_activate_take(&mut self, scheduler: &mut S, item: O::Item) {
+        self.take()
+            .expect("The single-use edge was already used.")
+            .send_activate_once(scheduler, item)
+    }
+}
+
+/// A type-erased, single-use input edge.
+pub type ErasedInputOnce<'a, S, T> = ErasedEdge<dyn InputEdgeTake<S, Item = T> + Send + Sync + 'a>;
+
+/// A type-erased, reusable input edge.
+pub type ErasedInput<'a, S, T> = ErasedEdge<dyn InputEdgeMut<S, Item = T> + Send + Sync + 'a>;
+
+/// A type-erased, single-use output edge.
+pub type ErasedOutputOnce<'a, S, T> =
+    ErasedEdge<dyn OutputEdgeTake<S, Item = T> + Send + Sync + 'a>;
+
+/// A type-erased, reusable output edge.
+pub type ErasedOutput<'a, S, T> = ErasedEdge<dyn OutputEdgeMut<S, Item = T> + Send + Sync + 'a>;
+
+impl<'a, S, T> ErasedInputOnce<'a, S, T> {
+    /// Erase the type of a single-use input edge.
+    pub fn input_once<I: InputEdgeOnce<S, Item = T> + Send + Sync + 'a>(edge: I) -> Self {
+        ErasedEdge::new(Some(edge), |edge| edge as *mut Option<I>, |edge| edge)
+    }
+}
+
+impl<'a, S, T> ErasedInput<'a, S, T> {
+    /// Erase the type of a reusable input edge.
+    pub fn input<I: InputEdgeMut<S, Item = T> + Send + Sync + 'a>(edge: I) -> Self {
+        ErasedEdge::new(edge, |edge| edge as *mut I, |edge| edge)
+    }
+}
+
+impl<'a, S, T> ErasedOutputOnce<'a, S, T> {
+    /// Erase the type of a single-use output edge.
+    pub fn output_once<O: OutputEdgeOnce<S, Item = T> + Send + Sync + 'a>(edge: O) -> Self {
+        ErasedEdge::new(Some(edge), |edge| edge as *mut Option<O>, |edge| edge)
+    }
+}
+
+impl<'a, S, T> ErasedOutput<'a, S, T> {
+    /// Erase the type of a reusable output edge.
+    pub fn output<O: OutputEdgeMut<S, Item = T> + Send + Sync + 'a>(edge: O) -> Self {
+        ErasedEdge::new(edge, |edge| edge as *mut O, |edge| edge)
+    }
+}
+
+impl<E: ?Sized> OutputEdgeExt for ErasedEdge<E> {}
+
+impl<'a, S, T> InputEdgeOnce<S> for ErasedInputOnce<'a, S, T> {
+    type Item = T;
+
+    fn recv_activate_once(mut self, scheduler: &mut S) -> T {
+        self.get_mut().recv_activate_take(scheduler)
+    }
+}
+
+impl<'a, S, T> InputEdgeOnce<S> for ErasedInput<'a, S, T> {
+    type Item = T;
+
+    fn recv_activate_once(mut self, scheduler: &mut S) -> T {
+        self.get_mut().recv_activate_mut(scheduler)
+    }
+}
+
+impl<'a, S, T> InputEdgeMut<S> for ErasedInput<'a, S, T> {
+    fn recv_activate_mut(&mut self, scheduler: &mut S) -> T {
+        self.get_mut().recv_activate_mut(scheduler)
+    }
+}
+
+impl<'a, S, T> OutputEdgeOnce<S> for ErasedOutputOnce<'a, S, T> {
+    type Item = T;
+
+    fn send_activate_once(mut self, scheduler: &mut S, item: T) {
+        self.get_mut().send_activate_take(scheduler, item)
+    }
+}
+
+impl<'a, S, T> OutputEdgeOnce<S> for ErasedOutput<'a, S, T> {
+    type Item = T;
+
+    fn send_activate_once(mut self, scheduler: &mut S, item: T) {
+        self.get_mut().send_activate_mut(scheduler, item)
+    }
+}
+
+impl<'a, S, T> OutputEdgeMut<S> for ErasedOutput<'a, S, T> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: T) {
+        self.get_mut().send_activate_mut(scheduler, item)
+    }
+}
+
+/// A node bundling type-erased edges with a boxed task.  `I`, `O` and `F` are trait object types;
+/// see the `ErasedTaskNodeOnce` and `ErasedTaskNodeMut` aliases.
+///
+/// Unlike `StrictTask`, the task receives the item of the input edge and returns the item of the
+/// output edge directly: when using tuples of edges, these are tuples of values.
+pub struct ErasedTaskNode<I: ?Sized, O: ?Sized, F: ?Sized> {
+    inputs: ErasedEdge<I>,
+    outputs: ErasedEdge<O>,
+    task: Box<F>,
+}
+
+/// A type-erased node for single-use graphs.
+pub type ErasedTaskNodeOnce<'a, S, I, O> = ErasedTaskNode<
+    dyn InputEdgeTake<S, Item = I> + Send + Sync + 'a,
+    dyn OutputEdgeTake<S, Item = O> + Send + Sync + 'a,
+    dyn FnOnce(I) -> O + Send + Sync + 'a,
+>;
+
+/// A type-erased node for reusable graphs.
+pub type ErasedTaskNodeMut<'a, S, I, O> = ErasedTaskNode<
+    dyn InputEdgeMut<S, Item = I> + Send + Sync + 'a,
+    dyn OutputEdgeMut<S, Item = O> + Send + Sync + 'a,
+    dyn FnMut(I) -> O + Send + Sync + 'a,
+>;
+
+impl<'a, S, I, O> ErasedTaskNodeOnce<'a, S, I, O> {
+    /// Create a single-use node executing `task` once with the item received from `inputs`, and
+    /// sending its result to `outputs`.
+    pub fn new_once<IE, OE, F>(inputs: IE, outputs: OE, task: F) -> Self
+    where
+        IE: InputEdgeOnce<S, Item = I> + Send + Sync + 'a,
+        OE: OutputEdgeOnce<S, Item = O> + Send + Sync + 'a,
+        F: FnOnce(I) -> O + Send + Sync + 'a,
+    {
+        ErasedTaskNode {
+            inputs: ErasedEdge::input_once(inputs),
+            outputs: ErasedEdge::output_once(outputs),
+            task: Box::new(task),
+        }
+    }
+}
+
+impl<'a, S, I, O> ErasedTaskNodeMut<'a, S, I, O> {
+    /// Create a reusable node executing `task` with the item received from `inputs` each time it
+    /// is activated, and sending its result to `outputs`.
+    pub fn new_mut<IE, OE, F>(inputs: IE, outputs: OE, task: F) -> Self
+    where
+        IE: InputEdgeMut<S, Item = I> + Send + Sync + 'a,
+        OE: OutputEdgeMut<S, Item = O> + Send + Sync + 'a,
+        F: FnMut(I) -> O + Send + Sync + 'a,
+    {
+        ErasedTaskNode {
+            inputs: ErasedEdge::input(inputs),
+            outputs: ErasedEdge::output(outputs),
+            task: Box::new(task),
+        }
+    }
+}
+
+impl<'a, S, I, O> NodeOnce<S> for ErasedTaskNodeOnce<'a, S, I, O> {
+    fn execute_once(self, scheduler: &mut S) {
+        let item = self.inputs.recv_activate_once(scheduler);
+        self.outputs
+            .send_activate_once(scheduler, (self.task)(item))
+    }
+}
+
+impl<'a, S, I, O> NodeOnce<S> for ErasedTaskNodeMut<'a, S, I, O> {
+    fn execute_once(mut self, scheduler: &mut S) {
+        self.execute_mut(scheduler)
+    }
+}
+
+impl<'a, S, I, O> NodeMut<S> for ErasedTaskNodeMut<'a, S, I, O> {
+    fn execute_mut(&mut self, scheduler: &mut S) {
+        let item = self.inputs.recv_activate_mut(scheduler);
+        self.outputs.send_activate_mut(scheduler, (self.task)(item))
+    }
+}
@@ -0,0 +1,257 @@
+//! Event logs saved to files, for re-executing live runs offline.
+//!
+//! An `EventLogWriter` saves the batches cut by a `Sequencer` during a live run, and an
+//! `EventLogReader` reads them back to re-drive a fresh runtime with the same events in the same
+//! instants, e.g. to debug a run or to check for regressions:
+//!
+//! ```rust,ignore
+//! // Live run.
+//! let mut writer = EventLogWriter::new(File::create("run.log")?)?;
+//! loop {
+//!     let batch = sequencer.cut();
+//!     writer.write_batch(&batch)?;
+//!     batch.deliver(&mut runtime, &mut edges);
+//!     runtime.execute(4);
+//! }
+//!
+//! // Offline re-execution.
+//! let reader = EventLogReader::new(BufReader::new(File::open("run.log")?))?;
+//! reader.drive(&mut runtime, &mut edges, |runtime| runtime.execute(4))?;
+//! ```
+//!
+//! The log is a text file with one line per instant and per event:
+//!
+//! ```text
+//! # rrs event log 1
+//! instant 0
+//! event <seq> <source> <elapsed nanoseconds> <value>
+//! ```
+//!
+//! Values are written with their `Display` implementation and parsed back with `FromStr`, with
+//! backslashes, tabs and line breaks escaped.  The instants are written even when they have no
+//! events, so that the re-execution runs the same number of instants.
+//!
+//! This format keeps the crate free of dependencies, but only suits values with a textual
+//! representation.  With the `serde` feature, `Batch` and `Event` implement `Serialize` and
+//! `Deserialize` instead, so that logs of structured values can be saved in any serde format,
+//! e.g. one JSON batch per line, and re-driven with `Batch::deliver`.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::Duration;
+
+use api::prelude::*;
+use common::sequencer::{Batch, Event};
+
+/// The first line of the event logs.
+const HEADER: &str = "# rrs event log 1";
+
+/// Saves the batches of a `Sequencer` to a file.  See the module documentation.
+#[derive(Debug)]
+pub struct EventLogWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> EventLogWriter<W> {
+    /// Start a log, writing its header to `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(EventLogWriter { writer })
+    }
+
+    /// Append the events of an instant to the log.
+    pub fn write_batch<T: Display>(&mut self, batch: &Batch<T>) -> io::Result<()> {
+        writeln!(self.writer, "instant {}", batch.instant)?;
+        for event in &batch.events {
+            writeln!(
+                self.writer,
+                "event {} {} {} {}",
+                event.seq,
+                event.source,
+                event.elapsed.as_nanos(),
+                escape(&event.value)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Flush the log and return the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the batches saved by an `EventLogWriter`.  This is an iterator over the batches, in
+/// order.  See the module documentation.
+#[derive(Debug)]
+pub struct EventLogReader<R: BufRead, T> {
+    lines: io::Lines<R>,
+    /// The instant whose line was read last, and whose events are being read.
+    instant: Option<u64>,
+    line: usize,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<R: BufRead, T: FromStr> EventLogReader<R, T> {
+    /// Start reading a log, checking its header.
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        match lines.next() {
+            Some(Ok(ref header)) if header == HEADER => {}
+            Some(Err(error)) => return Err(error),
+            _ => return Err(invalid(1, "not an event log")),
+        }
+        Ok(EventLogReader {
+            lines,
+            instant: None,
+            line: 1,
+            _value: PhantomData,
+        })
+    }
+
+    /// Re-drive a runtime with the events of the log: for each instant, deliver its batch to
+    /// `edges` and call `execute`.  This returns the number of instants.
+    pub fn drive<S, E, F>(
+        self,
+        scheduler: &mut S,
+        edges: &mut [E],
+        mut execute: F,
+    ) -> io::Result<u64>
+    where
+        E: OutputEdgeMut<S, Item = T>,
+        F: FnMut(&mut S),
+    {
+        let mut instants = 0;
+        for batch in self {
+            batch?.deliver(scheduler, edges);
+            execute(scheduler);
+            instants += 1;
+        }
+        Ok(instants)
+    }
+}
+
+impl<R: BufRead, T: FromStr> Iterator for EventLogReader<R, T> {
+    type Item = io::Result<Batch<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let instant = match self.instant.take() {
+            Some(instant) => instant,
+            None => {
+                let line = match self.lines.next()? {
+                    Ok(line) => line,
+                    Err(error) => return Some(Err(error)),
+                };
+                self.line += 1;
+                match parse_instant(self.line, &line) {
+                    Ok(instant) => instant,
+                    Err(error) => return Some(Err(error)),
+                }
+            }
+        };
+
+        let mut events = Vec::new();
+        for line in &mut self.lines {
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => return Some(Err(error)),
+            };
+            self.line += 1;
+            if line.starts_with("instant ") {
+                match parse_instant(self.line, &line) {
+                    Ok(next) => self.instant = Some(next),
+                    Err(error) => return Some(Err(error)),
+                }
+                break;
+            }
+            match parse_event(self.line, &line, instant) {
+                Ok(event) => events.push(event),
+                Err(error) => return Some(Err(error)),
+            }
+        }
+        Some(Ok(Batch { instant, events }))
+    }
+}
+
+/// Parse the line of an instant.
+fn parse_instant(number: usize, line: &str) -> io::Result<u64> {
+    match line.strip_prefix("instant ").map(str::parse) {
+        Some(Ok(instant)) => Ok(instant),
+        _ => Err(invalid(number, "expected an instant")),
+    }
+}
+
+/// Parse the line of an event of the instant `instant`.
+fn parse_event<T: FromStr>(number: usize, line: &str, instant: u64) -> io::Result<Event<T>> {
+    let error = || invalid(number, "malformed event");
+    let mut fields = line
+        .strip_prefix("event ")
+        .ok_or_else(error)?
+        .splitn(4, ' ');
+    let mut integer = || -> io::Result<u64> {
+        fields
+            .next()
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(error)
+    };
+    let seq = integer()?;
+    let source = integer()? as usize;
+    let elapsed = Duration::from_nanos(integer()?);
+    let value = fields
+        .next()
+        .and_then(unescape)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(error)?;
+    Ok(Event {
+        seq,
+        instant,
+        source,
+        elapsed,
+        value,
+    })
+}
+
+/// The error for a malformed line of a log.
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, message),
+    )
+}
+
+/// Write a value on a single line.
+fn escape<T: Display>(value: &T) -> String {
+    let mut escaped = String::new();
+    for c in value.to_string().chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Read a value written by `escape`, or `None` if it is not properly escaped.
+fn unescape(escaped: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        value.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                _ => return None,
+            },
+            c => c,
+        })
+    }
+    Some(value)
+}
@@ -0,0 +1,234 @@
+//! A fuzzing entry point for the builder layer.
+//!
+//! `run_build_script` interprets arbitrary bytes as a sequence of builder calls on the sequential
+//! reusable runtime: creating ports and nodes, adding activators, moving them into other nodes and
+//! declaring the connections, pre-filling ports, nesting namespaces, and finalizing nodes or
+//! dropping activators and ports in arbitrary orders.  Each call is decoded from the next bytes of
+//! the script, so that any input is a valid script.
+//!
+//! The builder layer guarantees that no script panics: malformed sequences, such as activation
+//! cycles without a delay, connections to unknown nodes or nodes finalized without activators, are
+//! reported as a `BuildError`. The `fuzz` directory at the root of the repository holds a
+//! `cargo fuzz` target checking this:
+//!
+//! ```text
+//! cargo fuzz run build_script
+//! ```
+//!
+//! The graphs are only built, never executed: their shape is arbitrary, so that executing them
+//! would mostly exercise the protocol errors of the runtime (e.g. activating a node twice before
+//! it is executed) rather than the builders.
+//!
+//! Moving activators into nodes can create reference cycles between the nodes, which are torn
+//! down before the script returns so that the leak checks of the fuzzer don't report them.
+
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use api::prelude::*;
+use common::builder::{BuildError, GraphSpecExt, ScopedGraphBuilder, ScopedNodeBuilder};
+use common::inspect::{Inspector, NodeId};
+use sequential::multiple_uses::{RcBuilder, RuntimeActivator, Toexec};
+
+/// The maximum nesting of namespaces in a script.
+const MAX_DEPTH: usize = 8;
+
+/// The activators moved into a node.  These are shared with the script, which clears them once
+/// the graph is built.
+type Moved = Arc<Mutex<Vec<RuntimeActivator<'static>>>>;
+
+/// A node of a script, activating the nodes whose activators were moved into it.
+struct ScriptNode {
+    activators: Moved,
+}
+
+impl NodeMut<Toexec<'static>> for ScriptNode {
+    fn execute_mut(&mut self, scheduler: &mut Toexec<'static>) {
+        for activator in self.activators.lock().unwrap().iter_mut() {
+            activator.activate_mut(scheduler)
+        }
+    }
+}
+
+type Builder<'a> = ScopedNodeBuilder<'a, Toexec<'static>, RcBuilder<ScriptNode>>;
+
+/// What a script built.  See `run_build_script`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScriptSummary {
+    /// The number of nodes created.
+    pub nodes: usize,
+    /// The number of activators created.
+    pub activators: usize,
+    /// The number of ports created.
+    pub ports: usize,
+}
+
+/// The state of a running script.
+struct Script<'d, 'a> {
+    data: &'d [u8],
+    /// The nodes which are not finalized yet.
+    open: Vec<Builder<'a>>,
+    /// The activators of all the nodes, see `Moved`.
+    moved: Vec<Moved>,
+    /// The activators which were not moved into a node, along with the identifier of their node.
+    activators: Vec<(RuntimeActivator<'static>, Option<NodeId>)>,
+    ports: Vec<<<Toexec<'static> as PortSpec<i32>>::Port as Port>::Sender>,
+    summary: ScriptSummary,
+}
+
+impl<'d, 'a> Script<'d, 'a> {
+    /// The next byte of the script, if any.
+    fn next(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(byte)
+    }
+
+    /// An index into a collection of `len` elements, if not empty.
+    fn index(&mut self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        Some(self.next().unwrap_or(0) as usize % len)
+    }
+
+    /// Remove the open node selected by the next byte, if any.
+    fn take(&mut self) -> Option<Builder<'a>> {
+        let i = self.index(self.open.len())?;
+        Some(self.open.swap_remove(i))
+    }
+
+    fn run(&mut self, b: &mut ScopedGraphBuilder<'a, Toexec<'static>>, depth: usize) {
+        while let Some(op) = self.next() {
+            match op % 12 {
+                0 => {
+                    let (sender, _) = b.port(0).split();
+                    self.ports.push(sender);
+                    self.summary.ports += 1;
+                }
+                1 => {
+                    let activators = Moved::default();
+                    self.moved.push(activators.clone());
+                    let node = ScriptNode { activators };
+                    let node = match self.next() {
+                        Some(label) if label % 2 == 1 => b.node_named(format!("n{}", label), node),
+                        _ => b.node(node),
+                    };
+                    self.open.push(node);
+                    self.summary.nodes += 1;
+                }
+                2 => {
+                    if let Some(mut node) = self.take() {
+                        self.activators.push((node.add_activator(), node.id()));
+                        self.summary.activators += 1;
+                        self.open.push(node);
+                    }
+                }
+                3 => {
+                    if let Some(j) = self.index(self.activators.len()) {
+                        let (activator, target) = self.activators.swap_remove(j);
+                        if let Some(mut node) = self.take() {
+                            node.borrow_mut().activators.lock().unwrap().push(activator);
+                            self.open.push(match target {
+                                Some(target) => node.activates(target),
+                                None => node,
+                            });
+                        }
+                    }
+                }
+                4 => {
+                    if let Some(node) = self.take() {
+                        drop(node);
+                    }
+                }
+                5 => {
+                    if let Some(j) = self.index(self.ports.len()) {
+                        let sender = self.ports.swap_remove(j);
+                        let value = self.next().unwrap_or(0) as i32;
+                        if let Some(node) = self.take() {
+                            self.open.push(node.preload(sender, value));
+                        }
+                    }
+                }
+                6 => {
+                    if let Some(node) = self.take() {
+                        self.open.push(node.delayed());
+                    }
+                }
+                7 => {
+                    let tag = self.next().unwrap_or(0);
+                    if let Some(node) = self.take() {
+                        let node = node.describe(format!("node {}", tag));
+                        self.open.push(node.tag(format!("t{}", tag % 4)));
+                    }
+                }
+                8 => {
+                    let target = NodeId(self.next().unwrap_or(0) as usize);
+                    if let Some(node) = self.take() {
+                        self.open.push(node.activates(target));
+                    }
+                }
+                9 => {
+                    let len = self.next().unwrap_or(0) as usize;
+                    if depth < MAX_DEPTH {
+                        let (nested, rest) = self.data.split_at(len.min(self.data.len()));
+                        self.data = nested;
+                        b.namespace(&format!("ns{}", depth), |b| self.run(b, depth + 1));
+                        self.data = rest;
+                    }
+                }
+                10 => {
+                    if let Some(j) = self.index(self.activators.len()) {
+                        self.activators.swap_remove(j);
+                    }
+                }
+                _ => {
+                    if let Some(j) = self.index(self.ports.len()) {
+                        self.ports.swap_remove(j);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Interpret `data` as a sequence of builder calls, and return what was built, or the first
+/// malformed call.  See the module documentation.
+///
+/// The first byte selects whether the scope has an inspector, which enables the cycle checks, and
+/// the order in which the remaining nodes are finalized at the end of the script.
+pub fn run_build_script(data: &[u8]) -> Result<ScriptSummary, BuildError> {
+    let (&flags, data) = match data.split_first() {
+        Some(split) => split,
+        None => return Ok(ScriptSummary::default()),
+    };
+    let mut runtime = Toexec::new();
+    let inspector = Inspector::new();
+    let mut moved = Vec::new();
+    let result = runtime.try_build_scope(|b| {
+        if flags & 1 == 1 {
+            b.inspect(&inspector);
+        }
+        let mut script = Script {
+            data,
+            open: Vec::new(),
+            moved: Vec::new(),
+            activators: Vec::new(),
+            ports: Vec::new(),
+            summary: ScriptSummary::default(),
+        };
+        script.run(b, 0);
+        let mut open = mem::take(&mut script.open);
+        if flags & 2 == 2 {
+            open.reverse();
+        }
+        drop(open);
+        moved = mem::take(&mut script.moved);
+        script.summary
+    });
+    // Break the reference cycles between the nodes, so that the graph is freed.
+    for activators in moved {
+        activators.lock().unwrap().clear();
+    }
+    result
+}
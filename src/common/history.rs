@@ -0,0 +1,176 @@
+//! Bounded histories of the values flowing through an edge.
+//!
+//! Reactive graphs often carry *signals*: a value sent on the same edge at each instant, e.g. a
+//! measurement sampled by a timer.  Computing a trend or drawing a sparkline needs the last few
+//! values of the signal, which each consumer would otherwise keep in its own task state.  A
+//! `SignalHistory` keeps them instead: the producer's output edge is wrapped with
+//! `SignalHistory::output`, which records a copy of each value sent, and any number of consumers
+//! can query the last values with `history`:
+//!
+//! ```rust,ignore
+//! let temperature = SignalHistory::new(16);
+//! let output = temperature.output(sender.with_activator(display.add_activator()));
+//! ...
+//! let trend = temperature
+//!     .history()
+//!     .windows(2)
+//!     .map(|pair| pair[1].1.unwrap() - pair[0].1.unwrap())
+//!     .sum::<f64>();
+//! ```
+//!
+//! Each value is recorded with its instant number, i.e. the number of values sent before it on the
+//! edge, so that gaps are visible once older values have been evicted from the ring.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use api::prelude::*;
+
+/// The values recorded by a history.
+#[derive(Debug)]
+struct HistoryState<T> {
+    /// The number of values sent so far.
+    instants: u64,
+    /// The last values, oldest first, along with their instant numbers.
+    values: VecDeque<(u64, T)>,
+}
+
+/// Records the last values sent on an edge.  See the module documentation.
+///
+/// Histories are cheap to clone; clones share the same values.
+pub struct SignalHistory<T> {
+    capacity: usize,
+    state: Arc<Mutex<HistoryState<T>>>,
+}
+
+impl<T> Clone for SignalHistory<T> {
+    fn clone(&self) -> Self {
+        SignalHistory {
+            capacity: self.capacity,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SignalHistory<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("SignalHistory")
+            .field("capacity", &self.capacity)
+            .field("instants", &state.instants)
+            .field("values", &state.values)
+            .finish()
+    }
+}
+
+impl<T> SignalHistory<T> {
+    /// Create a history keeping the last `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "A signal history must hold at least one value."
+        );
+
+        SignalHistory {
+            capacity,
+            state: Arc::new(Mutex::new(HistoryState {
+                instants: 0,
+                values: VecDeque::with_capacity(capacity),
+            })),
+        }
+    }
+
+    /// The maximum number of values kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of values sent so far, including the evicted ones.
+    pub fn instants(&self) -> u64 {
+        self.state.lock().unwrap().instants
+    }
+
+    /// Record the values sent on `output`.
+    pub fn output<E>(&self, output: E) -> HistoryOutput<E, T> {
+        HistoryOutput {
+            output,
+            history: self.clone(),
+        }
+    }
+
+    fn record(&self, value: T) {
+        let mut state = self.state.lock().unwrap();
+        if state.values.len() == self.capacity {
+            state.values.pop_front();
+        }
+        let instant = state.instants;
+        state.values.push_back((instant, value));
+        state.instants += 1;
+    }
+}
+
+impl<T: Clone> SignalHistory<T> {
+    /// The last values sent, oldest first, along with their instant numbers.
+    pub fn history(&self) -> Vec<(u64, T)> {
+        self.state.lock().unwrap().values.iter().cloned().collect()
+    }
+
+    /// The last `n` values sent, oldest first.  Fewer values are returned if fewer were kept.
+    pub fn last_n(&self, n: usize) -> Vec<(u64, T)> {
+        let state = self.state.lock().unwrap();
+        let skip = state.values.len().saturating_sub(n);
+        state.values.iter().skip(skip).cloned().collect()
+    }
+
+    /// The last value sent, if any.
+    pub fn last(&self) -> Option<(u64, T)> {
+        self.state.lock().unwrap().values.back().cloned()
+    }
+}
+
+/// The producer's side of an edge with a history.  See `SignalHistory::output`.
+#[derive(Debug)]
+pub struct HistoryOutput<E, T> {
+    output: E,
+    history: SignalHistory<T>,
+}
+
+impl<S, E> OutputEdgeOnce<S> for HistoryOutput<E, E::Item>
+where
+    E: OutputEdgeOnce<S>,
+    E::Item: Clone,
+{
+    type Item = E::Item;
+
+    fn send_activate_once(self, scheduler: &mut S, item: Self::Item) {
+        self.history.record(item.clone());
+        self.output.send_activate_once(scheduler, item)
+    }
+}
+
+impl<S, E> OutputEdgeMut<S> for HistoryOutput<E, E::Item>
+where
+    E: OutputEdgeMut<S>,
+    E::Item: Clone,
+{
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: Self::Item) {
+        self.history.record(item.clone());
+        self.output.send_activate_mut(scheduler, item)
+    }
+}
+
+impl<S, E> OutputEdge<S> for HistoryOutput<E, E::Item>
+where
+    E: OutputEdge<S>,
+    E::Item: Clone,
+{
+    fn send_activate(&self, scheduler: &mut S, item: Self::Item) {
+        self.history.record(item.clone());
+        self.output.send_activate(scheduler, item)
+    }
+}
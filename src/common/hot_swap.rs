@@ -0,0 +1,256 @@
+//! Live upgrades of parts of a running graph.
+//!
+//! Always-on applications can't be stopped to replace part of their graph.  A `HotSwap` is a
+//! boundary in front of a subgraph: the producers send to the edge returned by `HotSwap::input`,
+//! which forwards to the inputs of the current version of the subgraph.  A new version is built
+//! off-line, i.e. its nodes are created but not connected to the producers, then swapped in with
+//! an `Upgrade`:
+//!
+//! ```rust,ignore
+//! let output = SharedEdge::new(sender.as_data_output());
+//! let (v1, state1) = runtime.build_scope(|b| b.instantiate("v1", &Filter::new(), output.clone()));
+//! let swap = HotSwap::new(v1);
+//! ... // Connect the producers to `swap.input()`, and execute.
+//! let (v2, state2) = runtime.build_scope(|b| b.instantiate("v2", &Filter::new(), output.clone()));
+//! swap.prepare(v2)
+//!     .migrate(state1, state2)
+//!     .commit(&mut runtime, |runtime| check_output(runtime))?;
+//! ```
+//!
+//! Committing an upgrade copies the state of the old version's ports to the new version's ports
+//! (see `Checkpoint`), swaps the new version in, and runs a validation function.  If the
+//! validation fails, the old version is swapped back in.  Its ports were not modified, so that it
+//! resumes from its state before the upgrade.
+//!
+//! The outputs of the subgraph are shared by all the versions, typically by wrapping them in
+//! `SharedEdge`s: the old version is no longer activated once the new one is swapped in, so the
+//! fan-in of the nodes after the subgraph does not change.  Upgrades are committed between
+//! executions, which `Upgrade::commit` enforces by borrowing the runtime mutably.
+
+use std::sync::{Arc, Mutex};
+
+use api::prelude::*;
+use common::edge::OutputEdgeExt;
+
+/// A port whose contents can be saved and restored, e.g. to migrate state across versions of a
+/// subgraph.  Taking a checkpoint does not modify the port.
+pub trait Checkpoint {
+    /// The saved contents of the port.
+    type State;
+
+    /// Save the contents of the port.
+    fn checkpoint(&self) -> Self::State;
+
+    /// Replace the contents of the port with a saved state.
+    fn restore(&self, state: Self::State);
+}
+
+impl<T: Clone> Checkpoint for Mutex<T> {
+    type State = T;
+
+    fn checkpoint(&self) -> T {
+        self.lock().unwrap().clone()
+    }
+
+    fn restore(&self, state: T) {
+        *self.lock().unwrap() = state
+    }
+}
+
+/// The version of the subgraph currently receiving values.
+struct Live<E> {
+    inputs: E,
+    version: usize,
+}
+
+/// A boundary in front of a subgraph which can be upgraded while the graph is running.  See the
+/// module documentation.
+///
+/// `E` is the type of the edge sending values into a version of the subgraph.  Handles are cheap
+/// to clone and share the same current version.
+pub struct HotSwap<E> {
+    live: Arc<Mutex<Live<E>>>,
+}
+
+impl<E> Clone for HotSwap<E> {
+    fn clone(&self) -> Self {
+        HotSwap {
+            live: self.live.clone(),
+        }
+    }
+}
+
+impl<E> HotSwap<E> {
+    /// Create a boundary in front of the first version of a subgraph, whose inputs are `inputs`.
+    pub fn new(inputs: E) -> Self {
+        HotSwap {
+            live: Arc::new(Mutex::new(Live { inputs, version: 0 })),
+        }
+    }
+
+    /// An edge sending values to the current version of the subgraph.  This is the edge the
+    /// producers should be connected to.
+    pub fn input(&self) -> SwapInput<E> {
+        SwapInput {
+            live: self.live.clone(),
+        }
+    }
+
+    /// The number of upgrades committed so far.
+    pub fn version(&self) -> usize {
+        self.live.lock().unwrap().version
+    }
+
+    /// Start an upgrade to the version of the subgraph whose inputs are `inputs`.
+    pub fn prepare<'u>(&self, inputs: E) -> Upgrade<'u, E> {
+        Upgrade {
+            swap: self.clone(),
+            inputs,
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Replace the inputs of the current version, returning the previous ones.
+    fn replace(&self, inputs: E, version: usize) -> E {
+        let mut live = self.live.lock().unwrap();
+        live.version = version;
+        ::std::mem::replace(&mut live.inputs, inputs)
+    }
+}
+
+/// A pending upgrade of a subgraph, created by `HotSwap::prepare`.
+pub struct Upgrade<'u, E> {
+    swap: HotSwap<E>,
+    inputs: E,
+    migrations: Vec<Box<dyn FnOnce() + 'u>>,
+}
+
+impl<'u, E> Upgrade<'u, E> {
+    /// Copy the contents of the port `from` of the old version to the port `to` of the new version
+    /// when committing.
+    pub fn migrate<P, Q>(mut self, from: P, to: Q) -> Self
+    where
+        P: Checkpoint + 'u,
+        Q: Checkpoint<State = P::State> + 'u,
+    {
+        self.migrations
+            .push(Box::new(move || to.restore(from.checkpoint())));
+        self
+    }
+
+    /// Migrate the ports' state, swap the new version in, and call `validate` with the runtime,
+    /// e.g. to execute the graph and check its results.  This returns the new version number.
+    ///
+    /// If `validate` fails, the old version is swapped back in and the error is returned.  The new
+    /// version is dropped.
+    pub fn commit<R, V, Err>(self, runtime: &mut R, validate: V) -> Result<usize, Err>
+    where
+        V: FnOnce(&mut R) -> Result<(), Err>,
+    {
+        let Upgrade {
+            swap,
+            inputs,
+            migrations,
+        } = self;
+
+        for migrate in migrations {
+            migrate()
+        }
+        let version = swap.version();
+        let old = swap.replace(inputs, version + 1);
+
+        match validate(runtime) {
+            Ok(()) => Ok(version + 1),
+            Err(error) => {
+                swap.replace(old, version);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// The edge sending values to the current version of a subgraph.  See `HotSwap::input`.
+pub struct SwapInput<E> {
+    live: Arc<Mutex<Live<E>>>,
+}
+
+impl<E> Clone for SwapInput<E> {
+    fn clone(&self) -> Self {
+        SwapInput {
+            live: self.live.clone(),
+        }
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeOnce<S> for SwapInput<E> {
+    type Item = E::Item;
+
+    fn send_activate_once(mut self, scheduler: &mut S, item: Self::Item) {
+        self.send_activate_mut(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeMut<S> for SwapInput<E> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: Self::Item) {
+        self.live
+            .lock()
+            .unwrap()
+            .inputs
+            .send_activate_mut(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdge<S> for SwapInput<E> {
+    fn send_activate(&self, scheduler: &mut S, item: Self::Item) {
+        self.live
+            .lock()
+            .unwrap()
+            .inputs
+            .send_activate_mut(scheduler, item)
+    }
+}
+
+/// An output edge shared by several owners, e.g. by the versions of a subgraph.
+pub struct SharedEdge<E> {
+    edge: Arc<Mutex<E>>,
+}
+
+impl<E> SharedEdge<E> {
+    /// Share `edge`.
+    pub fn new(edge: E) -> Self {
+        SharedEdge {
+            edge: Arc::new(Mutex::new(edge)),
+        }
+    }
+}
+
+impl<E> Clone for SharedEdge<E> {
+    fn clone(&self) -> Self {
+        SharedEdge {
+            edge: self.edge.clone(),
+        }
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeOnce<S> for SharedEdge<E> {
+    type Item = E::Item;
+
+    fn send_activate_once(mut self, scheduler: &mut S, item: Self::Item) {
+        self.send_activate_mut(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeMut<S> for SharedEdge<E> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: Self::Item) {
+        self.edge.lock().unwrap().send_activate_mut(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdge<S> for SharedEdge<E> {
+    fn send_activate(&self, scheduler: &mut S, item: Self::Item) {
+        self.edge.lock().unwrap().send_activate_mut(scheduler, item)
+    }
+}
+
+impl<E> OutputEdgeExt for SwapInput<E> {}
+impl<E> OutputEdgeExt for SharedEdge<E> {}
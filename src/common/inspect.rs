@@ -0,0 +1,440 @@
+//! Build-time metadata about the nodes of a graph, for use in tooling.
+//!
+//! Nodes are anonymous once built, which makes large graphs hard to make sense of.  An `Inspector`
+//! can be attached to a `ScopedGraphBuilder` to record each node created in the scope, along with
+//! an optional human-readable description and a list of tags set through the node builder:
+//!
+//! ```rust,ignore
+//! let inspector = Inspector::new();
+//! runtime.build_scope(|b| {
+//!     b.inspect(&inspector);
+//!     b.node(task).describe("computes parity").tag("math").add_activator()
+//! });
+//! println!("{}", inspector.summary());
+//! ```
+//!
+//! Nodes created in a namespace (see `ScopedGraphBuilder::namespace`) can be queried together with
+//! `Inspector::subtree`.
+//!
+//! The recorded graph can also be exported to Graphviz with `Inspector::to_dot`, which is mostly
+//! useful to find out why a node never fires due to a missing activator.  Its topological order
+//! is used to shut graphs down from their sources to their sinks (see the `shutdown` module).
+//!
+//! The declared connections are also checked for activation cycles: a node on a cycle waits for
+//! its own activation, so that the nodes of the cycle never fire, unless the cycle goes through a
+//! node which delays its values, i.e. a `DelayNode` or a node whose ports were pre-filled with
+//! `ScopedNodeBuilder::preload`.  Such nodes are recorded as delays (`DelayNode`s are declared
+//! with `ScopedNodeBuilder::delayed`).  Any other cycle is rejected as soon as one of its nodes is
+//! finalized after all of its connections were declared, with a panic naming the nodes of the
+//! cycle (see `CycleError`), or an error in the scopes created with
+//! `GraphSpecExt::try_build_scope`.  This is mostly useful for reusable graphs, which are often
+//! built with feedback loops.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+/// Identifies a node recorded by an `Inspector`.  Identifiers are attributed in creation order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub usize);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Documentation attached to a node at build time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeMetadata {
+    /// The label given with `ScopedGraphBuilder::node_named`, including the namespace.
+    pub label: Option<String>,
+    /// The path of the namespace the node was created in, if any.
+    pub namespace: Option<String>,
+    /// A human-readable description of what the node does.
+    pub description: Option<String>,
+    /// Free-form tags, e.g. the subsystem the node belongs to.
+    pub tags: Vec<String>,
+}
+
+impl NodeMetadata {
+    /// Whether the node has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+/// Displays the metadata as `label: description [tag, ...]`.
+impl fmt::Display for NodeMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref label) = self.label {
+            write!(f, "{}: ", label)?;
+        }
+        write!(
+            f,
+            "{}",
+            self.description.as_deref().unwrap_or("<undocumented>")
+        )?;
+        if !self.tags.is_empty() {
+            write!(f, " [{}]", self.tags.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The records shared by the clones of an `Inspector`.
+#[derive(Debug, Default)]
+struct Records {
+    nodes: Vec<NodeMetadata>,
+    /// The number of activators created for each node.
+    activators: Vec<usize>,
+    /// The declared connections, from the node holding an activator to the activated node.
+    edges: Vec<(NodeId, NodeId)>,
+    /// Whether each node delays its values, which breaks the activation cycles through it.
+    delays: Vec<bool>,
+    ports: Vec<String>,
+}
+
+impl Records {
+    /// A node of the recorded graph, for use in diagnostics.
+    fn cycle_node(&self, id: NodeId) -> CycleNode {
+        CycleNode {
+            id,
+            label: self.nodes[id.0].label.clone(),
+        }
+    }
+
+    /// Find an activation cycle through `id` which goes through no delays.
+    fn cycle_through(&self, id: NodeId) -> Option<Vec<NodeId>> {
+        if self.delays[id.0] {
+            return None;
+        }
+
+        // Depth-first search from `id`, remembering the predecessor of each visited node.
+        let mut predecessors = vec![None; self.nodes.len()];
+        let mut stack = vec![id];
+        while let Some(from) = stack.pop() {
+            for &(source, to) in &self.edges {
+                if source != from || self.delays[to.0] {
+                    continue;
+                }
+                if to == id {
+                    let mut cycle = vec![from];
+                    while let Some(previous) = predecessors[cycle[cycle.len() - 1].0] {
+                        cycle.push(previous);
+                    }
+                    cycle.reverse();
+                    return Some(cycle);
+                }
+                if predecessors[to.0].is_none() {
+                    predecessors[to.0] = Some(from);
+                    stack.push(to);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A node on an activation cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleNode {
+    /// The identifier of the node in the inspector.
+    pub id: NodeId,
+    /// The label of the node, if any.
+    pub label: Option<String>,
+}
+
+impl fmt::Display for CycleNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.label {
+            Some(ref label) => write!(f, "`{}` ({})", label, self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// An activation cycle which goes through no delays.  See the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// The nodes of the cycle, in activation order, starting from the earliest created one.  The
+    /// last node activates the first one.
+    pub nodes: Vec<CycleNode>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "activation cycle without a delay:")?;
+        for node in &self.nodes {
+            write!(f, " {} ->", node)?;
+        }
+        write!(f, " {}", self.nodes[0])
+    }
+}
+
+impl Error for CycleError {}
+
+/// A shared record of the nodes created in the scopes it is attached to, as well as of the labels
+/// of the ports created with `ScopedGraphBuilder::port_named`.
+///
+/// The inspector also counts the activators created for each node.  Since activators are moved
+/// into arbitrary nodes, it cannot tell which node ends up holding them; producers can declare the
+/// connection with `ScopedNodeBuilder::activates` so that it appears in the `to_dot` export.
+///
+/// Inspectors are cheap to clone; clones share the same records.  The same inspector can be
+/// attached to multiple scopes, including scopes created dynamically from executing tasks on the
+/// same thread.
+#[derive(Debug, Clone, Default)]
+pub struct Inspector {
+    records: Rc<RefCell<Records>>,
+}
+
+impl Inspector {
+    /// Create an inspector with no recorded nodes.
+    pub fn new() -> Self {
+        Inspector::default()
+    }
+
+    /// Record a new node without metadata.
+    pub(crate) fn add_node(&self) -> NodeId {
+        let mut records = self.records.borrow_mut();
+        records.nodes.push(NodeMetadata::default());
+        records.activators.push(0);
+        records.delays.push(false);
+        NodeId(records.nodes.len() - 1)
+    }
+
+    /// Record the label of a new port.
+    pub(crate) fn add_port(&self, label: String) {
+        self.records.borrow_mut().ports.push(label)
+    }
+
+    /// Record the creation of an activator for a node.
+    pub(crate) fn add_activator(&self, id: NodeId) {
+        self.records.borrow_mut().activators[id.0] += 1
+    }
+
+    /// Record that `from` holds an activator of `to`.
+    pub(crate) fn add_edge(&self, from: NodeId, to: NodeId) {
+        self.records.borrow_mut().edges.push((from, to))
+    }
+
+    /// Record that a node delays its values.
+    pub(crate) fn add_delay(&self, id: NodeId) {
+        self.records.borrow_mut().delays[id.0] = true
+    }
+
+    /// Check that no activation cycle without a delay goes through `id`.
+    pub(crate) fn check_cycle(&self, id: NodeId) -> Result<(), CycleError> {
+        let records = self.records.borrow();
+        match records.cycle_through(id) {
+            Some(mut cycle) => {
+                // Start from the earliest created node, so that the diagnostic doesn't depend on
+                // which node closed the cycle.
+                let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+                cycle.rotate_left(first);
+                Err(CycleError {
+                    nodes: cycle.into_iter().map(|id| records.cycle_node(id)).collect(),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Update the metadata of a recorded node.
+    pub(crate) fn update<F: FnOnce(&mut NodeMetadata)>(&self, id: NodeId, f: F) {
+        f(&mut self.records.borrow_mut().nodes[id.0])
+    }
+
+    /// The number of recorded nodes.
+    pub fn len(&self) -> usize {
+        self.records.borrow().nodes.len()
+    }
+
+    /// Whether no nodes were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.records.borrow().nodes.is_empty()
+    }
+
+    /// The metadata of a recorded node.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `id` was not attributed by this inspector.
+    pub fn metadata(&self, id: NodeId) -> NodeMetadata {
+        self.records.borrow().nodes[id.0].clone()
+    }
+
+    /// The number of activators created for a recorded node.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `id` was not attributed by this inspector.
+    pub fn activators(&self, id: NodeId) -> usize {
+        self.records.borrow().activators[id.0]
+    }
+
+    /// The declared connections, as `(producer, activated node)` pairs.
+    pub fn edges(&self) -> Vec<(NodeId, NodeId)> {
+        self.records.borrow().edges.clone()
+    }
+
+    /// Whether a recorded node delays its values, i.e. breaks the activation cycles through it.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `id` was not attributed by this inspector.
+    pub fn is_delay(&self, id: NodeId) -> bool {
+        self.records.borrow().delays[id.0]
+    }
+
+    /// Check the whole recorded graph for activation cycles without a delay, returning the first
+    /// one found.
+    pub fn check_cycles(&self) -> Result<(), CycleError> {
+        (0..self.len()).try_for_each(|id| self.check_cycle(NodeId(id)))
+    }
+
+    /// The recorded nodes in topological order of the declared connections: producers come before
+    /// the nodes they activate, and independent nodes keep their creation order.  Cycles are
+    /// broken at their earliest created node.
+    pub fn topological_order(&self) -> Vec<NodeId> {
+        let records = self.records.borrow();
+        let mut incoming = vec![0; records.nodes.len()];
+        for &(_, to) in &records.edges {
+            incoming[to.0] += 1;
+        }
+
+        let mut order = Vec::with_capacity(records.nodes.len());
+        let mut done = vec![false; records.nodes.len()];
+        loop {
+            // Take the first available node, so that the order is deterministic.
+            let next = (0..incoming.len()).find(|&id| !done[id] && incoming[id] == 0);
+            let next = match next {
+                Some(id) => id,
+                // Break the cycles in creation order.
+                None => match done.iter().position(|&done| !done) {
+                    Some(id) => id,
+                    None => break,
+                },
+            };
+            done[next] = true;
+            order.push(NodeId(next));
+            for &(from, to) in &records.edges {
+                if from.0 == next && incoming[to.0] > 0 {
+                    incoming[to.0] -= 1;
+                }
+            }
+        }
+        order
+    }
+
+    /// The labels of the recorded ports, in creation order.
+    pub fn ports(&self) -> Vec<String> {
+        self.records.borrow().ports.clone()
+    }
+
+    /// The identifiers of the recorded nodes in the namespace `path`, including nested namespaces.
+    /// Named nodes whose label is `path` itself are also included.
+    pub fn subtree(&self, path: &str) -> Vec<NodeId> {
+        let within = |name: &Option<String>| {
+            name.as_ref().is_some_and(|name| {
+                name == path
+                    || (name.starts_with(path) && name[path.len()..].starts_with('/'))
+            })
+        };
+
+        self.records
+            .borrow()
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, metadata)| within(&metadata.namespace) || within(&metadata.label))
+            .map(|(id, _)| NodeId(id))
+            .collect()
+    }
+
+    /// The identifiers of the recorded nodes with the given tag.
+    pub fn tagged(&self, tag: &str) -> Vec<NodeId> {
+        self.records
+            .borrow()
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, metadata)| metadata.has_tag(tag))
+            .map(|(id, _)| NodeId(id))
+            .collect()
+    }
+
+    /// A human-readable listing of the recorded nodes, one per line, followed by the labels of the
+    /// recorded ports, if any.
+    pub fn summary(&self) -> String {
+        let records = self.records.borrow();
+        let mut summary: String = records
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(id, metadata)| format!("{}: {}\n", NodeId(id), metadata))
+            .collect();
+        if !records.ports.is_empty() {
+            summary.push_str(&format!("ports: {}\n", records.ports.join(", ")));
+        }
+        summary
+    }
+
+    /// Export the recorded graph in the Graphviz DOT format.
+    ///
+    /// Declared connections are drawn as plain edges.  Activators without a declared producer are
+    /// drawn as dashed edges from an anonymous point: these are the first place to look at when a
+    /// node never fires.  Nodes without activators are highlighted, and ports are drawn as
+    /// unconnected boxes.
+    pub fn to_dot(&self) -> String {
+        let records = self.records.borrow();
+        let mut dot = String::from("digraph {\n");
+
+        for (id, metadata) in records.nodes.iter().enumerate() {
+            let style = if records.activators[id] == 0 {
+                ", color=red"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    n{} [label=\"{} {}\"{}];\n",
+                id,
+                NodeId(id),
+                escape(&metadata.to_string()),
+                style
+            ));
+        }
+
+        for (id, label) in records.ports.iter().enumerate() {
+            dot.push_str(&format!(
+                "    p{} [shape=box, label=\"{}\"];\n",
+                id,
+                escape(label)
+            ));
+        }
+
+        for &(from, to) in &records.edges {
+            dot.push_str(&format!("    n{} -> n{};\n", from.0, to.0));
+        }
+
+        for (id, &count) in records.activators.iter().enumerate() {
+            let declared = records.edges.iter().filter(|&&(_, to)| to.0 == id).count();
+            for unknown in 0..count.saturating_sub(declared) {
+                dot.push_str(&format!(
+                    "    u{}_{} [shape=point];\n    u{}_{} -> n{} [style=dashed];\n",
+                    id, unknown, id, unknown, id
+                ));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escape a string for use in a quoted DOT identifier.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
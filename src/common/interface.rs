@@ -0,0 +1,125 @@
+//! Named, typed outputs for graphs used across module boundaries.
+//!
+//! A graph built by one module and executed by another usually has a handful of sinks whose
+//! values are its actual results.  Instead of threading the corresponding receivers through the
+//! code by hand, they can be exposed under a name while building the graph:
+//!
+//! ```rust,ignore
+//! runtime.build_scope(|b| {
+//!     let (sum_sender, sum_receiver) = b.port(None).split();
+//!     b.expose_output("sum", sum_receiver);
+//!     ...
+//! });
+//! runtime.execute(4);
+//! let sum: Option<i32> = runtime.output("sum").unwrap();
+//! ```
+//!
+//! Reading an output uses the `Receiver` it was exposed with, which usually takes the value out of
+//! the port: each value can be read once, after the execution (or instant) which produced it.
+
+use std::any::{self, Any};
+use std::collections::HashMap;
+use std::fmt;
+
+use api::prelude::*;
+
+/// A type-erased reader for an exposed output.
+struct OutputReader<T>(Box<dyn Fn() -> T + Send + Sync>);
+
+/// The set of named outputs exposed by a graph.
+#[derive(Default)]
+pub struct GraphOutputs {
+    readers: HashMap<String, (&'static str, Box<dyn Any + Send + Sync>)>,
+}
+
+impl GraphOutputs {
+    /// Create an empty set of outputs.
+    pub fn new() -> Self {
+        GraphOutputs::default()
+    }
+
+    /// Expose `receiver` under `name`.  Any output previously exposed with the same name is
+    /// replaced.
+    pub fn expose<T, R>(&mut self, name: &str, receiver: R)
+    where
+        T: 'static,
+        R: Receiver<Item = T> + Send + Sync + 'static,
+    {
+        let reader = OutputReader(Box::new(move || receiver.recv()));
+        self.readers
+            .insert(name.to_string(), (any::type_name::<T>(), Box::new(reader)));
+    }
+
+    /// Read the output exposed under `name`, checking that it carries values of type `T`.
+    pub fn read<T: 'static>(&self, name: &str) -> Result<T, OutputError> {
+        let (type_name, reader) = self
+            .readers
+            .get(name)
+            .ok_or_else(|| OutputError::Unknown(name.to_string()))?;
+        let reader = reader
+            .downcast_ref::<OutputReader<T>>()
+            .ok_or_else(|| OutputError::TypeMismatch {
+                name: name.to_string(),
+                expected: any::type_name::<T>(),
+                found: type_name,
+            })?;
+        Ok((reader.0)())
+    }
+
+    /// The names of the exposed outputs, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.readers.keys().map(String::as_str)
+    }
+}
+
+impl fmt::Debug for GraphOutputs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.readers.iter().map(|(name, (type_name, _))| (name, type_name)))
+            .finish()
+    }
+}
+
+/// The reasons reading a named output can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputError {
+    /// No output was exposed with this name.
+    Unknown(String),
+    /// The output was exposed with a different item type.
+    TypeMismatch {
+        name: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OutputError::Unknown(ref name) => write!(f, "no output named `{}`", name),
+            OutputError::TypeMismatch {
+                ref name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "output `{}` carries `{}` values, not `{}`",
+                name, found, expected
+            ),
+        }
+    }
+}
+
+/// A trait for runtimes which can hold the named outputs of their graphs.
+pub trait OutputSpec {
+    /// The outputs exposed so far.
+    fn outputs(&self) -> &GraphOutputs;
+
+    /// Mutable access to the exposed outputs.
+    fn outputs_mut(&mut self) -> &mut GraphOutputs;
+
+    /// Read the output exposed under `name`.  See `GraphOutputs::read`.
+    fn output<T: 'static>(&self, name: &str) -> Result<T, OutputError> {
+        self.outputs().read(name)
+    }
+}
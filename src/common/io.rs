@@ -0,0 +1,117 @@
+//! Adapters pumping values from Rust iterators into graphs, and from graphs into closures.
+//!
+//! An `IteratorSource` is a node sending the items of an iterator on its output, one item per
+//! execution.  It re-activates itself after each item once it has been given an activator of its
+//! own, which pumps the whole iterator through a reusable graph from a single activation.  Since
+//! the same activator also starts the source, it is typically shared in an `Arc`:
+//!
+//! ```rust,ignore
+//! let mut source = b.node(IteratorSource::new(lines.map(Some), sender.with_activator(sink)));
+//! let next = Arc::new(source.add_activator());
+//! source.borrow_mut().set_activator(next.clone());
+//! next
+//! ```
+//!
+//! A `CallbackSink` is a task feeding each value it receives to a closure, e.g. to collect the
+//! results of a graph in a `Vec`:
+//!
+//! ```rust,ignore
+//! b.node(TaskNode {
+//!     inputs: (receiver.as_data_input(),),
+//!     outputs: (),
+//!     task: CallbackSink::new(move |line: Option<String>| lines.push(line.unwrap())),
+//! })
+//! ```
+//!
+//! Note that a self-activating source sends its next item as soon as it is executed again, which
+//! must not happen before the consumers have read the previous one (see the `api::port` module).
+//! This is the case in the sequential runtimes, which execute nodes in scheduling order; in the
+//! parallel runtimes, the source should instead be activated by its consumer after each item.
+
+use api::prelude::*;
+
+/// A node sending the items of an iterator on its output.  See the module documentation.
+///
+/// Once the iterator is exhausted, executing the node does nothing: its outputs are not activated.
+pub struct IteratorSource<I, O, A> {
+    items: I,
+    output: O,
+    activator: Option<A>,
+}
+
+impl<I, O, A> IteratorSource<I, O, A> {
+    /// Create a source sending the items of `items` on `output`.  The source is not re-activated
+    /// until it is given an activator with `set_activator`.
+    pub fn new<T: IntoIterator<IntoIter = I>>(items: T, output: O) -> Self {
+        IteratorSource {
+            items: items.into_iter(),
+            output,
+            activator: None,
+        }
+    }
+
+    /// Set the activator used by the source to re-activate itself after each item.  This should
+    /// be an activator of the source itself.
+    pub fn set_activator(&mut self, activator: A) {
+        self.activator = Some(activator)
+    }
+}
+
+impl<S, I, O, A> NodeMut<S> for IteratorSource<I, O, A>
+where
+    I: Iterator,
+    O: OutputEdgeMut<S, Item = I::Item>,
+    A: Activator<S>,
+{
+    fn execute_mut(&mut self, scheduler: &mut S) {
+        if let Some(item) = self.items.next() {
+            self.output.send_activate_mut(scheduler, item);
+            if let Some(ref activator) = self.activator {
+                activator.activate(scheduler)
+            }
+        }
+    }
+}
+
+impl<S, I, O, A> NodeOnce<S> for IteratorSource<I, O, A>
+where
+    IteratorSource<I, O, A>: NodeMut<S>,
+{
+    fn execute_once(mut self, scheduler: &mut S) {
+        self.execute_mut(scheduler)
+    }
+}
+
+/// A task feeding the value received on its single input to a closure.  See the module
+/// documentation.
+#[derive(Debug, Clone)]
+pub struct CallbackSink<F> {
+    callback: F,
+}
+
+impl<F> CallbackSink<F> {
+    /// Create a sink calling `callback` with each value it receives.
+    pub fn new(callback: F) -> Self {
+        CallbackSink { callback }
+    }
+}
+
+impl<S, I, F> TaskOnce<(I,), (), S> for CallbackSink<F>
+where
+    I: InputEdgeOnce<S>,
+    F: FnOnce(I::Item),
+{
+    fn run_once(self, scheduler: &mut S, inputs: (I,), _outputs: ()) {
+        (self.callback)(inputs.0.recv_activate_once(scheduler))
+    }
+}
+
+impl<S, I, F> TaskMut<(I,), (), S> for CallbackSink<F>
+where
+    I: InputEdgeOnce<S>,
+    F: FnMut(I::Item),
+{
+    fn run_mut(&mut self, scheduler: &mut S, inputs: (I,), _outputs: ()) {
+        (self.callback)(inputs.0.recv_activate_once(scheduler))
+    }
+}
@@ -0,0 +1,311 @@
+//! Measuring the queueing delay of edges.
+//!
+//! In a reactive pipeline, the time between a producer sending a value and its consumer actually
+//! running is spent waiting for the consumer's other inputs, then waiting in the scheduler's
+//! queues.  An `EdgeTimer` measures that delay for a single edge: the producer's output edge is
+//! wrapped with `EdgeTimer::output`, which timestamps each `send_activate`, and the consumer's
+//! input edge with `EdgeTimer::input`, which records the elapsed time when the consumer reads the
+//! value, i.e. when it runs.
+//!
+//! ```rust,ignore
+//! let timer = EdgeTimer::new("parse -> render").with_bound(Duration::from_millis(5));
+//! let (sender, receiver) = b.port(None).split();
+//! let render = b.node(TaskNode {
+//!     inputs: (timer.input(receiver.as_data_input()),),
+//!     ...
+//! });
+//! let output = timer.output(sender.with_activator(render.add_activator()));
+//! ...
+//! println!("{}", timer);
+//! ```
+//!
+//! Delays are aggregated in a histogram with power-of-two buckets, so that timers can be left on
+//! long-running graphs.  When a bound is set, delays exceeding it are reported on the standard
+//! error as they happen, which helps pinpointing the edges which are latency bottlenecks.
+//!
+//! Delays are only measured with the `stats` feature, which is enabled by default.  Without it,
+//! the instrumented edges forward to the wrapped ones, and the timers report no delays.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use api::prelude::*;
+
+/// The number of buckets of the histogram.  The last bucket holds delays of 2^30 microseconds
+/// (about 18 minutes) and more.
+const BUCKETS: usize = 32;
+
+/// The distribution of the delays measured on an edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The number of delays measured.
+    pub count: u64,
+    /// The shortest delay, if any.
+    pub min: Option<Duration>,
+    /// The longest delay, if any.
+    pub max: Option<Duration>,
+    /// The sum of all the delays.
+    pub total: Duration,
+    /// The number of delays exceeding the bound, if any.
+    pub exceeded: u64,
+    /// `buckets[i]` counts the delays below 2^i microseconds (and at least 2^(i - 1) for `i > 0`).
+    buckets: [u64; BUCKETS],
+}
+
+impl LatencyStats {
+    fn new() -> Self {
+        LatencyStats {
+            count: 0,
+            min: None,
+            max: None,
+            total: Duration::from_secs(0),
+            exceeded: 0,
+            buckets: [0; BUCKETS],
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    fn record(&mut self, delay: Duration) {
+        self.count += 1;
+        self.min = Some(self.min.map_or(delay, |min| min.min(delay)));
+        self.max = Some(self.max.map_or(delay, |max| max.max(delay)));
+        self.total += delay;
+
+        let micros = delay.as_micros();
+        let bucket = (128 - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+    }
+
+    /// The average delay, if any.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_nanos(
+                (self.total.as_nanos() / u128::from(self.count)) as u64,
+            ))
+        }
+    }
+
+    /// An upper bound of the `p`-th percentile of the delays, with `p` between 0 and 100.  Since
+    /// the delays are aggregated in power-of-two buckets, the bound is within a factor of two of
+    /// the actual percentile.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `p` is not between 0 and 100.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        assert!((0. ..=100.).contains(&p), "Invalid percentile {}.", p);
+
+        let rank = ((p / 100. * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = Duration::from_micros(1 << bucket);
+                return Some(self.max.map_or(bound, |max| max.min(bound)));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.min, self.mean(), self.max) {
+            (Some(min), Some(mean), Some(max)) => write!(
+                f,
+                "{} values, min {:?}, mean {:?}, p99 <= {:?}, max {:?}",
+                self.count,
+                min,
+                mean,
+                self.percentile(99.).unwrap(),
+                max
+            )?,
+            _ => write!(f, "no values")?,
+        }
+        if self.exceeded > 0 {
+            write!(f, ", {} over bound", self.exceeded)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state shared by the two sides of an instrumented edge.
+#[derive(Debug)]
+struct TimerState {
+    /// The time at which the values which were not consumed yet were sent, in order.
+    sent: VecDeque<Instant>,
+    stats: LatencyStats,
+}
+
+/// Measures the delay between values being sent on an edge and their consumer running.
+///
+/// Timers are cheap to clone; clones share the same measurements.
+#[derive(Debug, Clone)]
+pub struct EdgeTimer {
+    name: Label,
+    #[cfg_attr(not(feature = "stats"), allow(dead_code))]
+    bound: Option<Duration>,
+    state: Arc<Mutex<TimerState>>,
+}
+
+impl EdgeTimer {
+    /// Create a timer for the edge named `name`.  The name is used when reporting.
+    pub fn new<L: Into<Label>>(name: L) -> Self {
+        EdgeTimer {
+            name: name.into(),
+            bound: None,
+            state: Arc::new(Mutex::new(TimerState {
+                sent: VecDeque::new(),
+                stats: LatencyStats::new(),
+            })),
+        }
+    }
+
+    /// Report the delays exceeding `bound` on the standard error.
+    pub fn with_bound(self, bound: Duration) -> Self {
+        EdgeTimer {
+            bound: Some(bound),
+            ..self
+        }
+    }
+
+    /// The name of the edge.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Instrument the producer's side of the edge.
+    pub fn output<E>(&self, output: E) -> TimedOutput<E> {
+        TimedOutput {
+            output,
+            #[cfg(feature = "stats")]
+            timer: self.clone(),
+        }
+    }
+
+    /// Instrument the consumer's side of the edge.
+    pub fn input<I>(&self, input: I) -> TimedInput<I> {
+        TimedInput {
+            input,
+            #[cfg(feature = "stats")]
+            timer: self.clone(),
+        }
+    }
+
+    /// The delays measured so far.
+    pub fn stats(&self) -> LatencyStats {
+        self.state.lock().unwrap().stats.clone()
+    }
+
+    /// The number of values which were sent but not consumed yet.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().sent.len()
+    }
+
+    #[cfg(feature = "stats")]
+    fn sent(&self) {
+        self.state.lock().unwrap().sent.push_back(Instant::now())
+    }
+
+    /// Record that a value was consumed.  Values which were not sent through the instrumented
+    /// output, e.g. the initial value of the port, are not measured.
+    #[cfg(feature = "stats")]
+    fn received(&self) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let delay = match state.sent.pop_front() {
+            Some(sent) => now - sent,
+            None => return,
+        };
+
+        state.stats.record(delay);
+        if let Some(bound) = self.bound {
+            if delay > bound {
+                state.stats.exceeded += 1;
+                drop(state);
+                eprintln!(
+                    "edge `{}`: queueing delay of {:?} exceeds the bound of {:?}",
+                    self.name, delay, bound
+                );
+            }
+        }
+    }
+}
+
+impl fmt::Display for EdgeTimer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.stats())
+    }
+}
+
+/// The producer's side of an instrumented edge.  See `EdgeTimer::output`.
+#[derive(Debug)]
+pub struct TimedOutput<E> {
+    output: E,
+    #[cfg(feature = "stats")]
+    timer: EdgeTimer,
+}
+
+impl<S, E: OutputEdgeOnce<S>> OutputEdgeOnce<S> for TimedOutput<E> {
+    type Item = E::Item;
+
+    fn send_activate_once(self, scheduler: &mut S, item: Self::Item) {
+        #[cfg(feature = "stats")]
+        self.timer.sent();
+        self.output.send_activate_once(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeMut<S> for TimedOutput<E> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: Self::Item) {
+        #[cfg(feature = "stats")]
+        self.timer.sent();
+        self.output.send_activate_mut(scheduler, item)
+    }
+}
+
+impl<S, E: OutputEdge<S>> OutputEdge<S> for TimedOutput<E> {
+    fn send_activate(&self, scheduler: &mut S, item: Self::Item) {
+        #[cfg(feature = "stats")]
+        self.timer.sent();
+        self.output.send_activate(scheduler, item)
+    }
+}
+
+/// The consumer's side of an instrumented edge.  See `EdgeTimer::input`.
+#[derive(Debug)]
+pub struct TimedInput<I> {
+    input: I,
+    #[cfg(feature = "stats")]
+    timer: EdgeTimer,
+}
+
+impl<S, I: InputEdgeOnce<S>> InputEdgeOnce<S> for TimedInput<I> {
+    type Item = I::Item;
+
+    fn recv_activate_once(self, scheduler: &mut S) -> Self::Item {
+        #[cfg(feature = "stats")]
+        self.timer.received();
+        self.input.recv_activate_once(scheduler)
+    }
+}
+
+impl<S, I: InputEdgeMut<S>> InputEdgeMut<S> for TimedInput<I> {
+    fn recv_activate_mut(&mut self, scheduler: &mut S) -> Self::Item {
+        #[cfg(feature = "stats")]
+        self.timer.received();
+        self.input.recv_activate_mut(scheduler)
+    }
+}
+
+impl<S, I: InputEdge<S>> InputEdge<S> for TimedInput<I> {
+    fn recv_activate(&self, scheduler: &mut S) -> Self::Item {
+        #[cfg(feature = "stats")]
+        self.timer.received();
+        self.input.recv_activate(scheduler)
+    }
+}
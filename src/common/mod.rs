@@ -1,15 +1,61 @@
 //! Common implementations which should be usable for both sequential and parallel runtimes.
 
+pub mod barrier;
+pub mod bridge;
 pub mod builder;
+pub mod capability;
+pub mod coalesce;
+pub mod dsl;
 pub mod edge;
+pub mod environment;
+pub mod erased;
+pub mod fuzz;
+pub mod event_log;
+pub mod history;
+pub mod hot_swap;
+pub mod inspect;
+pub mod interface;
+pub mod io;
+pub mod latency;
 pub mod node;
+pub mod offload;
+pub mod ordered_map;
 pub mod port;
+pub mod provenance;
+pub mod run;
+pub mod sequencer;
+pub mod service;
+pub mod shutdown;
 pub mod task;
+pub mod timer;
 
 pub mod prelude {
+    pub use super::barrier::*;
+    pub use super::bridge::*;
     pub use super::builder::*;
+    pub use super::capability::*;
+    pub use super::coalesce::*;
+    pub use super::dsl::*;
     pub use super::edge::*;
+    pub use super::environment::*;
+    pub use super::erased::*;
+    pub use super::fuzz::*;
+    pub use super::event_log::*;
+    pub use super::history::*;
+    pub use super::hot_swap::*;
+    pub use super::inspect::*;
+    pub use super::interface::*;
+    pub use super::io::*;
+    pub use super::latency::*;
     pub use super::node::*;
+    pub use super::offload::*;
+    pub use super::ordered_map::*;
     pub use super::port::*;
+    pub use super::provenance::*;
+    pub use super::run::*;
+    pub use super::sequencer::*;
+    pub use super::service::*;
+    pub use super::shutdown::*;
     pub use super::task::*;
+    pub use super::timer::*;
 }
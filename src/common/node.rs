@@ -1,22 +1,249 @@
 //! Common implementations for nodes.
 
+use std::fmt;
+
 use api::prelude::*;
+use common::edge::ErrorOutput;
+use common::task::SharedTask;
 
 /// A dummy node which panics when executed.
 ///
 /// This can be used to create uninitialized activators when creating nodes before all their output
-/// activators are available.
-pub struct UninitializedNode;
+/// activators are available.  The node can be given the label of the node it stands for, which is
+/// then included in the panic message.
+#[derive(Debug, Default)]
+pub struct UninitializedNode {
+    label: Option<Label>,
+}
+
+impl UninitializedNode {
+    /// Create an unnamed placeholder node.
+    pub fn new() -> Self {
+        UninitializedNode::default()
+    }
+
+    /// Create a placeholder node for the node named `label`.
+    pub fn named<L: Into<Label>>(label: L) -> Self {
+        UninitializedNode {
+            label: Some(label.into()),
+        }
+    }
+
+    fn fail(&self) -> ! {
+        match self.label {
+            Some(ref label) => panic!("Uninitialized node `{}` was executed.", label),
+            None => panic!("Uninitialized node was executed."),
+        }
+    }
+}
 
 impl<S> NodeOnce<S> for UninitializedNode {
     fn execute_once(self, _scheduler: &mut S) {
-        panic!("Uninitialized node was executed.");
+        self.fail()
     }
 }
 
 impl<S> NodeMut<S> for UninitializedNode {
     fn execute_mut(&mut self, _scheduler: &mut S) {
-        panic!("Uniniialized node was executed.");
+        self.fail()
+    }
+}
+
+/// A unit delay node, like Lustre's `pre` operator.
+///
+/// Each time it is executed, the node receives a value from its input, sends the value it received
+/// on its previous execution to its output, and stores the new one.  The first execution sends the
+/// initial value instead, so that the output stream is the initial value followed by the input
+/// stream (`init -> pre x` in Lustre).
+///
+/// The node is mostly useful in reusable graphs, where it allows comparing the values of
+/// successive executions without storing them in the tasks themselves.
+#[derive(Debug)]
+pub struct DelayNode<T, I, O> {
+    previous: T,
+    input: I,
+    output: O,
+}
+
+impl<T, I, O> DelayNode<T, I, O> {
+    /// Create a new delay node reading from `input` and writing to `output`, which will first send
+    /// `init`.
+    pub fn new(init: T, input: I, output: O) -> Self {
+        DelayNode {
+            previous: init,
+            input,
+            output,
+        }
+    }
+}
+
+impl<S, T, I: InputEdgeOnce<S, Item = T>, O: OutputEdgeOnce<S, Item = T>> NodeOnce<S>
+    for DelayNode<T, I, O>
+{
+    fn execute_once(self, scheduler: &mut S) {
+        // The received value is lost, since the node can't be executed again.
+        let _ = self.input.recv_activate_once(scheduler);
+        self.output.send_activate_once(scheduler, self.previous)
+    }
+}
+
+impl<S, T, I: InputEdgeMut<S, Item = T>, O: OutputEdgeMut<S, Item = T>> NodeMut<S>
+    for DelayNode<T, I, O>
+{
+    fn execute_mut(&mut self, scheduler: &mut S) {
+        let next = self.input.recv_activate_mut(scheduler);
+        let previous = std::mem::replace(&mut self.previous, next);
+        self.output.send_activate_mut(scheduler, previous)
+    }
+}
+
+/// A node collecting the values of a fixed number of inputs into a vector.
+///
+/// Each input is a slot, typically a port holding an `Option`, written by a different producer.
+/// The node runs once all of them were written, and sends their values to its output, in the order
+/// of the inputs.  Together with `CloneOutput`, this allows building scatter/gather graphs with any
+/// number of branches without writing a task for each arity; see `ScopedGraphBuilder::join`.  A
+/// fixed-size array can be obtained by mapping the output with `OutputEdgeExt::map`.
+#[derive(Debug)]
+pub struct JoinNode<I, O> {
+    inputs: Vec<I>,
+    output: O,
+}
+
+impl<I, O> JoinNode<I, O> {
+    /// Create a new join node reading from `inputs` and writing to `output`.
+    pub fn new(inputs: Vec<I>, output: O) -> Self {
+        JoinNode { inputs, output }
+    }
+
+    /// The number of inputs of the node.
+    pub fn arity(&self) -> usize {
+        self.inputs.len()
+    }
+}
+
+/// Unwrap the value of a slot read by a `JoinNode`.
+fn joined<T>(slot: Option<T>) -> T {
+    slot.expect("A join node was executed with an empty input.")
+}
+
+impl<S, T, I, O> NodeOnce<S> for JoinNode<I, O>
+where
+    I: InputEdgeOnce<S, Item = Option<T>>,
+    O: OutputEdgeOnce<S, Item = Vec<T>>,
+{
+    fn execute_once(self, scheduler: &mut S) {
+        let values = self
+            .inputs
+            .into_iter()
+            .map(|input| joined(input.recv_activate_once(scheduler)))
+            .collect();
+        self.output.send_activate_once(scheduler, values)
+    }
+}
+
+impl<S, T, I, O> NodeMut<S> for JoinNode<I, O>
+where
+    I: InputEdgeMut<S, Item = Option<T>>,
+    O: OutputEdgeMut<S, Item = Vec<T>>,
+{
+    fn execute_mut(&mut self, scheduler: &mut S) {
+        let values = self
+            .inputs
+            .iter_mut()
+            .map(|input| joined(input.recv_activate_mut(scheduler)))
+            .collect();
+        self.output.send_activate_mut(scheduler, values)
+    }
+}
+
+/// A node built on its first execution.
+///
+/// The node holds a constructor until it is first scheduled and executed, at which point the
+/// constructor is called and the node it returns is executed in its place.  Reusable graphs keep
+/// the built node for the following executions.  This saves the time and memory needed to build
+/// the nodes of rarely used branches of large graphs, which are often never executed in a given
+/// session.  See `ScopedGraphBuilder::lazy_node`.
+///
+/// Since executing a node requires exclusive access to it, the constructor is called at most once,
+/// even when the node is executed by the workers of a parallel runtime.  The built node is boxed,
+/// so that a node which was not built yet only uses the memory of its constructor.
+pub struct LazyNode<F, N> {
+    state: Lazy<F, N>,
+}
+
+enum Lazy<F, N> {
+    Pending(F),
+    Built(Box<N>),
+    /// The constructor panicked.
+    Poisoned,
+}
+
+impl<F: FnOnce() -> N, N> LazyNode<F, N> {
+    /// Create a node built with `constructor` on its first execution.
+    pub fn new(constructor: F) -> Self {
+        LazyNode {
+            state: Lazy::Pending(constructor),
+        }
+    }
+
+    /// The node, if it was built.
+    pub fn get(&self) -> Option<&N> {
+        match self.state {
+            Lazy::Built(ref node) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Whether the node was built.
+    pub fn is_built(&self) -> bool {
+        self.get().is_some()
+    }
+
+    /// Build the node if needed, and return it.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the constructor panicked on a previous call.
+    pub fn force(&mut self) -> &mut N {
+        if let Lazy::Pending(_) = self.state {
+            let constructor = match std::mem::replace(&mut self.state, Lazy::Poisoned) {
+                Lazy::Pending(constructor) => constructor,
+                _ => unreachable!(),
+            };
+            self.state = Lazy::Built(Box::new(constructor()));
+        }
+        match self.state {
+            Lazy::Built(ref mut node) => node,
+            _ => panic!("The constructor of a lazy node panicked."),
+        }
+    }
+}
+
+impl<F, N> fmt::Debug for LazyNode<F, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.state {
+            Lazy::Pending(_) => "pending",
+            Lazy::Built(_) => "built",
+            Lazy::Poisoned => "poisoned",
+        };
+        f.debug_struct("LazyNode").field("state", &state).finish()
+    }
+}
+
+impl<S, F: FnOnce() -> N, N: NodeOnce<S>> NodeOnce<S> for LazyNode<F, N> {
+    fn execute_once(mut self, scheduler: &mut S) {
+        self.force();
+        match self.state {
+            Lazy::Built(node) => node.execute_once(scheduler),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<S, F: FnOnce() -> N, N: NodeMut<S>> NodeMut<S> for LazyNode<F, N> {
+    fn execute_mut(&mut self, scheduler: &mut S) {
+        self.force().execute_mut(scheduler)
     }
 }
 
@@ -30,6 +257,63 @@ pub struct TaskNode<I: Tuple, O: Tuple, T> {
     pub task: T,
 }
 
+impl<I: Tuple, O: Tuple, T> TaskNode<I, O, T> {
+    /// Bundle a task with its input and output edges, checking that there is one edge for each
+    /// input and output of the task.
+    ///
+    /// Building a `TaskNode` directly with the wrong number of edges only fails when the node is
+    /// added to a graph, with an error about the missing `NodeOnce` implementation.  This fails
+    /// where the node is created instead, with an error pointing at the mismatched inputs or
+    /// outputs:
+    ///
+    /// ```rust,ignore
+    /// // error: closure is expected to take 2 arguments, but it takes 1 argument
+    /// TaskNode::checked((x, y), (sum,), StrictTask::new(|x: Option<i32>| (x,)))
+    /// ```
+    ///
+    /// The task must implement `TaskArity`, as `StrictTask` and `FoldTask` do.
+    pub fn checked<Args: Tuple>(inputs: I, outputs: O, task: T) -> Self
+    where
+        T: TaskArity<Args>,
+        I: SameArity<Args>,
+        O: SameArity<T::Outputs>,
+    {
+        TaskNode {
+            inputs,
+            outputs,
+            task,
+        }
+    }
+}
+
+impl<I: Tuple, O: Tuple, T: ?Sized> TaskNode<I, O, SharedTask<T>> {
+    /// Bundle a task shared with other nodes with its input and output edges.  See `SharedTask`.
+    pub fn shared(inputs: I, outputs: O, task: SharedTask<T>) -> Self {
+        TaskNode {
+            inputs,
+            outputs,
+            task,
+        }
+    }
+}
+
+/// A node which bundles a fallible task with the corresponding input and output edges, and with
+/// an error edge.
+///
+/// When the task returns an error, the error is sent on `errors` instead of unwinding through the
+/// scheduler, and the outputs are not used.  Nodes waiting on the outputs are thus not activated:
+/// in single-use graphs, they are never executed.
+pub struct TryTaskNode<I: Tuple, O: Tuple, E, T> {
+    /// The inputs for the node.  This should be a tuple of `InputEdge` instances.
+    pub inputs: I,
+    /// The outputs of the node.  This should be a tuple of `OutputEdge` instances.
+    pub outputs: O,
+    /// The edge receiving the errors of the task, e.g. connected to an error-handler node.
+    pub errors: ErrorOutput<E>,
+    /// The task to execute.  This should be an instance of `TryTaskOnce` or `TryTaskMut`.
+    pub task: T,
+}
+
 /// Helper structure to enforce that the underlying task can only use the node's outputs once
 /// during its execution.  The implementations of `NodeMut` below wraps the underlying mutable
 /// reference into an `OutputOnce` before passing the outputs to the task.
@@ -74,10 +358,10 @@ impl<'a, S, I: InputEdgeMut<S>> InputEdgeOnce<S> for &'a mut I {
     }
 }
 
-// Implement the `NodeOnce` and `NodeMut` traits for `TaskNode`.  This is simply calling the
-// appropriate `run` function on the underlying task with the inputs and outputs from the node,
-// wrapped in `InputOnce` and `OutputOnce` structures to enforce statically that they can be only
-// used once during a single task execution.
+// Implement the `NodeOnce` and `NodeMut` traits for `TaskNode` and `TryTaskNode`.  This is simply
+// calling the appropriate `run` function on the underlying task with the inputs and outputs from
+// the node, wrapped in `InputOnce` and `OutputOnce` structures to enforce statically that they can
+// be only used once during a single task execution.
 macro_rules! auto_impl_node_tuple {
     (__next impl<($($Xs:ident),* ! ) -> ()>) => {};
     (__next impl<($($Xs:ident),* ! ) -> ($O:ident $(, $Os:ident)*)>) => {
@@ -123,6 +407,53 @@ macro_rules! auto_impl_node_tuple {
             }
         }
 
+        impl<S, $($Is,)* $($Os,)* E, T> NodeOnce<S> for TryTaskNode<($($Is,)*), ($($Os,)*), E, T>
+        where
+            T: TryTaskOnce<($(InputOnce<$Is>,)*), ($(OutputOnce<$Os>,)*), S>,
+            E: OutputEdgeOnce<S, Item = T::Error>,
+        {
+            fn execute_once(self, scheduler: &mut S) {
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = self.inputs;
+                #[allow(non_snake_case)]
+                let ($($Os,)*) = self.outputs;
+
+                let result = self.task.try_run_once(
+                    scheduler,
+                    ($(InputOnce($Is),)*),
+                    ($(OutputOnce($Os),)*));
+                if let Err(error) = result {
+                    self.errors.send_activate_once(scheduler, error)
+                }
+            }
+        }
+
+        impl<S, $($Is,)* $($Os,)* Er, E, T> NodeMut<S> for TryTaskNode<($($Is,)*), ($($Os,)*), E, T>
+        where
+            T: for<'a> TryTaskMut<
+                ($(InputOnce<&'a mut $Is>,)*),
+                ($(OutputOnce<&'a mut $Os>,)*),
+                S,
+                Error = Er,
+            >,
+            E: OutputEdgeMut<S, Item = Er>,
+        {
+            fn execute_mut(&mut self, scheduler: &mut S) {
+                #[allow(non_snake_case)]
+                let ($(ref mut $Is,)*) = self.inputs;
+                #[allow(non_snake_case)]
+                let ($(ref mut $Os,)*) = self.outputs;
+
+                let result = self.task.try_run_mut(
+                    scheduler,
+                    ($(InputOnce($Is),)*),
+                    ($(OutputOnce($Os),)*));
+                if let Err(error) = result {
+                    self.errors.send_activate_mut(scheduler, error)
+                }
+            }
+        }
+
         auto_impl_node_tuple! {
             __next impl<($($Xs),* ! $($Is),*) -> ($($Os),*)>
         }
@@ -0,0 +1,96 @@
+//! Offloading work to asynchronous accelerators.
+//!
+//! Accelerators such as GPUs, DMA engines or hardware queues process work asynchronously: jobs
+//! are submitted to a queue, and a callback is called once they are done.  An `OffloadTask`
+//! submits its input to such a queue through a user-provided closure and returns immediately,
+//! without blocking the worker.  The closure also receives a `Completion`, which must be called
+//! with the result once the job is done: the result is then sent on the task's output through the
+//! runtime's external event mechanism (see the `InjectorSpec` trait), activating the continuation.
+//!
+//! ```rust,ignore
+//! let kernel = OffloadTask::new(move |frame: Option<Frame>, done: Completion<Option<Frame>>| {
+//!     gpu.enqueue(blur_kernel, frame.unwrap(), move |frame| done.complete(Some(frame)));
+//! });
+//! ```
+//!
+//! Contrary to a `ServiceNode`, no thread is blocked while the job is being processed: the
+//! completion can be called from any thread, e.g. the accelerator driver's callback thread.
+
+use std::fmt;
+use std::sync::Arc;
+
+use api::prelude::*;
+
+/// The callback completing an offloaded job.  See the module documentation.
+///
+/// Until it is called or dropped, the runtime considers the graph as waiting for an external
+/// event: executions don't return before the completion.  Dropping the completion without
+/// calling it cancels the job, and the continuation is not activated.
+pub struct Completion<T> {
+    complete: Box<dyn FnOnce(T) + Send>,
+}
+
+impl<T> Completion<T> {
+    /// Complete the job with `result`, which is sent on the output of the task.  This can be
+    /// called from any thread.
+    pub fn complete(self, result: T) {
+        (self.complete)(result)
+    }
+}
+
+impl<T> fmt::Debug for Completion<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Completion").finish()
+    }
+}
+
+/// A task submitting its input to an asynchronous accelerator, and sending the result on its
+/// output once the accelerator completes the job.
+///
+/// The task has a single input receiving the job, and a single output for the result.  It can only
+/// be used in runtimes supporting external events.  `OffloadTask`s are cheap to clone: clones
+/// share the same submission closure.
+pub struct OffloadTask<F> {
+    submit: Arc<F>,
+}
+
+impl<F> Clone for OffloadTask<F> {
+    fn clone(&self) -> Self {
+        OffloadTask {
+            submit: self.submit.clone(),
+        }
+    }
+}
+
+impl<F> OffloadTask<F> {
+    /// Create a task calling `submit` with the input of the task and a `Completion` for its
+    /// result.  `submit` should return as soon as the job is queued.
+    pub fn new(submit: F) -> Self {
+        OffloadTask {
+            submit: Arc::new(submit),
+        }
+    }
+}
+
+impl<S, Job, Res, I, O, F> TaskOnce<(I,), (O,), S> for OffloadTask<F>
+where
+    S: InjectorSpec,
+    S::Injector: 'static,
+    I: InputEdgeOnce<S, Item = Job>,
+    O: OutputEdgeOnce<S, Item = Res> + Send + Sync + 'static,
+    Res: Send + Sync + 'static,
+    F: Fn(Job, Completion<Res>),
+{
+    fn run_once(self, scheduler: &mut S, inputs: (I,), outputs: (O,)) {
+        let job = inputs.0.recv_activate_once(scheduler);
+        let injector = scheduler.injector();
+        let output = outputs.0;
+
+        (self.submit)(
+            job,
+            Completion {
+                complete: Box::new(move |result| injector.inject_send(output, result)),
+            },
+        )
+    }
+}
@@ -0,0 +1,203 @@
+//! Bounded-parallelism mapping over a stream of items, with ordered results.
+//!
+//! An `OrderedMap` applies a function to the items pushed into it using up to `N` dynamically
+//! created worker nodes, which allows processing multiple items concurrently in parallel runtimes.
+//! Results are kept in an internal reorder buffer until all the results for the items which
+//! arrived before them have been emitted, so that the downstream part of the pipeline sees them in
+//! arrival order regardless of which worker finished first.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use api::prelude::*;
+use common::builder::GraphSpecExt;
+
+/// The mutable state of an `OrderedMap`.
+struct OrderedMapState<T, U, E> {
+    /// The sequence number of the next pushed item.
+    next_in: usize,
+    /// The sequence number of the next result to emit.
+    next_out: usize,
+    /// The number of workers currently alive.
+    workers: usize,
+    /// Items waiting for a worker, with their sequence numbers.
+    queued: VecDeque<(usize, T)>,
+    /// Results waiting for the results of earlier items.
+    done: BTreeMap<usize, U>,
+    /// The downstream callback.
+    emit: E,
+}
+
+struct OrderedMapShared<T, U, F, E> {
+    map: F,
+    max_workers: usize,
+    state: Mutex<OrderedMapState<T, U, E>>,
+}
+
+/// A combinator applying `F` to a stream of items on up to `max_workers` concurrent nodes, and
+/// calling `E` with the results in the order the items were pushed.
+///
+/// Items can be pushed directly with `push`, or the map can be used as the task of a (usually
+/// reusable) node with a single input, in which case each execution pushes the received item.
+///
+/// The emit callback is called with the map's internal lock held: it must not push items back into
+/// the same map.  It is typically used to send the result on an output edge.
+pub struct OrderedMap<T, U, F, E> {
+    shared: Arc<OrderedMapShared<T, U, F, E>>,
+}
+
+impl<T, U, F, E> Clone for OrderedMap<T, U, F, E> {
+    fn clone(&self) -> Self {
+        OrderedMap {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T, U, F, E> OrderedMap<T, U, F, E> {
+    /// Create a new map processing at most `max_workers` items concurrently.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `max_workers` is zero.
+    pub fn new(max_workers: usize, map: F, emit: E) -> Self {
+        assert!(max_workers > 0, "Ordered maps need at least one worker.");
+
+        OrderedMap {
+            shared: Arc::new(OrderedMapShared {
+                map,
+                max_workers,
+                state: Mutex::new(OrderedMapState {
+                    next_in: 0,
+                    next_out: 0,
+                    workers: 0,
+                    queued: VecDeque::new(),
+                    done: BTreeMap::new(),
+                    emit,
+                }),
+            }),
+        }
+    }
+
+    /// Push a new item.  A worker node is created to process it if less than `max_workers` are
+    /// currently alive; otherwise, the item is queued until a worker becomes available.
+    pub fn push<S>(&self, scheduler: &mut S, item: T)
+    where
+        S: NodeSpec<MapWorker<T, U, F, E>>,
+        S::Activator: ActivatorOnce<S>,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        let seq = state.next_in;
+        state.next_in += 1;
+        if state.workers < self.shared.max_workers {
+            state.workers += 1;
+            spawn(&self.shared, scheduler, seq, item);
+        } else {
+            state.queued.push_back((seq, item));
+        }
+    }
+}
+
+/// Create and activate a worker node processing `item`.
+fn spawn<S, T, U, F, E>(
+    shared: &Arc<OrderedMapShared<T, U, F, E>>,
+    scheduler: &mut S,
+    seq: usize,
+    item: T,
+) where
+    S: NodeSpec<MapWorker<T, U, F, E>>,
+    S::Activator: ActivatorOnce<S>,
+{
+    let activator = scheduler.build_scope(|b| {
+        b.node(MapWorker {
+            shared: shared.clone(),
+            job: Some((seq, item)),
+        })
+        .add_activator()
+    });
+    activator.activate_once(scheduler);
+}
+
+/// A dynamically created node processing items of an `OrderedMap`.
+///
+/// Once an item has been processed and the results which became available were emitted, the
+/// worker goes on with the next queued item, if any, and exits when the queue is empty.
+pub struct MapWorker<T, U, F, E> {
+    shared: Arc<OrderedMapShared<T, U, F, E>>,
+    job: Option<(usize, T)>,
+}
+
+impl<S, T, U, F, E> NodeMut<S> for MapWorker<T, U, F, E>
+where
+    F: Fn(T) -> U,
+    E: FnMut(&mut S, U),
+{
+    fn execute_mut(&mut self, scheduler: &mut S) {
+        let mut job = self.job.take();
+        while let Some((seq, item)) = job {
+            // Don't hold the lock while processing the item.
+            let result = (self.shared.map)(item);
+
+            let mut state = self.shared.state.lock().unwrap();
+            state.done.insert(seq, result);
+            loop {
+                let next_out = state.next_out;
+                match state.done.remove(&next_out) {
+                    Some(result) => {
+                        state.next_out += 1;
+                        (state.emit)(scheduler, result);
+                    }
+                    None => break,
+                }
+            }
+
+            job = state.queued.pop_front();
+            if job.is_none() {
+                state.workers -= 1;
+            }
+        }
+    }
+}
+
+impl<S, T, U, F, E> NodeOnce<S> for MapWorker<T, U, F, E>
+where
+    MapWorker<T, U, F, E>: NodeMut<S>,
+{
+    fn execute_once(mut self, scheduler: &mut S) {
+        self.execute_mut(scheduler)
+    }
+}
+
+impl<S, I, T, U, F, E> TaskOnce<(I,), (), S> for OrderedMap<T, U, F, E>
+where
+    I: InputEdgeOnce<S, Item = T>,
+    S: NodeSpec<MapWorker<T, U, F, E>>,
+    S::Activator: ActivatorOnce<S>,
+{
+    fn run_once(self, scheduler: &mut S, inputs: (I,), outputs: ()) {
+        Task::run(&self, scheduler, inputs, outputs)
+    }
+}
+
+impl<S, I, T, U, F, E> TaskMut<(I,), (), S> for OrderedMap<T, U, F, E>
+where
+    I: InputEdgeOnce<S, Item = T>,
+    S: NodeSpec<MapWorker<T, U, F, E>>,
+    S::Activator: ActivatorOnce<S>,
+{
+    fn run_mut(&mut self, scheduler: &mut S, inputs: (I,), outputs: ()) {
+        Task::run(self, scheduler, inputs, outputs)
+    }
+}
+
+impl<S, I, T, U, F, E> Task<(I,), (), S> for OrderedMap<T, U, F, E>
+where
+    I: InputEdgeOnce<S, Item = T>,
+    S: NodeSpec<MapWorker<T, U, F, E>>,
+    S::Activator: ActivatorOnce<S>,
+{
+    fn run(&self, scheduler: &mut S, inputs: (I,), _outputs: ()) {
+        let item = inputs.0.recv_activate_once(scheduler);
+        self.push(scheduler, item)
+    }
+}
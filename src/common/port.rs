@@ -1,6 +1,10 @@
 //! Common port implementations and extensions.
 
 use api::prelude::*;
+use common::edge::AckInput;
+use parallel::reset::Clear;
+#[cfg(feature = "checked-ports")]
+use sync::{AtomicBool, Ordering::SeqCst};
 
 /// A trait containing extensions for the `Receiver` family of traits.  It provides convenience
 /// methods to facilitate usage of types implementing those traits.
@@ -8,12 +12,20 @@ pub trait ReceiverExt: Sized {
     /// Convert a receiver into a pure data input edge.  The input edge doesn't have a control
     /// component and receiving data through it will never activate another node.
     fn as_data_input(self) -> DataInput<Self>;
+
+    /// Convert a receiver into the consumer side of a two-way edge: receiving data through it will
+    /// activate `ack`, which should activate the producer.  See `AckInput`.
+    fn with_ack<A>(self, ack: A) -> AckInput<Self, A>;
 }
 
 impl<T: ReceiverOnce> ReceiverExt for T {
     fn as_data_input(self) -> DataInput<Self> {
         DataInput { receiver: self }
     }
+
+    fn with_ack<A>(self, ack: A) -> AckInput<Self, A> {
+        AckInput::new(self, ack)
+    }
 }
 
 /// A newtype wrapper converting a receiver into a pure data edge with no control component.
@@ -223,3 +235,121 @@ impl<'a, T: Sender + Receiver + 'a> Port for RefPort<'a, T> {
         (RefSender(self.0), RefReceiver(self.0))
     }
 }
+
+/// A port wrapper enforcing the port contract.
+///
+/// Writing into a full port or reading from an empty one is a logic error (see the `api::port`
+/// module documentation), but most ports silently overwrite the previous value or return a default
+/// value in those cases.  A `CheckedPort` tracks whether the underlying port is full and panics
+/// with the port's name instead, which helps catching graph construction bugs such as a missing
+/// activation or a node reading from the wrong port.
+///
+/// The port is only checked with the `checked-ports` feature, which is enabled by default.
+/// Without it, a `CheckedPort` only forwards to the underlying port.
+#[derive(Debug)]
+pub struct CheckedPort<P> {
+    port: P,
+    name: String,
+    #[cfg(feature = "checked-ports")]
+    full: AtomicBool,
+}
+
+impl<P> CheckedPort<P> {
+    /// Wrap an empty `port`.  `name` is used in panic messages to identify the port.
+    pub fn new<N: Into<String>>(name: N, port: P) -> Self {
+        CheckedPort {
+            port,
+            name: name.into(),
+            #[cfg(feature = "checked-ports")]
+            full: AtomicBool::new(false),
+        }
+    }
+
+    /// The name of the port.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the port currently holds a value.
+    #[cfg(feature = "checked-ports")]
+    pub fn is_full(&self) -> bool {
+        self.full.load(SeqCst)
+    }
+
+    #[cfg(feature = "checked-ports")]
+    fn check_send(&self) {
+        if self.full.swap(true, SeqCst) {
+            panic!("Port `{}` was written to while full.", self.name);
+        }
+    }
+
+    #[cfg(not(feature = "checked-ports"))]
+    #[inline(always)]
+    fn check_send(&self) {}
+
+    #[cfg(feature = "checked-ports")]
+    fn check_recv(&self) {
+        if !self.full.swap(false, SeqCst) {
+            panic!("Port `{}` was read from while empty.", self.name);
+        }
+    }
+
+    #[cfg(not(feature = "checked-ports"))]
+    #[inline(always)]
+    fn check_recv(&self) {}
+}
+
+impl<P: SenderOnce> SenderOnce for CheckedPort<P> {
+    type Item = P::Item;
+
+    fn send_once(self, item: Self::Item) {
+        self.check_send();
+        self.port.send_once(item)
+    }
+}
+
+impl<P: SenderMut> SenderMut for CheckedPort<P> {
+    fn send_mut(&mut self, item: Self::Item) {
+        self.check_send();
+        self.port.send_mut(item)
+    }
+}
+
+impl<P: Sender> Sender for CheckedPort<P> {
+    fn send(&self, item: Self::Item) {
+        self.check_send();
+        self.port.send(item)
+    }
+}
+
+impl<P: ReceiverOnce> ReceiverOnce for CheckedPort<P> {
+    type Item = P::Item;
+
+    fn recv_once(self) -> Self::Item {
+        self.check_recv();
+        self.port.recv_once()
+    }
+}
+
+impl<P: ReceiverMut> ReceiverMut for CheckedPort<P> {
+    fn recv_mut(&mut self) -> Self::Item {
+        self.check_recv();
+        self.port.recv_mut()
+    }
+}
+
+impl<P: Receiver> Receiver for CheckedPort<P> {
+    fn recv(&self) -> Self::Item {
+        self.check_recv();
+        self.port.recv()
+    }
+}
+
+/// Clearing a checked port empties it without checking that it was full.
+impl<P: Clear> Clear for CheckedPort<P> {
+    fn clear(&self) {
+        self.port.clear();
+        #[cfg(feature = "checked-ports")]
+        self.full.store(false, SeqCst);
+    }
+}
@@ -0,0 +1,241 @@
+//! Opt-in provenance tracking for values transiting through a graph.
+//!
+//! When debugging a graph producing wrong results, it is often useful to know which nodes (and
+//! which executions of those nodes) contributed to a suspicious value.  Values can be wrapped in a
+//! `Traced` structure which carries a compact `Lineage` record along with the value, and computed
+//! by `TracedTask`s which automatically merge the lineages of their inputs and append a `Stamp`
+//! identifying themselves before sending their output.
+//!
+//! Lineages are capped to a configurable depth (the most recent stamps are kept) in order to keep
+//! the overhead bounded on long paths and in feedback loops.  Provenance tracking is entirely
+//! opt-in: graphs which don't use `Traced` values don't pay for it.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+use api::prelude::*;
+
+/// The depth used for lineages started by `TracedTask`s without any inputs, and for default
+/// `Traced` values.
+pub const DEFAULT_LINEAGE_DEPTH: usize = 16;
+
+/// Identifies a single execution of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Stamp {
+    /// The name of the node.
+    pub node: &'static str,
+    /// The number of times the node was executed before this execution.
+    pub instant: usize,
+}
+
+impl fmt::Display for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}#{}", self.node, self.instant)
+    }
+}
+
+/// The list of node executions a value went through, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lineage {
+    stamps: Vec<Stamp>,
+    depth: usize,
+    truncated: bool,
+}
+
+impl Lineage {
+    /// Create an empty lineage keeping at most `depth` stamps.
+    pub fn new(depth: usize) -> Self {
+        Lineage {
+            stamps: Vec::new(),
+            depth,
+            truncated: false,
+        }
+    }
+
+    /// Merge the lineages of multiple values, for instance the inputs of a node.
+    ///
+    /// Stamps are kept in order and deduplicated, and the resulting lineage uses the largest depth
+    /// of the merged lineages.
+    pub fn merge(lineages: &[&Lineage]) -> Self {
+        let depth = lineages
+            .iter()
+            .map(|lineage| lineage.depth)
+            .max()
+            .unwrap_or(DEFAULT_LINEAGE_DEPTH);
+        let mut merged = Lineage::new(depth);
+        for lineage in lineages {
+            merged.truncated |= lineage.truncated;
+            for stamp in &lineage.stamps {
+                if !merged.stamps.contains(stamp) {
+                    merged.stamps.push(*stamp);
+                }
+            }
+        }
+        merged.truncate();
+        merged
+    }
+
+    /// Record an additional node execution.
+    pub fn push(&mut self, stamp: Stamp) {
+        self.stamps.push(stamp);
+        self.truncate();
+    }
+
+    /// The recorded stamps, oldest first.
+    pub fn stamps(&self) -> &[Stamp] {
+        &self.stamps
+    }
+
+    /// Whether older stamps were dropped due to the depth cap.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn truncate(&mut self) {
+        if self.stamps.len() > self.depth {
+            let excess = self.stamps.len() - self.depth;
+            self.stamps.drain(..excess);
+            self.truncated = true;
+        }
+    }
+}
+
+/// Displays the lineage as a path, e.g. `... -> parse#3 -> add#3`.
+impl fmt::Display for Lineage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.truncated {
+            write!(f, "...")?;
+        } else {
+            write!(f, "source")?;
+        }
+        for stamp in &self.stamps {
+            write!(f, " -> {}", stamp)?;
+        }
+        Ok(())
+    }
+}
+
+/// A value along with its lineage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Traced<T> {
+    pub value: T,
+    pub lineage: Lineage,
+}
+
+impl<T> Traced<T> {
+    /// Wrap a source value with an empty lineage keeping at most `depth` stamps.
+    pub fn new(value: T, depth: usize) -> Self {
+        Traced {
+            value,
+            lineage: Lineage::new(depth),
+        }
+    }
+
+    /// Describe where the value comes from.  This is meant to be called from sinks when a
+    /// suspicious value is received.
+    pub fn dump(&self) -> String
+    where
+        T: fmt::Debug,
+    {
+        format!("{:?} <= {}", self.value, self.lineage)
+    }
+}
+
+/// The default traced value has an empty lineage with the default depth.  This allows creating
+/// ports carrying `Traced` values.
+impl<T: Default> Default for Traced<T> {
+    fn default() -> Self {
+        Traced::new(T::default(), DEFAULT_LINEAGE_DEPTH)
+    }
+}
+
+/// A wrapper converting a strict function into a provenance-tracking task.
+///
+/// The inputs of the task must be `Traced` values, which are unwrapped before calling the
+/// function.  The function returns a single (non-tuple) value, which is wrapped into a `Traced`
+/// value carrying the merged lineages of the inputs as well as a stamp for the current execution,
+/// and sent on the single output edge of the task.
+pub struct TracedTask<F> {
+    name: &'static str,
+    executions: AtomicUsize,
+    inner: F,
+}
+
+impl<F> TracedTask<F> {
+    /// Create a new task identified by `name` in the lineages it produces.
+    pub fn new(name: &'static str, inner: F) -> TracedTask<F> {
+        TracedTask {
+            name,
+            executions: AtomicUsize::new(0),
+            inner,
+        }
+    }
+
+    fn stamp(&self) -> Stamp {
+        Stamp {
+            node: self.name,
+            instant: self.executions.fetch_add(1, SeqCst),
+        }
+    }
+}
+
+// Macro implementation of the Task family of traits for `TracedTask`, following the one for
+// `StrictTask`.
+macro_rules! auto_impl_traced_task_tuple {
+    (impl<> { $($Xs:ident :: $xs:ident($Selfs:ty) for $Fs:ident,)* ! }) => {};
+    (impl<$I:ident : $T:ident, $($Is:ident : $Ts:ident,)*> {
+        $($Xs:ident :: $xs:ident($Selfs:ty) for $Fs:ident,)* !
+     }) => {
+        auto_impl_traced_task_tuple! {
+            impl<$($Is : $Ts,)*> { ! $($Xs::$xs($Selfs) for $Fs,)* }
+        }
+    };
+    (impl<$($Is:ident : $Ts:ident,)*> {
+         $($Xs:ident :: $xs:ident($Selfs:ty) for $Fs:ident,)*
+         ! $Task:ident :: $execute:ident($Self:ty) for $Fn:ident,
+         $($rest:tt)*
+     }) => {
+        impl<S, U, $($Ts, $Is: InputEdgeOnce<S, Item = Traced<$Ts>>,)*
+             O: Tuple + OutputEdgeOnce<S, Item = (Traced<U>,)>, F: $Fn($($Ts,)*) -> U>
+            $Task<($($Is,)*), O, S> for TracedTask<F>
+        {
+            fn $execute(self: $Self, scheduler: &mut S, inputs: ($($Is,)*), outputs: O) {
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = inputs;
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = ($($Is.recv_activate_once(scheduler),)*);
+                let mut lineage = Lineage::merge(&[$(&$Is.lineage,)*]);
+                lineage.push(self.stamp());
+                let value = (self.inner)($($Is.value,)*);
+                outputs.send_activate_once(scheduler, (Traced { value, lineage },));
+            }
+        }
+
+        auto_impl_traced_task_tuple! {
+            impl<$($Is : $Ts,)*> {
+                $($Xs::$xs($Selfs) for $Fs,)*
+                $Task::$execute($Self) for $Fn,
+                ! $($rest)*
+            }
+        }
+    };
+}
+
+auto_impl_traced_task_tuple! {
+    impl<
+        R0: A0,
+        R1: A1,
+        R2: A2,
+        R3: A3,
+        R4: A4,
+        R5: A5,
+        R6: A6,
+        R7: A7,
+        R8: A8,
+        R9: A9,
+    > {
+        ! TaskOnce::run_once(Self) for FnOnce,
+        TaskMut::run_mut(&mut Self) for FnMut,
+        Task::run(&Self) for Fn,
+    }
+}
@@ -0,0 +1,177 @@
+//! Running a graph in a single call.
+//!
+//! Scripts and examples usually go through the same steps: create a runtime, build the graph,
+//! activate its roots, execute it, and read its results.  `run_graph` does all of them given the
+//! function building the graph, which declares the roots of the graph in a `GraphIo` and exposes
+//! its results as named outputs (see the `interface` module):
+//!
+//! ```rust,no_run
+//! # extern crate rrs;
+//! use rrs::api::prelude::*;
+//! use rrs::common::prelude::*;
+//! use rrs::parallel::single_use::Toexec;
+//!
+//! # fn main() {
+//! let outputs = run_graph::<Toexec, _>(4, |b, io| {
+//!     let (sum_sender, sum_receiver) = b.port(None).split();
+//!     b.expose_output("sum", sum_receiver);
+//!     // The node activated once the sum is written: it does nothing, since the sum is read
+//!     // from the outputs.
+//!     let sink_activator = b
+//!         .node(TaskNode {
+//!             inputs: (),
+//!             outputs: (),
+//!             task: StrictTask::new(|| ()),
+//!         })
+//!         .add_activator();
+//!     let (sender, receiver) = b.port(None).split();
+//!     let activator = b
+//!         .node(TaskNode {
+//!             inputs: (receiver.as_data_input(),),
+//!             outputs: (sum_sender.with_activator(sink_activator),),
+//!             task: StrictTask::new(|x: Option<(i32, i32)>| (x.map(|(x, y)| x + y),)),
+//!         })
+//!         .add_activator();
+//!     io.input(sender.with_activator(activator), Some((3, 4)));
+//! });
+//! let sum: Option<i32> = outputs.read("sum").unwrap();
+//! # assert_eq!(sum, Some(7));
+//! # }
+//! ```
+//!
+//! This works with all the runtimes implementing `ExecuteSpec`, including the reusable ones.  The
+//! graph is executed once: graphs which are activated again after an execution, or which receive
+//! values while executing, are driven by hand.
+
+use std::fmt;
+use std::mem;
+
+use api::prelude::*;
+use common::builder::{GraphSpecExt, ScopedGraphBuilder};
+use common::interface::{GraphOutputs, OutputSpec};
+
+/// A trait for runtimes which can be created and executed by generic code, such as `run_graph`.
+pub trait ExecuteSpec: GraphSpec + OutputSpec {
+    /// Create a runtime without any node.
+    fn new() -> Self;
+
+    /// Execute the scheduled nodes with `workers` workers, until there are no nodes left to
+    /// execute.  The sequential runtimes ignore the number of workers, and the parallel ones
+    /// panic with their `ExecutionError` if the execution fails.
+    fn execute(&mut self, workers: usize);
+}
+
+/// A trait for runtimes which can be paused between *instants*, for debugging.
+///
+/// For the cooperative runtime, an instant is the processing of a single external event, along
+/// with all the microtasks it schedules.  For the sequential and parallel runtimes, an instant is
+/// an execution running the graph until it quiesces, such as `execute`: while paused, these
+/// executions return immediately, and the scheduled nodes stay scheduled until an execution runs.
+pub trait PauseSpec {
+    /// Stop starting new instants.  The instant in progress, if any, still completes.
+    fn pause(&mut self);
+
+    /// Allow exactly one more instant to run, then pause again.
+    fn step(&mut self);
+
+    /// Return to free-running mode, starting instants again.
+    fn resume(&mut self);
+
+    /// Whether the runtime is paused, either through `pause` or after the instant allowed by
+    /// `step` was started.
+    fn is_paused(&self) -> bool;
+}
+
+/// Whether a runtime may start new instants.  See `PauseSpec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RunMode {
+    /// Instants are started as long as there is work to do.
+    Running,
+    /// No new instant is started.
+    Paused,
+    /// A single instant may be started, after which the runtime pauses.
+    Step,
+}
+
+impl RunMode {
+    /// Whether a new instant may start.  This pauses the runtime if it was stepping, so that the
+    /// instant started is the last one.
+    pub(crate) fn start_instant(&mut self) -> bool {
+        match *self {
+            RunMode::Running => true,
+            RunMode::Paused => false,
+            RunMode::Step => {
+                *self = RunMode::Paused;
+                true
+            }
+        }
+    }
+}
+
+/// A root of a graph, activating it from outside of the runtime.
+type Root<Spec> = Box<dyn FnOnce(&mut Spec)>;
+
+/// The roots of a graph built by `run_graph`, activated once the graph is built.
+pub struct GraphIo<Spec> {
+    roots: Vec<Root<Spec>>,
+}
+
+impl<Spec> Default for GraphIo<Spec> {
+    fn default() -> Self {
+        GraphIo { roots: Vec::new() }
+    }
+}
+
+impl<Spec> fmt::Debug for GraphIo<Spec> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GraphIo")
+            .field("roots", &self.roots.len())
+            .finish()
+    }
+}
+
+impl<Spec> GraphIo<Spec> {
+    /// Create an empty set of roots.
+    pub fn new() -> Self {
+        GraphIo::default()
+    }
+
+    /// Send `value` on `edge` and activate its target before the execution.
+    pub fn input<E>(&mut self, edge: E, value: E::Item)
+    where
+        E: OutputEdgeOnce<Spec> + 'static,
+        E::Item: 'static,
+    {
+        self.roots.push(Box::new(move |runtime| {
+            edge.send_activate_once(runtime, value)
+        }))
+    }
+
+    /// Activate `activator` before the execution, e.g. for a node without inputs.
+    pub fn root<A: ActivatorOnce<Spec> + 'static>(&mut self, activator: A) {
+        self.roots
+            .push(Box::new(move |runtime| activator.activate_once(runtime)))
+    }
+
+    /// Activate the roots, in the order they were declared.
+    fn activate(self, runtime: &mut Spec) {
+        for root in self.roots {
+            root(runtime)
+        }
+    }
+}
+
+/// Create a runtime, build a graph with `build_fn`, activate the roots it declared, execute the
+/// graph with `workers` workers, and return the outputs it exposed.  See the module documentation.
+pub fn run_graph<Spec, F>(workers: usize, build_fn: F) -> GraphOutputs
+where
+    Spec: ExecuteSpec,
+    F: for<'a> FnOnce(&mut ScopedGraphBuilder<'a, Spec>, &mut GraphIo<Spec>),
+{
+    let mut runtime = Spec::new();
+    let mut io = GraphIo::new();
+    runtime.build_scope(|b| build_fn(b, &mut io));
+    io.activate(&mut runtime);
+    runtime.execute(workers);
+    mem::take(runtime.outputs_mut())
+}
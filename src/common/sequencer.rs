@@ -0,0 +1,236 @@
+//! Reproducible ordering of external events.
+//!
+//! Events injected from several threads (see the `InjectorSpec` trait) are executed in whatever
+//! order the threads happen to push them, and the events attributed to a given execution depend
+//! on when the execution starts.  Reactive runs are then impossible to reproduce.  A `Sequencer`
+//! orders the events explicitly instead:
+//!
+//!  - Each external source pushes its events through an `Ingest` handle, which gives them
+//!    monotonic sequence numbers.
+//!  - The instants are cut at explicit boundaries with `Sequencer::cut`, which returns the events
+//!    pushed since the previous cut as a `Batch`.
+//!  - The batch is delivered to the graph before executing the instant: the events are sent on
+//!    the sources' edges in sequence order.
+//!
+//! ```rust,ignore
+//! let sequencer = Sequencer::recording();
+//! let sensor = sequencer.source(0);
+//! thread::spawn(move || loop { sensor.push(read_sensor()); });
+//! loop {
+//!     sequencer.cut().deliver(&mut runtime, &mut edges);
+//!     runtime.execute(4);
+//! }
+//! ```
+//!
+//! A recording sequencer keeps the log of all the events, with their sequence numbers and
+//! instants.  Replaying the log with `Sequencer::replay` produces the same batches, so that the
+//! run can be reproduced without the external sources.  See the `event_log` module for saving
+//! the log to a file.  With the `serde` feature, events and batches can also be saved in any
+//! serde format.
+
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use api::prelude::*;
+
+/// An external event, numbered by a `Sequencer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Event<T> {
+    /// The position of the event in the order of all the events of the sequencer.
+    pub seq: u64,
+    /// The index of the instant the event was attributed to.
+    pub instant: u64,
+    /// The identifier of the source which pushed the event, i.e. the index of its edge when
+    /// delivering the batch.
+    pub source: usize,
+    /// The time elapsed between the creation of the sequencer and the event.
+    pub elapsed: Duration,
+    /// The value of the event.
+    pub value: T,
+}
+
+/// The events of an instant, in sequence order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Batch<T> {
+    /// The index of the instant.
+    pub instant: u64,
+    /// The events, sorted by sequence number.
+    pub events: Vec<Event<T>>,
+}
+
+impl<T> Batch<T> {
+    /// Send the value of each event on the edge of its source, i.e. on `edges[event.source]`, in
+    /// sequence order.
+    ///
+    /// # Panics
+    ///
+    /// This panics if an event's source has no edge.
+    pub fn deliver<S, E>(self, scheduler: &mut S, edges: &mut [E])
+    where
+        E: OutputEdgeMut<S, Item = T>,
+    {
+        for event in self.events {
+            edges[event.source].send_activate_mut(scheduler, event.value)
+        }
+    }
+}
+
+struct SequencerState<T> {
+    start: Instant,
+    next_seq: u64,
+    instant: u64,
+    pending: Vec<Event<T>>,
+    /// The events of the previous instants, if recording.
+    log: Option<Vec<Event<T>>>,
+}
+
+/// Numbers external events and groups them in instants.  See the module documentation.
+///
+/// Sequencers are cheap to clone; clones share the same events.
+pub struct Sequencer<T> {
+    state: Arc<Mutex<SequencerState<T>>>,
+}
+
+impl<T> Clone for Sequencer<T> {
+    fn clone(&self) -> Self {
+        Sequencer {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Default for Sequencer<T> {
+    fn default() -> Self {
+        Sequencer::new()
+    }
+}
+
+impl<T> Sequencer<T> {
+    /// Create a sequencer which does not keep a log of its events.
+    pub fn new() -> Self {
+        Sequencer::with_log(None)
+    }
+
+    fn with_log(log: Option<Vec<Event<T>>>) -> Self {
+        Sequencer {
+            state: Arc::new(Mutex::new(SequencerState {
+                start: Instant::now(),
+                next_seq: 0,
+                instant: 0,
+                pending: Vec::new(),
+                log,
+            })),
+        }
+    }
+
+    /// A handle for pushing the events of the source `source`.
+    pub fn source(&self, source: usize) -> Ingest<T> {
+        Ingest {
+            source,
+            state: self.state.clone(),
+        }
+    }
+
+    /// The index of the current instant, i.e. the number of cuts so far.
+    pub fn instant(&self) -> u64 {
+        self.state.lock().unwrap().instant
+    }
+
+    /// Close the current instant, returning its events in sequence order.  Events pushed
+    /// afterwards belong to the next instant.
+    pub fn cut(&self) -> Batch<T>
+    where
+        T: Clone,
+    {
+        let mut state = self.state.lock().unwrap();
+        let events = mem::take(&mut state.pending);
+        if let Some(ref mut log) = state.log {
+            log.extend(events.iter().cloned());
+        }
+        let instant = state.instant;
+        state.instant += 1;
+        Batch { instant, events }
+    }
+}
+
+impl<T: Clone> Sequencer<T> {
+    /// Create a sequencer keeping a log of the events of the closed instants.
+    pub fn recording() -> Self {
+        Sequencer::with_log(Some(Vec::new()))
+    }
+
+    /// The events of the instants closed so far, in sequence order, or `None` if the sequencer is
+    /// not recording.
+    pub fn log(&self) -> Option<Vec<Event<T>>> {
+        self.state.lock().unwrap().log.clone()
+    }
+
+    /// Split a log back into the batches of its instants, including the instants without events.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the log is not in sequence order.
+    pub fn replay(log: &[Event<T>]) -> Vec<Batch<T>> {
+        let mut batches: Vec<Batch<T>> = Vec::new();
+        for (i, event) in log.iter().enumerate() {
+            assert!(
+                i == 0 || log[i - 1].seq < event.seq,
+                "The log is not in sequence order."
+            );
+            while batches.len() as u64 <= event.instant {
+                batches.push(Batch {
+                    instant: batches.len() as u64,
+                    events: Vec::new(),
+                });
+            }
+            batches[event.instant as usize].events.push(event.clone());
+        }
+        batches
+    }
+}
+
+/// A handle for pushing the events of an external source into a `Sequencer`.  Handles are cheap to
+/// clone, and can be sent to other threads.
+pub struct Ingest<T> {
+    source: usize,
+    state: Arc<Mutex<SequencerState<T>>>,
+}
+
+impl<T> Clone for Ingest<T> {
+    fn clone(&self) -> Self {
+        Ingest {
+            source: self.source,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Ingest<T> {
+    /// The identifier of the source.
+    pub fn source(&self) -> usize {
+        self.source
+    }
+
+    /// Push an event, returning its sequence number.  The event belongs to the current instant.
+    pub fn push(&self, value: T) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let instant = state.instant;
+        let elapsed = state.start.elapsed();
+        state.pending.push(Event {
+            seq,
+            instant,
+            source: self.source,
+            elapsed,
+            value,
+        });
+        seq
+    }
+}
@@ -0,0 +1,111 @@
+//! Lifting blocking request/response services into graph nodes.
+//!
+//! Calling a database or an HTTP API from a task would block the worker executing it, and with it
+//! all the nodes which could have been executed in the meantime.  A `ServiceNode` instead hands
+//! each request to a small pool of dedicated threads and returns immediately; once the response is
+//! available, it is sent on the node's output through the runtime's external event mechanism (see
+//! the `InjectorSpec` trait).
+//!
+//! The number of threads in the pool bounds the number of requests being processed concurrently;
+//! additional requests are queued until a thread becomes available.
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use api::prelude::*;
+
+/// A unit of work executed by one of the pool's threads.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The shared state of a `ServiceNode`: the service function and the thread pool calling it.
+///
+/// Dropping the pool closes the job queue: the threads exit once they have processed the pending
+/// requests.
+struct ServicePool<F> {
+    service: Arc<F>,
+    jobs: Mutex<Sender<Job>>,
+}
+
+impl<F> ServicePool<F> {
+    fn submit(&self, job: Job) {
+        self.jobs
+            .lock()
+            .unwrap()
+            .send(job)
+            .expect("Service pool threads have exited.")
+    }
+}
+
+/// A task calling a blocking service function on a dedicated thread pool.
+///
+/// The task has a single input receiving the request, and a single output on which the response
+/// is sent once available.  It can only be used in runtimes supporting external events.
+///
+/// `ServiceNode`s are cheap to clone: clones share the same thread pool, which allows using the
+/// same service from multiple (possibly dynamically created) single-use nodes.  The pool is shut
+/// down once all the clones have been dropped and the pending requests were processed.
+pub struct ServiceNode<F> {
+    pool: Arc<ServicePool<F>>,
+}
+
+impl<F> Clone for ServiceNode<F> {
+    fn clone(&self) -> Self {
+        ServiceNode {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<F: Send + Sync + 'static> ServiceNode<F> {
+    /// Create a new service calling `service` on a pool of `pool_size` threads.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `pool_size` is zero.
+    pub fn new(pool_size: usize, service: F) -> Self {
+        assert!(pool_size > 0, "Service pools need at least one thread.");
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..pool_size {
+            let receiver = receiver.clone();
+            thread::spawn(move || loop {
+                // Don't hold the lock while executing the job.
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => return,
+                }
+            });
+        }
+
+        ServiceNode {
+            pool: Arc::new(ServicePool {
+                service: Arc::new(service),
+                jobs: Mutex::new(sender),
+            }),
+        }
+    }
+}
+
+impl<S, Req, Resp, I, O, F> TaskOnce<(I,), (O,), S> for ServiceNode<F>
+where
+    S: InjectorSpec,
+    S::Injector: 'static,
+    I: InputEdgeOnce<S, Item = Req>,
+    O: OutputEdgeOnce<S, Item = Resp> + Send + Sync + 'static,
+    Req: Send + 'static,
+    Resp: Send + Sync + 'static,
+    F: Fn(Req) -> Resp + Send + Sync + 'static,
+{
+    fn run_once(self, scheduler: &mut S, inputs: (I,), outputs: (O,)) {
+        let request = inputs.0.recv_activate_once(scheduler);
+        let injector = scheduler.injector();
+        let service = self.pool.service.clone();
+        let output = outputs.0;
+
+        self.pool
+            .submit(Box::new(move || injector.inject_send(output, service(request))))
+    }
+}
@@ -0,0 +1,162 @@
+//! Orderly shutdown of long-lived graphs.
+//!
+//! Stopping the nodes of a long-lived graph in an arbitrary order loses data: a sink closed before
+//! its producers can't flush the values still flowing through the graph, and a source which keeps
+//! running keeps feeding nodes which are being torn down.  A `ShutdownSequence` stops the nodes
+//! recorded by an `Inspector` in the topological order of their declared connections instead (see
+//! `Inspector::topological_order`): sources are stopped first, and sinks last.
+//!
+//! Each node can register `Teardown` hooks.  The hooks of a node are stopped, and the sequence
+//! waits for them to drain, before moving on to the next node; nodes which fail to drain within
+//! the timeout are reported, but don't prevent the following nodes from being stopped:
+//!
+//! ```rust,ignore
+//! let mut shutdown = ShutdownSequence::new(&inspector).with_timeout(Duration::from_secs(1));
+//! shutdown.on_shutdown(camera_id, move || camera.stop());
+//! shutdown.on_shutdown(encoder_id, encoder_flush);
+//! if let Err(error) = shutdown.run() {
+//!     eprintln!("{}", error);
+//! }
+//! ```
+//!
+//! Only the declared connections are known to the inspector (see `ScopedNodeBuilder::activates`):
+//! nodes without declared connections are stopped in creation order.
+
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::inspect::{Inspector, NodeId};
+
+/// A hook stopping a node during a shutdown.
+///
+/// This is implemented for closures, which are called once and are immediately drained.
+pub trait Teardown {
+    /// Stop the node, e.g. close a source or ask a sink to flush its buffers.
+    fn stop(&mut self);
+
+    /// Whether the node has processed all its in-flight values.  This is polled after `stop` until
+    /// it returns `true` or the timeout elapses.
+    fn is_drained(&mut self) -> bool {
+        true
+    }
+}
+
+impl<F: FnMut()> Teardown for F {
+    fn stop(&mut self) {
+        self()
+    }
+}
+
+/// Stops the nodes of a graph in topological order.  See the module documentation.
+pub struct ShutdownSequence<'a> {
+    inspector: Inspector,
+    hooks: Vec<(NodeId, Box<dyn Teardown + 'a>)>,
+    timeout: Duration,
+}
+
+impl<'a> ShutdownSequence<'a> {
+    /// Create a sequence for the nodes recorded by `inspector`, with a timeout of one second.
+    pub fn new(inspector: &Inspector) -> Self {
+        ShutdownSequence {
+            inspector: inspector.clone(),
+            hooks: Vec::new(),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    /// Wait at most `timeout` for each node to drain.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Register a hook stopping the node `id`.  Hooks registered for the same node are stopped
+    /// together, in registration order.
+    pub fn on_shutdown<T: Teardown + 'a>(&mut self, id: NodeId, teardown: T) {
+        self.hooks.push((id, Box::new(teardown)))
+    }
+
+    /// Stop the nodes, and return the nodes with hooks in the order they were stopped.
+    pub fn run(mut self) -> Result<Vec<NodeId>, ShutdownError> {
+        let mut stopped = Vec::new();
+        let mut undrained = Vec::new();
+
+        for id in self.inspector.topological_order() {
+            let mut hooks: Vec<_> = self
+                .hooks
+                .iter_mut()
+                .filter(|&&mut (hook_id, _)| hook_id == id)
+                .map(|(_, hook)| hook)
+                .collect();
+            if hooks.is_empty() {
+                continue;
+            }
+
+            for hook in &mut hooks {
+                hook.stop();
+            }
+            stopped.push(id);
+
+            let deadline = Instant::now() + self.timeout;
+            loop {
+                hooks.retain_mut(|hook| !hook.is_drained());
+                if hooks.is_empty() {
+                    break;
+                }
+                if Instant::now() >= deadline {
+                    undrained.push(UndrainedNode {
+                        id,
+                        label: self.inspector.metadata(id).label,
+                    });
+                    break;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        if undrained.is_empty() {
+            Ok(stopped)
+        } else {
+            Err(ShutdownError { nodes: undrained })
+        }
+    }
+}
+
+/// A node which did not drain within the timeout of a shutdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndrainedNode {
+    /// The identifier of the node in the inspector.
+    pub id: NodeId,
+    /// The label of the node, if any.
+    pub label: Option<String>,
+}
+
+impl fmt::Display for UndrainedNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.label {
+            Some(ref label) => write!(f, "`{}` ({})", label, self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// The error returned by `ShutdownSequence::run` when some nodes did not drain in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownError {
+    /// The nodes which did not drain, in the order they were stopped.
+    pub nodes: Vec<UndrainedNode>,
+}
+
+impl fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} nodes did not drain in time:", self.nodes.len())?;
+        for node in &self.nodes {
+            write!(f, " {}", node)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ShutdownError {}
@@ -2,6 +2,13 @@
 //!
 //! This module is meant to include generic implementation for tasks.
 
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
 use api::prelude::*;
 
 /// A wrapper for converting a strict function into a task.
@@ -18,6 +25,9 @@ use api::prelude::*;
 /// of the Rust type system (otherwise it would fail when converting into a node).  This is
 /// enforced by the implementations below with a `Tuple` bound on the output which should generate
 /// understandable error messages.
+///
+/// Functions returning a `Result` of a tuple of outputs can be used as fallible tasks in a
+/// `TryTaskNode`: the outputs are only sent when the function succeeds.
 pub struct StrictTask<F> {
     inner: F,
 }
@@ -88,3 +98,374 @@ auto_impl_strict_task_tuple! {
         Task::run(&Self) for Fn,
     }
 }
+
+// Macro implementation of the TryTask family of traits for StrictTask with functions returning a
+// `Result` of a tuple of outputs.  An error is returned as is, without sending any output.
+macro_rules! auto_impl_strict_try_task_tuple {
+    (@impl $($Is:ident,)*) => {
+        impl<S, $($Is: InputEdgeOnce<S>,)* O, Er, F>
+            TryTaskOnce<($($Is,)*), O, S> for StrictTask<F>
+        where
+            O: Tuple + OutputEdgeOnce<S>,
+            F: FnOnce($($Is::Item,)*) -> Result<O::Item, Er>,
+        {
+            type Error = Er;
+
+            fn try_run_once(
+                self,
+                scheduler: &mut S,
+                inputs: ($($Is,)*),
+                outputs: O,
+            ) -> Result<(), Er> {
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = inputs;
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = ($($Is.recv_activate_once(scheduler),)*);
+                outputs.send_activate_once(scheduler, (self.inner)($($Is,)*)?);
+                Ok(())
+            }
+        }
+
+        impl<S, $($Is: InputEdgeOnce<S>,)* O, Er, F>
+            TryTaskMut<($($Is,)*), O, S> for StrictTask<F>
+        where
+            O: Tuple + OutputEdgeOnce<S>,
+            F: FnMut($($Is::Item,)*) -> Result<O::Item, Er>,
+        {
+            type Error = Er;
+
+            fn try_run_mut(
+                &mut self,
+                scheduler: &mut S,
+                inputs: ($($Is,)*),
+                outputs: O,
+            ) -> Result<(), Er> {
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = inputs;
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = ($($Is.recv_activate_once(scheduler),)*);
+                outputs.send_activate_once(scheduler, (self.inner)($($Is,)*)?);
+                Ok(())
+            }
+        }
+    };
+    () => {
+        auto_impl_strict_try_task_tuple! { @impl }
+    };
+    ($I:ident, $($Is:ident,)*) => {
+        auto_impl_strict_try_task_tuple! { @impl $I, $($Is,)* }
+        auto_impl_strict_try_task_tuple! { $($Is,)* }
+    };
+}
+
+auto_impl_strict_try_task_tuple! {
+    R0,
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+    R8,
+    R9,
+}
+
+// Macro implementation of `TaskArity` for StrictTask with functions of multiple arguments.
+macro_rules! auto_impl_strict_task_arity {
+    (@impl $($Is:ident,)*) => {
+        impl<$($Is,)* O: Tuple, F: FnOnce($($Is,)*) -> O> TaskArity<($($Is,)*)> for StrictTask<F> {
+            type Outputs = O;
+        }
+    };
+    () => {
+        auto_impl_strict_task_arity! { @impl }
+    };
+    ($I:ident, $($Is:ident,)*) => {
+        auto_impl_strict_task_arity! { @impl $I, $($Is,)* }
+        auto_impl_strict_task_arity! { $($Is,)* }
+    };
+}
+
+auto_impl_strict_task_arity! {
+    R0,
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+    R8,
+    R9,
+}
+
+/// A task folding its inputs into a persistent state.
+///
+/// The underlying function receives a mutable reference to the state along with the values of the
+/// inputs, and returns a tuple of output values as for `StrictTask`.  The state is kept between
+/// executions of the task, which allows writing counters, integrators or moving averages for
+/// reusable graphs without a manual `TaskMut` implementation.  For instance, a running sum:
+///
+/// ```rust,ignore
+/// FoldTask::new(0, |sum: &mut i32, x: Option<i32>| {
+///     *sum += x.unwrap();
+///     (Some(*sum),)
+/// })
+/// ```
+pub struct FoldTask<St, F> {
+    state: St,
+    inner: F,
+}
+
+impl<St, F> FoldTask<St, F> {
+    /// Create a new task with the initial state `init`.
+    ///
+    /// Note that the underlying function `F` must return a tuple of output values.
+    pub fn new(init: St, inner: F) -> FoldTask<St, F> {
+        FoldTask { state: init, inner }
+    }
+}
+
+// Macro implementation of `TaskOnce`, `TaskMut` and `TaskArity` for `FoldTask` with functions of
+// multiple arguments.  There is no `Task` implementation, since running the task mutates its state.
+macro_rules! auto_impl_fold_task_tuple {
+    (@impl $($Is:ident,)*) => {
+        impl<S, St, $($Is: InputEdgeOnce<S>,)* O, F>
+            TaskOnce<($($Is,)*), O, S> for FoldTask<St, F>
+        where
+            O: Tuple + OutputEdgeOnce<S>,
+            F: FnOnce(&mut St, $($Is::Item,)*) -> O::Item,
+        {
+            fn run_once(mut self, scheduler: &mut S, inputs: ($($Is,)*), outputs: O) {
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = inputs;
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = ($($Is.recv_activate_once(scheduler),)*);
+                outputs.send_activate_once(scheduler, (self.inner)(&mut self.state, $($Is,)*));
+            }
+        }
+
+        impl<S, St, $($Is: InputEdgeOnce<S>,)* O, F>
+            TaskMut<($($Is,)*), O, S> for FoldTask<St, F>
+        where
+            O: Tuple + OutputEdgeOnce<S>,
+            F: FnMut(&mut St, $($Is::Item,)*) -> O::Item,
+        {
+            fn run_mut(&mut self, scheduler: &mut S, inputs: ($($Is,)*), outputs: O) {
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = inputs;
+                #[allow(non_snake_case)]
+                let ($($Is,)*) = ($($Is.recv_activate_once(scheduler),)*);
+                outputs.send_activate_once(scheduler, (self.inner)(&mut self.state, $($Is,)*));
+            }
+        }
+
+        impl<St, $($Is,)* O: Tuple, F: FnOnce(&mut St, $($Is,)*) -> O>
+            TaskArity<($($Is,)*)> for FoldTask<St, F>
+        {
+            type Outputs = O;
+        }
+    };
+    () => {
+        auto_impl_fold_task_tuple! { @impl }
+    };
+    ($I:ident, $($Is:ident,)*) => {
+        auto_impl_fold_task_tuple! { @impl $I, $($Is,)* }
+        auto_impl_fold_task_tuple! { $($Is,)* }
+    };
+}
+
+auto_impl_fold_task_tuple! {
+    R0,
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+    R8,
+    R9,
+}
+
+/// A task shared by several nodes.
+///
+/// Graphs instantiated from a template often run the same task in thousands of nodes.  Sharing a
+/// single copy of the task, e.g. a closure capturing a large lookup table, reduces the memory
+/// footprint of the graph, and erasing its type with `into_dyn` lets all the nodes share the same
+/// machine code, whatever the closure.  The task is only borrowed when run, so that it must
+/// implement `Task`; tasks with state should use a `Mutex` (or not be shared).
+///
+/// Identical tasks are usually shared through a `TaskInterner`.
+pub struct SharedTask<T: ?Sized> {
+    inner: Arc<T>,
+}
+
+impl<T> SharedTask<T> {
+    /// Create a new shared task.
+    pub fn new(task: T) -> Self {
+        SharedTask {
+            inner: Arc::new(task),
+        }
+    }
+
+    /// Erase the type of the task, so that nodes running different tasks with the same inputs and
+    /// outputs have the same type.
+    ///
+    /// Erased tasks can only be run by single-use nodes: reusable nodes lend their edges to the
+    /// task, which must then accept edges borrowed for any lifetime.
+    pub fn into_dyn<I: Tuple, O: Tuple, S>(self) -> SharedTask<DynTask<I, O, S>>
+    where
+        T: Task<I, O, S> + Send + Sync + 'static,
+    {
+        SharedTask { inner: self.inner }
+    }
+}
+
+impl<T: ?Sized> SharedTask<T> {
+    /// Share an existing task.
+    pub fn from_arc(inner: Arc<T>) -> Self {
+        SharedTask { inner }
+    }
+
+    /// Whether `self` and `other` share the same task.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T: ?Sized> Clone for SharedTask<T> {
+    fn clone(&self) -> Self {
+        SharedTask {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for SharedTask<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedTask")
+            .field("shares", &Arc::strong_count(&self.inner))
+            .finish()
+    }
+}
+
+/// A type-erased task, as shared by `SharedTask::into_dyn`.
+pub type DynTask<I, O, S> = dyn Task<I, O, S> + Send + Sync;
+
+impl<I: Tuple, O: Tuple, S, T: Task<I, O, S> + ?Sized> TaskOnce<I, O, S> for SharedTask<T> {
+    fn run_once(self, scheduler: &mut S, inputs: I, outputs: O) {
+        self.inner.run(scheduler, inputs, outputs)
+    }
+}
+
+impl<I: Tuple, O: Tuple, S, T: Task<I, O, S> + ?Sized> TaskMut<I, O, S> for SharedTask<T> {
+    fn run_mut(&mut self, scheduler: &mut S, inputs: I, outputs: O) {
+        self.inner.run(scheduler, inputs, outputs)
+    }
+}
+
+impl<I: Tuple, O: Tuple, S, T: Task<I, O, S> + ?Sized> Task<I, O, S> for SharedTask<T> {
+    fn run(&self, scheduler: &mut S, inputs: I, outputs: O) {
+        self.inner.run(scheduler, inputs, outputs)
+    }
+}
+
+impl<Args: Tuple, T: TaskArity<Args> + ?Sized> TaskArity<Args> for SharedTask<T> {
+    type Outputs = T::Outputs;
+}
+
+/// The tasks of a `TaskInterner`.
+#[derive(Default)]
+struct Interned {
+    /// The tasks by type of key and type of task.  Each entry is a `HashMap<K, SharedTask<T>>`.
+    tasks: HashMap<(TypeId, TypeId), Box<dyn Any + Send>>,
+    /// The number of tasks.
+    count: usize,
+}
+
+/// Shares identical tasks between nodes.  See `SharedTask`.
+///
+/// Stateless closures, i.e. closures which don't capture anything, are identical when they have
+/// the same type, and are interned by `intern`: all the nodes built from the same closure
+/// expression share the same task.  Closures capturing values are interned under a key describing
+/// the captured values by `intern_keyed`:
+///
+/// ```rust,ignore
+/// let interner = TaskInterner::new();
+/// for (i, table) in tables.iter().enumerate() {
+///     let task = interner.intern_keyed(table.name(), || {
+///         let table = table.load();
+///         StrictTask::new(move |x: Option<u8>| (x.map(|x| table[x as usize]),))
+///     });
+///     b.node(TaskNode::shared(inputs[i], outputs[i], task));
+/// }
+/// ```
+///
+/// Interners are cheap to clone, and the clones share the same tasks.
+#[derive(Clone, Default)]
+pub struct TaskInterner {
+    interned: Arc<Mutex<Interned>>,
+}
+
+impl TaskInterner {
+    /// Create an empty interner.
+    pub fn new() -> Self {
+        TaskInterner::default()
+    }
+
+    /// Share the strict function `f` with the nodes previously built from the same stateless
+    /// closure.  Closures capturing values are never identical to another one, and are shared by
+    /// the nodes using the returned task only.
+    pub fn intern<F: Send + Sync + 'static>(&self, f: F) -> SharedTask<StrictTask<F>> {
+        if mem::size_of::<F>() != 0 {
+            return SharedTask::new(StrictTask::new(f));
+        }
+        self.intern_keyed((), move || StrictTask::new(f))
+    }
+
+    /// Share the task interned under `key`, or the one created by `make` if there is none.  Tasks
+    /// of different types are interned separately, even under the same key.
+    pub fn intern_keyed<K, T, M>(&self, key: K, make: M) -> SharedTask<T>
+    where
+        K: Hash + Eq + Send + 'static,
+        T: Send + Sync + 'static,
+        M: FnOnce() -> T,
+    {
+        let mut interned = self.interned.lock().unwrap();
+        let Interned {
+            ref mut tasks,
+            ref mut count,
+        } = *interned;
+        tasks
+            .entry((TypeId::of::<K>(), TypeId::of::<T>()))
+            .or_insert_with(|| Box::new(HashMap::<K, SharedTask<T>>::new()))
+            .downcast_mut::<HashMap<K, SharedTask<T>>>()
+            .expect("Tasks are interned by type.")
+            .entry(key)
+            .or_insert_with(|| {
+                *count += 1;
+                SharedTask::new(make())
+            })
+            .clone()
+    }
+
+    /// The number of distinct tasks interned.
+    pub fn len(&self) -> usize {
+        self.interned.lock().unwrap().count
+    }
+
+    /// Whether no task was interned.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for TaskInterner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskInterner")
+            .field("tasks", &self.len())
+            .finish()
+    }
+}
@@ -0,0 +1,245 @@
+//! Periodic activation of graphs.
+//!
+//! Reactive programs often need to sample their inputs at a fixed rate, or to model a clocked
+//! synchronous system.  A `TimerSource` describes such a clock: every `period`, it sends a value
+//! derived from the current `Tick` on an edge into the graph and activates its target.  Since the
+//! graph is usually single-use, a new edge is requested for each tick, through a closure which can
+//! stop the timer by returning `None`.
+//!
+//! Timers are driven by a dedicated thread and push their ticks through the runtime's external
+//! event mechanism (see the `InjectorSpec` trait), so that they can only be used with runtimes
+//! supporting external events.  The thread stops when the `Timer` handle is dropped; runtimes
+//! owning timers (through the `add_timer` method of `wasm::single_use::Toexec` and of the parallel
+//! runtimes) stop them when dropped.
+//!
+//! The parallel runtimes inject ticks through an `InjectorHandle`, so that their executions only
+//! return once their timers have stopped: timers used with them should have a `limit`, eventually
+//! run out of edges, or be stopped through a `TimerHandle`, which can be cloned and moved into the
+//! nodes of the graph or to other threads.
+//!
+//! Ticks are scheduled at a fixed rate from the time the timer was started: a tick which is late
+//! does not delay the following ones.
+
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use api::prelude::*;
+
+/// A single tick of a timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tick {
+    /// The number of ticks before this one.
+    pub index: u64,
+    /// The time elapsed between the start of the timer and this tick.
+    pub elapsed: Duration,
+}
+
+/// Values which can be sent on a timer's edges.
+///
+/// This is implemented for `()` for pure activations, and for `Tick` and `Duration` (the elapsed
+/// time) for timestamped activations.  It is also implemented for `Option`s of those, so that
+/// timers can send directly into ports initialized with `None`.
+pub trait TickPayload {
+    fn from_tick(tick: Tick) -> Self;
+}
+
+impl TickPayload for () {
+    fn from_tick(_tick: Tick) -> Self {}
+}
+
+impl TickPayload for Tick {
+    fn from_tick(tick: Tick) -> Self {
+        tick
+    }
+}
+
+impl TickPayload for Duration {
+    fn from_tick(tick: Tick) -> Self {
+        tick.elapsed
+    }
+}
+
+impl<T: TickPayload> TickPayload for Option<T> {
+    fn from_tick(tick: Tick) -> Self {
+        Some(T::from_tick(tick))
+    }
+}
+
+/// The description of a periodic timer, before it is started.
+///
+/// `edges` is called on the timer thread before each tick to get the edge on which the tick is
+/// sent; the timer stops once it returns `None`.
+pub struct TimerSource<F> {
+    period: Duration,
+    limit: Option<u64>,
+    edges: F,
+}
+
+impl<F> TimerSource<F> {
+    /// Create a timer ticking every `period`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `period` is zero.
+    pub fn new(period: Duration, edges: F) -> Self {
+        assert!(period > Duration::from_secs(0), "Timer periods must be positive.");
+
+        TimerSource {
+            period,
+            limit: None,
+            edges,
+        }
+    }
+
+    /// Stop the timer after `count` ticks.
+    pub fn limit(mut self, count: u64) -> Self {
+        self.limit = Some(count);
+        self
+    }
+
+    /// The period of the timer.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Start the timer thread, injecting ticks into `runtime`.
+    pub fn start<S, E>(self, runtime: &S) -> Timer
+    where
+        S: InjectorSpec,
+        S::Injector: 'static,
+        F: FnMut(Tick) -> Option<E> + Send + 'static,
+        E: OutputEdgeOnce<S> + Send + Sync + 'static,
+        E::Item: TickPayload + Send + Sync + 'static,
+    {
+        self.start_with::<S, _, E>(runtime.injector())
+    }
+
+    /// Start the timer thread, injecting ticks through `injector`.  This is used by the parallel
+    /// runtimes, whose injectors send on the edges of the workers rather than of the runtime.
+    pub fn start_with<S, I, E>(self, injector: I) -> Timer
+    where
+        I: EventInjector<S> + 'static,
+        F: FnMut(Tick) -> Option<E> + Send + 'static,
+        E: OutputEdgeOnce<S> + Send + Sync + 'static,
+        E::Item: TickPayload + Send + Sync + 'static,
+    {
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let shared = stopped.clone();
+        let TimerSource {
+            period,
+            limit,
+            mut edges,
+        } = self;
+        let thread = thread::spawn(move || {
+            let (ref lock, ref condvar) = *shared;
+            let start = Instant::now();
+            let mut deadline = start;
+            let mut index = 0;
+
+            while limit.is_none_or(|limit| index < limit) {
+                deadline += period;
+                let mut stopped = lock.lock().unwrap();
+                loop {
+                    if *stopped {
+                        return;
+                    }
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    stopped = condvar.wait_timeout(stopped, deadline - now).unwrap().0;
+                }
+                drop(stopped);
+
+                let tick = Tick {
+                    index,
+                    elapsed: start.elapsed(),
+                };
+                match edges(tick) {
+                    Some(edge) => injector.inject_send(edge, E::Item::from_tick(tick)),
+                    None => return,
+                }
+                index += 1;
+            }
+        });
+
+        Timer {
+            stopped,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// A handle to a running timer.  Dropping the handle stops the timer, waiting for its thread to
+/// exit.
+pub struct Timer {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Timer {
+    /// A handle stopping the timer without access to the `Timer` itself.
+    pub fn handle(&self) -> TimerHandle {
+        TimerHandle {
+            stopped: self.stopped.clone(),
+        }
+    }
+
+    /// Whether the timer stopped by itself, either because it reached its limit or because it ran
+    /// out of edges.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|thread| thread.is_finished())
+    }
+
+    /// Stop the timer.  No ticks are injected once this returns.
+    pub fn stop(mut self) {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.handle().stop();
+            // Don't panic while dropping if the `edges` closure panicked.
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.shutdown()
+    }
+}
+
+/// A handle stopping a timer from any thread.  Handles are cheap to clone, and can be moved into
+/// the nodes of the graph receiving the ticks.
+#[derive(Clone)]
+pub struct TimerHandle {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl TimerHandle {
+    /// Stop the timer.  The timer thread exits as soon as it is notified, dropping its injector;
+    /// however, a tick which was being injected when this is called may still be delivered.
+    pub fn stop(&self) {
+        let (ref lock, ref condvar) = *self.stopped;
+        *lock.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+
+    /// Whether `stop` was called on one of the handles of the timer, or the `Timer` was stopped.
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.0.lock().unwrap()
+    }
+}
+
+impl fmt::Debug for TimerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimerHandle")
+            .field("stopped", &self.is_stopped())
+            .finish()
+    }
+}
@@ -0,0 +1,182 @@
+//! Line-oriented file and standard IO.
+//!
+//! A `LineSource` is a fallible task reading one line from a file, or any other reader, each time
+//! its node is executed, and a `LineSink` is a fallible task writing each line it receives.  Both
+//! are buffered, and are meant to be run in a `TryTaskNode`, whose error edge receives the IO
+//! errors instead of panicking the worker:
+//!
+//! ```rust,ignore
+//! let read = b.node(TryTaskNode {
+//!     inputs: (),
+//!     outputs: (line_sender.with_activator(write.add_activator()),),
+//!     errors: ErrorOutput::new(error_sender.with_activator(report).map(Some)),
+//!     task: LineFileSource::open("input.txt")?,
+//! });
+//! ```
+//!
+//! Lines are sent without their line terminator, and the end of the input is signaled by sending
+//! `None`.  Conversely, a sink writes each `Some` line followed by a newline, and flushes its
+//! buffer when it receives `None`.
+//!
+//! The tasks read and write synchronously: a slow file or terminal blocks the worker executing
+//! them, so that they are best given their own worker (see `parallel::affinity`) when the graph
+//! does other work concurrently.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Stdin, Stdout, Write};
+use std::path::Path;
+
+use api::prelude::*;
+
+/// A task sending the lines of a reader, one line per execution.  See the module documentation.
+#[derive(Debug)]
+pub struct LineSource<R> {
+    reader: BufReader<R>,
+}
+
+/// A `LineSource` reading a file.
+pub type LineFileSource = LineSource<File>;
+
+impl<R: Read> LineSource<R> {
+    /// Create a source reading the lines of `reader`.
+    pub fn new(reader: R) -> Self {
+        LineSource {
+            reader: BufReader::new(reader),
+        }
+    }
+
+    /// Read the next line, without its line terminator.
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+impl LineSource<File> {
+    /// Create a source reading the file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(LineSource::new(File::open(path)?))
+    }
+}
+
+impl LineSource<Stdin> {
+    /// Create a source reading the standard input.
+    pub fn stdin() -> Self {
+        LineSource::new(io::stdin())
+    }
+}
+
+impl<S, O, R> TryTaskOnce<(), (O,), S> for LineSource<R>
+where
+    O: OutputEdgeOnce<S, Item = Option<String>>,
+    R: Read,
+{
+    type Error = io::Error;
+
+    fn try_run_once(mut self, scheduler: &mut S, inputs: (), outputs: (O,)) -> io::Result<()> {
+        self.try_run_mut(scheduler, inputs, outputs)
+    }
+}
+
+impl<S, O, R> TryTaskMut<(), (O,), S> for LineSource<R>
+where
+    O: OutputEdgeOnce<S, Item = Option<String>>,
+    R: Read,
+{
+    type Error = io::Error;
+
+    fn try_run_mut(&mut self, scheduler: &mut S, _inputs: (), outputs: (O,)) -> io::Result<()> {
+        let line = self.next_line()?;
+        outputs.0.send_activate_once(scheduler, line);
+        Ok(())
+    }
+}
+
+/// A task writing each line it receives to a writer.  See the module documentation.
+///
+/// The buffer is also flushed when the sink is dropped, but errors are then ignored: the sink
+/// should be sent `None` at the end of the output to report them.
+#[derive(Debug)]
+pub struct LineSink<W: Write> {
+    writer: BufWriter<W>,
+}
+
+/// A `LineSink` writing to a file.
+pub type LineFileSink = LineSink<File>;
+
+impl<W: Write> LineSink<W> {
+    /// Create a sink writing lines to `writer`.
+    pub fn new(writer: W) -> Self {
+        LineSink {
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    fn write_line<L: AsRef<str>>(&mut self, line: Option<L>) -> io::Result<()> {
+        match line {
+            Some(line) => {
+                self.writer.write_all(line.as_ref().as_bytes())?;
+                self.writer.write_all(b"\n")
+            }
+            None => self.writer.flush(),
+        }
+    }
+}
+
+impl LineSink<File> {
+    /// Create a sink writing to the file at `path`, truncating it if it exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(LineSink::new(File::create(path)?))
+    }
+
+    /// Create a sink appending to the file at `path`, creating it if it does not exist.
+    pub fn append<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(LineSink::new(file))
+    }
+}
+
+impl LineSink<Stdout> {
+    /// Create a sink writing to the standard output.
+    pub fn stdout() -> Self {
+        LineSink::new(io::stdout())
+    }
+}
+
+impl<S, I, L, W> TryTaskOnce<(I,), (), S> for LineSink<W>
+where
+    I: InputEdgeOnce<S, Item = Option<L>>,
+    L: AsRef<str>,
+    W: Write,
+{
+    type Error = io::Error;
+
+    fn try_run_once(mut self, scheduler: &mut S, inputs: (I,), outputs: ()) -> io::Result<()> {
+        self.try_run_mut(scheduler, inputs, outputs)?;
+        // Dropping the sink would flush the buffer but ignore the errors.
+        self.writer.flush()
+    }
+}
+
+impl<S, I, L, W> TryTaskMut<(I,), (), S> for LineSink<W>
+where
+    I: InputEdgeOnce<S, Item = Option<L>>,
+    L: AsRef<str>,
+    W: Write,
+{
+    type Error = io::Error;
+
+    fn try_run_mut(&mut self, scheduler: &mut S, inputs: (I,), _outputs: ()) -> io::Result<()> {
+        let line = inputs.0.recv_activate_once(scheduler);
+        self.write_line(line)
+    }
+}
@@ -0,0 +1,404 @@
+//! Boolean circuits built from gates.
+//!
+//! The fragments in this module simulate digital circuits on reusable graphs: each gate is a node
+//! created with the DSL (see `common::dsl`), and each wire carries a boolean.  Multi-bit values are
+//! vectors of wires, least significant bit first:
+//!
+//! ```rust,ignore
+//! runtime.build_scope(|b| {
+//!     let sum = (0..5).map(|_| InputHandle::from_edge(...)).collect::<Vec<_>>();
+//!     let carry_out = sum.pop().unwrap();
+//!     let (x, y, carry_in) = b.instantiate("adder", &RippleCarryAdder::new(4), (sum, carry_out));
+//!     ...
+//! })
+//! ```
+//!
+//! As with all `Subgraph`s, an instance is given the wires its results are sent to, i.e. inputs of
+//! the gates of other instances or `InputHandle::from_edge`, and returns the wires sending values
+//! into it, which are connected with `OutputHandle::connect`.  Each wire must be sent a value at
+//! each execution of the graph for the circuit to produce its results.
+
+use std::marker::PhantomData;
+
+use api::prelude::*;
+use common::builder::{ScopedGraphBuilder, Subgraph};
+use common::dsl::{DslPortSpec, DslTask, InputHandle, OutputHandle, SharedOutput};
+use common::erased::ErasedInput;
+use common::node::TaskNode;
+
+/// A wire carrying a boolean to the input of a gate.  `S` is the scheduler executing the gates.
+pub type Wire<'r, S> = InputHandle<'r, S, bool>;
+
+type Op1 = fn(bool) -> bool;
+type Op2 = fn(bool, bool) -> bool;
+type Op3 = fn(bool, bool, bool) -> bool;
+
+type GateInput<'r, S> = ErasedInput<'r, S, Option<bool>>;
+type Gate<'r, S, I, F> = TaskNode<I, (SharedOutput<'r, S, bool>,), DslTask<F>>;
+
+/// A runtime on which circuits can be built, i.e. which can create the gates of one to three
+/// inputs.
+///
+/// This is implemented for all runtimes supporting the DSL.
+pub trait LogicSpec<'r, S>:
+    DslPortSpec<'r, S, S, bool>
+    + NodeSpec<Gate<'r, S, (GateInput<'r, S>,), Op1>>
+    + NodeSpec<Gate<'r, S, (GateInput<'r, S>, GateInput<'r, S>), Op2>>
+    + NodeSpec<Gate<'r, S, (GateInput<'r, S>, GateInput<'r, S>, GateInput<'r, S>), Op3>>
+{
+}
+
+impl<'r, S, Spec> LogicSpec<'r, S> for Spec where
+    Spec: DslPortSpec<'r, S, S, bool>
+        + NodeSpec<Gate<'r, S, (GateInput<'r, S>,), Op1>>
+        + NodeSpec<Gate<'r, S, (GateInput<'r, S>, GateInput<'r, S>), Op2>>
+        + NodeSpec<Gate<'r, S, (GateInput<'r, S>, GateInput<'r, S>, GateInput<'r, S>), Op3>>
+{
+}
+
+fn identity(x: bool) -> bool {
+    x
+}
+
+fn xor(x: bool, y: bool) -> bool {
+    x ^ y
+}
+
+fn and(x: bool, y: bool) -> bool {
+    x & y
+}
+
+fn or(x: bool, y: bool) -> bool {
+    x | y
+}
+
+fn greater(x: bool, y: bool) -> bool {
+    x & !y
+}
+
+fn less(x: bool, y: bool) -> bool {
+    !x & y
+}
+
+/// Whether `x` is greater than `y`, knowing whether the less significant bits are.
+fn greater_cascade(lower: bool, x: bool, y: bool) -> bool {
+    x & !y | (x == y) & lower
+}
+
+/// Whether `x` is less than `y`, knowing whether the less significant bits are.
+fn less_cascade(lower: bool, x: bool, y: bool) -> bool {
+    !x & y | (x == y) & lower
+}
+
+fn neither(x: bool, y: bool) -> bool {
+    !x & !y
+}
+
+fn select(select: bool, x: bool, y: bool) -> bool {
+    if select {
+        y
+    } else {
+        x
+    }
+}
+
+/// Create a gate forwarding its input, so that the input of a fragment can be sent to several
+/// gates.
+fn buffer<'a, 'r, S, Spec>(
+    builder: &mut ScopedGraphBuilder<'a, Spec>,
+) -> (Wire<'r, S>, OutputHandle<'r, S, bool>)
+where
+    Spec: LogicSpec<'r, S> + 'a,
+    'r: 'a,
+    S: 'r,
+{
+    let mut node = builder.task1(identity as Op1);
+    (node.input0(), node.output())
+}
+
+/// Create `width` buffers.
+fn buffers<'a, 'r, S, Spec>(
+    builder: &mut ScopedGraphBuilder<'a, Spec>,
+    width: usize,
+) -> (Vec<Wire<'r, S>>, Vec<OutputHandle<'r, S, bool>>)
+where
+    Spec: LogicSpec<'r, S> + 'a,
+    'r: 'a,
+    S: 'r,
+{
+    (0..width).map(|_| buffer(builder)).unzip()
+}
+
+/// A half adder, sending the sum and the carry of two bits.
+///
+/// Its inputs are `(x, y)`, and its outputs `(sum, carry)`.
+pub struct HalfAdder<'r, S> {
+    marker: PhantomData<fn() -> Wire<'r, S>>,
+}
+
+impl<'r, S> HalfAdder<'r, S> {
+    /// Create a half adder.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        HalfAdder {
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A full adder, sending the sum and the carry of two bits and an incoming carry.  It is built
+/// from two half adders, in the `low` and `high` namespaces.
+///
+/// Its inputs are `(x, y, carry_in)`, and its outputs `(sum, carry_out)`.
+pub struct FullAdder<'r, S> {
+    marker: PhantomData<fn() -> Wire<'r, S>>,
+}
+
+impl<'r, S> FullAdder<'r, S> {
+    /// Create a full adder.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        FullAdder {
+            marker: PhantomData,
+        }
+    }
+}
+
+// The fragments parameterized by their width.  They are generic over the scheduler, which only
+// appears in their inputs and outputs.
+macro_rules! fragment {
+    ($($(#[$attr:meta])* $name:ident: $new:expr;)*) => {
+        $(
+            $(#[$attr])*
+            pub struct $name<'r, S> {
+                width: usize,
+                marker: PhantomData<fn() -> Wire<'r, S>>,
+            }
+
+            impl<'r, S> $name<'r, S> {
+                #[doc = $new]
+                ///
+                /// # Panics
+                ///
+                /// This panics if `width` is zero.
+                pub fn new(width: usize) -> Self {
+                    assert!(width > 0, "A circuit needs at least one bit.");
+                    $name {
+                        width,
+                        marker: PhantomData,
+                    }
+                }
+
+                /// The number of bits of the numbers.
+                pub fn width(&self) -> usize {
+                    self.width
+                }
+            }
+        )*
+    };
+}
+
+fragment! {
+    /// A ripple-carry adder, sending the sum of two `width`-bit numbers and an incoming carry.  It
+    /// is built from `width` full adders, in the `bit0` to `bit<width - 1>` namespaces.
+    ///
+    /// Its inputs are `(x, y, carry_in)`, and its outputs `(sum, carry_out)`, where `x`, `y` and
+    /// `sum` have `width` bits.
+    RippleCarryAdder: "Create an adder of `width`-bit numbers.";
+
+    /// A comparator of two unsigned `width`-bit numbers.  Exactly one of its outputs is sent
+    /// `true`.
+    ///
+    /// Its inputs are `(x, y)`, and its outputs `(less, equal, greater)`, telling how `x` compares
+    /// to `y`.
+    Comparator: "Create a comparator of `width`-bit numbers.";
+
+    /// A multiplexer of two `width`-bit numbers, sending `y` when its selector is `true` and `x`
+    /// otherwise.
+    ///
+    /// Its inputs are `(select, x, y)`, and its output has `width` bits.
+    Multiplexer: "Create a multiplexer of `width`-bit numbers.";
+}
+
+/// Check that a multi-bit output has the width of the fragment.
+fn check_width<T>(bits: &[T], width: usize) {
+    assert_eq!(
+        bits.len(),
+        width,
+        "Expected {} output bits, got {}.",
+        width,
+        bits.len()
+    );
+}
+
+impl<'a, 'r, S, Spec> Subgraph<'a, Spec> for HalfAdder<'r, S>
+where
+    Spec: LogicSpec<'r, S> + 'a,
+    'r: 'a,
+    S: 'r,
+{
+    type Inputs = (Wire<'r, S>, Wire<'r, S>);
+    type Outputs = (Wire<'r, S>, Wire<'r, S>);
+
+    fn build(
+        &self,
+        builder: &mut ScopedGraphBuilder<'a, Spec>,
+        (sum, carry): Self::Outputs,
+    ) -> Self::Inputs {
+        let (x, x_out) = buffer(builder);
+        let (y, y_out) = buffer(builder);
+        let mut xor = builder.task2(xor as Op2);
+        let mut and = builder.task2(and as Op2);
+
+        x_out.connect(xor.input0());
+        x_out.connect(and.input0());
+        y_out.connect(xor.input1());
+        y_out.connect(and.input1());
+        xor.output().connect(sum);
+        and.output().connect(carry);
+
+        (x, y)
+    }
+}
+
+impl<'a, 'r, S, Spec> Subgraph<'a, Spec> for FullAdder<'r, S>
+where
+    Spec: LogicSpec<'r, S> + 'a,
+    'r: 'a,
+    S: 'r,
+{
+    type Inputs = (Wire<'r, S>, Wire<'r, S>, Wire<'r, S>);
+    type Outputs = (Wire<'r, S>, Wire<'r, S>);
+
+    fn build(
+        &self,
+        builder: &mut ScopedGraphBuilder<'a, Spec>,
+        (sum, carry_out): Self::Outputs,
+    ) -> Self::Inputs {
+        let mut or = builder.task2(or as Op2);
+        or.output().connect(carry_out);
+
+        let (partial, carry_in) =
+            builder.instantiate("high", &HalfAdder::new(), (sum, or.input1()));
+        let (x, y) = builder.instantiate("low", &HalfAdder::new(), (partial, or.input0()));
+
+        (x, y, carry_in)
+    }
+}
+
+impl<'a, 'r, S, Spec> Subgraph<'a, Spec> for RippleCarryAdder<'r, S>
+where
+    Spec: LogicSpec<'r, S> + 'a,
+    'r: 'a,
+    S: 'r,
+{
+    type Inputs = (Vec<Wire<'r, S>>, Vec<Wire<'r, S>>, Wire<'r, S>);
+    type Outputs = (Vec<Wire<'r, S>>, Wire<'r, S>);
+
+    fn build(
+        &self,
+        builder: &mut ScopedGraphBuilder<'a, Spec>,
+        (sum, carry_out): Self::Outputs,
+    ) -> Self::Inputs {
+        check_width(&sum, self.width);
+
+        // The carry of each bit is sent to the next more significant one, which is built first.
+        let mut carry = carry_out;
+        let mut xs = Vec::with_capacity(self.width);
+        let mut ys = Vec::with_capacity(self.width);
+        for (i, sum) in sum.into_iter().enumerate().rev() {
+            let (x, y, carry_in) =
+                builder.instantiate(&format!("bit{}", i), &FullAdder::new(), (sum, carry));
+            xs.push(x);
+            ys.push(y);
+            carry = carry_in;
+        }
+        xs.reverse();
+        ys.reverse();
+
+        (xs, ys, carry)
+    }
+}
+
+impl<'a, 'r, S, Spec> Subgraph<'a, Spec> for Comparator<'r, S>
+where
+    Spec: LogicSpec<'r, S> + 'a,
+    'r: 'a,
+    S: 'r,
+{
+    type Inputs = (Vec<Wire<'r, S>>, Vec<Wire<'r, S>>);
+    type Outputs = (Wire<'r, S>, Wire<'r, S>, Wire<'r, S>);
+
+    fn build(
+        &self,
+        builder: &mut ScopedGraphBuilder<'a, Spec>,
+        (less_out, equal_out, greater_out): Self::Outputs,
+    ) -> Self::Inputs {
+        let (xs, x_outs) = buffers(builder, self.width);
+        let (ys, y_outs) = buffers(builder, self.width);
+
+        // Compare from the least significant bit, each bit overriding the less significant ones
+        // unless both numbers have the same bit.
+        let mut greater_node = builder.task2(greater as Op2);
+        let mut less_node = builder.task2(less as Op2);
+        x_outs[0].connect(greater_node.input0());
+        x_outs[0].connect(less_node.input0());
+        y_outs[0].connect(greater_node.input1());
+        y_outs[0].connect(less_node.input1());
+        let mut greater_lower = greater_node.output();
+        let mut less_lower = less_node.output();
+
+        for (x_out, y_out) in x_outs.iter().zip(&y_outs).skip(1) {
+            let mut greater_node = builder.task3(greater_cascade as Op3);
+            let mut less_node = builder.task3(less_cascade as Op3);
+            greater_lower.connect(greater_node.input0());
+            less_lower.connect(less_node.input0());
+            x_out.connect(greater_node.input1());
+            x_out.connect(less_node.input1());
+            y_out.connect(greater_node.input2());
+            y_out.connect(less_node.input2());
+            greater_lower = greater_node.output();
+            less_lower = less_node.output();
+        }
+
+        let mut equal_node = builder.task2(neither as Op2);
+        greater_lower.connect(equal_node.input0());
+        less_lower.connect(equal_node.input1());
+        equal_node.output().connect(equal_out);
+        greater_lower.connect(greater_out);
+        less_lower.connect(less_out);
+
+        (xs, ys)
+    }
+}
+
+impl<'a, 'r, S, Spec> Subgraph<'a, Spec> for Multiplexer<'r, S>
+where
+    Spec: LogicSpec<'r, S> + 'a,
+    'r: 'a,
+    S: 'r,
+{
+    type Inputs = (Wire<'r, S>, Vec<Wire<'r, S>>, Vec<Wire<'r, S>>);
+    type Outputs = Vec<Wire<'r, S>>;
+
+    fn build(
+        &self,
+        builder: &mut ScopedGraphBuilder<'a, Spec>,
+        outputs: Self::Outputs,
+    ) -> Self::Inputs {
+        check_width(&outputs, self.width);
+
+        let (selector, selector_out) = buffer(builder);
+        let mut xs = Vec::with_capacity(self.width);
+        let mut ys = Vec::with_capacity(self.width);
+        for output in outputs {
+            let mut node = builder.task3(select as Op3);
+            selector_out.connect(node.input0());
+            xs.push(node.input1());
+            ys.push(node.input2());
+            node.output().connect(output);
+        }
+
+        (selector, xs, ys)
+    }
+}
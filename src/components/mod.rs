@@ -0,0 +1,10 @@
+//! Ready-made graph fragments for common applications.
+//!
+//! The fragments implement `Subgraph`, and can be instantiated any number of times with
+//! `ScopedGraphBuilder::instantiate` in graphs built on runtimes supporting reusable graphs.
+//!
+//! This includes boolean circuits in `logic`, as well as tasks reading and writing files line by
+//! line in `io`.
+
+pub mod io;
+pub mod logic;
@@ -0,0 +1,15 @@
+//! Runtimes delegating the execution of nodes to a user-supplied scheduler.
+//!
+//! The other runtimes own the threads executing the nodes.  Applications which already have a
+//! thread pool (or an event loop) can instead plug it in through the `CustomScheduler` adapter in
+//! `single_use`: the application only supplies a `spawn` callback running a job on the pool, and
+//! this crate provides the activators, builders and ports needed to build graphs on top of it.
+//!
+//! The activators, builders and ports are generic over the scheduler, and are found in the `rc`
+//! module.  They are also used by the other runtimes of this crate, which only differ in the way
+//! they schedule nodes.
+//!
+//! This includes a single-use runtime in `single_use`.
+
+pub mod rc;
+pub mod single_use;
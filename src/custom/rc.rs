@@ -0,0 +1,497 @@
+//! Reference-counted activators, builders and ports, generic over the scheduler.
+//!
+//! All the runtimes of this crate activate nodes the same way: the activators of a node share a
+//! pending count, which is set to the number of activators when the node is finalized and
+//! decremented on each activation, and the node is handed to the scheduler when it reaches zero.
+//! This module implements this scheme once, for any scheduler:
+//!
+//!  - `OnceActivator` and `OnceBuilder` are used by single-use runtimes, whose handles are boxed
+//!    `NodeBox` trait objects.  The node is moved out of the activator when it is scheduled.
+//!  - `RcActivator`, `RcHandle` and `RcBuilder` are used by reusable runtimes, whose handles are
+//!    `RcHandle`s.  The node is re-armed each time it is executed.
+//!  - Schedulers implementing `RcPortSpec` get buffered and checked ports.
+//!
+//! Runtimes needing more information about their nodes, such as the worker a node is pinned to in
+//! the parallel runtimes, store it in the `extra` field of the inner structures.  The activator and
+//! builder traits are only implemented here for the unit extension `()`: runtimes with their own
+//! extension implement them on top of the methods of the inner structures.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use api::prelude::*;
+use common::node::UninitializedNode;
+use common::port::CheckedPort;
+
+use parallel::port::{ChannelPort, RcPort};
+use parallel::reset::Rearmable;
+use parallel::validation::{StalledNode, Tracked};
+use sync::{Arc, AtomicUsize, Mutex, MutexGuard, Ordering::SeqCst};
+
+/// The inner structure for a single-use activator, containing the pending count and the node
+/// handle.
+pub(crate) struct OnceInner<H: ?Sized, X = ()> {
+    /// The pending count.
+    pub(crate) pending: AtomicUsize,
+
+    /// The number of activators, once finalized.
+    pub(crate) activators: AtomicUsize,
+
+    /// The label of the node, if any.
+    pub(crate) label: Mutex<Option<Label>>,
+
+    /// The runtime-specific information about the node.
+    pub(crate) extra: X,
+
+    /// The underlying node to schedule.  Note that we store a Box of a trait object here, instead
+    /// of using a type parameter and embedding the node in the structure.  This is because of a
+    /// Rust limitation which prevents us from calling a method with `self` as argument on a trait
+    /// object -- the same reason why we use `NodeBox`.  Unfortunately, that trick only works for
+    /// `Box`, which the Rust compiler has special knowledge of -- so instead we use an extra level
+    /// of indirection and put a box here.
+    pub(crate) handle: Box<H>,
+}
+
+impl<H: ?Sized, X> OnceInner<H, X> {
+    pub(crate) fn new(handle: Box<H>, extra: X) -> Self {
+        OnceInner {
+            pending: AtomicUsize::new(0),
+            activators: AtomicUsize::new(0),
+            label: Mutex::new(None),
+            extra,
+            handle,
+        }
+    }
+
+    /// Record an activation.  If the node is ready, the inner structure is returned so that the
+    /// node can be scheduled; otherwise, the remaining pending count is returned.
+    ///
+    /// The node is returned to the activator dropping the last reference to the inner structure,
+    /// which is not necessarily the one bringing the pending count to zero: in parallel runtimes,
+    /// another activator may have decremented the count but not dropped its reference yet.
+    pub(crate) fn release(self: Arc<Self>) -> Result<Self, usize> {
+        let pending = self.pending.fetch_sub(1, SeqCst) - 1;
+        match Arc::into_inner(self) {
+            Some(inner) if inner.pending.load(SeqCst) == 0 => Ok(inner),
+            _ => Err(pending),
+        }
+    }
+
+    /// The label of the node, or a placeholder for unnamed nodes.
+    fn name(&self) -> Label {
+        self.label
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or(Label::Borrowed("<unnamed>"))
+    }
+}
+
+/// The inner structure is consumed when the node is scheduled: nodes which are still alive once the
+/// execution has quiesced were never executed.
+impl<H: ?Sized + Send + Sync, X: Send + Sync> Tracked for OnceInner<H, X> {
+    fn stalled(&self) -> Option<StalledNode> {
+        Some(StalledNode {
+            label: self.label.lock().unwrap().clone(),
+            pending: self.pending.load(SeqCst),
+        })
+    }
+
+    fn label(&self) -> Option<Label> {
+        self.label.lock().unwrap().clone()
+    }
+}
+
+/// A reference-counted, single-use activator.
+///
+/// The activator contains a handle to a node, as well as a counter for the number of activations
+/// remaining before the node should be scheduled.
+///
+/// When the node is finalized, the counter is set to the number of activators created.  It is
+/// decremented by one on each activation, and the node is scheduled when the counter reaches zero.
+/// Since activating consumes an activator, we ensure that the pending count only ever reaches zero
+/// if all activators have been called.
+pub struct OnceActivator<H: ?Sized, X = ()> {
+    pub(crate) inner: Arc<OnceInner<H, X>>,
+}
+
+impl<H: ?Sized, X> OnceActivator<H, X> {
+    /// The label of the underlying node, if any.
+    pub fn label(&self) -> Option<Label> {
+        self.inner.label.lock().unwrap().clone()
+    }
+}
+
+impl<H: ?Sized, X> fmt::Debug for OnceActivator<H, X> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcActivator")
+            .field("node", &self.inner.name())
+            .field("pending", &self.inner.pending.load(SeqCst))
+            .finish()
+    }
+}
+
+impl<'r, S> ActivatorOnce<S> for OnceActivator<dyn NodeBox<S> + Send + Sync + 'r>
+where
+    S: Scheduler<Handle = Box<dyn NodeBox<S> + Send + Sync + 'r>>,
+{
+    fn activate_once(self, scheduler: &mut S) {
+        if let Ok(inner) = self.inner.release() {
+            scheduler.schedule(inner.handle)
+        }
+    }
+}
+
+/// A builder for single-use nodes.  Allow creation of activators and arms them when finalized.
+///
+/// Note that once the builder is created, no modifications to the node are permitted (the builder
+/// does not implement the `NodeBorrowMut` trait).  This is due to the fact that we need to store a
+/// (boxed) trait object inside the activator in order to be able to call it later using
+/// `execute_box`; see the documentation on `OnceInner`.
+pub struct OnceBuilder<H: ?Sized, N, X = ()> {
+    pub(crate) inner: Arc<OnceInner<H, X>>,
+    _marker: PhantomData<*const N>,
+    num_activators: usize,
+}
+
+impl<H: ?Sized, N> OnceBuilder<H, N> {
+    /// Create a builder for a node boxed as `handle`.  The node is usually boxed by the `NodeSpec`
+    /// implementation of the runtime, which knows the trait object type `H`.
+    pub(crate) fn new(handle: Box<H>) -> Self {
+        OnceBuilder::with_extra(handle, ())
+    }
+}
+
+impl<H: ?Sized, N, X> OnceBuilder<H, N, X> {
+    pub(crate) fn with_extra(handle: Box<H>, extra: X) -> Self {
+        OnceBuilder {
+            inner: Arc::new(OnceInner::new(handle, extra)),
+            _marker: PhantomData,
+            num_activators: 0,
+        }
+    }
+
+    /// Create a new activator for the node.
+    pub(crate) fn activator(&mut self) -> OnceActivator<H, X> {
+        self.num_activators += 1;
+
+        OnceActivator {
+            inner: self.inner.clone(),
+        }
+    }
+
+    pub(crate) fn label(&mut self, label: Label) {
+        *self.inner.label.lock().unwrap() = Some(label);
+    }
+
+    /// Set the pending count to the number of activators created.
+    pub(crate) fn arm(&mut self) {
+        self.inner.pending.store(self.num_activators, SeqCst);
+        self.inner.activators.store(self.num_activators, SeqCst);
+    }
+}
+
+impl<S: GraphSpec<Activator = OnceActivator<H>>, H: ?Sized, N> NodeBuilder<S>
+    for OnceBuilder<H, N>
+{
+    type Node = N;
+
+    fn add_activator(&mut self) -> OnceActivator<H> {
+        self.activator()
+    }
+
+    fn set_label(&mut self, label: Label) {
+        self.label(label)
+    }
+
+    fn finalize(&mut self, _spec: &mut S) {
+        self.arm()
+    }
+}
+
+/// The inner structure for a reusable activator.  This include a handle to the node, as well as a
+/// pending count with interior mutability.  Contrary to `OnceInner`, we also use interior
+/// mutability for the handle because we need to be able to access the handle while there are still
+/// other references to the inner structure (hence the reusable nature).
+#[derive(Debug)]
+pub(crate) struct RcInner<H: ?Sized, X = ()> {
+    /// The pending count.  If 0, there is currently a builder or a handle pointing to the node.
+    pub(crate) pending: AtomicUsize,
+    /// The initial pending count to reset to.  This includes the handle.
+    pub(crate) initial: AtomicUsize,
+    /// The label of the node, if any.
+    pub(crate) label: Mutex<Option<Label>>,
+    /// The runtime-specific information about the node.
+    pub(crate) extra: X,
+    /// The underlying node to schedule.
+    pub(crate) handle: Mutex<H>,
+}
+
+impl<H, X: Default> RcInner<H, X> {
+    pub(crate) fn new(node: H) -> Self {
+        RcInner {
+            pending: AtomicUsize::new(0),
+            initial: AtomicUsize::new(1),
+            label: Mutex::new(None),
+            extra: X::default(),
+            handle: Mutex::new(node),
+        }
+    }
+}
+
+impl<H: ?Sized, X> RcInner<H, X> {
+    /// Account for a new activator in the initial pending count.
+    pub(crate) fn add_activator(&self) {
+        self.initial.fetch_add(1, SeqCst);
+    }
+
+    /// Rearm the activation structure with a new pending count. This should only be called when
+    /// the activator was depleted.
+    pub(crate) fn rearm(&self) {
+        let initial = self.initial.load(SeqCst);
+        assert!(
+            self.pending.swap(initial, SeqCst) == 0,
+            "Node `{}` was rearmed while still pending.",
+            self.name()
+        );
+    }
+
+    /// Decrement the pending count and return the new pending count.
+    pub(crate) fn decrement_pending(&self) -> usize {
+        let old_pending = self.pending.fetch_sub(1, SeqCst);
+        assert!(
+            old_pending > 0,
+            "Node `{}` was activated while not armed.",
+            self.name()
+        );
+        old_pending - 1
+    }
+
+    /// The label of the node, or a placeholder for unnamed nodes.
+    pub(crate) fn name(&self) -> Label {
+        self.label
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or(Label::Borrowed("<unnamed>"))
+    }
+}
+
+/// Idle nodes are waiting for all their activators: nodes which were only partially activated (or
+/// scheduled, but not executed) once the execution has quiesced are stalled.
+impl<H: ?Sized + Send + Sync, X: Send + Sync> Tracked for RcInner<H, X> {
+    fn stalled(&self) -> Option<StalledNode> {
+        let pending = self.pending.load(SeqCst);
+        if pending == self.initial.load(SeqCst) - 1 {
+            return None;
+        }
+        Some(StalledNode {
+            label: self.label.lock().unwrap().clone(),
+            pending,
+        })
+    }
+
+    fn label(&self) -> Option<Label> {
+        self.label.lock().unwrap().clone()
+    }
+}
+
+/// Resetting a node restores its initial pending count, as if it had just been finalized.
+impl<H: ?Sized + Send + Sync, X: Send + Sync> Rearmable for RcInner<H, X> {
+    fn reset(&self) {
+        self.pending.store(self.initial.load(SeqCst) - 1, SeqCst)
+    }
+}
+
+/// A reference-counted, reusable activator.
+///
+/// The activator contains a handle to a node, a counter for the number of activations
+/// remaining before the node should be scheduled, and the total number of activators.
+///
+/// When the node is finalized, the counter is set to the total number of activators.  It is
+/// decremented by one on each activation, and the node is scheduled when the counter reaches zero.
+pub struct RcActivator<H: ?Sized, X = ()> {
+    pub(crate) inner: Arc<RcInner<H, X>>,
+}
+
+impl<H: ?Sized, X> RcActivator<H, X> {
+    /// The label of the underlying node, if any.
+    pub fn label(&self) -> Option<Label> {
+        self.inner.label.lock().unwrap().clone()
+    }
+}
+
+impl<H: ?Sized, X> fmt::Debug for RcActivator<H, X> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcActivator")
+            .field("node", &self.inner.name())
+            .field("pending", &self.inner.pending.load(SeqCst))
+            .finish()
+    }
+}
+
+/// A default activator which schedules a panicking node.  This can be used as a placeholder
+/// activator when the target node is not yet known.  Note that trying to activate this will
+/// already trigger a panic in `decrement_pending` since it never gets armed.
+impl<'r, S: 'r, X: Default> Default for RcActivator<dyn NodeMut<S> + Send + Sync + 'r, X> {
+    fn default() -> Self {
+        RcActivator {
+            inner: Arc::new(RcInner::new(UninitializedNode::new())),
+        }
+    }
+}
+
+impl<'r, S: 'r, X: Default> RcActivator<dyn NodeMut<S> + Send + Sync + 'r, X> {
+    /// Create a placeholder activator, like `default`, standing for the node named `label`.
+    pub fn uninitialized<L: Into<Label>>(label: L) -> Self {
+        let label = label.into();
+        let inner = RcInner::new(UninitializedNode::named(label.clone()));
+        *inner.label.lock().unwrap() = Some(label);
+        RcActivator {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl<'r, S> ActivatorOnce<S> for RcActivator<dyn NodeMut<S> + Send + Sync + 'r>
+where
+    S: Scheduler<Handle = RcHandle<dyn NodeMut<S> + Send + Sync + 'r>>,
+{
+    fn activate_once(self, scheduler: &mut S) {
+        if self.inner.decrement_pending() == 0 {
+            scheduler.schedule(RcHandle { inner: self.inner })
+        }
+    }
+}
+
+impl<'r, S> ActivatorMut<S> for RcActivator<dyn NodeMut<S> + Send + Sync + 'r>
+where
+    S: Scheduler<Handle = RcHandle<dyn NodeMut<S> + Send + Sync + 'r>>,
+{
+    fn activate_mut(&mut self, scheduler: &mut S) {
+        Activator::activate(self, scheduler)
+    }
+}
+
+impl<'r, S> Activator<S> for RcActivator<dyn NodeMut<S> + Send + Sync + 'r>
+where
+    S: Scheduler<Handle = RcHandle<dyn NodeMut<S> + Send + Sync + 'r>>,
+{
+    fn activate(&self, scheduler: &mut S) {
+        if self.inner.decrement_pending() == 0 {
+            scheduler.schedule(RcHandle {
+                inner: self.inner.clone(),
+            })
+        }
+    }
+}
+
+/// A node handle.  This is the structured used to actually schedule nodes.  A single handle to a
+/// given node should ever exist, and it can only exist when the node's pending count is 0.
+pub struct RcHandle<H: ?Sized, X = ()> {
+    pub(crate) inner: Arc<RcInner<H, X>>,
+}
+
+impl<H: ?Sized, X> fmt::Debug for RcHandle<H, X> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcHandle")
+            .field("node", &self.inner.name())
+            .finish()
+    }
+}
+
+impl<S, H: NodeMut<S> + ?Sized, X> NodeOnce<S> for RcHandle<H, X>
+where
+    RcActivator<H, X>: ActivatorOnce<S>,
+{
+    /// Execute the guard.  This consumes the guard and re-arm the activators, which allows the
+    /// node to be executed again later.
+    fn execute_once(self, scheduler: &mut S) {
+        self.inner.rearm();
+        self.inner.handle.lock().unwrap().execute_mut(scheduler);
+        RcActivator { inner: self.inner }.activate_once(scheduler);
+    }
+}
+
+/// A builder for reusable nodes.  Allow creation of activators and arms them when finalized.
+#[derive(Debug)]
+pub struct RcBuilder<N, X = ()> {
+    pub(crate) inner: Arc<RcInner<N, X>>,
+    _marker: PhantomData<*const N>,
+}
+
+impl<N, X: Default> RcBuilder<N, X> {
+    pub(crate) fn new(node: N) -> Self {
+        RcBuilder {
+            inner: Arc::new(RcInner::new(node)),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<N, X> RcBuilder<N, X> {
+    pub(crate) fn label(&mut self, label: Label) {
+        *self.inner.label.lock().unwrap() = Some(label);
+    }
+
+    /// Set the pending count to the number of activators, minus the builder's own handle.
+    pub(crate) fn arm(&mut self) {
+        self.inner.rearm();
+        self.inner.decrement_pending();
+    }
+}
+
+impl<'r, S, N> NodeBuilder<S> for RcBuilder<N>
+where
+    S: GraphSpec<Activator = RcActivator<dyn NodeMut<S> + Send + Sync + 'r>>,
+    N: NodeMut<S> + Send + Sync + 'r,
+{
+    type Node = N;
+
+    fn add_activator(&mut self) -> S::Activator {
+        self.inner.add_activator();
+
+        RcActivator {
+            inner: self.inner.clone(),
+        }
+    }
+
+    fn set_label(&mut self, label: Label) {
+        self.label(label)
+    }
+
+    fn finalize(&mut self, _spec: &mut S) {
+        self.arm()
+    }
+}
+
+impl<'a, S: GraphSpec, N: 'a, X: 'a> NodeBorrowMut<'a, S> for RcBuilder<N, X>
+where
+    RcBuilder<N, X>: NodeBuilder<S, Node = N>,
+{
+    type RefMut = MutexGuard<'a, N>;
+
+    fn borrow_mut(&'a mut self) -> Self::RefMut {
+        self.inner.handle.lock().unwrap()
+    }
+}
+
+/// A scheduler using the buffered and checked ports of this module, as opposed to ports with
+/// runtime-specific bookkeeping.
+pub trait RcPortSpec {}
+
+impl<S: RcPortSpec, T: Default> BufferedPortSpec<T> for S {
+    type Port = RcPort<ChannelPort<T>>;
+
+    fn port_buffered(&self, capacity: usize) -> Self::Port {
+        RcPort::new(ChannelPort::new(capacity))
+    }
+}
+
+impl<S: RcPortSpec, T: Default> CheckedPortSpec<T> for S {
+    type Port = RcPort<CheckedPort<Mutex<T>>>;
+
+    fn port_checked(&self, name: &str) -> Self::Port {
+        RcPort::new(CheckedPort::new(name, Mutex::new(T::default())))
+    }
+}
@@ -0,0 +1,128 @@
+//! A single-use runtime executing nodes on a user-supplied scheduler.
+//!
+//! The scheduler is described by a `spawn` callback receiving `Job`s: each job executes a single
+//! node, and may be run on any thread, in any order.  For instance, with an existing pool:
+//!
+//! ```rust,ignore
+//! let pool = Arc::new(MyPool::new());
+//! let mut scheduler = CustomScheduler::new(move |job| pool.execute(job));
+//! let root = scheduler.build_scope(|b| { ... });
+//! root.activate_once(&mut scheduler);
+//! scheduler.wait();
+//! ```
+//!
+//! `CustomScheduler::thread_per_node` provides an example implementation spawning a new thread for
+//! each node.
+//!
+//! The scheduler keeps track of the jobs which were spawned but not completed, so that `wait` can
+//! block until the graph has quiesced.  Since jobs may outlive any scope, nodes must be `'static`.
+
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+
+use api::prelude::*;
+use custom::rc::{OnceActivator, OnceBuilder, RcPortSpec};
+
+use parallel::port::{RcPort, SlotPort};
+use parallel::termination::Termination;
+
+/// A unit of work handed to the user-supplied scheduler.  Running it executes a single node.
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// The type of nodes manipulated by the custom runtime.
+type RuntimeNode = dyn NodeBox<CustomScheduler> + Send + Sync;
+
+/// A reference-counted, single-use activator.  The node is spawned when all its activators have
+/// been activated.
+pub type RcActivator = OnceActivator<RuntimeNode>;
+
+/// A builder for single-use nodes.
+pub type RcBuilder<N> = OnceBuilder<RuntimeNode, N>;
+
+/// An adapter turning a `spawn` callback into a runtime for single-use graphs.
+///
+/// The scheduler is cheap to clone; clones share the same callback and count of running jobs.  It
+/// is also the scheduler type passed to executing tasks.
+#[derive(Clone)]
+pub struct CustomScheduler {
+    spawn: Arc<dyn Fn(Job) + Send + Sync>,
+    termination: Arc<Termination>,
+}
+
+impl CustomScheduler {
+    /// Create a new runtime handing the nodes to execute to `spawn`.
+    pub fn new<F: Fn(Job) + Send + Sync + 'static>(spawn: F) -> Self {
+        CustomScheduler {
+            spawn: Arc::new(spawn),
+            termination: Arc::new(Termination::new(0)),
+        }
+    }
+
+    /// Create a new runtime executing each node on a new thread.  This is mostly meant as an
+    /// example of a `spawn` callback.
+    pub fn thread_per_node() -> Self {
+        CustomScheduler::new(|job| {
+            thread::spawn(job);
+        })
+    }
+
+    /// Whether all the spawned nodes have completed.
+    pub fn is_idle(&self) -> bool {
+        self.termination.is_done()
+    }
+
+    /// Block until all the spawned nodes, as well as the nodes they spawn, have completed.
+    ///
+    /// Note that this never returns if the user-supplied scheduler drops jobs.
+    pub fn wait(&self) {
+        while !self.termination.is_done() {
+            self.termination.park()
+        }
+    }
+}
+
+impl fmt::Debug for CustomScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomScheduler")
+            .field("idle", &self.is_idle())
+            .finish()
+    }
+}
+
+impl Scheduler for CustomScheduler {
+    type Handle = Box<RuntimeNode>;
+
+    fn schedule(&mut self, handle: Self::Handle) {
+        let mut scheduler = self.clone();
+        self.termination.scheduled();
+        (self.spawn)(Box::new(move || {
+            handle.execute_box(&mut scheduler);
+            scheduler.termination.completed();
+        }))
+    }
+}
+
+impl Capabilities for CustomScheduler {}
+
+impl GraphSpec for CustomScheduler {
+    type Activator = RcActivator;
+}
+
+impl<N: NodeBox<CustomScheduler> + Send + Sync + 'static> NodeSpec<N> for CustomScheduler {
+    type Builder = RcBuilder<N>;
+
+    fn node(&self, node: N) -> Self::Builder {
+        RcBuilder::new(Box::new(node))
+    }
+}
+
+impl<T> PortSpec<T> for CustomScheduler {
+    type Port = RcPort<SlotPort<T>>;
+
+    fn port(&self, init: T) -> Self::Port {
+        RcPort::new(SlotPort::new(init))
+    }
+}
+
+impl RcPortSpec for CustomScheduler {}
@@ -3,12 +3,30 @@
 #![recursion_limit = "256"]
 
 extern crate crossbeam;
+#[cfg(loom)]
+extern crate loom;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+#[macro_use]
+pub mod macros;
 
 pub mod api;
+pub mod bench_support;
 pub mod common;
+pub mod components;
+pub mod custom;
 pub mod parallel;
+pub mod patterns;
+pub mod sequential;
+mod sync;
+pub mod testing;
+pub mod wasm;
 
-#[cfg(test)]
+// The loom primitives can only be used by the loom tests (see the `sync` module).
+#[cfg(all(test, not(loom)))]
 mod tests {
     // Those tests build the following graph, where:
     //
@@ -156,7 +174,7 @@ mod tests {
             });
             root.send_activate_once(&mut runtime, Some(1));
 
-            runtime.execute(5);
+            runtime.execute(5).unwrap();
         }
         assert_eq!(x, Some(1));
         assert_eq!(y, Some(1));
@@ -263,7 +281,7 @@ fn demi_additionneur() {
         root.0.send_activate_once(&mut runtime, Some(true));
         root.1.send_activate_once(&mut runtime, Some(false));
 
-        runtime.execute(2);
+        runtime.execute(2).unwrap();
     }
 
     assert_eq!(x, Some(true));
@@ -347,10 +365,7 @@ fn demi_additionneur() {
                         if data < 10 {
                             let next_activator = scheduler.build_scope(|b| {
                                 let (sender, receiver) = b.port(None).split();
-                                sender.send_once(Some(data + 1));
-
                                 let (output_sender, output_receiver) = b.port(None).split();
-                                output_sender.send_once(Some(output));
                                 b.node(TaskNode {
                                     inputs: (
                                         receiver.as_data_input(),
@@ -359,6 +374,8 @@ fn demi_additionneur() {
                                     outputs: (),
                                     task: Loop10,
                                 })
+                                .preload(sender, Some(data + 1))
+                                .preload(output_sender, Some(output))
                                 .add_activator()
                             });
                             next_activator.activate_once(scheduler);
@@ -369,8 +386,6 @@ fn demi_additionneur() {
                 }
 
                 let (loop10_output_send, loop10_output_recv) = b.port(None).split();
-                loop10_output_send.send(Some(setz_input));
-
                 let loop10_activator = b
                     .node(TaskNode {
                         inputs: (
@@ -380,6 +395,7 @@ fn demi_additionneur() {
                         outputs: (),
                         task: Loop10,
                     })
+                    .preload(loop10_output_send, Some(setz_input))
                     .add_activator();
                 let loop10_input = loop10_sender.with_activator(loop10_activator);
 
@@ -399,7 +415,7 @@ fn demi_additionneur() {
             });
             root.send_activate(&mut runtime, Some(1));
 
-            runtime.execute(5);
+            runtime.execute(5).unwrap();
         }
         assert_eq!(x, Some(1));
         assert_eq!(y, Some(1));
@@ -504,7 +520,7 @@ fn demi_additionneur() {
             });
             root.send_activate(&mut runtime, Some(1));
 
-            runtime.execute(5);
+            runtime.execute(5).unwrap();
         }
 
         assert_eq!(x, Some(1));
@@ -512,4 +528,5541 @@ fn demi_additionneur() {
         assert_eq!(z, Some(10));
   
   }
-}
\ No newline at end of file
+
+    #[test]
+    fn wasm_cooperative() {
+        use std::time::Duration;
+        use wasm::clock::ManualClock;
+        use wasm::single_use::*;
+
+        let mut x = None;
+        let mut y = None;
+
+        {
+            let x_ref = &mut x;
+            let y_ref = &mut y;
+
+            let clock = ManualClock::new();
+            let mut runtime = Toexec::with_clock(clock.clone());
+
+            let (setx_input, sety_input) = runtime.build_scope(|b| {
+                let (setx_sender, setx_receiver) = b.port(None).split();
+                let setx_activator = b
+                    .node(TaskNode {
+                        inputs: (setx_receiver.as_data_input(),),
+                        outputs: (),
+                        task: StrictTask::new(move |x| *x_ref = x),
+                    })
+                    .add_activator();
+
+                let (sety_sender, sety_receiver) = b.port(None).split();
+                let sety_activator = b
+                    .node(TaskNode {
+                        inputs: (sety_receiver.as_data_input(),),
+                        outputs: (),
+                        task: StrictTask::new(move |y| *y_ref = y),
+                    })
+                    .add_activator();
+
+                (
+                    setx_sender.with_activator(setx_activator),
+                    sety_sender.with_activator(sety_activator),
+                )
+            });
+
+            runtime.injector().inject_send(setx_input, Some(1));
+            runtime.set_timeout(Duration::from_millis(10), sety_input, Some(2));
+
+            // The injected event is processed first, then the node it activated as a microtask.
+            assert!(runtime.execute_step(1));
+            assert!(!runtime.execute_step(1));
+
+            // The timer only injects its event once the clock reaches its deadline.
+            clock.advance(Duration::from_millis(5));
+            assert!(runtime.is_idle());
+            clock.advance(Duration::from_millis(5));
+            assert!(!runtime.is_idle());
+            runtime.execute();
+        }
+
+        assert_eq!(x, Some(1));
+        assert_eq!(y, Some(2));
+    }
+
+    #[test]
+    fn start_barrier() {
+        use parallel::single_use::*;
+
+        let mut x = None;
+
+        {
+            let x_ref = &mut x;
+
+            let mut runtime = Toexec::new();
+            let mut barrier = StartBarrier::new();
+
+            let setx_input = runtime.build_scope(|b| {
+                let (setx_sender, setx_receiver) = b.port(None).split();
+                let mut setx_node = b.node(TaskNode {
+                    inputs: (setx_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x| *x_ref = x),
+                });
+                barrier.register(&mut setx_node);
+                setx_sender.with_activator(setx_node.add_activator())
+            });
+
+            // The input is sent, but the node is held by the barrier.
+            setx_input.send_activate_once(&mut runtime, Some(1));
+            assert_eq!(runtime.ready.len(), 0);
+
+            barrier.release(&mut runtime);
+            assert_eq!(runtime.ready.len(), 1);
+
+            runtime.execute(2).unwrap();
+        }
+
+        assert_eq!(x, Some(1));
+    }
+
+    #[test]
+    fn provenance() {
+        use parallel::single_use::*;
+
+        let mut result = None;
+
+        {
+            let result_ref = &mut result;
+
+            let mut runtime = Toexec::new();
+
+            let (x_input, y_input) = runtime.build_scope(|b| {
+                let (sink_sender, sink_receiver) = b.port(Traced::default()).split();
+                let sink_activator = b
+                    .node(TaskNode {
+                        inputs: (sink_receiver.as_data_input(),),
+                        outputs: (),
+                        task: StrictTask::new(move |r| *result_ref = Some(r)),
+                    })
+                    .add_activator();
+
+                let (add_x_sender, add_x_receiver) = b.port(Traced::default()).split();
+                let (add_y_sender, add_y_receiver) = b.port(Traced::default()).split();
+                let mut add_node = b.node(TaskNode {
+                    inputs: (add_x_receiver.as_data_input(), add_y_receiver.as_data_input()),
+                    outputs: (sink_sender.with_activator(sink_activator),),
+                    task: TracedTask::new("add", |x: Option<i32>, y: Option<i32>| {
+                        Some(x.unwrap() + y.unwrap())
+                    }),
+                });
+                let add_x_input = add_x_sender.with_activator(add_node.add_activator());
+                let add_y_input = add_y_sender.with_activator(add_node.add_activator());
+
+                let (double_sender, double_receiver) = b.port(Traced::default()).split();
+                let double_activator = b
+                    .node(TaskNode {
+                        inputs: (double_receiver.as_data_input(),),
+                        outputs: (add_x_input,),
+                        task: TracedTask::new("double", |x: Option<i32>| x.map(|x| 2 * x)),
+                    })
+                    .add_activator();
+
+                (double_sender.with_activator(double_activator), add_y_input)
+            });
+
+            x_input.send_activate_once(&mut runtime, Traced::new(Some(3), 8));
+            y_input.send_activate_once(&mut runtime, Traced::new(Some(1), 8));
+
+            runtime.execute(2).unwrap();
+        }
+
+        let result = result.unwrap();
+        assert_eq!(result.value, Some(7));
+        assert_eq!(
+            result.lineage.stamps(),
+            &[
+                Stamp { node: "double", instant: 0 },
+                Stamp { node: "add", instant: 0 },
+            ]
+        );
+        assert_eq!(result.dump(), "Some(7) <= source -> double#0 -> add#0");
+    }
+
+    #[test]
+    fn smu_reconfigure_fan_in() {
+        use parallel::multiple_uses::*;
+
+        let mut count = 0;
+
+        {
+            let count_ref = &mut count;
+
+            let mut runtime = Toexec::new();
+
+            let (first, second) = runtime.build_scope(|b| {
+                let mut node = b.node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(move || *count_ref += 1),
+                });
+                (node.add_activator(), node.add_activator())
+            });
+
+            // The fan-in can't be changed while the node is waiting for its other producer.
+            first.activate(&mut runtime);
+            let mut change = first.reconfigure();
+            change.unsubscribe(second);
+            let second = match change.commit() {
+                Err(FanInError::Busy(mut removed)) => removed.pop().unwrap(),
+                _ => panic!("Reconfiguring an activated node should fail."),
+            };
+            second.activate(&mut runtime);
+            runtime.execute(2).unwrap();
+
+            // Between executions, replace the second producer with two new ones.
+            let mut change = first.reconfigure();
+            change.unsubscribe(second);
+            change.subscribe();
+            change.subscribe();
+            let added = change.commit().unwrap();
+            assert_eq!(added.len(), 2);
+
+            first.activate(&mut runtime);
+            added[0].activate(&mut runtime);
+            assert_eq!(runtime.ready.len(), 0);
+            added[1].activate(&mut runtime);
+            runtime.execute(2).unwrap();
+        }
+
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn service_node() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+        use wasm::single_use::*;
+
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let service = ServiceNode::new(2, |x: Option<i32>| {
+            thread::sleep(Duration::from_millis(10));
+            x.map(|x| x * x)
+        });
+
+        let mut runtime = Toexec::new();
+
+        let inputs: Vec<_> = (0..4)
+            .map(|_| {
+                let results = results.clone();
+                runtime.build_scope(|b| {
+                    let (sink_sender, sink_receiver) = b.port(None).split();
+                    let sink_activator = b
+                        .node(TaskNode {
+                            inputs: (sink_receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |x: Option<i32>| {
+                                results.lock().unwrap().push(x.unwrap())
+                            }),
+                        })
+                        .add_activator();
+
+                    let (sender, receiver) = b.port(None).split();
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (sink_sender.with_activator(sink_activator),),
+                            task: service.clone(),
+                        })
+                        .add_activator();
+                    sender.with_activator(activator)
+                })
+            })
+            .collect();
+
+        for (i, input) in inputs.into_iter().enumerate() {
+            input.send_activate_once(&mut runtime, Some(i as i32));
+        }
+
+        // The service nodes return immediately; responses are injected back as they arrive.
+        runtime.execute();
+        for _ in 0..500 {
+            if results.lock().unwrap().len() == 4 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(2));
+            runtime.execute();
+        }
+
+        let mut results = results.lock().unwrap().clone();
+        results.sort();
+        assert_eq!(results, vec![0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn sequential_order() {
+        use sequential::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let root = runtime.build_scope(|b| {
+            let (c_sender, c_receiver) = b.port(None).split();
+            let c_log = log.clone();
+            let c_activator = b
+                .node(TaskNode {
+                    inputs: (c_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        c_log.lock().unwrap().push(("c", x.unwrap()))
+                    }),
+                })
+                .add_activator();
+            let c_input = c_sender.with_activator(c_activator);
+
+            let (a_sender, a_receiver) = b.port(None).split();
+            let a_log = log.clone();
+            let a_activator = b
+                .node(TaskNode {
+                    inputs: (a_receiver.as_data_input(),),
+                    outputs: (c_input,),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        a_log.lock().unwrap().push(("a", x.unwrap()));
+                        (x.map(|x| x + 1),)
+                    }),
+                })
+                .add_activator();
+            let a_input = a_sender.with_activator(a_activator);
+
+            let (b_sender, b_receiver) = b.port(None).split();
+            let b_log = log.clone();
+            let b_activator = b
+                .node(TaskNode {
+                    inputs: (b_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        b_log.lock().unwrap().push(("b", x.unwrap()))
+                    }),
+                })
+                .add_activator();
+            let b_input = b_sender.with_activator(b_activator);
+
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (a_input, b_input),
+                    task: StrictTask::new(|x: Option<i32>| (x, x)),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        root.send_activate_once(&mut runtime, Some(1));
+
+        runtime.execute(4);
+
+        // Nodes are executed in scheduling order: `c` is only scheduled by `a`, after `b`.
+        assert_eq!(*log.lock().unwrap(), vec![("a", 1), ("b", 1), ("c", 2)]);
+    }
+
+    #[test]
+    fn node_metadata() {
+        use sequential::single_use::*;
+
+        let inspector = Inspector::new();
+        let mut runtime = Toexec::new();
+
+        let root = runtime.build_scope(|b| {
+            b.inspect(&inspector);
+
+            let (sink_sender, sink_receiver) = b.port(None).split();
+            let sink_activator = b
+                .node(TaskNode {
+                    inputs: (sink_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(|_: Option<bool>| ()),
+                })
+                .add_activator();
+
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (sink_sender.with_activator(sink_activator),),
+                    task: StrictTask::new(|x: Option<i32>| (x.map(|x| x % 2 == 0),)),
+                })
+                .describe("computes parity")
+                .tag("math")
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        root.send_activate_once(&mut runtime, Some(3));
+        runtime.execute(1);
+
+        assert_eq!(inspector.len(), 2);
+        assert_eq!(inspector.tagged("math"), vec![NodeId(1)]);
+        assert_eq!(
+            inspector.metadata(NodeId(1)).description,
+            Some("computes parity".to_string())
+        );
+        assert_eq!(
+            inspector.summary(),
+            "#0: <undocumented>\n#1: computes parity [math]\n"
+        );
+    }
+
+    #[test]
+    fn psu_termination() {
+        use parallel::single_use::*;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::Duration;
+
+        // A binary tree of dynamically scheduled nodes.  The leaves take some time to execute so
+        // that workers run out of work while others are still busy.
+        struct Spawn {
+            depth: usize,
+            counter: Arc<AtomicUsize>,
+        }
+
+        impl<'r> TaskOnce<(), (), RuntimeLoc<'r>> for Spawn {
+            fn run_once(self, scheduler: &mut RuntimeLoc<'r>, _inputs: (), _outputs: ()) {
+                self.counter.fetch_add(1, SeqCst);
+                if self.depth == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                    return;
+                }
+                for _ in 0..2 {
+                    scheduler.schedule(Box::new(TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: Spawn {
+                            depth: self.depth - 1,
+                            counter: self.counter.clone(),
+                        },
+                    }))
+                }
+            }
+        }
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut runtime = Toexec::new();
+        let root = runtime.build_scope(|b| {
+            b.node(TaskNode {
+                inputs: (),
+                outputs: (),
+                task: Spawn {
+                    depth: 6,
+                    counter: counter.clone(),
+                },
+            })
+            .add_activator()
+        });
+        root.activate_once(&mut runtime);
+
+        runtime.execute(4).unwrap();
+
+        assert_eq!(counter.load(SeqCst), 127);
+    }
+
+    #[test]
+    fn expose_output() {
+        use sequential::single_use::*;
+
+        let mut runtime = Toexec::new();
+
+        let root = runtime.build_scope(|b| {
+            // The value stays in the port, which is read through the named output.
+            let (sum_sender, sum_receiver) = b.port(None).split();
+            b.expose_output("sum", sum_receiver);
+            let sink_activator = b
+                .node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                })
+                .add_activator();
+
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (sum_sender.with_activator(sink_activator),),
+                    task: StrictTask::new(|x: Option<(i32, i32)>| (x.map(|(x, y)| x + y),)),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        root.send_activate_once(&mut runtime, Some((3, 4)));
+        runtime.execute(1);
+
+        match runtime.output::<Option<u32>>("sum") {
+            Err(OutputError::TypeMismatch { ref name, .. }) if name == "sum" => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert_eq!(
+            runtime.output::<Option<i32>>("product"),
+            Err(OutputError::Unknown("product".to_string()))
+        );
+        assert_eq!(runtime.output::<Option<i32>>("sum"), Ok(Some(7)));
+    }
+
+    #[test]
+    fn execute_until() {
+        use parallel::single_use::*;
+
+        let mut runtime = Toexec::new();
+
+        let (root, result) = runtime.build_scope(|b| {
+            let (result_sender, result_receiver) = b.port(None).split();
+            let result_activator = b
+                .node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                })
+                .add_activator();
+
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (result_sender.with_activator(result_activator),),
+                    task: StrictTask::new(|x: Option<i32>| (x.map(|x| x * 2),)),
+                })
+                .add_activator();
+            (sender.with_activator(activator), result_receiver)
+        });
+        root.send_activate_once(&mut runtime, Some(21));
+
+        assert_eq!(runtime.execute_until(4, result), Ok(42));
+    }
+
+    #[test]
+    fn ordered_map() {
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let sink = results.clone();
+        let map = OrderedMap::new(
+            3,
+            |x: u64| {
+                // Later items finish first.
+                thread::sleep(Duration::from_millis(10 - x));
+                x * x
+            },
+            move |_: &mut RuntimeLoc, y| sink.lock().unwrap().push(y),
+        );
+
+        let mut runtime = Toexec::new();
+        for x in 0..10 {
+            map.push(&mut runtime, x);
+        }
+        runtime.execute(4).unwrap();
+
+        assert_eq!(
+            *results.lock().unwrap(),
+            (0..10).map(|x| x * x).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn thread_pool() {
+        use parallel::config::RuntimeConfig;
+        use parallel::multiple_uses::*;
+        use parallel::pool::{Queues, ThreadPool};
+        use std::sync::{Arc, Mutex};
+
+        let total = Arc::new(Mutex::new(0));
+        let pool = ThreadPool::new(3);
+        let mut runtime = Toexec::new();
+
+        let sink = total.clone();
+        let root = runtime.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        *sink.lock().unwrap() += x.unwrap()
+                    }),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+
+        // The same threads are used for each tick.
+        for tick in 1..=3 {
+            root.send_activate(&mut runtime, Some(tick));
+            runtime.execute_on(&pool).unwrap();
+        }
+
+        assert_eq!(*total.lock().unwrap(), 6);
+
+        // The queues are kept by the pool across executions: a stealer taken during the first one
+        // steals from the queue used by the second one.
+        let config = RuntimeConfig::new();
+        let kept = Mutex::new(None);
+        pool.run_workers(
+            &config,
+            |queues: &mut Queues<u32>| {
+                *kept.lock().unwrap() = Some(queues.stealers[0].clone());
+                queues.workers.drain(..).enumerate().collect()
+            },
+            |worker| worker,
+        );
+        let stealer = kept.into_inner().unwrap().unwrap();
+        pool.run_workers(
+            &config,
+            |queues: &mut Queues<u32>| queues.workers.drain(..).enumerate().collect(),
+            move |(index, worker)| {
+                if index == 0 {
+                    worker.push(7);
+                    assert_eq!(stealer.steal(), Some(7));
+                }
+                (index, worker)
+            },
+        );
+    }
+
+    #[test]
+    fn async_adapter() {
+        use parallel::async_adapter::*;
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::sync::{Arc, Mutex};
+        use std::task::{Context, Poll, Wake, Waker};
+        use std::thread;
+        use std::time::Duration;
+
+        // A future completed by another thread after a delay.
+        type DelayedState = (Option<Option<i32>>, Option<Waker>);
+
+        struct Delayed {
+            value: Arc<Mutex<DelayedState>>,
+        }
+
+        impl Delayed {
+            fn new(value: Option<i32>) -> Self {
+                let shared = Arc::new(Mutex::new((None, None::<Waker>)));
+                let state = shared.clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    let mut state = state.lock().unwrap();
+                    state.0 = Some(value);
+                    if let Some(waker) = state.1.take() {
+                        waker.wake()
+                    }
+                });
+                Delayed { value: shared }
+            }
+        }
+
+        impl Future for Delayed {
+            type Output = Option<i32>;
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<i32>> {
+                let mut state = self.value.lock().unwrap();
+                match state.0.take() {
+                    Some(value) => Poll::Ready(value),
+                    None => {
+                        state.1 = Some(cx.waker().clone());
+                        Poll::Pending
+                    }
+                }
+            }
+        }
+
+        // A minimal executor blocking the current thread.
+        struct Unpark(thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark()
+            }
+        }
+
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let mut future = Box::pin(future);
+            let waker = Waker::from(Arc::new(Unpark(thread::current())));
+            loop {
+                match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                    Poll::Ready(output) => return output,
+                    Poll::Pending => thread::park(),
+                }
+            }
+        }
+
+        let result = Arc::new(Mutex::new(None));
+        let sink = result.clone();
+        let mut runtime = AsyncToexec::new();
+
+        let root = runtime.build_scope(|b| {
+            let (sink_sender, sink_receiver) = b.port(None).split();
+            let sink_activator = b
+                .node(TaskNode {
+                    inputs: (sink_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| *sink.lock().unwrap() = x),
+                })
+                .add_activator();
+
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (sink_sender.with_activator(sink_activator),),
+                    task: AsyncTask::new(|x: Option<i32>| Delayed::new(x.map(|x| x * 2 + 1))),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        root.send_activate_once(&mut *runtime, Some(20));
+
+        let (_runtime, execution) = block_on(runtime.execute(2));
+        assert!(execution.is_ok());
+
+        assert_eq!(*result.lock().unwrap(), Some(41));
+    }
+
+    #[test]
+    fn buffered_port() {
+        use sequential::single_use::*;
+
+        let mut runtime = Toexec::new();
+
+        let (sender, receiver) = runtime.build_scope(|b| b.port_buffered(4).split());
+        for x in 1..4 {
+            sender.send(Some(x));
+        }
+
+        assert_eq!(receiver.recv(), Some(1));
+        sender.send(Some(4));
+        assert_eq!(receiver.recv(), Some(2));
+        assert_eq!(receiver.recv(), Some(3));
+        assert_eq!(receiver.recv(), Some(4));
+        assert_eq!(receiver.recv(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Buffered port is full")]
+    fn buffered_port_full() {
+        use parallel::port::ChannelPort;
+
+        let port = ChannelPort::new(1);
+        port.send(1);
+        port.send(2);
+    }
+
+    #[test]
+    fn checked_port() {
+        use sequential::single_use::*;
+
+        let mut runtime = Toexec::new();
+        let result = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let sink = result.clone();
+
+        let root = runtime.build_scope(|b| {
+            let (sender, receiver) = b.port_checked("input").split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| *sink.lock().unwrap() = x),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        root.send_activate_once(&mut runtime, Some(3));
+        runtime.execute(1);
+
+        assert_eq!(*result.lock().unwrap(), Some(3));
+    }
+
+    #[test]
+    #[cfg(feature = "checked-ports")]
+    #[should_panic(expected = "Port `input` was written to while full.")]
+    fn checked_port_double_write() {
+        use common::port::CheckedPort;
+        use std::sync::Mutex;
+
+        let port = CheckedPort::new("input", Mutex::new(0));
+        port.send(1);
+        port.send(2);
+    }
+
+    #[test]
+    #[cfg(feature = "checked-ports")]
+    #[should_panic(expected = "Port `input` was read from while empty.")]
+    fn checked_port_empty_read() {
+        use common::port::CheckedPort;
+        use std::sync::Mutex;
+
+        let port = CheckedPort::new("input", Mutex::new(0));
+        port.send(1);
+        assert_eq!(port.recv(), 1);
+        port.recv();
+    }
+
+    #[test]
+    fn self_check() {
+        use parallel::single_use::*;
+
+        let report = Toexec::self_check(4);
+
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(report.runs, 16);
+    }
+
+    #[test]
+    fn named_nodes() {
+        use parallel::multiple_uses::*;
+
+        let inspector = Inspector::new();
+        let mut runtime = Toexec::new();
+
+        let activator = runtime.build_scope(|b| {
+            b.inspect(&inspector);
+
+            let (_sender, receiver) = b.port_named("input", None).split();
+            b.node_named(
+                "sink",
+                TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(|_: Option<i32>| ()),
+                },
+            )
+            .add_activator()
+        });
+
+        assert_eq!(activator.label(), Some("sink".into()));
+        assert!(format!("{:?}", activator).contains("sink"));
+        assert_eq!(inspector.summary(), "#0: sink: <undocumented>\nports: input\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "Node `placeholder` was activated while not armed.")]
+    fn named_uninitialized_activator() {
+        use parallel::multiple_uses::*;
+
+        let mut runtime = Toexec::new();
+        RcActivator::uninitialized("placeholder").activate_once(&mut runtime);
+    }
+
+    #[test]
+    fn inspector_dot() {
+        use sequential::single_use::*;
+
+        let inspector = Inspector::new();
+        let mut runtime = Toexec::new();
+
+        let root = runtime.build_scope(|b| {
+            b.inspect(&inspector);
+
+            let (sink_sender, sink_receiver) = b.port_named("result", None).split();
+            let mut sink = b.node_named(
+                "sink",
+                TaskNode {
+                    inputs: (sink_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(|_: Option<i32>| ()),
+                },
+            );
+            let sink_id = sink.id().unwrap();
+            let sink_activator = sink.add_activator();
+            drop(sink);
+
+            b.node_named(
+                "double",
+                TaskNode {
+                    inputs: (),
+                    outputs: (sink_sender.with_activator(sink_activator),),
+                    task: StrictTask::new(|| (Some(2),)),
+                },
+            )
+            .activates(sink_id)
+            .add_activator()
+        });
+        root.activate_once(&mut runtime);
+        runtime.execute(1);
+
+        assert_eq!(inspector.activators(NodeId(0)), 1);
+        assert_eq!(inspector.edges(), vec![(NodeId(1), NodeId(0))]);
+        assert_eq!(
+            inspector.to_dot(),
+            "digraph {\n\
+             \x20   n0 [label=\"#0 sink: <undocumented>\"];\n\
+             \x20   n1 [label=\"#1 double: <undocumented>\"];\n\
+             \x20   p0 [shape=box, label=\"result\"];\n\
+             \x20   n1 -> n0;\n\
+             \x20   u1_0 [shape=point];\n\
+             \x20   u1_0 -> n1 [style=dashed];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn trace_hook() {
+        use parallel::single_use::*;
+        use parallel::trace::TraceHook;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        #[derive(Default)]
+        struct Counts {
+            scheduled: AtomicUsize,
+            started: AtomicUsize,
+            ended: AtomicUsize,
+        }
+
+        struct Counter(Arc<Counts>);
+
+        struct Fan(Vec<RcActivator<'static>>);
+
+        impl NodeOnce<RuntimeLoc<'static>> for Fan {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+                for sink in self.0 {
+                    sink.activate_once(scheduler)
+                }
+            }
+        }
+
+        impl TraceHook for Counter {
+            fn on_schedule(&self, _worker: Option<usize>) {
+                self.0.scheduled.fetch_add(1, SeqCst);
+            }
+
+            fn on_execute_start(&self, _worker: usize) {
+                self.0.started.fetch_add(1, SeqCst);
+            }
+
+            fn on_execute_end(&self, _worker: usize) {
+                self.0.ended.fetch_add(1, SeqCst);
+            }
+        }
+
+        let counts = Arc::new(Counts::default());
+        let mut runtime = Toexec::new();
+        runtime.set_trace_hook(Counter(counts.clone()));
+
+        let root = runtime.build_scope(|b| {
+            let sinks = (0..8)
+                .map(|_| {
+                    b.node(TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| ()),
+                    })
+                    .add_activator()
+                })
+                .collect::<Vec<_>>();
+            b.node(Fan(sinks)).add_activator()
+        });
+        root.activate_once(&mut runtime);
+        runtime.execute(4).unwrap();
+
+        assert_eq!(counts.scheduled.load(SeqCst), 9);
+        assert_eq!(counts.started.load(SeqCst), 9);
+        assert_eq!(counts.ended.load(SeqCst), 9);
+    }
+
+    #[test]
+    fn help_until() {
+        use parallel::single_use::*;
+        use parallel::termination::HelpError;
+        use std::sync::{Arc, Mutex};
+
+        type Slot = Arc<Mutex<Option<i32>>>;
+
+        // Waits for a value which is written by a node scheduled after it.
+        struct Wait {
+            input: Slot,
+            result: Arc<Mutex<Option<Result<i32, HelpError>>>>,
+        }
+
+        impl NodeOnce<RuntimeLoc<'static>> for Wait {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+                let input = self.input;
+                let outcome = scheduler
+                    .help_until(|| input.lock().unwrap().is_some())
+                    .map(|()| input.lock().unwrap().unwrap());
+                *self.result.lock().unwrap() = Some(outcome);
+            }
+        }
+
+        struct Write(Option<Slot>);
+
+        impl NodeOnce<RuntimeLoc<'static>> for Write {
+            fn execute_once(self, _scheduler: &mut RuntimeLoc<'static>) {
+                if let Some(output) = self.0 {
+                    *output.lock().unwrap() = Some(7);
+                }
+            }
+        }
+
+        for &writes in &[true, false] {
+            let slot = Arc::new(Mutex::new(None));
+            let result = Arc::new(Mutex::new(None));
+            let mut runtime = Toexec::new();
+
+            let (wait, write) = runtime.build_scope(|b| {
+                let wait = b
+                    .node(Wait {
+                        input: slot.clone(),
+                        result: result.clone(),
+                    })
+                    .add_activator();
+                let write = b
+                    .node(Write(if writes { Some(slot.clone()) } else { None }))
+                    .add_activator();
+                (wait, write)
+            });
+            wait.activate_once(&mut runtime);
+            write.activate_once(&mut runtime);
+            runtime.execute(1).unwrap();
+
+            let expected = if writes { Ok(7) } else { Err(HelpError::Deadlock) };
+            assert_eq!(*result.lock().unwrap(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn stalled_nodes() {
+        use parallel::failure::ExecutionError;
+        use parallel::single_use::*;
+        use parallel::validation::StalledNode;
+
+        let mut runtime = Toexec::with_validation();
+
+        let (first, forgotten) = runtime.build_scope(|b| {
+            let mut join = b.node_named(
+                "join",
+                TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                },
+            );
+            (join.add_activator(), join.add_activator())
+        });
+        first.activate_once(&mut runtime);
+
+        let error = match runtime.execute(2) {
+            Err(ExecutionError::Stalled(error)) => error,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(
+            error.nodes,
+            vec![StalledNode {
+                label: Some("join".into()),
+                pending: 1,
+            }]
+        );
+        assert_eq!(
+            error.to_string(),
+            "1 nodes are waiting for activations: `join` (1 pending)"
+        );
+
+        let root = runtime.build_scope(|b| {
+            b.node(TaskNode {
+                inputs: (),
+                outputs: (),
+                task: StrictTask::new(|| ()),
+            })
+            .add_activator()
+        });
+        root.activate_once(&mut runtime);
+        drop(forgotten);
+
+        assert!(runtime.execute(2).is_ok());
+    }
+
+    #[test]
+    fn stalled_nodes_without_validation() {
+        use parallel::multiple_uses::*;
+
+        // The leak detection of debug builds does not enable validation.
+        let mut runtime = Toexec::new();
+        let (first, _second) = runtime.build_scope(|b| {
+            let mut join = b.node(TaskNode {
+                inputs: (),
+                outputs: (),
+                task: StrictTask::new(|| ()),
+            });
+            (join.add_activator(), join.add_activator())
+        });
+        first.activate(&mut runtime);
+        assert!(runtime.execute(1).is_ok());
+    }
+
+    #[test]
+    fn execution_errors() {
+        use parallel::failure::ExecutionError;
+        use parallel::memory::Accountant;
+        use parallel::single_use::*;
+
+        let accountant = Accountant::new().with_hard_limit(1 << 20);
+        let mut runtime = Toexec::with_accountant(accountant);
+        runtime.set_validation(true);
+
+        let (bad, join) = runtime.build_scope(|b| {
+            let bad = b
+                .node_named(
+                    "bad",
+                    TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| panic!("boom")),
+                    },
+                )
+                .add_activator();
+            let mut join = b.node_named(
+                "join",
+                TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                },
+            );
+            (bad, (join.add_activator(), join.add_activator()))
+        });
+        bad.activate_once(&mut runtime);
+        join.0.activate_once(&mut runtime);
+
+        // Panics are reported first, and stalled nodes once the graph has quiesced.
+        let error = runtime.execute(2).unwrap_err();
+        assert_eq!(error.failures().len(), 1);
+        assert_eq!(error.failures()[0].label.as_deref(), Some("bad"));
+        match runtime.execute(2) {
+            Err(ExecutionError::Stalled(ref error)) if error.nodes.len() == 1 => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // All the checks apply to the executions on a thread pool as well.
+        let accountant = runtime.accountant().unwrap().clone();
+        let reservation = Accountant::charge(&accountant, 1 << 20);
+        let pool = ::parallel::pool::ThreadPool::new(2);
+        match runtime.execute_on(&pool) {
+            Err(ExecutionError::MemoryLimit(error)) => assert_eq!(error.limit, 1 << 20),
+            result => panic!("unexpected result {:?}", result),
+        }
+        drop(reservation);
+        join.1.activate_once(&mut runtime);
+        assert!(runtime.execute_on(&pool).is_ok());
+    }
+
+    #[test]
+    fn namespaces() {
+        use sequential::single_use::*;
+
+        let inspector = Inspector::new();
+        let mut runtime = Toexec::new();
+
+        runtime.build_scope(|b| {
+            b.inspect(&inspector);
+
+            b.namespace("motor", |b| {
+                b.namespace("controller", |b| {
+                    let _port = b.port_named("setpoint", None::<i32>);
+                    b.node_named(
+                        "pid",
+                        TaskNode {
+                            inputs: (),
+                            outputs: (),
+                            task: StrictTask::new(|| ()),
+                        },
+                    )
+                    .never_start();
+                    b.node(TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| ()),
+                    })
+                    .never_start();
+                });
+            });
+            b.node_named(
+                "motorbike",
+                TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                },
+            )
+            .never_start();
+        });
+
+        assert_eq!(
+            inspector.metadata(NodeId(0)).label,
+            Some("motor/controller/pid".to_string())
+        );
+        assert_eq!(inspector.ports(), vec!["motor/controller/setpoint"]);
+        assert_eq!(inspector.subtree("motor"), vec![NodeId(0), NodeId(1)]);
+        assert_eq!(inspector.subtree("motor/controller/pid"), vec![NodeId(0)]);
+        assert_eq!(inspector.subtree("motorbike"), vec![NodeId(2)]);
+    }
+
+    #[test]
+    fn leak_registry() {
+        use api::builder::Label;
+        use parallel::validation::{Registry, StalledNode, Tracked};
+        use std::sync::Arc;
+
+        struct Leaky(Label);
+
+        impl Tracked for Leaky {
+            fn stalled(&self) -> Option<StalledNode> {
+                None
+            }
+
+            fn label(&self) -> Option<Label> {
+                Some(self.0.clone())
+            }
+        }
+
+        let registry = Registry::default();
+        let leaked: Arc<dyn Tracked> = Arc::new(Leaky("cycle".into()));
+        for _ in 0..100 {
+            let dropped: Arc<dyn Tracked> = Arc::new(Leaky("dropped".into()));
+            registry.track(Arc::downgrade(&dropped));
+        }
+        registry.track(Arc::downgrade(&leaked));
+
+        assert_eq!(registry.alive(), vec![Some("cycle".into())]);
+        drop(leaked);
+        assert!(registry.alive().is_empty());
+    }
+
+    #[test]
+    fn leak_detector() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let mut runtime = Toexec::with_validation();
+        let detector = runtime.leak_detector().unwrap();
+        let slot = Arc::new(Mutex::new(None));
+        let held = runtime.build_scope(|b| {
+            // A node holding its own activator, which is never freed.
+            let own = slot.clone();
+            let cycle = b
+                .node_named(
+                    "cycle",
+                    TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(move || drop(own.lock().unwrap())),
+                    },
+                )
+                .add_activator();
+            *slot.lock().unwrap() = Some(cycle);
+            b.node_named(
+                "held",
+                TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                },
+            )
+            .add_activator()
+        });
+        held.activate(&mut runtime);
+        runtime.execute(1).unwrap();
+        drop(runtime);
+
+        // Nodes are reported until the activators held outside of the graph are dropped.
+        let error = detector.check().unwrap_err();
+        assert_eq!(error.nodes, vec![Some("cycle".into()), Some("held".into())]);
+        drop(held);
+        let error = detector.check().unwrap_err();
+        assert_eq!(error.nodes, vec![Some("cycle".into())]);
+        assert_eq!(
+            error.to_string(),
+            "1 reusable nodes are still alive after dropping the runtime, and may be leaked: cycle"
+        );
+
+        // Breaking the cycle frees the node.
+        slot.lock().unwrap().take();
+        assert!(detector.check().is_ok());
+    }
+
+    #[test]
+    fn timer_source() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+        use wasm::single_use::*;
+
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let edges: Vec<_> = (0..5)
+            .map(|_| {
+                let ticks = ticks.clone();
+                runtime.build_scope(|b| {
+                    let (sender, receiver) = b.port(None).split();
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |tick: Option<Tick>| {
+                                ticks.lock().unwrap().push(tick.unwrap())
+                            }),
+                        })
+                        .add_activator();
+                    sender.with_activator(activator)
+                })
+            })
+            .collect();
+
+        let period = Duration::from_millis(2);
+        let mut edges = edges.into_iter();
+        runtime.add_timer(TimerSource::new(period, move |_| edges.next()).limit(3));
+
+        for _ in 0..500 {
+            runtime.execute();
+            if ticks.lock().unwrap().len() == 3 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        // The limit stops the timer before it runs out of edges.
+        thread::sleep(Duration::from_millis(10));
+        runtime.execute();
+        runtime.stop_timers();
+
+        let ticks = ticks.lock().unwrap();
+        assert_eq!(
+            ticks.iter().map(|tick| tick.index).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        for (i, tick) in ticks.iter().enumerate() {
+            assert!(tick.elapsed >= period * (i as u32 + 1));
+        }
+    }
+
+    #[test]
+    fn parallel_timers() {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let period = Duration::from_millis(2);
+
+        {
+            use parallel::single_use::*;
+
+            let ticks = Arc::new(Mutex::new(Vec::new()));
+            let mut runtime = Toexec::new();
+            let edges: Vec<_> = (0..5)
+                .map(|_| {
+                    let ticks = ticks.clone();
+                    runtime.build_scope(|b| {
+                        let (sender, receiver) = b.port(None).split();
+                        let activator = b
+                            .node(TaskNode {
+                                inputs: (receiver.as_data_input(),),
+                                outputs: (),
+                                task: StrictTask::new(move |tick: Option<Tick>| {
+                                    ticks.lock().unwrap().push(tick.unwrap().index)
+                                }),
+                            })
+                            .add_activator();
+                        sender.with_activator(activator)
+                    })
+                })
+                .collect();
+
+            let mut edges = edges.into_iter();
+            runtime.add_timer(TimerSource::new(period, move |_| edges.next()).limit(3));
+
+            // The execution waits for the timer to stop.
+            runtime.execute(2).unwrap();
+            let mut ticks = ticks.lock().unwrap().clone();
+            ticks.sort();
+            assert_eq!(ticks, vec![0, 1, 2]);
+        }
+
+        {
+            use parallel::multiple_uses::*;
+
+            let ticks = Arc::new(Mutex::new(Vec::new()));
+            let mut runtime = Toexec::new();
+            let sink = ticks.clone();
+            let edge = runtime.build_scope(|b| {
+                let (sender, receiver) = b.port(None).split();
+                let activator = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (),
+                        task: StrictTask::new(move |tick: Option<Tick>| {
+                            sink.lock().unwrap().push(tick.unwrap().index)
+                        }),
+                    })
+                    .add_activator();
+                // The timer sends each tick on a new handle to the same edge.
+                SharedEdge::new(sender.with_activator(activator))
+            });
+
+            let late = edge.clone();
+            runtime.add_timer(TimerSource::new(period, move |_| Some(edge.clone())).limit(4));
+
+            // A single worker executes the node before the following tick.
+            runtime.execute(1).unwrap();
+            assert_eq!(*ticks.lock().unwrap(), vec![0, 1, 2, 3]);
+
+            // Dropping the runtime stops the timers which are still running.
+            let hour = Duration::from_secs(3600);
+            runtime.add_timer(TimerSource::new(hour, move |_| Some(late.clone())));
+            drop(runtime);
+            assert_eq!(ticks.lock().unwrap().len(), 4);
+        }
+
+        {
+            use parallel::multiple_uses::*;
+
+            // A timer without a limit, stopped by the node receiving its ticks.
+            let ticks = Arc::new(Mutex::new(Vec::new()));
+            let handle: Arc<Mutex<Option<TimerHandle>>> = Arc::new(Mutex::new(None));
+            let mut runtime = Toexec::new();
+            let (sink, stopper) = (ticks.clone(), handle.clone());
+            let edge = runtime.build_scope(|b| {
+                let (sender, receiver) = b.port(None).split();
+                let activator = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (),
+                        task: StrictTask::new(move |tick: Option<Tick>| {
+                            let index = tick.unwrap().index;
+                            sink.lock().unwrap().push(index);
+                            if index == 2 {
+                                stopper.lock().unwrap().as_ref().unwrap().stop();
+                            }
+                        }),
+                    })
+                    .add_activator();
+                SharedEdge::new(sender.with_activator(activator))
+            });
+
+            let timer = runtime.add_timer(TimerSource::new(period, move |_| Some(edge.clone())));
+            *handle.lock().unwrap() = Some(timer.clone());
+
+            // The execution returns once the node has stopped the timer.
+            runtime.execute(1).unwrap();
+            assert!(timer.is_stopped());
+            let ticks = ticks.lock().unwrap();
+            assert_eq!(ticks[..3], [0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn par_map() {
+        use parallel::single_use::*;
+        use patterns::par_map::par_map_graph;
+
+        let data: Vec<u64> = (0..100).collect();
+        let mut runtime = Toexec::new();
+
+        let squares = par_map_graph(&mut runtime, 4, &data, |x| x * x).unwrap();
+        assert_eq!(squares, data.iter().map(|x| x * x).collect::<Vec<_>>());
+
+        let empty: Vec<String> =
+            par_map_graph(&mut runtime, 4, &data[..0], |x| x.to_string()).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn breakpoints() {
+        use parallel::breakpoint::{BreakEvent, BreakMode};
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        struct Forward(RcActivator<'static>);
+
+        impl NodeOnce<RuntimeLoc<'static>> for Forward {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+                self.0.activate_once(scheduler)
+            }
+        }
+
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let recorded = hits.clone();
+        runtime.set_breakpoint("join", BreakMode::Pause, move |context| {
+            assert_eq!(context.activators, 2);
+            recorded
+                .lock()
+                .unwrap()
+                .push((context.event, context.received()))
+        });
+
+        let roots: Vec<_> = runtime.build_scope(|b| {
+            let mut join = b.node_named(
+                "join",
+                TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                },
+            );
+            let inputs = (join.add_activator(), join.add_activator());
+            vec![
+                b.node(Forward(inputs.0)).add_activator(),
+                b.node(Forward(inputs.1)).add_activator(),
+            ]
+        });
+        for root in roots {
+            root.activate_once(&mut runtime);
+        }
+        runtime.execute(2).unwrap();
+
+        let mut hits = hits.lock().unwrap().clone();
+        hits.sort_by_key(|&(event, received)| (event == BreakEvent::Executed, received));
+        assert_eq!(
+            hits,
+            vec![
+                (BreakEvent::Activated, 1),
+                (BreakEvent::Activated, 2),
+                (BreakEvent::Executed, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn injector_handle() {
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let inputs: Vec<_> = (0..3)
+            .map(|_| {
+                let results = results.clone();
+                runtime.build_scope(|b| {
+                    let (sender, receiver) = b.port(None).split();
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |x: Option<i32>| {
+                                results.lock().unwrap().push(x.unwrap())
+                            }),
+                        })
+                        .add_activator();
+                    sender.with_activator(activator)
+                })
+            })
+            .collect();
+
+        let injector = runtime.injector_handle();
+        let io = thread::spawn(move || {
+            for (i, input) in inputs.into_iter().enumerate() {
+                thread::sleep(Duration::from_millis(5));
+                injector.clone().inject_send(input, Some(i as i32));
+            }
+        });
+
+        // The execution only returns once the handle was dropped by the I/O thread.
+        runtime.execute(2).unwrap();
+        io.join().unwrap();
+        assert_eq!(*results.lock().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn ack_edges() {
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let (output, counter) = runtime.build_scope(|b| {
+            let ack_log = log.clone();
+            let ack = b
+                .node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(move || ack_log.lock().unwrap().push("ack".to_string())),
+                })
+                .add_activator();
+
+            let (sender, receiver) = b.port(None).split();
+            let input = receiver.with_ack(ack);
+            let counter = input.counter();
+            let consumer_log = log.clone();
+            let consumer = b
+                .node(TaskNode {
+                    inputs: (input,),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        consumer_log
+                            .lock()
+                            .unwrap()
+                            .push(format!("consume {}", x.unwrap()))
+                    }),
+                })
+                .add_activator();
+
+            (counter.output(sender.with_activator(consumer)), counter)
+        });
+
+        assert!(output.is_acked());
+        output.send_activate_once(&mut runtime, Some(3));
+        assert_eq!(counter.outstanding(), 1);
+
+        runtime.execute(2).unwrap();
+        assert_eq!(counter.outstanding(), 0);
+        assert_eq!(*log.lock().unwrap(), vec!["consume 3", "ack"]);
+    }
+
+    #[test]
+    fn merge_activator() {
+        use parallel::activator::MergeActivator;
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let (first, second) = runtime.build_scope(|b| {
+            let merge = MergeActivator::new();
+            let recorded = fired.clone();
+            let mut node = b.node(TaskNode {
+                inputs: (merge.fired().as_data_input(),),
+                outputs: (),
+                task: StrictTask::new(move |index: Option<usize>| {
+                    recorded.lock().unwrap().push(index)
+                }),
+            });
+            (node.add_any_activator(&merge), node.add_any_activator(&merge))
+        });
+        assert_eq!((first.index(), second.index()), (0, 1));
+
+        // Only the first input to fire schedules the node.
+        second.activate_once(&mut runtime);
+        first.activate_once(&mut runtime);
+        runtime.execute(2).unwrap();
+
+        assert_eq!(*fired.lock().unwrap(), vec![Some(1)]);
+    }
+
+    #[test]
+    fn filter_output() {
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let sink = |b: &mut ScopedGraphBuilder<Toexec<'static>>, name: &'static str| {
+            let log = log.clone();
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        log.lock().unwrap().push((name, x.unwrap()))
+                    }),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        };
+        let is_even = |x: &Option<i32>| x.is_some_and(|x| x % 2 == 0);
+
+        let (routed, dropped) = runtime.build_scope(|b| {
+            let routed = FilterOutput::new(sink(b, "even"), is_even).otherwise(sink(b, "odd"));
+            let dropped = FilterOutput::new(sink(b, "unreachable"), is_even);
+            (routed, dropped)
+        });
+        routed.send_activate_once(&mut runtime, Some(3));
+        dropped.send_activate_once(&mut runtime, Some(5));
+        runtime.execute(2).unwrap();
+
+        assert_eq!(*log.lock().unwrap(), vec![("odd", 3)]);
+    }
+
+    #[test]
+    fn custom_scheduler() {
+        use custom::single_use::*;
+        use std::sync::mpsc;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        // A minimal "in-house" pool: a single thread running the jobs sent on a channel.
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let pool = thread::spawn(move || {
+            for job in queue {
+                job()
+            }
+        });
+        let jobs = Mutex::new(jobs);
+        let mut scheduler =
+            CustomScheduler::new(move |job| jobs.lock().unwrap().send(job).unwrap());
+
+        let result = Arc::new(Mutex::new(None));
+        let root = scheduler.build_scope(|b| {
+            let sink_result = result.clone();
+            let (x_sender, x_receiver) = b.port(None).split();
+            let (y_sender, y_receiver) = b.port(None).split();
+            let mut sink = b.node(TaskNode {
+                inputs: (x_receiver.as_data_input(), y_receiver.as_data_input()),
+                outputs: (),
+                task: StrictTask::new(move |x: Option<i32>, y: Option<i32>| {
+                    *sink_result.lock().unwrap() = Some(x.unwrap() + y.unwrap())
+                }),
+            });
+            let outputs = (
+                x_sender.with_activator(sink.add_activator()),
+                y_sender.with_activator(sink.add_activator()),
+            );
+
+            b.node(TaskNode {
+                inputs: (),
+                outputs,
+                task: StrictTask::new(|| (Some(1), Some(2))),
+            })
+            .add_activator()
+        });
+        root.activate_once(&mut scheduler);
+        scheduler.wait();
+        assert!(scheduler.is_idle());
+        assert_eq!(*result.lock().unwrap(), Some(3));
+
+        // Dropping the scheduler closes the channel, stopping the pool.
+        drop(scheduler);
+        pool.join().unwrap();
+    }
+
+    #[test]
+    fn pause_step_resume() {
+        use std::sync::{Arc, Mutex};
+        use wasm::single_use::*;
+
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let edges: Vec<_> = (0..4)
+            .map(|_| {
+                let values = values.clone();
+                runtime.build_scope(|b| {
+                    let (sender, receiver) = b.port(None).split();
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |x: Option<usize>| {
+                                values.lock().unwrap().push(x.unwrap())
+                            }),
+                        })
+                        .add_activator();
+                    sender.with_activator(activator)
+                })
+            })
+            .collect();
+
+        // External events queue up while paused.
+        runtime.pause();
+        for (i, edge) in edges.into_iter().enumerate() {
+            runtime.injector().inject_send(edge, Some(i));
+        }
+        runtime.execute();
+        assert!(values.lock().unwrap().is_empty());
+        assert!(!runtime.is_idle());
+
+        // Each step runs exactly one instant, including its microtasks.
+        runtime.step();
+        assert!(!runtime.is_paused());
+        runtime.execute();
+        assert!(runtime.is_paused());
+        assert_eq!(*values.lock().unwrap(), vec![0]);
+
+        runtime.step();
+        assert!(!runtime.execute_step(usize::MAX));
+        assert_eq!(*values.lock().unwrap(), vec![0, 1]);
+
+        runtime.resume();
+        runtime.execute();
+        assert!(runtime.is_idle());
+        assert_eq!(*values.lock().unwrap(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn pause_executions() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        /// Count the executions of the node.
+        struct Count(Arc<AtomicUsize>);
+
+        impl<S> NodeMut<S> for Count {
+            fn execute_mut(&mut self, _: &mut S) {
+                self.0.fetch_add(1, SeqCst);
+            }
+        }
+
+        impl<S> NodeOnce<S> for Count {
+            fn execute_once(mut self, scheduler: &mut S) {
+                self.execute_mut(scheduler)
+            }
+        }
+
+        /// Check that executions only run when allowed, activating a new node with `activate`.
+        fn check<Spec: ExecuteSpec + PauseSpec>(
+            mut runtime: Spec,
+            count: &AtomicUsize,
+            activate: impl Fn(&mut Spec),
+        ) {
+            runtime.pause();
+            activate(&mut runtime);
+            runtime.execute(2);
+            assert_eq!(count.load(SeqCst), 0);
+
+            // The node scheduled while paused is executed by the step.
+            runtime.step();
+            assert!(!runtime.is_paused());
+            runtime.execute(2);
+            assert!(runtime.is_paused());
+            assert_eq!(count.load(SeqCst), 1);
+
+            activate(&mut runtime);
+            runtime.execute(2);
+            assert_eq!(count.load(SeqCst), 1);
+            runtime.resume();
+            runtime.execute(2);
+            assert!(!runtime.is_paused());
+            assert_eq!(count.load(SeqCst), 2);
+        }
+
+        {
+            use sequential::single_use::*;
+
+            let count = Arc::new(AtomicUsize::new(0));
+            check(Toexec::new(), &count, |runtime| {
+                let activator =
+                    runtime.build_scope(|b| b.node(Count(count.clone())).add_activator());
+                activator.activate_once(runtime)
+            });
+        }
+        {
+            use sequential::multiple_uses::*;
+
+            let count = Arc::new(AtomicUsize::new(0));
+            check(Toexec::new(), &count, |runtime| {
+                let activator =
+                    runtime.build_scope(|b| b.node(Count(count.clone())).add_activator());
+                activator.activate(runtime)
+            });
+        }
+        {
+            use parallel::single_use::*;
+
+            let count = Arc::new(AtomicUsize::new(0));
+            check(Toexec::new(), &count, |runtime| {
+                let activator =
+                    runtime.build_scope(|b| b.node(Count(count.clone())).add_activator());
+                activator.activate_once(runtime)
+            });
+        }
+        {
+            use parallel::multiple_uses::*;
+
+            let count = Arc::new(AtomicUsize::new(0));
+            check(Toexec::new(), &count, |runtime| {
+                let activator =
+                    runtime.build_scope(|b| b.node(Count(count.clone())).add_activator());
+                activator.activate(runtime)
+            });
+        }
+    }
+
+    #[test]
+    fn route_output() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Shape {
+            Circle,
+            Square,
+            Triangle,
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let router = runtime.build_scope(|b| {
+            let mut router = RouteOutput::new();
+            for &shape in &[Shape::Circle, Shape::Square] {
+                let log = log.clone();
+                let (sender, receiver) = b.port(None).split();
+                let activator = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (),
+                        task: StrictTask::new(move |x: Option<u32>| {
+                            log.lock().unwrap().push((shape, x.unwrap()))
+                        }),
+                    })
+                    .add_activator();
+                router.connect(shape, sender.with_activator(activator));
+            }
+            router
+        });
+        assert!(router.is_connected(&Shape::Square));
+        assert!(!router.is_connected(&Shape::Triangle));
+
+        router.send_activate(&mut runtime, (Shape::Square, Some(4)));
+        runtime.execute(2).unwrap();
+        router.send_activate(&mut runtime, (Shape::Triangle, Some(3)));
+        runtime.execute(2).unwrap();
+        router.send_activate(&mut runtime, (Shape::Circle, Some(0)));
+        runtime.execute(2).unwrap();
+
+        assert_eq!(*log.lock().unwrap(), vec![(Shape::Square, 4), (Shape::Circle, 0)]);
+    }
+
+    #[test]
+    fn erased_task_node() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let total = Arc::new(Mutex::new(0));
+        let mut runtime = Toexec::new();
+
+        let sink = total.clone();
+        let (mut left, mut right) = runtime.build_scope(|b| {
+            let (sink_sender, sink_receiver) = b.port(None).split();
+            let sink_activator = b
+                .node(TaskNode {
+                    inputs: (sink_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        *sink.lock().unwrap() += x.unwrap()
+                    }),
+                })
+                .add_activator();
+
+            // Both inputs and the output are erased, so that the node's type only depends on the
+            // types of the items.
+            let (left_sender, left_receiver) = b.port(None).split();
+            let (right_sender, right_receiver) = b.port(None).split();
+            let mut add = b.node(ErasedTaskNodeMut::new_mut(
+                (left_receiver.as_data_input(), right_receiver.as_data_input()),
+                (sink_sender.with_activator(sink_activator),),
+                |(x, y): (Option<i32>, Option<i32>)| (Some(x.unwrap() + y.unwrap()),),
+            ));
+            (
+                ErasedOutput::output(left_sender.with_activator(add.add_activator())),
+                ErasedOutput::output(right_sender.with_activator(add.add_activator())),
+            )
+        });
+
+        for x in 1..=3 {
+            left.send_activate_mut(&mut runtime, Some(x));
+            right.send_activate_mut(&mut runtime, Some(10 * x));
+            runtime.execute(2).unwrap();
+        }
+
+        assert_eq!(*total.lock().unwrap(), 66);
+    }
+
+    #[test]
+    fn erased_edge_storage() {
+        use common::erased::*;
+        use std::sync::{Arc, Mutex};
+
+        /// An edge logging its uses and its drop, with a payload of type `P`.
+        struct Logged<P> {
+            log: Arc<Mutex<Vec<&'static str>>>,
+            _payload: P,
+        }
+
+        impl<P> OutputEdgeOnce<()> for Logged<P> {
+            type Item = ();
+
+            fn send_activate_once(self, _scheduler: &mut (), _item: ()) {
+                self.log.lock().unwrap().push("send")
+            }
+        }
+
+        impl<P> OutputEdgeMut<()> for Logged<P> {
+            fn send_activate_mut(&mut self, _scheduler: &mut (), _item: ()) {
+                self.log.lock().unwrap().push("send")
+            }
+        }
+
+        impl<P> Drop for Logged<P> {
+            fn drop(&mut self) {
+                self.log.lock().unwrap().push("drop")
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let logged = |payload| Logged {
+            log: log.clone(),
+            _payload: payload,
+        };
+        let large = |()| Logged {
+            log: log.clone(),
+            _payload: [0u64; 8],
+        };
+
+        // Small edges are stored inline, and dropped exactly once whether or not they are used.
+        let once: ErasedOutputOnce<(), ()> = ErasedEdge::output_once(logged(0u64));
+        assert!(once.is_inline());
+        once.send_activate_once(&mut (), ());
+        let mut reusable: ErasedOutput<(), ()> = ErasedEdge::output(logged(0u64));
+        assert!(reusable.is_inline());
+        reusable.send_activate_mut(&mut (), ());
+        drop(reusable);
+        drop(ErasedOutputOnce::<(), ()>::output_once(logged(0u64)));
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["send", "drop", "send", "drop", "drop"]
+        );
+
+        // Large edges are boxed.
+        log.lock().unwrap().clear();
+        let once: ErasedOutputOnce<(), ()> = ErasedEdge::output_once(large(()));
+        assert!(!once.is_inline());
+        once.send_activate_once(&mut (), ());
+        assert_eq!(*log.lock().unwrap(), vec!["send", "drop"]);
+
+        // Port senders with their activator fit inline.
+        let mut runtime = ::parallel::multiple_uses::Toexec::new();
+        runtime.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(|_: Option<i32>| ()),
+                })
+                .add_activator();
+            let edge: ErasedOutput<::parallel::multiple_uses::Toexec, Option<i32>> =
+                ErasedOutput::output(sender.with_activator(activator));
+            assert!(edge.is_inline());
+        });
+    }
+
+    #[test]
+    fn zip_input() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let sink = log.clone();
+        let (left, right, activator) = runtime.build_scope(|b| {
+            let (left_sender, left_receiver) = b.port(None).split();
+            let (right_sender, right_receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (ZipInput::new((left_receiver, right_receiver)),),
+                    outputs: (),
+                    task: StrictTask::new(move |latest: (Option<i32>, Option<&'static str>)| {
+                        sink.lock().unwrap().push(latest)
+                    }),
+                })
+                .add_activator();
+            (left_sender, right_sender, activator)
+        });
+
+        left.send(Some(1));
+        activator.activate(&mut runtime);
+        runtime.execute(2).unwrap();
+
+        right.send(Some("a"));
+        activator.activate(&mut runtime);
+        runtime.execute(2).unwrap();
+
+        // Only the left input was updated: the right value is re-emitted.
+        left.send(Some(2));
+        activator.activate(&mut runtime);
+        runtime.execute(2).unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![(Some(1), None), (Some(1), Some("a")), (Some(2), Some("a"))]
+        );
+    }
+
+    #[test]
+    fn pid_control() {
+        use parallel::control::*;
+        use std::time::Duration;
+
+        let period = Duration::from_millis(100);
+        let setpoint = Setpoint::Step {
+            at: 10,
+            before: 0.,
+            after: 1.,
+        };
+        let mut control = ControlLoop::new(
+            Pid::new(2., 1., 0.),
+            setpoint,
+            FirstOrderPlant::new(1., 1.),
+            period,
+        );
+
+        // The system stays at rest until the setpoint changes.
+        let samples = control.run(200);
+        assert!(samples[..10].iter().all(|sample| sample.measurement == 0.));
+        assert_eq!(samples[10].setpoint, 1.);
+        assert_eq!(samples[10].tick.elapsed, period * 10);
+
+        // The integral term removes the static error.
+        let last = samples.last().unwrap();
+        assert!((last.measurement - 1.).abs() < 1e-3);
+        assert!((last.command - 1.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn fold_task() {
+        use parallel::multiple_uses::*;
+
+        let mut runtime = Toexec::new();
+
+        let (root, averages) = runtime.build_scope(|b| {
+            let (input_sender, input_receiver) = b.port(None).split();
+            let (average_sender, average_receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (input_receiver.as_data_input(),),
+                    outputs: (average_sender.as_data_output(),),
+                    // A moving average over the last three values.
+                    task: FoldTask::new(Vec::new(), |window: &mut Vec<f64>, x: Option<f64>| {
+                        window.push(x.unwrap());
+                        if window.len() > 3 {
+                            window.remove(0);
+                        }
+                        (Some(window.iter().sum::<f64>() / window.len() as f64),)
+                    }),
+                })
+                .add_activator();
+            (input_sender.with_activator(activator), average_receiver)
+        });
+
+        let mut results = Vec::new();
+        for &x in &[3., 6., 9., 12.] {
+            root.send_activate(&mut runtime, Some(x));
+            runtime.execute(2).unwrap();
+            results.push(averages.recv().unwrap());
+        }
+
+        assert_eq!(results, vec![3., 4.5, 6., 9.]);
+    }
+
+    #[test]
+    fn delay_node() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let differences = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        // Compute `x - pre(x)`, with `pre(x)` initialized to zero.
+        let sink = differences.clone();
+        let root = runtime.build_scope(|b| {
+            let (current_sender, current_receiver) = b.port(None).split();
+            let (previous_sender, previous_receiver) = b.port(None).split();
+            let mut difference = b.node(TaskNode {
+                inputs: (
+                    current_receiver.as_data_input(),
+                    previous_receiver.as_data_input(),
+                ),
+                outputs: (),
+                task: StrictTask::new(move |x: Option<i32>, previous: Option<i32>| {
+                    sink.lock().unwrap().push(x.unwrap() - previous.unwrap())
+                }),
+            });
+
+            let (delay_sender, delay_receiver) = b.port(None).split();
+            let delay = b
+                .node(DelayNode::new(
+                    Some(0),
+                    delay_receiver.as_data_input(),
+                    previous_sender.with_activator(difference.add_activator()),
+                ))
+                .add_activator();
+
+            let current = current_sender.with_activator(difference.add_activator());
+            (current, delay_sender.with_activator(delay))
+        });
+
+        for &x in &[1, 4, 9, 16] {
+            root.send_activate(&mut runtime, (Some(x), Some(x)));
+            runtime.execute(2).unwrap();
+        }
+
+        assert_eq!(*differences.lock().unwrap(), vec![1, 3, 5, 7]);
+    }
+
+    #[test]
+    fn non_default_ports() {
+        use parallel::single_use::*;
+        use std::sync::mpsc;
+
+        // Channel endpoints have no default value.
+        let (tx, rx) = mpsc::channel();
+        let mut runtime = Toexec::new();
+
+        let root = runtime.build_scope(|b| {
+            let (sender, receiver) = b.port(tx).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(|tx: mpsc::Sender<i32>| tx.send(42).unwrap()),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        let (replacement, replaced) = mpsc::channel();
+        root.send_activate_once(&mut runtime, replacement);
+        runtime.execute(2).unwrap();
+
+        // The value sent on the edge replaced the initial one.
+        assert_eq!(replaced.recv().unwrap(), 42);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn edge_timer() {
+        use parallel::multiple_uses::*;
+        use std::thread;
+        use std::time::Duration;
+
+        let timer = EdgeTimer::new("source -> sink").with_bound(Duration::from_millis(1));
+        let mut runtime = Toexec::new();
+
+        let root = runtime.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (timer.input(receiver.as_data_input()),),
+                    outputs: (),
+                    task: StrictTask::new(|x: Option<i32>| assert!(x.is_some())),
+                })
+                .add_activator();
+            timer.output(sender.with_activator(activator))
+        });
+
+        for x in 0..3 {
+            root.send_activate(&mut runtime, Some(x));
+            assert_eq!(timer.in_flight(), 1);
+            // The node only runs once the runtime executes.
+            thread::sleep(Duration::from_millis(2));
+            runtime.execute(2).unwrap();
+        }
+
+        let stats = timer.stats();
+        assert_eq!(timer.in_flight(), 0);
+        assert_eq!((stats.count, stats.exceeded), (3, 3));
+        assert!(stats.min.unwrap() >= Duration::from_millis(2));
+        assert!(stats.percentile(50.).unwrap() >= Duration::from_millis(2));
+        assert!(timer.to_string().starts_with("source -> sink: 3 values"));
+    }
+
+    #[test]
+    fn output_adapters() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let sink = received.clone();
+        let mut root = runtime.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| sink.lock().unwrap().push(x)),
+                })
+                .add_activator();
+
+            // Keep the odd numbers, then send their running sum.
+            sender
+                .with_activator(activator)
+                .map(Some)
+                .scan(0, |sum: &mut i32, x: i32| {
+                    *sum += x;
+                    *sum
+                })
+                .filter(|x: &i32| x % 2 == 1)
+        });
+
+        for x in 1..=5 {
+            root.send_activate_mut(&mut runtime, x);
+            runtime.execute(2).unwrap();
+        }
+
+        assert_eq!(*received.lock().unwrap(), vec![Some(1), Some(4), Some(9)]);
+    }
+
+    #[test]
+    fn dsl_tasks() {
+        use parallel::multiple_uses::*;
+
+        let mut runtime = Toexec::new();
+        let (mut x, mut y, result) = runtime.build_scope(|b| {
+            // Compute (2 * x + y) * (x - y).
+            let (x_source, x) = b.source();
+            let (y_source, y) = b.source();
+            let mut double = b.task1(|x: i32| 2 * x);
+            let mut add = b.task2(|x: i32, y: i32| x + y);
+            let mut sub = b.task2(|x: i32, y: i32| x - y);
+            let mut mul = b.task2(|x: i32, y: i32| x * y);
+
+            x.connect(double.input0());
+            x.connect(sub.input0());
+            y.connect(add.input1());
+            y.connect(sub.input1());
+            double.output().connect(add.input0());
+            add.output().connect(mul.input0());
+            sub.output().connect(mul.input1());
+
+            let (sender, receiver) = b.port(None).split();
+            mul.output().connect_edge(sender.as_data_output().map(Some));
+
+            (x_source, y_source, receiver)
+        });
+
+        for &(a, b) in &[(3, 1), (5, 7), (-2, 4)] {
+            x.send_activate_mut(&mut runtime, a);
+            y.send_activate_mut(&mut runtime, b);
+            runtime.execute(2).unwrap();
+            assert_eq!(result.recv(), Some((2 * a + b) * (a - b)));
+        }
+    }
+
+    #[test]
+    fn capabilities() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Add `value` to the partial sum of the worker executing the node.
+        struct Add {
+            value: usize,
+            sums: Arc<WorkerLocal<usize>>,
+        }
+
+        impl<S: Capabilities> NodeMut<S> for Add {
+            fn execute_mut(&mut self, scheduler: &mut S) {
+                self.sums.with(scheduler, |sum| *sum += self.value)
+            }
+        }
+
+        impl<S: Capabilities> NodeOnce<S> for Add {
+            fn execute_once(mut self, scheduler: &mut S) {
+                self.execute_mut(scheduler)
+            }
+        }
+
+        let sums = Arc::new(WorkerLocal::new(4, || 0));
+        {
+            use parallel::multiple_uses::*;
+
+            let mut runtime = Toexec::new();
+            let activators = runtime.build_scope(|b| {
+                (1..=100)
+                    .map(|value| {
+                        b.node(Add {
+                            value,
+                            sums: sums.clone(),
+                        })
+                        .add_activator()
+                    })
+                    .collect::<Vec<_>>()
+            });
+            for _ in 0..2 {
+                for activator in &activators {
+                    activator.activate(&mut runtime);
+                }
+                runtime.execute(4).unwrap();
+            }
+        }
+        let sums = Arc::try_unwrap(sums).unwrap().into_inner();
+        assert_eq!(sums.iter().sum::<usize>(), 2 * 5050);
+
+        // Without workers, everything goes to the first slot, and tasks can't be cancelled.
+        let sums = Arc::new(WorkerLocal::new(4, || 0));
+        let ran = Arc::new(AtomicUsize::new(0));
+        {
+            use sequential::single_use::*;
+
+            let mut runtime = Toexec::new();
+            let roots = runtime.build_scope(|b| {
+                let ran = ran.clone();
+                let task = b
+                    .node(TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: CancellableTask::new(StrictTask::new(move || {
+                            ran.fetch_add(1, Ordering::SeqCst);
+                        })),
+                    })
+                    .add_activator();
+                let add = b
+                    .node(Add {
+                        value: 42,
+                        sums: sums.clone(),
+                    })
+                    .add_activator();
+                (task, add)
+            });
+            roots.0.activate_once(&mut runtime);
+            roots.1.activate_once(&mut runtime);
+            runtime.execute(1);
+        }
+        let sums = Arc::try_unwrap(sums).unwrap().into_inner();
+        assert_eq!(sums, vec![42, 0, 0, 0]);
+        assert_eq!(ran.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn execute_slice() {
+        use parallel::multiple_uses::*;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Count the executions of the node, and activate the following nodes.
+        struct Count {
+            count: Arc<AtomicUsize>,
+            next: Vec<RuntimeActivator<'static>>,
+        }
+
+        impl NodeMut<RuntimeLoc<'static>> for Count {
+            fn execute_mut(&mut self, scheduler: &mut RuntimeLoc<'static>) {
+                self.count.fetch_add(1, Ordering::SeqCst);
+                for activator in &self.next {
+                    activator.activate(scheduler);
+                }
+            }
+        }
+
+        let counts: Vec<_> = (0..5).map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let counted = || -> Vec<usize> {
+            counts
+                .iter()
+                .map(|count| count.load(Ordering::SeqCst))
+                .collect()
+        };
+
+        // a -> sum <- b -> other <- c
+        let mut runtime = Toexec::with_slicing();
+        let roots = runtime.build_scope(|b| {
+            let mut node = |label: &'static str, i: usize, next| {
+                b.node_named(
+                    label,
+                    Count {
+                        count: counts[i].clone(),
+                        next,
+                    },
+                )
+            };
+            let mut sum = node("sum", 3, vec![]);
+            let mut other = node("other", 4, vec![]);
+            let (sum_a, sum_b) = (sum.add_activator(), sum.add_activator());
+            let (other_b, other_c) = (other.add_activator(), other.add_activator());
+            drop((sum, other));
+
+            vec![
+                node("a", 0, vec![sum_a]).add_activator(),
+                node("b", 1, vec![sum_b, other_b]).add_activator(),
+                node("c", 2, vec![other_c]).add_activator(),
+            ]
+        });
+        let activate_roots = |runtime: &mut Toexec<'static>| {
+            for root in &roots {
+                root.activate(runtime);
+            }
+        };
+
+        // The dependencies are observed during a full execution.
+        activate_roots(&mut runtime);
+        runtime.execute(2).unwrap();
+        assert_eq!(counted(), vec![1, 1, 1, 1, 1]);
+
+        for _ in 0..2 {
+            activate_roots(&mut runtime);
+            runtime.execute_slice(2, &["sum"]).unwrap();
+        }
+        assert_eq!(counted(), vec![3, 3, 1, 3, 1]);
+
+        activate_roots(&mut runtime);
+        runtime.execute_slice(2, &["other"]).unwrap();
+        assert_eq!(counted(), vec![3, 4, 2, 3, 2]);
+
+        // The nodes outside of the slices are still armed.
+        activate_roots(&mut runtime);
+        runtime.execute(2).unwrap();
+        assert_eq!(counted(), vec![4, 5, 3, 4, 3]);
+
+        // Dropped nodes are forgotten.
+        let node = Count {
+            count: Arc::new(AtomicUsize::new(0)),
+            next: vec![],
+        };
+        let temporary = runtime.build_scope(|b| b.node_named("temporary", node).add_activator());
+        runtime.execute_slice(2, &["temporary"]).unwrap();
+        drop(temporary);
+        let sliced = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            runtime.execute_slice(2, &["temporary"])
+        }));
+        assert!(sliced.is_err());
+        drop(roots);
+        let sliced = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            runtime.execute_slice(2, &["sum"])
+        }));
+        assert!(sliced.is_err());
+    }
+
+    #[test]
+    fn graph_macro() {
+        use parallel::multiple_uses::*;
+
+        let mut runtime = Toexec::new();
+        let (x, y, result) = runtime.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            graph!(b => {
+                x: Source;
+                y: Source;
+                sum: Strict(|x: i32, y: i32| (x + y, x - y));
+                product: Strict(|x: i32, y: i32| (x * y,));
+                x -> sum.0;
+                y -> sum.1;
+                sum.0 -> product.0;
+                sum.1 -> product.1;
+                product.0 -> edge(sender.as_data_output().map(Some));
+            });
+            (x.0, y.0, receiver)
+        });
+        let (mut x, mut y) = (x, y);
+
+        for &(a, b) in &[(3, 1), (5, 7)] {
+            x.send_activate_mut(&mut runtime, a);
+            y.send_activate_mut(&mut runtime, b);
+            runtime.execute(2).unwrap();
+            assert_eq!(result.recv(), Some(a * a - b * b));
+        }
+    }
+
+    #[test]
+    fn subgraph_adder() {
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        type Wire = ErasedOutputOnce<'static, RuntimeLoc<'static>, bool>;
+        type Builder<'a> = ScopedGraphBuilder<'a, Toexec<'static>>;
+
+        fn wire<E>(edge: E) -> Wire
+        where
+            E: OutputEdgeOnce<RuntimeLoc<'static>, Item = bool> + Send + Sync + 'static,
+        {
+            ErasedEdge::output_once(edge)
+        }
+
+        /// A node computing `f` on its two inputs.
+        fn gate(b: &mut Builder, f: fn(bool, bool) -> bool, output: Wire) -> (Wire, Wire) {
+            let (x_sender, x_receiver) = b.port(None).split();
+            let (y_sender, y_receiver) = b.port(None).split();
+            let mut node = b.node(TaskNode {
+                inputs: (x_receiver.as_data_input(), y_receiver.as_data_input()),
+                outputs: (output,),
+                task: StrictTask::new(move |x: Option<bool>, y: Option<bool>| {
+                    (f(x.unwrap(), y.unwrap()),)
+                }),
+            });
+            (
+                wire(x_sender.with_activator(node.add_activator()).map(Some)),
+                wire(y_sender.with_activator(node.add_activator()).map(Some)),
+            )
+        }
+
+        /// A node sending its input to all of `outputs`.
+        fn fork(b: &mut Builder, outputs: Vec<Wire>) -> Wire {
+            let (sender, receiver) = b.port(None).split();
+            let mut clone = CloneOutput::new();
+            for output in outputs {
+                clone.connect(output);
+            }
+            let node = TaskNode {
+                inputs: (receiver.as_data_input(),),
+                outputs: (clone,),
+                task: StrictTask::new(|x: Option<bool>| (x.unwrap(),)),
+            };
+            wire(sender.with_activator(b.node(node).add_activator()).map(Some))
+        }
+
+        struct HalfAdder;
+
+        impl<'a> Subgraph<'a, Toexec<'static>> for HalfAdder {
+            type Inputs = (Wire, Wire);
+            type Outputs = (Wire, Wire);
+
+            fn build(&self, b: &mut Builder<'a>, (sum, carry): (Wire, Wire)) -> (Wire, Wire) {
+                let (xor_x, xor_y) = gate(b, |x, y| x ^ y, sum);
+                let (and_x, and_y) = gate(b, |x, y| x & y, carry);
+                (fork(b, vec![xor_x, and_x]), fork(b, vec![xor_y, and_y]))
+            }
+        }
+
+        struct FullAdder;
+
+        impl<'a> Subgraph<'a, Toexec<'static>> for FullAdder {
+            type Inputs = (Wire, Wire, Wire);
+            type Outputs = (Wire, Wire);
+
+            fn build(&self, b: &mut Builder<'a>, (sum, carry): (Wire, Wire)) -> Self::Inputs {
+                let (carry_x, carry_y) = gate(b, |x, y| x | y, carry);
+                let (partial, carry_in) = b.instantiate("high", &HalfAdder, (sum, carry_y));
+                let (x, y) = b.instantiate("low", &HalfAdder, (partial, carry_x));
+                (x, y, carry_in)
+            }
+        }
+
+        /// A node sending values on edges.
+        struct Inject(Vec<(Wire, bool)>);
+
+        impl NodeOnce<RuntimeLoc<'static>> for Inject {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+                for (edge, value) in self.0 {
+                    edge.send_activate_once(scheduler, value)
+                }
+            }
+        }
+
+        let add = |x: u8, y: u8| -> u8 {
+            let bits = Arc::new(Mutex::new(vec![None; 5]));
+            let mut runtime = Toexec::new();
+            let root = runtime.build_scope(|b| {
+                let sink = |b: &mut Builder, i: usize| {
+                    let bits = bits.clone();
+                    let (sender, receiver) = b.port(None).split();
+                    let node = TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (),
+                        task: StrictTask::new(move |bit: Option<bool>| {
+                            bits.lock().unwrap()[i] = bit;
+                        }),
+                    };
+                    wire(sender.with_activator(b.node(node).add_activator()).map(Some))
+                };
+
+                // Build from the most significant bit, whose carry is the fifth bit of the sum.
+                let mut carry = sink(b, 4);
+                let mut injected = Vec::new();
+                for i in (0..4).rev() {
+                    let sum = sink(b, i);
+                    let (x_bit, y_bit, carry_in) =
+                        b.instantiate(&format!("bit{}", i), &FullAdder, (sum, carry));
+                    injected.push((x_bit, x >> i & 1 == 1));
+                    injected.push((y_bit, y >> i & 1 == 1));
+                    carry = carry_in;
+                }
+                injected.push((carry, false));
+
+                b.node(Inject(injected)).add_activator()
+            });
+            root.activate_once(&mut runtime);
+            runtime.execute(4).unwrap();
+
+            let bits = bits.lock().unwrap();
+            (0..5).map(|i| (bits[i].unwrap() as u8) << i).sum()
+        };
+
+        assert_eq!(add(11, 6), 17);
+        assert_eq!(add(15, 15), 30);
+        assert_eq!(add(0, 9), 9);
+    }
+
+    #[test]
+    fn logic_components() {
+        use components::logic::*;
+        use parallel::multiple_uses::*;
+
+        let mut runtime = Toexec::new();
+        let (mut sources, sum, comparison, selection) = runtime.build_scope(|b| {
+            // The bits of x and y, the selector and the incoming carry.
+            let (sources, wires): (Vec<_>, Vec<_>) = (0..10).map(|_| b.source()).unzip();
+
+            let receivers = |n: usize| {
+                (0..n)
+                    .map(|_| {
+                        let (sender, receiver) = b.port(None).split();
+                        (
+                            InputHandle::from_edge(sender.as_data_output().map(Some)),
+                            receiver,
+                        )
+                    })
+                    .unzip::<_, _, Vec<_>, Vec<_>>()
+            };
+            let (mut sum_wires, sum) = receivers(5);
+            let (mut comparison_wires, comparison) = receivers(3);
+            let (selection_wires, selection) = receivers(4);
+
+            let carry_out = sum_wires.pop().unwrap();
+            let (adder_x, adder_y, carry_in) =
+                b.instantiate("adder", &RippleCarryAdder::new(4), (sum_wires, carry_out));
+            let greater = comparison_wires.pop().unwrap();
+            let equal = comparison_wires.pop().unwrap();
+            let less = comparison_wires.pop().unwrap();
+            let (comparator_x, comparator_y) =
+                b.instantiate("comparator", &Comparator::new(4), (less, equal, greater));
+            let (select, multiplexer_x, multiplexer_y) =
+                b.instantiate("multiplexer", &Multiplexer::new(4), selection_wires);
+
+            for (wire, ((adder, comparator), multiplexer)) in wires[..4]
+                .iter()
+                .zip(adder_x.into_iter().zip(comparator_x).zip(multiplexer_x))
+            {
+                wire.connect(adder);
+                wire.connect(comparator);
+                wire.connect(multiplexer);
+            }
+            for (wire, ((adder, comparator), multiplexer)) in wires[4..8]
+                .iter()
+                .zip(adder_y.into_iter().zip(comparator_y).zip(multiplexer_y))
+            {
+                wire.connect(adder);
+                wire.connect(comparator);
+                wire.connect(multiplexer);
+            }
+            wires[8].connect(select);
+            wires[9].connect(carry_in);
+
+            (sources, sum, comparison, selection)
+        });
+
+        fn number<R: Receiver<Item = Option<bool>>>(bits: &[R]) -> u8 {
+            bits.iter()
+                .enumerate()
+                .map(|(i, bit)| (bit.recv().unwrap() as u8) << i)
+                .sum()
+        }
+
+        for &(x, y) in &[(11u8, 6u8), (15, 15), (3, 9), (0, 0), (7, 7)] {
+            for i in 0..4 {
+                sources[i].send_activate_mut(&mut runtime, x >> i & 1 == 1);
+                sources[4 + i].send_activate_mut(&mut runtime, y >> i & 1 == 1);
+            }
+            sources[8].send_activate_mut(&mut runtime, x < y);
+            sources[9].send_activate_mut(&mut runtime, false);
+            runtime.execute(2).unwrap();
+
+            assert_eq!(number(&sum), x + y);
+            assert_eq!(number(&selection), x.max(y));
+            let comparison: Vec<_> = comparison.iter().map(|bit| bit.recv().unwrap()).collect();
+            assert_eq!(comparison, vec![x < y, x == y, x > y]);
+        }
+    }
+
+    #[test]
+    fn memory_limits() {
+        use parallel::failure::ExecutionError;
+        use parallel::memory::*;
+        use parallel::single_use::*;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        /// A node holding a payload.
+        #[allow(dead_code)]
+        struct Payload([u8; 1024]);
+
+        impl<'r> NodeOnce<RuntimeLoc<'r>> for Payload {
+            fn execute_once(self, _scheduler: &mut RuntimeLoc<'r>) {}
+        }
+
+        /// A node creating a payload node which is never activated, then creating itself again:
+        /// the memory grows without bound.
+        struct Leak<'r> {
+            leaked: Arc<Mutex<Vec<RcActivator<'r>>>>,
+        }
+
+        impl<'r> NodeOnce<RuntimeLoc<'r>> for Leak<'r> {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+                let leaked = self.leaked.clone();
+                let next = scheduler.build_scope(|b| {
+                    leaked
+                        .lock()
+                        .unwrap()
+                        .push(b.node(Payload([0; 1024])).add_activator());
+                    b.node(self).add_activator()
+                });
+                next.activate_once(scheduler)
+            }
+        }
+
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let accountant = {
+            let warnings = warnings.clone();
+            Accountant::new()
+                .with_soft_limit(16 * 1024, move |used| {
+                    assert!(used > 16 * 1024);
+                    warnings.fetch_add(1, Ordering::SeqCst);
+                })
+                .with_hard_limit(64 * 1024)
+        };
+        let mut runtime = Toexec::with_accountant(accountant);
+        let leaked = Arc::new(Mutex::new(Vec::new()));
+        let root = runtime.build_scope(|b| {
+            b.node(Leak {
+                leaked: leaked.clone(),
+            })
+            .add_activator()
+        });
+        root.activate_once(&mut runtime);
+
+        let error = match runtime.execute(2) {
+            Err(ExecutionError::MemoryLimit(error)) => error,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(error.limit, 64 * 1024);
+        assert!(error.used > 64 * 1024);
+        assert_eq!(warnings.load(Ordering::SeqCst), 1);
+
+        // Dropping the leaked nodes releases their memory.
+        let accountant = runtime.accountant().unwrap().clone();
+        assert!(Accountant::reserve(&accountant, 1).is_err());
+        leaked.lock().unwrap().clear();
+        assert_eq!(accountant.used(), 0);
+        let reservation = Accountant::reserve(&accountant, 32 * 1024).unwrap();
+        assert_eq!(accountant.used(), 32 * 1024);
+        assert_eq!(warnings.load(Ordering::SeqCst), 2);
+        drop(reservation);
+        runtime.execute(2).unwrap();
+    }
+
+    #[test]
+    fn hot_swap() {
+        use parallel::multiple_uses::*;
+        use parallel::port::{RcReceiver, RcSender};
+        use std::sync::Mutex;
+
+        /// Add the input multiplied by `factor` to a running sum, and send the sum.
+        struct Accumulate<O> {
+            input: RcReceiver<Mutex<i32>>,
+            sum: (RcSender<Mutex<i32>>, RcReceiver<Mutex<i32>>),
+            factor: i32,
+            output: O,
+        }
+
+        impl<'r, O: OutputEdgeMut<RuntimeLoc<'r>, Item = Option<i32>>> NodeMut<RuntimeLoc<'r>>
+            for Accumulate<O>
+        {
+            fn execute_mut(&mut self, scheduler: &mut RuntimeLoc<'r>) {
+                let sum = self.sum.1.recv() + self.factor * self.input.recv();
+                self.sum.0.send(sum);
+                self.output.send_activate_mut(scheduler, Some(sum))
+            }
+        }
+
+        let mut runtime = Toexec::new();
+        let (result_sender, result) = runtime.build_scope(|b| b.port(None).split());
+        let output = SharedEdge::new(result_sender.as_data_output());
+
+        // Build a version of the accumulator, without connecting it.
+        let build = |runtime: &mut Toexec<'static>, factor| {
+            runtime.build_scope(|b| {
+                let (input_sender, input) = b.port(0).split();
+                let (sum_sender, sum_receiver) = b.port(0).split();
+                let mut node = b.node(Accumulate {
+                    input,
+                    sum: (sum_sender.clone(), sum_receiver),
+                    factor,
+                    output: output.clone(),
+                });
+                (input_sender.with_activator(node.add_activator()), sum_sender)
+            })
+        };
+
+        let (v1, sum1) = build(&mut runtime, 1);
+        let swap = HotSwap::new(v1);
+        let mut input = swap.input();
+        for x in 1..=3 {
+            input.send_activate_mut(&mut runtime, x);
+            runtime.execute(2).unwrap();
+        }
+        assert_eq!(result.recv(), Some(6));
+
+        // Upgrade, keeping the running sum.
+        let (v2, sum2) = build(&mut runtime, 10);
+        let version = swap
+            .prepare(v2)
+            .migrate(sum1, sum2.clone())
+            .commit(&mut runtime, |_| Ok::<(), ()>(()))
+            .unwrap();
+        assert_eq!((version, swap.version()), (1, 1));
+        input.send_activate_mut(&mut runtime, 1);
+        runtime.execute(2).unwrap();
+        assert_eq!(result.recv(), Some(16));
+
+        // An upgrade whose validation fails is rolled back.
+        let (v3, sum3) = build(&mut runtime, -1);
+        let mut probe = swap.input();
+        let error = swap
+            .prepare(v3)
+            .migrate(sum2, sum3)
+            .commit(&mut runtime, |runtime| {
+                probe.send_activate_mut(runtime, 100);
+                runtime.execute(2).unwrap();
+                match result.recv() {
+                    Some(sum) if sum < 0 => Err(sum),
+                    _ => Ok(()),
+                }
+            })
+            .unwrap_err();
+        assert_eq!(error, -84);
+        assert_eq!(swap.version(), 1);
+        input.send_activate_mut(&mut runtime, 1);
+        runtime.execute(2).unwrap();
+        assert_eq!(result.recv(), Some(26));
+    }
+
+    #[test]
+    fn par_map_pattern() {
+        use parallel::single_use::*;
+        use patterns::par_map::par_map;
+
+        let data: Vec<u64> = (1..=10).collect();
+        let mut runtime = Toexec::new();
+        let (squares, empty) = runtime.build_scope(|b| {
+            (
+                par_map(b, &data, 3, |x| x * x),
+                par_map(b, &data[..0], 3, |x| x + 1),
+            )
+        });
+        squares.activator.activate_once(&mut runtime);
+        runtime.execute(4).unwrap();
+        assert_eq!(
+            squares.output.recv(),
+            Some(vec![1, 4, 9, 16, 25, 36, 49, 64, 81, 100])
+        );
+        assert_eq!(empty.output.recv(), None);
+
+        empty.activator.activate_once(&mut runtime);
+        runtime.execute(4).unwrap();
+        assert_eq!(empty.output.recv(), Some(vec![]));
+    }
+
+    #[test]
+    fn join_node() {
+        use parallel::single_use::*;
+
+        let mut runtime = Toexec::new();
+        let (root, result) = runtime.build_scope(|b| {
+            let (result_sender, result_receiver) = b.port(None).split();
+            let inputs = b.join(5, result_sender.as_data_output().map(Some));
+
+            // Scatter the input to five branches multiplying it by their index.
+            let mut scatter = CloneOutput::new();
+            for (i, input) in (0..).zip(inputs) {
+                let (sender, receiver) = b.port(None).split();
+                let branch = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (input,),
+                        task: StrictTask::new(move |x: Option<u32>| (x.unwrap() * i,)),
+                    })
+                    .add_activator();
+                scatter.connect(sender.with_activator(branch).map(Some));
+            }
+
+            let (sender, receiver) = b.port(Some(7)).split();
+            drop(sender);
+            let root = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (scatter,),
+                    task: StrictTask::new(|x: Option<u32>| (x.unwrap(),)),
+                })
+                .add_activator();
+            (root, result_receiver)
+        });
+        root.activate_once(&mut runtime);
+        runtime.execute(4).unwrap();
+        assert_eq!(result.recv(), Some(vec![0, 7, 14, 21, 28]));
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn steal_stats() {
+        use parallel::single_use::*;
+        use parallel::trace::{FairnessReport, StealStats, WorkerSteals};
+
+        struct Fan(Vec<RcActivator<'static>>);
+
+        impl NodeOnce<RuntimeLoc<'static>> for Fan {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+                for sink in self.0 {
+                    sink.activate_once(scheduler)
+                }
+            }
+        }
+
+        let stats = StealStats::new(4);
+        let mut runtime = Toexec::new();
+        runtime.set_trace_hook(stats.clone());
+        let root = runtime.build_scope(|b| {
+            let sinks = (0..64)
+                .map(|_| {
+                    b.node(TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| ()),
+                    })
+                    .add_activator()
+                })
+                .collect::<Vec<_>>();
+            b.node(Fan(sinks)).add_activator()
+        });
+        root.activate_once(&mut runtime);
+        runtime.execute(4).unwrap();
+
+        let report = stats.report();
+        assert_eq!(report.workers.len(), 4);
+        assert_eq!(report.workers.iter().map(|w| w.executed).sum::<usize>(), 65);
+        assert_eq!(
+            report.workers.iter().map(|w| w.stolen_from).sum::<usize>(),
+            report.total_steals()
+        );
+        for worker in 0..4 {
+            assert_eq!(stats.steals(worker, worker), 0);
+        }
+        stats.reset();
+        assert_eq!(stats.report().total_steals(), 0);
+
+        let worker = |worker, steals, stolen_from, idle| WorkerSteals {
+            worker,
+            executed: 10,
+            steals,
+            stolen_from,
+            idle,
+        };
+        let report = FairnessReport {
+            workers: vec![worker(0, 0, 9, 1), worker(1, 5, 1, 2), worker(2, 5, 0, 1)],
+        };
+        assert_eq!(report.hot_spots(), vec![0]);
+        assert_eq!(report.starving(), vec![0]);
+        let text = report.to_string();
+        assert!(text.contains("hot-spots: workers [0]"));
+        assert!(text.contains("starving: workers [0]"));
+    }
+
+    #[test]
+    fn runtime_config() {
+        use parallel::config::{QueueOrder, RuntimeConfig};
+        use parallel::single_use::*;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        // Recursively split a range until it contains a single number, and add it to the sum.
+        struct Split {
+            range: (usize, usize),
+            sum: Arc<AtomicUsize>,
+        }
+
+        impl NodeOnce<RuntimeLoc<'static>> for Split {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+                let (start, end) = self.range;
+                if end - start == 1 {
+                    self.sum.fetch_add(start, SeqCst);
+                    return;
+                }
+                let middle = (start + end) / 2;
+                for &range in &[(start, middle), (middle, end)] {
+                    let sum = self.sum.clone();
+                    let half =
+                        scheduler.build_scope(|b| b.node(Split { range, sum }).add_activator());
+                    half.activate_once(scheduler);
+                }
+            }
+        }
+
+        let configs = [
+            RuntimeConfig::new(),
+            RuntimeConfig::new().queue(QueueOrder::Lifo),
+            RuntimeConfig::new().queue(QueueOrder::Lifo).steal_batch(8),
+            RuntimeConfig::new().steal_batch(3),
+        ];
+        for &config in &configs {
+            let sum = Arc::new(AtomicUsize::new(0));
+            let mut runtime = Toexec::new();
+            runtime.set_config(config);
+            assert_eq!(runtime.config(), config);
+            let root = runtime.build_scope(|b| {
+                b.node(Split {
+                    range: (0, 1000),
+                    sum: sum.clone(),
+                })
+                .add_activator()
+            });
+            root.activate_once(&mut runtime);
+            runtime.execute(4).unwrap();
+            assert_eq!(sum.load(SeqCst), 999 * 1000 / 2);
+        }
+    }
+
+    #[test]
+    fn idle_backoff() {
+        use parallel::config::RuntimeConfig;
+        use parallel::single_use::*;
+        use parallel::termination::Termination;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        // Parking returns immediately when work was published since the epoch was read.
+        let termination = Termination::new(1);
+        let epoch = termination.epoch();
+        termination.published();
+        let start = Instant::now();
+        termination.park_since(epoch, Duration::from_secs(10));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // Idle workers park for long periods, but wake up as soon as values are injected.
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+        runtime.set_config(
+            RuntimeConfig::new()
+                .spins(0)
+                .max_park(Duration::from_secs(10)),
+        );
+        let inputs: Vec<_> = (0..3)
+            .map(|_| {
+                let results = results.clone();
+                runtime.build_scope(|b| {
+                    let (sender, receiver) = b.port(None).split();
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |x: Option<i32>| {
+                                results.lock().unwrap().push(x.unwrap())
+                            }),
+                        })
+                        .add_activator();
+                    sender.with_activator(activator)
+                })
+            })
+            .collect();
+
+        let injector = runtime.injector_handle();
+        let io = thread::spawn(move || {
+            for (i, input) in inputs.into_iter().enumerate() {
+                thread::sleep(Duration::from_millis(20));
+                injector.clone().inject_send(input, Some(i as i32));
+            }
+        });
+
+        let start = Instant::now();
+        runtime.execute(2).unwrap();
+        io.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(*results.lock().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn static_plan() {
+        use sequential::static_plan::*;
+
+        fn sum(inputs: &Inputs<i32>) -> i32 {
+            inputs.iter().sum()
+        }
+
+        fn difference(inputs: &Inputs<i32>) -> i32 {
+            inputs.get(0) - inputs.get(1)
+        }
+
+        fn product(inputs: &Inputs<i32>) -> i32 {
+            inputs.iter().product()
+        }
+
+        // (x + y) * (x - y), with the nodes listed out of order.
+        static PLAN: ExecutablePlan<i32, 5, 6> = ExecutablePlan::new(
+            [
+                StaticNode::Map(product),
+                StaticNode::Input(0),
+                StaticNode::Map(difference),
+                StaticNode::Map(sum),
+                StaticNode::Input(1),
+            ],
+            [(1, 3), (4, 3), (1, 2), (4, 2), (3, 0), (2, 0)],
+        );
+
+        let mut values = [0; 5];
+        PLAN.execute(&[5, 3], &mut values);
+        assert_eq!(values, [16, 5, 2, 8, 3]);
+        PLAN.execute(&[3, 5], &mut values);
+        assert_eq!(values, [-16, 3, -2, 8, 5]);
+        assert_eq!(PLAN.order()[4], 0);
+
+        let cycle = ::std::panic::catch_unwind(|| {
+            ExecutablePlan::<i32, 2, 2>::new(
+                [StaticNode::Map(sum), StaticNode::Map(sum)],
+                [(0, 1), (1, 0)],
+            )
+        });
+        assert!(cycle.is_err());
+    }
+
+    #[test]
+    fn worker_count() {
+        use parallel::config::RuntimeConfig;
+        use parallel::multiple_uses::*;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        assert!(RuntimeConfig::new().worker_count() >= 1);
+        assert_eq!(RuntimeConfig::new().workers(3).worker_count(), 3);
+
+        let count = Arc::new(AtomicUsize::new(0));
+        for &workers in &[1, 2] {
+            let mut runtime = Toexec::new();
+            runtime.set_config(RuntimeConfig::new().workers(workers));
+            let counter = count.clone();
+            let mut root = runtime.build_scope(|b| {
+                b.node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(move || {
+                        counter.fetch_add(1, SeqCst);
+                    }),
+                })
+                .add_activator()
+            });
+            root.activate_mut(&mut runtime);
+            runtime.execute_default().unwrap();
+            root.activate_mut(&mut runtime);
+            runtime.execute(1).unwrap();
+        }
+        assert_eq!(count.load(SeqCst), 4);
+
+        let mut runtime = Toexec::new();
+        let zero =
+            ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| runtime.execute(0)));
+        assert!(zero.is_err());
+    }
+
+    #[test]
+    fn event_sequencer() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        // An edge recording the values it receives, along with its source.
+        struct Record(usize, Arc<Mutex<Vec<(usize, i32)>>>);
+
+        impl<S> OutputEdgeOnce<S> for Record {
+            type Item = i32;
+
+            fn send_activate_once(mut self, scheduler: &mut S, item: i32) {
+                self.send_activate_mut(scheduler, item)
+            }
+        }
+
+        impl<S> OutputEdgeMut<S> for Record {
+            fn send_activate_mut(&mut self, _scheduler: &mut S, item: i32) {
+                self.1.lock().unwrap().push((self.0, item))
+            }
+        }
+
+        let sequencer = Sequencer::recording();
+        let threads: Vec<_> = (0..2)
+            .map(|source| {
+                let ingest = sequencer.source(source);
+                thread::spawn(move || {
+                    for i in 0..10 {
+                        ingest.push(i);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let first = sequencer.cut();
+        let empty = sequencer.cut();
+        sequencer.source(1).push(10);
+        let last = sequencer.cut();
+
+        assert_eq!(first.events.len(), 20);
+        assert!(first.events.windows(2).all(|w| w[0].seq < w[1].seq));
+        assert!(empty.events.is_empty());
+        assert_eq!((last.instant, last.events[0].seq), (2, 20));
+
+        // Replaying the log gives back the same batches, which are delivered in sequence order.
+        let replayed = Sequencer::replay(&sequencer.log().unwrap());
+        assert_eq!(replayed, vec![first.clone(), empty, last]);
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut edges = vec![Record(0, received.clone()), Record(1, received.clone())];
+        let mut runtime = Toexec::new();
+        let expected: Vec<_> = first.events.iter().map(|e| (e.source, e.value)).collect();
+        first.deliver(&mut runtime, &mut edges);
+        assert_eq!(*received.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn event_log() {
+        use parallel::multiple_uses::*;
+        use parallel::port::{RcReceiver, RcSender};
+        use std::io::Cursor;
+        use std::sync::Mutex;
+
+        /// Add the input to a running sum, and send the sum.
+        struct Accumulate<O> {
+            input: RcReceiver<Mutex<i32>>,
+            sum: (RcSender<Mutex<i32>>, RcReceiver<Mutex<i32>>),
+            output: O,
+        }
+
+        impl<'r, O: OutputEdgeMut<RuntimeLoc<'r>, Item = Option<i32>>> NodeMut<RuntimeLoc<'r>>
+            for Accumulate<O>
+        {
+            fn execute_mut(&mut self, scheduler: &mut RuntimeLoc<'r>) {
+                let sum = self.sum.1.recv() + self.input.recv();
+                self.sum.0.send(sum);
+                self.output.send_activate_mut(scheduler, Some(sum))
+            }
+        }
+
+        let build = |runtime: &mut Toexec<'static>| {
+            runtime.build_scope(|b| {
+                let (result_sender, result) = b.port(None).split();
+                let (input_sender, input) = b.port(0).split();
+                let (sum_sender, sum_receiver) = b.port(0).split();
+                let mut node = b.node(Accumulate {
+                    input,
+                    sum: (sum_sender, sum_receiver),
+                    output: result_sender.as_data_output(),
+                });
+                (vec![input_sender.with_activator(node.add_activator())], result)
+            })
+        };
+
+        // Live run, logging the events.
+        let sequencer = Sequencer::new();
+        let source = sequencer.source(0);
+        let mut writer = EventLogWriter::new(Vec::new()).unwrap();
+        let mut runtime = Toexec::new();
+        let (mut edges, result) = build(&mut runtime);
+        let mut live = Vec::new();
+        for &events in &[&[1][..], &[], &[20]] {
+            for &event in events {
+                source.push(event);
+            }
+            let batch = sequencer.cut();
+            writer.write_batch(&batch).unwrap();
+            batch.deliver(&mut runtime, &mut edges);
+            runtime.execute(2).unwrap();
+            live.push(result.recv());
+        }
+        assert_eq!(live, vec![Some(1), None, Some(21)]);
+        let log = writer.into_inner().unwrap();
+
+        // Offline re-execution.
+        let reader = EventLogReader::new(Cursor::new(&log)).unwrap();
+        let mut runtime = Toexec::new();
+        let (mut edges, result) = build(&mut runtime);
+        let mut replayed = Vec::new();
+        let instants = reader
+            .drive(&mut runtime, &mut edges, |runtime| {
+                runtime.execute(2).unwrap();
+                replayed.push(result.recv());
+            })
+            .unwrap();
+        assert_eq!((instants, replayed), (3, live));
+
+        let batches: Vec<Batch<String>> = EventLogReader::new(Cursor::new(&log))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(batches[2].events[0].value, "20");
+        assert!(EventLogReader::<_, i32>::new(Cursor::new(b"instant 0")).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_event_log() {
+        // Structured values, without a textual representation, saved as one JSON batch per line.
+        let sequencer = Sequencer::new();
+        let source = sequencer.source(1);
+        source.push(("temperature".to_string(), vec![21, 22]));
+        let mut batches = vec![sequencer.cut(), sequencer.cut()];
+        source.push(("pressure".to_string(), vec![1013]));
+        batches.push(sequencer.cut());
+
+        let log: String = batches
+            .iter()
+            .map(|batch| serde_json::to_string(batch).unwrap() + "\n")
+            .collect();
+        let read: Vec<Batch<(String, Vec<i32>)>> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(read, batches);
+        assert_eq!(read[2].events[0].source, 1);
+    }
+
+    #[test]
+    fn node_affinity() {
+        use std::sync::{Arc, Mutex};
+
+        /// Record the index of the worker executing the node.
+        struct Where(Arc<Mutex<Vec<usize>>>);
+
+        impl<S: HasWorkerIndex> NodeMut<S> for Where {
+            fn execute_mut(&mut self, scheduler: &mut S) {
+                self.0.lock().unwrap().push(scheduler.worker_index())
+            }
+        }
+
+        impl<S: HasWorkerIndex> NodeOnce<S> for Where {
+            fn execute_once(mut self, scheduler: &mut S) {
+                self.execute_mut(scheduler)
+            }
+        }
+
+        // Worker indices are taken modulo the number of workers: 4 is worker 1 out of 3.
+        let workers = Arc::new(Mutex::new(Vec::new()));
+        {
+            use parallel::single_use::*;
+
+            let mut runtime = Toexec::new();
+            let activators = runtime.build_scope(|b| {
+                (0..50)
+                    .map(|i| {
+                        b.node(Where(workers.clone()))
+                            .with_affinity(if i % 2 == 0 { 1 } else { 4 })
+                            .add_activator()
+                    })
+                    .collect::<Vec<_>>()
+            });
+            for activator in activators {
+                activator.activate_once(&mut runtime);
+            }
+            runtime.execute(3).unwrap();
+        }
+        assert_eq!(*workers.lock().unwrap(), vec![1; 50]);
+
+        let workers = Arc::new(Mutex::new(Vec::new()));
+        {
+            use parallel::multiple_uses::*;
+
+            let mut runtime = Toexec::new();
+            let activators = runtime.build_scope(|b| {
+                (0..50)
+                    .map(|_| b.node(Where(workers.clone())).with_affinity(2).add_activator())
+                    .collect::<Vec<_>>()
+            });
+            for _ in 0..2 {
+                for activator in &activators {
+                    activator.activate(&mut runtime);
+                }
+                runtime.execute(3).unwrap();
+            }
+        }
+        assert_eq!(*workers.lock().unwrap(), vec![2; 100]);
+
+        /// Record the thread executing the node.
+        struct Which(Arc<Mutex<Vec<::std::thread::ThreadId>>>);
+
+        impl<S> NodeMut<S> for Which {
+            fn execute_mut(&mut self, _: &mut S) {
+                self.0.lock().unwrap().push(::std::thread::current().id())
+            }
+        }
+
+        // On a pool, pinned nodes run on the same thread across executions.
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        {
+            use parallel::multiple_uses::*;
+
+            let pool = ::parallel::pool::ThreadPool::new(3);
+            let mut runtime = Toexec::new();
+            let activator = runtime.build_scope(|b| {
+                b.node(Which(threads.clone()))
+                    .with_affinity(1)
+                    .add_activator()
+            });
+            for _ in 0..10 {
+                activator.activate(&mut runtime);
+                runtime.execute_on(&pool).unwrap();
+            }
+        }
+        let threads = threads.lock().unwrap();
+        assert_eq!(threads.len(), 10);
+        assert!(threads.iter().all(|&thread| thread == threads[0]));
+    }
+
+    #[test]
+    fn checked_task_node() {
+        use parallel::multiple_uses::*;
+
+        assert_eq!(<()>::ARITY, 0);
+        assert_eq!(<(u8, u16, u32)>::ARITY, 3);
+
+        let mut runtime = Toexec::new();
+        let (root, totals) = runtime.build_scope(|b| {
+            let (sum_sender, sum_receiver) = b.port(None).split();
+            let (total_sender, total_receiver) = b.port(None).split();
+            let total = b
+                .node(TaskNode::checked(
+                    (sum_receiver.as_data_input(),),
+                    (total_sender.as_data_output(),),
+                    FoldTask::new(0, |total: &mut i32, sum: Option<i32>| {
+                        *total += sum.unwrap();
+                        (Some(*total),)
+                    }),
+                ))
+                .add_activator();
+
+            let (x_sender, x_receiver) = b.port(None).split();
+            let (y_sender, y_receiver) = b.port(None).split();
+            let mut add = b.node(TaskNode::checked(
+                (x_receiver.as_data_input(), y_receiver.as_data_input()),
+                (sum_sender.with_activator(total),),
+                StrictTask::new(|x: Option<i32>, y: Option<i32>| (Some(x.unwrap() + y.unwrap()),)),
+            ));
+            let root = (
+                x_sender.with_activator(add.add_activator()),
+                y_sender.with_activator(add.add_activator()),
+            );
+            (root, total_receiver)
+        });
+
+        let mut results = Vec::new();
+        for &(x, y) in &[(1, 2), (3, 4), (5, 6)] {
+            root.send_activate(&mut runtime, (Some(x), Some(y)));
+            runtime.execute(2).unwrap();
+            results.push(totals.recv().unwrap());
+        }
+        assert_eq!(results, vec![3, 10, 21]);
+    }
+
+    #[test]
+    fn try_task_node() {
+        use parallel::multiple_uses::*;
+        use std::num::ParseIntError;
+        use std::sync::{Arc, Mutex};
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let log = errors.clone();
+        let (root, values) = runtime.build_scope(|b| {
+            let (error_sender, error_receiver) = b.port(None).split();
+            let handler = b
+                .node(TaskNode {
+                    inputs: (error_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |error: Option<String>| {
+                        log.lock().unwrap().push(error.unwrap())
+                    }),
+                })
+                .add_activator();
+
+            let (input_sender, input_receiver) = b.port(None).split();
+            let (value_sender, value_receiver) = b.port(None).split();
+            let parse = b
+                .node(TryTaskNode {
+                    inputs: (input_receiver.as_data_input(),),
+                    outputs: (value_sender.as_data_output(),),
+                    errors: ErrorOutput::new(
+                        error_sender
+                            .with_activator(handler)
+                            .map(|error: ParseIntError| Some(error.to_string())),
+                    ),
+                    task: StrictTask::new(|input: Option<&str>| {
+                        input.unwrap().parse::<i32>().map(|value| (Some(value),))
+                    }),
+                })
+                .add_activator();
+            (input_sender.with_activator(parse), value_receiver)
+        });
+
+        let mut results = Vec::new();
+        for &input in &["1", "two", "3"] {
+            root.send_activate(&mut runtime, Some(input));
+            runtime.execute(2).unwrap();
+            results.push(values.recv());
+        }
+
+        assert_eq!(results, vec![Some(1), None, Some(3)]);
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec!["invalid digit found in string".to_string()]
+        );
+    }
+
+    #[test]
+    fn offload_task() {
+        use parallel::single_use::*;
+        use std::sync::mpsc;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        // A fake accelerator squaring its jobs on its own thread.
+        let (queue, jobs) = mpsc::channel::<(i32, Completion<Option<i32>>)>();
+        let accelerator = thread::spawn(move || {
+            for (job, done) in jobs {
+                done.complete(Some(job * job))
+            }
+        });
+        let queue = Mutex::new(queue);
+        let kernel = OffloadTask::new(move |job: Option<i32>, done| {
+            queue.lock().unwrap().send((job.unwrap(), done)).unwrap()
+        });
+
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+        let inputs: Vec<_> = (0..4)
+            .map(|_| {
+                let results = results.clone();
+                runtime.build_scope(|b| {
+                    let (sink_sender, sink_receiver) = b.port(None).split();
+                    let sink = b
+                        .node(TaskNode {
+                            inputs: (sink_receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |x: Option<i32>| {
+                                results.lock().unwrap().push(x.unwrap())
+                            }),
+                        })
+                        .add_activator();
+
+                    let (sender, receiver) = b.port(None).split();
+                    let offload = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (sink_sender.with_activator(sink),),
+                            task: kernel.clone(),
+                        })
+                        .add_activator();
+                    sender.with_activator(offload)
+                })
+            })
+            .collect();
+        for (i, input) in inputs.into_iter().enumerate() {
+            input.send_activate_once(&mut runtime, Some(i as i32));
+        }
+
+        // The execution waits for the pending completions.
+        runtime.execute(2).unwrap();
+        let mut results = results.lock().unwrap().clone();
+        results.sort();
+        assert_eq!(results, vec![0, 1, 4, 9]);
+
+        drop(kernel);
+        accelerator.join().unwrap();
+    }
+
+    #[test]
+    fn panic_isolation() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        let executed = Arc::new(AtomicUsize::new(0));
+        {
+            use parallel::single_use::*;
+
+            let mut runtime = Toexec::new();
+            let activators = runtime.build_scope(|b| {
+                let bad = b
+                    .node_named(
+                        "bad",
+                        TaskNode {
+                            inputs: (),
+                            outputs: (),
+                            task: StrictTask::new(|| panic!("boom")),
+                        },
+                    )
+                    .add_activator();
+                let executed = executed.clone();
+                let good = b
+                    .node(TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(move || {
+                            executed.fetch_add(1, SeqCst);
+                        }),
+                    })
+                    .add_activator();
+                (bad, good)
+            });
+            activators.0.activate_once(&mut runtime);
+            let error = runtime.execute(2).unwrap_err();
+            assert_eq!(error.failures().len(), 1);
+            assert_eq!(error.failures()[0].label.as_deref(), Some("bad"));
+            assert_eq!(error.failures()[0].message, "boom");
+
+            // The runtime can still be used after a failure.
+            activators.1.activate_once(&mut runtime);
+            assert!(runtime.execute(2).is_ok());
+            assert_eq!(executed.load(SeqCst), 1);
+        }
+
+        {
+            use parallel::multiple_uses::*;
+
+            let mut runtime = Toexec::new();
+            let bad = runtime.build_scope(|b| {
+                b.node_named(
+                    "bad",
+                    TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| panic!("boom {}", 42)),
+                    },
+                )
+                .add_activator()
+            });
+            bad.activate(&mut runtime);
+            let report = runtime.execute(2).unwrap_err().to_string();
+            assert!(report.starts_with("1 nodes panicked: `bad` panicked on worker"));
+            assert!(report.ends_with(": boom 42;"));
+        }
+    }
+
+    #[test]
+    fn quiescence_groups() {
+        use parallel::quiescence::Quiescence;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::{Arc, Mutex};
+
+        /// Count its executions, then activate the next node of the chain, if any.
+        struct Step<A> {
+            count: Arc<AtomicUsize>,
+            next: Option<A>,
+        }
+
+        impl<S, A: ActivatorOnce<S>> NodeOnce<S> for Step<A> {
+            fn execute_once(self, scheduler: &mut S) {
+                self.count.fetch_add(1, SeqCst);
+                if let Some(next) = self.next {
+                    next.activate_once(scheduler)
+                }
+            }
+        }
+
+        impl<S> NodeMut<S> for Step<()> {
+            fn execute_mut(&mut self, _scheduler: &mut S) {
+                self.count.fetch_add(1, SeqCst);
+            }
+        }
+
+        // The callback sees all the nodes of the stage executed, including the ones activated by
+        // other nodes of the stage.
+        let count = Arc::new(AtomicUsize::new(0));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        {
+            use parallel::single_use::*;
+
+            let stage = Quiescence::new({
+                let count = count.clone();
+                let seen = seen.clone();
+                move || seen.lock().unwrap().push(count.load(SeqCst))
+            });
+            let mut runtime = Toexec::new();
+            let activators = runtime.build_scope(|b| {
+                b.in_group(&stage, |b| {
+                    let mut activators = Vec::new();
+                    for _ in 0..10 {
+                        let mut next = None;
+                        for _ in 0..5 {
+                            let step = Step {
+                                count: count.clone(),
+                                next: next.take(),
+                            };
+                            next = Some(b.node(step).add_activator());
+                        }
+                        activators.extend(next);
+                    }
+                    activators
+                })
+            });
+            for activator in activators {
+                activator.activate_once(&mut runtime);
+            }
+            runtime.execute(4).unwrap();
+            assert_eq!(stage.in_flight(), 0);
+        }
+        assert_eq!(*seen.lock().unwrap(), vec![50]);
+
+        // Reusable graphs quiesce once per execution.
+        let count = Arc::new(AtomicUsize::new(0));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        {
+            use parallel::multiple_uses::*;
+
+            let stage = Quiescence::new({
+                let count = count.clone();
+                let seen = seen.clone();
+                move || seen.lock().unwrap().push(count.load(SeqCst))
+            });
+            let mut runtime = Toexec::new();
+            let activators = runtime.build_scope(|b| {
+                b.in_group(&stage, |b| {
+                    (0..20)
+                        .map(|_| {
+                            b.node(Step::<()> {
+                                count: count.clone(),
+                                next: None,
+                            })
+                            .add_activator()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            });
+            for _ in 0..2 {
+                for activator in &activators {
+                    activator.activate(&mut runtime);
+                }
+                runtime.execute(3).unwrap();
+            }
+        }
+        assert_eq!(*seen.lock().unwrap(), vec![20, 40]);
+    }
+
+    #[test]
+    fn coalescing_source() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+        use wasm::single_use::*;
+
+        let sums = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let edges: Vec<_> = (0..3)
+            .map(|_| {
+                let sums = sums.clone();
+                runtime.build_scope(|b| {
+                    let (sender, receiver) = b.port(None).split();
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |sum: Option<u32>| {
+                                sums.lock().unwrap().push(sum.unwrap())
+                            }),
+                        })
+                        .add_activator();
+                    sender.with_activator(activator)
+                })
+            })
+            .collect();
+        let edges = Arc::new(Mutex::new(edges.into_iter()));
+
+        // A burst within the window is injected once.
+        let source = CoalescingSource::new(Duration::from_secs(60), |x, y| x + y, {
+            let edges = edges.clone();
+            move || edges.lock().unwrap().next()
+        })
+        .start(&runtime);
+        for i in 1..=10 {
+            source.push(i);
+        }
+        source.close();
+        runtime.execute();
+        assert_eq!(*sums.lock().unwrap(), vec![55]);
+
+        // Events separated by more than the window are injected separately.
+        let source = CoalescingSource::new(Duration::from_millis(1), |x, y| x + y, move || {
+            edges.lock().unwrap().next()
+        })
+        .start(&runtime);
+        source.push(1);
+        thread::sleep(Duration::from_millis(50));
+        source.push(2);
+        source.close();
+        runtime.execute();
+        assert_eq!(*sums.lock().unwrap(), vec![55, 1, 2]);
+    }
+
+    #[test]
+    fn reset_reusable_graph() {
+        use parallel::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let pairs = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+        let (mut x_edge, mut y_edge) = runtime.build_scope(|b| {
+            let pairs = pairs.clone();
+            let (x_sender, x_receiver) = b.port_buffered(2).split();
+            let (y_sender, y_receiver) = b.port(None).split();
+            let mut pair = b.node(TaskNode {
+                inputs: (x_receiver.as_data_input(), y_receiver.as_data_input()),
+                outputs: (),
+                task: StrictTask::new(move |x: Option<i32>, y: Option<i32>| {
+                    pairs.lock().unwrap().push((x, y))
+                }),
+            });
+            (
+                x_sender.with_activator(pair.add_activator()),
+                y_sender.with_activator(pair.add_activator()),
+            )
+        });
+
+        // An incomplete batch leaves the node waiting for `y`, and `x` queued in its port.
+        x_edge.send_activate_mut(&mut runtime, Some(1));
+        runtime.execute(2).unwrap();
+        assert!(pairs.lock().unwrap().is_empty());
+
+        runtime.reset();
+
+        // The next batch is not mixed up with the stale one.
+        y_edge.send_activate_mut(&mut runtime, Some(2));
+        runtime.execute(2).unwrap();
+        assert!(pairs.lock().unwrap().is_empty());
+        x_edge.send_activate_mut(&mut runtime, Some(3));
+        runtime.execute(2).unwrap();
+        assert_eq!(*pairs.lock().unwrap(), vec![(Some(3), Some(2))]);
+    }
+
+    #[test]
+    fn shutdown_sequence() {
+        use sequential::single_use::*;
+        use std::cell::RefCell;
+        use std::time::Duration;
+
+        /// A sink which never finishes flushing.
+        struct Stuck;
+
+        impl Teardown for Stuck {
+            fn stop(&mut self) {}
+
+            fn is_drained(&mut self) -> bool {
+                false
+            }
+        }
+
+        let inspector = Inspector::new();
+        let mut runtime = Toexec::new();
+        let (sink, middle, source) = runtime.build_scope(|b| {
+            b.inspect(&inspector);
+            let mut id = |label: &'static str, target: Option<NodeId>| {
+                let node = TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                };
+                let mut builder = b.node_named(label, node).never_start();
+                if let Some(target) = target {
+                    builder = builder.activates(target);
+                }
+                builder.id().unwrap()
+            };
+            let sink = id("sink", None);
+            let middle = id("middle", Some(sink));
+            let source = id("source", Some(middle));
+            (sink, middle, source)
+        });
+        assert_eq!(inspector.topological_order(), vec![source, middle, sink]);
+
+        let log = RefCell::new(Vec::new());
+        let mut shutdown = ShutdownSequence::new(&inspector).with_timeout(Duration::from_millis(5));
+        shutdown.on_shutdown(sink, || log.borrow_mut().push("sink"));
+        shutdown.on_shutdown(sink, Stuck);
+        shutdown.on_shutdown(source, || log.borrow_mut().push("source"));
+        shutdown.on_shutdown(middle, || log.borrow_mut().push("middle"));
+
+        let error = shutdown.run().unwrap_err();
+        assert_eq!(*log.borrow(), vec!["source", "middle", "sink"]);
+        assert_eq!(
+            error.nodes,
+            vec![UndrainedNode {
+                id: sink,
+                label: Some("sink".to_string()),
+            }]
+        );
+        assert_eq!(error.to_string(), "1 nodes did not drain in time: `sink` (#0)");
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn determinism_check() {
+        use parallel::determinism::*;
+        use std::sync::{Arc, Mutex};
+
+        // A sum over a fan-out computes the same result whatever the scheduling.
+        let runs = DeterminismCheck::new()
+            .check(|runtime| {
+                let total = Arc::new(Mutex::new(0));
+                let roots = runtime.build_scope(|b| {
+                    (0..20)
+                        .map(|i| {
+                            let total = total.clone();
+                            b.node_named(
+                                format!("add {}", i),
+                                TaskNode {
+                                    inputs: (),
+                                    outputs: (),
+                                    task: StrictTask::new(move || *total.lock().unwrap() += i),
+                                },
+                            )
+                            .add_activator()
+                        })
+                        .collect::<Vec<_>>()
+                });
+                for root in roots {
+                    root.activate_once(runtime);
+                }
+                move || *total.lock().unwrap()
+            })
+            .unwrap();
+        assert_eq!(runs, 16);
+
+        /// Record the number of workers, and activate another node if there are several.
+        struct Workers<A> {
+            seen: Arc<Mutex<Vec<usize>>>,
+            parallel: A,
+        }
+
+        impl<S: HasWorkerIndex, A: ActivatorOnce<S>> NodeOnce<S> for Workers<A> {
+            fn execute_once(self, scheduler: &mut S) {
+                let workers = scheduler.worker_count();
+                self.seen.lock().unwrap().push(workers);
+                if workers > 1 {
+                    self.parallel.activate_once(scheduler)
+                }
+            }
+        }
+
+        let divergence = DeterminismCheck::new()
+            .workers(vec![1, 2])
+            .check(|runtime| {
+                let seen = Arc::new(Mutex::new(Vec::new()));
+                let root = runtime.build_scope(|b| {
+                    let mut parallel = b.node_named(
+                        "parallel",
+                        TaskNode {
+                            inputs: (),
+                            outputs: (),
+                            task: StrictTask::new(|| ()),
+                        },
+                    );
+                    let workers = Workers {
+                        seen: seen.clone(),
+                        parallel: parallel.add_activator(),
+                    };
+                    drop(parallel);
+                    b.node_named("workers", workers).add_activator()
+                });
+                root.activate_once(runtime);
+                move || seen.lock().unwrap().clone()
+            })
+            .unwrap_err();
+        assert_eq!((divergence.expected.workers, divergence.actual.workers), (1, 2));
+        assert_eq!(
+            (divergence.expected.outputs.clone(), divergence.actual.outputs.clone()),
+            (vec![1], vec![2])
+        );
+        assert_eq!(divergence.node.as_deref(), Some("parallel"));
+        assert!(divergence
+            .to_string()
+            .starts_with("outputs differ between 1 workers (seed 0) and 2 workers (seed 0)"));
+    }
+
+    #[test]
+    fn iterator_source() {
+        use sequential::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+        let root = runtime.build_scope(|b| {
+            let lines = lines.clone();
+            let (sender, receiver) = b.port(None).split();
+            let sink = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: CallbackSink::new(move |line: Option<&str>| {
+                        lines.lock().unwrap().push(line.unwrap().to_uppercase())
+                    }),
+                })
+                .add_activator();
+
+            let text = "first\nsecond\nthird";
+            let mut source = b.node(IteratorSource::new(
+                text.lines().map(Some),
+                sender.with_activator(sink),
+            ));
+            // The source and the caller share the activator, as for the `loop_node` above.
+            let next = Arc::new(source.add_activator());
+            source.borrow_mut().set_activator(next.clone());
+            next
+        });
+
+        // A single activation pumps the whole iterator.
+        root.activate(&mut runtime);
+        runtime.execute(1);
+        assert_eq!(*lines.lock().unwrap(), vec!["FIRST", "SECOND", "THIRD"]);
+    }
+
+    #[test]
+    fn signal_history() {
+        use sequential::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let history = SignalHistory::new(3);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+        let mut root = runtime.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let seen = seen.clone();
+            let sink = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: CallbackSink::new(move |x: Option<i32>| {
+                        seen.lock().unwrap().push(x.unwrap())
+                    }),
+                })
+                .add_activator();
+            history.output(sender.with_activator(sink))
+        });
+
+        for x in 1..6 {
+            root.send_activate_mut(&mut runtime, Some(x));
+            runtime.execute(1);
+        }
+
+        assert_eq!(*seen.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(history.instants(), 5);
+        assert_eq!(
+            history.history(),
+            vec![(2, Some(3)), (3, Some(4)), (4, Some(5))]
+        );
+        assert_eq!(history.last_n(2), vec![(3, Some(4)), (4, Some(5))]);
+        assert_eq!(history.last(), Some((4, Some(5))));
+    }
+
+    #[test]
+    fn line_file_io() {
+        use components::io::*;
+        use sequential::multiple_uses::*;
+        use std::fs;
+        use std::sync::{Arc, Mutex};
+
+        let directory = std::env::temp_dir();
+        let input = directory.join(format!("line_file_io_{}.in", std::process::id()));
+        let output = directory.join(format!("line_file_io_{}.out", std::process::id()));
+        fs::write(&input, &b"alpha\r\nbeta\n\xff\ngamma"[..]).unwrap();
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+        let (root, write_errors) = runtime.build_scope(|b| {
+            let log = errors.clone();
+            let (error_sender, error_receiver) = b.port(None).split();
+            let handler = b
+                .node(TaskNode {
+                    inputs: (error_receiver.as_data_input(),),
+                    outputs: (),
+                    task: CallbackSink::new(move |error: Option<std::io::ErrorKind>| {
+                        log.lock().unwrap().push(error.unwrap())
+                    }),
+                })
+                .add_activator();
+
+            let (line_sender, line_receiver) = b.port(None).split();
+            let (write_error_sender, write_errors) = b.port(None).split();
+            let write = b
+                .node(TryTaskNode {
+                    inputs: (line_receiver.as_data_input(),),
+                    outputs: (),
+                    errors: ErrorOutput::new(
+                        write_error_sender
+                            .as_data_output()
+                            .map(|error: std::io::Error| Some(error.kind())),
+                    ),
+                    task: LineFileSink::create(&output).unwrap(),
+                })
+                .add_activator();
+            let mut read = b.node(TryTaskNode {
+                inputs: (),
+                outputs: (line_sender.with_activator(write),),
+                errors: ErrorOutput::new(
+                    error_sender
+                        .with_activator(handler)
+                        .map(|error: std::io::Error| Some(error.kind())),
+                ),
+                task: LineFileSource::open(&input).unwrap(),
+            });
+            (read.add_activator(), write_errors)
+        });
+
+        // One line per activation, including the invalid one and the end of the file.
+        for _ in 0..5 {
+            root.activate(&mut runtime);
+            runtime.execute(1);
+        }
+
+        let written = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+        assert_eq!(written, "alpha\nbeta\ngamma\n");
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![std::io::ErrorKind::InvalidData]
+        );
+        assert_eq!(write_errors.recv(), None);
+    }
+
+    #[test]
+    fn bridge_port() {
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        let answers = Arc::new(Mutex::new(Vec::new()));
+        let mut interactive = Toexec::new();
+        let mut batch = Toexec::new();
+
+        let answers_ref = answers.clone();
+        let answer = interactive.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: Option<i32>| {
+                        answers_ref.lock().unwrap().push(x.unwrap())
+                    }),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        let reply = BridgePort::new(interactive.injector_handle(), answer);
+
+        let question = batch.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (reply,),
+                    task: StrictTask::new(|x: Option<i32>| (x.map(|x| 2 * x),)),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        let ask = BridgePort::new(batch.injector_handle(), question);
+
+        let root = interactive.build_scope(|b| {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (ask,),
+                    task: StrictTask::new(|x: Option<i32>| (x.map(|x| x + 1),)),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        });
+        root.send_activate_once(&mut interactive, Some(20));
+
+        // Each execution returns once the bridge into its runtime was used and dropped.
+        thread::scope(|s| {
+            s.spawn(|| batch.execute(2));
+            interactive.execute(2).unwrap();
+        });
+        assert_eq!(*answers.lock().unwrap(), vec![42]);
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "readiness"))]
+    fn readiness_handle() {
+        use parallel::async_adapter::AsyncToexec;
+        use parallel::readiness::Readiness;
+        use std::thread;
+        use std::time::Duration;
+
+        let readiness = Readiness::new().unwrap();
+        let notifier = readiness.notifier();
+        assert!(!readiness.clear());
+
+        let mut runtime = AsyncToexec::new();
+        let (root, result) = runtime.build_scope(|b| {
+            let (input_sender, input_receiver) = b.port(None).split();
+            let (output_sender, output_receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (input_receiver.as_data_input(),),
+                    outputs: (notifier.output(output_sender.as_data_output()),),
+                    task: StrictTask::new(|x: Option<i32>| (x.map(|x| x * x),)),
+                })
+                .add_activator();
+            (input_sender.with_activator(activator), output_receiver)
+        });
+        root.send_activate_once(&mut *runtime, Some(7));
+
+        // Wait for the descriptor as an event loop would, until the execution has completed.
+        let mut execution = runtime.execute(2);
+        let mut wakeups = 0;
+        let runtime = loop {
+            if let Some((runtime, result)) = readiness.poll(&mut execution) {
+                assert!(result.is_ok());
+                break runtime;
+            }
+            while !readiness.clear() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            wakeups += 1;
+        };
+
+        assert!(wakeups <= 2);
+        assert_eq!(result.recv(), Some(49));
+        drop(runtime);
+        notifier.notify();
+        notifier.notify();
+        assert!(readiness.clear());
+        assert!(!readiness.clear());
+    }
+
+    #[test]
+    fn in_place_port() {
+        use parallel::multiple_uses::*;
+        use parallel::port::{InPlacePort, InPlaceRef};
+        use std::sync::{Arc, Mutex};
+
+        let front = vec![0; 1024];
+        let back = vec![0; 1024];
+        let buffers = [front.as_ptr() as usize, back.as_ptr() as usize];
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let seen_ref = seen.clone();
+        let root = runtime.build_scope(|b| {
+            let (ref_sender, ref_receiver) = b.port(None).split();
+            let sum = b
+                .node(TaskNode {
+                    inputs: (ref_receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |state: Option<InPlaceRef<Vec<i32>>>| {
+                        let state = state.unwrap();
+                        let state = state.lock();
+                        let total: i32 = state.iter().sum();
+                        seen_ref
+                            .lock()
+                            .unwrap()
+                            .push((state.as_ptr() as usize, total));
+                    }),
+                })
+                .add_activator();
+
+            let (state_sender, state_receiver) = InPlacePort::new(front, back).split();
+            let double = b
+                .node(TaskNode {
+                    inputs: (state_receiver.as_data_input(),),
+                    outputs: (ref_sender.with_activator(sum),),
+                    task: StrictTask::new(|state: InPlaceRef<Vec<i32>>| {
+                        for x in state.lock().iter_mut() {
+                            *x *= 2;
+                        }
+                        (Some(state),)
+                    }),
+                })
+                .add_activator();
+
+            let writer = state_sender.clone();
+            let (step_sender, step_receiver) = b.port(None).split();
+            let fill = b
+                .node(TaskNode {
+                    inputs: (step_receiver.as_data_input(),),
+                    outputs: (state_sender.with_activator(double),),
+                    task: StrictTask::new(move |step: Option<i32>| {
+                        for x in writer.back().iter_mut() {
+                            *x = step.unwrap();
+                        }
+                        ((),)
+                    }),
+                })
+                .add_activator();
+            step_sender.with_activator(fill)
+        });
+
+        for step in 1..4 {
+            root.send_activate(&mut runtime, Some(step));
+            runtime.execute(2).unwrap();
+        }
+
+        // The buffers are reused, alternately, instead of being moved or cloned.
+        let seen = seen.lock().unwrap();
+        assert_eq!(
+            *seen,
+            vec![
+                (buffers[1], 2 * 1024),
+                (buffers[0], 4 * 1024),
+                (buffers[1], 6 * 1024)
+            ]
+        );
+    }
+
+    #[test]
+    fn mpsc_port() {
+        use parallel::port::MpscPort;
+        use sequential::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let log = batches.clone();
+        let roots = runtime.build_scope(|b| {
+            let (sender, receiver) = MpscPort::new().split();
+            let logger = Arc::new(
+                b.node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |batch: Vec<i32>| log.lock().unwrap().push(batch)),
+                })
+                .add_activator(),
+            );
+
+            (0..3)
+                .map(|_| {
+                    let (input_sender, input_receiver) = b.port(None).split();
+                    let producer = b
+                        .node(TaskNode {
+                            inputs: (input_receiver.as_data_input(),),
+                            outputs: (sender.clone().with_batch_activator(logger.clone()),),
+                            task: StrictTask::new(|x: Option<i32>| (x.unwrap(),)),
+                        })
+                        .add_activator();
+                    input_sender.with_activator(producer)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // All the producers run before the logger, which is only scheduled once.
+        for (root, x) in roots.iter().zip(1..) {
+            root.send_activate(&mut runtime, Some(x));
+        }
+        runtime.execute(1);
+        roots[1].send_activate(&mut runtime, Some(4));
+        runtime.execute(1);
+
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1, 2, 3], vec![4]]);
+    }
+
+    #[test]
+    fn broadcast_port() {
+        use parallel::port::BroadcastPort;
+        use sequential::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let early = Arc::new(Mutex::new(Vec::new()));
+        let late = Arc::new(Mutex::new(Vec::new()));
+        let port = BroadcastPort::new(2);
+        let mut runtime = Toexec::new();
+
+        let log = early.clone();
+        let root = runtime.build_scope(|b| {
+            let receiver = port.subscribe();
+            let id = receiver.id();
+            let monitor = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |values: Vec<i32>| log.lock().unwrap().push(values)),
+                })
+                .add_activator();
+            port.set_activator(id, monitor);
+
+            let (sender, receiver) = b.port(None).split();
+            let producer = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (port.sender(),),
+                    task: StrictTask::new(|x: Option<i32>| (x.unwrap(),)),
+                })
+                .add_activator();
+            sender.with_activator(producer)
+        });
+
+        for x in 1..4 {
+            root.send_activate(&mut runtime, Some(x));
+            runtime.execute(1);
+        }
+
+        // A subgraph built later first observes the values still held by the port.
+        let log = late.clone();
+        runtime.build_scope(|b| {
+            let receiver = port.subscribe();
+            let id = receiver.id();
+            let monitor = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |values: Vec<i32>| log.lock().unwrap().push(values)),
+                })
+                .add_activator();
+            port.set_activator(id, monitor);
+        });
+        root.send_activate(&mut runtime, Some(4));
+        runtime.execute(1);
+
+        assert_eq!(
+            *early.lock().unwrap(),
+            vec![vec![1], vec![2], vec![3], vec![4]]
+        );
+        assert_eq!(*late.lock().unwrap(), vec![vec![3, 4]]);
+        assert_eq!(port.subscribers(), 2);
+    }
+
+    #[test]
+    fn admission_control() {
+        use parallel::admission::{Admission, AdmissionContext};
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+        runtime.set_admission_policy(|context: &AdmissionContext| {
+            match context.label.as_deref() {
+                Some("denied") => Admission::Reject("not allowed".to_string()),
+                Some("later") if context.deferrals == 0 => Admission::Defer,
+                _ => Admission::Accept,
+            }
+        });
+
+        let roots: Vec<_> = runtime.build_scope(|b| {
+            ["accepted", "later", "denied"]
+                .iter()
+                .map(|&label| {
+                    let executed = executed.clone();
+                    b.node_named(
+                        label,
+                        TaskNode {
+                            inputs: (),
+                            outputs: (),
+                            task: StrictTask::new(move || executed.lock().unwrap().push(label)),
+                        },
+                    )
+                    .add_activator()
+                })
+                .collect()
+        });
+        for root in roots {
+            root.activate_once(&mut runtime);
+        }
+        runtime.execute(2).unwrap();
+
+        assert_eq!(*executed.lock().unwrap(), vec!["accepted"]);
+        assert_eq!(runtime.deferred(), 1);
+        let rejected = runtime.take_rejected();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].to_string(), "`denied` was rejected: not allowed");
+        assert!(runtime.take_rejected().is_empty());
+
+        assert_eq!(runtime.release_deferred(), 1);
+        runtime.execute(2).unwrap();
+        assert_eq!(*executed.lock().unwrap(), vec!["accepted", "later"]);
+        assert_eq!(runtime.deferred(), 0);
+    }
+
+    #[test]
+    fn watch_port() {
+        use parallel::port::WatchPort;
+        use sequential::multiple_uses::*;
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let (sender, receiver) = WatchPort::new(0).split();
+        let watcher = receiver.clone();
+        assert_eq!(watcher.latest(), (0, 0));
+        assert!(!watcher.has_changed(0));
+
+        let log = seen.clone();
+        let root = runtime.build_scope(|b| {
+            let consumer = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (),
+                    task: StrictTask::new(move |x: i32| log.lock().unwrap().push(x)),
+                })
+                .add_activator();
+            sender.clone().with_activator(consumer)
+        });
+
+        // Intermediate values are skipped: the consumer only sees the latest one.
+        sender.send(1);
+        sender.send(2);
+        root.send_activate(&mut runtime, 3);
+        runtime.execute(1);
+
+        assert_eq!(*seen.lock().unwrap(), vec![3]);
+        assert!(watcher.has_changed(0));
+        assert_eq!(watcher.latest(), (3, 3));
+        assert!(!watcher.has_changed(3));
+        assert_eq!(watcher.recv(), 3);
+        assert_eq!(watcher.version(), 3);
+    }
+
+    #[test]
+    fn node_environment() {
+        use sequential::single_use::*;
+        use std::hint::black_box;
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Toexec::new();
+
+        let (enter, exit) = (events.clone(), events.clone());
+        let environment = (
+            Hooks::new(
+                move || enter.lock().unwrap().push("enter"),
+                move |()| exit.lock().unwrap().push("exit"),
+            ),
+            // SAFETY: the node only does floating-point arithmetic through `black_box`, which the
+            // compiler can't evaluate assuming the default rounding mode.
+            unsafe { FloatMode::new().rounding(Rounding::Up) },
+        );
+        let third = || black_box(1.0f64) / black_box(3.0);
+        let log = events.clone();
+        let (roots, up, down) = runtime.build_scope(|b| {
+            let (up_sender, up) = b.port(None).split();
+            let (down_sender, down) = b.port(None).split();
+            let roots = vec![
+                b.node(InEnvironment::new(
+                    TaskNode {
+                        inputs: (),
+                        outputs: (up_sender.as_data_output(),),
+                        task: StrictTask::new(move || {
+                            log.lock().unwrap().push("run");
+                            (Some(third()),)
+                        }),
+                    },
+                    environment,
+                ))
+                .add_activator(),
+                b.node(InEnvironment::new(
+                    TaskNode {
+                        inputs: (),
+                        outputs: (down_sender.as_data_output(),),
+                        task: StrictTask::new(move || (Some(third()),)),
+                    },
+                    // SAFETY: as above.
+                    unsafe { FloatMode::new().rounding(Rounding::Down) },
+                ))
+                .add_activator(),
+            ];
+            (roots, up, down)
+        });
+        for root in roots {
+            root.activate_once(&mut runtime);
+        }
+        runtime.execute(1);
+
+        assert_eq!(*events.lock().unwrap(), vec!["enter", "run", "exit"]);
+        let (up, down) = (up.recv().unwrap(), down.recv().unwrap());
+        if cfg!(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")) {
+            assert!(up > down);
+        }
+        // The default rounding mode is restored once the nodes are executed.
+        let nearest = third();
+        assert!(down <= nearest && nearest <= up);
+        assert_eq!(nearest, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn thread_priority_restore() {
+        use std::thread;
+
+        // Nice values are per-thread on Linux: use a dedicated thread to leave the others alone.
+        thread::spawn(|| {
+            let before = match ThreadPriority::current() {
+                Some(nice) if nice < 19 => nice,
+                _ => return,
+            };
+            let lower = ThreadPriority::new(before + 1);
+            let saved = lower.enter();
+            // The priority is only lowered if it can be restored.
+            let during = ThreadPriority::current().unwrap();
+            assert!((during == before + 1 && saved == Some(before)) || during == before);
+            lower.exit(saved);
+            assert_eq!(ThreadPriority::current(), Some(before));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn generic_library() {
+        use common::builder::ScopedGraphBuilder;
+        use common::port::NodeInput;
+        use std::sync::{Arc, Mutex};
+
+        // A library task and graph fragment, written against `api::generic` only.
+        struct Scale(i32);
+
+        impl<S, I, O> TaskOnce<(I,), (O,), S> for Scale
+        where
+            I: InputFor<S, Option<i32>>,
+            O: EdgeFor<S, Option<i32>>,
+        {
+            fn run_once(mut self, scheduler: &mut S, inputs: (I,), outputs: (O,)) {
+                self.run_mut(scheduler, inputs, outputs)
+            }
+        }
+
+        impl<S, I, O> TaskMut<(I,), (O,), S> for Scale
+        where
+            I: InputFor<S, Option<i32>>,
+            O: EdgeFor<S, Option<i32>>,
+        {
+            fn run_mut(&mut self, scheduler: &mut S, (input,): (I,), (output,): (O,)) {
+                let x = input.recv_activate_once(scheduler);
+                output.send_activate_once(scheduler, x.map(|x| x * self.0))
+            }
+        }
+
+        type ScaleNode<Spec, O> = TaskNode<(DataInput<DataReceiver<Spec, i32>>,), (O,), Scale>;
+
+        fn scale<'a, S, Spec, O>(
+            b: &mut ScopedGraphBuilder<'a, Spec>,
+            factor: i32,
+            output: O,
+        ) -> NodeInput<Spec::Activator, DataSender<Spec, i32>>
+        where
+            O: EdgeFor<S, Option<i32>> + 'a,
+            Spec: RuntimeFor<S> + DataPortFor<i32> + NodeSpec<ScaleNode<Spec, O>> + 'a,
+        {
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (output,),
+                    task: Scale(factor),
+                })
+                .add_activator();
+            sender.with_activator(activator)
+        }
+
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let sink = |results: Arc<Mutex<Vec<i32>>>| {
+            StrictTask::new(move |x: Option<i32>| results.lock().unwrap().push(x.unwrap()))
+        };
+
+        {
+            use sequential::single_use::*;
+
+            let mut runtime = Toexec::new();
+            let results = results.clone();
+            let root = runtime.build_scope(|b| {
+                let (sender, receiver) = b.port(None).split();
+                let activator = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (),
+                        task: sink(results),
+                    })
+                    .add_activator();
+                let tripled = scale(b, 3, sender.with_activator(activator));
+                scale(b, 2, tripled)
+            });
+            root.send_activate_once(&mut runtime, Some(1));
+            runtime.execute(1);
+        }
+
+        {
+            use parallel::multiple_uses::*;
+
+            let mut runtime = Toexec::new();
+            let results = results.clone();
+            let root = runtime.build_scope(|b| {
+                let (sender, receiver) = b.port(None).split();
+                let activator = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (),
+                        task: sink(results),
+                    })
+                    .add_activator();
+                scale::<RuntimeLoc, _, _>(b, 5, sender.with_activator(activator))
+            });
+            for x in 1..3 {
+                root.send_activate(&mut runtime, Some(x));
+                runtime.execute(2).unwrap();
+            }
+        }
+
+        assert_eq!(*results.lock().unwrap(), vec![6, 5, 10]);
+    }
+
+    #[test]
+    fn cycle_detection() {
+        use sequential::multiple_uses::*;
+        use std::panic::{self, AssertUnwindSafe};
+
+        let task = || TaskNode {
+            inputs: (),
+            outputs: (),
+            task: StrictTask::new(|| ()),
+        };
+
+        // Build `a -> b -> a`, with `b` delaying its values or not.
+        let build = |inspector: &Inspector, delayed: bool| {
+            let mut runtime = Toexec::new();
+            runtime.build_scope(|b| {
+                b.inspect(inspector);
+                let mut a = b.node_named("a", task()).never_start();
+                let mut middle = b
+                    .node_named("b", task())
+                    .never_start()
+                    .activates(a.id().unwrap());
+                if delayed {
+                    middle = middle.delayed();
+                }
+                a = a.activates(middle.id().unwrap());
+                drop(middle);
+                drop(a);
+            });
+        };
+
+        let inspector = Inspector::new();
+        build(&inspector, true);
+        assert!(inspector.is_delay(NodeId(1)));
+        assert_eq!(inspector.check_cycles(), Ok(()));
+
+        let inspector = Inspector::new();
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| build(&inspector, false)))
+            .unwrap_err();
+        let expected = "activation cycle without a delay: `a` (#0) -> `b` (#1) -> `a` (#0)";
+        assert_eq!(payload.downcast_ref::<String>().unwrap(), expected);
+        let error = inspector.check_cycles().unwrap_err();
+        assert_eq!(error.nodes.len(), 2);
+        assert_eq!(error.to_string(), expected);
+    }
+
+    #[test]
+    fn schedule_replay() {
+        use parallel::replay::{ReplayError, ScheduleLog};
+        use parallel::single_use::*;
+        use std::sync::{Arc, Mutex};
+
+        struct Spawn {
+            name: String,
+            depth: usize,
+            executed: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl NodeOnce<RuntimeLoc<'static>> for Spawn {
+            fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+                self.executed.lock().unwrap().push(self.name.clone());
+                if self.depth == 0 {
+                    return;
+                }
+                for i in 0..2 {
+                    let child = scheduler.build_scope(|b| {
+                        b.node(Spawn {
+                            name: format!("{}.{}", self.name, i),
+                            depth: self.depth - 1,
+                            executed: self.executed.clone(),
+                        })
+                        .add_activator()
+                    });
+                    child.activate_once(scheduler);
+                }
+            }
+        }
+
+        let run = |roots: usize, log: &ScheduleLog, replay: bool| {
+            let executed = Arc::new(Mutex::new(Vec::new()));
+            let mut runtime = Toexec::new();
+            let activators: Vec<_> = runtime.build_scope(|b| {
+                (0..roots)
+                    .map(|i| {
+                        b.node(Spawn {
+                            name: i.to_string(),
+                            depth: 3,
+                            executed: executed.clone(),
+                        })
+                        .add_activator()
+                    })
+                    .collect()
+            });
+            for activator in activators {
+                activator.activate_once(&mut runtime);
+            }
+            let result = if replay {
+                runtime.execute_replay(log)
+            } else {
+                runtime.record_schedule(log);
+                runtime.execute(4).unwrap();
+                Ok(())
+            };
+            let executed = executed.lock().unwrap().clone();
+            (result, executed)
+        };
+
+        let log = ScheduleLog::new();
+        let (_, recorded) = run(2, &log, false);
+        assert_eq!(recorded.len(), 30);
+        assert_eq!(log.len(), 30);
+
+        let (result, replayed) = run(2, &ScheduleLog::from_keys(log.keys()), true);
+        assert_eq!(result, Ok(()));
+        assert_eq!(replayed, recorded);
+
+        let (result, _) = run(3, &log, true);
+        assert_eq!(result, Err(ReplayError::Unfinished { remaining: 1 }));
+        let (result, replayed) = run(1, &log, true);
+        match result {
+            Err(ReplayError::Diverged { step, .. }) => assert_eq!(step, replayed.len()),
+            _ => panic!("The replay did not diverge: {:?}", result),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn group_timings() {
+        use parallel::single_use::*;
+        use parallel::trace::GroupTimings;
+        use std::thread;
+        use std::time::Duration;
+
+        let timings = GroupTimings::new(2);
+        let mut runtime = Toexec::new();
+        runtime.set_trace_hook(timings.clone());
+        let roots: Vec<_> = runtime.build_scope(|b| {
+            let mut roots: Vec<_> = b.namespace("dsp", |b| {
+                (0..4)
+                    .map(|i| {
+                        b.node_named(
+                            format!("filter{}", i),
+                            TaskNode {
+                                inputs: (),
+                                outputs: (),
+                                task: StrictTask::new(|| thread::sleep(Duration::from_millis(5))),
+                            },
+                        )
+                        .add_activator()
+                    })
+                    .collect()
+            });
+            roots.push(
+                b.node_named(
+                    "io",
+                    TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| ()),
+                    },
+                )
+                .add_activator(),
+            );
+            roots.push(
+                b.node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                })
+                .add_activator(),
+            );
+            roots
+        });
+        for root in roots {
+            root.activate_once(&mut runtime);
+        }
+        runtime.execute(2).unwrap();
+
+        let report = timings.report();
+        assert_eq!(report.groups.len(), 2);
+        let dsp = report.group("dsp").unwrap();
+        assert_eq!(dsp.nodes, 4);
+        assert!(dsp.busy >= Duration::from_millis(20));
+        assert!(dsp.critical_path >= Duration::from_millis(10));
+        assert!(dsp.makespan >= dsp.critical_path);
+        assert!(dsp.finished >= dsp.started);
+        assert!(dsp.parallelism() > 0.0 && dsp.parallelism() <= 2.0 + 1e-6);
+        assert!(dsp.efficiency() > 0.0 && dsp.efficiency() <= 1.0);
+        assert_eq!(report.group("io").unwrap().nodes, 1);
+        assert!(report.to_string().contains("dsp"));
+
+        timings.reset();
+        assert!(timings.report().groups.is_empty());
+    }
+
+    #[test]
+    fn build_script_fuzzing() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        // Two nodes activating each other.
+        let cycle = [1, 1, 0, 1, 0, 2, 0, 2, 0, 3, 0, 1, 3, 0, 0];
+        match run_build_script(&cycle) {
+            Err(BuildError::Cycle(error)) => assert_eq!(
+                error.to_string(),
+                "activation cycle without a delay: #0 -> #1 -> #0"
+            ),
+            result => panic!("The cycle was not reported: {:?}", result),
+        }
+        // The same cycle through a delay, and without an inspector.
+        assert!(run_build_script(&[1, 1, 0, 1, 0, 6, 0, 2, 0, 2, 0, 3, 0, 1, 3, 0, 0]).is_ok());
+        assert!(run_build_script(&[0, 1, 0, 1, 0, 2, 0, 2, 0, 3, 0, 1, 3, 0, 0]).is_ok());
+
+        assert_eq!(
+            run_build_script(&[1, 1, 0, 8, 200, 0]),
+            Err(BuildError::UnknownNode {
+                node: NodeId(0),
+                target: NodeId(200),
+            })
+        );
+        assert_eq!(
+            run_build_script(&[0, 1, 0, 0, 2, 0]),
+            Ok(ScriptSummary {
+                nodes: 1,
+                activators: 1,
+                ports: 1,
+            })
+        );
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..500 {
+            let len = random() as usize % 256;
+            let script: Vec<u8> = (0..len).map(|_| random() as u8).collect();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| run_build_script(&script)));
+            assert!(result.is_ok(), "The script {:?} panicked.", script);
+        }
+    }
+
+    #[test]
+    fn graph_fuzzer() {
+        use testing::{GraphFuzzer, RandomDag};
+
+        let dag = RandomDag::generate(7, 30, 3);
+        assert_eq!(dag, RandomDag::generate(7, 30, 3));
+        assert!(dag.inputs.iter().enumerate().all(|(node, inputs)| {
+            inputs.len() <= 3 && inputs.iter().all(|&input| input < node)
+        }));
+        assert_eq!(
+            dag.run(2).into_iter().map(|values| values[0]).collect::<Vec<_>>(),
+            dag.expected()
+        );
+
+        let fuzzer = GraphFuzzer::new().dags(5).nodes(30).max_workers(3);
+        assert_eq!(fuzzer.check(), Ok(5 * 3 * 2));
+
+        // A runtime executing the last node twice.
+        let failure = fuzzer
+            .check_with(|dag, workers| {
+                let mut produced = dag.run(workers);
+                let last = produced.last_mut().unwrap();
+                last.push(last[0]);
+                produced
+            })
+            .unwrap_err();
+        assert_eq!((failure.node, failure.workers), (29, 1));
+        assert!(failure
+            .to_string()
+            .starts_with("node 29 of the DAG with seed 0 (30 nodes) was executed 2 times"));
+    }
+
+    #[test]
+    fn lazy_nodes() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::{Arc, Mutex};
+
+        let built = Arc::new(AtomicUsize::new(0));
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let lazy = |name: &'static str| {
+            let built = built.clone();
+            let executed = executed.clone();
+            move || {
+                built.fetch_add(1, SeqCst);
+                TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(move || executed.lock().unwrap().push(name)),
+                }
+            }
+        };
+
+        {
+            use sequential::multiple_uses::*;
+
+            let mut runtime = Toexec::new();
+            let (mut used, _unused) = runtime.build_scope(|b| {
+                let used = b.lazy_node(lazy("used")).add_activator();
+                let unused = b.lazy_node(lazy("unused")).add_activator();
+                (used, unused)
+            });
+            assert_eq!(built.load(SeqCst), 0);
+            for _ in 0..3 {
+                used.activate_mut(&mut runtime);
+                runtime.execute(1);
+            }
+            assert_eq!(built.load(SeqCst), 1);
+            assert_eq!(*executed.lock().unwrap(), vec!["used"; 3]);
+        }
+
+        {
+            use parallel::single_use::*;
+
+            let mut runtime = Toexec::new();
+            let roots: Vec<_> = runtime.build_scope(|b| {
+                (0..8)
+                    .map(|_| b.lazy_node(lazy("parallel")).add_activator())
+                    .collect()
+            });
+            for root in roots.into_iter().take(4) {
+                root.activate_once(&mut runtime);
+            }
+            runtime.execute(4).unwrap();
+            assert_eq!(built.load(SeqCst), 5);
+            assert_eq!(executed.lock().unwrap().len(), 7);
+        }
+
+        let mut node = LazyNode::new(|| 42);
+        assert!(!node.is_built());
+        assert_eq!(*node.force(), 42);
+        assert_eq!(node.get(), Some(&42));
+        assert_eq!(format!("{:?}", node), "LazyNode { state: \"built\" }");
+    }
+
+    #[test]
+    fn one_shot_run() {
+        let outputs = run_graph::<::sequential::single_use::Toexec, _>(1, |b, io| {
+            let (sum_sender, sum_receiver) = b.port(None).split();
+            b.expose_output("sum", sum_receiver);
+            let sink_activator = b
+                .node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                })
+                .add_activator();
+            let (sender, receiver) = b.port(None).split();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (receiver.as_data_input(),),
+                    outputs: (sum_sender.with_activator(sink_activator),),
+                    task: StrictTask::new(|x: Option<(i32, i32)>| (x.map(|(x, y)| x + y),)),
+                })
+                .add_activator();
+            io.input(sender.with_activator(activator), Some((3, 4)));
+        });
+        assert_eq!(outputs.read::<Option<i32>>("sum"), Ok(Some(7)));
+
+        // Reusable graphs are executed once, starting from the declared roots.
+        let outputs = run_graph::<::parallel::multiple_uses::Toexec, _>(4, |b, io| {
+            let (sender, receiver) = b.port(None).split();
+            b.expose_output("answer", receiver);
+            let sink_activator = b
+                .node(TaskNode {
+                    inputs: (),
+                    outputs: (),
+                    task: StrictTask::new(|| ()),
+                })
+                .add_activator();
+            let activator = b
+                .node(TaskNode {
+                    inputs: (),
+                    outputs: (sender.with_activator(sink_activator),),
+                    task: StrictTask::new(|| (Some(42),)),
+                })
+                .add_activator();
+            io.root(activator);
+        });
+        assert_eq!(outputs.read::<Option<i32>>("answer"), Ok(Some(42)));
+        assert_eq!(outputs.read::<Option<i32>>("answer"), Ok(None));
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn chrome_trace() {
+        use parallel::single_use::*;
+        use parallel::trace::TraceCollector;
+
+        let collector = TraceCollector::new(2);
+        let mut runtime = Toexec::new();
+        runtime.set_trace_hook(collector.clone());
+        let roots: Vec<_> = runtime.build_scope(|b| {
+            (0..8)
+                .map(|i| {
+                    let node = TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| ()),
+                    };
+                    match i {
+                        0 => b.node_named("say \"hi\"", node).add_activator(),
+                        1 => b.node(node).add_activator(),
+                        _ => b.node_named(format!("node{}", i), node).add_activator(),
+                    }
+                })
+                .collect()
+        });
+        for root in roots {
+            root.activate_once(&mut runtime);
+        }
+        runtime.execute(2).unwrap();
+
+        let spans = collector.spans();
+        assert_eq!(spans.len(), 8);
+        assert!(spans.iter().all(|span| span.worker < 2 && span.end >= span.start));
+        assert_eq!(spans.iter().filter(|span| span.label.is_none()).count(), 1);
+
+        let trace = collector.to_chrome_trace();
+        assert!(trace.starts_with("{\"traceEvents\":["));
+        assert!(trace.trim_end().ends_with("]}"));
+        assert_eq!(trace.matches("\"ph\":\"X\"").count(), 8);
+        assert_eq!(trace.matches("\"ph\":\"i\"").count(), collector.steals().len());
+        assert!(trace.contains("\"name\":\"say \\\"hi\\\"\""));
+        assert!(trace.contains("\"name\":\"<unnamed>\""));
+        assert!(trace.contains("\"args\":{\"name\":\"worker 1\"}"));
+
+        collector.reset();
+        assert!(collector.spans().is_empty());
+    }
+
+    #[test]
+    fn shared_tasks() {
+        use std::sync::{Arc, Mutex};
+
+        let interner = TaskInterner::new();
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = ::parallel::multiple_uses::Toexec::new();
+        let roots: Vec<_> = runtime.build_scope(|b| {
+            (0..100)
+                .map(|i| {
+                    let (sender, receiver) = b.port(None).split();
+                    let results = results.clone();
+                    let sink_activator = b
+                        .node(TaskNode {
+                            inputs: (receiver.as_data_input(),),
+                            outputs: (),
+                            task: StrictTask::new(move |x: Option<i32>| {
+                                results.lock().unwrap().push(x.unwrap())
+                            }),
+                        })
+                        .add_activator();
+                    let (input_sender, input_receiver) = b.port(None).split();
+                    let inputs = (input_receiver.as_data_input(),);
+                    let outputs = (sender.with_activator(sink_activator),);
+                    // The first nodes share the same stateless closure, and the other ones share
+                    // one of two scaling closures.
+                    let activator = if i < 50 {
+                        let task = interner.intern(|x: Option<i32>| (x.map(|x| x + 1),));
+                        b.node(TaskNode::shared(inputs, outputs, task))
+                            .add_activator()
+                    } else {
+                        let factor = i % 2 + 2;
+                        let task = interner.intern_keyed(factor, || {
+                            StrictTask::new(move |x: Option<i32>| (x.map(|x| x * factor),))
+                        });
+                        b.node(TaskNode::shared(inputs, outputs, task))
+                            .add_activator()
+                    };
+                    input_sender.with_activator(activator)
+                })
+                .collect()
+        });
+        assert_eq!(interner.len(), 3);
+
+        for root in roots {
+            root.send_activate_once(&mut runtime, Some(10));
+        }
+        runtime.execute(4).unwrap();
+        let results = results.lock().unwrap().clone();
+        assert_eq!(results.len(), 100);
+        assert_eq!(results.iter().filter(|&&x| x == 11).count(), 50);
+        assert_eq!(results.iter().filter(|&&x| x == 20).count(), 25);
+        assert_eq!(results.iter().filter(|&&x| x == 30).count(), 25);
+
+        // Type-erased tasks make nodes running different closures have the same type.
+        let mut runtime = ::parallel::single_use::Toexec::new();
+        let (roots, outputs): (Vec<_>, Vec<_>) = runtime.build_scope(|b| {
+            let tasks = vec![
+                SharedTask::new(StrictTask::new(|x: Option<i32>| (x.map(|x| -x),))).into_dyn(),
+                SharedTask::new(StrictTask::new(|x: Option<i32>| (x.map(|x| x * x),))).into_dyn(),
+            ];
+            let mut nodes = Vec::new();
+            for task in tasks {
+                let (sender, receiver) = b.port(None).split();
+                let (output_sender, output_receiver) = b.port(None).split();
+                let sink_activator = b
+                    .node(TaskNode {
+                        inputs: (),
+                        outputs: (),
+                        task: StrictTask::new(|| ()),
+                    })
+                    .add_activator();
+                nodes.push((
+                    TaskNode::shared(
+                        (receiver.as_data_input(),),
+                        (output_sender.with_activator(sink_activator),),
+                        task,
+                    ),
+                    sender,
+                    output_receiver,
+                ));
+            }
+            nodes
+                .into_iter()
+                .map(|(node, sender, receiver)| {
+                    (sender.with_activator(b.node(node).add_activator()), receiver)
+                })
+                .unzip()
+        });
+        for root in roots {
+            root.send_activate_once(&mut runtime, Some(7));
+        }
+        runtime.execute(2).unwrap();
+        let outputs: Vec<_> = outputs.iter().map(Receiver::recv).collect();
+        assert_eq!(outputs, vec![Some(-7), Some(49)]);
+    }
+
+    #[test]
+    fn bench_graphs() {
+        use bench_support::BenchDag;
+
+        let dags = vec![
+            BenchDag::fork_join(16, 100),
+            BenchDag::pipeline(4, 8, 100),
+            BenchDag::irregular_loop(8, 6, 200, 3),
+        ];
+        assert_eq!(dags[0].len(), 18);
+        assert_eq!(dags[1].len(), 32);
+        assert_eq!(dags[2], BenchDag::irregular_loop(8, 6, 200, 3));
+        for dag in &dags {
+            let expected = dag.expected();
+            for workers in 1..4 {
+                let mut runtime = ::parallel::single_use::Toexec::new();
+                assert_eq!(dag.run_single_use(&mut runtime, workers), expected);
+            }
+            let mut runtime = ::parallel::multiple_uses::Toexec::new();
+            let graph = dag.build_multiple_uses(&mut runtime);
+            for workers in 1..4 {
+                assert_eq!(graph.run(&mut runtime, workers), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn diagnostics_overhead() {
+        use std::mem::size_of;
+        use std::panic;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::{Arc, Mutex};
+
+        use bench_support::BenchDag;
+        use common::latency::{TimedInput, TimedOutput};
+        use common::port::CheckedPort;
+        use parallel::trace::{TraceHook, TraceSlot};
+
+        // Without their feature, the hooks take no space next to what they wrap, so that the hot
+        // path is the one of the bare runtime.
+        assert_eq!(size_of::<TraceSlot>() == 0, !cfg!(feature = "tracing"));
+        assert_eq!(
+            size_of::<TimedOutput<u64>>() == size_of::<u64>(),
+            !cfg!(feature = "stats")
+        );
+        assert_eq!(
+            size_of::<TimedInput<u64>>() == size_of::<u64>(),
+            !cfg!(feature = "stats")
+        );
+        assert_eq!(
+            size_of::<CheckedPort<Mutex<u64>>>() == size_of::<(Mutex<u64>, String)>(),
+            !cfg!(feature = "checked-ports")
+        );
+
+        struct Counter(Arc<AtomicUsize>);
+
+        impl TraceHook for Counter {
+            fn on_execute_start(&self, _worker: usize) {
+                self.0.fetch_add(1, SeqCst);
+            }
+        }
+
+        let dag = BenchDag::fork_join(8, 10);
+        let events = Arc::new(AtomicUsize::new(0));
+        let mut runtime = ::parallel::single_use::Toexec::new();
+        runtime.set_trace_hook(Counter(events.clone()));
+        assert_eq!(dag.run_single_use(&mut runtime, 2), dag.expected());
+        let mut runtime = ::parallel::multiple_uses::Toexec::new();
+        runtime.set_trace_hook(Counter(events.clone()));
+        let graph = dag.build_multiple_uses(&mut runtime);
+        assert_eq!(graph.run(&mut runtime, 2), dag.expected());
+        assert_eq!(events.load(SeqCst) > 0, cfg!(feature = "tracing"));
+
+        let port = CheckedPort::new("input", Mutex::new(0));
+        let written_twice = panic::catch_unwind(|| {
+            port.send(1);
+            port.send(2);
+        });
+        assert_eq!(written_twice.is_err(), cfg!(feature = "checked-ports"));
+    }
+
+    #[test]
+    fn zero_activator_nodes() {
+        use common::builder::BuildError;
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let source = |runs: &Arc<AtomicUsize>| {
+            let runs = runs.clone();
+            TaskNode {
+                inputs: (),
+                outputs: (),
+                task: StrictTask::new(move || {
+                    runs.fetch_add(1, SeqCst);
+                }),
+            }
+        };
+
+        // Started nodes are scheduled once the scope returns, and the others never run.
+        let mut runtime = ::parallel::single_use::Toexec::new();
+        runtime.build_scope(|b| {
+            b.node(source(&runs)).auto_start();
+            b.node(source(&runs)).never_start();
+        });
+        runtime.execute(2).unwrap();
+        assert_eq!(runs.load(SeqCst), 1);
+
+        let mut runtime = ::parallel::multiple_uses::Toexec::new();
+        runtime.build_scope(|b| {
+            b.node(source(&runs)).auto_start();
+        });
+        runtime.execute(2).unwrap();
+        runtime.execute(2).unwrap();
+        assert_eq!(runs.load(SeqCst), 2);
+
+        let mut runtime = ::sequential::single_use::Toexec::new();
+        let error = runtime
+            .try_build_scope(|b| {
+                b.node_named("orphan", source(&runs));
+            })
+            .unwrap_err();
+        assert_eq!(
+            error,
+            BuildError::NoActivator {
+                node: None,
+                label: Some("orphan".into()),
+            }
+        );
+
+        assert_eq!(
+            error.to_string(),
+            "node `orphan` has no activator and would never run; call `auto_start` or \
+             `never_start` on its builder"
+        );
+
+        // Other scopes accept nodes without activators, which never run.
+        let mut runtime = ::sequential::single_use::Toexec::new();
+        runtime.build_scope(|b| {
+            b.node(source(&runs));
+        });
+        runtime.execute(1);
+        assert_eq!(runs.load(SeqCst), 2);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::sync::atomic::AtomicBool;
+    use loom::thread;
+    use std::sync::Arc;
+
+    use custom::rc::RcInner;
+    use sync::Ordering::SeqCst;
+
+    /// A finalized node with `activators` activators, as left by `RcBuilder::finalize`.
+    fn armed(activators: usize) -> Arc<RcInner<()>> {
+        let inner = RcInner::new(());
+        for _ in 0..activators {
+            inner.add_activator();
+        }
+        inner.rearm();
+        inner.decrement_pending();
+        Arc::new(inner)
+    }
+
+    #[test]
+    fn loom_concurrent_activations() {
+        loom::model(|| {
+            let inner = armed(2);
+            let producer = inner.clone();
+            let thread = thread::spawn(move || producer.decrement_pending() == 0);
+            let scheduled = inner.decrement_pending() == 0;
+            assert!(
+                scheduled != thread.join().unwrap(),
+                "The node must be scheduled exactly once."
+            );
+        })
+    }
+
+    #[test]
+    fn loom_activation_during_execution() {
+        // The worker executing the node rearms it, sends its output and releases the handle, while
+        // the consumer of the output activates the node again for the next round.
+        loom::model(|| {
+            let inner = armed(1);
+            assert_eq!(inner.decrement_pending(), 0);
+            let sent = Arc::new(AtomicBool::new(false));
+            let (consumer, output) = (inner.clone(), sent.clone());
+            let thread = thread::spawn(move || {
+                while !output.load(SeqCst) {
+                    thread::yield_now();
+                }
+                consumer.decrement_pending() == 0
+            });
+            inner.rearm();
+            sent.store(true, SeqCst);
+            let released = inner.decrement_pending() == 0;
+            assert!(
+                released != thread.join().unwrap(),
+                "The node must be scheduled exactly once."
+            );
+        })
+    }
+
+    #[test]
+    #[should_panic(expected = "activated while not armed")]
+    fn loom_activation_before_rearm() {
+        // Activating the node before it is rearmed is a protocol error, which must be detected in
+        // some interleaving.  If `rearm` runs before the spawned decrement, the pending count goes
+        // 0 -> 2 -> 1 -> 0 and nothing panics; if the spawned decrement runs first, it finds the
+        // count at zero and panics with "activated while not armed".  That is the only panicking
+        // interleaving, so it is the panic loom reports.
+        loom::model(|| {
+            let inner = armed(1);
+            assert_eq!(inner.decrement_pending(), 0);
+            let consumer = inner.clone();
+            let thread = thread::spawn(move || {
+                consumer.decrement_pending();
+            });
+            inner.rearm();
+            inner.decrement_pending();
+            thread.join().unwrap();
+        })
+    }
+}
@@ -0,0 +1,95 @@
+//! Declarative graph construction.
+//!
+//! The `graph!` macro expands a description of the nodes and edges of a reusable graph into calls
+//! to the builder methods of the `common::dsl` module, which create the ports and activators:
+//!
+//! ```rust,ignore
+//! runtime.build_scope(|b| {
+//!     graph!(b => {
+//!         x: Source;
+//!         y: Source;
+//!         sum: Strict(|x: i32, y: i32| (x + y, x - y));
+//!         product: Strict(|x: i32, y: i32| (x * y,));
+//!         x -> sum.0;
+//!         y -> sum.1;
+//!         sum.0 -> product.0;
+//!         sum.1 -> product.1;
+//!         product.0 -> edge(sender.as_data_output().map(Some));
+//!     });
+//!     (x.0, y.0)
+//! })
+//! ```
+//!
+//! The statements are:
+//!
+//!  - `name: Strict(|arg: Type, ...| body);` declares a node executing a function of one to four
+//!    arguments and returning a tuple.  The arguments must be annotated with their types.
+//!  - `name: Source;` declares an entry point of the graph, bound to the pair returned by
+//!    `ScopedGraphBuilder::source`: its first field is the edge used to send values into the graph.
+//!  - `a.i -> b.j;` connects the `i`-th field of the result of `a` to the `j`-th argument of `b`.
+//!  - `x -> b.j;` connects the source `x` to the `j`-th argument of `b`.
+//!  - `a.i -> edge(expr);` connects the `i`-th field of the result of `a` to an arbitrary output
+//!    edge, e.g. to read it from the outside of the graph.
+//!
+//! The names are bound as variables in the calling scope, and nodes must be declared before they
+//! are connected.  Each argument must be connected exactly once, but results can be connected to
+//! any number of arguments.
+
+/// Build a reusable graph from a declarative description.  See the `macros` module.
+#[macro_export]
+macro_rules! graph {
+    ($b:ident => { $($body:tt)* }) => {
+        graph!(@stmts $b; $($body)*)
+    };
+
+    (@stmts $b:ident;) => {};
+    (@stmts $b:ident; $name:ident : Source; $($rest:tt)*) => {
+        #[allow(unused_mut)]
+        let mut $name = $b.source();
+        graph!(@stmts $b; $($rest)*)
+    };
+    (@stmts $b:ident;
+        $name:ident : Strict(|$($arg:ident : $ty:ty),*| $task:expr); $($rest:tt)*
+    ) => {
+        let mut $name = graph!(@task $b; |$($arg: $ty),*| $task; $($arg)*);
+        graph!(@stmts $b; $($rest)*)
+    };
+    (@stmts $b:ident; $src:ident . $i:tt -> edge($edge:expr); $($rest:tt)*) => {
+        $src.output().connect_edge_map($edge, |result| result.$i);
+        graph!(@stmts $b; $($rest)*)
+    };
+    (@stmts $b:ident; $src:ident . $i:tt -> $dst:ident . $j:tt; $($rest:tt)*) => {
+        $src.output().connect_map(graph!(@input $dst $j), |result| result.$i);
+        graph!(@stmts $b; $($rest)*)
+    };
+    (@stmts $b:ident; $src:ident -> $dst:ident . $j:tt; $($rest:tt)*) => {
+        $src.1.connect(graph!(@input $dst $j));
+        graph!(@stmts $b; $($rest)*)
+    };
+
+    (@task $b:ident; $task:expr; $a0:ident) => {
+        $b.task1($task)
+    };
+    (@task $b:ident; $task:expr; $a0:ident $a1:ident) => {
+        $b.task2($task)
+    };
+    (@task $b:ident; $task:expr; $a0:ident $a1:ident $a2:ident) => {
+        $b.task3($task)
+    };
+    (@task $b:ident; $task:expr; $a0:ident $a1:ident $a2:ident $a3:ident) => {
+        $b.task4($task)
+    };
+
+    (@input $dst:ident 0) => {
+        $dst.input0()
+    };
+    (@input $dst:ident 1) => {
+        $dst.input1()
+    };
+    (@input $dst:ident 2) => {
+        $dst.input2()
+    };
+    (@input $dst:ident 3) => {
+        $dst.input3()
+    };
+}
@@ -2,11 +2,15 @@
 //!
 //! This implements the activator traits on reference counted activators in order to allow sharing
 //! activators for nodes whose inputs can come from multiple source nodes.
+//!
+//! It also provides a `MergeActivator`, which implements merge semantics on top of the usual
+//! activators: the node is scheduled as soon as *any* of its inputs is activated, instead of once
+//! all of them were.
 
 use api::prelude::*;
 
 //use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 impl<S, A: Activator<S>> ActivatorOnce<S> for Arc<A> {
     fn activate_once(self, scheduler: &mut S) {
@@ -25,3 +29,166 @@ impl<S, A: Activator<S>> Activator<S> for Arc<A> {
         Activator::activate(&**self, scheduler)
     }
 }
+
+/// The state shared by the inputs of a `MergeActivator`.
+#[derive(Debug)]
+struct MergeInner<A> {
+    /// The activator of the merge node.
+    target: Mutex<Option<A>>,
+    /// The index of the input which fired, until it is read by the merge node.
+    fired: Mutex<Option<usize>>,
+    /// The number of inputs created.
+    inputs: Mutex<usize>,
+}
+
+/// A group of activators with merge semantics.
+///
+/// The merge node is built with a single regular activator, which is handed to the group with
+/// `arm`; the node's inputs then get an `AnyActivator` from `add_input` (or from the node builder's
+/// `add_any_activator`).  The first input to fire activates the node and records its index, which
+/// the node can read through the `fired` receiver.  Other inputs are ignored until the index was
+/// read: in reusable graphs, the node should read it on each execution to accept the next firing.
+///
+/// Merge activators are cheap to clone; clones share the same group.
+#[derive(Debug)]
+pub struct MergeActivator<A> {
+    inner: Arc<MergeInner<A>>,
+}
+
+impl<A> Clone for MergeActivator<A> {
+    fn clone(&self) -> Self {
+        MergeActivator {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A> Default for MergeActivator<A> {
+    fn default() -> Self {
+        MergeActivator::new()
+    }
+}
+
+impl<A> MergeActivator<A> {
+    /// Create an unarmed group without inputs.
+    pub fn new() -> Self {
+        MergeActivator {
+            inner: Arc::new(MergeInner {
+                target: Mutex::new(None),
+                fired: Mutex::new(None),
+                inputs: Mutex::new(0),
+            }),
+        }
+    }
+
+    /// Set the activator of the merge node.  The node should have no other activators.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the group was already armed.
+    pub fn arm(&self, target: A) {
+        let mut current = self.inner.target.lock().unwrap();
+        assert!(current.is_none(), "Merge activator was armed twice.");
+        *current = Some(target);
+    }
+
+    /// Whether the group was armed.
+    pub fn is_armed(&self) -> bool {
+        self.inner.target.lock().unwrap().is_some()
+    }
+
+    /// Create a new input.  Inputs are numbered from zero, in creation order.
+    pub fn add_input(&self) -> AnyActivator<A> {
+        let mut inputs = self.inner.inputs.lock().unwrap();
+        *inputs += 1;
+        AnyActivator {
+            inner: self.inner.clone(),
+            index: *inputs - 1,
+        }
+    }
+
+    /// A receiver for the index of the input which fired.  Receiving takes the index, allowing
+    /// the next firing.
+    pub fn fired(&self) -> MergeFired<A> {
+        MergeFired {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// An input of a `MergeActivator`.
+#[derive(Debug)]
+pub struct AnyActivator<A> {
+    inner: Arc<MergeInner<A>>,
+    index: usize,
+}
+
+impl<A> AnyActivator<A> {
+    /// The index of this input in its group.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Record the firing of this input, and return whether it is the first one.
+    fn fire(&self) -> bool {
+        let mut fired = self.inner.fired.lock().unwrap();
+        if fired.is_some() {
+            return false;
+        }
+        *fired = Some(self.index);
+        true
+    }
+}
+
+impl<S, A: ActivatorOnce<S>> ActivatorOnce<S> for AnyActivator<A> {
+    fn activate_once(self, scheduler: &mut S) {
+        if self.fire() {
+            let target = self.inner.target.lock().unwrap().take();
+            if let Some(target) = target {
+                target.activate_once(scheduler)
+            }
+        }
+    }
+}
+
+impl<S, A: Activator<S>> ActivatorMut<S> for AnyActivator<A> {
+    fn activate_mut(&mut self, scheduler: &mut S) {
+        Activator::activate(self, scheduler)
+    }
+}
+
+impl<S, A: Activator<S>> Activator<S> for AnyActivator<A> {
+    fn activate(&self, scheduler: &mut S) {
+        if self.fire() {
+            if let Some(ref target) = *self.inner.target.lock().unwrap() {
+                target.activate(scheduler)
+            }
+        }
+    }
+}
+
+/// The receiver for the index of the input of a `MergeActivator` which fired, if any.
+#[derive(Debug)]
+pub struct MergeFired<A> {
+    inner: Arc<MergeInner<A>>,
+}
+
+impl<A> ReceiverOnce for MergeFired<A> {
+    type Item = Option<usize>;
+
+    fn recv_once(self) -> Self::Item {
+        Receiver::recv(&self)
+    }
+}
+
+impl<A> ReceiverMut for MergeFired<A> {
+    fn recv_mut(&mut self) -> Self::Item {
+        Receiver::recv(self)
+    }
+}
+
+impl<A> Receiver for MergeFired<A> {
+    fn recv(&self) -> Self::Item {
+        self.inner.fired.lock().unwrap().take()
+    }
+}
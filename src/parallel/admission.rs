@@ -0,0 +1,174 @@
+//! Admission control for the nodes scheduled by the parallel single-use runtime.
+//!
+//! An `AdmissionPolicy` is installed on a runtime with `Toexec::set_admission_policy`.  Each node
+//! which becomes ready for execution, either because its last activator was activated or because
+//! it was scheduled directly (e.g. by a node spawning new work), is submitted to the policy before
+//! it is queued.  The policy is given an `AdmissionContext` describing the node, and decides to:
+//!
+//!  - `Accept` the node, which is queued as usual;
+//!  - `Defer` the node, which is put in a holding queue until `Toexec::release_deferred` is
+//!    called, at which point it is submitted to the policy again;
+//!  - `Reject` the node, which is dropped without being executed.  The rejection is reported to the
+//!    policy's `on_reject` handler as an `AdmissionError`, and kept until `Toexec::take_rejected`
+//!    is called.
+//!
+//! This allows rate limiting the work spawned dynamically by a graph, enforcing quotas, or
+//! refusing to run nodes which are not allowed by a security policy:
+//!
+//! ```rust,ignore
+//! let admitted = AtomicUsize::new(0);
+//! runtime.set_admission_policy(move |context: &AdmissionContext| {
+//!     if admitted.fetch_add(1, SeqCst) < 100 {
+//!         Admission::Accept
+//!     } else {
+//!         Admission::Defer
+//!     }
+//! });
+//! runtime.execute(4);
+//! // Allow 100 more nodes.
+//! ...
+//! runtime.release_deferred();
+//! runtime.execute(4);
+//! ```
+//!
+//! Policies are called on the worker threads (or on the calling thread for nodes scheduled from
+//! outside of the workers), and must not activate nodes themselves.  Note that the nodes waiting
+//! on a dropped node are never scheduled: rejecting a node usually leaves the rest of the graph
+//! stalled, which `execute` reports for runtimes created with `with_validation`.
+
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::sync::Mutex;
+
+use api::builder::Label;
+
+/// The description of a node submitted to an admission policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdmissionContext {
+    /// The label of the node, if any.  Nodes scheduled directly have no label.
+    pub label: Option<Label>,
+    /// The index of the worker which scheduled the node, or `None` if the node was scheduled from
+    /// outside of the workers.
+    pub worker: Option<usize>,
+    /// The worker the node is pinned to, if any.
+    pub affinity: Option<usize>,
+    /// The number of times the node was deferred before.
+    pub deferrals: usize,
+}
+
+/// The decision of an admission policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// Queue the node for execution.
+    Accept,
+    /// Hold the node until `Toexec::release_deferred` is called.
+    Defer,
+    /// Drop the node, for the given reason.
+    Reject(String),
+}
+
+/// A node rejected by an admission policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdmissionError {
+    /// The label of the node, if any.
+    pub label: Option<Label>,
+    /// The index of the worker which scheduled the node, if any.
+    pub worker: Option<usize>,
+    /// The reason given by the policy.
+    pub reason: String,
+}
+
+impl fmt::Display for AdmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.label {
+            Some(ref label) => write!(f, "`{}`", label)?,
+            None => write!(f, "<unnamed>")?,
+        }
+        write!(f, " was rejected: {}", self.reason)
+    }
+}
+
+impl Error for AdmissionError {}
+
+/// A policy deciding whether the scheduled nodes are executed.  See the module documentation.
+///
+/// Closures taking an `&AdmissionContext` and returning an `Admission` are policies.
+pub trait AdmissionPolicy: Send + Sync {
+    /// Decide what to do with the node described by `context`.
+    fn admit(&self, context: &AdmissionContext) -> Admission;
+
+    /// A node was rejected.  This is called before the error is recorded.
+    fn on_reject(&self, _error: &AdmissionError) {}
+}
+
+impl<F> AdmissionPolicy for F
+where
+    F: Fn(&AdmissionContext) -> Admission + Send + Sync,
+{
+    fn admit(&self, context: &AdmissionContext) -> Admission {
+        self(context)
+    }
+}
+
+/// The admission policy of a runtime along with its holding queue, shared with its workers.
+pub(crate) struct Admissions<H> {
+    policy: Box<dyn AdmissionPolicy>,
+    deferred: Mutex<Vec<(AdmissionContext, H)>>,
+    rejected: Mutex<Vec<AdmissionError>>,
+}
+
+impl<H> Admissions<H> {
+    /// Create a policy whose holding queue already contains `deferred`.
+    pub(crate) fn new(
+        policy: Box<dyn AdmissionPolicy>,
+        deferred: Vec<(AdmissionContext, H)>,
+    ) -> Self {
+        Admissions {
+            policy,
+            deferred: Mutex::new(deferred),
+            rejected: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Submit a node to the policy, and return it if it was accepted.
+    pub(crate) fn submit(&self, context: AdmissionContext, handle: H) -> Option<H> {
+        match self.policy.admit(&context) {
+            Admission::Accept => Some(handle),
+            Admission::Defer => {
+                self.defer(context, handle);
+                None
+            }
+            Admission::Reject(reason) => {
+                let error = AdmissionError {
+                    label: context.label,
+                    worker: context.worker,
+                    reason,
+                };
+                self.policy.on_reject(&error);
+                self.rejected.lock().unwrap().push(error);
+                None
+            }
+        }
+    }
+
+    fn defer(&self, mut context: AdmissionContext, handle: H) {
+        context.deferrals += 1;
+        self.deferred.lock().unwrap().push((context, handle));
+    }
+
+    /// Remove the deferred nodes from the holding queue.
+    pub(crate) fn take_deferred(&self) -> Vec<(AdmissionContext, H)> {
+        mem::take(&mut *self.deferred.lock().unwrap())
+    }
+
+    /// The number of nodes in the holding queue.
+    pub(crate) fn deferred(&self) -> usize {
+        self.deferred.lock().unwrap().len()
+    }
+
+    /// Remove the rejections recorded so far.
+    pub(crate) fn take_rejected(&self) -> Vec<AdmissionError> {
+        mem::take(&mut *self.rejected.lock().unwrap())
+    }
+}
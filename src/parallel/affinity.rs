@@ -0,0 +1,87 @@
+//! Pinning nodes to workers.
+//!
+//! Some nodes should run on a dedicated worker, e.g. for latency reasons like an audio sink, or
+//! must always run on the same thread because they own thread-local resources.  Nodes built with
+//! `ScopedNodeBuilder::with_affinity` are pinned to a worker: when scheduled, they are pushed to
+//! the worker's *mailbox* instead of a work-stealing deque, so that other workers can't steal
+//! them.  Workers execute the nodes of their mailbox before the other ready nodes.
+//!
+//! A worker only lives for one execution, so which thread runs a pinned node depends on how the
+//! runtime is executed.  `execute` spawns new threads on each call: a pinned node runs on the same
+//! thread within one execution, but on a different thread in the next one.  `execute_on` runs
+//! worker `i` on the `i`-th thread of the pool in every execution, so pinned nodes keep running on
+//! the same thread as long as the runtime is executed on the same pool; only this mode suits
+//! nodes owning thread-local resources.
+//!
+//! ```rust,ignore
+//! let sink = b.node(AudioSink::new(device)).with_affinity(0).add_activator();
+//! ```
+//!
+//! Worker indices are taken modulo the number of workers of the execution, so that graphs pinning
+//! nodes to worker 3 can still be executed with two workers.  Note that pinning nodes can leave
+//! the other workers idle, and that a busy pinned worker delays its pinned nodes even when the
+//! other workers are idle.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Mutex;
+
+/// The affinity of a node for a worker, if any.  This can be updated through shared references.
+#[derive(Debug)]
+pub(crate) struct Affinity(AtomicUsize);
+
+impl Affinity {
+    const NONE: usize = usize::MAX;
+
+    pub(crate) fn new() -> Self {
+        Affinity(AtomicUsize::new(Affinity::NONE))
+    }
+
+    pub(crate) fn set(&self, worker: usize) {
+        assert!(worker != Affinity::NONE, "Invalid worker index.");
+        self.0.store(worker, SeqCst)
+    }
+
+    pub(crate) fn get(&self) -> Option<usize> {
+        match self.0.load(SeqCst) {
+            Affinity::NONE => None,
+            worker => Some(worker),
+        }
+    }
+}
+
+/// The queues of the nodes pinned to each worker of an execution.
+pub(crate) struct Mailboxes<H> {
+    queues: Vec<Mutex<VecDeque<H>>>,
+    /// The number of nodes in all the queues, to skip locking them when they are empty.
+    len: AtomicUsize,
+}
+
+impl<H> Mailboxes<H> {
+    /// Create empty mailboxes for `workers` workers.
+    pub(crate) fn new(workers: usize) -> Self {
+        Mailboxes {
+            queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Push a node to the mailbox of worker `worker`, modulo the number of workers.
+    pub(crate) fn push(&self, worker: usize, handle: H) {
+        self.queues[worker % self.queues.len()]
+            .lock()
+            .unwrap()
+            .push_back(handle);
+        self.len.fetch_add(1, SeqCst);
+    }
+
+    /// Take the oldest node pinned to worker `worker`.
+    pub(crate) fn pop(&self, worker: usize) -> Option<H> {
+        if self.len.load(SeqCst) == 0 {
+            return None;
+        }
+        let handle = self.queues[worker].lock().unwrap().pop_front()?;
+        self.len.fetch_sub(1, SeqCst);
+        Some(handle)
+    }
+}
@@ -0,0 +1,210 @@
+//! Integration with Rust futures.
+//!
+//! This provides two adapters for using the parallel single-use runtime from asynchronous code:
+//!
+//!  - `AsyncToexec`, whose `execute` method returns a future resolving when the graph has
+//!    quiesced or the execution failed, instead of blocking the calling thread.  The execution itself happens on a
+//!    background thread, so this can be awaited from any executor.
+//!  - `AsyncTask`, a task computing its output with a future.  When the future is not ready, the
+//!    node is suspended without blocking its worker, and re-scheduled when the future's waker is
+//!    called.  Suspended nodes keep the graph from quiescing.
+//!
+//! Since futures and wakers must be `'static`, those adapters can only be used with
+//! `Toexec<'static>`.
+
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+use api::prelude::*;
+
+use parallel::failure::ExecutionError;
+use parallel::single_use::{External, RuntimeLoc, Toexec};
+
+/// A parallel single-use runtime whose executions can be awaited.
+///
+/// `AsyncToexec` dereferences to the underlying `Toexec`, which is used to build graphs.
+pub struct AsyncToexec {
+    runtime: Toexec<'static>,
+}
+
+impl AsyncToexec {
+    pub fn new() -> Self {
+        AsyncToexec {
+            runtime: Toexec::new(),
+        }
+    }
+
+    /// Execute the graph on `k` worker threads.  The returned future resolves to the runtime and
+    /// the result of the execution once the graph has quiesced, including the nodes suspended on
+    /// futures, or once the execution failed.  See `Toexec::execute`.
+    pub fn execute(self, k: usize) -> Execution {
+        let shared = Arc::new(Mutex::new(ExecutionState {
+            runtime: None,
+            waker: None,
+        }));
+
+        let state = shared.clone();
+        let mut runtime = self;
+        thread::spawn(move || {
+            let result = runtime.runtime.execute(k);
+
+            let mut state = state.lock().unwrap();
+            state.runtime = Some((runtime, result));
+            if let Some(waker) = state.waker.take() {
+                waker.wake()
+            }
+        });
+
+        Execution { shared }
+    }
+}
+
+impl Default for AsyncToexec {
+    fn default() -> Self {
+        AsyncToexec::new()
+    }
+}
+
+impl Deref for AsyncToexec {
+    type Target = Toexec<'static>;
+
+    fn deref(&self) -> &Toexec<'static> {
+        &self.runtime
+    }
+}
+
+impl DerefMut for AsyncToexec {
+    fn deref_mut(&mut self) -> &mut Toexec<'static> {
+        &mut self.runtime
+    }
+}
+
+struct ExecutionState {
+    /// The runtime and the result of the execution, once it has completed.
+    runtime: Option<(AsyncToexec, Result<(), ExecutionError>)>,
+    /// The waker of the task awaiting the execution.
+    waker: Option<Waker>,
+}
+
+/// A future resolving to the runtime and the result of an execution started by
+/// `AsyncToexec::execute`, once it has completed.
+pub struct Execution {
+    shared: Arc<Mutex<ExecutionState>>,
+}
+
+impl Future for Execution {
+    type Output = (AsyncToexec, Result<(), ExecutionError>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.shared.lock().unwrap();
+        match state.runtime.take() {
+            Some(runtime) => Poll::Ready(runtime),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A task computing its single output by awaiting on a future.
+///
+/// The wrapped function is called with the task's single input and returns the future.  The
+/// future is first polled on the worker executing the node; if it is not ready, the node is
+/// suspended and polled again on a worker each time it is woken up, until it completes.  The
+/// output is then sent and activated as usual.
+pub struct AsyncTask<F>(F);
+
+impl<F> AsyncTask<F> {
+    pub fn new(f: F) -> Self {
+        AsyncTask(f)
+    }
+}
+
+impl<I, O, F, Fut> TaskOnce<(I,), (O,), RuntimeLoc<'static>> for AsyncTask<F>
+where
+    I: InputEdgeOnce<RuntimeLoc<'static>>,
+    O: OutputEdgeOnce<RuntimeLoc<'static>, Item = Fut::Output> + Send + 'static,
+    F: FnOnce(I::Item) -> Fut,
+    Fut: Future + Send + 'static,
+{
+    fn run_once(self, scheduler: &mut RuntimeLoc<'static>, inputs: (I,), outputs: (O,)) {
+        let input = inputs.0.recv_activate_once(scheduler);
+        let suspended = Arc::new(Suspended {
+            state: Mutex::new(Some((Box::pin((self.0)(input)), outputs.0))),
+            notified: AtomicBool::new(false),
+            external: scheduler.external(),
+        });
+        suspended.external.start();
+        suspended.poll(scheduler);
+    }
+}
+
+/// The state of an `AsyncTask` waiting on its future.  This is also the future's waker.
+struct Suspended<Fut, O> {
+    /// The future and the output edge, until the future completes.
+    state: Mutex<Option<(Pin<Box<Fut>>, O)>>,
+    /// Whether the node was already re-scheduled since the last time it was polled.
+    notified: AtomicBool,
+    external: External<'static>,
+}
+
+impl<Fut, O> Suspended<Fut, O>
+where
+    Fut: Future + Send + 'static,
+    O: OutputEdgeOnce<RuntimeLoc<'static>, Item = Fut::Output> + Send + 'static,
+{
+    /// Poll the future, and send its output if it has completed.
+    fn poll(self: &Arc<Self>, scheduler: &mut RuntimeLoc<'static>) {
+        let mut state = self.state.lock().unwrap();
+        let (mut future, output) = match state.take() {
+            Some(pending) => pending,
+            // The future already completed; this is a stale wake-up.
+            None => return,
+        };
+
+        let waker = Waker::from(self.clone());
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(item) => {
+                drop(state);
+                output.send_activate_once(scheduler, item);
+                self.external.complete();
+            }
+            Poll::Pending => *state = Some((future, output)),
+        }
+    }
+}
+
+impl<Fut, O> Wake for Suspended<Fut, O>
+where
+    Fut: Future + Send + 'static,
+    O: OutputEdgeOnce<RuntimeLoc<'static>, Item = Fut::Output> + Send + 'static,
+{
+    fn wake(self: Arc<Self>) {
+        if !self.notified.swap(true, SeqCst) {
+            let external = self.external.clone();
+            external.inject(Box::new(Resume { suspended: self }))
+        }
+    }
+}
+
+/// A node polling a suspended `AsyncTask` again after it was woken up.
+struct Resume<Fut, O> {
+    suspended: Arc<Suspended<Fut, O>>,
+}
+
+impl<Fut, O> NodeOnce<RuntimeLoc<'static>> for Resume<Fut, O>
+where
+    Fut: Future + Send + 'static,
+    O: OutputEdgeOnce<RuntimeLoc<'static>, Item = Fut::Output> + Send + 'static,
+{
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+        self.suspended.notified.store(false, SeqCst);
+        self.suspended.poll(scheduler)
+    }
+}
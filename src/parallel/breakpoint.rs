@@ -0,0 +1,132 @@
+//! Breakpoints on named nodes of the parallel single-use runtime.
+//!
+//! A breakpoint is set on a node label with `Toexec::set_breakpoint`.  Whenever a node with that
+//! label is activated, and once more when it is executed, the runtime invokes the breakpoint's
+//! callback with a `BreakContext` describing the event: which worker hit the breakpoint, when, and
+//! how many of the node's activators were already activated.
+//!
+//! Breakpoints set with `BreakMode::Pause` additionally pause the scheduling until the callback
+//! returns: the other workers finish the node they are executing, then wait before starting a new
+//! one.  Pausing is cooperative, so that a long-running node can delay the pause.  Callbacks of
+//! pausing breakpoints can for instance wait for user input, or inspect ports shared with the
+//! graph while it is in a consistent state.
+//!
+//! Callbacks are invoked on the worker threads (or on the calling thread for activations from
+//! outside of the workers) and must not activate nodes themselves.
+
+use std::fmt;
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+use api::builder::Label;
+
+/// The events triggering a breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakEvent {
+    /// One of the node's activators was activated.
+    Activated,
+    /// The node is about to be executed.
+    Executed,
+}
+
+/// What happens to the other workers while a breakpoint's callback runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakMode {
+    /// The other workers keep executing nodes.
+    Continue,
+    /// The other workers don't start executing new nodes until the callback returns.
+    Pause,
+}
+
+/// The context passed to breakpoint callbacks.
+#[derive(Debug, Clone)]
+pub struct BreakContext {
+    /// The label of the node.
+    pub label: Label,
+    /// The event which triggered the breakpoint.
+    pub event: BreakEvent,
+    /// The index of the worker which triggered the breakpoint, or `None` if the node was activated
+    /// from outside of the workers.
+    pub worker: Option<usize>,
+    /// When the breakpoint was triggered.
+    pub instant: Instant,
+    /// The number of activations the node is still waiting for, after the current one.
+    pub pending: usize,
+    /// The number of activators of the node.
+    pub activators: usize,
+}
+
+impl BreakContext {
+    /// The number of activators which were activated, including the current one.  This is the
+    /// number of inputs which are present when the node uses one activator per input.
+    pub fn received(&self) -> usize {
+        self.activators - self.pending
+    }
+}
+
+impl fmt::Display for BreakContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let event = match self.event {
+            BreakEvent::Activated => "activated",
+            BreakEvent::Executed => "executed",
+        };
+        write!(f, "`{}` {} ", self.label, event)?;
+        match self.worker {
+            Some(worker) => write!(f, "on worker {}", worker)?,
+            None => write!(f, "from outside of the workers")?,
+        }
+        write!(f, " ({}/{} activations)", self.received(), self.activators)
+    }
+}
+
+/// A breakpoint callback.
+type Callback = Box<dyn Fn(&BreakContext) + Send + Sync>;
+
+/// The breakpoints of a runtime, shared with its workers.
+#[derive(Default)]
+pub(crate) struct Breakpoints {
+    points: Vec<(Label, BreakMode, Callback)>,
+    /// The number of pausing callbacks currently running.
+    paused: Mutex<usize>,
+    resumed: Condvar,
+}
+
+impl Breakpoints {
+    pub(crate) fn add(&mut self, label: Label, mode: BreakMode, callback: Callback) {
+        self.points.push((label, mode, callback))
+    }
+
+    /// Whether there is a breakpoint on `label`.
+    pub(crate) fn is_set(&self, label: &str) -> bool {
+        self.points.iter().any(|(point, _, _)| point == label)
+    }
+
+    /// Invoke the callbacks of the breakpoints on `context.label`.
+    pub(crate) fn hit(&self, context: &BreakContext) {
+        for &(ref label, mode, ref callback) in &self.points {
+            if *label != context.label {
+                continue;
+            }
+
+            if mode == BreakMode::Pause {
+                *self.paused.lock().unwrap() += 1;
+            }
+            callback(context);
+            if mode == BreakMode::Pause {
+                let mut paused = self.paused.lock().unwrap();
+                *paused -= 1;
+                if *paused == 0 {
+                    self.resumed.notify_all();
+                }
+            }
+        }
+    }
+
+    /// Wait until no pausing callbacks are running.
+    pub(crate) fn wait(&self) {
+        let mut paused = self.paused.lock().unwrap();
+        while *paused > 0 {
+            paused = self.resumed.wait(paused).unwrap();
+        }
+    }
+}
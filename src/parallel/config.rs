@@ -0,0 +1,204 @@
+//! Tuning of the scheduling of the parallel runtimes.
+//!
+//! By default, each worker executes its own nodes in the order they were scheduled, and idle
+//! workers steal one node at a time.  Recursive divide-and-conquer graphs usually run better with
+//! LIFO local queues, which execute the most recently scheduled node first while its data is still
+//! in the cache, and with batch stealing, which moves several nodes at once to an idle worker:
+//!
+//! ```rust,ignore
+//! runtime.set_config(RuntimeConfig::new().queue(QueueOrder::Lifo).steal_batch(8));
+//! runtime.execute(4);
+//! ```
+//!
+//! Thieves always take the oldest nodes of their victim's queue, whatever the order of the local
+//! queues.
+//!
+//! The configuration also controls how idle workers wait for work (see `termination::Backoff`):
+//! they spin for a few rounds, then park until work is published.  Graphs which spend most of
+//! their time waiting for external events can park for longer, which uses less CPU but makes
+//! timeouts, e.g. those of `RuntimeLoc::help_until_timeout`, less precise:
+//!
+//! ```rust,ignore
+//! runtime.set_config(RuntimeConfig::new().max_park(Duration::from_millis(50)));
+//! ```
+//!
+//! Finally, the configuration holds the number of workers used by `Toexec::execute_default`,
+//! which is the available parallelism of the machine unless set with `workers`.
+
+use crossbeam::deque::{self, Stealer, Worker};
+use std::thread;
+use std::time::Duration;
+
+/// The order in which a worker executes the nodes of its own queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueOrder {
+    /// Execute the oldest node first.
+    Fifo,
+    /// Execute the most recently scheduled node first.
+    Lifo,
+}
+
+/// The scheduling configuration of a parallel runtime.  See the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    queue: QueueOrder,
+    steal_batch: usize,
+    spin_limit: u32,
+    max_park: Duration,
+    /// The number of workers set with `workers`.  The available parallelism is only queried when
+    /// it is needed, since this is a system call.
+    workers: Option<usize>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig::new()
+    }
+}
+
+impl RuntimeConfig {
+    /// The default configuration: FIFO local queues, stealing one node at a time, idle workers
+    /// spinning ten times before parking for up to a millisecond at a time, and one worker per
+    /// available core.
+    pub fn new() -> Self {
+        RuntimeConfig {
+            queue: QueueOrder::Fifo,
+            steal_batch: 1,
+            spin_limit: 10,
+            max_park: Duration::from_millis(1),
+            workers: None,
+        }
+    }
+
+    /// Use `workers` workers in `Toexec::execute_default`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `workers` is zero.
+    pub fn workers(self, workers: usize) -> Self {
+        check_workers(workers);
+        RuntimeConfig {
+            workers: Some(workers),
+            ..self
+        }
+    }
+
+    /// Set the order of the workers' local queues.
+    pub fn queue(self, queue: QueueOrder) -> Self {
+        RuntimeConfig { queue, ..self }
+    }
+
+    /// Steal up to `batch` nodes at once from the same victim.  The first stolen node is executed,
+    /// and the others are pushed to the thief's queue.
+    ///
+    /// The deques don't report their length, so that batches are taken one node at a time and
+    /// stop early when the victim's queue is empty.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `batch` is zero.
+    pub fn steal_batch(self, batch: usize) -> Self {
+        assert!(batch > 0, "The steal batch must contain at least one node.");
+        RuntimeConfig {
+            steal_batch: batch,
+            ..self
+        }
+    }
+
+    /// Let idle workers look for work `spins` times, with increasing pauses, before parking.
+    pub fn spins(self, spins: u32) -> Self {
+        RuntimeConfig {
+            spin_limit: spins,
+            ..self
+        }
+    }
+
+    /// Let idle workers park for up to `timeout` at a time.  Workers are woken up as soon as work
+    /// is published, so the timeout mostly bounds how late deadlines are noticed.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `timeout` is zero.
+    pub fn max_park(self, timeout: Duration) -> Self {
+        assert!(
+            timeout > Duration::from_secs(0),
+            "The park timeout must not be zero."
+        );
+        RuntimeConfig {
+            max_park: timeout,
+            ..self
+        }
+    }
+
+    /// The order of the workers' local queues.
+    pub fn queue_order(&self) -> QueueOrder {
+        self.queue
+    }
+
+    /// The maximum number of nodes stolen at once.
+    pub fn batch_size(&self) -> usize {
+        self.steal_batch
+    }
+
+    /// The number of times idle workers look for work before parking.
+    pub fn spin_limit(&self) -> u32 {
+        self.spin_limit
+    }
+
+    /// The maximum duration idle workers park for at a time.
+    pub fn park_limit(&self) -> Duration {
+        self.max_park
+    }
+
+    /// The number of workers used by `Toexec::execute_default`.  Unless set with `workers`, this
+    /// queries the available parallelism of the machine on each call.
+    pub fn worker_count(&self) -> usize {
+        self.workers
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |workers| workers.get()))
+    }
+
+    /// Create the queue of a worker.
+    pub(crate) fn deque<T>(&self) -> (Worker<T>, Stealer<T>) {
+        match self.queue {
+            QueueOrder::Fifo => deque::fifo(),
+            QueueOrder::Lifo => deque::lifo(),
+        }
+    }
+
+    /// Steal a batch of nodes from the first of `stealers` with available nodes, pushing all but
+    /// the first one to `local`.  This returns the index of the victim in `stealers`, the first
+    /// node, and the total number of stolen nodes.
+    pub(crate) fn steal<T>(
+        &self,
+        stealers: &[Stealer<T>],
+        local: &Worker<T>,
+    ) -> Option<(usize, T, usize)> {
+        // A single worker has nobody to steal from.
+        if stealers.is_empty() {
+            return None;
+        }
+        let (i, node) = stealers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, stealer)| stealer.steal().map(|node| (i, node)))
+            .next()?;
+        let mut stolen = 1;
+        while stolen < self.steal_batch {
+            match stealers[i].steal() {
+                Some(node) => local.push(node),
+                None => break,
+            }
+            stolen += 1;
+        }
+        Some((i, node, stolen))
+    }
+}
+
+/// Check the number of workers of an execution.
+///
+/// # Panics
+///
+/// This panics if `workers` is zero, since the nodes would never be executed.
+pub(crate) fn check_workers(workers: usize) {
+    assert!(workers > 0, "An execution needs at least one worker.");
+}
@@ -0,0 +1,303 @@
+//! A feedback control loop on top of the reusable runtime.
+//!
+//! This module provides a PID controller, setpoint signals and scaffolding for simulating the
+//! controlled system (the *plant*), wired together as a reusable graph executed once per sampling
+//! period:
+//!
+//! ```text
+//!   tick  +----------+  r  +-----+  u  +-------+  (u, y)
+//!   ----->| Setpoint |---->| PID |---->| Plant |--------> output
+//!         +----------+     +-----+     +-------+
+//!                           ^   |          |
+//!                   (state) +---+          | y
+//!                           ^              |
+//!                           +-- pre(y) <---+
+//! ```
+//!
+//! Each step is started by sending a `Tick` to the setpoint node, which samples the setpoint
+//! signal.  The PID controller compares it with the measurement from the *previous* step, read
+//! from a memory port (a pure data edge written by the plant and read during the next execution,
+//! i.e. a unit delay), and keeps its own state (integral and previous error) in a second memory
+//! port.  The plant, on the other hand, is a stateful task owning the simulated system.
+//!
+//! The loop is driven by the engine with a fixed, simulated time step: `ControlLoop::step` runs
+//! exactly one sampling period, as fast as possible.  Running against a real system instead would
+//! use a `TimerSource` (see `common::timer`) to start the steps in real time.
+
+use std::time::Duration;
+
+use api::prelude::*;
+use common::prelude::*;
+
+use parallel::multiple_uses::Toexec;
+use parallel::port::RcReceiver;
+use sync::Mutex;
+
+/// The gains of a PID controller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pid {
+    /// The proportional gain.
+    pub kp: f64,
+    /// The integral gain.
+    pub ki: f64,
+    /// The derivative gain.
+    pub kd: f64,
+}
+
+/// The state of a PID controller between two steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PidState {
+    /// The integral of the error since the start of the loop.
+    pub integral: f64,
+    /// The error at the previous step, if any.
+    pub previous_error: Option<f64>,
+}
+
+impl Pid {
+    /// Create a controller with the given gains.
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Pid { kp, ki, kd }
+    }
+
+    /// Compute the command for an error of `error` over a step of `dt` seconds, and the state for
+    /// the next step.  The derivative term is zero on the first step.
+    pub fn command(&self, state: PidState, error: f64, dt: f64) -> (f64, PidState) {
+        let integral = state.integral + error * dt;
+        let derivative = state
+            .previous_error
+            .map_or(0., |previous| (error - previous) / dt);
+        let command = self.kp * error + self.ki * integral + self.kd * derivative;
+
+        (
+            command,
+            PidState {
+                integral,
+                previous_error: Some(error),
+            },
+        )
+    }
+}
+
+/// A setpoint signal, sampled at each step of the loop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setpoint {
+    /// A constant setpoint.
+    Constant(f64),
+    /// A setpoint switching from `before` to `after` at step `at`.
+    Step { at: u64, before: f64, after: f64 },
+}
+
+impl Setpoint {
+    /// The value of the setpoint at the given tick.
+    pub fn sample(&self, tick: Tick) -> f64 {
+        match *self {
+            Setpoint::Constant(value) => value,
+            Setpoint::Step { at, before, after } => {
+                if tick.index < at {
+                    before
+                } else {
+                    after
+                }
+            }
+        }
+    }
+}
+
+/// A simulated system controlled by the loop.
+pub trait Plant {
+    /// The current output (measurement) of the system.
+    fn output(&self) -> f64;
+
+    /// Apply `command` for `dt` seconds and return the new output.
+    fn update(&mut self, command: f64, dt: f64) -> f64;
+}
+
+/// A first-order system `tau * y' = gain * u - y`, simulated with Euler steps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FirstOrderPlant {
+    /// The static gain.
+    pub gain: f64,
+    /// The time constant, in seconds.
+    pub time_constant: f64,
+    /// The current output.
+    pub output: f64,
+}
+
+impl FirstOrderPlant {
+    /// Create a system at rest.
+    pub fn new(gain: f64, time_constant: f64) -> Self {
+        FirstOrderPlant {
+            gain,
+            time_constant,
+            output: 0.,
+        }
+    }
+}
+
+impl Plant for FirstOrderPlant {
+    fn output(&self) -> f64 {
+        self.output
+    }
+
+    fn update(&mut self, command: f64, dt: f64) -> f64 {
+        self.output += dt * (self.gain * command - self.output) / self.time_constant;
+        self.output
+    }
+}
+
+/// The values observed during a step of the loop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// The tick which started the step.
+    pub tick: Tick,
+    /// The sampled setpoint.
+    pub setpoint: f64,
+    /// The command computed by the controller.
+    pub command: f64,
+    /// The output of the plant at the end of the step.
+    pub measurement: f64,
+}
+
+/// A control loop executing a PID controller and a simulated plant with a fixed time step.
+pub struct ControlLoop {
+    // The edges are declared before the runtime so that they are dropped first, and no activator
+    // outlives the runtime.
+    tick: ErasedOutput<'static, Toexec<'static>, Option<Tick>>,
+    output: RcReceiver<Mutex<Option<(f64, f64)>>>,
+    setpoint: Setpoint,
+    period: Duration,
+    next: u64,
+    workers: usize,
+    runtime: Toexec<'static>,
+}
+
+impl ControlLoop {
+    /// Build the loop's graph.  The loop samples `setpoint` and updates `plant` every `period` of
+    /// simulated time.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `period` is zero.
+    pub fn new<P>(pid: Pid, setpoint: Setpoint, plant: P, period: Duration) -> Self
+    where
+        P: Plant + Send + Sync + 'static,
+    {
+        assert!(
+            period > Duration::from_secs(0),
+            "The period must be positive."
+        );
+
+        let dt = period.as_secs_f64();
+        let mut runtime = Toexec::new();
+        let initial = plant.output();
+        let mut plant = plant;
+
+        let (tick, output) = runtime.build_scope(|b| {
+            let (output_sender, output_receiver) = b.port(None).split();
+            let (measurement_sender, measurement_receiver) = b.port(Some(initial)).split();
+            let (state_sender, state_receiver) = b.port(PidState::default()).split();
+
+            let (command_sender, command_receiver) = b.port(None).split();
+            let plant_activator = b
+                .node(TaskNode {
+                    inputs: (command_receiver.as_data_input(),),
+                    outputs: (
+                        measurement_sender.as_data_output(),
+                        output_sender.as_data_output(),
+                    ),
+                    task: StrictTask::new(move |command: Option<f64>| {
+                        let command = command.expect("Missing command.");
+                        let measurement = plant.update(command, dt);
+                        (Some(measurement), Some((command, measurement)))
+                    }),
+                })
+                .add_activator();
+
+            let (setpoint_sender, setpoint_receiver) = b.port(None).split();
+            let pid_activator = b
+                .node(TaskNode {
+                    inputs: (
+                        setpoint_receiver.as_data_input(),
+                        measurement_receiver.as_data_input(),
+                        state_receiver.as_data_input(),
+                    ),
+                    outputs: (
+                        command_sender.with_activator(plant_activator),
+                        state_sender.as_data_output(),
+                    ),
+                    task: StrictTask::new(
+                        move |setpoint: Option<f64>, measurement: Option<f64>, state: PidState| {
+                            let error = setpoint.expect("Missing setpoint.")
+                                - measurement.expect("Missing measurement.");
+                            let (command, state) = pid.command(state, error, dt);
+                            (Some(command), state)
+                        },
+                    ),
+                })
+                .add_activator();
+
+            let (tick_sender, tick_receiver) = b.port(None).split();
+            let setpoint_activator = b
+                .node(TaskNode {
+                    inputs: (tick_receiver.as_data_input(),),
+                    outputs: (setpoint_sender.with_activator(pid_activator),),
+                    task: StrictTask::new(move |tick: Option<Tick>| {
+                        (Some(setpoint.sample(tick.expect("Missing tick."))),)
+                    }),
+                })
+                .add_activator();
+
+            (
+                ErasedOutput::output(tick_sender.with_activator(setpoint_activator)),
+                output_receiver,
+            )
+        });
+
+        ControlLoop {
+            tick,
+            output,
+            setpoint,
+            period,
+            next: 0,
+            workers: 1,
+            runtime,
+        }
+    }
+
+    /// Execute the graph on `workers` threads.  The default is a single worker, since the loop's
+    /// nodes are executed one after the other anyways.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Run a single step of the loop.
+    pub fn step(&mut self) -> Sample {
+        let tick = Tick {
+            index: self.next,
+            elapsed: self.period * self.next as u32,
+        };
+        self.next += 1;
+
+        self.tick.send_activate_mut(&mut self.runtime, Some(tick));
+        if let Err(error) = self.runtime.execute(self.workers) {
+            panic!("{}", error)
+        }
+
+        let (command, measurement) = self
+            .output
+            .recv()
+            .expect("The plant was not executed during the step.");
+        Sample {
+            tick,
+            setpoint: self.setpoint.sample(tick),
+            command,
+            measurement,
+        }
+    }
+
+    /// Run `steps` steps of the loop and return their samples.
+    pub fn run(&mut self, steps: usize) -> Vec<Sample> {
+        (0..steps).map(|_| self.step()).collect()
+    }
+}
@@ -0,0 +1,294 @@
+//! Checking that graphs compute the same results whatever the scheduling.
+//!
+//! The results of a dataflow graph should only depend on its inputs, but a node reading a port
+//! which is written concurrently, or a task relying on a shared counter, silently makes them
+//! depend on the scheduling instead.  Such bugs usually go unnoticed until the graph is run on a
+//! machine with more cores.  A `DeterminismCheck` builds the same graph several times on the
+//! parallel single-use runtime, executes it with varying numbers of workers and scheduling seeds,
+//! and compares the outputs of each run with those of the first one:
+//!
+//! ```rust,ignore
+//! DeterminismCheck::new().assert(|runtime| {
+//!     let (root, result) = runtime.build_scope(|b| build_pipeline(b, &inputs));
+//!     root.activate_once(runtime);
+//!     move || result.recv()
+//! });
+//! ```
+//!
+//! The closure builds the graph and activates its roots, and returns a closure collecting the
+//! outputs once the graph has been executed.  The seed of a run selects the scheduling
+//! configuration (see the `parallel::config` module), and perturbs the interleaving of the
+//! workers by making them yield randomly before executing nodes.
+//!
+//! When the outputs of two runs differ, the returned `Divergence` holds the labelled nodes
+//! executed by each run (see `ScopedGraphBuilder::node_named`), along with the first node which
+//! was executed a different number of times, if any.
+//!
+//! The executed nodes are recorded with a trace hook, so that this module is only available with
+//! the `tracing` feature.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use parallel::config::{QueueOrder, RuntimeConfig};
+use parallel::single_use::Toexec;
+use parallel::trace::TraceHook;
+
+/// A labelled node executed during a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// The worker which executed the node.
+    pub worker: usize,
+    /// The label of the node.
+    pub label: String,
+}
+
+/// An execution of the graph under test.
+#[derive(Debug, Clone)]
+pub struct Run<T> {
+    /// The number of workers of the execution.
+    pub workers: usize,
+    /// The scheduling seed of the execution.
+    pub seed: u64,
+    /// The outputs collected after the execution.
+    pub outputs: T,
+    /// The labelled nodes executed, in order.
+    pub trace: Vec<TraceEvent>,
+}
+
+impl<T> fmt::Display for Run<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "trace with {} workers (seed {}):",
+            self.workers, self.seed
+        )?;
+        for event in &self.trace {
+            writeln!(f, "  worker {}: {}", event.worker, event.label)?;
+        }
+        Ok(())
+    }
+}
+
+/// Two runs of the same graph which produced different outputs.
+#[derive(Debug, Clone)]
+pub struct Divergence<T> {
+    /// The first run of the check.
+    pub expected: Run<T>,
+    /// The run whose outputs differ from the first one.
+    pub actual: Run<T>,
+    /// The label of the first node, in the order of the first run, which was executed a different
+    /// number of times by the two runs.  This is `None` if all the labelled nodes were executed the
+    /// same number of times, i.e. if the runs only differ by the values flowing through the graph.
+    pub node: Option<String>,
+}
+
+impl<T> Divergence<T> {
+    fn new(expected: Run<T>, actual: Run<T>) -> Self {
+        let expected_counts = execution_counts(&expected.trace);
+        let actual_counts = execution_counts(&actual.trace);
+        let node = expected
+            .trace
+            .iter()
+            .chain(&actual.trace)
+            .find(|event| {
+                let label = event.label.as_str();
+                expected_counts.get(label) != actual_counts.get(label)
+            })
+            .map(|event| event.label.clone());
+
+        Divergence {
+            expected,
+            actual,
+            node,
+        }
+    }
+}
+
+/// The number of executions of each labelled node of a trace.
+fn execution_counts(trace: &[TraceEvent]) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for event in trace {
+        *counts.entry(event.label.as_str()).or_insert(0) += 1;
+    }
+    counts
+}
+
+impl<T: fmt::Debug> fmt::Display for Divergence<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "outputs differ between {} workers (seed {}) and {} workers (seed {}): {:?} != {:?}",
+            self.expected.workers,
+            self.expected.seed,
+            self.actual.workers,
+            self.actual.seed,
+            self.expected.outputs,
+            self.actual.outputs
+        )?;
+        match self.node {
+            Some(ref label) => writeln!(f, "first diverging node: `{}`", label)?,
+            None => writeln!(
+                f,
+                "all labelled nodes were executed the same number of times"
+            )?,
+        }
+        write!(f, "{}{}", self.expected, self.actual)
+    }
+}
+
+impl<T: fmt::Debug> Error for Divergence<T> {}
+
+/// Runs a graph with varying numbers of workers and scheduling seeds, and compares its outputs.
+/// See the module documentation.
+#[derive(Debug, Clone)]
+pub struct DeterminismCheck {
+    workers: Vec<usize>,
+    seeds: Vec<u64>,
+}
+
+impl Default for DeterminismCheck {
+    fn default() -> Self {
+        DeterminismCheck::new()
+    }
+}
+
+impl DeterminismCheck {
+    /// A check running the graph with 1, 2, 4 and 8 workers, for the seeds 0 to 3.
+    pub fn new() -> Self {
+        DeterminismCheck {
+            workers: vec![1, 2, 4, 8],
+            seeds: (0..4).collect(),
+        }
+    }
+
+    /// Run the graph with each of the numbers of workers in `workers`.
+    pub fn workers<I: IntoIterator<Item = usize>>(mut self, workers: I) -> Self {
+        self.workers = workers.into_iter().collect();
+        self
+    }
+
+    /// Run the graph with each of the seeds in `seeds`, for each number of workers.
+    pub fn seeds<I: IntoIterator<Item = u64>>(mut self, seeds: I) -> Self {
+        self.seeds = seeds.into_iter().collect();
+        self
+    }
+
+    /// Build and run the graph for each number of workers and seed, stopping at the first run
+    /// whose outputs differ from those of the first run.  This returns the number of runs.
+    pub fn check<B, C, T>(&self, build: B) -> Result<usize, Box<Divergence<T>>>
+    where
+        B: Fn(&mut Toexec<'static>) -> C,
+        C: FnOnce() -> T,
+        T: PartialEq,
+    {
+        let mut expected: Option<Run<T>> = None;
+        let mut runs = 0;
+        for &seed in &self.seeds {
+            for &workers in &self.workers {
+                let run = run_once(&build, workers, seed);
+                runs += 1;
+                match expected {
+                    None => expected = Some(run),
+                    Some(ref first) if first.outputs == run.outputs => {}
+                    Some(_) => {
+                        let expected = expected.take().unwrap();
+                        return Err(Box::new(Divergence::new(expected, run)));
+                    }
+                }
+            }
+        }
+        Ok(runs)
+    }
+
+    /// Like `check`, but panic with the divergence, if any.
+    pub fn assert<B, C, T>(&self, build: B)
+    where
+        B: Fn(&mut Toexec<'static>) -> C,
+        C: FnOnce() -> T,
+        T: PartialEq + fmt::Debug,
+    {
+        if let Err(divergence) = self.check(build) {
+            panic!("Non-deterministic graph: {}", divergence)
+        }
+    }
+}
+
+/// Build and execute the graph once.
+fn run_once<B, C, T>(build: &B, workers: usize, seed: u64) -> Run<T>
+where
+    B: Fn(&mut Toexec<'static>) -> C,
+    C: FnOnce() -> T,
+{
+    let recorder = Recorder::new(seed);
+    let mut runtime = Toexec::new();
+    runtime.set_config(
+        RuntimeConfig::new()
+            .queue(if seed & 1 == 0 {
+                QueueOrder::Fifo
+            } else {
+                QueueOrder::Lifo
+            })
+            .steal_batch(1 + (seed / 2 % 4) as usize),
+    );
+    runtime.set_trace_hook(recorder.clone());
+
+    let collect = build(&mut runtime);
+    if let Err(error) = runtime.execute(workers) {
+        panic!("{}", error)
+    }
+    let outputs = collect();
+    let trace = mem::take(&mut *recorder.trace.lock().unwrap());
+
+    Run {
+        workers,
+        seed,
+        outputs,
+        trace,
+    }
+}
+
+/// A hook recording the labelled nodes, and perturbing the workers' interleaving.
+#[derive(Clone)]
+struct Recorder {
+    trace: Arc<Mutex<Vec<TraceEvent>>>,
+    /// The state of the xorshift generator deciding how long the workers yield.
+    state: Arc<Mutex<u64>>,
+}
+
+impl Recorder {
+    fn new(seed: u64) -> Self {
+        Recorder {
+            trace: Arc::new(Mutex::new(Vec::new())),
+            // The state of a xorshift generator must not be zero.
+            state: Arc::new(Mutex::new(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)),
+        }
+    }
+
+    fn next(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+}
+
+impl TraceHook for Recorder {
+    fn on_execute_start(&self, _worker: usize) {
+        for _ in 0..self.next() % 4 {
+            thread::yield_now()
+        }
+    }
+
+    fn on_execute_labeled(&self, worker: usize, label: &str) {
+        self.trace.lock().unwrap().push(TraceEvent {
+            worker,
+            label: label.to_string(),
+        })
+    }
+}
@@ -0,0 +1,140 @@
+//! Isolation of the nodes which panic during an execution.
+//!
+//! A panicking node would otherwise unwind through its worker: the other workers would wait
+//! forever for the node to complete, and the panic would only be reported once the whole thread
+//! scope aborts, without saying which node died.  Instead, the workers of both parallel runtimes
+//! catch the panics of the nodes they execute: the failing node is recorded along with the panic
+//! message, and the remaining work is cancelled as with `execute_until`.
+//!
+//! `Toexec::execute` returns the failures once all the workers have stopped, along with the other
+//! reasons an execution can fail, as an `ExecutionError`:
+//!
+//! ```rust,ignore
+//! if let Err(error) = runtime.execute(4) {
+//!     for failure in error.failures() {
+//!         eprintln!("{}", failure);
+//!     }
+//! }
+//! ```
+//!
+//! Nodes are identified by their label, if any (see `ScopedGraphBuilder::node_named`).  Note that a
+//! graph is left in an inconsistent state by a failure: nodes which were scheduled but not
+//! executed yet are dropped, and reusable nodes which panicked can't be executed again.
+
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::sync::Mutex;
+
+use api::builder::Label;
+use parallel::memory::MemoryLimitError;
+use parallel::validation::StalledGraphError;
+
+/// A node which panicked during an execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeFailure {
+    /// The label of the node, if any.
+    pub label: Option<Label>,
+    /// The index of the worker which was executing the node.
+    pub worker: usize,
+    /// The panic message, if it was a string.
+    pub message: String,
+}
+
+impl fmt::Display for NodeFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.label {
+            Some(ref label) => write!(f, "`{}`", label)?,
+            None => write!(f, "<unnamed>")?,
+        }
+        write!(f, " panicked on worker {}: {}", self.worker, self.message)
+    }
+}
+
+/// The error returned by the executions of the parallel runtimes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionError {
+    /// Some nodes panicked.  The failed nodes are listed in the order they panicked.
+    Panicked(Vec<NodeFailure>),
+    /// The memory usage went over the hard limit of the runtime's accountant.  See the
+    /// `parallel::memory` module.
+    MemoryLimit(MemoryLimitError),
+    /// Some nodes were waiting for activations once the graph quiesced, in a runtime created with
+    /// `with_validation`.  See the `parallel::validation` module.
+    Stalled(StalledGraphError),
+}
+
+impl ExecutionError {
+    /// The nodes which panicked, if any.
+    pub fn failures(&self) -> &[NodeFailure] {
+        match *self {
+            ExecutionError::Panicked(ref failures) => failures,
+            _ => &[],
+        }
+    }
+}
+
+impl From<MemoryLimitError> for ExecutionError {
+    fn from(error: MemoryLimitError) -> Self {
+        ExecutionError::MemoryLimit(error)
+    }
+}
+
+impl From<StalledGraphError> for ExecutionError {
+    fn from(error: StalledGraphError) -> Self {
+        ExecutionError::Stalled(error)
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExecutionError::Panicked(ref failures) => {
+                write!(f, "{} nodes panicked:", failures.len())?;
+                for failure in failures {
+                    write!(f, " {};", failure)?;
+                }
+                Ok(())
+            }
+            ExecutionError::MemoryLimit(ref error) => error.fmt(f),
+            ExecutionError::Stalled(ref error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for ExecutionError {}
+
+/// The failures of the current execution, shared by its workers.
+#[derive(Debug, Default)]
+pub(crate) struct Failures {
+    failures: Mutex<Vec<NodeFailure>>,
+}
+
+impl Failures {
+    /// Record that a node panicked with `payload`.
+    pub(crate) fn record(&self, label: Option<Label>, worker: usize, payload: &(dyn Any + Send)) {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "<non-string panic payload>".to_string(),
+            },
+        };
+        self.failures.lock().unwrap().push(NodeFailure {
+            label,
+            worker,
+            message,
+        })
+    }
+
+    /// Report the failures recorded since the last call, if any.
+    pub(crate) fn take(&self) -> Result<(), ExecutionError> {
+        let failures = mem::take(&mut *self.failures.lock().unwrap());
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(ExecutionError::Panicked(failures))
+        }
+    }
+}
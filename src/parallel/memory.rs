@@ -0,0 +1,222 @@
+//! Accounting of the memory used by a graph.
+//!
+//! Long-running reactive processes can grow without bound, e.g. when nodes keep creating nodes
+//! faster than they are executed, or when buffered ports fill up.  Runtimes created with
+//! `Toexec::with_accountant` charge the approximate size of each node and port they create to an
+//! `Accountant`, and release it when the node or port is dropped.  Sizes are approximate: they
+//! only cover the memory allocated by the runtime itself, and not e.g. the heap allocations owned
+//! by the nodes' data.  Nodes can account for such allocations with `Accountant::reserve`.
+//!
+//! The accountant can be configured with two limits:
+//!
+//!  - A soft limit, which invokes a callback when the memory usage goes over it.  The callback is
+//!    called again the next time the usage goes over the limit after having been below it, and is
+//!    typically used to apply backpressure or to drop low-priority work.
+//!  - A hard limit, above which `Toexec::execute` stops the workers and fails with a
+//!    `MemoryLimitError`.  Fallible reservations made with `Accountant::reserve` fail when they
+//!    would exceed the hard limit.
+//!
+//! ```rust,ignore
+//! let accountant = Accountant::new()
+//!     .with_soft_limit(64 << 20, |used| eprintln!("{} bytes used, throttling", used))
+//!     .with_hard_limit(256 << 20);
+//! let mut runtime = Toexec::with_accountant(accountant);
+//! ...
+//! runtime.execute(4)?;
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+
+/// A soft limit and its callback.
+struct SoftLimit {
+    limit: usize,
+    callback: Box<dyn Fn(usize) + Send + Sync>,
+    /// Whether the usage is over the limit, so that the callback is only called when crossing it.
+    exceeded: AtomicBool,
+}
+
+/// Tracks the memory used by the nodes and ports of a runtime.  See the module documentation.
+pub struct Accountant {
+    used: AtomicUsize,
+    soft_limit: Option<SoftLimit>,
+    hard_limit: Option<usize>,
+}
+
+impl Default for Accountant {
+    fn default() -> Self {
+        Accountant::new()
+    }
+}
+
+impl fmt::Debug for Accountant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Accountant")
+            .field("used", &self.used())
+            .field("soft_limit", &self.soft_limit())
+            .field("hard_limit", &self.hard_limit)
+            .finish()
+    }
+}
+
+impl Accountant {
+    /// Create an accountant without limits.
+    pub fn new() -> Self {
+        Accountant {
+            used: AtomicUsize::new(0),
+            soft_limit: None,
+            hard_limit: None,
+        }
+    }
+
+    /// Call `callback` with the memory usage, in bytes, when it goes over `limit`.
+    ///
+    /// The callback is called on the thread which created the node or port that crossed the
+    /// limit, typically a worker: it should return quickly, and must not create nodes itself.
+    pub fn with_soft_limit<F>(self, limit: usize, callback: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        Accountant {
+            soft_limit: Some(SoftLimit {
+                limit,
+                callback: Box::new(callback),
+                exceeded: AtomicBool::new(false),
+            }),
+            ..self
+        }
+    }
+
+    /// Fail the executions and reservations once the memory usage goes over `limit` bytes.
+    pub fn with_hard_limit(self, limit: usize) -> Self {
+        Accountant {
+            hard_limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// The memory currently used, in bytes.
+    pub fn used(&self) -> usize {
+        self.used.load(SeqCst)
+    }
+
+    /// The soft limit, if any.
+    pub fn soft_limit(&self) -> Option<usize> {
+        self.soft_limit.as_ref().map(|soft_limit| soft_limit.limit)
+    }
+
+    /// The hard limit, if any.
+    pub fn hard_limit(&self) -> Option<usize> {
+        self.hard_limit
+    }
+
+    /// Fail if the memory usage is over the hard limit.
+    pub fn check(&self) -> Result<(), MemoryLimitError> {
+        self.check_with(0)
+    }
+
+    /// Account for `bytes` more bytes, e.g. a buffer allocated by a node.  The bytes are released
+    /// when the returned reservation is dropped.
+    ///
+    /// This fails without reserving anything if the reservation would exceed the hard limit.
+    pub fn reserve(this: &Arc<Self>, bytes: usize) -> Result<Reservation, MemoryLimitError> {
+        this.check_with(bytes)?;
+        Ok(Accountant::charge(this, bytes))
+    }
+
+    /// Account for `bytes` more bytes, even if this exceeds the hard limit.
+    pub(crate) fn charge(this: &Arc<Self>, bytes: usize) -> Reservation {
+        let used = this.used.fetch_add(bytes, SeqCst) + bytes;
+        if let Some(ref soft_limit) = this.soft_limit {
+            if used > soft_limit.limit && !soft_limit.exceeded.swap(true, SeqCst) {
+                (soft_limit.callback)(used)
+            }
+        }
+
+        Reservation {
+            accountant: this.clone(),
+            bytes,
+        }
+    }
+
+    fn check_with(&self, bytes: usize) -> Result<(), MemoryLimitError> {
+        let used = self.used();
+        match self.hard_limit {
+            Some(limit) if used + bytes > limit => Err(MemoryLimitError {
+                used,
+                requested: bytes,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn release(&self, bytes: usize) {
+        let used = self.used.fetch_sub(bytes, SeqCst) - bytes;
+        if let Some(ref soft_limit) = self.soft_limit {
+            if used <= soft_limit.limit {
+                soft_limit.exceeded.store(false, SeqCst)
+            }
+        }
+    }
+}
+
+/// Memory accounted for by an `Accountant`, released when dropped.
+pub struct Reservation {
+    accountant: Arc<Accountant>,
+    bytes: usize,
+}
+
+impl Reservation {
+    /// The number of bytes reserved.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl fmt::Debug for Reservation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Reservation")
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.accountant.release(self.bytes)
+    }
+}
+
+/// The error returned when the memory usage goes over the hard limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimitError {
+    /// The memory used, in bytes.
+    pub used: usize,
+    /// The number of bytes which were requested, or zero if the usage is already over the limit.
+    pub requested: usize,
+    /// The hard limit, in bytes.
+    pub limit: usize,
+}
+
+impl fmt::Display for MemoryLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.requested == 0 {
+            write!(
+                f,
+                "memory usage of {} bytes exceeds the limit of {} bytes",
+                self.used, self.limit
+            )
+        } else {
+            write!(
+                f,
+                "reserving {} bytes with {} bytes used would exceed the limit of {} bytes",
+                self.requested, self.used, self.limit
+            )
+        }
+    }
+}
+
+impl Error for MemoryLimitError {}
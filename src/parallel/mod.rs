@@ -4,6 +4,27 @@
 //! runtime in `single_use`, and a reusable runtime in `multiple_uses`.
 
 pub mod activator;
+pub mod admission;
+pub mod affinity;
+pub mod async_adapter;
+pub mod breakpoint;
+pub mod config;
+pub mod control;
+#[cfg(feature = "tracing")]
+pub mod determinism;
+pub mod failure;
+pub mod memory;
+pub mod pool;
+pub mod quiescence;
+#[cfg(all(unix, feature = "readiness"))]
+pub mod readiness;
+pub mod replay;
+pub mod reset;
 pub mod port;
+pub mod self_check;
 pub mod single_use;
+pub mod slice;
+pub mod termination;
+pub mod trace;
+pub mod validation;
 pub mod multiple_uses;
@@ -10,18 +10,35 @@
 //! use an [arena](https://docs.rs/typed-arena/1.4.1/typed_arena/struct.Arena.html) to store the
 //! data in a buffer, and may also be interested in using the `RefPort` from the `common` module
 //! instead of `RcPort`.
+//!
+//! In debug builds with the `diagnostics` feature, the runtime keeps track of the nodes built on
+//! it, and `Toexec::leak_detector` reports the ones which outlive it, so that such leaks are at
+//! least visible.
 
 use api::prelude::*;
 use common::prelude::*;
+use common::run::RunMode;
+use custom::rc::{self, RcInner};
 
 use crossbeam::deque;
-use std::marker::PhantomData;
-use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
-use std::sync::Arc;
-use std::sync::{Mutex, MutexGuard};
-use std::thread;
-
-use parallel::port::RcPort;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use parallel::affinity::{Affinity, Mailboxes};
+use parallel::quiescence::Quiescence;
+use parallel::reset::Rearmables;
+use parallel::config::{self, RuntimeConfig};
+use parallel::failure::{ExecutionError, Failures};
+use parallel::pool::{Queues, ThreadPool};
+use parallel::port::{ChannelPort, RcPort};
+use parallel::slice::{NodeKey, Slice, Topology};
+use parallel::termination::{Backoff, HelpError, Termination};
+use parallel::trace::{TraceHook, TraceSlot};
+use parallel::validation::{LeakDetector, Registry};
+use sync::{Arc, AtomicUsize, Mutex, Ordering::SeqCst, Weak};
 
 
 /* 
@@ -59,72 +76,55 @@ impl CompteurAtomic for Compteur {
 
 
 
-/// The inner structure for the iterator.  This include a handle to the node, as well as a pending
-/// count with interior mutability.  Contrary to the `single_use` implementation, we also use
-/// interior mutability for the handle because we need to be able to access the handle while there
-/// are still other references to the inner structure (hence the reusable nature).
+/// The information the parallel runtime keeps about a reusable node, in addition to the pending
+/// count and label shared by all the runtimes.  See `custom::rc`.
 #[derive(Debug)]
-struct RcActivatorInner<H: ?Sized> {
-    /// The pending count.  If 0, there is currently a builder or a handle pointing to the node.
-    pending: AtomicUsize,
-    /// The initial pending count to reset to.  This includes the handle.
-    initial: AtomicUsize,
-    /// The underlying node to schedule.
-    handle: Mutex<H>,
+pub struct NodeExtra {
+    /// The identity of the node, unique among all the nodes created by the process.
+    key: NodeKey,
+    /// The worker the node is pinned to, if any.
+    affinity: Affinity,
+    /// The quiescence groups the node belongs to.
+    groups: Mutex<Vec<Quiescence>>,
+    /// The dependencies recorded by a runtime created with `with_slicing`, which forget the node
+    /// once it is dropped.
+    topology: Mutex<Option<Weak<Topology>>>,
 }
 
-impl<H> RcActivatorInner<H> {
-    fn new(node: H) -> Self {
-        RcActivatorInner {
-            pending: AtomicUsize::new(0),
-            initial: AtomicUsize::new(1),
-            handle: Mutex::new(node),
+/// The key of the next node created.  Keys are never reused, unlike the addresses of the nodes.
+/// This is a `std` atomic even with loom, whose atomics can't be statics.
+static NEXT_KEY: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+
+impl Default for NodeExtra {
+    fn default() -> Self {
+        NodeExtra {
+            key: NEXT_KEY.fetch_add(1, atomic::Ordering::Relaxed),
+            affinity: Affinity::new(),
+            groups: Mutex::new(Vec::new()),
+            topology: Mutex::new(None),
         }
     }
 }
 
-impl<H: ?Sized> RcActivatorInner<H> {
-    /// Rearm the activation structure with a new pending count. This should only be called when
-    /// the activator was depleted.
-    fn rearm(&self) {
-        let initial = self.initial.load(SeqCst);
-        assert!(self.pending.swap(initial, SeqCst) == 0);
-    }
-
-    /// Decrement the pending count and return the new pending count.
-    fn decrement_pending(&self) -> usize {
-        let old_pending = self.pending.fetch_sub(1, SeqCst);
-        assert!(old_pending > 0);
-        old_pending - 1
+impl Drop for NodeExtra {
+    fn drop(&mut self) {
+        let topology = self.topology.lock().unwrap().take();
+        if let Some(topology) = topology.and_then(|topology| topology.upgrade()) {
+            topology.remove_node(self.key);
+        }
     }
 }
 
-/// A reference-counted, reusable activator.
-///
-/// The activator contains a handle to a node, a counter for the number of activations
-/// remaining before the node should be scheduled, and the total number of activators.
-///
-/// When the node is finalized, the counter is set to the total number of activators.  It is
-/// decremented by one on each activation, and the node is scheduled when the counter reaches zero.
-#[derive(Debug)]
-pub struct RcActivator<H: ?Sized> {
-    inner: Arc<RcActivatorInner<H>>,
-}
+/// The inner structure for the activator.  This include a handle to the node, as well as a pending
+/// count with interior mutability.
+pub(crate) type RcActivatorInner<H> = RcInner<H, NodeExtra>;
 
-/// A default activator which schedules a panicking node.  This can be used as a placeholder
-/// activator when the target node is not yet known.  Note that trying to activate this will
-/// already trigger a panic in `decrement_pending` since it never gets armed.
-impl<'r> Default for RcActivator<RuntimeNode<'r>> {
-    fn default() -> Self {
-        RcActivator {
-            inner: Arc::new(RcActivatorInner::new(UninitializedNode)),
-        }
-    }
-}
+/// A reference-counted, reusable activator.  See `custom::rc`.
+pub type RcActivator<H> = rc::RcActivator<H, NodeExtra>;
 
 impl<'r> ActivatorOnce<RuntimeLoc<'r>> for RcActivator<RuntimeNode<'r>> {
     fn activate_once(self, scheduler: &mut RuntimeLoc<'r>) {
-        if self.inner.decrement_pending() == 0 {
+        if scheduler.accepts(self.key()) && self.inner.decrement_pending() == 0 {
             scheduler.schedule(RcHandle { inner: self.inner })
         }
     }
@@ -152,7 +152,7 @@ impl<'r> ActivatorMut<Toexec<'r>> for RcActivator<RuntimeNode<'r>> {
 
 impl<'r> Activator<RuntimeLoc<'r>> for RcActivator<RuntimeNode<'r>> {
     fn activate(&self, scheduler: &mut RuntimeLoc<'r>) {
-        if self.inner.decrement_pending() == 0 {
+        if scheduler.accepts(self.key()) && self.inner.decrement_pending() == 0 {
             scheduler.schedule(RcHandle {
                 inner: self.inner.clone(),
             })
@@ -170,60 +170,173 @@ impl<'r> Activator<Toexec<'r>> for RcActivator<RuntimeNode<'r>> {
     }
 }
 
-/// A node handle.  This is the structured used to actually schedule nodes.  A single handle to a
-/// given node should ever exist, and it can only exist when the node's pending count is 0.
-#[derive(Debug)]
-pub struct RcHandle<H: ?Sized> {
+impl<H: ?Sized> RcActivator<H> {
+    /// The identity of the underlying node.
+    fn key(&self) -> NodeKey {
+        self.inner.extra.key
+    }
+
+    /// Start changing the number of activators of the underlying node.
+    ///
+    /// See `FanInChange` for details.
+    pub fn reconfigure(&self) -> FanInChange<H> {
+        FanInChange {
+            inner: self.inner.clone(),
+            added: 0,
+            removed: Vec::new(),
+        }
+    }
+}
+
+/// A pending change to the fan-in (number of activators) of a reusable node.
+///
+/// In adaptive graphs, the number of producers of a node can vary over time.  Changing the fan-in
+/// requires updating both the initial count the node is re-armed with and its current pending
+/// count, which is only meaningful between two executions of the node: the changes are recorded
+/// in a `FanInChange`, then validated and applied all at once by `commit`.
+///
+/// New activators are only handed out once the change has been committed; it is then up to the
+/// caller to connect them to the new producers (and to drop the edges of the removed ones).
+pub struct FanInChange<H: ?Sized> {
     inner: Arc<RcActivatorInner<H>>,
+    added: usize,
+    removed: Vec<RcActivator<H>>,
 }
 
-impl<S, H: NodeMut<S> + ?Sized> NodeOnce<S> for RcHandle<H>
-where
-    RcActivator<H>: ActivatorOnce<S>,
-{
-    /// Execute the guard.  This consumes the guard and re-arm the activators, which allows the
-    /// node to be executed again later.
-    fn execute_once(self, scheduler: &mut S) {
-        self.inner.rearm();
-        self.inner.handle.lock().unwrap().execute_mut(scheduler);
-        RcActivator { inner: self.inner }.activate_once(scheduler);
+impl<H: ?Sized> FanInChange<H> {
+    /// Request an additional activator for the node.
+    pub fn subscribe(&mut self) {
+        self.added += 1;
+    }
+
+    /// Remove an existing activator of the node.
+    pub fn unsubscribe(&mut self, activator: RcActivator<H>) {
+        self.removed.push(activator);
+    }
+
+    /// Validate and apply the change, returning the newly subscribed activators.
+    ///
+    /// The change is rejected if one of the removed activators belongs to a different node, or if
+    /// the node is not idle, i.e. if it is currently executing or was already activated by some of
+    /// its producers since its last execution.  In that case, the node is left untouched and the
+    /// removed activators are returned in the error.
+    pub fn commit(self) -> Result<Vec<RcActivator<H>>, FanInError<H>> {
+        let FanInChange {
+            inner,
+            added,
+            removed,
+        } = self;
+
+        if removed
+            .iter()
+            .any(|activator| !Arc::ptr_eq(&activator.inner, &inner))
+        {
+            return Err(FanInError::ForeignActivator(removed));
+        }
+
+        // Holding the node prevents it from being executed while we update the counts.
+        let _guard = match inner.handle.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return Err(FanInError::Busy(removed)),
+        };
+
+        // The initial count includes the handle, which is released after each execution.
+        let initial = inner.initial.load(SeqCst);
+        let idle = initial - 1;
+        let target = idle + added - removed.len();
+        if inner
+            .pending
+            .compare_exchange(idle, target, SeqCst, SeqCst)
+            .is_err()
+        {
+            return Err(FanInError::Busy(removed));
+        }
+        inner.initial.store(initial + added - removed.len(), SeqCst);
+
+        Ok((0..added)
+            .map(|_| RcActivator {
+                inner: inner.clone(),
+            })
+            .collect())
     }
 }
 
-/// A builder for reusable nodes.  Allow creation of activators and arms them when finalized.
-#[derive(Debug)]
-pub struct RcBuilder<N> {
-    inner: Arc<RcActivatorInner<N>>,
-    _marker: PhantomData<*const N>,
-    num_activators: usize,
+/// The reasons a `FanInChange` can be rejected.  The removed activators are given back so that the
+/// graph can keep running in its previous configuration.
+pub enum FanInError<H: ?Sized> {
+    /// The node is currently executing, or some of its activators were already activated.
+    Busy(Vec<RcActivator<H>>),
+    /// One of the removed activators does not belong to the reconfigured node.
+    ForeignActivator(Vec<RcActivator<H>>),
 }
 
-impl<N> RcBuilder<N> {
-    fn new(node: N) -> Self {
-        RcBuilder {
-            inner: Arc::new(RcActivatorInner::new(node)),
-            _marker: PhantomData,
-            num_activators: 0,
+impl<H: ?Sized> fmt::Debug for FanInError<H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FanInError::Busy(ref removed) => write!(f, "Busy({} activators)", removed.len()),
+            FanInError::ForeignActivator(ref removed) => {
+                write!(f, "ForeignActivator({} activators)", removed.len())
+            }
         }
     }
 }
 
+/// A node handle.  This is the structured used to actually schedule nodes.
+pub type RcHandle<H> = rc::RcHandle<H, NodeExtra>;
+
+impl<'r> RcHandle<RuntimeNode<'r>> {
+    /// The identity of the underlying node.
+    fn key(&self) -> NodeKey {
+        self.inner.extra.key
+    }
+
+    /// Release the handle without executing the node, re-arming it for the next execution.
+    fn skip(self) {
+        self.inner.rearm();
+        self.inner.decrement_pending();
+    }
+}
+
+/// A builder for reusable nodes.  Allow creation of activators and arms them when finalized.
+pub type RcBuilder<N> = rc::RcBuilder<N, NodeExtra>;
+
 impl<'r, N: NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r> NodeBuilder<RuntimeLoc<'r>>
     for RcBuilder<N>
 {
     type Node = N;
 
     fn add_activator(&mut self) -> RcActivator<RuntimeNode<'r>> {
-        self.inner.initial.fetch_add(1, SeqCst);
+        self.inner.add_activator();
 
         RcActivator {
             inner: self.inner.clone(),
         }
     }
 
-    fn finalize(&mut self, _builder: &mut RuntimeLoc<'r>) {
-        self.inner.rearm();
-        self.inner.decrement_pending();
+    fn set_label(&mut self, label: Label) {
+        self.label(label)
+    }
+
+    fn set_affinity(&mut self, worker: usize) {
+        self.inner.extra.affinity.set(worker)
+    }
+
+    fn add_to_group(&mut self, group: &Quiescence) {
+        self.inner.extra.groups.lock().unwrap().push(group.clone())
+    }
+
+    fn finalize(&mut self, builder: &mut RuntimeLoc<'r>) {
+        self.arm();
+        let inner: Weak<RcActivatorInner<N>> = Arc::downgrade(&self.inner);
+        builder.rearmables.track(inner.clone());
+        if let Some(ref registry) = builder.registry {
+            registry.track(inner);
+        }
+        if let Some(ref topology) = builder.topology {
+            let extra = &self.inner.extra;
+            topology.add_node(extra.key, self.inner.label.lock().unwrap().clone());
+            *extra.topology.lock().unwrap() = Some(Arc::downgrade(topology));
+        }
     }
 }
 
@@ -233,36 +346,37 @@ impl<'r, N: NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r> NodeBuilder<Toexec<'r>>
     type Node = N;
 
     fn add_activator(&mut self) -> RcActivator<RuntimeNode<'r>> {
-        self.inner.initial.fetch_add(1, SeqCst);
+        self.inner.add_activator();
 
         RcActivator {
             inner: self.inner.clone(),
         }
     }
 
-    fn finalize(&mut self, _builder: &mut Toexec<'r>) {
-        self.inner.rearm();
-        self.inner.decrement_pending();
+    fn set_label(&mut self, label: Label) {
+        self.label(label)
     }
-}
-
-impl<'a, 'r: 'a, N: NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r> NodeBorrowMut<'a, RuntimeLoc<'r>>
-    for RcBuilder<N>
-{
-    type RefMut = MutexGuard<'a, N>;
 
-    fn borrow_mut(&'a mut self) -> Self::RefMut {
-        self.inner.handle.lock().unwrap()
+    fn set_affinity(&mut self, worker: usize) {
+        self.inner.extra.affinity.set(worker)
     }
-}
 
-impl<'a, 'r: 'a, N: NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r> NodeBorrowMut<'a, Toexec<'r>>
-    for RcBuilder<N>
-{
-    type RefMut = MutexGuard<'a, N>;
+    fn add_to_group(&mut self, group: &Quiescence) {
+        self.inner.extra.groups.lock().unwrap().push(group.clone())
+    }
 
-    fn borrow_mut(&'a mut self) -> Self::RefMut {
-        self.inner.handle.lock().unwrap()
+    fn finalize(&mut self, builder: &mut Toexec<'r>) {
+        self.arm();
+        let inner: Weak<RcActivatorInner<N>> = Arc::downgrade(&self.inner);
+        builder.rearmables.track(inner.clone());
+        if let Some(ref registry) = builder.registry {
+            registry.track(inner);
+        }
+        if let Some(ref topology) = builder.topology {
+            let extra = &self.inner.extra;
+            topology.add_node(extra.key, self.inner.label.lock().unwrap().clone());
+            *extra.topology.lock().unwrap() = Some(Arc::downgrade(topology));
+        }
     }
 }
 
@@ -271,17 +385,342 @@ pub type RuntimeNode<'r> = dyn NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r;
 
 pub type RuntimeActivator<'r> = RcActivator<RuntimeNode<'r>>;
 
+/// Values sent from outside of the workers, e.g. by timers.  Each event sends its value when
+/// executed by a worker.
+type InjectQueue<'r> = Mutex<VecDeque<Box<dyn FnOnce(&mut RuntimeLoc<'r>) + Send + 'r>>>;
+
 /// A worker doing work stealing
 pub struct RuntimeLoc<'r> {
     pub ready: deque::Worker<RcHandle<RuntimeNode<'r>>>,
     pub stealers: Vec<deque::Stealer<RcHandle<RuntimeNode<'r>>>>,
+    /// The nodes pinned to each worker.
+    pinned: Arc<Mailboxes<RcHandle<RuntimeNode<'r>>>>,
+    injected: Arc<InjectQueue<'r>>,
+    termination: Arc<Termination>,
+    /// The index of the worker, for tracing.
+    index: usize,
+    trace: TraceSlot,
+    registry: Option<Arc<Registry<'r>>>,
+    /// The nodes and ports to reset, see `Toexec::reset`.
+    rearmables: Arc<Rearmables<'r>>,
+    /// The observed dependencies, when slicing is enabled.
+    topology: Option<Arc<Topology>>,
+    /// The nodes to execute, when executing a slice of the graph.
+    slice: Option<Slice>,
+    /// The node being executed, if any.
+    current: Option<NodeKey>,
+    config: RuntimeConfig,
+    failures: Arc<Failures>,
+}
+
+impl<'r> RuntimeLoc<'r> {
+    /// Whether `node` should be activated, recording the dependency on the node being executed if
+    /// slicing is enabled.  Nodes outside of the executed slice ignore their activations.
+    fn accepts(&self, node: NodeKey) -> bool {
+        if let Some(current) = self.current {
+            if let Some(ref topology) = self.topology {
+                topology.add_dependency(current, node);
+            }
+            // The node being executed releases its own handle.
+            if current == node {
+                return true;
+            }
+        }
+        self.slice.as_ref().is_none_or(|slice| slice.contains(&node))
+    }
+
+    /// Execute a node, keeping track of it as the current node.  If the node panics, the panic is
+    /// recorded and the workers are stopped.
+    fn execute_handle(&mut self, handle: RcHandle<RuntimeNode<'r>>) {
+        let previous = self.current.replace(handle.key());
+        let inner = handle.inner.clone();
+        self.trace(|hook| {
+            hook.on_execute_start(self.index);
+            if let Some(ref label) = *inner.label.lock().unwrap() {
+                hook.on_execute_labeled(self.index, label);
+            }
+        });
+        match panic::catch_unwind(AssertUnwindSafe(|| handle.execute_once(self))) {
+            Ok(()) => {
+                self.trace(|hook| hook.on_execute_end(self.index));
+                for group in inner.extra.groups.lock().unwrap().iter() {
+                    group.completed();
+                }
+            }
+            Err(payload) => {
+                // The node's lock is poisoned by the panic, so that it can't be executed again.
+                let label = inner.label.lock().unwrap().clone();
+                self.failures.record(label, self.index, &*payload);
+                self.termination.stop();
+            }
+        }
+        self.current = previous;
+    }
+
+    /// Execute an event pushed by an `InjectorHandle`, if any, and return whether there was one.
+    fn execute_injected(&mut self) -> bool {
+        let event = self.injected.lock().unwrap().pop_front();
+        match event {
+            Some(event) => {
+                event(self);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Try to steal a batch of nodes from the other workers, returning the first one.
+    fn steal(&self) -> Option<RcHandle<RuntimeNode<'r>>> {
+        let (i, node, stolen) = self.config.steal(&self.stealers, &self.ready)?;
+        // The stealers are ordered starting from the next worker.
+        let victim = (self.index + 1 + i) % (self.stealers.len() + 1);
+        for _ in 0..stolen {
+            self.trace(|hook| hook.on_steal(self.index, victim));
+        }
+        Some(node)
+    }
+
+    fn trace<F: FnOnce(&dyn TraceHook)>(&self, f: F) {
+        self.trace.call(f)
+    }
+
+    /// Execute other nodes until `predicate` returns `true`.
+    ///
+    /// This allows an executing node to wait for a value produced by another node, e.g. a port
+    /// written by a node which was scheduled later, without blocking the worker: the worker
+    /// executes the ready nodes (including stolen ones) while waiting.  The predicate is checked
+    /// after each executed node, and periodically while no nodes are available.
+    ///
+    /// This fails with `HelpError::Deadlock` if there are no nodes left to execute and all the
+    /// nodes in flight are waiting, and with `HelpError::Stopped` if the workers were stopped.
+    ///
+    /// Note that nodes executed while helping are executed on top of the current node's stack, so
+    /// that deeply nested waits can overflow the stack.
+    pub fn help_until<P: Fn() -> bool>(&mut self, predicate: P) -> Result<(), HelpError> {
+        self.help(&predicate, None)
+    }
+
+    /// Like `help_until`, but fails with `HelpError::Timeout` if `predicate` still returns `false`
+    /// after `timeout`.
+    pub fn help_until_timeout<P: Fn() -> bool>(
+        &mut self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<(), HelpError> {
+        self.help(&predicate, Some(Instant::now() + timeout))
+    }
+
+    fn help(
+        &mut self,
+        predicate: &dyn Fn() -> bool,
+        deadline: Option<Instant>,
+    ) -> Result<(), HelpError> {
+        self.termination.help_started();
+        let mut backoff = Backoff::new(&self.config);
+        let result = loop {
+            if predicate() {
+                break Ok(());
+            }
+            if self.termination.is_stopped() {
+                break Err(HelpError::Stopped);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break Err(HelpError::Timeout);
+            }
+            let epoch = self.termination.epoch();
+            match self
+                .pinned
+                .pop(self.index)
+                .or_else(|| self.ready.pop())
+                .or_else(|| self.steal())
+            {
+                Some(t) => {
+                    backoff.reset();
+                    self.execute_handle(t);
+                    self.termination.completed();
+                }
+                None if self.execute_injected() => {
+                    backoff.reset();
+                    self.termination.completed();
+                }
+                None => {
+                    if self.termination.is_stalled() && !predicate() {
+                        break Err(HelpError::Deadlock);
+                    }
+                    self.trace(|hook| hook.on_idle(self.index));
+                    backoff.wait(&self.termination, epoch, deadline);
+                }
+            }
+        };
+        self.termination.help_completed();
+        result
+    }
+
+    /// Execute nodes until the graph has quiesced, or until `until` returns `true`.  The
+    /// condition is checked after each execution.
+    fn run(&mut self, until: &(dyn Fn() -> bool + Sync)) {
+        let mut backoff = Backoff::new(&self.config);
+        loop {
+            if self.termination.should_exit() {
+                return;
+            }
+            let epoch = self.termination.epoch();
+            match self
+                .pinned
+                .pop(self.index)
+                .or_else(|| self.ready.pop())
+                .or_else(|| self.steal())
+            {
+                Some(t) => {
+                    backoff.reset();
+                    self.execute_handle(t);
+                    if until() {
+                        self.termination.stop();
+                    }
+                    self.termination.completed();
+                }
+                None if self.execute_injected() => {
+                    backoff.reset();
+                    if until() {
+                        self.termination.stop();
+                    }
+                    self.termination.completed();
+                }
+                None => {
+                    self.trace(|hook| hook.on_idle(self.index));
+                    backoff.wait(&self.termination, epoch, None)
+                }
+            }
+        }
+    }
 }
 
 impl<'r> Scheduler for RuntimeLoc<'r> {
     type Handle = RcHandle<RuntimeNode<'r>>;
 
     fn schedule(&mut self, handle: Self::Handle) {
-        self.ready.push(handle);
+        self.trace(|hook| hook.on_schedule(Some(self.index)));
+        self.termination.scheduled();
+        for group in handle.inner.extra.groups.lock().unwrap().iter() {
+            group.scheduled();
+        }
+        match handle.inner.extra.affinity.get() {
+            Some(worker) => {
+                self.pinned.push(worker, handle);
+                self.termination.published_all();
+            }
+            None => {
+                self.ready.push(handle);
+                self.termination.published();
+            }
+        }
+    }
+}
+
+impl<'r> HasCancellation for RuntimeLoc<'r> {
+    fn is_cancelled(&self) -> bool {
+        self.termination.is_stopped()
+    }
+}
+
+impl<'r> HasWorkerIndex for RuntimeLoc<'r> {
+    fn worker_index(&self) -> usize {
+        self.index
+    }
+
+    fn worker_count(&self) -> usize {
+        self.stealers.len() + 1
+    }
+}
+
+impl<'r> Capabilities for RuntimeLoc<'r> {
+    fn as_cancellation(&self) -> Option<&dyn HasCancellation> {
+        Some(self)
+    }
+
+    fn as_worker_index(&self) -> Option<&dyn HasWorkerIndex> {
+        Some(self)
+    }
+}
+
+impl<'r> Capabilities for Toexec<'r> {}
+
+/// Push an event to the queue of injected events.
+///
+/// Like the nodes injected into the single-use runtime, injected events are counted as scheduled
+/// as soon as they are pushed, and the events left in the queue are counted again when starting
+/// an execution (see `Toexec::workers`).
+fn push_injected<'r>(
+    queue: &InjectQueue<'r>,
+    termination: &Termination,
+    event: Box<dyn FnOnce(&mut RuntimeLoc<'r>) + Send + 'r>,
+) {
+    {
+        let mut queue = queue.lock().unwrap();
+        termination.scheduled();
+        queue.push_back(event);
+    }
+    termination.notify();
+}
+
+/// A handle for sending values into a graph from other threads while it is executing.  This is
+/// the reusable counterpart of `parallel::single_use::InjectorHandle`, and behaves the same way:
+/// executions only return once all the handles were dropped.
+pub struct InjectorHandle<'r> {
+    injected: Arc<InjectQueue<'r>>,
+    termination: Arc<Termination>,
+}
+
+impl<'r> InjectorHandle<'r> {
+    fn new(injected: Arc<InjectQueue<'r>>, termination: Arc<Termination>) -> Self {
+        termination.external_started();
+        InjectorHandle {
+            injected,
+            termination,
+        }
+    }
+
+    /// Send `item` on `edge`, activating the target node.
+    pub fn inject_send<E>(&self, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<RuntimeLoc<'r>> + Send + Sync + 'r,
+        E::Item: Send + Sync + 'r,
+    {
+        push_injected(
+            &self.injected,
+            &self.termination,
+            Box::new(move |scheduler: &mut RuntimeLoc<'r>| edge.send_activate_once(scheduler, item)),
+        )
+    }
+}
+
+impl<'r> Clone for InjectorHandle<'r> {
+    fn clone(&self) -> Self {
+        InjectorHandle::new(self.injected.clone(), self.termination.clone())
+    }
+}
+
+impl<'r> Drop for InjectorHandle<'r> {
+    fn drop(&mut self) {
+        self.termination.external_completed()
+    }
+}
+
+impl<'r> EventInjector<RuntimeLoc<'r>> for InjectorHandle<'r> {
+    fn inject_send<E>(&self, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<RuntimeLoc<'r>> + Send + Sync + 'static,
+        E::Item: Send + Sync + 'static,
+    {
+        InjectorHandle::inject_send(self, edge, item)
+    }
+}
+
+impl<'r> InjectorSpec for RuntimeLoc<'r> {
+    type Injector = InjectorHandle<'r>;
+
+    fn injector(&self) -> InjectorHandle<'r> {
+        InjectorHandle::new(self.injected.clone(), self.termination.clone())
     }
 }
 
@@ -289,6 +728,10 @@ impl<'r> Scheduler for Toexec<'r> {
     type Handle = RcHandle<RuntimeNode<'r>>;
 
     fn schedule(&mut self, handle: Self::Handle) {
+        self.trace(|hook| hook.on_schedule(None));
+        for group in handle.inner.extra.groups.lock().unwrap().iter() {
+            group.scheduled();
+        }
         self.ready.push(handle);
     }
 }
@@ -296,87 +739,399 @@ impl<'r> Scheduler for Toexec<'r> {
 /// A parallel runtime for reusable graphs.
 pub struct Toexec<'r> {
     pub ready: Vec<RcHandle<RuntimeNode<'r>>>,
+    /// The named outputs of the graphs built on this runtime.
+    outputs: GraphOutputs,
+    /// The hook notified of scheduling events, if any.
+    trace: TraceSlot,
+    /// The finalized nodes, when created with `with_validation` or in debug builds with the
+    /// `diagnostics` feature, where it is used to detect leaks.
+    registry: Option<Arc<Registry<'r>>>,
+    /// Whether executions report stalled nodes, i.e. the runtime was created with
+    /// `with_validation`.
+    /// This does not depend on the build profile, contrary to `registry`.
+    validate: bool,
+    /// The nodes and ports built on this runtime, see `reset`.
+    rearmables: Arc<Rearmables<'r>>,
+    /// The observed dependencies, when created with `with_slicing`.
+    topology: Option<Arc<Topology>>,
+    /// The work stealing configuration.
+    config: RuntimeConfig,
+    /// The nodes which panicked during the current execution.
+    failures: Arc<Failures>,
+    /// Values sent by injector handles.  This is shared with the workers.
+    injected: Arc<InjectQueue<'r>>,
+    /// The termination state.  This is kept across executions in order to track the live
+    /// injector handles.
+    termination: Arc<Termination>,
+    /// The periodic timers owned by the runtime, see `add_timer`.
+    timers: Vec<Timer>,
+    /// Whether executions may run, see `pause`.
+    mode: RunMode,
 }
 
 impl<'r> Toexec<'r> {
     pub fn new() -> Self {
-        Toexec { ready: Vec::new(),}
+        Toexec {
+            ready: Vec::new(),
+            outputs: GraphOutputs::new(),
+            trace: TraceSlot::default(),
+            registry: if cfg!(all(debug_assertions, feature = "diagnostics")) {
+                Some(Arc::new(Registry::default()))
+            } else {
+                None
+            },
+            validate: false,
+            rearmables: Arc::new(Rearmables::default()),
+            topology: None,
+            config: RuntimeConfig::new(),
+            failures: Arc::new(Failures::default()),
+            injected: Arc::new(Mutex::new(VecDeque::new())),
+            termination: Arc::new(Termination::new(0)),
+            timers: Vec::new(),
+            mode: RunMode::Running,
+        }
+    }
+
+    /// Create a runtime recording the dependencies between its nodes, for use with
+    /// `execute_slice`.  See the `parallel::slice` module.
+    ///
+    /// Note that recording the dependencies slows down activations.
+    pub fn with_slicing() -> Self {
+        let mut toexec = Toexec::new();
+        toexec.topology = Some(Arc::new(Topology::default()));
+        toexec
+    }
+
+    /// Create a runtime keeping track of the nodes it finalizes, so that executions report the
+    /// nodes left waiting for activations.  See the `parallel::validation` module.
+    pub fn with_validation() -> Self {
+        let mut toexec = Toexec::new();
+        toexec.set_validation(true);
+        toexec
+    }
+
+    /// Enable or disable the report of the nodes left waiting for activations, as with
+    /// `with_validation`.  Only the nodes finalized while validation is enabled, or in debug
+    /// builds with the `diagnostics` feature, are tracked.
+    pub fn set_validation(&mut self, enabled: bool) {
+        if enabled && self.registry.is_none() {
+            self.registry = Some(Arc::new(Registry::default()));
+        }
+        self.validate = enabled;
+    }
+
+    /// A detector reporting the nodes built on this runtime which outlive it, or `None` if the
+    /// runtime does not track its nodes.  Nodes are tracked in debug builds with the
+    /// `diagnostics` feature, and by runtimes created with `with_validation`.  See
+    /// `LeakDetector`.
+    pub fn leak_detector(&self) -> Option<LeakDetector<'r>> {
+        self.registry.clone().map(LeakDetector::new)
+    }
+
+    /// Set the work stealing configuration of the following executions.  See the
+    /// `parallel::config` module.
+    pub fn set_config(&mut self, config: RuntimeConfig) {
+        self.config = config;
+    }
+
+    /// The work stealing configuration.
+    pub fn config(&self) -> RuntimeConfig {
+        self.config
+    }
+
+    /// Install a hook notified of the scheduling events of the following executions, replacing any
+    /// previous one.  See the `parallel::trace` module.  This does nothing without the `tracing`
+    /// feature.
+    pub fn set_trace_hook<H: TraceHook + 'static>(&mut self, hook: H) {
+        self.trace.set(Arc::new(hook));
+    }
+
+    fn trace<F: FnOnce(&dyn TraceHook)>(&self, f: F) {
+        self.trace.call(f)
+    }
+
+    /// Create a handle for sending values into the graph from other threads.  See
+    /// `InjectorHandle`.
+    pub fn injector_handle(&self) -> InjectorHandle<'r> {
+        InjectorHandle::new(self.injected.clone(), self.termination.clone())
+    }
+
+    /// Stop running executions: `execute`, `execute_default`, `execute_slice` and `execute_on` return
+    /// `Ok(())` without executing anything, and the scheduled nodes stay scheduled until `step` or
+    /// `resume` is called.  Other executions, which stop before the graph quiesces, are not
+    /// affected.  See `PauseSpec`.
+    pub fn pause(&mut self) {
+        self.mode = RunMode::Paused
+    }
+
+    /// Allow exactly one more execution to run, then pause again.
+    pub fn step(&mut self) {
+        self.mode = RunMode::Step
+    }
+
+    /// Return to free-running mode, running executions again.
+    pub fn resume(&mut self) {
+        self.mode = RunMode::Running
+    }
+
+    /// Whether the runtime is paused, either through `pause` or after the execution allowed by
+    /// `step` was started.
+    pub fn is_paused(&self) -> bool {
+        self.mode == RunMode::Paused
+    }
+
+    /// Execute the graph on `k` worker threads.  This returns once all the scheduled nodes, as
+    /// well as all the nodes they schedule, have been executed, or once the execution failed.
+    ///
+    /// The execution fails if a node panics: the workers are stopped, the nodes which were
+    /// scheduled but not executed yet are dropped, and the nodes which panicked can't be executed
+    /// again.  Runtimes created with `with_validation` also fail if nodes were waiting for
+    /// activations once the graph quiesced, whatever the build profile.  See `ExecutionError`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `k` is zero.
+    pub fn execute(&mut self, k: usize) -> Result<(), ExecutionError> {
+        if !self.mode.start_instant() {
+            return Ok(());
+        }
+        self.execute_inner(k, &|| false)?;
+        self.check_stalled()
+    }
+
+    /// Like `execute`, with the number of workers of the runtime's configuration, i.e. one per
+    /// available core by default.  See `RuntimeConfig::workers`.
+    pub fn execute_default(&mut self) -> Result<(), ExecutionError> {
+        let k = self.config.worker_count();
+        self.execute(k)
+    }
+
+    /// Report the nodes waiting for activations, if the runtime was created with
+    /// `with_validation`.
+    fn check_stalled(&self) -> Result<(), ExecutionError> {
+        match self.registry {
+            Some(ref registry) if self.validate => Ok(registry.check()?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Restore the graph to its state after construction, so that it can be executed again with
+    /// new inputs.  See the `parallel::reset` module.
+    ///
+    /// This drops the scheduled nodes and the injected values, restores the pending counts of all
+    /// the nodes built on the runtime, and clears all the ports created on the runtime.
+    pub fn reset(&mut self) {
+        self.ready.clear();
+        self.injected.lock().unwrap().clear();
+        self.rearmables.reset();
     }
 
-    pub fn execute(&mut self, k: usize) {    	
-        // création des listes de taches 
-        let mut fifos = Vec::new();
-	    let mut stealers = Vec::new();
+    /// Execute the graph on `k` worker threads until a value is written on the port read by
+    /// `receiver`, and return that value.
+    ///
+    /// The workers are stopped as soon as the value is available: nodes which were scheduled but
+    /// not executed yet are dropped.  This fails as `execute`, except that stalled nodes are not
+    /// reported since the execution is interrupted.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the graph quiesces without writing to the port.
+    pub fn execute_until<T, R>(&mut self, k: usize, receiver: R) -> Result<T, ExecutionError>
+    where
+        T: Send,
+        R: Receiver<Item = Option<T>> + Sync,
+    {
+        let result = Mutex::new(receiver.recv());
+        if result.lock().unwrap().is_none() {
+            self.execute_inner(k, &|| match receiver.recv() {
+                Some(value) => {
+                    *result.lock().unwrap() = Some(value);
+                    true
+                }
+                None => false,
+            })?;
+        }
+        Ok(result
+            .into_inner()
+            .unwrap()
+            .expect("Graph quiesced without writing the result port."))
+    }
 
-        for _ in 0..k {
-	        let fs = deque::fifo();
-            fifos.push(fs.0);
-	        stealers.push(fs.1);
+    /// Execute the part of the graph needed to compute the nodes labelled with one of `outputs`,
+    /// on `k` worker threads.  See the `parallel::slice` module.
+    ///
+    /// The nodes outside of the slice are not executed, even if they were activated before the
+    /// execution, and ignore the activations from the nodes in the slice.
+    ///
+    /// This fails as `execute`, except that stalled nodes are not reported since the nodes outside
+    /// of the slice are left waiting.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the runtime was not created with `with_slicing`, or if there is no node
+    /// labelled with one of `outputs`.
+    pub fn execute_slice(&mut self, k: usize, outputs: &[&str]) -> Result<(), ExecutionError> {
+        if !self.mode.start_instant() {
+            return Ok(());
+        }
+        let slice = self
+            .topology
+            .as_ref()
+            .expect("execute_slice requires a runtime created with `with_slicing`.")
+            .slice(outputs);
+
+        let (kept, skipped): (Vec<_>, Vec<_>) = self
+            .ready
+            .drain(..)
+            .partition(|handle| slice.contains(&handle.key()));
+        self.ready = kept;
+        for handle in skipped {
+            handle.skip();
         }
+        self.execute_sliced(k, Some(slice), &|| false)
+    }
+
+    fn execute_inner(
+        &mut self,
+        k: usize,
+        until: &(dyn Fn() -> bool + Sync),
+    ) -> Result<(), ExecutionError> {
+        self.execute_sliced(k, None, until)
+    }
 
-        // création des threads et runtimes associées
+    /// Execute the nodes in `slice`, if any, until the graph quiesces, `until` returns true, or a
+    /// node panics.  This reports the failed nodes, but not the stalled ones.
+    fn execute_sliced(
+        &mut self,
+        k: usize,
+        slice: Option<Slice>,
+        until: &(dyn Fn() -> bool + Sync),
+    ) -> Result<(), ExecutionError> {
+        let workers = self.workers(&mut Queues::new(k, &self.config), slice);
+
+        // création des threads
         crossbeam::scope(|scope| {
-            for i in 0..(k) {
-                let j = i.clone();
+            for mut runtime_loc in workers {
+                scope.spawn(move || runtime_loc.run(until));
+            }
+        });
+        self.failures.take()
+    }
 
-                let ready_j = fifos.pop().unwrap();
-                
-                if i == 0 {
+    /// Create `k` workers sharing the nodes ready for execution, and only executing the nodes in
+    /// `slice`, if any.
+    fn workers(
+        &mut self,
+        queues: &mut Queues<RcHandle<RuntimeNode<'r>>>,
+        slice: Option<Slice>,
+    ) -> Vec<RuntimeLoc<'r>> {
+        let k = queues.stealers.len();
+        config::check_workers(k);
+
+        // The events injected since the last execution are counted again.  Keep the queue locked
+        // while resetting the count, so that concurrent injections are not lost.
+        {
+            let injected = self.injected.lock().unwrap();
+            self.termination.reset(self.ready.len() + injected.len());
+        }
+        let pinned = Arc::new(Mailboxes::new(k));
+
+        // création des runtimes associées
+        let stealers = &queues.stealers;
+        queues
+            .workers
+            .drain(..)
+            .enumerate()
+            .map(|(j, ready_j)| {
+                if j == 0 {
                     for w in self.ready.drain(..) {
-                        ready_j.push(w)
+                        match w.inner.extra.affinity.get() {
+                            Some(worker) => pinned.push(worker, w),
+                            None => ready_j.push(w),
+                        }
                     }
                 }
-                
-                let mut stealers_j = Vec::new();
-                
+
                 // l'ordre des stealers n'est pas "naturelle" pour que tout le monde ne vole pas au premier
-                for w in (j + 1)..k {
-                    stealers_j.push(stealers[w].clone());
+                let stealers_j = stealers[(j + 1)..]
+                    .iter()
+                    .chain(&stealers[..j])
+                    .cloned()
+                    .collect();
+
+                RuntimeLoc {
+                    ready: ready_j,
+                    stealers: stealers_j,
+                    pinned: pinned.clone(),
+                    injected: self.injected.clone(),
+                    termination: self.termination.clone(),
+                    index: j,
+                    config: self.config,
+                    trace: self.trace.clone(),
+                    registry: self.registry.clone(),
+                    rearmables: self.rearmables.clone(),
+                    topology: self.topology.clone(),
+                    slice: slice.clone(),
+                    current: None,
+                    failures: self.failures.clone(),
                 }
+            })
+            .collect()
+    }
+}
 
-                for w in 0..j {
-                    stealers_j.push(stealers[w].clone());
-                }
-		
-                scope.spawn(move || {
-
-                    let mut runtime_loc = RuntimeLoc {
-                        ready: ready_j,
-                        stealers: stealers_j,
-                    };
-                    
-                    loop {
-                        match runtime_loc.ready.pop() {
-                            Some(t) => t.execute_once(&mut runtime_loc),
-                            None => {
-                                let mut i = 0;
-                                let mut tour = Arc::new(Compteur::new(0));
-                                loop {
-                                    match runtime_loc.stealers[i].steal() {
-                                        Some(t) => {
-					                        t.execute_once(&mut runtime_loc);
-					                        break
-					                    },
-                                        None => (),
-                                    }
-                                    i = (i + 1) % (k-1);
-
-                                    if i == 0{
-                                        if tour.get()==10{
-                                            return;
-                                        }
-                                        else{
-                                            tour.inc();
-                                            thread::yield_now();
-                                        }
-                                    }                              
-                                }
-                            }
-                        }
-                    }
-                });
-            }
-        });
+/// Stop the timers before the rest of the runtime is dropped, so that no ticks are injected into
+/// a runtime being dropped.
+impl<'r> Drop for Toexec<'r> {
+    fn drop(&mut self) {
+        self.timers.clear()
+    }
+}
+
+impl Toexec<'static> {
+    /// Execute the graph on the threads of `pool`, using one worker per thread.  This behaves
+    /// like `execute`, but without spawning new threads.
+    pub fn execute_on(&mut self, pool: &ThreadPool) -> Result<(), ExecutionError> {
+        if !self.mode.start_instant() {
+            return Ok(());
+        }
+        let config = self.config;
+        pool.run_workers(
+            &config,
+            |queues| self.workers(queues, None),
+            |mut runtime_loc: RuntimeLoc<'static>| {
+                runtime_loc.run(&|| false);
+                (runtime_loc.index, runtime_loc.ready)
+            },
+        );
+        self.failures.take()?;
+        self.check_stalled()
+    }
+
+    /// Start a periodic timer injecting its ticks into this runtime through an `InjectorHandle`.
+    /// The timer runs on its own thread until it stops by itself, it is stopped through the
+    /// returned handle, `stop_timers` is called, or the runtime is dropped.  See the
+    /// `common::timer` module.
+    ///
+    /// Ticks sent while the graph is not executing are processed by the next execution.  Since
+    /// the timer holds an `InjectorHandle`, executions only return once it has stopped: timers
+    /// without a limit can be stopped during an execution by moving the handle into a node.
+    pub fn add_timer<F, E>(&mut self, source: TimerSource<F>) -> TimerHandle
+    where
+        F: FnMut(Tick) -> Option<E> + Send + 'static,
+        E: OutputEdgeOnce<RuntimeLoc<'static>> + Send + Sync + 'static,
+        E::Item: TickPayload + Send + Sync + 'static,
+    {
+        let timer = source.start_with(self.injector_handle());
+        let handle = timer.handle();
+        self.timers.push(timer);
+        handle
+    }
+
+    /// Stop all the timers started with `add_timer`.  Ticks which were already injected are still
+    /// processed by the following executions.
+    pub fn stop_timers(&mut self) {
+        self.timers.clear()
     }
 }
 
@@ -384,6 +1139,46 @@ impl<'r> GraphSpec for RuntimeLoc<'r> {
     type Activator = RuntimeActivator<'r>;
 }
 
+impl<'r> OutputSpec for Toexec<'r> {
+    fn outputs(&self) -> &GraphOutputs {
+        &self.outputs
+    }
+
+    fn outputs_mut(&mut self) -> &mut GraphOutputs {
+        &mut self.outputs
+    }
+}
+
+impl<'r> ExecuteSpec for Toexec<'r> {
+    fn new() -> Self {
+        Toexec::new()
+    }
+
+    fn execute(&mut self, workers: usize) {
+        if let Err(error) = Toexec::execute(self, workers) {
+            panic!("{}", error)
+        }
+    }
+}
+
+impl<'r> PauseSpec for Toexec<'r> {
+    fn pause(&mut self) {
+        Toexec::pause(self)
+    }
+
+    fn step(&mut self) {
+        Toexec::step(self)
+    }
+
+    fn resume(&mut self) {
+        Toexec::resume(self)
+    }
+
+    fn is_paused(&self) -> bool {
+        Toexec::is_paused(self)
+    }
+}
+
 impl<'r> GraphSpec for Toexec<'r> {
     type Activator = RuntimeActivator<'r>;
 }
@@ -404,18 +1199,53 @@ impl<'r, N: NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r> NodeSpec<N> for Toexec<'
     }
 }
 
-impl<'r, T: Default + 'r> PortSpec<T> for RuntimeLoc<'r> {
+impl<'r, T: Default + Send + 'r> PortSpec<T> for RuntimeLoc<'r> {
     type Port = RcPort<Mutex<T>>;
 
     fn port(&self, init: T) -> Self::Port {
-        RcPort::new(Mutex::new(init))
+        RcPort::new(Mutex::new(init)).tracked_by(&self.rearmables)
+    }
+}
+
+impl<'r, T: Default + Send + 'r> BufferedPortSpec<T> for RuntimeLoc<'r> {
+    type Port = RcPort<ChannelPort<T>>;
+
+    fn port_buffered(&self, capacity: usize) -> Self::Port {
+        RcPort::new(ChannelPort::new(capacity)).tracked_by(&self.rearmables)
     }
 }
 
-impl<'r, T: Default + 'r> PortSpec<T> for Toexec<'r> {
+impl<'r, T: Default + Send + 'r> CheckedPortSpec<T> for RuntimeLoc<'r> {
+    type Port = RcPort<CheckedPort<Mutex<T>>>;
+
+    fn port_checked(&self, name: &str) -> Self::Port {
+        RcPort::new(CheckedPort::new(name, Mutex::new(T::default())))
+            .tracked_by(&self.rearmables)
+    }
+}
+
+impl<'r, T: Default + Send + 'r> PortSpec<T> for Toexec<'r> {
     type Port = RcPort<Mutex<T>>;
 
     fn port(&self, init: T) -> Self::Port {
-        RcPort::new(Mutex::new(init))
+        RcPort::new(Mutex::new(init)).tracked_by(&self.rearmables)
     }
-}
\ No newline at end of file
+}
+
+impl<'r, T: Default + Send + 'r> BufferedPortSpec<T> for Toexec<'r> {
+    type Port = RcPort<ChannelPort<T>>;
+
+    fn port_buffered(&self, capacity: usize) -> Self::Port {
+        RcPort::new(ChannelPort::new(capacity)).tracked_by(&self.rearmables)
+    }
+}
+
+impl<'r, T: Default + Send + 'r> CheckedPortSpec<T> for Toexec<'r> {
+    type Port = RcPort<CheckedPort<Mutex<T>>>;
+
+    fn port_checked(&self, name: &str) -> Self::Port {
+        RcPort::new(CheckedPort::new(name, Mutex::new(T::default())))
+            .tracked_by(&self.rearmables)
+    }
+}
+
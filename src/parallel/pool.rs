@@ -0,0 +1,198 @@
+//! A persistent pool of worker threads.
+//!
+//! `Toexec::execute` spawns fresh threads on each call, which is wasteful for reactive graphs
+//! driven by frequent external events.  A `ThreadPool` instead keeps its threads alive between
+//! executions; runtimes can run on it with `execute_on`.  The pool also keeps the work-stealing
+//! queue of each thread, which is lent to the worker running on that thread during each
+//! execution, so that executions don't allocate new queues either.
+//!
+//! Since the threads outlive each execution, only runtimes whose nodes don't borrow from the
+//! environment (i.e. `Toexec<'static>`) can be executed on a pool.
+
+use crossbeam::deque::{Stealer, Worker};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use parallel::config::{QueueOrder, RuntimeConfig};
+
+/// A unit of work executed on one of the pool's threads.
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// A worker thread, along with the queue used to send it jobs.
+struct WorkerThread {
+    jobs: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The work-stealing queues of an execution: the local queue of each worker, and the stealers of
+/// all the queues, in the order of the workers.
+pub(crate) struct Queues<T> {
+    /// The local queues, which are moved into the workers during an execution.
+    pub(crate) workers: Vec<Worker<T>>,
+    pub(crate) stealers: Vec<Stealer<T>>,
+    order: QueueOrder,
+}
+
+impl<T> Queues<T> {
+    /// Create `k` empty queues with the order of `config`.
+    pub(crate) fn new(k: usize, config: &RuntimeConfig) -> Self {
+        let (workers, stealers) = (0..k).map(|_| config.deque()).unzip();
+        Queues {
+            workers,
+            stealers,
+            order: config.queue_order(),
+        }
+    }
+}
+
+/// A fixed-size pool of threads reusable across multiple executions.
+pub struct ThreadPool {
+    workers: Vec<WorkerThread>,
+    /// The queues of the threads, by type of item.  They are taken out by each execution, and
+    /// put back once it has completed.
+    queues: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl ThreadPool {
+    /// Create a pool with `k` threads.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `k` is zero.
+    pub fn new(k: usize) -> Self {
+        assert!(k > 0, "Thread pools need at least one thread.");
+
+        let workers = (0..k)
+            .map(|_| {
+                let (sender, receiver) = mpsc::channel::<Job>();
+                let thread = thread::spawn(move || {
+                    for job in receiver {
+                        job()
+                    }
+                });
+                WorkerThread {
+                    jobs: Some(sender),
+                    thread: Some(thread),
+                }
+            })
+            .collect();
+
+        ThreadPool {
+            workers,
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The number of threads in the pool.
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Whether the pool has no threads.  This is never the case, but is provided for consistency
+    /// with `len`.
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// Run one worker on each thread, using the pool's queues for items of type `T`.  `workers`
+    /// creates the workers by moving the local queues out of the `Queues`, and `run` runs a
+    /// worker and returns its index along with its local queue, which is then put back.  The
+    /// `i`-th worker always runs on the `i`-th thread, so that nodes pinned to a worker stay on
+    /// the same thread across executions.
+    ///
+    /// The queues are created on first use, and when the queue order of `config` changes.  They
+    /// are lent to one execution at a time: concurrent executions on the same pool use
+    /// temporary queues.  Items left in the queues, e.g. by an interrupted execution, are dropped
+    /// once it has completed.
+    pub(crate) fn run_workers<T, W, M, F>(&self, config: &RuntimeConfig, workers: M, run: F)
+    where
+        T: Send + 'static,
+        W: Send + 'static,
+        M: FnOnce(&mut Queues<T>) -> Vec<W>,
+        F: Fn(W) -> (usize, Worker<T>) + Send + Sync + 'static,
+    {
+        let lent = self.queues.lock().unwrap().remove(&TypeId::of::<T>());
+        let mut queues = match lent.and_then(|queues| queues.downcast::<Queues<T>>().ok()) {
+            Some(queues) if queues.order == config.queue_order() => *queues,
+            _ => Queues::new(self.len(), config),
+        };
+
+        let run = Arc::new(run);
+        let (sender, receiver) = mpsc::channel();
+        let jobs = workers(&mut queues)
+            .into_iter()
+            .map(|worker| -> Job {
+                let run = run.clone();
+                let sender = sender.clone();
+                Box::new(move || {
+                    let _ = sender.send(run(worker));
+                })
+            })
+            .collect();
+        drop(sender);
+        self.run_all(jobs);
+
+        let mut returned: Vec<_> = receiver.into_iter().collect();
+        returned.sort_by_key(|&(index, _)| index);
+        for (_, worker) in returned {
+            while worker.pop().is_some() {}
+            queues.workers.push(worker);
+        }
+        self.queues
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Box::new(queues));
+    }
+
+    /// Run each job on its own thread and wait for all of them to complete.  Jobs are assigned to
+    /// threads in a round-robin fashion if there are more jobs than threads.
+    ///
+    /// If a job panics, the panic is propagated to the caller once all the jobs have completed;
+    /// the pool remains usable.
+    pub fn run_all(&self, jobs: Vec<Job>) {
+        let (done_sender, done_receiver) = mpsc::channel();
+        let count = jobs.len();
+
+        for (i, job) in jobs.into_iter().enumerate() {
+            let done_sender = done_sender.clone();
+            let worker = &self.workers[i % self.workers.len()];
+            worker
+                .jobs
+                .as_ref()
+                .unwrap()
+                .send(Box::new(move || {
+                    let result = panic::catch_unwind(AssertUnwindSafe(job));
+                    let _ = done_sender.send(result);
+                }))
+                .expect("Thread pool worker has exited.");
+        }
+
+        let mut panicked = None;
+        for _ in 0..count {
+            if let Err(payload) = done_receiver.recv().expect("Thread pool worker has exited.") {
+                panicked = Some(payload);
+            }
+        }
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// Stop and join all the threads.
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            worker.jobs.take();
+        }
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
@@ -6,7 +6,14 @@
 use api::prelude::*;
 //use std::cell::Cell;
 //use std::rc::Rc;
-use std::sync::{Arc,Mutex};
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+
+use common::hot_swap::Checkpoint;
+use parallel::memory::Reservation;
+use parallel::reset::{Clear, Rearmable, Rearmables};
+use sync::{Arc, AtomicUsize, Mutex, MutexGuard, Ordering::SeqCst, Weak};
 
 /*
 impl<T> SenderOnce for Cell<T> {
@@ -94,13 +101,123 @@ impl<T: Default> Receiver for Mutex<T> {
     }
 }
 
+/// A port holding at most one value, for types without a `Default` value.
+///
+/// Contrary to a `Mutex` port, which leaves the default value behind when receiving, a `SlotPort`
+/// becomes empty, so that it can hold values of any type.  Receiving from an empty port is a logic
+/// error (see the `api::port` module documentation) and panics.  This is the port used by the
+/// single-use runtimes, where each port is usually read once; reusable runtimes, which re-read
+/// ports that were not written to, use `Mutex` ports.
+#[derive(Debug)]
+pub struct SlotPort<T>(Mutex<Option<T>>);
+
+impl<T> SlotPort<T> {
+    /// Create a port holding `init`.
+    pub fn new(init: T) -> Self {
+        SlotPort(Mutex::new(Some(init)))
+    }
+
+    /// Create an empty port.
+    pub fn empty() -> Self {
+        SlotPort(Mutex::new(None))
+    }
+
+    /// Whether the port currently holds a value.
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_none()
+    }
+}
+
+impl<T> SenderOnce for SlotPort<T> {
+    type Item = T;
+
+    fn send_once(self, item: Self::Item) {
+        Sender::send(&self, item);
+    }
+}
+
+impl<T> SenderMut for SlotPort<T> {
+    fn send_mut(&mut self, item: Self::Item) {
+        Sender::send(self, item);
+    }
+}
+
+impl<T> Sender for SlotPort<T> {
+    fn send(&self, item: Self::Item) {
+        *self.0.lock().unwrap() = Some(item);
+    }
+}
+
+impl<T> ReceiverOnce for SlotPort<T> {
+    type Item = T;
+
+    fn recv_once(self) -> Self::Item {
+        Receiver::recv(&self)
+    }
+}
+
+impl<T> ReceiverMut for SlotPort<T> {
+    fn recv_mut(&mut self) -> Self::Item {
+        Receiver::recv(self)
+    }
+}
+
+impl<T> Receiver for SlotPort<T> {
+    fn recv(&self) -> Self::Item {
+        self.0
+            .lock()
+            .unwrap()
+            .take()
+            .expect("Received from an empty port.")
+    }
+}
+
+impl<T: Clone> Checkpoint for SlotPort<T> {
+    type State = Option<T>;
+
+    fn checkpoint(&self) -> Option<T> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn restore(&self, state: Option<T>) {
+        *self.0.lock().unwrap() = state
+    }
+}
+
+/// A receiver which can be polled, returning `None` instead of panicking while the port is empty.
+///
+/// Ports which always hold a value, such as `Mutex` ports, always return `Some`.
+pub trait TryReceiver {
+    /// The type of items that are read by this receiver.
+    type Item;
+
+    /// Receive the value held by the port, if any.
+    fn try_recv(&self) -> Option<Self::Item>;
+}
+
+impl<T: Default> TryReceiver for Mutex<T> {
+    type Item = T;
+
+    fn try_recv(&self) -> Option<T> {
+        Some(Receiver::recv(self))
+    }
+}
+
+impl<T> TryReceiver for SlotPort<T> {
+    type Item = T;
+
+    fn try_recv(&self) -> Option<T> {
+        self.0.lock().unwrap().take()
+    }
+}
+
 /// The sending part of a `RcPort`.  Wraps a `Sender` inside a reference counter pointer and expose
 /// the sending methods.
 ///
 /// The `RcSender` implements the whole family of `Sender` traits and passes on the data to the
 /// underlying sender.
 #[derive(Debug)]
-pub struct RcSender<T: Sender>(Arc<T>);
+pub struct RcSender<T: Sender>(Arc<RcSlot<T>>);
 
 impl<T: Sender> Clone for RcSender<T> {
     fn clone(&self) -> Self {
@@ -124,7 +241,7 @@ impl<T: Sender> SenderMut for RcSender<T> {
 
 impl<T: Sender> Sender for RcSender<T> {
     fn send(&self, item: Self::Item) {
-        Sender::send(&*self.0, item)
+        Sender::send(&self.0.slot, item)
     }
 }
 
@@ -134,7 +251,7 @@ impl<T: Sender> Sender for RcSender<T> {
 /// The `RcReceiver` implements the whole family of `Receiver` trants and gets the data from the
 /// underlying receiver.
 #[derive(Debug, Clone)]
-pub struct RcReceiver<T>(Arc<T>);
+pub struct RcReceiver<T>(Arc<RcSlot<T>>);
 
 impl<T: Receiver> ReceiverOnce for RcReceiver<T> {
     type Item = T::Item;
@@ -152,18 +269,87 @@ impl<T: Receiver> ReceiverMut for RcReceiver<T> {
 
 impl<T: Receiver> Receiver for RcReceiver<T> {
     fn recv(&self) -> Self::Item {
-        Receiver::recv(&*self.0)
+        Receiver::recv(&self.0.slot)
+    }
+}
+
+impl<T: TryReceiver> TryReceiver for RcReceiver<T> {
+    type Item = T::Item;
+
+    fn try_recv(&self) -> Option<Self::Item> {
+        self.0.slot.try_recv()
+    }
+}
+
+impl<T: Sender + Checkpoint> Checkpoint for RcSender<T> {
+    type State = T::State;
+
+    fn checkpoint(&self) -> Self::State {
+        self.0.slot.checkpoint()
+    }
+
+    fn restore(&self, state: Self::State) {
+        self.0.slot.restore(state)
+    }
+}
+
+impl<T: Checkpoint> Checkpoint for RcReceiver<T> {
+    type State = T::State;
+
+    fn checkpoint(&self) -> Self::State {
+        self.0.slot.checkpoint()
+    }
+
+    fn restore(&self, state: Self::State) {
+        self.0.slot.restore(state)
+    }
+}
+
+/// The data slot shared by the sender and the receiver of a `RcPort`, along with the memory
+/// accounted for the port, if any.
+#[derive(Debug)]
+struct RcSlot<T> {
+    slot: T,
+    _reservation: Option<Reservation>,
+}
+
+/// Ports are cleared when the graph is reset.  See the `parallel::reset` module.
+impl<T: Clear + Send + Sync> Rearmable for RcSlot<T> {
+    fn reset(&self) {
+        self.slot.clear()
     }
 }
 
 /// A reference counted port.
 #[derive(Debug)]
-pub struct RcPort<T: Sender + Receiver>(T);
+pub struct RcPort<T: Sender + Receiver>(Arc<RcSlot<T>>);
 
 impl<T: Sender + Receiver> RcPort<T> {
     /// Create a new `RcPort` from an underlying data slot, such as a cell.
     pub fn new(initial: T) -> Self {
-        RcPort(initial)
+        RcPort(Arc::new(RcSlot {
+            slot: initial,
+            _reservation: None,
+        }))
+    }
+
+    /// Attach the memory accounted for the port, which is released when both the sender and the
+    /// receiver are dropped.  See the `parallel::memory` module.
+    pub(crate) fn with_reservation(mut self, reservation: Option<Reservation>) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("The port was already split.")
+            ._reservation = reservation;
+        self
+    }
+
+    /// Clear the port when `rearmables` are reset.
+    pub(crate) fn tracked_by<'r>(self, rearmables: &Rearmables<'r>) -> Self
+    where
+        T: Clear + Send + Sync + 'r,
+    {
+        let slot: Weak<RcSlot<T>> = Arc::downgrade(&self.0);
+        rearmables.track(slot);
+        self
     }
 }
 
@@ -172,8 +358,873 @@ impl<T: Sender + Receiver> Port for RcPort<T> {
     type Receiver = RcReceiver<T>;
 
     fn split(self) -> (Self::Sender, Self::Receiver) {
-        let sender = RcSender(Arc::new(self.0));
+        let sender = RcSender(self.0);
         let receiver = RcReceiver(sender.0.clone());
         (sender, receiver)
     }
 }
+
+/// A port buffering values in a bounded FIFO queue.
+///
+/// Contrary to a `Mutex` port, which only holds a single value and silently overwrites it when
+/// sending twice, a `ChannelPort` keeps the values until they are received, in the order they were
+/// sent.  This allows producers to run at a higher rate than their consumers without losing data.
+///
+/// Receiving from an empty port returns the default value, just like for a `Mutex` port.  Sending
+/// to a full port is considered a logic error (see the `api::port` module documentation), and
+/// panics instead of losing data.
+#[derive(Debug)]
+pub struct ChannelPort<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: usize,
+}
+
+impl<T> ChannelPort<T> {
+    /// Create an empty port holding at most `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Buffered ports need a non-zero capacity.");
+
+        ChannelPort {
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// The number of values currently buffered.
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Whether no values are currently buffered.
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+}
+
+impl<T> SenderOnce for ChannelPort<T> {
+    type Item = T;
+
+    fn send_once(self, item: Self::Item) {
+        Sender::send(&self, item);
+    }
+}
+
+impl<T> SenderMut for ChannelPort<T> {
+    fn send_mut(&mut self, item: Self::Item) {
+        Sender::send(self, item);
+    }
+}
+
+impl<T> Sender for ChannelPort<T> {
+    fn send(&self, item: Self::Item) {
+        let mut queue = self.queue.lock().unwrap();
+        assert!(
+            queue.len() < self.capacity,
+            "Buffered port is full ({} values).",
+            self.capacity
+        );
+        queue.push_back(item);
+    }
+}
+
+impl<T: Default> ReceiverOnce for ChannelPort<T> {
+    type Item = T;
+
+    fn recv_once(self) -> Self::Item {
+        Receiver::recv(&self)
+    }
+}
+
+impl<T: Default> ReceiverMut for ChannelPort<T> {
+    fn recv_mut(&mut self) -> Self::Item {
+        Receiver::recv(self)
+    }
+}
+
+impl<T: Default> Receiver for ChannelPort<T> {
+    fn recv(&self) -> Self::Item {
+        self.queue.lock().unwrap().pop_front().unwrap_or_default()
+    }
+}
+
+impl<T> Clear for ChannelPort<T> {
+    fn clear(&self) {
+        self.queue.lock().unwrap().clear()
+    }
+}
+
+/// The saved state of a `ChannelPort` is the values it holds, in order.
+impl<T: Clone> Checkpoint for ChannelPort<T> {
+    type State = VecDeque<T>;
+
+    fn checkpoint(&self) -> VecDeque<T> {
+        self.queue.lock().unwrap().clone()
+    }
+
+    /// # Panics
+    ///
+    /// This panics if `state` holds more values than the capacity of the port.
+    fn restore(&self, state: VecDeque<T>) {
+        assert!(
+            state.len() <= self.capacity,
+            "Restored {} values into a port of capacity {}.",
+            state.len(),
+            self.capacity
+        );
+        *self.queue.lock().unwrap() = state
+    }
+}
+
+/// The two buffers of an `InPlacePort`.
+#[derive(Debug)]
+struct InPlaceBuffers<T> {
+    slots: [Mutex<T>; 2],
+    /// The index of the buffer read by the consumers.  The other buffer is written by the producer.
+    front: AtomicUsize,
+}
+
+/// A double-buffered port whose values are updated in place.
+///
+/// Nodes transforming a large state, e.g. a big matrix, along a pipeline would otherwise move or
+/// clone it at each hop, and allocate a new one for each execution of the pipeline.  An
+/// `InPlacePort` owns two buffers instead.  The producer fills the *back* buffer in place through
+/// `InPlaceSender::back`, then sends `()` to swap the buffers; the consumer receives an
+/// `InPlaceRef` to the *front* buffer, which it mutates in place with `InPlaceRef::lock`:
+///
+/// ```rust,ignore
+/// let (sender, receiver) = InPlacePort::new(Matrix::zeros(n), Matrix::zeros(n)).split();
+/// let writer = sender.clone();
+/// ... task: StrictTask::new(move |step| { writer.back().fill(step); ((),) }) ...
+/// ... outputs: (sender.with_activator(consumer),) ...
+/// ... task: StrictTask::new(|matrix: InPlaceRef<Matrix>| matrix.lock().transpose()) ...
+/// ```
+///
+/// The reference can be forwarded to the following stages on regular ports, so that the whole
+/// pipeline works on the same buffer.  Since the producer writes to the other buffer, it can
+/// prepare the next value while the pipeline processes the current one, even on parallel
+/// runtimes.  However, the buffers must not be swapped again before the pipeline is done with the
+/// current value (see the `api::port` module documentation).
+#[derive(Debug)]
+pub struct InPlacePort<T> {
+    buffers: Arc<InPlaceBuffers<T>>,
+}
+
+impl<T> InPlacePort<T> {
+    /// Create a port with the given initial buffers.  The front buffer is received until the
+    /// buffers are first swapped.
+    pub fn new(front: T, back: T) -> Self {
+        InPlacePort {
+            buffers: Arc::new(InPlaceBuffers {
+                slots: [Mutex::new(front), Mutex::new(back)],
+                front: AtomicUsize::new(0),
+            }),
+        }
+    }
+}
+
+impl<T> Port for InPlacePort<T> {
+    type Sender = InPlaceSender<T>;
+    type Receiver = InPlaceReceiver<T>;
+
+    fn split(self) -> (Self::Sender, Self::Receiver) {
+        let receiver = InPlaceReceiver {
+            buffers: self.buffers.clone(),
+        };
+        (
+            InPlaceSender {
+                buffers: self.buffers,
+            },
+            receiver,
+        )
+    }
+}
+
+/// The sending part of an `InPlacePort`.  Sending `()` swaps the buffers.
+#[derive(Debug)]
+pub struct InPlaceSender<T> {
+    buffers: Arc<InPlaceBuffers<T>>,
+}
+
+impl<T> Clone for InPlaceSender<T> {
+    fn clone(&self) -> Self {
+        InPlaceSender {
+            buffers: self.buffers.clone(),
+        }
+    }
+}
+
+impl<T> InPlaceSender<T> {
+    /// Lock the back buffer, i.e. the buffer which will be received after the next swap.
+    pub fn back(&self) -> MutexGuard<'_, T> {
+        let back = 1 - self.buffers.front.load(SeqCst);
+        self.buffers.slots[back].lock().unwrap()
+    }
+}
+
+impl<T> SenderOnce for InPlaceSender<T> {
+    type Item = ();
+
+    fn send_once(self, item: ()) {
+        Sender::send(&self, item)
+    }
+}
+
+impl<T> SenderMut for InPlaceSender<T> {
+    fn send_mut(&mut self, item: ()) {
+        Sender::send(self, item)
+    }
+}
+
+impl<T> Sender for InPlaceSender<T> {
+    fn send(&self, _item: ()) {
+        self.buffers.front.fetch_xor(1, SeqCst);
+    }
+}
+
+/// The receiving part of an `InPlacePort`.  Receiving returns a reference to the front buffer.
+#[derive(Debug)]
+pub struct InPlaceReceiver<T> {
+    buffers: Arc<InPlaceBuffers<T>>,
+}
+
+impl<T> ReceiverOnce for InPlaceReceiver<T> {
+    type Item = InPlaceRef<T>;
+
+    fn recv_once(self) -> InPlaceRef<T> {
+        Receiver::recv(&self)
+    }
+}
+
+impl<T> ReceiverMut for InPlaceReceiver<T> {
+    fn recv_mut(&mut self) -> InPlaceRef<T> {
+        Receiver::recv(self)
+    }
+}
+
+impl<T> Receiver for InPlaceReceiver<T> {
+    fn recv(&self) -> InPlaceRef<T> {
+        InPlaceRef {
+            buffers: self.buffers.clone(),
+            index: self.buffers.front.load(SeqCst),
+        }
+    }
+}
+
+/// A reference to the buffer of an `InPlacePort` received by a consumer.
+///
+/// The reference keeps pointing to the same buffer when the buffers are swapped.
+#[derive(Debug)]
+pub struct InPlaceRef<T> {
+    buffers: Arc<InPlaceBuffers<T>>,
+    index: usize,
+}
+
+impl<T> InPlaceRef<T> {
+    /// Lock the buffer for updating it in place.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.buffers.slots[self.index].lock().unwrap()
+    }
+}
+
+/// A port aggregating the values of many producers for a single consumer.
+///
+/// A `Mutex` port only holds a single value, so that the values of all but the last producer are
+/// lost when several producers send before the consumer is executed.  An `MpscPort` queues them
+/// instead, and its receiver yields all the values sent since the previous read, in order.
+///
+/// The producers' edges are created with `MpscSender::with_batch_activator`, and share a single
+/// activator of the consumer (typically in an `Arc`).  Only the first value of each batch, i.e.
+/// the first value sent to an empty queue, activates the consumer, so that it is scheduled at most
+/// once per batch, however many values are sent before it is executed:
+///
+/// ```rust,ignore
+/// let (sender, receiver) = MpscPort::new().split();
+/// let log = Arc::new(b.node(log_task(receiver.as_data_input())).add_activator());
+/// for worker in &workers {
+///     worker.connect(sender.clone().with_batch_activator(log.clone()));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MpscPort<T> {
+    queue: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T> MpscPort<T> {
+    /// Create a port with an empty queue.
+    pub fn new() -> Self {
+        MpscPort {
+            queue: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl<T> Default for MpscPort<T> {
+    fn default() -> Self {
+        MpscPort::new()
+    }
+}
+
+impl<T> Port for MpscPort<T> {
+    type Sender = MpscSender<T>;
+    type Receiver = MpscReceiver<T>;
+
+    fn split(self) -> (Self::Sender, Self::Receiver) {
+        let receiver = MpscReceiver {
+            queue: self.queue.clone(),
+        };
+        (MpscSender { queue: self.queue }, receiver)
+    }
+}
+
+/// The sending part of an `MpscPort`, which can be cloned for each producer.
+#[derive(Debug)]
+pub struct MpscSender<T> {
+    queue: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T> Clone for MpscSender<T> {
+    fn clone(&self) -> Self {
+        MpscSender {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<T> MpscSender<T> {
+    /// Bundle the sender with an activator of the consumer into an output edge activating the
+    /// consumer once per batch.  See `MpscPort`.
+    pub fn with_batch_activator<A>(self, activator: A) -> BatchInput<A, T> {
+        BatchInput {
+            activator,
+            sender: self,
+        }
+    }
+
+    /// Queue `item`, and return whether it is the first value of a batch.
+    fn push(&self, item: T) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        queue.push(item);
+        queue.len() == 1
+    }
+}
+
+impl<T> SenderOnce for MpscSender<T> {
+    type Item = T;
+
+    fn send_once(self, item: T) {
+        Sender::send(&self, item)
+    }
+}
+
+impl<T> SenderMut for MpscSender<T> {
+    fn send_mut(&mut self, item: T) {
+        Sender::send(self, item)
+    }
+}
+
+impl<T> Sender for MpscSender<T> {
+    fn send(&self, item: T) {
+        self.push(item);
+    }
+}
+
+/// The receiving part of an `MpscPort`.  Receiving drains the queue.
+#[derive(Debug)]
+pub struct MpscReceiver<T> {
+    queue: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T> ReceiverOnce for MpscReceiver<T> {
+    type Item = Vec<T>;
+
+    fn recv_once(self) -> Vec<T> {
+        Receiver::recv(&self)
+    }
+}
+
+impl<T> ReceiverMut for MpscReceiver<T> {
+    fn recv_mut(&mut self) -> Vec<T> {
+        Receiver::recv(self)
+    }
+}
+
+impl<T> Receiver for MpscReceiver<T> {
+    fn recv(&self) -> Vec<T> {
+        mem::take(&mut *self.queue.lock().unwrap())
+    }
+}
+
+/// An output edge into an `MpscPort`, activating the consumer on the first value of each batch.
+/// See `MpscSender::with_batch_activator`.
+#[derive(Debug, Clone)]
+pub struct BatchInput<A, T> {
+    activator: A,
+    sender: MpscSender<T>,
+}
+
+impl<S, A: ActivatorOnce<S>, T> OutputEdgeOnce<S> for BatchInput<A, T> {
+    type Item = T;
+
+    fn send_activate_once(self, scheduler: &mut S, item: T) {
+        if self.sender.push(item) {
+            self.activator.activate_once(scheduler)
+        }
+    }
+}
+
+impl<S, A: ActivatorMut<S>, T> OutputEdgeMut<S> for BatchInput<A, T> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: T) {
+        if self.sender.push(item) {
+            self.activator.activate_mut(scheduler)
+        }
+    }
+}
+
+impl<S, A: Activator<S>, T> OutputEdge<S> for BatchInput<A, T> {
+    fn send_activate(&self, scheduler: &mut S, item: T) {
+        if self.sender.push(item) {
+            self.activator.activate(scheduler)
+        }
+    }
+}
+
+/// A subscriber of a `BroadcastPort`.
+#[derive(Debug)]
+struct Subscriber<A> {
+    id: usize,
+    /// The sequence number of the next value to be read by the subscriber.
+    cursor: u64,
+    /// Whether the subscriber was activated since its last read.
+    notified: bool,
+    activator: Option<A>,
+}
+
+/// The state shared by the handles of a `BroadcastPort`.
+#[derive(Debug)]
+struct BroadcastState<T, A> {
+    /// The last values sent, oldest first.
+    values: VecDeque<T>,
+    /// The sequence number of the oldest value.
+    first: u64,
+    subscribers: Vec<Subscriber<A>>,
+    next_id: usize,
+}
+
+/// A port broadcasting its values to a dynamic set of subscribers.
+///
+/// Contrary to a `CloneOutput`, which clones each value into a fixed set of edges when sending,
+/// a `BroadcastPort` keeps the last `depth` values in a ring buffer which is shared by all the
+/// subscribers.  Each subscriber has its own read cursor, and receives all the values sent since
+/// its previous read, so that subscribers can be added at any time, e.g. by subgraphs built on a
+/// reusable runtime after some executions.  New subscribers first receive the values still held
+/// by the buffer:
+///
+/// ```rust,ignore
+/// let port = BroadcastPort::new(16);
+/// let output = port.sender();
+/// ...
+/// let receiver = port.subscribe();
+/// let id = receiver.id();
+/// let monitor = b.node(TaskNode { inputs: (receiver.as_data_input(),), ... }).add_activator();
+/// port.set_activator(id, monitor);
+/// ```
+///
+/// Sending a value activates the subscribers which were not activated since their last read,
+/// which schedules each consumer at most once per batch of values (see also `MpscPort`).  A
+/// subscriber which falls behind by more than `depth` values misses the oldest ones; the number of
+/// missed values is reported by `BroadcastReceiver::missed`.  Dropping a receiver unsubscribes it.
+pub struct BroadcastPort<T, A> {
+    state: Arc<Mutex<BroadcastState<T, A>>>,
+    depth: usize,
+}
+
+impl<T, A> Clone for BroadcastPort<T, A> {
+    fn clone(&self) -> Self {
+        BroadcastPort {
+            state: self.state.clone(),
+            depth: self.depth,
+        }
+    }
+}
+
+impl<T, A> fmt::Debug for BroadcastPort<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("BroadcastPort")
+            .field("depth", &self.depth)
+            .field("values", &state.values.len())
+            .field("subscribers", &state.subscribers.len())
+            .finish()
+    }
+}
+
+impl<T, A> BroadcastPort<T, A> {
+    /// Create a port keeping the last `depth` values.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `depth` is zero.
+    pub fn new(depth: usize) -> Self {
+        assert!(depth > 0, "Broadcast ports need a non-zero depth.");
+
+        BroadcastPort {
+            state: Arc::new(Mutex::new(BroadcastState {
+                values: VecDeque::with_capacity(depth),
+                first: 0,
+                subscribers: Vec::new(),
+                next_id: 0,
+            })),
+            depth,
+        }
+    }
+
+    /// The output edge sending values to all the subscribers.
+    pub fn sender(&self) -> BroadcastSender<T, A> {
+        BroadcastSender { port: self.clone() }
+    }
+
+    /// Subscribe to the port.  The subscriber first receives the values currently held by the
+    /// port.  It is not activated by new values until it is given an activator with
+    /// `set_activator`.
+    pub fn subscribe(&self) -> BroadcastReceiver<T, A> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        let cursor = state.first;
+        state.subscribers.push(Subscriber {
+            id,
+            cursor,
+            notified: false,
+            activator: None,
+        });
+
+        BroadcastReceiver {
+            port: self.clone(),
+            id,
+            missed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Activate `activator` when new values are sent to the subscriber `id`.  See
+    /// `BroadcastReceiver::id`.
+    pub fn set_activator(&self, id: SubscriberId, activator: A) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(subscriber) = state.subscribers.iter_mut().find(|sub| sub.id == id.0) {
+            subscriber.activator = Some(activator);
+        }
+    }
+
+    /// The number of subscribers.
+    pub fn subscribers(&self) -> usize {
+        self.state.lock().unwrap().subscribers.len()
+    }
+
+    /// Push `item` into the buffer, and call `notify` with the activators of the subscribers
+    /// which need to be activated.
+    fn push<F: FnMut(&A)>(&self, item: T, mut notify: F) {
+        let mut state = self.state.lock().unwrap();
+        if state.values.len() == self.depth {
+            state.values.pop_front();
+            state.first += 1;
+        }
+        state.values.push_back(item);
+        for subscriber in state.subscribers.iter_mut().filter(|sub| !sub.notified) {
+            if let Some(ref activator) = subscriber.activator {
+                subscriber.notified = true;
+                notify(activator);
+            }
+        }
+    }
+}
+
+/// The output edge of a `BroadcastPort`.  See `BroadcastPort::sender`.
+pub struct BroadcastSender<T, A> {
+    port: BroadcastPort<T, A>,
+}
+
+impl<T, A> Clone for BroadcastSender<T, A> {
+    fn clone(&self) -> Self {
+        BroadcastSender {
+            port: self.port.clone(),
+        }
+    }
+}
+
+impl<T, A> fmt::Debug for BroadcastSender<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BroadcastSender")
+            .field("port", &self.port)
+            .finish()
+    }
+}
+
+impl<S, T, A: Activator<S>> OutputEdgeOnce<S> for BroadcastSender<T, A> {
+    type Item = T;
+
+    fn send_activate_once(self, scheduler: &mut S, item: T) {
+        OutputEdge::send_activate(&self, scheduler, item)
+    }
+}
+
+impl<S, T, A: Activator<S>> OutputEdgeMut<S> for BroadcastSender<T, A> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: T) {
+        OutputEdge::send_activate(self, scheduler, item)
+    }
+}
+
+impl<S, T, A: Activator<S>> OutputEdge<S> for BroadcastSender<T, A> {
+    fn send_activate(&self, scheduler: &mut S, item: T) {
+        self.port
+            .push(item, |activator| activator.activate(scheduler))
+    }
+}
+
+/// The identifier of a subscriber of a `BroadcastPort`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriberId(usize);
+
+/// A subscriber of a `BroadcastPort`.  Receiving returns the values sent since the previous read.
+pub struct BroadcastReceiver<T, A> {
+    port: BroadcastPort<T, A>,
+    id: usize,
+    missed: AtomicUsize,
+}
+
+impl<T, A> fmt::Debug for BroadcastReceiver<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BroadcastReceiver")
+            .field("id", &self.id)
+            .field("missed", &self.missed())
+            .finish()
+    }
+}
+
+impl<T, A> BroadcastReceiver<T, A> {
+    /// The identifier of the subscriber in its port.
+    pub fn id(&self) -> SubscriberId {
+        SubscriberId(self.id)
+    }
+
+    /// The number of values which were evicted from the buffer before the subscriber read them.
+    pub fn missed(&self) -> usize {
+        self.missed.load(SeqCst)
+    }
+}
+
+impl<T: Clone, A> ReceiverOnce for BroadcastReceiver<T, A> {
+    type Item = Vec<T>;
+
+    fn recv_once(self) -> Vec<T> {
+        Receiver::recv(&self)
+    }
+}
+
+impl<T: Clone, A> ReceiverMut for BroadcastReceiver<T, A> {
+    fn recv_mut(&mut self) -> Vec<T> {
+        Receiver::recv(self)
+    }
+}
+
+impl<T: Clone, A> Receiver for BroadcastReceiver<T, A> {
+    fn recv(&self) -> Vec<T> {
+        let mut state = self.port.state.lock().unwrap();
+        let first = state.first;
+        let end = first + state.values.len() as u64;
+        let subscriber = state
+            .subscribers
+            .iter_mut()
+            .find(|sub| sub.id == self.id)
+            .unwrap();
+        let cursor = subscriber.cursor.max(first);
+        self.missed
+            .fetch_add((cursor - subscriber.cursor) as usize, SeqCst);
+        subscriber.cursor = end;
+        subscriber.notified = false;
+
+        state
+            .values
+            .iter()
+            .skip((cursor - first) as usize)
+            .cloned()
+            .collect()
+    }
+}
+
+impl<T, A> Drop for BroadcastReceiver<T, A> {
+    fn drop(&mut self) {
+        let id = self.id;
+        if let Ok(mut state) = self.port.state.lock() {
+            state.subscribers.retain(|sub| sub.id != id);
+        }
+    }
+}
+
+/// The value of a `WatchPort`, along with its version.
+#[derive(Debug)]
+struct Watched<T> {
+    version: u64,
+    value: T,
+}
+
+/// A port holding the latest value of a signal, along with a version counter.
+///
+/// Like a `Mutex` port, a `WatchPort` only holds the last value sent, and receiving it does not
+/// empty the port: this is the right port for sampled signals, whose consumers are only interested
+/// in the current value and may skip the intermediate ones.  Each value sent increments the
+/// version of the port, so that consumers can tell whether the value changed since they last read
+/// it with `WatchReceiver::has_changed`, without cloning it:
+///
+/// ```rust,ignore
+/// let (sender, receiver) = WatchPort::new(0.0).split();
+/// let mut seen = 0;
+/// ... task: StrictTask::new(move || {
+///     if receiver.has_changed(seen) {
+///         let (version, temperature) = receiver.latest();
+///         seen = version;
+///         redraw(temperature);
+///     }
+/// }) ...
+/// ```
+///
+/// The initial value has version 0.  Receivers can be cloned, e.g. for each consumer.
+#[derive(Debug)]
+pub struct WatchPort<T> {
+    watched: Arc<Mutex<Watched<T>>>,
+}
+
+impl<T> WatchPort<T> {
+    /// Create a port holding `initial`, with version 0.
+    pub fn new(initial: T) -> Self {
+        WatchPort {
+            watched: Arc::new(Mutex::new(Watched {
+                version: 0,
+                value: initial,
+            })),
+        }
+    }
+}
+
+impl<T: Default> Default for WatchPort<T> {
+    fn default() -> Self {
+        WatchPort::new(T::default())
+    }
+}
+
+impl<T> Port for WatchPort<T> {
+    type Sender = WatchSender<T>;
+    type Receiver = WatchReceiver<T>;
+
+    fn split(self) -> (Self::Sender, Self::Receiver) {
+        let receiver = WatchReceiver {
+            watched: self.watched.clone(),
+        };
+        (
+            WatchSender {
+                watched: self.watched,
+            },
+            receiver,
+        )
+    }
+}
+
+/// The sending part of a `WatchPort`.  Sending overwrites the value and increments the version.
+#[derive(Debug)]
+pub struct WatchSender<T> {
+    watched: Arc<Mutex<Watched<T>>>,
+}
+
+impl<T> Clone for WatchSender<T> {
+    fn clone(&self) -> Self {
+        WatchSender {
+            watched: self.watched.clone(),
+        }
+    }
+}
+
+impl<T> SenderOnce for WatchSender<T> {
+    type Item = T;
+
+    fn send_once(self, item: T) {
+        Sender::send(&self, item)
+    }
+}
+
+impl<T> SenderMut for WatchSender<T> {
+    fn send_mut(&mut self, item: T) {
+        Sender::send(self, item)
+    }
+}
+
+impl<T> Sender for WatchSender<T> {
+    fn send(&self, item: T) {
+        let mut watched = self.watched.lock().unwrap();
+        watched.version += 1;
+        watched.value = item;
+    }
+}
+
+/// The receiving part of a `WatchPort`.  Receiving returns a copy of the latest value.
+#[derive(Debug)]
+pub struct WatchReceiver<T> {
+    watched: Arc<Mutex<Watched<T>>>,
+}
+
+impl<T> Clone for WatchReceiver<T> {
+    fn clone(&self) -> Self {
+        WatchReceiver {
+            watched: self.watched.clone(),
+        }
+    }
+}
+
+impl<T> WatchReceiver<T> {
+    /// The version of the latest value, i.e. the number of values sent so far.
+    pub fn version(&self) -> u64 {
+        self.watched.lock().unwrap().version
+    }
+
+    /// Whether a value was sent since version `last_seen` was read.
+    pub fn has_changed(&self, last_seen: u64) -> bool {
+        self.version() > last_seen
+    }
+}
+
+impl<T: Clone> WatchReceiver<T> {
+    /// The latest value, along with its version.
+    pub fn latest(&self) -> (u64, T) {
+        let watched = self.watched.lock().unwrap();
+        (watched.version, watched.value.clone())
+    }
+}
+
+impl<T: Clone> ReceiverOnce for WatchReceiver<T> {
+    type Item = T;
+
+    fn recv_once(self) -> T {
+        Receiver::recv(&self)
+    }
+}
+
+impl<T: Clone> ReceiverMut for WatchReceiver<T> {
+    fn recv_mut(&mut self) -> T {
+        Receiver::recv(self)
+    }
+}
+
+impl<T: Clone> Receiver for WatchReceiver<T> {
+    fn recv(&self) -> T {
+        self.watched.lock().unwrap().value.clone()
+    }
+}
+
+impl<T: Clone> TryReceiver for WatchReceiver<T> {
+    type Item = T;
+
+    fn try_recv(&self) -> Option<T> {
+        Some(Receiver::recv(self))
+    }
+}
@@ -0,0 +1,82 @@
+//! Notifications when a part of the graph quiesces.
+//!
+//! Staged pipelines need to know when a stage is done, e.g. to set up the next stage as soon as
+//! the previous one has processed its inputs, without waiting for the whole execution to end.  A
+//! `Quiescence` group counts the nodes of a subgraph which are scheduled but not yet executed: the
+//! count is incremented when a node of the group is scheduled, decremented once it has executed,
+//! and the group's callback is called whenever it drops back to zero.
+//!
+//! Nodes are added to a group by building them in `ScopedGraphBuilder::in_group`, including the
+//! nodes of nested namespaces and fragments:
+//!
+//! ```rust,ignore
+//! let (decoded, stage1_done) = mpsc::channel();
+//! let stage1 = Quiescence::new(move || decoded.send(()).unwrap());
+//! b.in_group(&stage1, |b| b.instantiate("decode", &decoder, outputs));
+//! ```
+//!
+//! A node activated by another node of its group is scheduled before its predecessor completes, so
+//! that the callback only fires once the work flowing through the group is done.  However, the
+//! group goes idle, and the callback is called again, each time the group is activated anew from
+//! outside, e.g. once per execution of a reusable graph.
+//!
+//! The callback is called on the worker which completed the last node of the group, before it
+//! looks for other nodes to execute: it should return quickly, e.g. by notifying another thread or
+//! by injecting an event into the graph (see the `InjectorSpec` trait).  Only the parallel
+//! runtimes support groups; the other runtimes ignore them.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+
+struct QuiescenceInner {
+    /// The number of nodes of the group which are scheduled but not yet executed.
+    in_flight: AtomicUsize,
+    callback: Box<dyn Fn() + Send + Sync>,
+}
+
+/// A group of nodes calling a callback when none of its nodes are scheduled anymore.  See the
+/// module documentation.
+///
+/// Groups are cheap to clone: clones share the same count and callback.
+#[derive(Clone)]
+pub struct Quiescence {
+    inner: Arc<QuiescenceInner>,
+}
+
+impl Quiescence {
+    /// Create an empty group, calling `callback` each time it quiesces.
+    pub fn new<F: Fn() + Send + Sync + 'static>(callback: F) -> Self {
+        Quiescence {
+            inner: Arc::new(QuiescenceInner {
+                in_flight: AtomicUsize::new(0),
+                callback: Box::new(callback),
+            }),
+        }
+    }
+
+    /// The number of nodes of the group which are currently scheduled or executing.
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(SeqCst)
+    }
+
+    /// Record that a node of the group was scheduled.
+    pub(crate) fn scheduled(&self) {
+        self.inner.in_flight.fetch_add(1, SeqCst);
+    }
+
+    /// Record that a node of the group has executed, calling the callback if it was the last one.
+    pub(crate) fn completed(&self) {
+        if self.inner.in_flight.fetch_sub(1, SeqCst) == 1 {
+            (self.inner.callback)()
+        }
+    }
+}
+
+impl fmt::Debug for Quiescence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Quiescence")
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
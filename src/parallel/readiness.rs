@@ -0,0 +1,189 @@
+//! Integration with `select`/`poll` event loops.
+//!
+//! Applications built around an external event loop wait on file descriptors, and can't block on
+//! `Toexec::execute`.  A `Readiness` is a file descriptor (the reading end of a Unix socket pair)
+//! which becomes readable when the graph has something for the application:
+//!
+//!  - when a sink sends a value on an edge wrapped with `Notifier::output`, so that the
+//!    application can read the sink's port;
+//!  - when an execution started with `AsyncToexec::execute` has quiesced, once it was polled with
+//!    `Readiness::poll`;
+//!  - when a group of nodes quiesces, by calling `Notifier::notify` from the group's callback (see
+//!    `parallel::quiescence`).
+//!
+//! ```rust,ignore
+//! let readiness = Readiness::new()?;
+//! let mut execution = runtime.execute(4);
+//! loop {
+//!     poll(&[readiness.as_raw_fd(), socket.as_raw_fd()]);
+//!     if readiness.clear() {
+//!         if let Some((runtime, result)) = readiness.poll(&mut execution) {
+//!             break;
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Notifications are coalesced: the descriptor stays readable until `clear` is called, however
+//! many notifications were sent in between, so that the application should check all the sources
+//! it is interested in after each wake-up.
+//!
+//! This module is only available on Unix, with the `readiness` feature.
+
+use std::future::Future;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use api::prelude::*;
+
+use parallel::async_adapter::{AsyncToexec, Execution};
+use parallel::failure::ExecutionError;
+
+/// A file descriptor becoming readable when the graph notifies the application.  See the module
+/// documentation.
+#[derive(Debug)]
+pub struct Readiness {
+    reader: UnixStream,
+    notifier: Notifier,
+}
+
+impl Readiness {
+    /// Create a new descriptor, which is not readable until notified.
+    pub fn new() -> io::Result<Self> {
+        let (reader, writer) = UnixStream::pair()?;
+        reader.set_nonblocking(true)?;
+        writer.set_nonblocking(true)?;
+        Ok(Readiness {
+            reader,
+            notifier: Notifier {
+                writer: Arc::new(NotifierWriter { writer }),
+            },
+        })
+    }
+
+    /// A handle for making the descriptor readable, which can be sent to other threads.
+    pub fn notifier(&self) -> Notifier {
+        self.notifier.clone()
+    }
+
+    /// Make the descriptor not readable until the next notification, and return whether it was
+    /// notified since the last call.
+    pub fn clear(&self) -> bool {
+        let mut buffer = [0; 64];
+        let mut notified = false;
+        loop {
+            match (&self.reader).read(&mut buffer) {
+                Ok(0) => return notified,
+                Ok(_) => notified = true,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return notified,
+            }
+        }
+    }
+
+    /// Poll an execution without blocking, returning the runtime and the result of the execution
+    /// once it has completed.  If the execution has not completed yet, the descriptor is notified
+    /// once it completes.
+    pub fn poll(
+        &self,
+        execution: &mut Execution,
+    ) -> Option<(AsyncToexec, Result<(), ExecutionError>)> {
+        let waker = Waker::from(self.notifier.writer.clone());
+        match Pin::new(execution).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(completed) => Some(completed),
+            Poll::Pending => None,
+        }
+    }
+}
+
+impl AsRawFd for Readiness {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+}
+
+/// The writing end of the socket pair.
+#[derive(Debug)]
+struct NotifierWriter {
+    writer: UnixStream,
+}
+
+impl NotifierWriter {
+    fn notify(&self) {
+        // A full socket is readable already, so that the notification can be dropped.  Other
+        // errors mean that the `Readiness` was dropped, and nobody is listening anymore.
+        loop {
+            match (&self.writer).write(&[1]) {
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => {}
+                _ => return,
+            }
+        }
+    }
+}
+
+impl Wake for NotifierWriter {
+    fn wake(self: Arc<Self>) {
+        self.notify()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notify()
+    }
+}
+
+/// A handle making a `Readiness` readable.
+///
+/// Notifiers are cheap to clone, and can be sent to other threads.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    writer: Arc<NotifierWriter>,
+}
+
+impl Notifier {
+    /// Make the descriptor readable.
+    pub fn notify(&self) {
+        self.writer.notify()
+    }
+
+    /// Notify the descriptor each time a value is sent on `output`, after the value was sent.
+    pub fn output<E>(&self, output: E) -> NotifyOutput<E> {
+        NotifyOutput {
+            output,
+            notifier: self.clone(),
+        }
+    }
+}
+
+/// An output edge notifying a `Readiness` each time a value is sent.  See `Notifier::output`.
+#[derive(Debug)]
+pub struct NotifyOutput<E> {
+    output: E,
+    notifier: Notifier,
+}
+
+impl<S, E: OutputEdgeOnce<S>> OutputEdgeOnce<S> for NotifyOutput<E> {
+    type Item = E::Item;
+
+    fn send_activate_once(self, scheduler: &mut S, item: Self::Item) {
+        self.output.send_activate_once(scheduler, item);
+        self.notifier.notify()
+    }
+}
+
+impl<S, E: OutputEdgeMut<S>> OutputEdgeMut<S> for NotifyOutput<E> {
+    fn send_activate_mut(&mut self, scheduler: &mut S, item: Self::Item) {
+        self.output.send_activate_mut(scheduler, item);
+        self.notifier.notify()
+    }
+}
+
+impl<S, E: OutputEdge<S>> OutputEdge<S> for NotifyOutput<E> {
+    fn send_activate(&self, scheduler: &mut S, item: Self::Item) {
+        self.output.send_activate(scheduler, item);
+        self.notifier.notify()
+    }
+}
@@ -0,0 +1,148 @@
+//! Recording the schedule of a parallel execution, and replaying it on a single thread.
+//!
+//! Bugs depending on the interleaving of the workers are hard to reproduce: running the graph
+//! again usually executes its nodes in another order.  A runtime recording its schedule logs the
+//! order in which the nodes are executed, whatever the worker executing them, and the same graph
+//! can later be executed on the calling thread in exactly that order, e.g. under a debugger:
+//!
+//! ```rust,ignore
+//! let log = ScheduleLog::new();
+//! let mut runtime = Toexec::new();
+//! runtime.record_schedule(&log);
+//! build_and_activate(&mut runtime);
+//! runtime.execute(8);
+//!
+//! // Later, with the same graph.
+//! let mut runtime = Toexec::new();
+//! build_and_activate(&mut runtime);
+//! runtime.execute_replay(&log)?;
+//! ```
+//!
+//! Nodes are identified by the order in which they were scheduled: the nodes scheduled from
+//! outside of the workers are numbered in order, and the nodes scheduled by an executing node are
+//! numbered after it.  This identifies the same nodes in the replay as long as the graph is built
+//! and activated in the same way, and each node schedules the same nodes in the same order given
+//! the same inputs.  Nodes injected while the graph is executing (see `InjectorHandle`) are
+//! scheduled from another thread at an arbitrary time, and can't be replayed.
+//!
+//! A log can be kept across processes with `ScheduleLog::keys` and `ScheduleLog::from_keys`.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use parallel::failure::ExecutionError;
+
+/// The order in which the nodes of an execution were executed.
+///
+/// Logs are cheap to clone, and the clones share the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleLog {
+    keys: Arc<Mutex<Vec<u64>>>,
+}
+
+impl ScheduleLog {
+    /// Create an empty log.
+    pub fn new() -> Self {
+        ScheduleLog::default()
+    }
+
+    /// Create a log holding `keys`, as returned by `keys`.
+    pub fn from_keys(keys: Vec<u64>) -> Self {
+        ScheduleLog {
+            keys: Arc::new(Mutex::new(keys)),
+        }
+    }
+
+    /// The keys identifying the executed nodes, in order.
+    pub fn keys(&self) -> Vec<u64> {
+        self.keys.lock().unwrap().clone()
+    }
+
+    /// The number of executed nodes.
+    pub fn len(&self) -> usize {
+        self.keys.lock().unwrap().len()
+    }
+
+    /// Whether no node was executed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all the entries.
+    pub fn clear(&self) {
+        self.keys.lock().unwrap().clear()
+    }
+
+    pub(crate) fn push(&self, key: u64) {
+        self.keys.lock().unwrap().push(key)
+    }
+}
+
+/// The error returned by `Toexec::execute_replay` when the execution doesn't follow the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The node logged at `step` is not ready: the graph or the order of its activations differ
+    /// from the recorded execution.
+    Diverged {
+        /// The index of the entry in the log.
+        step: usize,
+        /// The key of the missing node.
+        key: u64,
+    },
+    /// The whole log was replayed, but `remaining` nodes are still ready for execution.
+    Unfinished {
+        /// The number of nodes left.
+        remaining: usize,
+    },
+    /// A node panicked.  The replay stops after the panicking node.
+    Failed(ExecutionError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Diverged { step, key } => write!(
+                f,
+                "the replay diverged at step {}: node {:#x} is not ready",
+                step, key
+            ),
+            ReplayError::Unfinished { remaining } => write!(
+                f,
+                "the replay ended with {} nodes still ready for execution",
+                remaining
+            ),
+            ReplayError::Failed(ref error) => write!(f, "the replay failed: {}", error),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+/// The parent of the nodes scheduled from outside of the workers.
+const ROOT: u64 = 0x726f_6f74;
+
+/// The parent of the nodes scheduled by a node which is not being replayed, e.g. an injected one.
+const UNKEYED: u64 = 0x0075_6e6b_6579_6564;
+
+/// Mix the bits of `x` (the SplitMix64 finalizer).
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// The key of the `index`-th node scheduled by the node `parent`.
+pub(crate) fn child_key(parent: u64, index: u64) -> u64 {
+    mix(parent ^ mix(index.wrapping_add(0x9e37_79b9_7f4a_7c15)))
+}
+
+/// The key of the `index`-th node scheduled from outside of the workers.
+pub(crate) fn root_key(index: u64) -> u64 {
+    child_key(ROOT, index)
+}
+
+/// The key of the `index`-th node scheduled by a worker outside of a replayable node.
+pub(crate) fn unkeyed_key(index: u64) -> u64 {
+    child_key(UNKEYED, index)
+}
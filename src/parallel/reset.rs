@@ -0,0 +1,73 @@
+//! Resetting reusable graphs between executions.
+//!
+//! Reusable nodes re-arm themselves once executed, so that a graph which ran to completion can be
+//! executed again as-is.  However, an execution which was interrupted, e.g. by `execute_until` or
+//! by a panicking node, leaves the graph in an inconsistent state: some nodes are still waiting
+//! for part of their activations, and some ports still hold the values of the previous inputs.
+//!
+//! The reusable runtime keeps a weak reference to each node and port created on it, which allows
+//! `Toexec::reset` to restore all the nodes to their initial pending count, and to clear all the
+//! ports, before sending the next batch of inputs:
+//!
+//! ```rust,ignore
+//! for batch in batches {
+//!     runtime.reset();
+//!     for (edge, value) in roots.iter_mut().zip(batch) {
+//!         edge.send_activate_mut(&mut runtime, value);
+//!     }
+//!     let _ = runtime.execute_until(4, &result);
+//! }
+//! ```
+//!
+//! Note that ports are cleared to their default value rather than their initial value, so that
+//! graphs relying on initialized ports, e.g. for accumulators, must send their initial values
+//! again after a reset.
+
+use sync::{Mutex, Weak};
+
+/// Graph elements which can be restored to their initial state between executions.
+pub(crate) trait Rearmable: Send + Sync {
+    /// Restore the initial state.  This must not be called during an execution.
+    fn reset(&self);
+}
+
+/// Data slots which can be emptied between executions.
+pub(crate) trait Clear {
+    /// Drop the values held by the slot.
+    fn clear(&self);
+}
+
+impl<T: Default> Clear for Mutex<T> {
+    fn clear(&self) {
+        *self.lock().unwrap() = T::default()
+    }
+}
+
+/// Weak references to the nodes and ports created on a runtime.
+#[derive(Default)]
+pub(crate) struct Rearmables<'r> {
+    elements: Mutex<Vec<Weak<dyn Rearmable + 'r>>>,
+}
+
+impl<'r> Rearmables<'r> {
+    /// Start tracking a node or port.
+    pub(crate) fn track(&self, element: Weak<dyn Rearmable + 'r>) {
+        let mut elements = self.elements.lock().unwrap();
+        // Forget about dropped elements before growing, as for the validation registry.
+        if elements.len() == elements.capacity() {
+            elements.retain(|element| element.upgrade().is_some());
+        }
+        elements.push(element)
+    }
+
+    /// Reset the tracked elements which are still alive.
+    pub(crate) fn reset(&self) {
+        let mut elements = self.elements.lock().unwrap();
+        elements.retain(|element| element.upgrade().is_some());
+        for element in elements.iter() {
+            if let Some(element) = element.upgrade() {
+                element.reset()
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Built-in smoke tests for the parallel single-use runtime.
+//!
+//! `Toexec::self_check` runs a battery of small graphs exercising the runtime's invariants --
+//! activation counts, work stealing, dynamic scheduling and early termination -- with a varying
+//! number of workers, and reports the checks which failed.  This is meant to be run when embedding
+//! the crate on a new platform, or after modifying the scheduling or termination policies.
+
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use api::prelude::*;
+use common::prelude::*;
+
+use parallel::port::{RcSender, SlotPort};
+use parallel::single_use::{RcActivator, RuntimeLoc, Toexec};
+
+/// How long a single check may run before it is considered deadlocked.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A check, run with the given number of workers.
+type Check = fn(usize) -> Result<(), String>;
+
+/// The checks run by `Toexec::self_check`.
+const CHECKS: &[(&str, Check)] = &[
+    ("fan-out", fan_out),
+    ("loop", chain),
+    ("dynamic spawn", dynamic_spawn),
+    ("cancellation", cancellation),
+];
+
+/// A check which failed for a given number of workers.
+#[derive(Debug, Clone)]
+pub struct SelfCheckFailure {
+    /// The name of the failed check.
+    pub check: &'static str,
+    /// The number of workers the check was run with.
+    pub workers: usize,
+    /// What went wrong: an invariant violation, a panic message or a timeout.
+    pub diagnostic: String,
+}
+
+impl fmt::Display for SelfCheckFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} workers): {}",
+            self.check, self.workers, self.diagnostic
+        )
+    }
+}
+
+/// The outcome of `Toexec::self_check`.
+#[derive(Debug, Clone, Default)]
+pub struct SelfCheckReport {
+    /// The number of check runs, counting each number of workers separately.
+    pub runs: usize,
+    /// The runs which failed.
+    pub failures: Vec<SelfCheckFailure>,
+}
+
+impl SelfCheckReport {
+    /// Whether all the checks passed.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for SelfCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} checks passed",
+            self.runs - self.failures.len(),
+            self.runs
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl Toexec<'static> {
+    /// Run the built-in checks with 1 to `max_workers` workers.
+    ///
+    /// Each check runs on a fresh runtime in a separate thread; panics are caught and reported, and
+    /// checks which do not terminate within a few seconds are reported as deadlocked.  Note that
+    /// the threads of deadlocked checks are leaked.
+    pub fn self_check(max_workers: usize) -> SelfCheckReport {
+        let mut report = SelfCheckReport::default();
+
+        for workers in 1..=max_workers {
+            for &(name, check) in CHECKS {
+                report.runs += 1;
+                if let Err(diagnostic) = run_check(check, workers) {
+                    report.failures.push(SelfCheckFailure {
+                        check: name,
+                        workers,
+                        diagnostic,
+                    });
+                }
+            }
+        }
+
+        report
+    }
+}
+
+fn run_check(check: Check, workers: usize) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| check(workers)))
+            .unwrap_or_else(|payload| Err(format!("panicked: {}", panic_message(&*payload))));
+        // The receiver is gone if the check timed out.
+        let _ = sender.send(result);
+    });
+
+    receiver
+        .recv_timeout(CHECK_TIMEOUT)
+        .unwrap_or_else(|_| Err("did not terminate, the runtime may be deadlocked".to_string()))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| &s[..]))
+        .unwrap_or("<unknown payload>")
+}
+
+/// A node counting its executions and activating a list of nodes.
+struct Fork {
+    counter: Arc<AtomicUsize>,
+    next: Vec<RcActivator<'static>>,
+}
+
+impl NodeOnce<RuntimeLoc<'static>> for Fork {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+        self.counter.fetch_add(1, SeqCst);
+        for activator in self.next {
+            activator.activate_once(scheduler)
+        }
+    }
+}
+
+/// A node scheduling a new node from within the runtime until `remaining` reaches zero, with
+/// `width` children per node.  The result port, if any, is written to after `target` nodes have
+/// been executed.
+struct Spawn {
+    remaining: usize,
+    width: usize,
+    counter: Arc<AtomicUsize>,
+    result: Option<(usize, RcSender<SlotPort<Option<usize>>>)>,
+}
+
+impl NodeOnce<RuntimeLoc<'static>> for Spawn {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'static>) {
+        let count = self.counter.fetch_add(1, SeqCst) + 1;
+        if let Some((target, ref sender)) = self.result {
+            if count == target {
+                sender.send(Some(count))
+            }
+        }
+
+        if self.remaining == 0 {
+            return;
+        }
+        for _ in 0..self.width {
+            scheduler.schedule(Box::new(Spawn {
+                remaining: self.remaining - 1,
+                width: self.width,
+                counter: self.counter.clone(),
+                result: self.result.clone(),
+            }))
+        }
+    }
+}
+
+/// Run a root `Spawn` node to completion and return the number of executed nodes.
+fn run_spawn(workers: usize, remaining: usize, width: usize) -> Result<usize, String> {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let mut runtime = Toexec::new();
+    let root = runtime.build_scope(|b| {
+        b.node(Spawn {
+            remaining,
+            width,
+            counter: counter.clone(),
+            result: None,
+        })
+        .add_activator()
+    });
+    root.activate_once(&mut runtime);
+    runtime
+        .execute(workers)
+        .map_err(|error| error.to_string())?;
+
+    Ok(counter.load(SeqCst))
+}
+
+/// A root fanning out to many nodes, which all fan in to a single sink.  The sink must execute
+/// exactly once, after all the other nodes.
+fn fan_out(workers: usize) -> Result<(), String> {
+    const WIDTH: usize = 64;
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let mut runtime = Toexec::new();
+
+    let root = runtime.build_scope(|b| {
+        let (observed, sink_counter) = (observed.clone(), counter.clone());
+        let mut sink = b.node(TaskNode {
+            inputs: (),
+            outputs: (),
+            task: StrictTask::new(move || observed.lock().unwrap().push(sink_counter.load(SeqCst))),
+        });
+
+        let leaves = (0..WIDTH)
+            .map(|_| {
+                b.node(Fork {
+                    counter: counter.clone(),
+                    next: vec![sink.add_activator()],
+                })
+                .add_activator()
+            })
+            .collect();
+
+        b.node(Fork {
+            counter: counter.clone(),
+            next: leaves,
+        })
+        .add_activator()
+    });
+    root.activate_once(&mut runtime);
+    runtime
+        .execute(workers)
+        .map_err(|error| error.to_string())?;
+
+    let observed = observed.lock().unwrap();
+    if *observed != [WIDTH + 1] {
+        return Err(format!(
+            "expected the sink to run once after {} nodes, observed runs after {:?}",
+            WIDTH + 1,
+            *observed
+        ));
+    }
+    Ok(())
+}
+
+/// A long chain of nodes, each scheduling the next one.
+fn chain(workers: usize) -> Result<(), String> {
+    const LENGTH: usize = 1000;
+
+    match run_spawn(workers, LENGTH - 1, 1)? {
+        LENGTH => Ok(()),
+        count => Err(format!("expected {} iterations, got {}", LENGTH, count)),
+    }
+}
+
+/// A binary tree of dynamically scheduled nodes.
+fn dynamic_spawn(workers: usize) -> Result<(), String> {
+    const DEPTH: usize = 9;
+
+    let expected = (1 << DEPTH) - 1;
+    match run_spawn(workers, DEPTH - 1, 2)? {
+        count if count == expected => Ok(()),
+        count => Err(format!("expected {} nodes, got {}", expected, count)),
+    }
+}
+
+/// An unbounded chain of nodes, stopped with `execute_until` once it has written its result.
+fn cancellation(workers: usize) -> Result<(), String> {
+    const TARGET: usize = 100;
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let mut runtime = Toexec::new();
+    let (root, result) = runtime.build_scope(|b| {
+        let (sender, receiver) = b.port(None).split();
+        let root = b
+            .node(Spawn {
+                remaining: usize::MAX,
+                width: 1,
+                counter: counter.clone(),
+                result: Some((TARGET, sender)),
+            })
+            .add_activator();
+        (root, receiver)
+    });
+    root.activate_once(&mut runtime);
+
+    match runtime
+        .execute_until(workers, result)
+        .map_err(|error| error.to_string())?
+    {
+        TARGET => Ok(()),
+        value => Err(format!("expected result {}, got {}", TARGET, value)),
+    }
+}
+
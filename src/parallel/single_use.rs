@@ -1,14 +1,34 @@
 //! Sequential implementation of a single-use runtime with reference-counted activators.
 
 use crossbeam::deque;
-use std::thread;
-use std::marker::PhantomData;
-use std::sync::{Arc,Mutex}; // ,Condvar retiré
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Weak}; // ,Condvar retiré
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::time::{Duration, Instant};
 
 use api::prelude::*;
+use common::interface::{GraphOutputs, OutputSpec};
+use common::run::{ExecuteSpec, PauseSpec, RunMode};
+use common::port::CheckedPort;
+use common::timer::{Tick, TickPayload, Timer, TimerHandle, TimerSource};
+use custom::rc::{OnceActivator, OnceBuilder, OnceInner};
 
-use parallel::port::RcPort;
+use parallel::admission::{AdmissionContext, AdmissionError, AdmissionPolicy, Admissions};
+use parallel::breakpoint::{BreakContext, BreakEvent, BreakMode, Breakpoints};
+use parallel::memory::{Accountant, Reservation};
+use parallel::affinity::{Affinity, Mailboxes};
+use parallel::config::{self, RuntimeConfig};
+use parallel::failure::{ExecutionError, Failures};
+use parallel::pool::{Queues, ThreadPool};
+use parallel::quiescence::Quiescence;
+use parallel::replay::{self, ReplayError, ScheduleLog};
+use parallel::port::{ChannelPort, RcPort, SlotPort, TryReceiver};
+use parallel::termination::{Backoff, HelpError, Termination};
+use parallel::trace::{TraceHook, TraceSlot};
+use parallel::validation::Registry;
+use sync::Mutex;
 
 /* 
 Implémentation d'un compteur atomique 
@@ -44,94 +64,249 @@ impl CompteurAtomic for Compteur {
 }
 
 
-/// The inner structure for a single-use activator, containing the pending count and the node
-/// handle.
-struct RcActivatorInner<'r> {
-    /// The pending count.
-    pending: AtomicUsize, // seqcst
+/// The information the parallel runtime keeps about a single-use node, in addition to the pending
+/// count and label shared by all the runtimes.  See `custom::rc`.
+pub struct NodeExtra {
+    /// The worker the node is pinned to, if any.
+    affinity: Affinity,
+
+    /// The quiescence groups the node belongs to.
+    groups: Mutex<Vec<Quiescence>>,
 
-    /// The underlying node to schedule.  Note that we store a Box of a trait object here, instead
-    /// of using a type parameter and embedding the node in the structure.  This is because of a
-    /// Rust limitation which prevents us from calling a method with `self` as argument on a trait
-    /// object -- the same reason why we use `NodeBox`.  Unfortunately, that trick only works for
-    /// `Box`, which the Rust compiler has special knowledge of -- so instead we use an extra level
-    /// of indirection and put a box here.
-    handle: Box<RuntimeNode<'r>>,
+    /// The memory accounted for the node, if any.  This is kept until the node is executed.
+    reservation: Option<Reservation>,
 }
 
+impl NodeExtra {
+    fn new(reservation: Option<Reservation>) -> Self {
+        NodeExtra {
+            affinity: Affinity::new(),
+            groups: Mutex::new(Vec::new()),
+            reservation,
+        }
+    }
+}
+
+/// The inner structure for a single-use activator, containing the pending count and the node
+/// handle.
+type RcActivatorInner<'r> = OnceInner<RuntimeNode<'r>, NodeExtra>;
+
 impl<'r> RcActivatorInner<'r> {
-    fn new<N: NodeBox<RuntimeLoc<'r>> + Send + Sync + 'r>(node: N) -> Self { //+sync ?
-        RcActivatorInner {
-            pending: AtomicUsize::new(0),
-            handle: Box::new(node),
+    /// Record an activation, and return the node to schedule, along with the worker it is pinned
+    /// to, if this was the last one.
+    ///
+    /// If there is a breakpoint on the node, its callbacks are invoked, and the returned node is
+    /// wrapped so that they are invoked again when it is executed.
+    fn activate(
+        self: Arc<Self>,
+        breakpoints: Option<&Breakpoints>,
+        worker: Option<usize>,
+    ) -> Option<(Box<RuntimeNode<'r>>, Option<usize>)> {
+        // Look for a breakpoint before decrementing the pending count, since the inner structure
+        // may be consumed by another activator as soon as it is decremented.
+        let breakpoint = breakpoints.and_then(|breakpoints| {
+            let label = self.label.lock().unwrap().clone()?;
+            if breakpoints.is_set(&label) {
+                Some((breakpoints, label, self.activators.load(SeqCst)))
+            } else {
+                None
+            }
+        });
+
+        let (handle, pending) = match self.release() {
+            Ok(inner) => {
+                let affinity = inner.extra.affinity.get();
+                let mut node: Box<RuntimeNode<'r>> = match inner.extra.reservation {
+                    Some(reservation) => Box::new(Accounted {
+                        node: inner.handle,
+                        _reservation: reservation,
+                    }),
+                    None => inner.handle,
+                };
+                if let Some(label) = inner.label.into_inner().unwrap() {
+                    node = Box::new(Labeled { node, label });
+                }
+                let groups = inner.extra.groups.into_inner().unwrap();
+                if !groups.is_empty() {
+                    for group in &groups {
+                        group.scheduled();
+                    }
+                    node = Box::new(InGroups { node, groups });
+                }
+                (Some((node, affinity)), 0)
+            }
+            Err(pending) => (None, pending),
+        };
+
+        match breakpoint {
+            Some((breakpoints, label, activators)) => {
+                breakpoints.hit(&BreakContext {
+                    label: label.clone(),
+                    event: BreakEvent::Activated,
+                    worker,
+                    instant: Instant::now(),
+                    pending,
+                    activators,
+                });
+                handle.map(|(node, affinity)| -> (Box<RuntimeNode<'r>>, _) {
+                    let node = Box::new(Break {
+                        node,
+                        label,
+                        activators,
+                    });
+                    (node, affinity)
+                })
+            }
+            None => handle,
         }
     }
 }
 
 /// A reference-counted, single-use activator.
 ///
-/// The activator contains a handle to a node, as well as a counter for the number of activations
-/// remaining before the node should be scheduled.
-///
-/// When the node is finalized, the counter is set to the number of activators created.  It is
-/// decremented by one on each activation, and the node is scheduled when the counter reaches zero.
-/// Since activating consumes an activator, we ensure that the pending count only ever reaches zero
-/// if all activators have been called.
-pub struct RcActivator<'r> {
-    inner: Arc<RcActivatorInner<'r>>,
-}
+/// The node is scheduled when all its activators have been activated.  See `custom::rc`.
+pub type RcActivator<'r> = OnceActivator<RuntimeNode<'r>, NodeExtra>;
 
 impl<'r> ActivatorOnce<RuntimeLoc<'r>> for RcActivator<'r> {
     fn activate_once(self, scheduler: &mut RuntimeLoc<'r>) {
-        if self.inner.pending.fetch_sub(1,SeqCst) == 1 {
-            scheduler.schedule(Arc::try_unwrap(self.inner).ok().unwrap().handle)
+        let breakpoints = scheduler.breakpoints.clone();
+        // The label is only needed by the admission policy, and must be read before the node is
+        // consumed.
+        let label = scheduler.admission.as_ref().and_then(|_| self.label());
+        if let Some((handle, affinity)) = self
+            .inner
+            .activate(breakpoints.as_deref(), Some(scheduler.index))
+        {
+            scheduler.submit(label, handle, affinity)
         }
     }
 }
 
 impl<'r> ActivatorOnce<Toexec<'r>> for RcActivator<'r> {
     fn activate_once(self, scheduler: &mut Toexec<'r>) {
-        if self.inner.pending.fetch_sub(1,SeqCst) == 1 {
-            scheduler.ready.push(Arc::try_unwrap(self.inner).ok().unwrap().handle)
+        let breakpoints = scheduler.breakpoints.clone();
+        let label = scheduler.admission.as_ref().and_then(|_| self.label());
+        if let Some((handle, affinity)) = self.inner.activate(breakpoints.as_deref(), None) {
+            scheduler.submit(label, handle, affinity)
         }
     }
 }
 
-/// A builder for single-use nodes.  Allow creation of activators and arms them when finalized.
-///
-/// Note that once the builder is created, no modifications to the node are permitted (the builder
-/// does not implement the `NodeBorrowMut` trait).  This is due to the fact that we need to store a
-/// (boxed) trait object inside the activator in order to be able to call it later using
-/// `execute_box`; see the documentation on `RcActivatorInner`.
-pub struct RcBuilder<'r, N> {
-    inner: Arc<RcActivatorInner<'r>>,
-    _marker: PhantomData<*const N>,
-    num_activators: usize,
+/// A node with a breakpoint, invoking the breakpoint's callbacks before being executed.
+struct Break<'r> {
+    node: Box<RuntimeNode<'r>>,
+    label: Label,
+    activators: usize,
+}
+
+impl<'r> NodeOnce<RuntimeLoc<'r>> for Break<'r> {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        if let Some(ref breakpoints) = scheduler.breakpoints {
+            breakpoints.hit(&BreakContext {
+                label: self.label,
+                event: BreakEvent::Executed,
+                worker: Some(scheduler.index),
+                instant: Instant::now(),
+                pending: 0,
+                activators: self.activators,
+            });
+        }
+        self.node.execute_box(scheduler)
+    }
+}
+
+/// A scheduled node, holding the memory accounted for it until it is executed.
+struct Accounted<'r> {
+    node: Box<RuntimeNode<'r>>,
+    _reservation: Reservation,
+}
+
+impl<'r> NodeOnce<RuntimeLoc<'r>> for Accounted<'r> {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        self.node.execute_box(scheduler)
+    }
+}
+
+/// A scheduled node with a label, which is recorded as the node being executed so that it can be
+/// reported if the node panics.
+struct Labeled<'r> {
+    node: Box<RuntimeNode<'r>>,
+    label: Label,
+}
+
+impl<'r> NodeOnce<RuntimeLoc<'r>> for Labeled<'r> {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        scheduler.trace(|hook| hook.on_execute_labeled(scheduler.index, &self.label));
+        // This is not restored if the node panics.
+        let previous = scheduler.executing.replace(self.label);
+        self.node.execute_box(scheduler);
+        scheduler.executing = previous;
+    }
+}
+
+/// A scheduled node belonging to quiescence groups, which are notified once it has executed.
+struct InGroups<'r> {
+    node: Box<RuntimeNode<'r>>,
+    groups: Vec<Quiescence>,
+}
+
+impl<'r> NodeOnce<RuntimeLoc<'r>> for InGroups<'r> {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        self.node.execute_box(scheduler);
+        for group in &self.groups {
+            group.completed();
+        }
+    }
 }
 
-impl<'r, N: NodeBox<RuntimeLoc<'r>> + Send + Sync + 'r> RcBuilder<'r, N> {  //MMM
-    fn new(node: N) -> Self {
-        RcBuilder {
-            inner: Arc::new(RcActivatorInner::new(node)),
-            _marker: PhantomData,
-            num_activators: 0,
+/// A scheduled node with a key identifying it across executions, which is logged when it is
+/// executed.  See the `parallel::replay` module.
+struct Keyed<'r> {
+    node: Box<RuntimeNode<'r>>,
+    key: u64,
+}
+
+impl<'r> NodeOnce<RuntimeLoc<'r>> for Keyed<'r> {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        if let Some(ref log) = scheduler.recording {
+            log.push(self.key);
         }
+        let previous = scheduler.keying.replace((self.key, 0));
+        self.node.execute_box(scheduler);
+        scheduler.keying = previous;
     }
 }
 
+/// The approximate memory used by a node of type `N`.
+fn node_size<N>() -> usize {
+    mem::size_of::<RcActivatorInner<'static>>() + mem::size_of::<N>()
+}
+
+/// A builder for single-use nodes.  Allow creation of activators and arms them when finalized.
+pub type RcBuilder<'r, N> = OnceBuilder<RuntimeNode<'r>, N, NodeExtra>;
+
 impl<'r, N: NodeBox<RuntimeLoc<'r>> + Send + 'r> NodeBuilder<Toexec<'r>> // + Sync ?
     for RcBuilder<'r, N>
 {
     type Node = N;
     fn add_activator(&mut self) -> RcActivator<'r> {
-        self.num_activators += 1;
-
-        RcActivator {
-            inner: self.inner.clone(),
-        }
+        self.activator()
     }
-    fn finalize(&mut self, _runtime: &mut Toexec<'r>) { // MODIFIÉ
-        self.inner.pending.store(self.num_activators,SeqCst);
+    fn set_label(&mut self, label: Label) {
+        self.label(label)
+    }
+    fn set_affinity(&mut self, worker: usize) {
+        self.inner.extra.affinity.set(worker)
+    }
+    fn add_to_group(&mut self, group: &Quiescence) {
+        self.inner.extra.groups.lock().unwrap().push(group.clone())
+    }
+    fn finalize(&mut self, runtime: &mut Toexec<'r>) { // MODIFIÉ
+        self.arm();
+        if let Some(ref registry) = runtime.registry {
+            let inner: Weak<RcActivatorInner<'r>> = Arc::downgrade(&self.inner);
+            registry.track(inner);
+        }
     }
 }
 
@@ -140,14 +315,23 @@ impl<'r, N: NodeBox<RuntimeLoc<'r>> + Send + 'r> NodeBuilder<RuntimeLoc<'r>> //
 {
     type Node = N;
     fn add_activator(&mut self) -> RcActivator<'r> {
-        self.num_activators += 1;
-
-        RcActivator {
-            inner: self.inner.clone(),
-        }
+        self.activator()
+    }
+    fn set_label(&mut self, label: Label) {
+        self.label(label)
+    }
+    fn set_affinity(&mut self, worker: usize) {
+        self.inner.extra.affinity.set(worker)
     }
-    fn finalize(&mut self, _runtime: &mut RuntimeLoc<'r>) { // MODIFIÉ
-        self.inner.pending.store(self.num_activators,SeqCst);
+    fn add_to_group(&mut self, group: &Quiescence) {
+        self.inner.extra.groups.lock().unwrap().push(group.clone())
+    }
+    fn finalize(&mut self, runtime: &mut RuntimeLoc<'r>) { // MODIFIÉ
+        self.arm();
+        if let Some(ref registry) = runtime.registry {
+            let inner: Weak<RcActivatorInner<'r>> = Arc::downgrade(&self.inner);
+            registry.track(inner);
+        }
     }
 }
 
@@ -155,135 +339,1044 @@ impl<'r, N: NodeBox<RuntimeLoc<'r>> + Send + 'r> NodeBuilder<RuntimeLoc<'r>> //
 
 type RuntimeNode<'r> = dyn NodeBox<RuntimeLoc<'r>> + Send + Sync + 'r;
 
+/// Nodes pushed from outside of the workers, e.g. when an external event completes.
+type InjectQueue<'r> = Mutex<VecDeque<Box<RuntimeNode<'r>>>>;
+
 pub struct Toexec<'r> {
     pub ready: Vec<Box<RuntimeNode<'r>>>,
+    /// The ready nodes pinned to a worker, along with the index of the worker.
+    pinned: Vec<(usize, Box<RuntimeNode<'r>>)>,
+    /// The named outputs of the graphs built on this runtime.
+    outputs: GraphOutputs,
+    /// Nodes pushed by external events.  This is shared with the workers.
+    injected: Arc<InjectQueue<'r>>,
+    /// The termination state.  This is kept across executions in order to track the nodes waiting
+    /// for external events.
+    termination: Arc<Termination>,
+    /// The hook notified of scheduling events, if any.
+    trace: TraceSlot,
+    /// The finalized nodes, when created with `with_validation`.
+    registry: Option<Arc<Registry<'r>>>,
+    /// The breakpoints set with `set_breakpoint`, if any.
+    breakpoints: Option<Arc<Breakpoints>>,
+    /// The admission policy set with `set_admission_policy`, if any.
+    admission: Option<Arc<Admissions<Box<RuntimeNode<'r>>>>>,
+    /// The log set with `record_schedule`, if any.
+    recording: Option<ScheduleLog>,
+    /// The memory accountant, when created with `with_accountant`.
+    accountant: Option<Arc<Accountant>>,
+    /// The work stealing configuration.
+    config: RuntimeConfig,
+    /// The nodes which panicked during the current execution.
+    failures: Arc<Failures>,
+    /// The periodic timers owned by the runtime, see `add_timer`.
+    timers: Vec<Timer>,
+    /// Whether executions may run, see `pause`.
+    mode: RunMode,
 }
 
+/// A worker doing work stealing.
 pub struct RuntimeLoc<'r> {
     ready: deque::Worker<Box<RuntimeNode<'r>>>,
     stealers: Vec<deque::Stealer<Box<RuntimeNode<'r>>>>,
-    // condvar: Arc<Condvar> // la méthode essayée avec des signaux ne fonctionne pas
+    /// The nodes pinned to each worker.
+    pinned: Arc<Mailboxes<Box<RuntimeNode<'r>>>>,
+    injected: Arc<InjectQueue<'r>>,
+    termination: Arc<Termination>,
+    /// The index of the worker, for tracing.
+    index: usize,
+    trace: TraceSlot,
+    registry: Option<Arc<Registry<'r>>>,
+    breakpoints: Option<Arc<Breakpoints>>,
+    admission: Option<Arc<Admissions<Box<RuntimeNode<'r>>>>>,
+    accountant: Option<Arc<Accountant>>,
+    config: RuntimeConfig,
+    failures: Arc<Failures>,
+    /// The label of the node being executed, if any.
+    executing: Option<Label>,
+    /// The log of the executed nodes, when recording the schedule.
+    recording: Option<ScheduleLog>,
+    /// Whether the scheduled nodes are keyed, i.e. when recording or replaying the schedule.
+    keyed: bool,
+    /// The key of the node being executed, along with the number of nodes it scheduled so far.
+    keying: Option<(u64, u64)>,
+    /// The number of nodes scheduled outside of a keyed node.
+    unkeyed: u64,
+    /// The nodes ready for execution, by key, when replaying a schedule.
+    replaying: Option<HashMap<u64, Box<RuntimeNode<'r>>>>,
+}
+
+/// A handle for nodes waiting on external events.
+///
+/// Such nodes must be registered with `start` while they are executing, and either `complete` or
+/// `inject` a node which will eventually call `complete`; the runtime does not consider the graph
+/// as quiesced while there are registered nodes.
+#[derive(Clone)]
+pub(crate) struct External<'r> {
+    injected: Arc<InjectQueue<'r>>,
+    termination: Arc<Termination>,
+}
+
+impl<'r> External<'r> {
+    /// Register a node waiting on an external event.
+    pub(crate) fn start(&self) {
+        self.termination.external_started()
+    }
+
+    /// Record that a registered node is done waiting.
+    pub(crate) fn complete(&self) {
+        self.termination.external_completed()
+    }
+
+    /// Push a node to be executed by the workers.  This can be called from any thread.
+    pub(crate) fn inject(&self, node: Box<RuntimeNode<'r>>) {
+        push_injected(&self.injected, &self.termination, node)
+    }
+}
+
+/// Push a node to the queue of injected nodes.
+///
+/// Injected nodes are counted as scheduled as soon as they are pushed, so that the graph does not
+/// quiesce while they are waiting in the queue.  The count is updated with the queue locked, since
+/// the nodes left in the queue are counted again when starting an execution (see
+/// `Toexec::workers`).
+fn push_injected<'r>(
+    queue: &InjectQueue<'r>,
+    termination: &Termination,
+    node: Box<RuntimeNode<'r>>,
+) {
+    {
+        let mut queue = queue.lock().unwrap();
+        termination.scheduled();
+        queue.push_back(node);
+    }
+    termination.notify();
+}
+
+/// A node sending a value on an edge when executed.  This is used to inject external events.
+struct Injected<E, T> {
+    edge: E,
+    item: T,
+}
+
+impl<'r, T, E: OutputEdgeOnce<RuntimeLoc<'r>, Item = T>> NodeOnce<RuntimeLoc<'r>>
+    for Injected<E, T>
+{
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        self.edge.send_activate_once(scheduler, self.item)
+    }
+}
+
+/// A handle for pushing values into a graph from other threads, e.g. I/O threads, while it is
+/// executing.
+///
+/// Handles are cheap to clone and `Send`.  Injected nodes are pushed to a queue shared with the
+/// workers, which execute them as soon as they are idle; nodes injected while the graph is not
+/// executing are executed by the next execution.
+///
+/// The graph is not considered as quiesced while there are live handles, since they may still
+/// inject new values: executions only return once all the handles were dropped.  In particular, a
+/// handle must not be kept alive on the thread calling `execute`.
+pub struct InjectorHandle<'r> {
+    injected: Arc<InjectQueue<'r>>,
+    termination: Arc<Termination>,
+}
+
+impl<'r> InjectorHandle<'r> {
+    fn new(injected: Arc<InjectQueue<'r>>, termination: Arc<Termination>) -> Self {
+        termination.external_started();
+        InjectorHandle {
+            injected,
+            termination,
+        }
+    }
+
+    /// Push a node to be executed by the workers.
+    pub fn inject<N: NodeBox<RuntimeLoc<'r>> + Send + Sync + 'r>(&self, node: N) {
+        push_injected(&self.injected, &self.termination, Box::new(node))
+    }
+
+    /// Send `item` on `edge`, activating the target node.
+    pub fn inject_send<E>(&self, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<RuntimeLoc<'r>> + Send + Sync + 'r,
+        E::Item: Send + Sync + 'r,
+    {
+        self.inject(Injected { edge, item })
+    }
+}
+
+impl<'r> Clone for InjectorHandle<'r> {
+    fn clone(&self) -> Self {
+        InjectorHandle::new(self.injected.clone(), self.termination.clone())
+    }
+}
+
+impl<'r> Drop for InjectorHandle<'r> {
+    fn drop(&mut self) {
+        self.termination.external_completed()
+    }
+}
+
+impl<'r> EventInjector<RuntimeLoc<'r>> for InjectorHandle<'r> {
+    fn inject_send<E>(&self, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<RuntimeLoc<'r>> + Send + Sync + 'static,
+        E::Item: Send + Sync + 'static,
+    {
+        InjectorHandle::inject_send(self, edge, item)
+    }
+}
+
+/// Nodes executing on the workers can create handles, e.g. to hand them to a service (see
+/// `common::service`).
+impl<'r> InjectorSpec for RuntimeLoc<'r> {
+    type Injector = InjectorHandle<'r>;
+
+    fn injector(&self) -> InjectorHandle<'r> {
+        InjectorHandle::new(self.injected.clone(), self.termination.clone())
+    }
+}
+
+impl<'r> RuntimeLoc<'r> {
+    /// Schedule a node which is ready for execution, once it is admitted by the admission policy,
+    /// if any.
+    fn submit(
+        &mut self,
+        label: Option<Label>,
+        handle: Box<RuntimeNode<'r>>,
+        affinity: Option<usize>,
+    ) {
+        let handle = match self.admission {
+            Some(ref admission) => {
+                let context = AdmissionContext {
+                    label,
+                    worker: Some(self.index),
+                    affinity,
+                    deferrals: 0,
+                };
+                match admission.submit(context, handle) {
+                    Some(handle) => handle,
+                    None => return,
+                }
+            }
+            None => handle,
+        };
+        if self.keyed {
+            let key = self.next_key();
+            let handle = Box::new(Keyed { node: handle, key });
+            if let Some(ref mut replaying) = self.replaying {
+                // The replay picks the nodes itself, whatever their affinity.
+                replaying.insert(key, handle);
+                return;
+            }
+            return match affinity {
+                Some(worker) => self.schedule_pinned(worker, handle),
+                None => self.schedule_ready(handle),
+            };
+        }
+        match affinity {
+            Some(worker) => self.schedule_pinned(worker, handle),
+            None => self.schedule_ready(handle),
+        }
+    }
+
+    /// The key of the next node scheduled by this worker.
+    fn next_key(&mut self) -> u64 {
+        match self.keying {
+            Some((parent, ref mut scheduled)) => {
+                *scheduled += 1;
+                replay::child_key(parent, *scheduled)
+            }
+            None => {
+                self.unkeyed += 1;
+                replay::unkeyed_key(self.unkeyed)
+            }
+        }
+    }
+
+    /// Schedule a node on this worker's queue.
+    fn schedule_ready(&mut self, handle: Box<RuntimeNode<'r>>) {
+        self.trace(|hook| hook.on_schedule(Some(self.index)));
+        self.termination.scheduled();
+        self.ready.push(handle);
+        self.termination.published();
+    }
+
+    /// Schedule a node pinned to the worker `worker`.  All the workers are woken up, since only
+    /// the designated one can execute the node.
+    fn schedule_pinned(&mut self, worker: usize, handle: Box<RuntimeNode<'r>>) {
+        self.trace(|hook| hook.on_schedule(Some(self.index)));
+        self.termination.scheduled();
+        self.pinned.push(worker, handle);
+        self.termination.published_all();
+    }
+
+    /// Execute a node.  If the node panics, the panic is recorded and the workers are stopped.
+    fn execute_node(&mut self, node: Box<RuntimeNode<'r>>) {
+        // Nodes executed while helping must not be reported as the helping node.
+        let previous = self.executing.take();
+        self.trace(|hook| hook.on_execute_start(self.index));
+        match panic::catch_unwind(AssertUnwindSafe(|| node.execute_box(self))) {
+            Ok(()) => self.trace(|hook| hook.on_execute_end(self.index)),
+            Err(payload) => {
+                let label = self.executing.take();
+                self.failures.record(label, self.index, &*payload);
+                self.termination.stop();
+            }
+        }
+        self.executing = previous;
+    }
+
+    /// Try to steal a batch of nodes from the other workers, returning the first one.
+    fn steal(&self) -> Option<Box<RuntimeNode<'r>>> {
+        let (i, node, stolen) = self.config.steal(&self.stealers, &self.ready)?;
+        // The stealers are ordered starting from the next worker.
+        let victim = (self.index + 1 + i) % (self.stealers.len() + 1);
+        for _ in 0..stolen {
+            self.trace(|hook| hook.on_steal(self.index, victim));
+        }
+        Some(node)
+    }
+
+    fn trace<F: FnOnce(&dyn TraceHook)>(&self, f: F) {
+        self.trace.call(f)
+    }
+
+    /// Wait while a pausing breakpoint is hit.
+    fn wait_breakpoints(&self) {
+        if let Some(ref breakpoints) = self.breakpoints {
+            breakpoints.wait()
+        }
+    }
+
+    /// Take a node pushed by an external event.
+    fn pop_injected(&self) -> Option<Box<RuntimeNode<'r>>> {
+        self.injected.lock().unwrap().pop_front()
+    }
+
+    /// The memory accountant of the runtime, if any.  Nodes can use it to reserve memory for
+    /// their own allocations, or to throttle themselves when the usage is high.
+    pub fn accountant(&self) -> Option<&Arc<Accountant>> {
+        self.accountant.as_ref()
+    }
+
+    /// A handle for registering nodes waiting on external events.
+    pub(crate) fn external(&self) -> External<'r> {
+        External {
+            injected: self.injected.clone(),
+            termination: self.termination.clone(),
+        }
+    }
+
+    /// Execute other nodes until `predicate` returns `true`.
+    ///
+    /// This allows an executing node to wait for a value produced by another node, e.g. a port
+    /// written by a node which was scheduled later, without blocking the worker: the worker
+    /// executes the ready nodes (including stolen ones) while waiting.  The predicate is checked
+    /// after each executed node, and periodically while no nodes are available.
+    ///
+    /// This fails with `HelpError::Deadlock` if there are no nodes left to execute and all the
+    /// nodes in flight are waiting, and with `HelpError::Stopped` if the workers were stopped.
+    ///
+    /// Note that nodes executed while helping are executed on top of the current node's stack, so
+    /// that deeply nested waits can overflow the stack.
+    pub fn help_until<P: Fn() -> bool>(&mut self, predicate: P) -> Result<(), HelpError> {
+        self.help(&predicate, None)
+    }
+
+    /// Like `help_until`, but fails with `HelpError::Timeout` if `predicate` still returns `false`
+    /// after `timeout`.
+    pub fn help_until_timeout<P: Fn() -> bool>(
+        &mut self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<(), HelpError> {
+        self.help(&predicate, Some(Instant::now() + timeout))
+    }
+
+    fn help(
+        &mut self,
+        predicate: &dyn Fn() -> bool,
+        deadline: Option<Instant>,
+    ) -> Result<(), HelpError> {
+        self.termination.help_started();
+        let mut backoff = Backoff::new(&self.config);
+        let result = loop {
+            if predicate() {
+                break Ok(());
+            }
+            if self.termination.is_stopped() {
+                break Err(HelpError::Stopped);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break Err(HelpError::Timeout);
+            }
+            self.wait_breakpoints();
+            let epoch = self.termination.epoch();
+            match self
+                .pinned
+                .pop(self.index)
+                .or_else(|| self.ready.pop())
+                .or_else(|| self.steal())
+                .or_else(|| self.pop_injected()) {
+                Some(t) => {
+                    backoff.reset();
+                    self.execute_node(t);
+                    self.termination.completed();
+                }
+                None => {
+                    if self.termination.is_stalled() && !predicate() {
+                        break Err(HelpError::Deadlock);
+                    }
+                    self.trace(|hook| hook.on_idle(self.index));
+                    backoff.wait(&self.termination, epoch, deadline);
+                }
+            }
+        };
+        self.termination.help_completed();
+        result
+    }
+
+    /// Execute nodes until the graph has quiesced, or until `until` returns `true`.  The
+    /// condition is checked after each execution.
+    fn run(&mut self, until: &(dyn Fn() -> bool + Sync)) {
+        let mut backoff = Backoff::new(&self.config);
+        loop {
+            if self.termination.should_exit() {
+                return;
+            }
+            self.wait_breakpoints();
+            let epoch = self.termination.epoch();
+            match self
+                .pinned
+                .pop(self.index)
+                .or_else(|| self.ready.pop())
+                .or_else(|| self.steal())
+                .or_else(|| self.pop_injected())
+            {
+                Some(t) => {
+                    backoff.reset();
+                    self.execute_node(t);
+                    if until() {
+                        self.termination.stop();
+                    }
+                    self.termination.completed();
+                }
+                None => {
+                    self.trace(|hook| hook.on_idle(self.index));
+                    backoff.wait(&self.termination, epoch, None)
+                }
+            }
+        }
+    }
 }
 
 impl<'r> Toexec<'r> {
     pub fn new() -> Self {
-        Toexec { ready: Vec::new() }
+        Toexec {
+            ready: Vec::new(),
+            pinned: Vec::new(),
+            outputs: GraphOutputs::new(),
+            injected: Arc::new(Mutex::new(VecDeque::new())),
+            termination: Arc::new(Termination::new(0)),
+            trace: TraceSlot::default(),
+            registry: None,
+            breakpoints: None,
+            admission: None,
+            recording: None,
+            accountant: None,
+            config: RuntimeConfig::new(),
+            failures: Arc::new(Failures::default()),
+            timers: Vec::new(),
+            mode: RunMode::Running,
+        }
+    }
+
+    /// Create a runtime keeping track of the nodes it finalizes, so that executions report the
+    /// nodes left waiting for activations.  See the `parallel::validation` module.
+    pub fn with_validation() -> Self {
+        let mut toexec = Toexec::new();
+        toexec.set_validation(true);
+        toexec
+    }
+
+    /// Enable or disable the report of the nodes left waiting for activations, as with
+    /// `with_validation`.  Only the nodes finalized while validation is enabled are tracked.
+    pub fn set_validation(&mut self, enabled: bool) {
+        if !enabled {
+            self.registry = None;
+        } else if self.registry.is_none() {
+            self.registry = Some(Arc::new(Registry::default()));
+        }
+    }
+
+    /// Create a runtime charging the memory used by its nodes and ports to `accountant`, whose hard
+    /// limit is enforced by the executions.  See the `parallel::memory` module.
+    pub fn with_accountant(accountant: Accountant) -> Self {
+        let mut toexec = Toexec::new();
+        toexec.accountant = Some(Arc::new(accountant));
+        toexec
+    }
+
+    /// The memory accountant, if any.
+    pub fn accountant(&self) -> Option<&Arc<Accountant>> {
+        self.accountant.as_ref()
+    }
+
+    /// Set the work stealing configuration of the following executions.  See the
+    /// `parallel::config` module.
+    pub fn set_config(&mut self, config: RuntimeConfig) {
+        self.config = config;
+    }
+
+    /// The work stealing configuration.
+    pub fn config(&self) -> RuntimeConfig {
+        self.config
+    }
+
+    /// Install a hook notified of the scheduling events of the following executions, replacing any
+    /// previous one.  See the `parallel::trace` module.  This does nothing without the `tracing`
+    /// feature.
+    pub fn set_trace_hook<H: TraceHook + 'static>(&mut self, hook: H) {
+        self.trace.set(Arc::new(hook));
+    }
+
+    /// Set a breakpoint on the nodes labeled `label`, invoking `callback` when they are activated
+    /// or executed.  See the `parallel::breakpoint` module.
+    pub fn set_breakpoint<L, F>(&mut self, label: L, mode: BreakMode, callback: F)
+    where
+        L: Into<Label>,
+        F: Fn(&BreakContext) + Send + Sync + 'static,
+    {
+        let breakpoints = self.breakpoints.get_or_insert_with(Default::default);
+        Arc::get_mut(breakpoints)
+            .expect("Breakpoints are shared with running workers.")
+            .add(label.into(), mode, Box::new(callback))
+    }
+
+    /// Create a handle for injecting values into the graph from other threads.  See
+    /// `InjectorHandle`.
+    pub fn injector_handle(&self) -> InjectorHandle<'r> {
+        InjectorHandle::new(self.injected.clone(), self.termination.clone())
+    }
+
+    /// Remove all the breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints = None;
+    }
+
+    /// Submit the nodes which are ready for execution to `policy` before scheduling them, replacing
+    /// any previous policy.  The nodes deferred by the previous policy are kept in the holding queue.
+    /// See the `parallel::admission` module.
+    pub fn set_admission_policy<P: AdmissionPolicy + 'static>(&mut self, policy: P) {
+        let deferred = match self.admission.take() {
+            Some(previous) => previous.take_deferred(),
+            None => Vec::new(),
+        };
+        self.admission = Some(Arc::new(Admissions::new(Box::new(policy), deferred)));
+    }
+
+    /// Remove the admission policy.  The deferred nodes are scheduled.
+    pub fn clear_admission_policy(&mut self) {
+        if let Some(admission) = self.admission.take() {
+            for (context, handle) in admission.take_deferred() {
+                self.schedule_admitted(handle, context.affinity);
+            }
+        }
+    }
+
+    /// Submit the nodes of the holding queue to the admission policy again, scheduling those
+    /// which are accepted.  Return the number of nodes scheduled.
+    pub fn release_deferred(&mut self) -> usize {
+        let admission = match self.admission {
+            Some(ref admission) => admission.clone(),
+            None => return 0,
+        };
+        let mut scheduled = 0;
+        for (mut context, handle) in admission.take_deferred() {
+            context.worker = None;
+            let affinity = context.affinity;
+            if let Some(handle) = admission.submit(context, handle) {
+                self.schedule_admitted(handle, affinity);
+                scheduled += 1;
+            }
+        }
+        scheduled
+    }
+
+    /// The number of nodes in the holding queue of the admission policy.
+    pub fn deferred(&self) -> usize {
+        self.admission
+            .as_ref()
+            .map_or(0, |admission| admission.deferred())
+    }
+
+    /// Return the nodes rejected by the admission policy since the last call.
+    pub fn take_rejected(&self) -> Vec<AdmissionError> {
+        self.admission
+            .as_ref()
+            .map_or_else(Vec::new, |admission| admission.take_rejected())
+    }
+
+    /// Log the order in which the nodes of the following executions are executed into `log`, for
+    /// use with `execute_replay`.  See the `parallel::replay` module.
+    pub fn record_schedule(&mut self, log: &ScheduleLog) {
+        self.recording = Some(log.clone());
+    }
+
+    /// Stop logging the executed nodes.
+    pub fn stop_recording(&mut self) {
+        self.recording = None;
     }
 
-    pub fn execute(&mut self, k: usize) {    	
-        // création de la variable de condition
-	    //let syncr = &(Mutex::new( () ),Arc::new(Condvar::new())); // la méthode essayée avec des signaux ne fonctionne pas
-        //let n = Compteur::new(0);
+    /// Execute the graph on the calling thread, in the order recorded in `log` by a previous
+    /// execution of the same graph.  See the `parallel::replay` module.
+    ///
+    /// This fails if a node of the log is not ready when its turn comes, or if nodes are left once
+    /// the whole log was replayed.  Nodes panicking during the replay are reported as with
+    /// `execute`, in which case the replay stops.  The nodes which were not executed are
+    /// dropped.
+    pub fn execute_replay(&mut self, log: &ScheduleLog) -> Result<(), ReplayError> {
+        let ready = self.take_roots();
+        let mut worker = self
+            .workers(&mut Queues::new(1, &self.config))
+            .pop()
+            .unwrap();
+        worker.recording = None;
+        worker.keyed = true;
+        worker.replaying = Some(
+            ready
+                .into_iter()
+                .enumerate()
+                .map(|(i, node)| {
+                    let key = replay::root_key(i as u64);
+                    (key, Box::new(Keyed { node, key }) as Box<RuntimeNode<'r>>)
+                })
+                .collect(),
+        );
+        for (step, key) in log.keys().into_iter().enumerate() {
+            let node = worker
+                .replaying
+                .as_mut()
+                .unwrap()
+                .remove(&key)
+                .ok_or(ReplayError::Diverged { step, key })?;
+            worker.execute_node(node);
+            self.failures.take().map_err(ReplayError::Failed)?;
+        }
+        match worker.replaying.map_or(0, |replaying| replaying.len()) {
+            0 => Ok(()),
+            remaining => Err(ReplayError::Unfinished { remaining }),
+        }
+    }
 
-        // création des fifos
-        let mut fifos = Vec::new();
-	    let mut stealers = Vec::new();
+    /// Remove the nodes scheduled from outside of the workers, in the order in which they are
+    /// keyed.
+    fn take_roots(&mut self) -> Vec<Box<RuntimeNode<'r>>> {
+        let mut roots: Vec<_> = self.injected.lock().unwrap().drain(..).collect();
+        roots.append(&mut self.ready);
+        roots.extend(self.pinned.drain(..).map(|(_, handle)| handle));
+        roots
+    }
 
-        for _ in 0..k {
-	        let fs = deque::fifo();
-            fifos.push(fs.0);
-	        stealers.push(fs.1);
+    /// Key the nodes scheduled from outside of the workers, when recording the schedule.
+    fn key_roots(&mut self) {
+        if self.recording.is_none() {
+            return;
         }
+        let affinities: Vec<_> = self.pinned.iter().map(|&(worker, _)| worker).collect();
+        let mut roots = self
+            .take_roots()
+            .into_iter()
+            .enumerate()
+            .map(|(i, node)| -> Box<RuntimeNode<'r>> {
+                Box::new(Keyed {
+                    node,
+                    key: replay::root_key(i as u64),
+                })
+            });
+        let ready = roots.len() - affinities.len();
+        self.ready.extend(roots.by_ref().take(ready));
+        self.pinned.extend(affinities.into_iter().zip(roots));
+    }
+
+    /// Schedule a node activated from outside of the workers, once it is admitted by the
+    /// admission policy, if any.
+    fn submit(
+        &mut self,
+        label: Option<Label>,
+        handle: Box<RuntimeNode<'r>>,
+        affinity: Option<usize>,
+    ) {
+        let handle = match self.admission {
+            Some(ref admission) => {
+                let context = AdmissionContext {
+                    label,
+                    worker: None,
+                    affinity,
+                    deferrals: 0,
+                };
+                match admission.submit(context, handle) {
+                    Some(handle) => handle,
+                    None => return,
+                }
+            }
+            None => handle,
+        };
+        self.schedule_admitted(handle, affinity)
+    }
+
+    fn schedule_admitted(&mut self, handle: Box<RuntimeNode<'r>>, affinity: Option<usize>) {
+        self.trace(|hook| hook.on_schedule(None));
+        match affinity {
+            Some(worker) => self.pinned.push((worker, handle)),
+            None => self.ready.push(handle),
+        }
+    }
+
+    fn trace<F: FnOnce(&dyn TraceHook)>(&self, f: F) {
+        self.trace.call(f)
+    }
+
+    /// Stop running executions: `execute`, `execute_default` and `execute_on` return
+    /// `Ok(())` without executing anything, and the scheduled nodes stay scheduled until `step` or
+    /// `resume` is called.  Other executions, which stop before the graph quiesces, are not
+    /// affected.  See `PauseSpec`.
+    pub fn pause(&mut self) {
+        self.mode = RunMode::Paused
+    }
+
+    /// Allow exactly one more execution to run, then pause again.
+    pub fn step(&mut self) {
+        self.mode = RunMode::Step
+    }
+
+    /// Return to free-running mode, running executions again.
+    pub fn resume(&mut self) {
+        self.mode = RunMode::Running
+    }
 
-        // création des threads et runtimes associées
+    /// Whether the runtime is paused, either through `pause` or after the execution allowed by
+    /// `step` was started.
+    pub fn is_paused(&self) -> bool {
+        self.mode == RunMode::Paused
+    }
+
+    /// Execute the graph on `k` worker threads.  This returns once all the scheduled nodes, as
+    /// well as all the nodes they schedule, have been executed, or once the execution failed.
+    ///
+    /// The execution fails if a node panics, or if the memory usage goes over the hard limit of
+    /// the runtime's accountant, if any: the workers are stopped, and the nodes which were
+    /// scheduled but not executed yet are dropped.  The usage is checked before the execution and
+    /// after each executed node.  Runtimes created with `with_validation` also fail if nodes were
+    /// waiting for activations once the graph quiesced.  See `ExecutionError`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `k` is zero.
+    pub fn execute(&mut self, k: usize) -> Result<(), ExecutionError> {
+        if !self.mode.start_instant() {
+            return Ok(());
+        }
+        self.execute_inner(k, &|| false)?;
+        self.check_stalled()
+    }
+
+    /// Like `execute`, with the number of workers of the runtime's configuration, i.e. one per
+    /// available core by default.  See `RuntimeConfig::workers`.
+    pub fn execute_default(&mut self) -> Result<(), ExecutionError> {
+        let k = self.config.worker_count();
+        self.execute(k)
+    }
+
+    /// Execute the graph on `k` worker threads until a value is written on the port read by
+    /// `receiver`, and return that value.
+    ///
+    /// The workers are stopped as soon as the value is available: nodes which were scheduled but
+    /// not executed yet are dropped.  This fails as `execute`, except that stalled nodes are not
+    /// reported since the execution is interrupted.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the graph quiesces without writing to the port.
+    pub fn execute_until<T, R>(&mut self, k: usize, receiver: R) -> Result<T, ExecutionError>
+    where
+        T: Send,
+        R: TryReceiver<Item = Option<T>> + Sync,
+    {
+        let result = Mutex::new(receiver.try_recv().and_then(|value| value));
+        if result.lock().unwrap().is_none() {
+            self.execute_inner(k, &|| match receiver.try_recv().and_then(|value| value) {
+                Some(value) => {
+                    *result.lock().unwrap() = Some(value);
+                    true
+                }
+                None => false,
+            })?;
+        }
+        Ok(result
+            .into_inner()
+            .unwrap()
+            .expect("Graph quiesced without writing the result port."))
+    }
+
+    /// Execute the graph until it quiesces, `until` returns true, or the execution fails.  This
+    /// enforces the memory limit and reports the failed nodes, but not the stalled ones.
+    fn execute_inner(
+        &mut self,
+        k: usize,
+        until: &(dyn Fn() -> bool + Sync),
+    ) -> Result<(), ExecutionError> {
+        self.check_memory()?;
+        let over_limit = self.over_limit();
+        let workers = self.workers(&mut Queues::new(k, &self.config));
+
+        // création des threads
         crossbeam::scope(|scope| {
-            for i in 0..(k) {
-                let j = i.clone();
+            for mut runtime_loc in workers {
+                let over_limit = &over_limit;
+                scope.spawn(move || runtime_loc.run(&|| until() || over_limit()));
+            }
+        });
+        self.finish()
+    }
 
-		        //let (ref _lock, ref cvar) = *syncr.clone();
-                let ready_j = fifos.pop().unwrap();
-                
-                if i == 0 {
+    /// A condition stopping the workers once the memory usage is over the hard limit of the
+    /// accountant, if any.
+    fn over_limit(&self) -> impl Fn() -> bool + Send + Sync + 'static {
+        let accountant = self.accountant.clone();
+        move || {
+            accountant
+                .as_ref()
+                .is_some_and(|accountant| accountant.check().is_err())
+        }
+    }
+
+    /// Fail if the memory usage is over the hard limit of the accountant, if any.
+    fn check_memory(&self) -> Result<(), ExecutionError> {
+        match self.accountant {
+            Some(ref accountant) => Ok(accountant.check()?),
+            None => Ok(()),
+        }
+    }
+
+    /// Report the failures of the execution which just stopped: the nodes which panicked first,
+    /// then the memory usage.
+    fn finish(&mut self) -> Result<(), ExecutionError> {
+        self.failures.take()?;
+        self.check_memory()
+    }
+
+    /// Report the nodes waiting for activations, if the runtime was created with
+    /// `with_validation`.
+    fn check_stalled(&self) -> Result<(), ExecutionError> {
+        match self.registry {
+            Some(ref registry) => Ok(registry.check()?),
+            None => Ok(()),
+        }
+    }
+
+    /// Create one worker for each of `queues`, sharing the nodes ready for execution.  The local
+    /// queues are moved into the workers.
+    fn workers(&mut self, queues: &mut Queues<Box<RuntimeNode<'r>>>) -> Vec<RuntimeLoc<'r>> {
+        let k = queues.stealers.len();
+        config::check_workers(k);
+        self.key_roots();
+
+        // Nodes injected since the last execution are executed first.  Keep the queue locked while
+        // resetting the count, so that concurrent injections are not lost.
+        {
+            let mut injected = self.injected.lock().unwrap();
+            self.ready.extend(injected.drain(..));
+            self.termination.reset(self.ready.len() + self.pinned.len());
+        }
+        let pinned = Arc::new(Mailboxes::new(k));
+        for (worker, handle) in self.pinned.drain(..) {
+            pinned.push(worker, handle);
+        }
+
+        // création des runtimes associées
+        let stealers = &queues.stealers;
+        queues
+            .workers
+            .drain(..)
+            .enumerate()
+            .map(|(j, ready_j)| {
+                if j == 0 {
                     for w in self.ready.drain(..) {
                         ready_j.push(w)
                     }
                 }
-                
-                let mut stealers_j = Vec::new();
-                
+
                 // l'ordre des stealers n'est pas "naturelle" pour que tout le monde ne vole pas au premier
-                for w in (j + 1)..k {
-                    stealers_j.push(stealers[w].clone());
-                }
+                let stealers_j = stealers[(j + 1)..]
+                    .iter()
+                    .chain(&stealers[..j])
+                    .cloned()
+                    .collect();
 
-                for w in 0..j {
-                    stealers_j.push(stealers[w].clone());
+                RuntimeLoc {
+                    ready: ready_j,
+                    stealers: stealers_j,
+                    pinned: pinned.clone(),
+                    injected: self.injected.clone(),
+                    termination: self.termination.clone(),
+                    index: j,
+                    config: self.config,
+                    trace: self.trace.clone(),
+                    registry: self.registry.clone(),
+                    breakpoints: self.breakpoints.clone(),
+                    admission: self.admission.clone(),
+                    accountant: self.accountant.clone(),
+                    failures: self.failures.clone(),
+                    executing: None,
+                    recording: self.recording.clone(),
+                    keyed: self.recording.is_some(),
+                    keying: None,
+                    unkeyed: 0,
+                    replaying: None,
                 }
-		
-                //let nref = &n;
-                scope.spawn(move || {
-
-                    let mut runtime_loc = RuntimeLoc {
-                        ready: ready_j,
-                        stealers: stealers_j,
-			            //condvar: cvar.clone(),
-                    };
-
-                    //let n = Arc::clone(nref);
-                    //println!("{}",nref.get());
-                    
-                    loop {
-                        match runtime_loc.ready.pop() {
-                            Some(t) => t.execute_box(&mut runtime_loc),
-                            None => {
-                                let mut i = 0;
-                                let mut tour = Arc::new(Compteur::new(0));
-                                loop {
-                                    match runtime_loc.stealers[i].steal() {
-                                        Some(t) => {
-					                        t.execute_box(&mut runtime_loc);
-					                        break
-					                    },
-                                        None => (),
-                                    }
-                                    i = (i + 1) % (k-1);
-
-                                    if i == 0{
-                                        if tour.get()==10{
-                                            return;
-                                        }
-                                        else{
-                                            tour.inc();
-                                            thread::yield_now();
-                                        }
-                                    }
-/*                                      // on attend qu'un schedule soit appelé
-
-				                        let mut go = lock.lock().unwrap();
-                                        println!("%");
-                                        
-                                        //n.inc();
-                                        //kbis.inc();
-
-                                        //if n.get() == kbis {
-                                        //    return;
-                                        //}
-
-                                        let _ = cvar.wait(go).unwrap();
-                                        
-                                        //if q == 1 { // IMPORTANT <- Comment lire le contenu du CVAR ?
-                                            //return;
-                                        //}
-                                        println!("p");
-                                        //kbis.inc();
-				                    }
-*/                              
-                                }
-                            }
-                        }
-                    }
-                });
-            }
-        });
+            })
+            .collect()
     }
 }
 
-impl<'r> Scheduler for RuntimeLoc<'r> { 
+impl Toexec<'static> {
+    /// Execute the graph on the threads of `pool`, using one worker per thread.  This behaves
+    /// like `execute`, but without spawning new threads.
+    pub fn execute_on(&mut self, pool: &ThreadPool) -> Result<(), ExecutionError> {
+        if !self.mode.start_instant() {
+            return Ok(());
+        }
+        self.check_memory()?;
+        let over_limit = self.over_limit();
+        let config = self.config;
+        pool.run_workers(
+            &config,
+            |queues| self.workers(queues),
+            move |mut runtime_loc: RuntimeLoc<'static>| {
+                runtime_loc.run(&over_limit);
+                (runtime_loc.index, runtime_loc.ready)
+            },
+        );
+        self.finish()?;
+        self.check_stalled()
+    }
+
+    /// Start a periodic timer injecting its ticks into this runtime through an `InjectorHandle`.
+    /// The timer runs on its own thread until it stops by itself, it is stopped through the
+    /// returned handle, `stop_timers` is called, or the runtime is dropped.  See the
+    /// `common::timer` module.
+    ///
+    /// Ticks sent while the graph is not executing are processed by the next execution.  Since
+    /// the timer holds an `InjectorHandle`, executions only return once it has stopped: timers
+    /// without a limit can be stopped during an execution by moving the handle into a node.
+    pub fn add_timer<F, E>(&mut self, source: TimerSource<F>) -> TimerHandle
+    where
+        F: FnMut(Tick) -> Option<E> + Send + 'static,
+        E: OutputEdgeOnce<RuntimeLoc<'static>> + Send + Sync + 'static,
+        E::Item: TickPayload + Send + Sync + 'static,
+    {
+        let timer = source.start_with(self.injector_handle());
+        let handle = timer.handle();
+        self.timers.push(timer);
+        handle
+    }
+
+    /// Stop all the timers started with `add_timer`.  Ticks which were already injected are still
+    /// processed by the following executions.
+    pub fn stop_timers(&mut self) {
+        self.timers.clear()
+    }
+}
+
+/// Stop the timers before the rest of the runtime is dropped, so that no ticks are injected into
+/// a runtime being dropped.
+impl<'r> Drop for Toexec<'r> {
+    fn drop(&mut self) {
+        self.timers.clear()
+    }
+}
+
+impl<'r> Scheduler for RuntimeLoc<'r> {
     type Handle = Box<RuntimeNode<'r>>;
 
     fn schedule(&mut self, handle: Self::Handle) {
-        self.ready.push(handle);
-	    //self.condvar.notify_all()
+        self.submit(None, handle, None)
+    }
+}
+
+impl<'r> HasCancellation for RuntimeLoc<'r> {
+    fn is_cancelled(&self) -> bool {
+        self.termination.is_stopped()
+    }
+}
+
+impl<'r> HasWorkerIndex for RuntimeLoc<'r> {
+    fn worker_index(&self) -> usize {
+        self.index
+    }
+
+    fn worker_count(&self) -> usize {
+        self.stealers.len() + 1
+    }
+}
+
+impl<'r> Capabilities for RuntimeLoc<'r> {
+    fn as_cancellation(&self) -> Option<&dyn HasCancellation> {
+        Some(self)
+    }
+
+    fn as_worker_index(&self) -> Option<&dyn HasWorkerIndex> {
+        Some(self)
+    }
+}
+
+/// Charge `bytes` to `accountant`, if any.
+fn charge(accountant: &Option<Arc<Accountant>>, bytes: usize) -> Option<Reservation> {
+    accountant
+        .as_ref()
+        .map(|accountant| Accountant::charge(accountant, bytes))
+}
+
+impl<'r> OutputSpec for Toexec<'r> {
+    fn outputs(&self) -> &GraphOutputs {
+        &self.outputs
+    }
+
+    fn outputs_mut(&mut self) -> &mut GraphOutputs {
+        &mut self.outputs
+    }
+}
+
+impl<'r> ExecuteSpec for Toexec<'r> {
+    fn new() -> Self {
+        Toexec::new()
+    }
+
+    fn execute(&mut self, workers: usize) {
+        if let Err(error) = Toexec::execute(self, workers) {
+            panic!("{}", error)
+        }
+    }
+}
+
+impl<'r> PauseSpec for Toexec<'r> {
+    fn pause(&mut self) {
+        Toexec::pause(self)
+    }
+
+    fn step(&mut self) {
+        Toexec::step(self)
+    }
+
+    fn resume(&mut self) {
+        Toexec::resume(self)
+    }
+
+    fn is_paused(&self) -> bool {
+        Toexec::is_paused(self)
     }
 }
 
@@ -296,15 +1389,39 @@ impl<'r, N: NodeBox<RuntimeLoc<'r>> + Send + Sync  + 'r> NodeSpec<N> for Toexec<
     type Builder = RcBuilder<'r, N>;
 
     fn node(&self, node: N) -> Self::Builder {
-        RcBuilder::new(node)
+        RcBuilder::with_extra(
+            Box::new(node),
+            NodeExtra::new(charge(&self.accountant, node_size::<N>())),
+        )
     }
 }
 
-impl<'r, T: Default + 'r> PortSpec<T> for Toexec<'r> {
-    type Port = RcPort<Mutex<T>>;
+impl<'r, T: 'r> PortSpec<T> for Toexec<'r> {
+    type Port = RcPort<SlotPort<T>>;
 
     fn port(&self, init: T) -> Self::Port {
-        RcPort::new(Mutex::new(init))
+        RcPort::new(SlotPort::new(init))
+            .with_reservation(charge(&self.accountant, mem::size_of::<SlotPort<T>>()))
+    }
+}
+
+impl<'r, T: Default + 'r> BufferedPortSpec<T> for Toexec<'r> {
+    type Port = RcPort<ChannelPort<T>>;
+
+    fn port_buffered(&self, capacity: usize) -> Self::Port {
+        let size = mem::size_of::<ChannelPort<T>>() + capacity * mem::size_of::<T>();
+        RcPort::new(ChannelPort::new(capacity)).with_reservation(charge(&self.accountant, size))
+    }
+}
+
+impl<'r, T: Default + 'r> CheckedPortSpec<T> for Toexec<'r> {
+    type Port = RcPort<CheckedPort<Mutex<T>>>;
+
+    fn port_checked(&self, name: &str) -> Self::Port {
+        RcPort::new(CheckedPort::new(name, Mutex::new(T::default()))).with_reservation(charge(
+            &self.accountant,
+            mem::size_of::<CheckedPort<Mutex<T>>>(),
+        ))
     }
 }
 
@@ -317,14 +1434,39 @@ impl<'r, N: NodeBox<RuntimeLoc<'r>> + Send + Sync  + 'r> NodeSpec<N> for Runtime
     type Builder = RcBuilder<'r, N>;
 
     fn node(&self, node: N) -> Self::Builder {
-        RcBuilder::new(node)
+        RcBuilder::with_extra(
+            Box::new(node),
+            NodeExtra::new(charge(&self.accountant, node_size::<N>())),
+        )
     }
 }
 
-impl<'r, T: Default + 'r> PortSpec<T> for RuntimeLoc<'r> {
-    type Port = RcPort<Mutex<T>>;
+impl<'r, T: 'r> PortSpec<T> for RuntimeLoc<'r> {
+    type Port = RcPort<SlotPort<T>>;
 
     fn port(&self, init: T) -> Self::Port {
-        RcPort::new(Mutex::new(init))
+        RcPort::new(SlotPort::new(init))
+            .with_reservation(charge(&self.accountant, mem::size_of::<SlotPort<T>>()))
     }
-}
\ No newline at end of file
+}
+
+impl<'r, T: Default + 'r> BufferedPortSpec<T> for RuntimeLoc<'r> {
+    type Port = RcPort<ChannelPort<T>>;
+
+    fn port_buffered(&self, capacity: usize) -> Self::Port {
+        let size = mem::size_of::<ChannelPort<T>>() + capacity * mem::size_of::<T>();
+        RcPort::new(ChannelPort::new(capacity)).with_reservation(charge(&self.accountant, size))
+    }
+}
+
+impl<'r, T: Default + 'r> CheckedPortSpec<T> for RuntimeLoc<'r> {
+    type Port = RcPort<CheckedPort<Mutex<T>>>;
+
+    fn port_checked(&self, name: &str) -> Self::Port {
+        RcPort::new(CheckedPort::new(name, Mutex::new(T::default()))).with_reservation(charge(
+            &self.accountant,
+            mem::size_of::<CheckedPort<Mutex<T>>>(),
+        ))
+    }
+}
+
@@ -0,0 +1,150 @@
+//! Demand-driven execution of reusable graphs.
+//!
+//! In large reusable graphs, a given instant may only need some of the graph's results.  Runtimes
+//! created with `Toexec::with_slicing` record which nodes activate which other nodes during their
+//! executions, and `Toexec::execute_slice` uses these dependencies to execute only the *slice* of
+//! the graph needed to compute the requested nodes, i.e. the nodes themselves and their ancestors.
+//! The other nodes ignore their activations during the instant, so that they stay armed for the
+//! next one.
+//!
+//! The requested nodes are designated by their labels (see `ScopedGraphBuilder::node_named`).
+//! Slices are computed once per set of requested nodes and cached, until new dependencies are
+//! observed.  The labels and dependencies of a node are forgotten once it is dropped.
+//!
+//! # Caveats
+//!
+//! Dependencies are only known once they have been observed: the graph should be executed in full
+//! at least once (and after reconfigurations) before being sliced.  Pure data edges are not
+//! dependencies, since they don't activate their target; a node reading a memory written by a node
+//! outside of the slice reads the value from the last instant the writer was executed.  Similarly,
+//! the nodes in the slice still write into the ports of the nodes outside of it, so that buffered
+//! ports may accumulate values while their reader is sliced out.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use api::builder::Label;
+
+/// The identity of a node, assigned when it is created and never reused.
+pub(crate) type NodeKey = usize;
+
+/// A set of nodes to execute.
+pub(crate) type Slice = Arc<HashSet<NodeKey>>;
+
+/// The dependencies of a node, as observed during the executions.
+#[derive(Debug, Default)]
+struct Dependencies {
+    /// The nodes observed activating the node.
+    producers: HashSet<NodeKey>,
+    /// The nodes observed being activated by the node.
+    consumers: HashSet<NodeKey>,
+}
+
+/// The dependencies between the live nodes of a runtime, as observed during its executions.
+#[derive(Debug, Default)]
+pub(crate) struct Topology {
+    /// The labels of the labelled nodes.
+    labels: Mutex<HashMap<NodeKey, Label>>,
+    /// The dependencies of each node which activated or was activated by another node.
+    dependencies: Mutex<HashMap<NodeKey, Dependencies>>,
+    /// The slices computed so far, by requested labels.
+    slices: Mutex<HashMap<Vec<String>, Slice>>,
+}
+
+impl Topology {
+    /// Record a node built on the runtime.
+    pub(crate) fn add_node(&self, node: NodeKey, label: Option<Label>) {
+        if let Some(label) = label {
+            self.labels.lock().unwrap().insert(node, label);
+        }
+    }
+
+    /// Record that `producer` activated `consumer`.
+    pub(crate) fn add_dependency(&self, producer: NodeKey, consumer: NodeKey) {
+        if producer == consumer {
+            return;
+        }
+        let mut dependencies = self.dependencies.lock().unwrap();
+        let inserted = dependencies
+            .entry(consumer)
+            .or_default()
+            .producers
+            .insert(producer);
+        if inserted {
+            dependencies
+                .entry(producer)
+                .or_default()
+                .consumers
+                .insert(consumer);
+            self.slices.lock().unwrap().clear();
+        }
+    }
+
+    /// Forget a node which was dropped, along with its dependencies.
+    pub(crate) fn remove_node(&self, node: NodeKey) {
+        let labelled = self.labels.lock().unwrap().remove(&node).is_some();
+        let mut dependencies = self.dependencies.lock().unwrap();
+        match dependencies.remove(&node) {
+            Some(removed) => {
+                for producer in &removed.producers {
+                    if let Some(producer) = dependencies.get_mut(producer) {
+                        producer.consumers.remove(&node);
+                    }
+                }
+                for consumer in &removed.consumers {
+                    if let Some(consumer) = dependencies.get_mut(consumer) {
+                        consumer.producers.remove(&node);
+                    }
+                }
+            }
+            None if !labelled => return,
+            None => {}
+        }
+        self.slices.lock().unwrap().clear();
+    }
+
+    /// The nodes labelled with one of `outputs`, and their ancestors.
+    ///
+    /// # Panics
+    ///
+    /// This panics if there is no node labelled with one of `outputs`.
+    pub(crate) fn slice(&self, outputs: &[&str]) -> Slice {
+        let request: Vec<String> = outputs.iter().map(|output| output.to_string()).collect();
+        if let Some(slice) = self.slices.lock().unwrap().get(&request) {
+            return slice.clone();
+        }
+
+        let mut stack = Vec::new();
+        // Panic once the labels are unlocked, so that the mutex is not poisoned.
+        let missing = {
+            let labels = self.labels.lock().unwrap();
+            outputs.iter().find(|output| {
+                let start = stack.len();
+                stack.extend(
+                    labels
+                        .iter()
+                        .filter(|&(_, label)| label == *output)
+                        .map(|(&node, _)| node),
+                );
+                stack.len() == start
+            })
+        };
+        if let Some(output) = missing {
+            panic!("There is no node labelled `{}`.", output);
+        }
+
+        let dependencies = self.dependencies.lock().unwrap();
+        let mut slice = HashSet::new();
+        while let Some(node) = stack.pop() {
+            if slice.insert(node) {
+                if let Some(node_dependencies) = dependencies.get(&node) {
+                    stack.extend(node_dependencies.producers.iter().cloned());
+                }
+            }
+        }
+
+        let slice = Arc::new(slice);
+        self.slices.lock().unwrap().insert(request, slice.clone());
+        slice
+    }
+}
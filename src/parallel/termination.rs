@@ -0,0 +1,293 @@
+//! Termination detection for the work-stealing runtimes.
+//!
+//! Workers can't simply stop when they fail to find work: another worker may be executing a node
+//! which is about to schedule new ones.  Instead, the runtimes keep a shared count of the nodes
+//! which were scheduled but have not finished executing yet.  The count is incremented *before* a
+//! node is made visible to the other workers, and decremented only once its execution (including
+//! any scheduling it does) is over, so that it can only reach zero once the graph has quiesced.
+//!
+//! Idle workers spin for a little while with a `Backoff`, then park on a condition variable, and
+//! are woken up when new work is published or when the graph has quiesced.  Workers read the
+//! wake-up *epoch* before looking for work, and don't go to sleep if it changed since, so that
+//! wake-ups sent while they were looking are not lost.
+//!
+//! Nodes can also be suspended while waiting for an external event (see the `async_adapter`
+//! module).  Suspended nodes are counted separately, since they are not in any worker's queue:
+//! the graph has only quiesced once they have all completed.
+//!
+//! Execution can also be cut short with `stop`, for instance once a result is available: the
+//! workers then exit after finishing the node they are currently executing, without executing the
+//! remaining scheduled nodes.
+//!
+//! Finally, executing nodes can wait for a condition while helping with the other nodes (see
+//! `RuntimeLoc::help_until`).  Helping nodes are counted as well: once all the nodes in flight are
+//! helping, none of them can make progress and the graph is stalled.
+
+use std::error::Error;
+use std::fmt;
+use std::hint;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use parallel::config::RuntimeConfig;
+
+/// How long an idle worker sleeps the first time it parks.  Wake-ups are sent whenever new work is
+/// published, so this only bounds the latency of the wake-ups which are not sent, e.g. when a
+/// deadline expires.
+const PARK_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// The number of backoff steps spinning, before yielding the thread.
+const SPIN_STEPS: u32 = 6;
+
+/// The shared termination state of a runtime's workers.
+#[derive(Debug)]
+pub struct Termination {
+    /// The number of nodes scheduled but not yet fully executed.
+    in_flight: AtomicUsize,
+    /// The number of nodes waiting for an external event.  Contrary to `in_flight`, this may be
+    /// non-zero between executions.
+    external: AtomicUsize,
+    /// The number of executing nodes waiting for a condition while helping.
+    helping: AtomicUsize,
+    /// The number of parked workers.  This allows skipping notifications when nobody is waiting.
+    parked: AtomicUsize,
+    /// Incremented whenever new work is published, so that workers can detect wake-ups sent while
+    /// they were looking for work.
+    epoch: AtomicUsize,
+    /// Whether the workers were asked to stop early.
+    stopped: AtomicBool,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl Termination {
+    /// Create a new termination state with `in_flight` nodes already scheduled.
+    pub fn new(in_flight: usize) -> Self {
+        Termination {
+            in_flight: AtomicUsize::new(in_flight),
+            external: AtomicUsize::new(0),
+            helping: AtomicUsize::new(0),
+            parked: AtomicUsize::new(0),
+            epoch: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Prepare for a new execution with `in_flight` nodes already scheduled.  Nodes left over by an
+    /// execution which was stopped early are forgotten, but suspended nodes are kept.
+    pub fn reset(&self, in_flight: usize) {
+        self.in_flight.store(in_flight, SeqCst);
+        self.helping.store(0, SeqCst);
+        self.stopped.store(false, SeqCst);
+    }
+
+    /// Record that a node is about to be scheduled.  This must be called before the node becomes
+    /// visible to other workers, which must then be told with `published`.
+    pub fn scheduled(&self) {
+        self.in_flight.fetch_add(1, SeqCst);
+    }
+
+    /// Wake up a parked worker, if any, once a scheduled node is visible to the other workers.
+    pub fn published(&self) {
+        self.epoch.fetch_add(1, SeqCst);
+        if self.parked.load(SeqCst) > 0 {
+            let _guard = self.lock.lock().unwrap();
+            self.condvar.notify_one();
+        }
+    }
+
+    /// Like `published`, but wake up all the parked workers, e.g. because the node can only be
+    /// executed by a specific worker.
+    pub fn published_all(&self) {
+        self.epoch.fetch_add(1, SeqCst);
+        if self.parked.load(SeqCst) > 0 {
+            let _guard = self.lock.lock().unwrap();
+            self.condvar.notify_all();
+        }
+    }
+
+    /// The current wake-up epoch.  This should be read before looking for work, and passed to
+    /// `park_since` if none was found.
+    pub fn epoch(&self) -> usize {
+        self.epoch.load(SeqCst)
+    }
+
+    /// Record that a node has finished executing.  Wakes up all the parked workers if this was the
+    /// last node in flight.
+    pub fn completed(&self) {
+        let old_in_flight = self.in_flight.fetch_sub(1, SeqCst);
+        assert!(old_in_flight > 0);
+        if old_in_flight == 1 {
+            let _guard = self.lock.lock().unwrap();
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Record that a node is waiting for an external event.
+    pub fn external_started(&self) {
+        self.external.fetch_add(1, SeqCst);
+    }
+
+    /// Record that a node is no longer waiting for an external event.
+    pub fn external_completed(&self) {
+        let old_external = self.external.fetch_sub(1, SeqCst);
+        assert!(old_external > 0);
+        if old_external == 1 {
+            let _guard = self.lock.lock().unwrap();
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Record that an executing node started helping.
+    pub fn help_started(&self) {
+        self.helping.fetch_add(1, SeqCst);
+    }
+
+    /// Record that an executing node stopped helping.
+    pub fn help_completed(&self) {
+        let old_helping = self.helping.fetch_sub(1, SeqCst);
+        assert!(old_helping > 0);
+    }
+
+    /// Whether all the nodes in flight are helping and no external events are pending, in which
+    /// case none of the helping nodes can make progress.
+    pub fn is_stalled(&self) -> bool {
+        self.external.load(SeqCst) == 0
+            && self.in_flight.load(SeqCst) == self.helping.load(SeqCst)
+    }
+
+    /// Whether the workers were asked to stop.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(SeqCst)
+    }
+
+    /// Wake up a parked worker, e.g. because a node was pushed from outside of the workers.
+    pub fn notify(&self) {
+        self.epoch.fetch_add(1, SeqCst);
+        let _guard = self.lock.lock().unwrap();
+        self.condvar.notify_one();
+    }
+
+    /// Whether the graph has quiesced, i.e. all the scheduled nodes were executed and no nodes are
+    /// waiting for external events.
+    pub fn is_done(&self) -> bool {
+        self.in_flight.load(SeqCst) == 0 && self.external.load(SeqCst) == 0
+    }
+
+    /// Ask the workers to stop as soon as possible, even though the graph has not quiesced.
+    pub fn stop(&self) {
+        self.stopped.store(true, SeqCst);
+        let _guard = self.lock.lock().unwrap();
+        self.condvar.notify_all();
+    }
+
+    /// Whether the workers should exit, either because the graph has quiesced or because they
+    /// were asked to stop.
+    pub fn should_exit(&self) -> bool {
+        self.is_stopped() || self.is_done()
+    }
+
+    /// Park the calling worker until new work may be available or the graph has quiesced.
+    pub fn park(&self) {
+        self.park_since(self.epoch(), PARK_TIMEOUT)
+    }
+
+    /// Park the calling worker until new work is published after the epoch `epoch`, the graph has
+    /// quiesced, or `timeout` has elapsed.  This returns immediately if work was published since
+    /// `epoch`.
+    pub fn park_since(&self, epoch: usize, timeout: Duration) {
+        let guard = self.lock.lock().unwrap();
+        // Count the worker as parked before checking the epoch, so that publishers incrementing
+        // the epoch afterwards see it and wake it up.
+        self.parked.fetch_add(1, SeqCst);
+        if !self.should_exit() && self.epoch() == epoch {
+            let _ = self.condvar.wait_timeout(guard, timeout).unwrap();
+        }
+        self.parked.fetch_sub(1, SeqCst);
+    }
+}
+
+/// The waiting strategy of an idle worker: spin with exponentially longer pauses, then yield the
+/// thread, and finally park with exponentially longer timeouts, up to the limits set in the
+/// runtime's `RuntimeConfig`.
+///
+/// The backoff should be reset whenever the worker finds work.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    step: u32,
+    spin_limit: u32,
+    park: Duration,
+    park_limit: Duration,
+}
+
+impl Backoff {
+    /// Create a backoff following `config`.
+    pub fn new(config: &RuntimeConfig) -> Self {
+        let park_limit = config.park_limit();
+        Backoff {
+            step: 0,
+            spin_limit: config.spin_limit(),
+            park: park_limit.min(PARK_TIMEOUT),
+            park_limit,
+        }
+    }
+
+    /// Start over with short pauses, e.g. after finding work.
+    pub fn reset(&mut self) {
+        self.step = 0;
+        self.park = self.park_limit.min(PARK_TIMEOUT);
+    }
+
+    /// Wait after failing to find work, having read the wake-up epoch `epoch` before looking for
+    /// it.  Parking doesn't extend past `deadline`, if any.
+    pub fn wait(&mut self, termination: &Termination, epoch: usize, deadline: Option<Instant>) {
+        if self.step < self.spin_limit {
+            if self.step < SPIN_STEPS {
+                for _ in 0..1 << self.step {
+                    hint::spin_loop();
+                }
+            } else {
+                thread::yield_now();
+            }
+            self.step += 1;
+            return;
+        }
+
+        let timeout = match deadline {
+            Some(deadline) => self
+                .park
+                .min(deadline.saturating_duration_since(Instant::now())),
+            None => self.park,
+        };
+        termination.park_since(epoch, timeout);
+        self.park = self.park_limit.min(self.park * 2);
+    }
+}
+
+/// The reasons waiting with `help_until` can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpError {
+    /// There are no nodes left to execute, and all the nodes in flight are themselves waiting: the
+    /// condition can never become true.  This typically means nodes are waiting on each other.
+    Deadlock,
+    /// The condition did not become true before the timeout expired.
+    Timeout,
+    /// The workers were asked to stop, e.g. by `execute_until`.
+    Stopped,
+}
+
+impl fmt::Display for HelpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HelpError::Deadlock => write!(f, "all the nodes in flight are waiting"),
+            HelpError::Timeout => write!(f, "timed out while waiting"),
+            HelpError::Stopped => write!(f, "the workers were stopped while waiting"),
+        }
+    }
+}
+
+impl Error for HelpError {}
@@ -0,0 +1,843 @@
+//! Hooks for observing the scheduling decisions of the parallel runtimes.
+//!
+//! A `TraceHook` can be installed on a runtime with `Toexec::set_trace_hook`; it is then shared by
+//! all the workers, which call it when scheduling, executing and stealing nodes, and when running
+//! out of work.  All the methods have a no-op default implementation, so that hooks only need to
+//! implement the events they are interested in.
+//!
+//! Hooks are called from the workers' hot loop: they should be cheap, and must not block.
+//!
+//! `StealStats` is a hook counting the steals between each pair of workers, whose `report` tells
+//! which workers were disproportionately stolen from, and which ones never found work to steal:
+//!
+//! ```rust,ignore
+//! let stats = StealStats::new(4);
+//! runtime.set_trace_hook(stats.clone());
+//! runtime.execute(4);
+//! eprintln!("{}", stats.report());
+//! ```
+//!
+//! `GroupTimings` is a hook measuring the time spent executing the labelled nodes of each subsystem
+//! of a graph, i.e. each group of nodes sharing a tag.  By default, the tag of a node is the first
+//! component of its label, which is the name of the outermost namespace it was built in (see
+//! `ScopedGraphBuilder::namespace`).  Its `report` tells, for each group, how much of the
+//! available parallelism was used, so that the efficiency of each subsystem can be tracked over
+//! time:
+//!
+//! ```rust,ignore
+//! let timings = GroupTimings::new(8);
+//! runtime.set_trace_hook(timings.clone());
+//! runtime.execute(8);
+//! for group in timings.report().groups {
+//!     metrics.record(&group.tag, group.parallelism(), group.efficiency());
+//! }
+//! ```
+//!
+//! `TraceCollector` is a hook recording the span of each node executed by each worker, along with
+//! the steals, and exporting them in the `trace_event` format of Chrome.  The trace can be opened
+//! in `chrome://tracing` or in Perfetto to inspect the parallelism of a graph, the gaps during
+//! which the workers were idle, and where they stole their work from:
+//!
+//! ```rust,ignore
+//! let collector = TraceCollector::new(8);
+//! runtime.set_trace_hook(collector.clone());
+//! runtime.execute(8);
+//! collector.write_chrome_trace(File::create("trace.json")?)?;
+//! ```
+//!
+//! Hooks are only called with the `tracing` feature, which is enabled by default.  Without it,
+//! `set_trace_hook` drops the hook, and the workers don't even test whether a hook is installed.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Callbacks invoked by the workers of the parallel runtimes.
+///
+/// Workers are identified by their index, from 0 to the number of workers passed to `execute`.
+pub trait TraceHook: Send + Sync {
+    /// A node was scheduled, either by worker `worker`, or from outside of the workers (e.g. when
+    /// activating the root nodes of a graph) when `worker` is `None`.
+    fn on_schedule(&self, _worker: Option<usize>) {}
+
+    /// Worker `worker` is about to execute a node.
+    fn on_execute_start(&self, _worker: usize) {}
+
+    /// Worker `worker` is about to execute the node labelled `label`.  This is called after
+    /// `on_execute_start` for the nodes which have a label (see `ScopedGraphBuilder::node_named`).
+    fn on_execute_labeled(&self, _worker: usize, _label: &str) {}
+
+    /// Worker `worker` is done executing a node.
+    fn on_execute_end(&self, _worker: usize) {}
+
+    /// Worker `worker` stole a node from the queue of worker `victim`.
+    fn on_steal(&self, _worker: usize, _victim: usize) {}
+
+    /// Worker `worker` found no node to execute and is waiting for more work.
+    fn on_idle(&self, _worker: usize) {}
+}
+
+/// The hook installed on a runtime, if any.  This is empty without the `tracing` feature.
+#[derive(Clone, Default)]
+pub(crate) struct TraceSlot {
+    #[cfg(feature = "tracing")]
+    hook: Option<Arc<dyn TraceHook>>,
+}
+
+impl TraceSlot {
+    /// Install `hook`, replacing any previous one.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn set(&mut self, hook: Arc<dyn TraceHook>) {
+        self.hook = Some(hook);
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn set(&mut self, _hook: Arc<dyn TraceHook>) {}
+
+    /// Call `f` with the installed hook, if any.
+    #[cfg(feature = "tracing")]
+    #[inline]
+    pub(crate) fn call<F: FnOnce(&dyn TraceHook)>(&self, f: F) {
+        if let Some(ref hook) = self.hook {
+            f(&**hook)
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    pub(crate) fn call<F: FnOnce(&dyn TraceHook)>(&self, _f: F) {}
+}
+
+/// A hook printing all the events on the standard error, along with the time elapsed since the
+/// tracer was created.
+#[derive(Debug, Clone, Copy)]
+pub struct StderrTracer {
+    start: Instant,
+}
+
+impl StderrTracer {
+    /// Create a tracer measuring time from now.
+    pub fn new() -> Self {
+        StderrTracer {
+            start: Instant::now(),
+        }
+    }
+
+    fn log(&self, worker: Option<usize>, event: &str) {
+        let elapsed = self.start.elapsed();
+        match worker {
+            Some(worker) => eprintln!("[{:>12?}] worker {}: {}", elapsed, worker, event),
+            None => eprintln!("[{:>12?}] runtime: {}", elapsed, event),
+        }
+    }
+}
+
+impl Default for StderrTracer {
+    fn default() -> Self {
+        StderrTracer::new()
+    }
+}
+
+impl TraceHook for StderrTracer {
+    fn on_schedule(&self, worker: Option<usize>) {
+        self.log(worker, "schedule")
+    }
+
+    fn on_execute_start(&self, worker: usize) {
+        self.log(Some(worker), "execute start")
+    }
+
+    fn on_execute_labeled(&self, worker: usize, label: &str) {
+        self.log(Some(worker), &format!("execute `{}`", label))
+    }
+
+    fn on_execute_end(&self, worker: usize) {
+        self.log(Some(worker), "execute end")
+    }
+
+    fn on_steal(&self, worker: usize, victim: usize) {
+        self.log(Some(worker), &format!("steal from worker {}", victim))
+    }
+
+    fn on_idle(&self, worker: usize) {
+        self.log(Some(worker), "idle")
+    }
+}
+
+/// The counters of `StealStats`.
+struct StealCounters {
+    workers: usize,
+    /// The number of nodes stolen by each worker from each victim, indexed by
+    /// `worker * workers + victim`.
+    steals: Vec<AtomicUsize>,
+    executed: Vec<AtomicUsize>,
+    idle: Vec<AtomicUsize>,
+}
+
+/// A hook counting the steals between workers, to analyse the fairness of the work stealing.  See
+/// the module documentation.
+///
+/// The statistics are shared by the clones of a `StealStats`, so that a clone can be installed on
+/// the runtime while the original is used to read the report.  They accumulate over executions
+/// until `reset` is called.  Events of workers whose index is above the number of workers the
+/// statistics were created with are ignored.
+#[derive(Clone)]
+pub struct StealStats {
+    counters: Arc<StealCounters>,
+}
+
+impl StealStats {
+    /// Create statistics for executions with up to `workers` workers.
+    pub fn new(workers: usize) -> Self {
+        let counters = |len| (0..len).map(|_| AtomicUsize::new(0)).collect();
+        StealStats {
+            counters: Arc::new(StealCounters {
+                workers,
+                steals: counters(workers * workers),
+                executed: counters(workers),
+                idle: counters(workers),
+            }),
+        }
+    }
+
+    /// The number of nodes worker `worker` stole from worker `victim`.
+    pub fn steals(&self, worker: usize, victim: usize) -> usize {
+        let counters = &self.counters;
+        assert!(worker < counters.workers && victim < counters.workers);
+        counters.steals[worker * counters.workers + victim].load(Relaxed)
+    }
+
+    /// Reset all the counters to zero.
+    pub fn reset(&self) {
+        let counters = &self.counters;
+        for counter in counters
+            .steals
+            .iter()
+            .chain(&counters.executed)
+            .chain(&counters.idle)
+        {
+            counter.store(0, Relaxed)
+        }
+    }
+
+    /// Summarize the counters.  The report should be read once the executions are done.
+    pub fn report(&self) -> FairnessReport {
+        let counters = &self.counters;
+        let n = counters.workers;
+        let workers = (0..n)
+            .map(|worker| WorkerSteals {
+                worker,
+                executed: counters.executed[worker].load(Relaxed),
+                steals: (0..n).map(|victim| self.steals(worker, victim)).sum(),
+                stolen_from: (0..n).map(|thief| self.steals(thief, worker)).sum(),
+                idle: counters.idle[worker].load(Relaxed),
+            })
+            .collect();
+        FairnessReport { workers }
+    }
+
+    fn count(counters: &[AtomicUsize], index: usize) {
+        if let Some(counter) = counters.get(index) {
+            counter.fetch_add(1, Relaxed);
+        }
+    }
+}
+
+impl fmt::Debug for StealStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StealStats")
+            .field("workers", &self.counters.workers)
+            .finish()
+    }
+}
+
+impl TraceHook for StealStats {
+    fn on_execute_start(&self, worker: usize) {
+        StealStats::count(&self.counters.executed, worker)
+    }
+
+    fn on_steal(&self, worker: usize, victim: usize) {
+        let n = self.counters.workers;
+        if worker < n && victim < n {
+            StealStats::count(&self.counters.steals, worker * n + victim)
+        }
+    }
+
+    fn on_idle(&self, worker: usize) {
+        StealStats::count(&self.counters.idle, worker)
+    }
+}
+
+/// The steal statistics of a worker, in a `FairnessReport`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerSteals {
+    /// The index of the worker.
+    pub worker: usize,
+    /// The number of nodes the worker executed, including the stolen ones.
+    pub executed: usize,
+    /// The number of nodes the worker stole from the other workers.
+    pub steals: usize,
+    /// The number of nodes the other workers stole from this worker.
+    pub stolen_from: usize,
+    /// The number of times the worker found no node to execute or steal.
+    pub idle: usize,
+}
+
+/// A summary of the steals between the workers, created by `StealStats::report`.
+///
+/// Its `Display` implementation prints a table of the workers' statistics, followed by the
+/// hot-spots and starving workers and suggestions to balance the load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FairnessReport {
+    /// The statistics of each worker, by index.
+    pub workers: Vec<WorkerSteals>,
+}
+
+impl FairnessReport {
+    /// The total number of steals.
+    pub fn total_steals(&self) -> usize {
+        self.workers.iter().map(|worker| worker.steals).sum()
+    }
+
+    /// The workers which were stolen from at least twice as often as they would be if the steals
+    /// were evenly spread among the workers.
+    pub fn hot_spots(&self) -> Vec<usize> {
+        let total = self.total_steals();
+        let workers = self.workers.len();
+        self.workers
+            .iter()
+            .filter(|worker| workers > 1 && worker.stolen_from * workers >= 2 * total && total > 0)
+            .map(|worker| worker.worker)
+            .collect()
+    }
+
+    /// The workers which went idle without ever stealing a node.
+    pub fn starving(&self) -> Vec<usize> {
+        self.workers
+            .iter()
+            .filter(|worker| worker.steals == 0 && worker.idle > 0)
+            .map(|worker| worker.worker)
+            .collect()
+    }
+}
+
+impl fmt::Display for FairnessReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>10} {:>10} {:>12} {:>10}",
+            "worker", "executed", "steals", "stolen from", "idle"
+        )?;
+        for worker in &self.workers {
+            writeln!(
+                f,
+                "{:>6} {:>10} {:>10} {:>12} {:>10}",
+                worker.worker, worker.executed, worker.steals, worker.stolen_from, worker.idle
+            )?;
+        }
+
+        let hot_spots = self.hot_spots();
+        if !hot_spots.is_empty() {
+            writeln!(
+                f,
+                "hot-spots: workers {:?} were disproportionately stolen from.  The nodes ready \
+                 when an execution starts are all pushed to worker 0: consider injecting the \
+                 root nodes with an `InjectorHandle`, so that all the workers pick them up, or \
+                 scheduling independent branches from the root nodes.",
+                hot_spots
+            )?;
+        }
+        let starving = self.starving();
+        if !starving.is_empty() {
+            writeln!(
+                f,
+                "starving: workers {:?} never found a node to steal.  The graph may not expose \
+                 enough parallelism for this number of workers: consider executing it with \
+                 fewer workers, or splitting the work into more nodes.",
+                starving
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A node being executed by a worker, in `GroupTimings`.
+struct Frame {
+    start: Instant,
+    tag: Option<String>,
+    /// The time spent executing other nodes on top of this one, e.g. while helping.
+    nested: Duration,
+}
+
+/// The timings of a group, in `GroupTimings`.
+struct GroupStats {
+    nodes: usize,
+    busy: Duration,
+    longest: Duration,
+    first: Instant,
+    last: Instant,
+}
+
+/// A function computing the tag of a node from its label.
+type Tagger = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// The state of `GroupTimings`.
+struct Timings {
+    workers: usize,
+    tag: Tagger,
+    /// The instant and wall-clock time at which the timings were created, to convert instants.
+    origin: (Instant, SystemTime),
+    /// The nodes being executed by each worker, innermost last.
+    frames: Vec<Mutex<Vec<Frame>>>,
+    groups: Mutex<HashMap<String, GroupStats>>,
+}
+
+/// A hook measuring the execution of the labelled nodes by group.  See the module documentation.
+///
+/// As with `StealStats`, the timings are shared by the clones of a `GroupTimings`, accumulate over
+/// executions until `reset` is called, and ignore the workers above the number of workers they
+/// were created with.  The time a node spends executing other nodes while waiting (see
+/// `RuntimeLoc::help_until`) is counted for these nodes only.  Unlabelled nodes and nodes which
+/// panic are not counted.
+#[derive(Clone)]
+pub struct GroupTimings {
+    timings: Arc<Timings>,
+}
+
+impl GroupTimings {
+    /// Create timings for executions with up to `workers` workers, tagging the nodes with the first
+    /// component of their label.
+    pub fn new(workers: usize) -> Self {
+        GroupTimings::with_tags(workers, |label: &str| {
+            label.split('/').next().map(str::to_string)
+        })
+    }
+
+    /// Create timings for executions with up to `workers` workers, tagging the nodes with `tag`.
+    /// The nodes for which `tag` returns `None` are not counted.
+    pub fn with_tags<F>(workers: usize, tag: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        GroupTimings {
+            timings: Arc::new(Timings {
+                workers,
+                tag: Box::new(tag),
+                origin: (Instant::now(), SystemTime::now()),
+                frames: (0..workers).map(|_| Mutex::new(Vec::new())).collect(),
+                groups: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Forget all the timings.
+    pub fn reset(&self) {
+        for frames in &self.timings.frames {
+            frames.lock().unwrap().clear();
+        }
+        self.timings.groups.lock().unwrap().clear();
+    }
+
+    /// Summarize the timings.  The report should be read once the executions are done.
+    pub fn report(&self) -> ExecutionReport {
+        let timings = &self.timings;
+        let (origin, wall_clock) = timings.origin;
+        let mut groups: Vec<_> = timings
+            .groups
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(tag, stats)| GroupReport {
+                tag: tag.clone(),
+                nodes: stats.nodes,
+                busy: stats.busy,
+                started: wall_clock + stats.first.duration_since(origin),
+                finished: wall_clock + stats.last.duration_since(origin),
+                makespan: stats.last.duration_since(stats.first),
+                critical_path: stats
+                    .longest
+                    .max(stats.busy / timings.workers.max(1) as u32),
+            })
+            .collect();
+        groups.sort_by(|a, b| a.tag.cmp(&b.tag));
+        ExecutionReport {
+            workers: timings.workers,
+            groups,
+        }
+    }
+
+    fn frames(&self, worker: usize) -> Option<&Mutex<Vec<Frame>>> {
+        self.timings.frames.get(worker)
+    }
+}
+
+impl fmt::Debug for GroupTimings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GroupTimings")
+            .field("workers", &self.timings.workers)
+            .finish()
+    }
+}
+
+impl TraceHook for GroupTimings {
+    fn on_execute_start(&self, worker: usize) {
+        if let Some(frames) = self.frames(worker) {
+            frames.lock().unwrap().push(Frame {
+                start: Instant::now(),
+                tag: None,
+                nested: Duration::from_secs(0),
+            })
+        }
+    }
+
+    fn on_execute_labeled(&self, worker: usize, label: &str) {
+        if let Some(frames) = self.frames(worker) {
+            if let Some(frame) = frames.lock().unwrap().last_mut() {
+                frame.tag = (self.timings.tag)(label);
+            }
+        }
+    }
+
+    fn on_execute_end(&self, worker: usize) {
+        let end = Instant::now();
+        let frame = match self.frames(worker) {
+            Some(frames) => {
+                let mut frames = frames.lock().unwrap();
+                let frame = match frames.pop() {
+                    Some(frame) => frame,
+                    None => return,
+                };
+                if let Some(parent) = frames.last_mut() {
+                    parent.nested += end.duration_since(frame.start);
+                }
+                frame
+            }
+            None => return,
+        };
+        let tag = match frame.tag {
+            Some(tag) => tag,
+            None => return,
+        };
+        let busy = end.duration_since(frame.start).saturating_sub(frame.nested);
+        let mut groups = self.timings.groups.lock().unwrap();
+        let stats = groups.entry(tag).or_insert(GroupStats {
+            nodes: 0,
+            busy: Duration::from_secs(0),
+            longest: Duration::from_secs(0),
+            first: frame.start,
+            last: end,
+        });
+        stats.nodes += 1;
+        stats.busy += busy;
+        stats.longest = stats.longest.max(busy);
+        stats.first = stats.first.min(frame.start);
+        stats.last = stats.last.max(end);
+    }
+}
+
+/// The timings of a group of nodes, in an `ExecutionReport`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupReport {
+    /// The tag of the nodes of the group.
+    pub tag: String,
+    /// The number of nodes executed.
+    pub nodes: usize,
+    /// The total time spent executing the nodes, over all the workers.
+    pub busy: Duration,
+    /// The wall-clock time at which the first node started executing.
+    pub started: SystemTime,
+    /// The wall-clock time at which the last node finished executing.
+    pub finished: SystemTime,
+    /// The time between the start of the first node and the end of the last one.
+    pub makespan: Duration,
+    /// An estimate of the shortest possible makespan: the longest node, or the busy time spread
+    /// evenly over all the workers if longer.  The dependencies between the nodes are not known to
+    /// the hook, so that this is a lower bound of the actual critical path.
+    pub critical_path: Duration,
+}
+
+impl GroupReport {
+    /// The average number of workers executing the nodes of the group over its makespan.
+    pub fn parallelism(&self) -> f64 {
+        ratio(self.busy, self.makespan)
+    }
+
+    /// The ratio of the critical path estimate to the actual makespan, from 0 to 1: 1 means that
+    /// the group could not have been executed faster.
+    pub fn efficiency(&self) -> f64 {
+        ratio(self.critical_path, self.makespan).min(1.0)
+    }
+}
+
+/// `a / b`, or 1 if `b` is zero.
+fn ratio(a: Duration, b: Duration) -> f64 {
+    if b == Duration::from_secs(0) {
+        1.0
+    } else {
+        a.as_secs_f64() / b.as_secs_f64()
+    }
+}
+
+/// A summary of the timings of each group of nodes, created by `GroupTimings::report`.
+///
+/// Its `Display` implementation prints a table of the groups, by tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// The number of workers the timings were created with.
+    pub workers: usize,
+    /// The timings of each group, sorted by tag.
+    pub groups: Vec<GroupReport>,
+}
+
+impl ExecutionReport {
+    /// The timings of the group tagged `tag`, if any of its nodes was executed.
+    pub fn group(&self, tag: &str) -> Option<&GroupReport> {
+        self.groups.iter().find(|group| group.tag == tag)
+    }
+}
+
+impl fmt::Display for ExecutionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<20} {:>8} {:>12} {:>12} {:>12} {:>11} {:>10}",
+            "group", "nodes", "busy", "makespan", "critical", "parallelism", "efficiency"
+        )?;
+        for group in &self.groups {
+            writeln!(
+                f,
+                "{:<20} {:>8} {:>12?} {:>12?} {:>12?} {:>11.2} {:>9.0}%",
+                group.tag,
+                group.nodes,
+                group.busy,
+                group.makespan,
+                group.critical_path,
+                group.parallelism(),
+                group.efficiency() * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A node executed by a worker, as recorded by a `TraceCollector`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceSpan {
+    /// The worker which executed the node.
+    pub worker: usize,
+    /// The label of the node, if any.
+    pub label: Option<String>,
+    /// When the execution started, relative to the creation of the collector.
+    pub start: Duration,
+    /// When the execution ended, relative to the creation of the collector.
+    pub end: Duration,
+}
+
+/// A steal recorded by a `TraceCollector`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSteal {
+    /// The worker which stole a node.
+    pub worker: usize,
+    /// The worker the node was stolen from.
+    pub victim: usize,
+    /// When the node was stolen, relative to the creation of the collector.
+    pub time: Duration,
+}
+
+/// The events recorded for a worker by a `TraceCollector`.
+#[derive(Default)]
+struct WorkerTrace {
+    /// The nodes being executed, innermost last.
+    open: Vec<TraceSpan>,
+    spans: Vec<TraceSpan>,
+    steals: Vec<TraceSteal>,
+}
+
+/// A hook recording the execution spans of the nodes, for export to Chrome's trace viewer.  See
+/// the module documentation.
+///
+/// As with `StealStats`, the events are shared by the clones of a `TraceCollector`, accumulate
+/// over executions until `reset` is called, and are ignored for the workers above the number of
+/// workers the collector was created with.  Each worker records its events separately, so that
+/// the workers don't contend on the collector.
+#[derive(Clone)]
+pub struct TraceCollector {
+    origin: Instant,
+    workers: Arc<Vec<Mutex<WorkerTrace>>>,
+}
+
+impl TraceCollector {
+    /// Create a collector for executions with up to `workers` workers, measuring time from now.
+    pub fn new(workers: usize) -> Self {
+        TraceCollector {
+            origin: Instant::now(),
+            workers: Arc::new(
+                (0..workers)
+                    .map(|_| Mutex::new(WorkerTrace::default()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The spans of the executed nodes, by start time.  Nodes which are still executing are not
+    /// included.
+    pub fn spans(&self) -> Vec<TraceSpan> {
+        let mut spans: Vec<_> = self
+            .workers
+            .iter()
+            .flat_map(|trace| trace.lock().unwrap().spans.clone())
+            .collect();
+        spans.sort_by_key(|span| (span.start, span.worker));
+        spans
+    }
+
+    /// The steals, by time.
+    pub fn steals(&self) -> Vec<TraceSteal> {
+        let mut steals: Vec<_> = self
+            .workers
+            .iter()
+            .flat_map(|trace| trace.lock().unwrap().steals.clone())
+            .collect();
+        steals.sort_by_key(|steal| (steal.time, steal.worker));
+        steals
+    }
+
+    /// Forget all the events.
+    pub fn reset(&self) {
+        for trace in self.workers.iter() {
+            *trace.lock().unwrap() = WorkerTrace::default();
+        }
+    }
+
+    /// Write the events as a Chrome `trace_event` JSON document.  Each worker is a thread of the
+    /// trace, on which the nodes are complete events named after their label, and the steals are
+    /// instant events.
+    pub fn write_chrome_trace<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "{{\"traceEvents\":[")?;
+        for worker in 0..self.workers.len() {
+            if worker > 0 {
+                write!(writer, ",")?;
+            }
+            write!(
+                writer,
+                "\n{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\
+                 \"args\":{{\"name\":\"worker {}\"}}}}",
+                worker, worker
+            )?;
+        }
+        for span in self.spans() {
+            write!(
+                writer,
+                ",\n{{\"name\":{},\"cat\":\"node\",\"ph\":\"X\",\"pid\":0,\"tid\":{},\
+                 \"ts\":{},\"dur\":{}}}",
+                json_string(span.label.as_deref().unwrap_or("<unnamed>")),
+                span.worker,
+                Micros(span.start),
+                Micros(span.end.saturating_sub(span.start))
+            )?;
+        }
+        for steal in self.steals() {
+            write!(
+                writer,
+                ",\n{{\"name\":\"steal\",\"cat\":\"steal\",\"ph\":\"i\",\"s\":\"t\",\
+                 \"pid\":0,\"tid\":{},\"ts\":{},\"args\":{{\"victim\":{}}}}}",
+                steal.worker,
+                Micros(steal.time),
+                steal.victim
+            )?;
+        }
+        writeln!(writer, "\n]}}")
+    }
+
+    /// The events as a Chrome `trace_event` JSON document.  See `write_chrome_trace`.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut trace = Vec::new();
+        self.write_chrome_trace(&mut trace)
+            .expect("Writing to a vector can't fail.");
+        String::from_utf8(trace).expect("The trace is valid UTF-8.")
+    }
+
+    fn trace(&self, worker: usize) -> Option<&Mutex<WorkerTrace>> {
+        self.workers.get(worker)
+    }
+}
+
+impl fmt::Debug for TraceCollector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TraceCollector")
+            .field("workers", &self.workers.len())
+            .finish()
+    }
+}
+
+impl TraceHook for TraceCollector {
+    fn on_execute_start(&self, worker: usize) {
+        if let Some(trace) = self.trace(worker) {
+            let start = self.origin.elapsed();
+            trace.lock().unwrap().open.push(TraceSpan {
+                worker,
+                label: None,
+                start,
+                end: start,
+            })
+        }
+    }
+
+    fn on_execute_labeled(&self, worker: usize, label: &str) {
+        if let Some(trace) = self.trace(worker) {
+            if let Some(span) = trace.lock().unwrap().open.last_mut() {
+                span.label = Some(label.to_string());
+            }
+        }
+    }
+
+    fn on_execute_end(&self, worker: usize) {
+        if let Some(trace) = self.trace(worker) {
+            let mut trace = trace.lock().unwrap();
+            if let Some(mut span) = trace.open.pop() {
+                span.end = self.origin.elapsed();
+                trace.spans.push(span);
+            }
+        }
+    }
+
+    fn on_steal(&self, worker: usize, victim: usize) {
+        if let Some(trace) = self.trace(worker) {
+            let time = self.origin.elapsed();
+            trace.lock().unwrap().steals.push(TraceSteal {
+                worker,
+                victim,
+                time,
+            })
+        }
+    }
+}
+
+/// A duration formatted in microseconds, the time unit of Chrome traces.
+struct Micros(Duration);
+
+impl fmt::Display for Micros {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let nanos = self.0.as_nanos();
+        write!(f, "{}.{:03}", nanos / 1000, nanos % 1000)
+    }
+}
+
+/// Quote and escape a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
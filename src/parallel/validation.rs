@@ -0,0 +1,169 @@
+//! Detection of nodes which were never executed.
+//!
+//! A node whose activators are not all activated is never scheduled, and nothing signals it: the
+//! execution simply returns once the other nodes have quiesced, and the results which depended on
+//! the node are missing.  This is typically due to a forgotten connection when building the graph.
+//!
+//! Runtimes created with `Toexec::with_validation` keep a weak reference to each node they
+//! finalize, which allows `Toexec::execute` to report the nodes which were left pending once the
+//! execution has quiesced, as an `ExecutionError::Stalled`.
+//!
+//! The same registry is used by the reusable runtime to detect leaked nodes in debug builds (see
+//! `parallel::multiple_uses`): a `LeakDetector` reports the nodes which outlive the runtime.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+
+use api::builder::Label;
+
+/// Node state which can be inspected once an execution has quiesced.
+pub(crate) trait Tracked: Send + Sync {
+    /// Describe the node if it is waiting for activations, or `None` if it is idle.
+    fn stalled(&self) -> Option<StalledNode>;
+
+    /// The label of the node, if any.
+    fn label(&self) -> Option<Label>;
+}
+
+/// Weak references to the nodes finalized by a runtime.
+#[derive(Default)]
+pub(crate) struct Registry<'r> {
+    nodes: Mutex<Vec<Weak<dyn Tracked + 'r>>>,
+}
+
+impl<'r> Registry<'r> {
+    /// Start tracking a node.
+    pub(crate) fn track(&self, node: Weak<dyn Tracked + 'r>) {
+        let mut nodes = self.nodes.lock().unwrap();
+        // Forget about dropped nodes before growing, so that graphs creating nodes dynamically
+        // don't make the registry grow unboundedly.
+        if nodes.len() == nodes.capacity() {
+            nodes.retain(|node| node.upgrade().is_some());
+        }
+        nodes.push(node)
+    }
+
+    /// The labels of the tracked nodes which are still alive.
+    pub(crate) fn alive(&self) -> Vec<Option<Label>> {
+        self.nodes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|node| node.upgrade().map(|node| node.label()))
+            .collect()
+    }
+
+    /// Report the tracked nodes which are waiting for activations.  Nodes which were dropped are
+    /// forgotten.
+    pub(crate) fn check(&self) -> Result<(), StalledGraphError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        nodes.retain(|node| node.upgrade().is_some());
+
+        let stalled: Vec<_> = nodes
+            .iter()
+            .filter_map(|node| node.upgrade().and_then(|node| node.stalled()))
+            .collect();
+        if stalled.is_empty() {
+            Ok(())
+        } else {
+            Err(StalledGraphError { nodes: stalled })
+        }
+    }
+}
+
+/// A node which was waiting for activations when the execution quiesced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StalledNode {
+    /// The label of the node, if any.
+    pub label: Option<Label>,
+    /// The number of activations the node was still waiting for.
+    pub pending: usize,
+}
+
+impl fmt::Display for StalledNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.label {
+            Some(ref label) => write!(f, "`{}`", label)?,
+            None => write!(f, "<unnamed>")?,
+        }
+        write!(f, " ({} pending)", self.pending)
+    }
+}
+
+/// The nodes which were never executed by an execution, see `ExecutionError::Stalled`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StalledGraphError {
+    /// The stalled nodes, in creation order.
+    pub nodes: Vec<StalledNode>,
+}
+
+impl fmt::Display for StalledGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} nodes are waiting for activations:", self.nodes.len())?;
+        for node in &self.nodes {
+            write!(f, " {}", node)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for StalledGraphError {}
+
+/// Reports the nodes of a reusable runtime which are still alive after it was dropped.  See
+/// `multiple_uses::Toexec::leak_detector`.
+///
+/// Once the runtime is dropped, the nodes built on it should only be reachable from the activators
+/// held outside of the graph.  Nodes which are still alive once those are dropped as well are
+/// typically part of a reference cycle -- e.g. nodes in a loop holding each other's activators --
+/// and will never be freed.
+pub struct LeakDetector<'r> {
+    registry: Arc<Registry<'r>>,
+}
+
+impl<'r> LeakDetector<'r> {
+    pub(crate) fn new(registry: Arc<Registry<'r>>) -> Self {
+        LeakDetector { registry }
+    }
+
+    /// Report the nodes which are still alive.  This should be called once the runtime, and the
+    /// activators held outside of the graph, were dropped: the nodes they keep alive are reported
+    /// otherwise.
+    pub fn check(&self) -> Result<(), LeakError> {
+        let nodes = self.registry.alive();
+        if nodes.is_empty() {
+            Ok(())
+        } else {
+            Err(LeakError { nodes })
+        }
+    }
+}
+
+impl<'r> fmt::Debug for LeakDetector<'r> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LeakDetector").finish_non_exhaustive()
+    }
+}
+
+/// The error returned by `LeakDetector::check` when some nodes are still alive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakError {
+    /// The labels of the nodes, in creation order.
+    pub nodes: Vec<Option<Label>>,
+}
+
+impl fmt::Display for LeakError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} reusable nodes are still alive after dropping the runtime, and may be leaked:",
+            self.nodes.len()
+        )?;
+        for label in &self.nodes {
+            write!(f, " {}", label.as_deref().unwrap_or("<unnamed>"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for LeakError {}
@@ -0,0 +1,5 @@
+//! Common parallel patterns, built as graphs on the parallel runtimes.
+//!
+//! This includes a parallel map in `par_map`.
+
+pub mod par_map;
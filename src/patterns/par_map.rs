@@ -0,0 +1,186 @@
+//! A one-call parallel map on top of the single-use runtime.
+//!
+//! `par_map_graph` applies a function to each element of a slice in parallel, by building and
+//! executing a scatter/compute/gather graph:
+//!
+//!  - a root *scatter* node activates one *compute* node per element;
+//!  - each compute node applies the function to its element, writes the result to its own port and
+//!    activates the gather node;
+//!  - the *gather* node, which fires once all the compute nodes have executed, collects the results
+//!    in order.
+//!
+//! This is mostly meant as an introduction to the runtime: it does not require any knowledge of
+//! ports or edges, and the graph it builds is a good starting point for hand-written ones.
+//!
+//! `par_map` builds the same graph as part of a larger one, with compute nodes mapping chunks of
+//! the slice.  It returns a `ParMap`, holding the activator starting the map and the port the
+//! results are joined into:
+//!
+//! ```rust,ignore
+//! let squares = runtime.build_scope(|b| par_map(b, &data, 4, |x| x * x));
+//! squares.activator.activate_once(&mut runtime);
+//! runtime.execute(4)?;
+//! assert_eq!(squares.output.recv(), Some(vec![1, 4, 9]));
+//! ```
+
+use std::sync::Arc;
+
+use api::prelude::*;
+use common::prelude::*;
+
+use parallel::failure::ExecutionError;
+use parallel::port::{RcReceiver, RcSender, SlotPort};
+use parallel::single_use::{RcActivator, RuntimeLoc, Toexec};
+
+/// Apply `f` to each element of `data` in parallel on `k` worker threads, and return the results
+/// in order.
+///
+/// The graph is built on `runtime` and executed immediately: nodes which were already scheduled on
+/// `runtime` are executed as well.  This fails if the execution does, see `Toexec::execute`.
+pub fn par_map_graph<'r, T, U, F>(
+    runtime: &mut Toexec<'r>,
+    k: usize,
+    data: &'r [T],
+    f: F,
+) -> Result<Vec<U>, ExecutionError>
+where
+    T: Sync + 'r,
+    U: Send + 'r,
+    F: Fn(&T) -> U + Send + Sync + 'r,
+{
+    let map = runtime.build_scope(|b| par_map(b, data, data.len(), f));
+    map.activator.activate_once(runtime);
+    runtime.execute(k)?;
+    Ok(map
+        .output
+        .recv()
+        .expect("The gather node of `par_map_graph` was not executed."))
+}
+
+/// The port receiving the results of a parallel map.
+pub type ParMapOutput<U> = RcReceiver<SlotPort<Option<Vec<U>>>>;
+
+/// A parallel map built by `par_map`.
+pub struct ParMap<'r, U> {
+    /// The activator starting the map, e.g. to be connected to the output of another node.
+    pub activator: RcActivator<'r>,
+    /// The joined output: the results, in order, are written to this port once all the elements
+    /// have been mapped.
+    pub output: ParMapOutput<U>,
+}
+
+/// Build a scatter/compute/gather graph applying `f` to each element of `data`, joining the
+/// results in order.
+///
+/// The elements are split in (at most) `workers` contiguous chunks of the same size, each mapped
+/// by its own compute node.  The graph runs once the returned activator is activated, and the
+/// results can be read from the returned output, e.g. by the nodes activated after the execution.
+/// Unlike `par_map_graph`, this can be used as part of a larger graph.
+///
+/// # Panics
+///
+/// This panics if `workers` is zero and `data` is not empty.
+pub fn par_map<'a, 'r, T, U, F>(
+    builder: &mut ScopedGraphBuilder<'a, Toexec<'r>>,
+    data: &'r [T],
+    workers: usize,
+    f: F,
+) -> ParMap<'r, U>
+where
+    'r: 'a,
+    T: Sync + 'r,
+    U: Send + 'r,
+    F: Fn(&T) -> U + Send + Sync + 'r,
+{
+    assert!(
+        workers > 0 || data.is_empty(),
+        "A parallel map needs at least one worker."
+    );
+
+    let f = Arc::new(f);
+    let chunk_size = data.len().div_ceil(workers.max(1)).max(1);
+    let (senders, receivers): (Vec<_>, Vec<_>) = data
+        .chunks(chunk_size)
+        .map(|_| builder.port(None).split())
+        .unzip();
+
+    let (output_sender, output) = builder.port(None).split();
+    let mut gather = builder.node(Gather {
+        inputs: receivers,
+        output: output_sender,
+    });
+
+    let computes = data
+        .chunks(chunk_size)
+        .zip(senders)
+        .map(|(items, output)| {
+            builder
+                .node(Compute {
+                    items,
+                    f: f.clone(),
+                    output,
+                    next: gather.add_activator(),
+                })
+                .add_activator()
+        })
+        .collect();
+
+    // The scatter node also activates the gather node, so that it runs even without compute
+    // nodes.
+    let activator = builder
+        .node(Scatter {
+            next: computes,
+            gather: gather.add_activator(),
+        })
+        .add_activator();
+    ParMap { activator, output }
+}
+
+/// The root node, activating all the compute nodes, then the gather node.
+struct Scatter<'r> {
+    next: Vec<RcActivator<'r>>,
+    gather: RcActivator<'r>,
+}
+
+impl<'r> NodeOnce<RuntimeLoc<'r>> for Scatter<'r> {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        for activator in self.next {
+            activator.activate_once(scheduler)
+        }
+        self.gather.activate_once(scheduler)
+    }
+}
+
+/// A node applying the function to a chunk of the elements.
+struct Compute<'r, T: 'r, U, F> {
+    items: &'r [T],
+    f: Arc<F>,
+    output: RcSender<SlotPort<Option<Vec<U>>>>,
+    next: RcActivator<'r>,
+}
+
+impl<'r, T, U, F: Fn(&T) -> U> NodeOnce<RuntimeLoc<'r>> for Compute<'r, T, U, F> {
+    fn execute_once(self, scheduler: &mut RuntimeLoc<'r>) {
+        let f = &self.f;
+        self.output
+            .send(Some(self.items.iter().map(|item| f(item)).collect()));
+        self.next.activate_once(scheduler)
+    }
+}
+
+/// The final node, collecting the results of the compute nodes in order.
+struct Gather<U> {
+    inputs: Vec<RcReceiver<SlotPort<Option<Vec<U>>>>>,
+    output: RcSender<SlotPort<Option<Vec<U>>>>,
+}
+
+impl<'r, U> NodeOnce<RuntimeLoc<'r>> for Gather<U> {
+    fn execute_once(self, _scheduler: &mut RuntimeLoc<'r>) {
+        let results = self
+            .inputs
+            .iter()
+            .flat_map(|input| input.recv().expect("Missing result in `par_map`."))
+            .collect();
+        self.output.send(Some(results))
+    }
+}
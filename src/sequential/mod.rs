@@ -0,0 +1,17 @@
+//! Sequential runtime implementations.
+//!
+//! These runtimes execute the ready queue on the calling thread, in scheduling order, without any
+//! work-stealing.  They are mostly meant for debugging and for deterministic tests: the
+//! `single_use` and `multiple_uses` modules expose the same `Toexec` and `RuntimeLoc` types as
+//! their `parallel` counterparts, so that a graph can be moved between sequential and parallel
+//! execution by changing a single `use` declaration.
+//!
+//! In order to keep graphs interchangeable, nodes and edges still need to be `Send` and `Sync`,
+//! and the ports and activators are the same thread-safe ones as in the parallel runtimes.
+//!
+//! The `static_plan` module executes small fixed graphs, described with arrays, without any heap
+//! allocation.
+
+pub mod single_use;
+pub mod static_plan;
+pub mod multiple_uses;
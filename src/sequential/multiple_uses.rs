@@ -0,0 +1,219 @@
+//! Sequential implementation of a reusable runtime with reference-counted activators.
+//!
+//! The same memory leak caveats as for the `parallel::multiple_uses` runtime apply.
+
+use api::prelude::*;
+use common::prelude::*;
+use common::run::RunMode;
+use custom::rc::RcPortSpec;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use parallel::port::RcPort;
+use parallel::termination::HelpError;
+use sync::Mutex;
+
+pub use custom::rc::{RcActivator, RcBuilder, RcHandle};
+
+/// The type of nodes manipulated by the sequential reusable runtime.
+pub type RuntimeNode<'r> = dyn NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r;
+
+pub type RuntimeActivator<'r> = RcActivator<RuntimeNode<'r>>;
+
+/// A sequential runtime for reusable graphs.
+///
+/// Nodes are executed in the order in which they were scheduled.
+pub struct Toexec<'r> {
+    pub ready: VecDeque<RcHandle<RuntimeNode<'r>>>,
+    /// The named outputs of the graphs built on this runtime.
+    outputs: GraphOutputs,
+    /// Whether executions may run.  See `pause`.
+    mode: RunMode,
+}
+
+/// The scheduler type passed to executing tasks.
+///
+/// Since there are no workers, this is simply the runtime itself.
+pub type RuntimeLoc<'r> = Toexec<'r>;
+
+impl<'r> Toexec<'r> {
+    pub fn new() -> Self {
+        Toexec {
+            ready: VecDeque::new(),
+            outputs: GraphOutputs::new(),
+            mode: RunMode::Running,
+        }
+    }
+
+    /// Execute nodes until the ready queue is empty, unless the runtime is paused.
+    ///
+    /// The number of workers is ignored; it is only accepted so that code written against the
+    /// parallel runtimes can be reused unchanged.
+    pub fn execute(&mut self, _k: usize) {
+        if !self.mode.start_instant() {
+            return;
+        }
+        while let Some(handle) = self.ready.pop_front() {
+            handle.execute_once(self);
+        }
+    }
+
+    /// Stop running executions: `execute` returns without executing anything, and the ready nodes
+    /// stay queued until `step` or `resume` is called.  See `PauseSpec`.
+    pub fn pause(&mut self) {
+        self.mode = RunMode::Paused
+    }
+
+    /// Allow exactly one more execution to run, then pause again.
+    pub fn step(&mut self) {
+        self.mode = RunMode::Step
+    }
+
+    /// Return to free-running mode, running executions again.
+    pub fn resume(&mut self) {
+        self.mode = RunMode::Running
+    }
+
+    /// Whether the runtime is paused, either through `pause` or after the execution allowed by
+    /// `step` was started.
+    pub fn is_paused(&self) -> bool {
+        self.mode == RunMode::Paused
+    }
+
+    /// Execute other nodes until `predicate` returns `true`.
+    ///
+    /// This is the sequential counterpart of `parallel::single_use::RuntimeLoc::help_until`: the
+    /// ready nodes are executed in order until the predicate holds, and this fails with
+    /// `HelpError::Deadlock` once there are no nodes left to execute.
+    pub fn help_until<P: Fn() -> bool>(&mut self, predicate: P) -> Result<(), HelpError> {
+        self.help(&predicate, None)
+    }
+
+    /// Like `help_until`, but fails with `HelpError::Timeout` if `predicate` still returns `false`
+    /// after `timeout`.
+    pub fn help_until_timeout<P: Fn() -> bool>(
+        &mut self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<(), HelpError> {
+        self.help(&predicate, Some(Instant::now() + timeout))
+    }
+
+    fn help(
+        &mut self,
+        predicate: &dyn Fn() -> bool,
+        deadline: Option<Instant>,
+    ) -> Result<(), HelpError> {
+        loop {
+            if predicate() {
+                return Ok(());
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(HelpError::Timeout);
+            }
+            match self.ready.pop_front() {
+                Some(node) => node.execute_once(self),
+                None => return Err(HelpError::Deadlock),
+            }
+        }
+    }
+
+    /// Execute nodes until a value is written on the port read by `receiver`, and return that
+    /// value.  Nodes which were scheduled but not executed yet are dropped.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the ready queue is exhausted without writing to the port.
+    pub fn execute_until<T, R>(&mut self, _k: usize, receiver: R) -> T
+    where
+        R: Receiver<Item = Option<T>>,
+    {
+        loop {
+            if let Some(value) = receiver.recv() {
+                self.ready.clear();
+                return value;
+            }
+            match self.ready.pop_front() {
+                Some(handle) => handle.execute_once(self),
+                None => panic!("Graph quiesced without writing the result port."),
+            }
+        }
+    }
+}
+
+impl<'r> Default for Toexec<'r> {
+    fn default() -> Self {
+        Toexec::new()
+    }
+}
+
+impl<'r> Scheduler for Toexec<'r> {
+    type Handle = RcHandle<RuntimeNode<'r>>;
+
+    fn schedule(&mut self, handle: Self::Handle) {
+        self.ready.push_back(handle);
+    }
+}
+
+impl<'r> Capabilities for Toexec<'r> {}
+
+impl<'r> OutputSpec for Toexec<'r> {
+    fn outputs(&self) -> &GraphOutputs {
+        &self.outputs
+    }
+
+    fn outputs_mut(&mut self) -> &mut GraphOutputs {
+        &mut self.outputs
+    }
+}
+
+impl<'r> ExecuteSpec for Toexec<'r> {
+    fn new() -> Self {
+        Toexec::new()
+    }
+
+    fn execute(&mut self, workers: usize) {
+        Toexec::execute(self, workers)
+    }
+}
+
+impl<'r> PauseSpec for Toexec<'r> {
+    fn pause(&mut self) {
+        Toexec::pause(self)
+    }
+
+    fn step(&mut self) {
+        Toexec::step(self)
+    }
+
+    fn resume(&mut self) {
+        Toexec::resume(self)
+    }
+
+    fn is_paused(&self) -> bool {
+        Toexec::is_paused(self)
+    }
+}
+
+impl<'r> GraphSpec for Toexec<'r> {
+    type Activator = RuntimeActivator<'r>;
+}
+
+impl<'r, N: NodeMut<RuntimeLoc<'r>> + Send + Sync + 'r> NodeSpec<N> for Toexec<'r> {
+    type Builder = RcBuilder<N>;
+
+    fn node(&self, node: N) -> Self::Builder {
+        RcBuilder::new(node)
+    }
+}
+
+impl<'r, T: Default + 'r> PortSpec<T> for Toexec<'r> {
+    type Port = RcPort<Mutex<T>>;
+
+    fn port(&self, init: T) -> Self::Port {
+        RcPort::new(Mutex::new(init))
+    }
+}
+
+impl<'r> RcPortSpec for Toexec<'r> {}
@@ -0,0 +1,205 @@
+//! Fixed graphs executed without heap allocations.
+//!
+//! Small embedded graphs often have a shape known at compile time.  An `ExecutablePlan` describes
+//! such a graph with arrays: `N` nodes, and `E` edges given as `(source, target)` pairs of node
+//! indices.  The plan is built by a `const fn`, which checks the graph and computes its execution
+//! order at compile time, so that it can be stored in a `static`:
+//!
+//! ```rust,ignore
+//! fn sum(inputs: &Inputs<i32>) -> i32 {
+//!     inputs.iter().sum()
+//! }
+//!
+//! fn product(inputs: &Inputs<i32>) -> i32 {
+//!     inputs.iter().product()
+//! }
+//!
+//! static PLAN: ExecutablePlan<i32, 4, 4> = ExecutablePlan::new(
+//!     [StaticNode::Input(0), StaticNode::Input(1), StaticNode::Map(sum), StaticNode::Map(product)],
+//!     [(0, 2), (1, 2), (2, 3), (1, 3)],
+//! );
+//!
+//! let mut values = [0; 4];
+//! PLAN.execute(&[2, 3], &mut values);
+//! assert_eq!(values[3], 15);
+//! ```
+//!
+//! Executing a plan evaluates every node once, in topological order, and stores the value of each
+//! node in a caller-provided array; nothing is allocated.  The `i`-th input of a node is the
+//! source of the `i`-th edge, in the order of the edge list, whose target is the node.
+//!
+//! Contrary to the other runtimes, static plans have no ports or activators: all the nodes are
+//! executed at each execution, and values are plain `Copy` data.
+
+/// A node of a static graph.
+pub enum StaticNode<T> {
+    /// A node taking the value of the external input with the given index.  Input nodes must not
+    /// be the target of any edge.
+    Input(usize),
+    /// A node computing its value from the values of its inputs.
+    Map(fn(&Inputs<T>) -> T),
+}
+
+impl<T> Clone for StaticNode<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for StaticNode<T> {}
+
+/// The values of the inputs of a node being executed.
+pub struct Inputs<'a, T: 'a> {
+    values: &'a [T],
+    sources: &'a [usize],
+}
+
+impl<'a, T: Copy> Inputs<'a, T> {
+    /// The number of inputs.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Whether the node has no inputs.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// The value of the `i`-th input.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the node has `i` inputs or less.
+    pub fn get(&self, i: usize) -> T {
+        self.values[self.sources[i]]
+    }
+
+    /// Iterate over the values of the inputs, in order.
+    pub fn iter<'b>(&'b self) -> impl Iterator<Item = T> + 'b {
+        self.sources.iter().map(move |&source| self.values[source])
+    }
+}
+
+/// A checked static graph along with its execution order.  See the module documentation.
+pub struct ExecutablePlan<T, const N: usize, const E: usize> {
+    nodes: [StaticNode<T>; N],
+    /// The nodes, in execution order.
+    order: [usize; N],
+    /// The sources of the edges, sorted by target and then by position in the edge list.
+    sources: [usize; E],
+    /// The index in `sources` of the first input of each node.
+    first_input: [usize; N],
+    /// The number of inputs of each node.
+    input_count: [usize; N],
+}
+
+impl<T, const N: usize, const E: usize> ExecutablePlan<T, N, E> {
+    /// Check a static graph and compute its execution order.
+    ///
+    /// # Panics
+    ///
+    /// This panics, or fails to compile when evaluated in a constant, if an edge refers to a node
+    /// which does not exist, if an input node is the target of an edge, or if the graph has a
+    /// cycle.
+    pub const fn new(nodes: [StaticNode<T>; N], edges: [(usize, usize); E]) -> Self {
+        let mut input_count = [0; N];
+        let mut i = 0;
+        while i < E {
+            let (source, target) = edges[i];
+            assert!(
+                source < N && target < N,
+                "An edge refers to a node which does not exist."
+            );
+            if let StaticNode::Input(_) = nodes[target] {
+                panic!("An input node is the target of an edge.");
+            }
+            input_count[target] += 1;
+            i += 1;
+        }
+
+        // Group the sources by target, keeping the order of the edge list.
+        let mut first_input = [0; N];
+        let mut n = 1;
+        while n < N {
+            first_input[n] = first_input[n - 1] + input_count[n - 1];
+            n += 1;
+        }
+        let mut sources = [0; E];
+        let mut filled = [0; N];
+        i = 0;
+        while i < E {
+            let (source, target) = edges[i];
+            sources[first_input[target] + filled[target]] = source;
+            filled[target] += 1;
+            i += 1;
+        }
+
+        // Kahn's algorithm, using `order` as the queue of the nodes whose inputs are all known.
+        let mut order = [0; N];
+        let mut missing = input_count;
+        let mut queued = 0;
+        n = 0;
+        while n < N {
+            if missing[n] == 0 {
+                order[queued] = n;
+                queued += 1;
+            }
+            n += 1;
+        }
+        let mut done = 0;
+        while done < queued {
+            let node = order[done];
+            done += 1;
+            i = 0;
+            while i < E {
+                let (source, target) = edges[i];
+                if source == node {
+                    missing[target] -= 1;
+                    if missing[target] == 0 {
+                        order[queued] = target;
+                        queued += 1;
+                    }
+                }
+                i += 1;
+            }
+        }
+        assert!(queued == N, "The static graph has a cycle.");
+
+        ExecutablePlan {
+            nodes,
+            order,
+            sources,
+            first_input,
+            input_count,
+        }
+    }
+
+    /// The nodes, in the order they are executed.
+    pub fn order(&self) -> &[usize; N] {
+        &self.order
+    }
+}
+
+impl<T: Copy, const N: usize, const E: usize> ExecutablePlan<T, N, E> {
+    /// Execute all the nodes, storing the value of each node in `values`.  `inputs` holds the
+    /// values of the external inputs.
+    ///
+    /// # Panics
+    ///
+    /// This panics if an input node refers to an input which is not in `inputs`.
+    pub fn execute(&self, inputs: &[T], values: &mut [T; N]) {
+        for &node in &self.order {
+            let value = match self.nodes[node] {
+                StaticNode::Input(input) => inputs[input],
+                StaticNode::Map(f) => {
+                    let first = self.first_input[node];
+                    f(&Inputs {
+                        values: &values[..],
+                        sources: &self.sources[first..first + self.input_count[node]],
+                    })
+                }
+            };
+            values[node] = value;
+        }
+    }
+}
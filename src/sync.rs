@@ -0,0 +1,34 @@
+//! The synchronization primitives used by the activators and the ports.
+//!
+//! The pending counts of the reusable activators are updated from several workers at once: a node
+//! is rearmed by the worker executing it while its producers may already be activating it for the
+//! next round.  These races are model-checked with [loom](https://docs.rs/loom), which explores
+//! all the interleavings of a test.  When compiled with `--cfg loom`, this module exports the
+//! primitives of loom instead of the ones of `std`:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release loom
+//! ```
+//!
+//! Loom primitives can only be used inside of `loom::model`, so that only the loom tests can be
+//! run in this configuration.
+//!
+//! Loom's `Arc` supports neither weak references nor unsized coercions, which the activators and
+//! the ports rely on: reference counts always use the `std` implementation, and loom only checks
+//! the atomics and mutexes they point to.
+
+// Some primitives are only used with some features, e.g. `AtomicBool` by `CheckedPort`.
+#![allow(unused_imports)]
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicUsize};
+#[cfg(loom)]
+pub(crate) use loom::sync::{Mutex, MutexGuard};
+
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{AtomicBool, AtomicUsize};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Mutex, MutexGuard};
+
+pub(crate) use std::sync::atomic::Ordering;
+pub(crate) use std::sync::{Arc, Weak};
@@ -0,0 +1,325 @@
+//! Property-based stress testing of the runtimes.
+//!
+//! A `GraphFuzzer` generates random DAGs whose outputs are known in advance, runs each of them with
+//! every number of workers from 1 to a maximum, and checks the scheduling contracts: each node of
+//! the DAG is executed exactly once, i.e. each armed activator fires exactly once, and computes the
+//! expected value from the values of its inputs.
+//!
+//! ```rust,ignore
+//! GraphFuzzer::new().dags(100).max_workers(8).assert();
+//! ```
+//!
+//! Each node of a DAG is a `TaskNode` with a `StrictTask` combining the values of its inputs with
+//! `RandomDag::evaluate`; the inputs of a node are collected by a `JoinNode`.  Custom ports and
+//! activators are validated by building the same DAGs with them, and checking the values produced
+//! by each node with `check_with`:
+//!
+//! ```rust,ignore
+//! GraphFuzzer::new().assert_with(|dag, workers| {
+//!     // Build `dag` with the custom components, execute it with `workers` workers, and return
+//!     // the values produced by each node, in execution order.
+//!     run_custom(dag, workers)
+//! });
+//! ```
+//!
+//! Failures report the seed of the DAG, which can be regenerated with `RandomDag::generate`.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use api::prelude::*;
+use common::prelude::*;
+use parallel::single_use::Toexec;
+
+/// A random DAG.  Node `i` only reads from nodes with a smaller index, so that the nodes are in
+/// topological order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomDag {
+    /// The seed the DAG was generated from.
+    pub seed: u64,
+    /// The producers of the inputs of each node, in input order.  Nodes without inputs are the
+    /// roots of the DAG.
+    pub inputs: Vec<Vec<usize>>,
+}
+
+impl RandomDag {
+    /// Generate a DAG of `nodes` nodes with at most `max_inputs` inputs each.  The same seed always
+    /// generates the same DAG.
+    pub fn generate(seed: u64, nodes: usize, max_inputs: usize) -> Self {
+        // The state of a xorshift generator must not be zero.
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        let mut random = move |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let inputs = (0..nodes)
+            .map(|node| {
+                let count = if node == 0 {
+                    0
+                } else {
+                    random(max_inputs.min(node) + 1)
+                };
+                (0..count).map(|_| random(node)).collect()
+            })
+            .collect();
+        RandomDag { seed, inputs }
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Whether the DAG has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// The value computed by `node` from the values of its inputs, in input order.
+    pub fn evaluate(node: usize, inputs: &[u64]) -> u64 {
+        inputs.iter().fold(node as u64 + 1, |acc, &x| {
+            acc.wrapping_mul(31).wrapping_add(x)
+        })
+    }
+
+    /// The value computed by each node.
+    pub fn expected(&self) -> Vec<u64> {
+        let mut values: Vec<u64> = Vec::with_capacity(self.len());
+        for (node, inputs) in self.inputs.iter().enumerate() {
+            let inputs: Vec<_> = inputs.iter().map(|&input| values[input]).collect();
+            values.push(RandomDag::evaluate(node, &inputs));
+        }
+        values
+    }
+
+    /// Build the DAG on the parallel single-use runtime, execute it with `workers` workers, and
+    /// return the values produced by each node, in execution order.
+    pub fn run(&self, workers: usize) -> Vec<Vec<u64>> {
+        let produced: Arc<Vec<Mutex<Vec<u64>>>> =
+            Arc::new((0..self.len()).map(|_| Mutex::new(Vec::new())).collect());
+        let mut runtime = Toexec::new();
+        let roots = runtime.build_scope(|b| {
+            // Build from the sinks, so that the inputs of the consumers of a node are known when
+            // it is created.
+            let mut outputs: Vec<_> = (0..self.len())
+                .map(|_| CloneOutput::<JoinInput<Toexec<'static>, u64>>::new())
+                .collect();
+            let mut roots = Vec::new();
+            for node in (0..self.len()).rev() {
+                let output = outputs.pop().unwrap();
+                let produced = produced.clone();
+                let record = move |value: u64| {
+                    produced[node].lock().unwrap().push(value);
+                    (value,)
+                };
+                let inputs = &self.inputs[node];
+                if inputs.is_empty() {
+                    let activator = b
+                        .node(TaskNode {
+                            inputs: (),
+                            outputs: (output,),
+                            task: StrictTask::new(move || record(RandomDag::evaluate(node, &[]))),
+                        })
+                        .add_activator();
+                    roots.push(activator);
+                    continue;
+                }
+                let (sender, receiver) = b.port(None).split();
+                let activator = b
+                    .node(TaskNode {
+                        inputs: (receiver.as_data_input(),),
+                        outputs: (output,),
+                        task: StrictTask::new(move |values: Option<Vec<u64>>| {
+                            record(RandomDag::evaluate(node, &values.unwrap_or_default()))
+                        }),
+                    })
+                    .add_activator();
+                let joined = sender
+                    .with_activator(activator)
+                    .map(Some as fn(Vec<u64>) -> Option<Vec<u64>>);
+                for (&producer, input) in inputs.iter().zip(b.join(inputs.len(), joined)) {
+                    outputs[producer].connect(input);
+                }
+            }
+            roots
+        });
+        for root in roots {
+            root.activate_once(&mut runtime);
+        }
+        if let Err(error) = runtime.execute(workers) {
+            panic!("{}", error)
+        }
+        let produced = Arc::try_unwrap(produced).unwrap_or_else(|_| panic!("The graph leaked."));
+        produced
+            .into_iter()
+            .map(|values| values.into_inner().unwrap())
+            .collect()
+    }
+}
+
+/// A node of a random DAG which broke the scheduling contracts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzFailure {
+    /// The DAG.
+    pub dag: RandomDag,
+    /// The number of workers of the failing run.
+    pub workers: usize,
+    /// The failing node.
+    pub node: usize,
+    /// The value the node should have produced once.
+    pub expected: u64,
+    /// The values the node produced, in execution order.
+    pub actual: Vec<u64>,
+}
+
+impl fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "node {} of the DAG with seed {} ({} nodes) ",
+            self.node,
+            self.dag.seed,
+            self.dag.len()
+        )?;
+        match self.actual.len() {
+            0 => write!(f, "was never executed")?,
+            1 => write!(
+                f,
+                "produced {} instead of {}",
+                self.actual[0], self.expected
+            )?,
+            n => write!(f, "was executed {} times, producing {:?}", n, self.actual)?,
+        }
+        write!(f, " with {} workers", self.workers)
+    }
+}
+
+impl Error for FuzzFailure {}
+
+/// Runs random DAGs with varying numbers of workers, and checks their outputs.  See the module
+/// documentation.
+#[derive(Debug, Clone)]
+pub struct GraphFuzzer {
+    seed: u64,
+    dags: usize,
+    nodes: usize,
+    max_inputs: usize,
+    max_workers: usize,
+    repeat: usize,
+}
+
+impl Default for GraphFuzzer {
+    fn default() -> Self {
+        GraphFuzzer::new()
+    }
+}
+
+impl GraphFuzzer {
+    /// A fuzzer running 20 DAGs of 50 nodes with at most 4 inputs each, with 1 to 4 workers, twice
+    /// for each number of workers.
+    pub fn new() -> Self {
+        GraphFuzzer {
+            seed: 0,
+            dags: 20,
+            nodes: 50,
+            max_inputs: 4,
+            max_workers: 4,
+            repeat: 2,
+        }
+    }
+
+    /// Generate the DAGs from the seeds starting at `seed`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Run `dags` DAGs.
+    pub fn dags(mut self, dags: usize) -> Self {
+        self.dags = dags;
+        self
+    }
+
+    /// Generate DAGs of `nodes` nodes.
+    pub fn nodes(mut self, nodes: usize) -> Self {
+        self.nodes = nodes;
+        self
+    }
+
+    /// Generate nodes with at most `max_inputs` inputs.
+    pub fn max_inputs(mut self, max_inputs: usize) -> Self {
+        self.max_inputs = max_inputs;
+        self
+    }
+
+    /// Run each DAG with 1 to `max_workers` workers.
+    pub fn max_workers(mut self, max_workers: usize) -> Self {
+        self.max_workers = max_workers;
+        self
+    }
+
+    /// Run each DAG `repeat` times for each number of workers.
+    pub fn repeat(mut self, repeat: usize) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Run the DAGs on the parallel single-use runtime, stopping at the first failure.  This
+    /// returns the number of runs.
+    pub fn check(&self) -> Result<usize, Box<FuzzFailure>> {
+        self.check_with(|dag, workers| dag.run(workers))
+    }
+
+    /// Like `check`, but panic with the failure, if any.
+    pub fn assert(&self) {
+        if let Err(failure) = self.check() {
+            panic!("Scheduling contract broken: {}", failure)
+        }
+    }
+
+    /// Run the DAGs with `run`, stopping at the first failure.  `run` is given a DAG and a number
+    /// of workers, and returns the values produced by each node, in execution order.  This returns
+    /// the number of runs.
+    pub fn check_with<F>(&self, mut run: F) -> Result<usize, Box<FuzzFailure>>
+    where
+        F: FnMut(&RandomDag, usize) -> Vec<Vec<u64>>,
+    {
+        let mut runs = 0;
+        for seed in self.seed..self.seed + self.dags as u64 {
+            let dag = RandomDag::generate(seed, self.nodes, self.max_inputs);
+            let expected = dag.expected();
+            for workers in 1..=self.max_workers {
+                for _ in 0..self.repeat {
+                    let produced = run(&dag, workers);
+                    runs += 1;
+                    for (node, &expected) in expected.iter().enumerate() {
+                        let actual = produced.get(node).cloned().unwrap_or_default();
+                        if actual != [expected] {
+                            return Err(Box::new(FuzzFailure {
+                                dag,
+                                workers,
+                                node,
+                                expected,
+                                actual,
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(runs)
+    }
+
+    /// Like `check_with`, but panic with the failure, if any.
+    pub fn assert_with<F>(&self, run: F)
+    where
+        F: FnMut(&RandomDag, usize) -> Vec<Vec<u64>>,
+    {
+        if let Err(failure) = self.check_with(run) {
+            panic!("Scheduling contract broken: {}", failure)
+        }
+    }
+}
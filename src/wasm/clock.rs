@@ -0,0 +1,110 @@
+//! Pluggable clocks used to implement timers in the cooperative runtimes.
+//!
+//! The cooperative runtimes do not own any thread and can therefore not wait for time to pass by
+//! themselves.  Instead, timers are delegated to a `Clock` which is responsible for invoking a
+//! callback once the requested delay has elapsed.  The callback then pushes the timer's event into
+//! the runtime through its `Injector`.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A callback invoked by a `Clock` when a timer expires.
+pub type TimerCallback<'r> = Box<dyn FnOnce() + Send + 'r>;
+
+/// A source of timers.
+///
+/// When compiled to WebAssembly, this is expected to be implemented on top of `setTimeout` by
+/// moving the callback into a JS closure.  Natively, the `ThreadClock` and `ManualClock`
+/// implementations below can be used instead.
+pub trait Clock<'r> {
+    /// Invoke `callback` once, after `delay` has elapsed.
+    fn set_timeout(&self, delay: Duration, callback: TimerCallback<'r>);
+}
+
+/// A clock which spawns a sleeping thread for each timer.
+///
+/// This is only meant to be used natively, since threads are usually not available in the
+/// browser.  Because the sleeping threads may outlive any scope, the callbacks must be `'static`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadClock;
+
+impl Clock<'static> for ThreadClock {
+    fn set_timeout(&self, delay: Duration, callback: TimerCallback<'static>) {
+        thread::spawn(move || {
+            thread::sleep(delay);
+            callback()
+        });
+    }
+}
+
+struct ManualClockInner<'r> {
+    /// The time elapsed since the clock was created.
+    now: Duration,
+    /// The pending timers, along with their expiration time.
+    timers: Vec<(Duration, TimerCallback<'r>)>,
+}
+
+/// A clock which only advances when explicitly told to.
+///
+/// Cloning a `ManualClock` yields a new handle to the same clock, which allows keeping a handle
+/// around after installing the clock in a runtime.  This is mostly useful for deterministic tests.
+#[derive(Clone)]
+pub struct ManualClock<'r> {
+    inner: Arc<Mutex<ManualClockInner<'r>>>,
+}
+
+impl<'r> ManualClock<'r> {
+    /// Create a new clock starting at time zero.
+    pub fn new() -> Self {
+        ManualClock {
+            inner: Arc::new(Mutex::new(ManualClockInner {
+                now: Duration::from_secs(0),
+                timers: Vec::new(),
+            })),
+        }
+    }
+
+    /// The time elapsed since the clock was created.
+    pub fn now(&self) -> Duration {
+        self.inner.lock().unwrap().now
+    }
+
+    /// Advance the clock and invoke the callbacks of all the timers which expired, in expiration
+    /// order.
+    ///
+    /// Callbacks are invoked without holding any lock, so that they can set new timers; however
+    /// timers set by a callback will only fire on the next call to `advance`.
+    pub fn advance(&self, by: Duration) {
+        let mut expired: Vec<_> = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.now += by;
+            let now = inner.now;
+            let (expired, pending) = inner
+                .timers
+                .drain(..)
+                .partition(|&(deadline, _)| deadline <= now);
+            inner.timers = pending;
+            expired
+        };
+        expired.sort_by_key(|&(deadline, _)| deadline);
+
+        for (_, callback) in expired {
+            callback()
+        }
+    }
+}
+
+impl<'r> Default for ManualClock<'r> {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl<'r> Clock<'r> for ManualClock<'r> {
+    fn set_timeout(&self, delay: Duration, callback: TimerCallback<'r>) {
+        let mut inner = self.inner.lock().unwrap();
+        let deadline = inner.now + delay;
+        inner.timers.push((deadline, callback));
+    }
+}
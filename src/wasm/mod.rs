@@ -0,0 +1,21 @@
+//! Cooperative runtime implementations meant to run on the browser's event loop.
+//!
+//! WebAssembly modules running in the browser cannot block the main thread, nor can they spawn
+//! worker threads the way the `parallel` runtimes do.  Instead, the runtimes in this module never
+//! take control of the calling thread for longer than requested: `execute_step` processes a
+//! bounded batch of nodes and returns, leaving it to the host (typically a JS callback scheduled
+//! with `setTimeout` or `requestAnimationFrame`) to call it again while there is work left.
+//!
+//! Nodes scheduled by running nodes are treated as microtasks and are always executed before the
+//! next external event is processed, mirroring the browser's own microtask semantics.  External
+//! events are pushed from the outside through an `Injector`, and timers are delegated to a
+//! pluggable `Clock` (see the `clock` module) so that they can be mapped to `setTimeout` when
+//! compiled to WebAssembly, or to a plain thread or a manually driven clock natively.
+//! Natively, periodic timers (see `common::timer`) can also be registered with `add_timer`.
+//! For debugging, the runtimes can be paused between external events and stepped one event at a
+//! time.
+//!
+//! This includes a single-use runtime in `single_use`.
+
+pub mod clock;
+pub mod single_use;
@@ -0,0 +1,342 @@
+//! Cooperative implementation of a single-use runtime with reference-counted activators.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use api::prelude::*;
+use common::interface::{GraphOutputs, OutputSpec};
+use common::run::{ExecuteSpec, PauseSpec, RunMode};
+use common::timer::{Tick, TickPayload, Timer, TimerHandle, TimerSource};
+use custom::rc::{OnceActivator, OnceBuilder, RcPortSpec};
+
+use parallel::port::{RcPort, SlotPort};
+use sync::Mutex;
+use wasm::clock::Clock;
+
+/// A reference-counted, single-use activator.  The node is scheduled as a microtask when all its
+/// activators have been activated.
+///
+/// Even though the runtime is single-threaded, the activators use atomic reference counting so
+/// that edges pointing into the graph can be moved into an `Injector` and timer callbacks, which
+/// may be invoked from other threads when running natively.
+pub type RcActivator<'r> = OnceActivator<RuntimeNode<'r>>;
+
+/// A builder for single-use nodes.
+pub type RcBuilder<'r, N> = OnceBuilder<RuntimeNode<'r>, N>;
+
+/// The type of nodes manipulated by the cooperative single-use runtime.
+type RuntimeNode<'r> = dyn NodeBox<Toexec<'r>> + Send + Sync + 'r;
+
+/// A node sending a value on an edge when executed.  This is used to inject external events.
+struct Injected<E, T> {
+    edge: E,
+    item: T,
+}
+
+impl<'r, T, E: OutputEdgeOnce<Toexec<'r>, Item = T>> NodeOnce<Toexec<'r>> for Injected<E, T> {
+    fn execute_once(self, scheduler: &mut Toexec<'r>) {
+        self.edge.send_activate_once(scheduler, self.item)
+    }
+}
+
+/// A handle for pushing external events into a cooperative runtime.
+///
+/// Injectors are cheap to clone, `Send` and `Sync`, so that they can be moved into the closures
+/// handed to JS callbacks (or to other threads, when running natively).  Each injected event is
+/// processed as a separate macrotask by `Toexec::execute_step`.
+#[derive(Clone)]
+pub struct Injector<'r> {
+    queue: Arc<Mutex<VecDeque<Box<RuntimeNode<'r>>>>>,
+}
+
+impl<'r> Injector<'r> {
+    fn new() -> Self {
+        Injector {
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Queue a node for execution.
+    pub fn inject<N: NodeBox<Toexec<'r>> + Send + Sync + 'r>(&self, node: N) {
+        self.queue.lock().unwrap().push_back(Box::new(node))
+    }
+
+    /// Queue an event which will send `item` on `edge`, activating the target node.
+    pub fn inject_send<E>(&self, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<Toexec<'r>> + Send + Sync + 'r,
+        E::Item: Send + Sync + 'r,
+    {
+        self.inject(Injected { edge, item })
+    }
+
+    fn pop(&self) -> Option<Box<RuntimeNode<'r>>> {
+        self.queue.lock().unwrap().pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+}
+
+/// A cooperative runtime for single-use graphs.
+///
+/// The runtime executes nodes on the calling thread, and only when asked to through
+/// `execute_step` or `execute`.
+///
+/// An *instant* is the processing of a single external event, along with all the microtasks it
+/// schedules.  For debugging, the runtime can be paused between instants with `pause`, advanced
+/// one instant at a time with `step`, and resumed with `resume`.  External events keep queuing up
+/// while the runtime is paused.
+pub struct Toexec<'r> {
+    /// Nodes scheduled while executing other nodes.
+    microtasks: VecDeque<Box<RuntimeNode<'r>>>,
+    /// External events waiting to be processed.
+    injector: Injector<'r>,
+    /// The clock used for timers, if any.
+    clock: Option<Box<dyn Clock<'r> + 'r>>,
+    /// The periodic timers owned by the runtime.  They are stopped when the runtime is dropped.
+    timers: Vec<Timer>,
+    /// The named outputs of the graphs built on this runtime.
+    outputs: GraphOutputs,
+    /// Whether new instants may be started.
+    mode: RunMode,
+}
+
+/// The scheduler type passed to executing tasks.
+///
+/// Since the runtime is single-threaded, this is simply the runtime itself; this alias is provided
+/// so that tasks written against the parallel runtimes can be reused with only a `use` change.
+pub type RuntimeLoc<'r> = Toexec<'r>;
+
+impl<'r> Toexec<'r> {
+    /// Create a new runtime without a clock.  Such a runtime does not support timers.
+    pub fn new() -> Self {
+        Toexec {
+            microtasks: VecDeque::new(),
+            injector: Injector::new(),
+            clock: None,
+            timers: Vec::new(),
+            outputs: GraphOutputs::new(),
+            mode: RunMode::Running,
+        }
+    }
+
+    /// Create a new runtime using `clock` for timers.
+    pub fn with_clock<C: Clock<'r> + 'r>(clock: C) -> Self {
+        Toexec {
+            clock: Some(Box::new(clock)),
+            ..Toexec::new()
+        }
+    }
+
+    /// Get a handle for injecting external events into this runtime.
+    pub fn injector(&self) -> Injector<'r> {
+        self.injector.clone()
+    }
+
+    /// Send `item` on `edge` once `delay` has elapsed, as reported by the runtime's clock.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the runtime was created without a clock.
+    pub fn set_timeout<E>(&self, delay: Duration, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<Toexec<'r>> + Send + Sync + 'r,
+        E::Item: Send + Sync + 'r,
+    {
+        let clock = self
+            .clock
+            .as_ref()
+            .expect("Timers require a runtime created with a clock.");
+        let injector = self.injector();
+        clock.set_timeout(delay, Box::new(move || injector.inject_send(edge, item)))
+    }
+
+    /// Stop starting new instants.  The microtasks of the current instant are still executed, but
+    /// external events stay queued until `step` or `resume` is called.
+    pub fn pause(&mut self) {
+        self.mode = RunMode::Paused
+    }
+
+    /// Allow exactly one more instant to run, then pause again.  The instant is executed by the
+    /// next calls to `execute_step` or `execute`.
+    pub fn step(&mut self) {
+        self.mode = RunMode::Step
+    }
+
+    /// Return to free-running mode, processing queued external events again.
+    pub fn resume(&mut self) {
+        self.mode = RunMode::Running
+    }
+
+    /// Whether the runtime is paused, either through `pause` or after the instant allowed by
+    /// `step` was started.
+    pub fn is_paused(&self) -> bool {
+        self.mode == RunMode::Paused
+    }
+
+    /// Start a new instant by taking the next external event, unless the runtime is paused.
+    fn next_instant(&mut self) -> Option<Box<RuntimeNode<'r>>> {
+        if self.is_paused() {
+            return None;
+        }
+
+        let node = self.injector.pop()?;
+        self.mode.start_instant();
+        Some(node)
+    }
+
+    /// Execute at most `budget` nodes and return whether there are nodes left to execute.
+    ///
+    /// Microtasks (nodes scheduled by other nodes) are always executed before the next injected
+    /// event is processed.  When this returns `true`, the host is expected to call `execute_step`
+    /// again later, for instance from a `setTimeout(..., 0)` callback.  While the runtime is
+    /// paused, queued external events don't count as nodes left to execute.
+    pub fn execute_step(&mut self, budget: usize) -> bool {
+        for _ in 0..budget {
+            let node = match self.microtasks.pop_front() {
+                Some(node) => node,
+                None => match self.next_instant() {
+                    Some(node) => node,
+                    None => break,
+                },
+            };
+            node.execute_box(self);
+        }
+
+        !self.microtasks.is_empty() || (!self.is_paused() && !self.injector.is_empty())
+    }
+
+    /// Execute nodes until there are none left.
+    ///
+    /// Note that this returns without waiting for pending timers.
+    pub fn execute(&mut self) {
+        while self.execute_step(usize::MAX) {}
+    }
+
+    /// Whether there are no nodes ready to execute, including external events queued while the
+    /// runtime is paused.
+    pub fn is_idle(&self) -> bool {
+        self.microtasks.is_empty() && self.injector.is_empty()
+    }
+}
+
+impl Toexec<'static> {
+    /// Start a periodic timer injecting its ticks into this runtime.  The timer runs on its own
+    /// thread, independently of the runtime's clock, until it stops by itself, it is stopped
+    /// through the returned handle, `stop_timers` is called, or the runtime is dropped.  See the
+    /// `common::timer` module.
+    pub fn add_timer<F, E>(&mut self, source: TimerSource<F>) -> TimerHandle
+    where
+        F: FnMut(Tick) -> Option<E> + Send + 'static,
+        E: OutputEdgeOnce<Toexec<'static>> + Send + Sync + 'static,
+        E::Item: TickPayload + Send + Sync + 'static,
+    {
+        let timer = source.start(self);
+        let handle = timer.handle();
+        self.timers.push(timer);
+        handle
+    }
+
+    /// Stop all the timers started with `add_timer`.  Ticks which were already injected are still
+    /// processed by the following executions.
+    pub fn stop_timers(&mut self) {
+        self.timers.clear()
+    }
+}
+
+impl<'r> Default for Toexec<'r> {
+    fn default() -> Self {
+        Toexec::new()
+    }
+}
+
+impl<'r> EventInjector<Toexec<'r>> for Injector<'r> {
+    fn inject_send<E>(&self, edge: E, item: E::Item)
+    where
+        E: OutputEdgeOnce<Toexec<'r>> + Send + Sync + 'static,
+        E::Item: Send + Sync + 'static,
+    {
+        Injector::inject_send(self, edge, item)
+    }
+}
+
+impl<'r> InjectorSpec for Toexec<'r> {
+    type Injector = Injector<'r>;
+
+    fn injector(&self) -> Injector<'r> {
+        Toexec::injector(self)
+    }
+}
+
+impl<'r> Scheduler for Toexec<'r> {
+    type Handle = Box<RuntimeNode<'r>>;
+
+    fn schedule(&mut self, handle: Self::Handle) {
+        self.microtasks.push_back(handle);
+    }
+}
+
+impl<'r> Capabilities for Toexec<'r> {}
+
+impl<'r> OutputSpec for Toexec<'r> {
+    fn outputs(&self) -> &GraphOutputs {
+        &self.outputs
+    }
+
+    fn outputs_mut(&mut self) -> &mut GraphOutputs {
+        &mut self.outputs
+    }
+}
+
+impl<'r> ExecuteSpec for Toexec<'r> {
+    fn new() -> Self {
+        Toexec::new()
+    }
+
+    fn execute(&mut self, _workers: usize) {
+        Toexec::execute(self)
+    }
+}
+
+impl<'r> PauseSpec for Toexec<'r> {
+    fn pause(&mut self) {
+        Toexec::pause(self)
+    }
+
+    fn step(&mut self) {
+        Toexec::step(self)
+    }
+
+    fn resume(&mut self) {
+        Toexec::resume(self)
+    }
+
+    fn is_paused(&self) -> bool {
+        Toexec::is_paused(self)
+    }
+}
+
+impl<'r> GraphSpec for Toexec<'r> {
+    type Activator = RcActivator<'r>;
+}
+
+impl<'r, N: NodeBox<Toexec<'r>> + Send + Sync + 'r> NodeSpec<N> for Toexec<'r> {
+    type Builder = RcBuilder<'r, N>;
+
+    fn node(&self, node: N) -> Self::Builder {
+        RcBuilder::new(Box::new(node))
+    }
+}
+
+impl<'r, T: 'r> PortSpec<T> for Toexec<'r> {
+    type Port = RcPort<SlotPort<T>>;
+
+    fn port(&self, init: T) -> Self::Port {
+        RcPort::new(SlotPort::new(init))
+    }
+}
+
+impl<'r> RcPortSpec for Toexec<'r> {}